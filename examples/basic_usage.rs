@@ -48,6 +48,7 @@ async fn main() -> Result<()> {
         subject: "test.echo".to_string(),
         body: Bytes::from("Hello, WebSocket!"),
         reply_to: None,
+        headers: HashMap::new(),
     };
 
     // Publish message
@@ -70,6 +71,7 @@ async fn main() -> Result<()> {
             subject: "test.session".to_string(),
             body: Bytes::from("Session-specific message"),
             reply_to: None,
+            headers: HashMap::new(),
         };
 
         if let Err(e) = provider.send_to_session(session_id, session_msg).await {
@@ -64,6 +64,7 @@ async fn main() -> Result<()> {
         subject: "test.echo".to_string(),
         body: Bytes::from("Hello from wasmCloud provider!"),
         reply_to: None,
+        headers: HashMap::new(),
     };
 
     if let Err(e) = provider.publish(consumer_component_id, outgoing_msg).await {
@@ -84,6 +85,7 @@ async fn main() -> Result<()> {
             subject: "test.request".to_string(),
             body: Bytes::from("Request with reply-to"),
             reply_to: Some(session_id.clone()),
+            headers: HashMap::new(),
         };
 
         if let Err(e) = provider
@@ -0,0 +1,42 @@
+use anyhow::Result;
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::time::Instant;
+
+use wasmcloud_provider_messaging_websocket::{BrokerMessage, WebSocketMessagingProvider};
+
+/// Micro-benchmark of `broadcast_to_clients` with no clients connected
+///
+/// Run with `cargo run --release --example empty_broadcast_bench`.
+#[tokio::main]
+async fn main() -> Result<()> {
+    const ITERATIONS: u32 = 1_000_000;
+
+    let mut config = HashMap::new();
+    config.insert("MODE".to_string(), "server".to_string());
+    config.insert("URI".to_string(), "127.0.0.1:0".to_string());
+    let mut provider = WebSocketMessagingProvider::from_config(config)?;
+    provider.start_server_if_needed().await?;
+
+    let msg = BrokerMessage {
+        subject: "server.broadcast".to_string(),
+        body: Bytes::from(vec![b'x'; 1024]),
+        reply_to: None,
+        headers: HashMap::new(),
+    };
+
+    let started = Instant::now();
+    for _ in 0..ITERATIONS {
+        provider.broadcast_to_clients(msg.clone()).await?;
+    }
+    let elapsed = started.elapsed();
+    println!(
+        "{} empty broadcasts of a 1 KiB message in {:?} ({:.0} ns/op)",
+        ITERATIONS,
+        elapsed,
+        elapsed.as_nanos() as f64 / ITERATIONS as f64
+    );
+
+    provider.shutdown().await?;
+    Ok(())
+}
@@ -55,12 +55,12 @@ async fn main() -> Result<()> {
                 subject: "server.broadcast".to_string(),
                 body: Bytes::from(format!("Broadcast message #{}", i)),
                 reply_to: None,
+                headers: HashMap::new(),
             };
 
-            if let Err(e) = provider.broadcast_to_clients(broadcast_msg).await {
-                info!("Failed to broadcast: {}", e);
-            } else {
-                info!("Broadcasted message to all clients");
+            match provider.broadcast_to_clients(broadcast_msg).await {
+                Ok(recipients) => info!("Broadcasted message to {} clients", recipients),
+                Err(e) => info!("Failed to broadcast: {}", e),
             }
 
             // Send to specific session if available
@@ -69,6 +69,7 @@ async fn main() -> Result<()> {
                     subject: "server.direct".to_string(),
                     body: Bytes::from(format!("Direct message to session: {}", session_id)),
                     reply_to: Some(session_id.clone()),
+                    headers: HashMap::new(),
                 };
 
                 if let Err(e) = provider.send_to_session(session_id, specific_msg).await {
@@ -0,0 +1,252 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use tracing::info;
+
+use crate::connection::ConnectionConfig;
+use crate::subject::matches_any;
+
+/// Subject prefix reserved for provider lifecycle events
+pub(crate) const SYSTEM_PREFIX: &str = "$SYS.";
+
+/// Why a component may not publish on a subject
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForbiddenReason {
+    /// The subject is reserved for the provider (`$SYS.` or the inbox prefix)
+    Reserved,
+    /// The subject matches the link's `PUBLISH_DENY` list
+    Denied,
+    /// The link has a `PUBLISH_ALLOW` list and the subject matches none of it
+    NotAllowed,
+}
+
+/// Error returned when a component publishes on a subject its link does not permit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForbiddenSubject {
+    pub component_id: String,
+    pub subject: String,
+    pub reason: ForbiddenReason,
+}
+
+impl fmt::Display for ForbiddenSubject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self.reason {
+            ForbiddenReason::Reserved => "subject is reserved",
+            ForbiddenReason::Denied => "subject is denied by PUBLISH_DENY",
+            ForbiddenReason::NotAllowed => "subject is not in PUBLISH_ALLOW",
+        };
+        write!(
+            f,
+            "Component {} may not publish on {}: {}",
+            self.component_id, self.subject, reason
+        )
+    }
+}
+
+impl std::error::Error for ForbiddenSubject {}
+
+/// Error returned when a message is sent on a subject the connection's `OUTBOUND_ALLOW` /
+/// `OUTBOUND_DENY` rules don't permit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubjectNotPermitted {
+    pub subject: String,
+    /// `Denied` or `NotAllowed`
+    pub reason: ForbiddenReason,
+}
+
+impl fmt::Display for SubjectNotPermitted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self.reason {
+            ForbiddenReason::NotAllowed => "not in OUTBOUND_ALLOW",
+            _ => "denied by OUTBOUND_DENY",
+        };
+        write!(f, "Subject {} may not be sent: {}", self.subject, reason)
+    }
+}
+
+impl std::error::Error for SubjectNotPermitted {}
+
+/// Whether `subject` passes an allow/deny pair: the deny list overrides the allow list,
+/// and an empty allow list permits every subject that isn't denied
+fn check_lists(allow: &[String], deny: &[String], subject: &str) -> Option<ForbiddenReason> {
+    if matches_any(deny, subject) {
+        Some(ForbiddenReason::Denied)
+    } else if !allow.is_empty() && !matches_any(allow, subject) {
+        Some(ForbiddenReason::NotAllowed)
+    } else {
+        None
+    }
+}
+
+/// Check a subject about to be sent on a connection against its outbound rules
+pub(crate) fn check_outbound(config: &ConnectionConfig, subject: &str) -> anyhow::Result<()> {
+    match check_lists(&config.outbound_allow, &config.outbound_deny, subject) {
+        None => Ok(()),
+        Some(reason) => Err(SubjectNotPermitted {
+            subject: subject.to_string(),
+            reason,
+        }
+        .into()),
+    }
+}
+
+/// Check an outbound subject against a link's publish rules
+///
+/// Reserved prefixes are rejected unless `ALLOW_RESERVED_SUBJECTS` is set; otherwise the
+/// deny list is applied first, so it overrides the allow list.
+pub(crate) fn check_publish(config: &ConnectionConfig, subject: &str) -> Option<ForbiddenReason> {
+    let inbox_prefix = format!("{}.", config.inbox_prefix);
+    let reserved = subject.starts_with(SYSTEM_PREFIX) || subject.starts_with(&inbox_prefix);
+    if reserved && !config.allow_reserved_subjects {
+        return Some(ForbiddenReason::Reserved);
+    }
+    check_lists(&config.publish_allow, &config.publish_deny, subject)
+}
+
+/// A link's `INBOUND_ALLOW` / `INBOUND_DENY` rules for messages pushed by the remote
+/// server, applied before they reach handlers
+#[derive(Debug, Clone)]
+pub(crate) struct InboundFilter {
+    allow: Vec<String>,
+    deny: Vec<String>,
+    log_denied: bool,
+    denied: Arc<AtomicU64>,
+}
+
+impl InboundFilter {
+    pub(crate) fn new(config: &ConnectionConfig, denied: Arc<AtomicU64>) -> Self {
+        Self {
+            allow: config.inbound_allow.clone(),
+            deny: config.inbound_deny.clone(),
+            log_denied: config.log_inbound_denied,
+            denied,
+        }
+    }
+
+    /// Whether `subject` may be delivered, with the same precedence as the publish rules
+    pub(crate) fn permits(&self, subject: &str) -> bool {
+        check_lists(&self.allow, &self.deny, subject).is_none()
+    }
+
+    /// Check an inbound subject, counting (and optionally logging) the ones dropped
+    pub(crate) fn admit(&self, session_id: &str, subject: &str) -> bool {
+        if self.permits(subject) {
+            return true;
+        }
+        self.denied.fetch_add(1, Ordering::Relaxed);
+        if self.log_denied {
+            info!(
+                "Dropped inbound message on {} from session {}: subject not permitted",
+                subject, session_id
+            );
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn config(pairs: &[(&str, &str)]) -> ConnectionConfig {
+        let map: HashMap<String, String> = pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        ConnectionConfig::from_map(&map).unwrap()
+    }
+
+    #[test]
+    fn test_reserved_prefixes_rejected() {
+        let config = config(&[]);
+        assert_eq!(
+            check_publish(&config, "$SYS.lifecycle"),
+            Some(ForbiddenReason::Reserved)
+        );
+        assert_eq!(
+            check_publish(&config, "_INBOX.abc"),
+            Some(ForbiddenReason::Reserved)
+        );
+        assert_eq!(check_publish(&config, "orders.new"), None);
+    }
+
+    #[test]
+    fn test_configured_inbox_prefix_is_reserved() {
+        let config = config(&[("INBOX_PREFIX", "_R")]);
+        assert_eq!(
+            check_publish(&config, "_R.abc"),
+            Some(ForbiddenReason::Reserved)
+        );
+        assert_eq!(check_publish(&config, "_INBOX.abc"), None);
+    }
+
+    #[test]
+    fn test_allow_list_permits_prefix() {
+        let config = config(&[("PUBLISH_ALLOW", "tenant-a.>")]);
+        assert_eq!(check_publish(&config, "tenant-a.orders"), None);
+        assert_eq!(
+            check_publish(&config, "tenant-b.orders"),
+            Some(ForbiddenReason::NotAllowed)
+        );
+    }
+
+    #[test]
+    fn test_deny_overrides_allow() {
+        let config = config(&[
+            ("PUBLISH_ALLOW", "tenant-a.>"),
+            ("PUBLISH_DENY", "tenant-a.admin.>"),
+        ]);
+        assert_eq!(check_publish(&config, "tenant-a.orders"), None);
+        assert_eq!(
+            check_publish(&config, "tenant-a.admin.reset"),
+            Some(ForbiddenReason::Denied)
+        );
+    }
+
+    #[test]
+    fn test_escape_hatch_allows_reserved() {
+        let config = config(&[("ALLOW_RESERVED_SUBJECTS", "true")]);
+        assert_eq!(check_publish(&config, "$SYS.lifecycle"), None);
+        assert_eq!(check_publish(&config, "_INBOX.abc"), None);
+    }
+
+    #[test]
+    fn test_outbound_rules() {
+        let config = config(&[
+            ("OUTBOUND_ALLOW", "events.>"),
+            ("OUTBOUND_DENY", "events.internal.*"),
+        ]);
+        assert!(check_outbound(&config, "events.orders").is_ok());
+        let err = check_outbound(&config, "events.internal.audit").unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<SubjectNotPermitted>().unwrap().reason,
+            ForbiddenReason::Denied
+        );
+        let err = check_outbound(&config, "metrics.cpu").unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<SubjectNotPermitted>().unwrap().reason,
+            ForbiddenReason::NotAllowed
+        );
+    }
+
+    #[test]
+    fn test_inbound_filter() {
+        let denied = Arc::new(AtomicU64::new(0));
+        let open = InboundFilter::new(&config(&[]), Arc::clone(&denied));
+        assert!(open.admit("s", "anything.at.all"));
+
+        let filter = InboundFilter::new(
+            &config(&[
+                ("INBOUND_ALLOW", "events.>"),
+                ("INBOUND_DENY", "events.internal.*"),
+            ]),
+            Arc::clone(&denied),
+        );
+        assert!(filter.admit("s", "events.orders"));
+        assert!(!filter.admit("s", "events.internal.audit"));
+        assert!(!filter.admit("s", "metrics.cpu"));
+        assert_eq!(denied.load(Ordering::Relaxed), 2);
+    }
+}
@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::BrokerMessage;
+
+/// Accumulates inbound messages for batched delivery to handler components
+///
+/// A batch is released once it holds `max_size` messages or once `window` has elapsed
+/// since its first message, whichever comes first. A `max_size` of 1 releases every
+/// message immediately, which is the unbatched behavior.
+#[derive(Debug)]
+pub(crate) struct MessageBatcher {
+    max_size: usize,
+    window: Duration,
+    pending: Vec<BrokerMessage>,
+    deadline: Option<Instant>,
+}
+
+impl MessageBatcher {
+    pub(crate) fn new(max_size: usize, window: Duration) -> Self {
+        Self {
+            max_size: max_size.max(1),
+            window,
+            pending: Vec::new(),
+            deadline: None,
+        }
+    }
+
+    /// Add a message, returning the batch if it is now full
+    pub(crate) fn push(&mut self, msg: BrokerMessage) -> Option<Vec<BrokerMessage>> {
+        if self.pending.is_empty() {
+            self.deadline = Some(Instant::now() + self.window);
+        }
+        self.pending.push(msg);
+
+        if self.pending.len() >= self.max_size {
+            Some(self.take())
+        } else {
+            None
+        }
+    }
+
+    /// Time at which the pending batch must be flushed, if any messages are pending
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Take all pending messages, leaving the batcher empty
+    pub(crate) fn take(&mut self) -> Vec<BrokerMessage> {
+        self.deadline = None;
+        std::mem::take(&mut self.pending)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use std::collections::HashMap;
+
+    fn message(n: usize) -> BrokerMessage {
+        BrokerMessage {
+            subject: format!("burst.{}", n),
+            body: Bytes::from("x"),
+            reply_to: None,
+            headers: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_unbatched_releases_immediately() {
+        let mut batcher = MessageBatcher::new(1, Duration::from_millis(10));
+        let batch = batcher.push(message(0)).unwrap();
+        assert_eq!(batch.len(), 1);
+        assert!(batcher.deadline().is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_burst_respects_size_and_window() {
+        let mut batcher = MessageBatcher::new(3, Duration::from_millis(50));
+
+        let batches: Vec<_> = (0..7).filter_map(|n| batcher.push(message(n))).collect();
+        assert_eq!(batches.len(), 2);
+        assert!(batches.iter().all(|b| b.len() == 3));
+        assert_eq!(batches[1][0].subject, "burst.3");
+
+        // The remainder waits for the window, then is flushed on its own
+        let deadline = batcher.deadline().expect("remainder should be pending");
+        tokio::time::sleep_until(deadline).await;
+        let rest = batcher.take();
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].subject, "burst.6");
+        assert!(batcher.deadline().is_none());
+    }
+}
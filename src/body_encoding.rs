@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use bytes::Bytes;
+
+use crate::compression::CONTENT_ENCODING;
+use crate::connection::ConnectionConfig;
+use crate::legacy_hex::{self, LegacyHexPolicy, LEGACY_HEX_HEADER};
+
+/// Envelope field naming the encoding of a string body
+pub(crate) const BODY_ENCODING_FIELD: &str = "body_encoding";
+
+/// The only `body_encoding` the provider writes and reads
+pub(crate) const BASE64: &str = "base64";
+
+/// How an inbound envelope's string body is turned into bytes
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct BodyDecoding {
+    pub(crate) legacy_hex: LegacyHexPolicy,
+    /// Reject a string body whose envelope doesn't name its encoding
+    pub(crate) require_encoding: bool,
+}
+
+impl BodyDecoding {
+    pub(crate) fn from_config(config: &ConnectionConfig) -> Self {
+        Self {
+            legacy_hex: config.legacy_hex_bodies,
+            require_encoding: config.require_body_encoding,
+        }
+    }
+}
+
+/// Bytes of an envelope's string `body`, decoded as its `body_encoding` says
+///
+/// Nothing is guessed from the content: a marked body must be valid base64, and an
+/// unmarked one is its own UTF-8 bytes unless it comes from a legacy hex peer, whose
+/// bodies are tagged in `headers`. Compressed bodies predating the marker are base64.
+pub(crate) fn decode(
+    json: &serde_json::Value,
+    body: &str,
+    decoding: BodyDecoding,
+    headers: &mut HashMap<String, String>,
+) -> Result<Bytes> {
+    match json.get(BODY_ENCODING_FIELD) {
+        Some(serde_json::Value::String(encoding)) if encoding == BASE64 => {
+            return base64(body);
+        }
+        Some(other) => bail!("Unsupported {}: {}", BODY_ENCODING_FIELD, other),
+        None => {}
+    }
+    if decoding.require_encoding {
+        bail!(
+            "Envelope body has no {} and REQUIRE_BODY_ENCODING is set",
+            BODY_ENCODING_FIELD
+        );
+    }
+    let compressed = json
+        .get("headers")
+        .is_some_and(|headers| headers.get(CONTENT_ENCODING).is_some());
+    if compressed {
+        return base64(body);
+    }
+    if let Some(hex) = legacy_hex::decode(body, decoding.legacy_hex) {
+        headers.insert(LEGACY_HEX_HEADER.to_string(), "true".to_string());
+        return Ok(Bytes::from(hex));
+    }
+    Ok(Bytes::from(body.as_bytes().to_vec()))
+}
+
+fn base64(body: &str) -> Result<Bytes> {
+    let bytes = STANDARD
+        .decode(body)
+        .context("Envelope body is not valid base64")?;
+    Ok(Bytes::from(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_envelope(json: serde_json::Value, decoding: BodyDecoding) -> Result<(Bytes, bool)> {
+        let mut headers = HashMap::new();
+        let body = json["body"].as_str().unwrap().to_string();
+        let bytes = decode(&json, &body, decoding, &mut headers)?;
+        Ok((bytes, headers.contains_key(LEGACY_HEX_HEADER)))
+    }
+
+    #[test]
+    fn test_decodes_only_what_the_envelope_declares() {
+        let decoding = BodyDecoding::default();
+
+        let marked = serde_json::json!({"body": "cG9uZw==", "body_encoding": "base64"});
+        assert_eq!(
+            decode_envelope(marked, decoding).unwrap(),
+            (Bytes::from("pong"), false)
+        );
+
+        // Valid base64, but unmarked, so taken as text
+        let unmarked = serde_json::json!({"body": "pong"});
+        assert_eq!(
+            decode_envelope(unmarked, decoding).unwrap(),
+            (Bytes::from("pong"), false)
+        );
+
+        let marked_hex = serde_json::json!({"body": "68656c6c", "body_encoding": "base64"});
+        assert!(!decode_envelope(marked_hex, decoding).unwrap().1);
+
+        let legacy = serde_json::json!({"body": "68656c6c6f"});
+        assert_eq!(
+            decode_envelope(legacy, decoding).unwrap(),
+            (Bytes::from("hello"), true)
+        );
+
+        let invalid = serde_json::json!({"body": "hello!", "body_encoding": "base64"});
+        assert!(decode_envelope(invalid, decoding).is_err());
+
+        let unknown = serde_json::json!({"body": "hello", "body_encoding": "hex"});
+        assert!(decode_envelope(unknown, decoding).is_err());
+    }
+
+    #[test]
+    fn test_require_encoding_rejects_unmarked_bodies() {
+        let decoding = BodyDecoding {
+            require_encoding: true,
+            ..BodyDecoding::default()
+        };
+        assert!(decode_envelope(serde_json::json!({"body": "pong"}), decoding).is_err());
+        let marked = serde_json::json!({"body": "cG9uZw==", "body_encoding": "base64"});
+        assert_eq!(
+            decode_envelope(marked, decoding).unwrap().0,
+            Bytes::from("pong")
+        );
+    }
+}
@@ -0,0 +1,708 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Result};
+use bytes::Bytes;
+use futures::stream::{self, BoxStream, StreamExt};
+use tokio::sync::{mpsc, oneshot, Notify};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{debug, warn};
+
+use crate::budget::{SessionCharge, SessionLedger};
+use crate::reconnect::QueuedFrame;
+use crate::tasks;
+use crate::BrokerMessage;
+
+/// Header of a descriptor envelope naming the body stream that follows it
+pub const STREAM_ID_HEADER: &str = "x-body-stream-id";
+
+/// Header of a descriptor envelope declaring the stream's length in bytes, when known
+pub const STREAM_LENGTH_HEADER: &str = "x-body-length";
+
+/// Prefix of a body stream's binary frames; 0xFF never starts valid UTF-8, so the frames
+/// can't be mistaken for JSON envelopes or raw UTF-8 payloads
+const FRAME_MAGIC: [u8; 3] = [0xFF, b'B', b'S'];
+
+/// Largest chunk carried by one continuation frame
+pub(crate) const FRAME_CHUNK_BYTES: usize = 64 * 1024;
+
+/// Chunks queued for a subscriber before the connection stops reading
+const SUBSCRIBER_QUEUE: usize = 16;
+
+pub(crate) const CANCELLED_BY_RECEIVER: &str = "cancelled by receiver";
+pub(crate) const CANCELLED_BY_PUBLISHER: &str = "cancelled by publisher";
+pub(crate) const CONNECTION_LOST: &str = "connection lost";
+
+/// Reason a stream is cancelled when its prefix would take the session over
+/// `MAX_SESSION_BUFFER_BYTES`
+pub(crate) const SESSION_BUFFER_FULL: &str = "session buffer full";
+
+/// A body stream ended before its last chunk, on the publishing or the receiving end
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BodyStreamAborted {
+    pub stream_id: String,
+    pub reason: String,
+}
+
+impl BodyStreamAborted {
+    pub(crate) fn new(stream_id: &str, reason: &str) -> Self {
+        Self {
+            stream_id: stream_id.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for BodyStreamAborted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Body stream {} aborted: {}", self.stream_id, self.reason)
+    }
+}
+
+impl std::error::Error for BodyStreamAborted {}
+
+/// One continuation frame of a body stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum StreamFrame {
+    /// The next chunk of the body
+    Data(Bytes),
+    /// The body is complete
+    End,
+    /// Either end gave up on the stream
+    Cancel(String),
+}
+
+/// Encode a frame as `magic, kind, id length, id, payload`
+pub(crate) fn encode_frame(stream_id: &str, frame: &StreamFrame) -> Vec<u8> {
+    let (kind, payload): (u8, &[u8]) = match frame {
+        StreamFrame::Data(chunk) => (0, chunk),
+        StreamFrame::End => (1, &[]),
+        StreamFrame::Cancel(reason) => (2, reason.as_bytes()),
+    };
+    // Ids are generated UUIDs or echoed from a decoded frame, so they always fit
+    let id = stream_id.as_bytes();
+    debug_assert!(id.len() <= u8::MAX as usize);
+    let mut out = Vec::with_capacity(FRAME_MAGIC.len() + 2 + id.len() + payload.len());
+    out.extend_from_slice(&FRAME_MAGIC);
+    out.push(kind);
+    out.push(id.len() as u8);
+    out.extend_from_slice(id);
+    out.extend_from_slice(payload);
+    out
+}
+
+/// Decode a body stream frame, or hand the bytes back if they are not one
+pub(crate) fn decode_frame(data: Vec<u8>) -> Result<(String, StreamFrame), Vec<u8>> {
+    let header = FRAME_MAGIC.len() + 2;
+    if data.len() < header || !data.starts_with(&FRAME_MAGIC) {
+        return Err(data);
+    }
+    let (kind, id_len) = (
+        data[FRAME_MAGIC.len()],
+        data[FRAME_MAGIC.len() + 1] as usize,
+    );
+    let Some(Ok(stream_id)) = data
+        .get(header..header + id_len)
+        .map(|id| std::str::from_utf8(id).map(str::to_string))
+    else {
+        return Err(data);
+    };
+    let payload = header + id_len;
+    let frame = match kind {
+        0 => StreamFrame::Data(Bytes::from(data).slice(payload..)),
+        1 => StreamFrame::End,
+        2 => StreamFrame::Cancel(String::from_utf8_lossy(&data[payload..]).into_owned()),
+        _ => return Err(data),
+    };
+    Ok((stream_id, frame))
+}
+
+/// Chunks queued for or delivered to a subscriber
+type ChunkSender = mpsc::Sender<Result<Bytes>>;
+
+/// Where an incoming body stream stands
+enum Incoming {
+    /// Nobody has subscribed yet; chunks are kept up to the prefix limit
+    Pending {
+        prefix: Vec<Bytes>,
+        bytes: usize,
+        /// The prefix's bytes, charged to the sending session
+        charge: Option<SessionCharge>,
+    },
+    /// Chunks go straight to the subscriber
+    Subscribed(ChunkSender),
+    /// The publisher finished, or the stream failed, before anyone subscribed
+    Done {
+        prefix: Vec<Bytes>,
+        error: Option<String>,
+        since: Instant,
+        charge: Option<SessionCharge>,
+    },
+    /// The subscriber went away; chunks still in flight are dropped
+    Cancelled,
+}
+
+struct IncomingStream {
+    state: Incoming,
+    /// Connection the stream arrives on, once known
+    connection: Option<String>,
+    /// Woken when a subscriber shows up
+    subscribed: Arc<Notify>,
+}
+
+impl IncomingStream {
+    fn new(connection: Option<&str>) -> Self {
+        Self {
+            state: Incoming::Pending {
+                prefix: Vec::new(),
+                bytes: 0,
+                charge: None,
+            },
+            connection: connection.map(str::to_string),
+            subscribed: Arc::default(),
+        }
+    }
+}
+
+/// A stream being published, told why if it ends early
+struct OutgoingWatch {
+    /// Connection the stream is published on
+    connection: String,
+    cancelled: oneshot::Sender<String>,
+}
+
+#[derive(Default)]
+struct Registry {
+    incoming: HashMap<String, IncomingStream>,
+    /// Streams being published, told why if the receiver cancels or the connection drops
+    outgoing: HashMap<String, OutgoingWatch>,
+}
+
+/// Body streams being received and published, shared by client and server connections
+#[derive(Clone)]
+pub(crate) struct BodyStreams {
+    registry: Arc<Mutex<Registry>>,
+    /// Bytes kept for a stream nobody has subscribed to yet
+    prefix_bytes: usize,
+    /// How long a stream with a full prefix waits for a subscriber
+    subscribe_timeout: Duration,
+    /// Per-session buffer usage prefixes are charged to, keyed by connection
+    ledger: SessionLedger,
+}
+
+impl Default for BodyStreams {
+    fn default() -> Self {
+        Self::new(1024 * 1024, Duration::from_secs(5))
+    }
+}
+
+impl BodyStreams {
+    pub(crate) fn new(prefix_bytes: usize, subscribe_timeout: Duration) -> Self {
+        Self {
+            registry: Arc::default(),
+            prefix_bytes,
+            subscribe_timeout,
+            ledger: SessionLedger::default(),
+        }
+    }
+
+    /// Charge prefixes to the sessions in `ledger`
+    pub(crate) fn with_ledger(mut self, ledger: SessionLedger) -> Self {
+        self.ledger = ledger;
+        self
+    }
+
+    /// Note a descriptor, so the stream fails if `connection` drops before its first chunk
+    pub(crate) fn open(&self, stream_id: &str, connection: &str) {
+        let mut registry = self.registry.lock().unwrap();
+        // Streams whose handler never subscribed give their prefix back eventually
+        let expiry = self.subscribe_timeout;
+        registry.incoming.retain(|_, stream| {
+            !matches!(stream.state, Incoming::Done { since, .. } if since.elapsed() > expiry)
+        });
+        registry
+            .incoming
+            .entry(stream_id.to_string())
+            .or_insert_with(|| IncomingStream::new(None))
+            .connection
+            .get_or_insert_with(|| connection.to_string());
+    }
+
+    /// `open` the stream a descriptor message announces, if it is one
+    pub(crate) fn open_descriptor(&self, msg: &BrokerMessage, connection: &str) {
+        if let Some(stream_id) = msg.headers.get(STREAM_ID_HEADER) {
+            self.open(stream_id, connection);
+        }
+    }
+
+    /// Handle a frame received on `connection`, returning a cancel frame to send back
+    /// if the stream had to be given up
+    pub(crate) async fn receive(
+        &self,
+        connection: &str,
+        stream_id: String,
+        frame: StreamFrame,
+    ) -> Option<Vec<u8>> {
+        match frame {
+            StreamFrame::Data(chunk) => {
+                let reason = self.push(connection, &stream_id, chunk).await.err()?;
+                Some(encode_frame(&stream_id, &StreamFrame::Cancel(reason)))
+            }
+            StreamFrame::End => {
+                self.finish(&stream_id, None);
+                None
+            }
+            StreamFrame::Cancel(reason) => {
+                // Ids are unique, so a cancel is for either a publish or a subscription
+                if !self.cancel_outgoing(&stream_id, &reason) {
+                    self.finish(&stream_id, Some(reason));
+                }
+                None
+            }
+        }
+    }
+
+    /// Hand a chunk to the stream's subscriber, or keep it until one subscribes
+    ///
+    /// Waits while the subscriber's queue is full, which stops the connection reading
+    /// and so slows the publisher down. Fails with the reason to cancel the publisher
+    /// with once the subscriber is gone or never turns up.
+    async fn push(&self, connection: &str, stream_id: &str, chunk: Bytes) -> Result<(), String> {
+        loop {
+            let subscribed = {
+                let mut registry = self.registry.lock().unwrap();
+                let stream = registry
+                    .incoming
+                    .entry(stream_id.to_string())
+                    .or_insert_with(|| IncomingStream::new(Some(connection)));
+                stream
+                    .connection
+                    .get_or_insert_with(|| connection.to_string());
+                match &mut stream.state {
+                    Incoming::Subscribed(tx) => Ok(tx.clone()),
+                    Incoming::Pending {
+                        prefix,
+                        bytes,
+                        charge,
+                    } if *bytes + chunk.len() <= self.prefix_bytes => {
+                        if !self.ledger.charge(connection, chunk.len(), charge) {
+                            warn!(
+                                "Cancelling body stream {}: {}",
+                                stream_id, SESSION_BUFFER_FULL
+                            );
+                            stream.state = Incoming::Done {
+                                prefix: Vec::new(),
+                                error: Some(SESSION_BUFFER_FULL.to_string()),
+                                since: Instant::now(),
+                                charge: None,
+                            };
+                            return Err(SESSION_BUFFER_FULL.to_string());
+                        }
+                        *bytes += chunk.len();
+                        prefix.push(chunk);
+                        return Ok(());
+                    }
+                    Incoming::Pending { .. } => Err(Arc::clone(&stream.subscribed)),
+                    Incoming::Done { .. } | Incoming::Cancelled => return Ok(()),
+                }
+            };
+            let subscribed = match subscribed {
+                Ok(tx) => {
+                    if tx.send(Ok(chunk)).await.is_ok() {
+                        return Ok(());
+                    }
+                    self.set_state(stream_id, Incoming::Cancelled);
+                    return Err(CANCELLED_BY_RECEIVER.to_string());
+                }
+                Err(subscribed) => subscribed,
+            };
+            if tokio::time::timeout(self.subscribe_timeout, subscribed.notified())
+                .await
+                .is_err()
+            {
+                let reason = format!(
+                    "no subscriber within {:?} and the {} byte prefix is full",
+                    self.subscribe_timeout, self.prefix_bytes
+                );
+                warn!("Cancelling body stream {}: {}", stream_id, reason);
+                self.set_state(
+                    stream_id,
+                    Incoming::Done {
+                        prefix: Vec::new(),
+                        error: Some(reason.clone()),
+                        since: Instant::now(),
+                        charge: None,
+                    },
+                );
+                return Err(reason);
+            }
+        }
+    }
+
+    fn set_state(&self, stream_id: &str, state: Incoming) {
+        if let Some(stream) = self.registry.lock().unwrap().incoming.get_mut(stream_id) {
+            stream.state = state;
+        }
+    }
+
+    /// End an incoming stream, cleanly or with the reason it was aborted
+    fn finish(&self, stream_id: &str, error: Option<String>) {
+        let mut registry = self.registry.lock().unwrap();
+        let Some(stream) = registry.incoming.get_mut(stream_id) else {
+            return;
+        };
+        match std::mem::replace(&mut stream.state, Incoming::Cancelled) {
+            Incoming::Pending { prefix, charge, .. } => {
+                stream.state = Incoming::Done {
+                    prefix,
+                    error,
+                    since: Instant::now(),
+                    charge,
+                }
+            }
+            Incoming::Subscribed(tx) => {
+                registry.incoming.remove(stream_id);
+                if let Some(reason) = error {
+                    fail_subscriber(tx, stream_id, &reason);
+                }
+            }
+            done @ Incoming::Done { .. } => stream.state = done,
+            Incoming::Cancelled => {
+                registry.incoming.remove(stream_id);
+            }
+        }
+    }
+
+    /// Fail every stream arriving on or published to a connection that has gone away,
+    /// even if the link redials it
+    pub(crate) fn fail_connection(&self, connection: &str) {
+        let (incoming, outgoing): (Vec<String>, Vec<String>) = {
+            let registry = self.registry.lock().unwrap();
+            (
+                registry
+                    .incoming
+                    .iter()
+                    .filter(|(_, stream)| stream.connection.as_deref() == Some(connection))
+                    .map(|(stream_id, _)| stream_id.clone())
+                    .collect(),
+                registry
+                    .outgoing
+                    .iter()
+                    .filter(|(_, watch)| watch.connection == connection)
+                    .map(|(stream_id, _)| stream_id.clone())
+                    .collect(),
+            )
+        };
+        for stream_id in incoming {
+            debug!("Body stream {} lost its connection", stream_id);
+            self.finish(&stream_id, Some(CONNECTION_LOST.to_string()));
+        }
+        for stream_id in outgoing {
+            debug!("Published body stream {} lost its connection", stream_id);
+            self.cancel_outgoing(&stream_id, CONNECTION_LOST);
+        }
+    }
+
+    /// Resolve an incoming stream, replaying what arrived before the call
+    pub(crate) fn subscribe(&self, stream_id: &str) -> Result<BoxStream<'static, Result<Bytes>>> {
+        let mut registry = self.registry.lock().unwrap();
+        let stream = registry
+            .incoming
+            .entry(stream_id.to_string())
+            .or_insert_with(|| IncomingStream::new(None));
+        match std::mem::replace(&mut stream.state, Incoming::Cancelled) {
+            Incoming::Pending { prefix, .. } => {
+                let (tx, rx) = mpsc::channel(SUBSCRIBER_QUEUE);
+                stream.state = Incoming::Subscribed(tx);
+                stream.subscribed.notify_one();
+                let rest = stream::unfold(rx, |mut rx| async move {
+                    rx.recv().await.map(|item| (item, rx))
+                });
+                Ok(stream::iter(prefix.into_iter().map(Ok)).chain(rest).boxed())
+            }
+            Incoming::Done { prefix, error, .. } => {
+                registry.incoming.remove(stream_id);
+                let error: Option<Result<Bytes>> =
+                    error.map(|reason| Err(BodyStreamAborted::new(stream_id, &reason).into()));
+                Ok(stream::iter(prefix.into_iter().map(Ok).chain(error)).boxed())
+            }
+            state => {
+                stream.state = state;
+                bail!("Body stream {} already has a subscriber", stream_id)
+            }
+        }
+    }
+
+    /// Watch a stream being published on `connection` for the receiver cancelling it
+    pub(crate) fn watch_outgoing(
+        &self,
+        stream_id: &str,
+        connection: &str,
+    ) -> oneshot::Receiver<String> {
+        let (tx, rx) = oneshot::channel();
+        self.registry.lock().unwrap().outgoing.insert(
+            stream_id.to_string(),
+            OutgoingWatch {
+                connection: connection.to_string(),
+                cancelled: tx,
+            },
+        );
+        rx
+    }
+
+    /// Stop watching a stream that is no longer being published
+    pub(crate) fn forget_outgoing(&self, stream_id: &str) {
+        self.registry.lock().unwrap().outgoing.remove(stream_id);
+    }
+
+    /// Tell a publish its stream was cancelled, returning whether it was one of ours
+    fn cancel_outgoing(&self, stream_id: &str, reason: &str) -> bool {
+        let Some(watch) = self.registry.lock().unwrap().outgoing.remove(stream_id) else {
+            return false;
+        };
+        let _ = watch.cancelled.send(reason.to_string());
+        true
+    }
+}
+
+/// A stream being published, cancelled on the receiving end unless it is finished
+pub(crate) struct OutgoingStream {
+    streams: BodyStreams,
+    stream_id: String,
+    tx: mpsc::Sender<QueuedFrame>,
+    finished: bool,
+}
+
+impl OutgoingStream {
+    /// Start publishing on `connection`, returning the guard and where a cancel arrives
+    pub(crate) fn start(
+        streams: &BodyStreams,
+        stream_id: &str,
+        connection: &str,
+        tx: &mpsc::Sender<QueuedFrame>,
+    ) -> (Self, oneshot::Receiver<String>) {
+        let cancelled = streams.watch_outgoing(stream_id, connection);
+        let guard = Self {
+            streams: streams.clone(),
+            stream_id: stream_id.to_string(),
+            tx: tx.clone(),
+            finished: false,
+        };
+        (guard, cancelled)
+    }
+
+    /// The end frame has been queued; nothing left to cancel
+    pub(crate) fn finish(mut self) {
+        self.finished = true;
+    }
+}
+
+impl Drop for OutgoingStream {
+    fn drop(&mut self) {
+        self.streams.forget_outgoing(&self.stream_id);
+        if self.finished {
+            return;
+        }
+        // Also clears the receiver's entry when it was the receiver that cancelled
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let tx = self.tx.clone();
+        let cancel = StreamFrame::Cancel(CANCELLED_BY_PUBLISHER.to_string());
+        let frame = Message::Binary(encode_frame(&self.stream_id, &cancel));
+        let name = format!("body-stream-cancel stream={}", self.stream_id);
+        tasks::spawn_named_on(
+            &name,
+            async move {
+                let _ = tx.send(frame.into()).await;
+            },
+            &runtime,
+        );
+    }
+}
+
+/// Give a subscriber the error, once it has room for it
+fn fail_subscriber(tx: ChunkSender, stream_id: &str, reason: &str) {
+    let error = BodyStreamAborted::new(stream_id, reason).into();
+    tasks::spawn_named(
+        &format!("body-stream-error stream={}", stream_id),
+        async move {
+            let _ = tx.send(Err(error)).await;
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn collect(stream: BoxStream<'static, Result<Bytes>>) -> Vec<Result<Bytes, String>> {
+        stream
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .map(|item| item.map_err(|e| e.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_frame_round_trip() {
+        for frame in [
+            StreamFrame::Data(Bytes::from_static(b"chunk")),
+            StreamFrame::End,
+            StreamFrame::Cancel("gone".to_string()),
+        ] {
+            let encoded = encode_frame("abc", &frame);
+            assert_eq!(decode_frame(encoded), Ok(("abc".to_string(), frame)));
+        }
+    }
+
+    #[test]
+    fn test_other_binary_frames_pass_through() {
+        for data in [
+            b"{\"subject\":\"a\"}".to_vec(),
+            vec![0xFF, b'B'],
+            vec![0xFF, 0, 1],
+        ] {
+            assert_eq!(decode_frame(data.clone()), Err(data));
+        }
+        // Truncated id
+        let mut frame = encode_frame("abc", &StreamFrame::End);
+        frame.truncate(frame.len() - 1);
+        assert!(decode_frame(frame).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_prefix_replayed_to_late_subscriber() {
+        let streams = BodyStreams::default();
+        streams.open("s", "conn");
+        for chunk in ["a", "b"] {
+            let cancel = streams
+                .receive(
+                    "conn",
+                    "s".to_string(),
+                    StreamFrame::Data(Bytes::from(chunk)),
+                )
+                .await;
+            assert!(cancel.is_none());
+        }
+        streams
+            .receive("conn", "s".to_string(), StreamFrame::End)
+            .await;
+
+        let chunks = collect(streams.subscribe("s").unwrap()).await;
+        assert_eq!(chunks, vec![Ok(Bytes::from("a")), Ok(Bytes::from("b"))]);
+        assert!(streams.registry.lock().unwrap().incoming.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_full_prefix_cancels_without_subscriber() {
+        let streams = BodyStreams::new(4, Duration::from_millis(20));
+        let chunk = || StreamFrame::Data(Bytes::from("abc"));
+        assert!(streams
+            .receive("conn", "s".to_string(), chunk())
+            .await
+            .is_none());
+
+        let cancel = streams.receive("conn", "s".to_string(), chunk()).await;
+        let Ok((_, StreamFrame::Cancel(reason))) = decode_frame(cancel.unwrap()) else {
+            panic!("expected a cancel frame");
+        };
+        assert!(reason.contains("prefix is full"));
+        // Frames still in flight are dropped quietly
+        assert!(streams
+            .receive("conn", "s".to_string(), chunk())
+            .await
+            .is_none());
+
+        let chunks = collect(streams.subscribe("s").unwrap()).await;
+        assert_eq!(chunks.len(), 1);
+        assert!(chunks[0].as_ref().unwrap_err().contains("prefix is full"));
+    }
+
+    #[tokio::test]
+    async fn test_session_cap_cancels_prefix() {
+        use crate::budget::SessionBufferPolicy;
+
+        let ledger = SessionLedger::new(4, SessionBufferPolicy::DropOldest);
+        ledger.register("conn");
+        let streams = BodyStreams::default().with_ledger(ledger.clone());
+        let chunk = || StreamFrame::Data(Bytes::from("abc"));
+        assert!(streams
+            .receive("conn", "s".to_string(), chunk())
+            .await
+            .is_none());
+        assert_eq!(ledger.top(1)[0].buffered_bytes, 3);
+
+        let cancel = streams.receive("conn", "s".to_string(), chunk()).await;
+        let Ok((_, StreamFrame::Cancel(reason))) = decode_frame(cancel.unwrap()) else {
+            panic!("expected a cancel frame");
+        };
+        assert_eq!(reason, SESSION_BUFFER_FULL);
+        // The prefix was discarded and its bytes given back
+        assert_eq!(ledger.top(1)[0].buffered_bytes, 0);
+    }
+
+    #[tokio::test]
+    async fn test_connection_loss_fails_subscriber() {
+        let streams = BodyStreams::default();
+        streams.open("s", "conn");
+        let subscription = streams.subscribe("s").unwrap();
+        assert!(streams.subscribe("s").is_err());
+
+        streams.fail_connection("conn");
+        let items: Vec<_> = subscription.collect().await;
+        assert_eq!(items.len(), 1);
+        let error = items[0].as_ref().unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<BodyStreamAborted>(),
+            Some(&BodyStreamAborted::new("s", CONNECTION_LOST))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dropped_subscriber_cancels_publisher() {
+        let streams = BodyStreams::default();
+        drop(streams.subscribe("s").unwrap());
+        let cancel = streams
+            .receive("conn", "s".to_string(), StreamFrame::Data(Bytes::from("a")))
+            .await;
+        assert_eq!(
+            decode_frame(cancel.unwrap()),
+            Ok((
+                "s".to_string(),
+                StreamFrame::Cancel(CANCELLED_BY_RECEIVER.to_string())
+            ))
+        );
+        // The publisher's own cancel clears the entry
+        streams
+            .receive(
+                "conn",
+                "s".to_string(),
+                StreamFrame::Cancel("x".to_string()),
+            )
+            .await;
+        assert!(streams.registry.lock().unwrap().incoming.is_empty());
+    }
+
+    #[test]
+    fn test_cancel_reaches_publisher() {
+        let streams = BodyStreams::default();
+        let mut cancelled = streams.watch_outgoing("s", "conn");
+        assert!(cancelled.try_recv().is_err());
+        assert!(streams.cancel_outgoing("s", CANCELLED_BY_RECEIVER));
+        assert_eq!(cancelled.try_recv().unwrap(), CANCELLED_BY_RECEIVER);
+        assert!(!streams.cancel_outgoing("s", CANCELLED_BY_RECEIVER));
+    }
+
+    #[test]
+    fn test_connection_loss_cancels_publisher() {
+        let streams = BodyStreams::default();
+        let mut lost = streams.watch_outgoing("s", "conn");
+        let mut other = streams.watch_outgoing("t", "other");
+        streams.fail_connection("conn");
+        assert_eq!(lost.try_recv().unwrap(), CONNECTION_LOST);
+        assert!(other.try_recv().is_err());
+    }
+}
@@ -0,0 +1,671 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+use axum::extract::ws::Message;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{self, error::SendError};
+use tokio::sync::Notify;
+
+use crate::server::frame_len;
+
+/// Bytes of frames queued for server clients, against `SERVER_MEMORY_BUDGET_BYTES`
+#[derive(Debug, Clone, Default)]
+pub(crate) struct MemoryBudget {
+    used: Arc<AtomicUsize>,
+    /// Most bytes sheddable frames may take up (0 = no limit)
+    limit: usize,
+}
+
+impl MemoryBudget {
+    pub(crate) fn new(limit: usize) -> Self {
+        Self {
+            used: Arc::default(),
+            limit,
+        }
+    }
+
+    /// Bytes currently queued across all clients
+    pub(crate) fn used(&self) -> usize {
+        self.used.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Charge for a frame that is queued whatever the budget says, counting it in `frames`
+    ///
+    /// The bytes must already be reserved on `session`, which the charge gives them back to.
+    fn charge(
+        &self,
+        bytes: usize,
+        frames: &Arc<AtomicUsize>,
+        session: &Arc<SessionUsage>,
+    ) -> Charge {
+        self.used.fetch_add(bytes, Ordering::SeqCst);
+        Charge::new(&self.used, bytes, frames, session)
+    }
+
+    /// Charge for a frame that may be shed, or `None` if it would exceed the budget
+    fn try_charge(
+        &self,
+        bytes: usize,
+        frames: &Arc<AtomicUsize>,
+        session: &Arc<SessionUsage>,
+    ) -> Option<Charge> {
+        if self.limit > 0 {
+            self.used
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                    (used + bytes <= self.limit).then_some(used + bytes)
+                })
+                .ok()?;
+            return Some(Charge::new(&self.used, bytes, frames, session));
+        }
+        Some(self.charge(bytes, frames, session))
+    }
+}
+
+/// Bytes of one queued frame, given back to the budget and the session when the frame is
+/// written or discarded
+#[derive(Debug)]
+pub(crate) struct Charge {
+    used: Arc<AtomicUsize>,
+    bytes: usize,
+    /// The client's count of queued frames
+    frames: Arc<AtomicUsize>,
+    session: Arc<SessionUsage>,
+}
+
+impl Charge {
+    /// Take over `bytes` already added to `used`
+    fn new(
+        used: &Arc<AtomicUsize>,
+        bytes: usize,
+        frames: &Arc<AtomicUsize>,
+        session: &Arc<SessionUsage>,
+    ) -> Self {
+        frames.fetch_add(1, Ordering::SeqCst);
+        Self {
+            used: Arc::clone(used),
+            bytes,
+            frames: Arc::clone(frames),
+            session: Arc::clone(session),
+        }
+    }
+}
+
+impl Drop for Charge {
+    fn drop(&mut self) {
+        self.used.fetch_sub(self.bytes, Ordering::SeqCst);
+        self.frames.fetch_sub(1, Ordering::SeqCst);
+        self.session.release(self.bytes, true);
+    }
+}
+
+/// What happens when a session would go over `MAX_SESSION_BUFFER_BYTES`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionBufferPolicy {
+    /// Discard the session's oldest queued frames until the new one fits
+    #[default]
+    DropOldest,
+    /// Discard everything queued and close the connection with code 4006
+    Disconnect,
+}
+
+impl SessionBufferPolicy {
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        match value {
+            "drop_oldest" => Some(Self::DropOldest),
+            "disconnect" => Some(Self::Disconnect),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::DropOldest => "drop_oldest",
+            Self::Disconnect => "disconnect",
+        }
+    }
+}
+
+/// Bytes one server session has buffered, against `MAX_SESSION_BUFFER_BYTES`
+///
+/// Covers the session's outbound queue, the prefixes of body streams it is sending and
+/// messages it sent before a message sink was installed.
+#[derive(Debug, Default)]
+pub(crate) struct SessionUsage {
+    /// Most bytes the session may hold (0 = no limit)
+    limit: usize,
+    policy: SessionBufferPolicy,
+    /// Bytes across all of the session's buffers
+    bytes: AtomicUsize,
+    /// The part of `bytes` sitting in the outbound queue
+    queued: AtomicUsize,
+    /// Queued frames discarded to stay under the limit
+    dropped: AtomicU64,
+    /// Set once the session went over its limit under `Disconnect`
+    exceeded: AtomicBool,
+    exceeded_notify: Notify,
+}
+
+impl SessionUsage {
+    fn new(limit: usize, policy: SessionBufferPolicy) -> Self {
+        Self {
+            limit,
+            policy,
+            ..Self::default()
+        }
+    }
+
+    /// Reserve `bytes`, failing if they would take the session over its limit
+    ///
+    /// `exempt` bytes are reserved regardless, so close frames always get through.
+    fn try_reserve(&self, bytes: usize, queued: bool, exempt: bool) -> bool {
+        if self.limit == 0 || exempt {
+            self.bytes.fetch_add(bytes, Ordering::SeqCst);
+        } else if self.exceeded.load(Ordering::SeqCst)
+            || self
+                .bytes
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                    (used + bytes <= self.limit).then_some(used + bytes)
+                })
+                .is_err()
+        {
+            return false;
+        }
+        if queued {
+            self.queued.fetch_add(bytes, Ordering::SeqCst);
+        }
+        true
+    }
+
+    fn release(&self, bytes: usize, queued: bool) {
+        self.bytes.fetch_sub(bytes, Ordering::SeqCst);
+        if queued {
+            self.queued.fetch_sub(bytes, Ordering::SeqCst);
+        }
+    }
+
+    /// Mark the session as over its limit and wake whoever disconnects it
+    fn exceed(&self) {
+        if !self.exceeded.swap(true, Ordering::SeqCst) {
+            self.exceeded_notify.notify_one();
+        }
+    }
+
+    /// Resolves once the session went over its limit under `Disconnect`
+    pub(crate) async fn exceeded(&self) {
+        self.exceeded_notify.notified().await
+    }
+
+    pub(crate) fn bytes(&self) -> usize {
+        self.bytes.load(Ordering::SeqCst)
+    }
+
+    fn queued(&self) -> usize {
+        self.queued.load(Ordering::SeqCst)
+    }
+}
+
+/// Bytes a session holds outside its outbound queue, given back when dropped
+#[derive(Debug)]
+pub(crate) struct SessionCharge {
+    usage: Arc<SessionUsage>,
+    bytes: usize,
+}
+
+impl Drop for SessionCharge {
+    fn drop(&mut self) {
+        self.usage.release(self.bytes, false);
+    }
+}
+
+/// Memory one server client has buffered, as reported by `top_sessions_by_memory`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionMemory {
+    pub session_id: String,
+    /// Bytes across the session's outbound queue, stream prefixes and startup buffer
+    pub buffered_bytes: usize,
+    /// The part of `buffered_bytes` in the outbound queue
+    pub queued_bytes: usize,
+    /// Queued frames discarded to keep the session under `MAX_SESSION_BUFFER_BYTES`
+    pub dropped_frames: u64,
+}
+
+#[derive(Debug, Default)]
+struct LedgerInner {
+    limit: usize,
+    policy: SessionBufferPolicy,
+    sessions: Mutex<HashMap<String, Arc<SessionUsage>>>,
+}
+
+/// Per-session buffer usage of every connected server client
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SessionLedger {
+    inner: Arc<LedgerInner>,
+}
+
+impl SessionLedger {
+    pub(crate) fn new(limit: usize, policy: SessionBufferPolicy) -> Self {
+        Self {
+            inner: Arc::new(LedgerInner {
+                limit,
+                policy,
+                sessions: Mutex::default(),
+            }),
+        }
+    }
+
+    /// Start accounting for a session, replacing any earlier entry under the same id
+    pub(crate) fn register(&self, session_id: &str) -> Arc<SessionUsage> {
+        let usage = Arc::new(SessionUsage::new(self.inner.limit, self.inner.policy));
+        self.inner
+            .sessions
+            .lock()
+            .unwrap()
+            .insert(session_id.to_string(), Arc::clone(&usage));
+        usage
+    }
+
+    pub(crate) fn remove(&self, session_id: &str) {
+        self.inner.sessions.lock().unwrap().remove(session_id);
+    }
+
+    /// Add `bytes` buffered for `session_id` outside its queue to `held`
+    ///
+    /// Returns false, charging nothing, if that would take the session over its limit.
+    /// Sessions the ledger doesn't know, such as client-mode connections, aren't capped.
+    pub(crate) fn charge(
+        &self,
+        session_id: &str,
+        bytes: usize,
+        held: &mut Option<SessionCharge>,
+    ) -> bool {
+        let usage = match held {
+            Some(charge) => Arc::clone(&charge.usage),
+            None => match self.inner.sessions.lock().unwrap().get(session_id) {
+                Some(usage) => Arc::clone(usage),
+                None => return true,
+            },
+        };
+        if !usage.try_reserve(bytes, false, false) {
+            if usage.policy == SessionBufferPolicy::Disconnect {
+                usage.exceed();
+            }
+            return false;
+        }
+        match held {
+            Some(charge) => charge.bytes += bytes,
+            None => *held = Some(SessionCharge { usage, bytes }),
+        }
+        true
+    }
+
+    /// The `n` sessions holding the most buffered bytes, largest first
+    pub(crate) fn top(&self, n: usize) -> Vec<SessionMemory> {
+        let mut sessions: Vec<SessionMemory> = self
+            .inner
+            .sessions
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(session_id, usage)| SessionMemory {
+                session_id: session_id.clone(),
+                buffered_bytes: usage.bytes(),
+                queued_bytes: usage.queued(),
+                dropped_frames: usage.dropped.load(Ordering::Relaxed),
+            })
+            .collect();
+        sessions.sort_by(|a, b| {
+            b.buffered_bytes
+                .cmp(&a.buffered_bytes)
+                .then_with(|| a.session_id.cmp(&b.session_id))
+        });
+        sessions.truncate(n);
+        sessions
+    }
+
+    /// Queued bytes summed over every session, which must match the budget's usage
+    pub(crate) fn queued_total(&self) -> usize {
+        self.inner
+            .sessions
+            .lock()
+            .unwrap()
+            .values()
+            .map(|usage| usage.queued())
+            .sum()
+    }
+}
+
+/// A frame in a client's outbound queue, which the sender can discard before it is written
+#[derive(Debug)]
+pub(crate) struct QueuedFrame(Mutex<Option<(Message, Charge)>>);
+
+impl QueuedFrame {
+    fn take(&self) -> Option<(Message, Charge)> {
+        self.0.lock().unwrap().take()
+    }
+}
+
+/// Sending half of a server client's outbound queue, charging frames to the budget
+///
+/// Control frames that shouldn't wait behind queued data go on a separate lane, which the
+/// writer always empties first.
+#[derive(Debug, Clone)]
+pub(crate) struct ClientSender {
+    tx: mpsc::UnboundedSender<Arc<QueuedFrame>>,
+    urgent: mpsc::UnboundedSender<Arc<QueuedFrame>>,
+    budget: MemoryBudget,
+    /// Frames in the queue, for slow-consumer detection
+    frames: Arc<AtomicUsize>,
+    session: Arc<SessionUsage>,
+    /// Data frames not yet written, oldest first, so they can be discarded
+    evictable: Arc<Mutex<VecDeque<Weak<QueuedFrame>>>>,
+}
+
+/// Receiving half of a server client's outbound queue
+pub(crate) struct ClientReceiver {
+    data: mpsc::UnboundedReceiver<Arc<QueuedFrame>>,
+    urgent: mpsc::UnboundedReceiver<Arc<QueuedFrame>>,
+}
+
+impl ClientReceiver {
+    /// Next frame to write, urgent ones first, skipping frames discarded while queued
+    pub(crate) async fn recv(&mut self) -> Option<(Message, Charge)> {
+        loop {
+            let frame = tokio::select! {
+                biased;
+                Some(frame) = self.urgent.recv() => frame,
+                frame = self.data.recv() => frame?,
+            };
+            if let Some(frame) = frame.take() {
+                return Some(frame);
+            }
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn try_recv(&mut self) -> Result<(Message, Charge), mpsc::error::TryRecvError> {
+        loop {
+            let frame = match self.urgent.try_recv() {
+                Ok(frame) => frame,
+                Err(_) => self.data.try_recv()?,
+            };
+            if let Some(frame) = frame.take() {
+                return Ok(frame);
+            }
+        }
+    }
+}
+
+/// Outbound queue for a server client, attributing its bytes to `session`
+pub(crate) fn client_channel(
+    budget: &MemoryBudget,
+    session: Arc<SessionUsage>,
+) -> (ClientSender, ClientReceiver) {
+    let (tx, data) = mpsc::unbounded_channel();
+    let (urgent_tx, urgent) = mpsc::unbounded_channel();
+    let sender = ClientSender {
+        tx,
+        urgent: urgent_tx,
+        budget: budget.clone(),
+        frames: Arc::default(),
+        session,
+        evictable: Arc::default(),
+    };
+    (sender, ClientReceiver { data, urgent })
+}
+
+impl ClientSender {
+    /// Queue a frame regardless of the budget; for replies, control frames and direct sends
+    ///
+    /// Data frames still count against the session's limit. Pings and pongs skip ahead of
+    /// queued data.
+    pub(crate) fn send(&self, msg: Message) -> Result<(), SendError<Message>> {
+        let urgent = matches!(msg, Message::Ping(_) | Message::Pong(_));
+        match self.enqueue(msg, false, urgent)? {
+            Ok(()) => Ok(()),
+            Err(msg) => Err(SendError(msg)),
+        }
+    }
+
+    /// Queue a frame unless the budget is exhausted, returning whether it was queued
+    pub(crate) fn send_within_budget(&self, msg: Message) -> Result<bool, SendError<Message>> {
+        Ok(self.enqueue(msg, true, false)?.is_ok())
+    }
+
+    /// Queue a frame ahead of all queued data, regardless of the budget and session limit
+    pub(crate) fn send_urgent(&self, msg: Message) -> Result<(), SendError<Message>> {
+        match self.enqueue(msg, false, true)? {
+            Ok(()) => Ok(()),
+            Err(msg) => Err(SendError(msg)),
+        }
+    }
+
+    /// Discard every data frame still waiting to be written, returning how many there were
+    pub(crate) fn discard_queued(&self) -> usize {
+        let mut evictable = self.evictable.lock().unwrap();
+        let mut discarded = 0;
+        while evict_oldest(&mut evictable) {
+            discarded += 1;
+        }
+        discarded
+    }
+
+    /// Queue a frame, handing it back if the budget or the session limit refused it
+    fn enqueue(
+        &self,
+        msg: Message,
+        within_budget: bool,
+        urgent: bool,
+    ) -> Result<Result<(), Message>, SendError<Message>> {
+        if self.tx.is_closed() {
+            return Err(SendError(msg));
+        }
+        let bytes = frame_len(&msg);
+        let exempt = urgent || !matches!(msg, Message::Text(_) | Message::Binary(_));
+        // Held across the send so frames are evicted in the order they were queued
+        let mut evictable = self.evictable.lock().unwrap();
+        if !self.reserve(bytes, exempt, &mut evictable) {
+            return Ok(Err(msg));
+        }
+        let charge = if within_budget {
+            let Some(charge) = self.budget.try_charge(bytes, &self.frames, &self.session) else {
+                self.session.release(bytes, true);
+                return Ok(Err(msg));
+            };
+            charge
+        } else {
+            self.budget.charge(bytes, &self.frames, &self.session)
+        };
+        let frame = Arc::new(QueuedFrame(Mutex::new(Some((msg, charge)))));
+        if !exempt {
+            while evictable.front().is_some_and(|f| f.strong_count() == 0) {
+                evictable.pop_front();
+            }
+            evictable.push_back(Arc::downgrade(&frame));
+        }
+        let lane = if urgent { &self.urgent } else { &self.tx };
+        lane.send(frame).map_err(|SendError(frame)| {
+            let (msg, _) = frame.take().expect("frame was just queued");
+            SendError(msg)
+        })?;
+        Ok(Ok(()))
+    }
+
+    /// Reserve room for `bytes` on the session, applying its policy if it is full
+    fn reserve(
+        &self,
+        bytes: usize,
+        exempt: bool,
+        evictable: &mut VecDeque<Weak<QueuedFrame>>,
+    ) -> bool {
+        loop {
+            if self.session.try_reserve(bytes, true, exempt) {
+                return true;
+            }
+            match self.session.policy {
+                SessionBufferPolicy::DropOldest => {
+                    // A frame bigger than the whole limit never fits
+                    if bytes > self.session.limit || !evict_oldest(evictable) {
+                        return false;
+                    }
+                    self.session.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                SessionBufferPolicy::Disconnect => {
+                    while evict_oldest(evictable) {}
+                    self.session.exceed();
+                    return false;
+                }
+            }
+        }
+    }
+
+    /// Frames queued and not yet written
+    pub(crate) fn queued_frames(&self) -> usize {
+        self.frames.load(Ordering::SeqCst)
+    }
+
+    /// Whether the client's writer has gone away
+    pub(crate) fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+}
+
+/// Discard the oldest frame still waiting to be written, returning whether there was one
+fn evict_oldest(evictable: &mut VecDeque<Weak<QueuedFrame>>) -> bool {
+    while let Some(frame) = evictable.pop_front() {
+        if frame.upgrade().and_then(|frame| frame.take()).is_some() {
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(len: usize) -> Message {
+        Message::Text("x".repeat(len))
+    }
+
+    #[test]
+    fn test_frames_charged_until_dequeued() {
+        let budget = MemoryBudget::new(100);
+        let (tx, mut rx) = client_channel(&budget, Arc::default());
+
+        assert!(tx.send_within_budget(text(60)).unwrap());
+        assert!(!tx.send_within_budget(text(60)).unwrap());
+        assert_eq!(budget.used(), 60);
+
+        // Replies and control frames are queued over the budget
+        tx.send(text(60)).unwrap();
+        assert_eq!(budget.used(), 120);
+
+        assert_eq!(tx.queued_frames(), 2);
+        drop(rx.try_recv().unwrap());
+        assert_eq!(budget.used(), 60);
+        assert_eq!(tx.queued_frames(), 1);
+        drop(rx);
+        assert_eq!(budget.used(), 0);
+        assert_eq!(tx.queued_frames(), 0);
+    }
+
+    #[test]
+    fn test_no_limit() {
+        let budget = MemoryBudget::new(0);
+        let (tx, _rx) = client_channel(&budget, Arc::default());
+        for _ in 0..10 {
+            assert!(tx.send_within_budget(text(1000)).unwrap());
+        }
+        assert_eq!(budget.used(), 10_000);
+    }
+
+    #[test]
+    fn test_session_limit_drops_oldest() {
+        let budget = MemoryBudget::new(0);
+        let ledger = SessionLedger::new(100, SessionBufferPolicy::DropOldest);
+        let usage = ledger.register("greedy");
+        let (tx, mut rx) = client_channel(&budget, Arc::clone(&usage));
+
+        for i in 0..5 {
+            tx.send(Message::Text(format!("{:0>40}", i))).unwrap();
+        }
+        assert_eq!(usage.bytes(), 80);
+        assert_eq!(ledger.top(1)[0].dropped_frames, 3);
+        assert_eq!(ledger.queued_total(), budget.used());
+
+        // Only the newest frames are left to write
+        let Ok((Message::Text(first), _)) = rx.try_recv() else {
+            panic!("expected a text frame");
+        };
+        assert!(first.ends_with('3'));
+        assert!(tx.send(text(101)).is_err());
+        assert!(rx.try_recv().is_ok());
+        assert!(rx.try_recv().is_err());
+        assert_eq!(usage.bytes(), 0);
+        assert_eq!(budget.used(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_session_limit_disconnects() {
+        let budget = MemoryBudget::new(0);
+        let ledger = SessionLedger::new(100, SessionBufferPolicy::Disconnect);
+        let usage = ledger.register("greedy");
+        let (tx, mut rx) = client_channel(&budget, Arc::clone(&usage));
+
+        tx.send(text(60)).unwrap();
+        assert!(!tx.send_within_budget(text(60)).unwrap());
+        usage.exceeded().await;
+        assert_eq!(usage.bytes(), 0);
+
+        // The close frame still goes out
+        tx.send(Message::Close(None)).unwrap();
+        assert!(matches!(rx.try_recv(), Ok((Message::Close(None), _))));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_urgent_frames_skip_queued_data() {
+        let budget = MemoryBudget::new(0);
+        let (tx, mut rx) = client_channel(&budget, Arc::default());
+        for _ in 0..3 {
+            tx.send(text(10)).unwrap();
+        }
+        tx.send(Message::Pong(Vec::new())).unwrap();
+        assert!(matches!(rx.try_recv(), Ok((Message::Pong(_), _))));
+
+        assert_eq!(tx.discard_queued(), 3);
+        tx.send_urgent(Message::Close(None)).unwrap();
+        assert!(matches!(rx.try_recv(), Ok((Message::Close(None), _))));
+        assert!(rx.try_recv().is_err());
+        assert_eq!(budget.used(), 0);
+        assert_eq!(tx.queued_frames(), 0);
+    }
+
+    #[test]
+    fn test_ledger_charges_other_buffers() {
+        let ledger = SessionLedger::new(100, SessionBufferPolicy::DropOldest);
+        ledger.register("a");
+        ledger.register("b");
+        let mut held = None;
+        assert!(ledger.charge("a", 60, &mut held));
+        assert!(ledger.charge("a", 40, &mut held));
+        assert!(!ledger.charge("a", 1, &mut held));
+        let mut other = None;
+        assert!(ledger.charge("b", 10, &mut other));
+        assert!(ledger.charge("unknown", 1000, &mut None));
+
+        let top = ledger.top(2);
+        assert_eq!(top[0].session_id, "a");
+        assert_eq!(top[0].buffered_bytes, 100);
+        assert_eq!(top[0].queued_bytes, 0);
+        assert_eq!(top[1].buffered_bytes, 10);
+        drop(held);
+        assert_eq!(ledger.top(1)[0].session_id, "b");
+    }
+}
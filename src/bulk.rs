@@ -0,0 +1,51 @@
+use serde::Serialize;
+
+/// Items of a `send_batch` handled per acquisition of the clients lock, so a large batch
+/// doesn't keep connects and disconnects waiting while it is encoded
+pub(crate) const SEND_BATCH_CHUNK: usize = 256;
+
+/// What happened to one message of a `send_batch`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SendOutcome {
+    /// Handed to the session's writer with nothing queued ahead of it
+    Delivered,
+    /// Queued behind frames still waiting to be written to the session
+    Queued,
+    /// No client with this session is connected
+    UnknownSession,
+    /// The session's `MAX_SESSION_BUFFER_BYTES` had no room for the message
+    QueueFull,
+    /// The message couldn't be sent to anyone: past its deadline, too large or a
+    /// forbidden subject
+    Rejected(String),
+}
+
+impl SendOutcome {
+    /// Whether the message is on its way to the session
+    pub fn is_sent(&self) -> bool {
+        matches!(self, Self::Delivered | Self::Queued)
+    }
+}
+
+/// Per-message outcomes of a `send_batch`, in the order the messages were given
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct BatchReport {
+    /// `(session_id, outcome)` for every message of the batch
+    pub outcomes: Vec<(String, SendOutcome)>,
+}
+
+impl BatchReport {
+    /// Number of messages delivered or queued
+    pub fn sent(&self) -> usize {
+        self.outcomes
+            .iter()
+            .filter(|(_, outcome)| outcome.is_sent())
+            .count()
+    }
+
+    /// Number of messages that were not sent, for whatever reason
+    pub fn failed(&self) -> usize {
+        self.outcomes.len() - self.sent()
+    }
+}
@@ -0,0 +1,120 @@
+use std::fmt;
+
+use anyhow::Result;
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::warn;
+
+/// What happens to numbers outside 0–255 in a JSON byte-array body
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ByteArrayPolicy {
+    /// Reject the message with `InvalidByteArray`
+    #[default]
+    Reject,
+    /// Clamp the number into 0–255 (dropping any fraction) and log a warning; elements
+    /// that aren't numbers are still rejected
+    Clamp,
+}
+
+impl ByteArrayPolicy {
+    /// Parse a `BYTE_ARRAY_POLICY` value (`reject` or `clamp`, case-insensitive)
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "reject" => Some(Self::Reject),
+            "clamp" => Some(Self::Clamp),
+            _ => None,
+        }
+    }
+}
+
+/// Error returned when a JSON array body holds an element that isn't a byte
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidByteArray {
+    /// Position of the first offending element
+    pub index: usize,
+    /// The element as JSON
+    pub value: String,
+}
+
+impl fmt::Display for InvalidByteArray {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Body array element {} is not a byte (0-255): {}",
+            self.index, self.value
+        )
+    }
+}
+
+impl std::error::Error for InvalidByteArray {}
+
+/// Build a body from the elements of a JSON byte array
+pub(crate) fn parse_byte_array(values: &[Value], policy: ByteArrayPolicy) -> Result<Bytes> {
+    let mut bytes = Vec::with_capacity(values.len());
+    let mut clamped = 0;
+    for (index, value) in values.iter().enumerate() {
+        let byte = match (value.as_u64(), value.as_f64(), policy) {
+            (Some(n), _, _) if n <= u8::MAX as u64 => n as u8,
+            (_, Some(n), ByteArrayPolicy::Clamp) => {
+                clamped += 1;
+                n.clamp(0.0, u8::MAX as f64) as u8
+            }
+            _ => {
+                return Err(InvalidByteArray {
+                    index,
+                    value: value.to_string(),
+                }
+                .into())
+            }
+        };
+        bytes.push(byte);
+    }
+    if clamped > 0 {
+        warn!(
+            "Clamped {} body array elements outside 0-255 (BYTE_ARRAY_POLICY=clamp)",
+            clamped
+        );
+    }
+    Ok(Bytes::from(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn array(value: Value) -> Vec<Value> {
+        value.as_array().unwrap().clone()
+    }
+
+    #[test]
+    fn test_clean_byte_array() {
+        let body = parse_byte_array(&array(json!([0, 104, 105, 255])), ByteArrayPolicy::Reject);
+        assert_eq!(body.unwrap(), Bytes::from(vec![0, 104, 105, 255]));
+    }
+
+    #[test]
+    fn test_invalid_elements_rejected() {
+        for (values, index) in [
+            (json!([1, 256]), 1),
+            (json!([-1, 2]), 0),
+            (json!([1, 2, "3"]), 2),
+            (json!([1.5]), 0),
+            (json!([null]), 0),
+        ] {
+            let err = parse_byte_array(&array(values), ByteArrayPolicy::Reject).unwrap_err();
+            let invalid = err.downcast_ref::<InvalidByteArray>().unwrap();
+            assert_eq!(invalid.index, index);
+        }
+    }
+
+    #[test]
+    fn test_clamp_policy() {
+        let body = parse_byte_array(&array(json!([-5, 300, 7])), ByteArrayPolicy::Clamp);
+        assert_eq!(body.unwrap(), Bytes::from(vec![0, 255, 7]));
+        // Clamping doesn't turn non-numbers into bytes
+        assert!(parse_byte_array(&array(json!(["a"])), ByteArrayPolicy::Clamp).is_err());
+    }
+}
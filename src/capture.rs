@@ -0,0 +1,241 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::warn;
+
+use crate::connection::{ConnectionConfig, REDACTED};
+
+/// Which way a captured frame travelled, seen from the provider
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FrameDirection {
+    /// Received from the remote server
+    Inbound,
+    /// Sent to the remote server
+    Outbound,
+}
+
+/// One line of a capture file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapturedFrame {
+    pub direction: FrameDirection,
+    /// Milliseconds since the connection was established
+    pub ts_ms: u64,
+    /// `text`, `binary`, `ping`, `pong` or `close`
+    pub opcode: String,
+    /// Text payloads (and close reasons) as-is, other payloads hex-encoded
+    pub payload: String,
+    /// Close code of a `close` frame
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<u16>,
+}
+
+impl CapturedFrame {
+    fn new(direction: FrameDirection, ts_ms: u64, msg: &Message) -> Option<Self> {
+        let (opcode, payload, code) = match msg {
+            Message::Text(text) => ("text", text.clone(), None),
+            Message::Binary(data) => ("binary", to_hex(data), None),
+            Message::Ping(data) => ("ping", to_hex(data), None),
+            Message::Pong(data) => ("pong", to_hex(data), None),
+            Message::Close(frame) => (
+                "close",
+                frame
+                    .as_ref()
+                    .map(|f| f.reason.to_string())
+                    .unwrap_or_default(),
+                frame.as_ref().map(|f| f.code.into()),
+            ),
+            Message::Frame(_) => return None,
+        };
+        Some(Self {
+            direction,
+            ts_ms,
+            opcode: opcode.to_string(),
+            payload,
+            code,
+        })
+    }
+
+    /// Rebuild the WebSocket message this frame was captured from
+    pub fn to_message(&self) -> Result<Message> {
+        Ok(match self.opcode.as_str() {
+            "text" => Message::Text(self.payload.clone()),
+            "binary" => Message::Binary(from_hex(&self.payload)?),
+            "ping" => Message::Ping(from_hex(&self.payload)?),
+            "pong" => Message::Pong(from_hex(&self.payload)?),
+            "close" => Message::Close(self.code.map(|code| CloseFrame {
+                code: CloseCode::from(code),
+                reason: self.payload.clone().into(),
+            })),
+            other => bail!("Unknown opcode in capture: {}", other),
+        })
+    }
+}
+
+/// Appends a connection's frames to a capture file, scrubbing configured secrets
+///
+/// Secrets are the values `ConnectionConfig::redacted` hides (auth token and header
+/// values); any occurrence in a payload is replaced with the same placeholder.
+pub(crate) struct FrameCapture {
+    file: File,
+    started: Instant,
+    secrets: Vec<String>,
+}
+
+impl FrameCapture {
+    /// Open the capture file of `config`, if one is configured
+    pub(crate) fn open(config: &ConnectionConfig) -> Result<Option<Self>> {
+        if config.capture_path.is_empty() {
+            return Ok(None);
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&config.capture_path)
+            .with_context(|| format!("Failed to open capture file {}", config.capture_path))?;
+        Ok(Some(Self {
+            file,
+            started: Instant::now(),
+            secrets: config.secret_values(),
+        }))
+    }
+
+    /// Append one frame; write errors are logged rather than failing the connection
+    pub(crate) fn record(&mut self, direction: FrameDirection, msg: &Message) {
+        let ts_ms = self.started.elapsed().as_millis() as u64;
+        let Some(mut frame) = CapturedFrame::new(direction, ts_ms, msg) else {
+            return;
+        };
+        if !self.secrets.is_empty() {
+            frame.payload = match msg {
+                Message::Text(_) | Message::Close(_) => self.scrub_text(frame.payload),
+                Message::Binary(data) | Message::Ping(data) | Message::Pong(data) => {
+                    to_hex(&self.scrub_bytes(data))
+                }
+                Message::Frame(_) => unreachable!("raw frames are not captured"),
+            };
+        }
+
+        let written = serde_json::to_string(&frame)
+            .map_err(anyhow::Error::from)
+            .and_then(|line| Ok(writeln!(self.file, "{}", line)?));
+        if let Err(e) = written {
+            warn!("Failed to write frame capture: {}", e);
+        }
+    }
+
+    fn scrub_text(&self, mut text: String) -> String {
+        for secret in &self.secrets {
+            text = text.replace(secret.as_str(), REDACTED);
+        }
+        text
+    }
+
+    fn scrub_bytes(&self, data: &[u8]) -> Vec<u8> {
+        let mut data = data.to_vec();
+        for secret in &self.secrets {
+            let secret = secret.as_bytes();
+            let mut scrubbed = Vec::with_capacity(data.len());
+            let mut i = 0;
+            while i < data.len() {
+                if data[i..].starts_with(secret) {
+                    scrubbed.extend_from_slice(REDACTED.as_bytes());
+                    i += secret.len();
+                } else {
+                    scrubbed.push(data[i]);
+                    i += 1;
+                }
+            }
+            data = scrubbed;
+        }
+        data
+    }
+}
+
+/// Read every frame of a capture file
+pub fn read_capture(path: impl AsRef<Path>) -> Result<Vec<CapturedFrame>> {
+    let path = path.as_ref();
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read capture file {}", path.display()))?;
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .enumerate()
+        .map(|(idx, line)| {
+            serde_json::from_str(line)
+                .with_context(|| format!("Invalid frame on line {} of {}", idx + 1, path.display()))
+        })
+        .collect()
+}
+
+fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Result<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        bail!("Odd-length hex payload in capture");
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).context("Invalid hex payload in capture"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_frame_roundtrip() {
+        let messages = vec![
+            Message::Text("hello".to_string()),
+            Message::Binary(vec![0, 1, 0xff]),
+            Message::Ping(vec![9]),
+            Message::Close(Some(CloseFrame {
+                code: CloseCode::Away,
+                reason: "bye".into(),
+            })),
+        ];
+        for msg in messages {
+            let frame = CapturedFrame::new(FrameDirection::Inbound, 0, &msg).unwrap();
+            assert_eq!(frame.to_message().unwrap(), msg);
+        }
+    }
+
+    #[test]
+    fn test_secrets_redacted_before_writing() {
+        let path = std::env::temp_dir().join(format!("capture-{}.ndjson", uuid::Uuid::new_v4()));
+        let map = HashMap::from([
+            ("CAPTURE_PATH".to_string(), path.display().to_string()),
+            ("AUTH_TOKEN".to_string(), "s3cret".to_string()),
+        ]);
+        let config = ConnectionConfig::from_map(&map).unwrap();
+
+        let mut capture = FrameCapture::open(&config).unwrap().unwrap();
+        capture.record(
+            FrameDirection::Outbound,
+            &Message::Text(r#"{"token":"s3cret"}"#.to_string()),
+        );
+        capture.record(
+            FrameDirection::Inbound,
+            &Message::Binary(b"s3cret".to_vec()),
+        );
+
+        let frames = read_capture(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(frames[0].payload, r#"{"token":"[REDACTED]"}"#);
+        assert_eq!(
+            frames[1].to_message().unwrap(),
+            Message::Binary(REDACTED.as_bytes().to_vec())
+        );
+    }
+}
@@ -0,0 +1,683 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use futures::{SinkExt, StreamExt};
+use tokio::sync::{mpsc, oneshot, RwLock};
+use tokio_tungstenite::tungstenite::{Error as WsError, Message};
+use tracing::{debug, error, info, warn, Span};
+
+use crate::acl::InboundFilter;
+use crate::batch::MessageBatcher;
+use crate::body_encoding::BodyDecoding;
+use crate::body_stream::{self, BodyStreams};
+use crate::byte_array::ByteArrayPolicy;
+use crate::capture::{FrameCapture, FrameDirection};
+use crate::codec::Codec;
+use crate::connection::{ConnectionConfig, ConnectionMode};
+use crate::deadline::Deadlines;
+use crate::dispatch::HandlerDispatch;
+use crate::drop_notify::DropNotifier;
+use crate::envelope::{EnvelopeVersion, UpgradeOp};
+use crate::failover::Endpoints;
+use crate::flow::FlowGauge;
+use crate::heartbeat::{self, ClientHeartbeat};
+use crate::legacy_hex::LegacyHexTracker;
+use crate::link_status::{FailedLinks, LinkHealth, LinkRole};
+use crate::metrics::MessageMetrics;
+use crate::parse_guard::ParseFailureReason;
+use crate::pipeline::Pipeline;
+use crate::reconnect::{QueuedFrame, ReconnectBuffer, ReconnectPolicy};
+use crate::routing::{HandlerOverflow, RouteDelivery};
+use crate::session_store::SessionRegistration;
+use crate::signing::{self, MessageSigner};
+use crate::spill::{Spill, SpillingReceiver};
+use crate::subject::FieldLimits;
+use crate::tasks::Dispatcher;
+use crate::telemetry::{LabelMetrics, SessionCounters};
+use crate::transport::{WsSink, WsStream, WsTransport};
+use crate::{
+    BrokerMessage, MessageHandler, PendingRequests, TokenProvider, WebSocketClientBundle,
+    WebSocketMessagingProvider as Provider,
+};
+
+/// Links of one role, by component id
+type Components = Arc<RwLock<HashMap<String, Arc<WebSocketClientBundle>>>>;
+
+/// The writing side of a client connection
+///
+/// Every frame the connection task writes, queued or its own (pongs, pings, hellos and
+/// envelope upgrade replies), is signed, encoded for the link's `CODEC` and captured the
+/// same way on its way to the socket.
+pub(crate) struct FrameWriter {
+    /// Replaced when the connection is redialled
+    pub(crate) sink: WsSink,
+    capture: Option<FrameCapture>,
+    signer: Option<MessageSigner>,
+    codec: Codec,
+}
+
+impl FrameWriter {
+    pub(crate) fn new(
+        sink: WsSink,
+        config: &ConnectionConfig,
+        capture: Option<FrameCapture>,
+    ) -> Self {
+        Self {
+            sink,
+            capture,
+            signer: MessageSigner::from_config(config),
+            codec: config.codec,
+        }
+    }
+
+    /// Sign, encode, capture and send `msg`, returning the length written
+    pub(crate) async fn write_frame(&mut self, msg: Message) -> Result<usize, WsError> {
+        let msg = self.prepare(msg);
+        let len = msg.len();
+        self.sink.send(msg).await?;
+        Ok(len)
+    }
+
+    /// Like `write_frame`, but leaves the frame for the next `flush`
+    pub(crate) async fn feed_frame(&mut self, msg: Message) -> Result<usize, WsError> {
+        let msg = self.prepare(msg);
+        let len = msg.len();
+        self.sink.feed(msg).await?;
+        Ok(len)
+    }
+
+    pub(crate) async fn flush(&mut self) -> Result<(), WsError> {
+        self.sink.flush().await
+    }
+
+    /// Capture a frame received on the connection
+    pub(crate) fn capture_inbound(&mut self, msg: &Message) {
+        if let Some(capture) = self.capture.as_mut() {
+            capture.record(FrameDirection::Inbound, msg);
+        }
+    }
+
+    fn prepare(&mut self, msg: Message) -> Message {
+        let msg = match &self.signer {
+            Some(signer) => signer.sign(msg),
+            None => msg,
+        };
+        let msg = self.codec.encode(msg);
+        if let Some(capture) = self.capture.as_mut() {
+            capture.record(FrameDirection::Outbound, &msg);
+        }
+        msg
+    }
+}
+
+/// The task behind a client connection
+///
+/// It writes the link's queued frames, hands inbound messages to the handlers and, with
+/// `RECONNECT`, redials a connection that drops, so the queue, session and handler
+/// wiring carry over to the new socket.
+pub(crate) struct ClientTask {
+    pub(crate) component_id: String,
+    /// The server's own session id once it announces one
+    pub(crate) session_id: String,
+    /// Body streams and request replies belong to this connection only
+    pub(crate) connection_id: String,
+    pub(crate) config: ConnectionConfig,
+    pub(crate) rx: SpillingReceiver,
+    pub(crate) flush_rx: mpsc::UnboundedReceiver<oneshot::Sender<()>>,
+    pub(crate) queue_capacity: usize,
+    pub(crate) writer: FrameWriter,
+    pub(crate) stream: WsStream,
+    pub(crate) signer: Option<MessageSigner>,
+    pub(crate) codec: Codec,
+    pub(crate) counters: SessionCounters,
+    pub(crate) health: Arc<LinkHealth>,
+    /// The frame being written is the oldest one the connection hasn't delivered
+    pub(crate) dispatcher: Dispatcher,
+    pub(crate) reconnect_buffer: ReconnectBuffer,
+    pub(crate) deadlines: Deadlines,
+    /// Measures writes and signals the resume once the queue drains
+    pub(crate) flow: Arc<FlowGauge>,
+    pub(crate) flow_tx: mpsc::WeakSender<QueuedFrame>,
+    pub(crate) message_handler: Arc<std::sync::RwLock<Option<MessageHandler>>>,
+    pub(crate) heartbeat: ClientHeartbeat,
+    pub(crate) envelope: Arc<EnvelopeVersion>,
+    pub(crate) awaiting_server_session: bool,
+    pub(crate) registration: Option<SessionRegistration>,
+    pub(crate) pipeline: Pipeline,
+    pub(crate) inbound_filter: InboundFilter,
+    pub(crate) byte_array_policy: ByteArrayPolicy,
+    pub(crate) body_decoding: BodyDecoding,
+    pub(crate) field_limits: FieldLimits,
+    pub(crate) legacy_hex: LegacyHexTracker,
+    pub(crate) pending_requests: PendingRequests,
+    pub(crate) body_streams: BodyStreams,
+    /// Weak, like the senders below, so the task doesn't keep its own queue open
+    pub(crate) stream_tx: mpsc::WeakSender<QueuedFrame>,
+    pub(crate) metrics: LabelMetrics,
+    pub(crate) message_metrics: MessageMetrics,
+    pub(crate) batcher: MessageBatcher,
+    pub(crate) handler_components: Components,
+    pub(crate) consumer_components: Components,
+    pub(crate) route_delivery: RouteDelivery,
+    pub(crate) handler_overflow: HandlerOverflow,
+    pub(crate) handler_dispatch: HandlerDispatch,
+    pub(crate) drop_notices: Option<(DropNotifier, mpsc::WeakSender<QueuedFrame>)>,
+    pub(crate) spill: Spill,
+    pub(crate) reconnect: Option<ReconnectPolicy>,
+    pub(crate) transport: Arc<dyn WsTransport>,
+    pub(crate) token_provider: Option<TokenProvider>,
+    pub(crate) endpoints: Arc<Endpoints>,
+    pub(crate) failed_links: FailedLinks,
+    /// Set once this end starts the closing handshake, which isn't a lost connection
+    pub(crate) closing: bool,
+}
+
+impl ClientTask {
+    /// Serve the connection, and each one it is redialled as, until it ends for good
+    pub(crate) async fn run(mut self, span: Span) {
+        let mut gave_up = false;
+        loop {
+            self.serve().await;
+
+            // Streams still arriving can't complete any more
+            self.body_streams.fail_connection(&self.connection_id);
+
+            let Some(policy) = self.reconnect.filter(|_| !self.closing) else {
+                break;
+            };
+            if !self.redial(policy).await {
+                gave_up = true;
+                break;
+            }
+        }
+
+        // Deliver whatever is still batched before tearing down, unless that is
+        // the provider shutting down
+        let remaining = self.batcher.take();
+        if !remaining.is_empty() && !self.spill.drop_inbound(remaining.len()) {
+            Provider::forward_to_handlers(
+                &self.handler_components,
+                self.route_delivery,
+                self.handler_overflow,
+                &self.handler_dispatch,
+                remaining,
+            )
+            .await;
+        }
+
+        // Cleanup session on disconnect
+        drop(self.registration.take());
+        info!(
+            "WebSocket connection handler terminated for component {}",
+            self.component_id
+        );
+        self.counters.finish(&span);
+
+        // A link whose connection couldn't be restored is unlinked and listed as
+        // failed; the removed bundles are dropped last, as that aborts this task
+        if gave_up {
+            let mut removed = Vec::new();
+            for (components, role) in [
+                (&self.consumer_components, LinkRole::Consumer),
+                (&self.handler_components, LinkRole::Handler),
+            ] {
+                let mut components = components.write().await;
+                if components
+                    .get(&self.component_id)
+                    .is_some_and(|bundle| Arc::ptr_eq(&bundle.health, &self.health))
+                {
+                    removed.extend(components.remove(&self.component_id));
+                    self.failed_links.record(
+                        &self.component_id,
+                        role,
+                        "Connection lost and reconnect attempts exhausted",
+                    );
+                }
+            }
+        }
+    }
+
+    /// Move frames both ways until the connection ends
+    async fn serve(&mut self) {
+        loop {
+            tokio::select! {
+                // Handle outgoing messages
+                Some(queued) = self.rx.recv() => {
+                    let started = std::time::Instant::now();
+                    match self.write_queued(queued, false).await {
+                        Ok(true) => {
+                            self.flow.record_write(started.elapsed());
+                            self.observe_drain();
+                        }
+                        Ok(false) => {}
+                        Err(e) => {
+                            error!("Failed to send WebSocket message: {}", e);
+                            self.health.record_error(format!("Failed to send WebSocket message: {}", e));
+                            break;
+                        }
+                    }
+                }
+                // Write what was queued before a `flush_component` call, then confirm;
+                // at most a queue's worth, so frames queued since can't hold it up
+                Some(flushed) = self.flush_rx.recv() => {
+                    let mut written = Ok(());
+                    for _ in 0..self.queue_capacity {
+                        let Ok(queued) = self.rx.try_recv() else {
+                            break;
+                        };
+                        if let Err(e) = self.write_queued(queued, true).await {
+                            written = Err(e);
+                            break;
+                        }
+                    }
+                    let written = match written {
+                        Ok(()) => {
+                            let _in_flight = self.dispatcher.in_flight();
+                            self.writer.flush().await
+                        }
+                        failed => failed,
+                    };
+                    if let Err(e) = written {
+                        // Dropping `flushed` tells the caller the connection ended
+                        error!("Failed to flush WebSocket messages: {}", e);
+                        self.health.record_error(format!("Failed to flush WebSocket messages: {}", e));
+                        break;
+                    }
+                    let _ = flushed.send(());
+                    self.observe_drain();
+                }
+                // Handle incoming messages from remote WebSocket server
+                msg_result = self.stream.next() => {
+                    // The transport dropped without a close frame
+                    let Some(msg_result) = msg_result else {
+                        info!("WebSocket stream ended");
+                        break;
+                    };
+                    // Once shutdown has begun nothing new reaches the handlers, whose
+                    // queues are being torn down, and the connection winds up
+                    if matches!(msg_result, Ok(Message::Text(_) | Message::Binary(_))) && self.spill.drop_inbound(1) {
+                        debug!("Dropped inbound frame received during shutdown");
+                        self.closing = true;
+                        break;
+                    }
+                    if let Ok(msg) = &msg_result {
+                        self.writer.capture_inbound(msg);
+                    }
+                    // Envelopes on a MessagePack link are handled as the JSON they encode
+                    let msg_result = match msg_result {
+                        Ok(Message::Binary(data)) => match self.codec.decode(&data) {
+                            Some(text) => Ok(Message::Text(text)),
+                            None => Ok(Message::Binary(data)),
+                        },
+                        other => other,
+                    };
+                    match msg_result {
+                        Ok(Message::Text(text)) => {
+                            if let Err(e) = self.handle_inbound_text(text).await {
+                                error!("{:#}", e);
+                                self.health.record_error(format!("{:#}", e));
+                                break;
+                            }
+                        }
+                        Ok(Message::Binary(data)) => self.handle_inbound_binary(data).await,
+                        Ok(Message::Close(frame)) => {
+                            if let Some(frame) = frame {
+                                self.counters.record_close(frame.code.into());
+                                self.health.record_close(frame.code.into(), frame.reason.to_string());
+                            }
+                            info!("WebSocket connection closed");
+                            break;
+                        }
+                        Ok(Message::Ping(data)) => {
+                            if let Err(e) = self.writer.write_frame(Message::Pong(data)).await {
+                                error!("Failed to send pong: {}", e);
+                                break;
+                            }
+                        }
+                        Ok(Message::Pong(_)) => self.heartbeat.pong(),
+                        Ok(_) => {}
+                        Err(e) => {
+                            // tungstenite refuses text frames that aren't valid UTF-8
+                            if matches!(e, WsError::Utf8) {
+                                self.metrics.record_parse_failure(ParseFailureReason::InvalidUtf8);
+                            }
+                            error!("WebSocket error: {}", e);
+                            self.health.record_error(format!("WebSocket error: {}", e));
+                            break;
+                        }
+                    }
+                }
+                // Keep the connection from counting as idle at the server
+                _ = tokio::time::sleep_until(self.heartbeat.deadline().unwrap_or_else(tokio::time::Instant::now)), if self.heartbeat.deadline().is_some() => {
+                    self.heartbeat.sent();
+                    if let Err(e) = self.writer.write_frame(Message::Ping(Vec::new())).await {
+                        error!("Failed to send heartbeat ping: {}", e);
+                        self.health.record_error(format!("Failed to send heartbeat ping: {}", e));
+                        break;
+                    }
+                }
+                // A server that stops answering pings is gone, even if the socket isn't
+                _ = tokio::time::sleep_until(self.heartbeat.pong_deadline().unwrap_or_else(tokio::time::Instant::now)), if self.heartbeat.pong_deadline().is_some() => {
+                    warn!("No pong from remote server within {:?}, dropping the connection", self.config.pong_timeout);
+                    self.health.record_error("No pong within PONG_TIMEOUT");
+                    break;
+                }
+                // Flush a partial batch once its window has elapsed
+                _ = tokio::time::sleep_until(self.batcher.deadline().unwrap_or_else(tokio::time::Instant::now)), if self.batcher.deadline().is_some() => {
+                    let batch = self.batcher.take();
+                    if self.spill.drop_inbound(batch.len()) {
+                        self.closing = true;
+                        break;
+                    }
+                    self.forward(batch).await;
+                }
+                // Summarize drops that exceeded the notification rate
+                _ = tokio::time::sleep_until(Provider::drop_summary_deadline(&self.drop_notices).unwrap_or_else(tokio::time::Instant::now)), if Provider::drop_summary_deadline(&self.drop_notices).is_some() => {
+                    Provider::flush_drop_summary(&mut self.drop_notices, self.envelope.current());
+                }
+                else => break,
+            }
+        }
+    }
+
+    /// Write a frame taken off the outbound queue, unless it outlived an outage or its
+    /// deadline there; with `feed` it waits for the next flush. True if it was written
+    async fn write_queued(&mut self, queued: QueuedFrame, feed: bool) -> Result<bool, WsError> {
+        let Some(msg) = self.reconnect_buffer.admit(queued) else {
+            return Ok(false);
+        };
+        if !self.deadlines.admit_frame(&msg) {
+            debug!("Dropped queued frame past its deadline");
+            return Ok(false);
+        }
+        self.closing |= matches!(msg, Message::Close(_));
+        let _in_flight = self.dispatcher.in_flight();
+        let len = if feed {
+            self.writer.feed_frame(msg).await?
+        } else {
+            self.writer.write_frame(msg).await?
+        };
+        self.counters.record_frame(len);
+        self.health.record_out();
+        Ok(true)
+    }
+
+    /// Handle a text frame from the server, failing only if the connection did
+    async fn handle_inbound_text(&mut self, text: String) -> Result<()> {
+        self.counters.record_frame(text.len());
+        self.health.record_in();
+        debug!("Received text message from remote server: {}", text);
+
+        // The server's welcome or hello_ack may set the ping interval
+        if let Some((interval_ms, is_ack)) = heartbeat::server_interval(&text) {
+            debug!("Remote server expects a heartbeat every {} ms", interval_ms);
+            self.heartbeat.adopt(interval_ms);
+            if is_ack {
+                return Ok(());
+            }
+        }
+
+        // The first message may announce the server's session id
+        if self.awaiting_server_session {
+            self.awaiting_server_session = false;
+            if let Some((server_id, is_welcome)) =
+                Provider::server_session_id(&text, &self.config.server_session_field)
+            {
+                info!(
+                    "Remote server assigned session {} (local {})",
+                    server_id, self.session_id
+                );
+                if let Some(registration) = self.registration.as_mut() {
+                    registration.rename(&server_id).await;
+                }
+                self.rx.session_id = server_id.clone();
+                self.session_id = server_id;
+                if is_welcome {
+                    return Ok(());
+                }
+            }
+        }
+
+        // Envelope upgrades are negotiated in band and never dispatched
+        if let Some(op) = UpgradeOp::parse(&text) {
+            if let Some(reply) = self.envelope.handle(op, &self.session_id) {
+                self.writer
+                    .write_frame(Message::Text(reply.to_json()))
+                    .await
+                    .context("Failed to answer envelope upgrade")?;
+            }
+            return Ok(());
+        }
+
+        let parsed = self
+            .envelope
+            .check_inbound(&text)
+            .and_then(|()| signing::verify(self.signer.as_ref(), &text))
+            .and_then(|()| {
+                Provider::parse_message_with(
+                    &text,
+                    &self.session_id,
+                    self.byte_array_policy,
+                    self.body_decoding,
+                    self.field_limits,
+                )
+            })
+            .map_err(|e| Provider::reject_frame(&self.metrics, &self.health, &e))
+            .ok()
+            .and_then(|(msg, corr_id)| Some((self.pipeline.run(&self.session_id, msg)?, corr_id)));
+        if let Some((msg, corr_id)) = parsed {
+            self.deliver(msg, corr_id).await;
+        }
+        Ok(())
+    }
+
+    /// Handle a binary frame from the server: a body stream frame, an envelope, or raw
+    /// bytes handed on as they are
+    async fn handle_inbound_binary(&mut self, data: Vec<u8>) {
+        self.counters.record_frame(data.len());
+        self.health.record_in();
+        debug!(
+            "Received binary message from remote server: {} bytes",
+            data.len()
+        );
+
+        // Continuation frames of a streamed body go to its subscriber
+        let data = match body_stream::decode_frame(data) {
+            Ok((stream_id, frame)) => {
+                if let Some(cancel) = self
+                    .body_streams
+                    .receive(&self.connection_id, stream_id, frame)
+                    .await
+                {
+                    if let Some(tx) = self.stream_tx.upgrade() {
+                        let _ = tx.try_send(Message::Binary(cancel).into());
+                    }
+                }
+                return;
+            }
+            Err(data) => data,
+        };
+
+        // Parse valid UTF-8 as text in place, otherwise hand on the raw bytes
+        let parsed = match std::str::from_utf8(&data) {
+            Ok(text) => self
+                .envelope
+                .check_inbound(text)
+                .and_then(|()| signing::verify(self.signer.as_ref(), text))
+                .and_then(|()| {
+                    Provider::parse_message_with(
+                        text,
+                        &self.session_id,
+                        self.byte_array_policy,
+                        self.body_decoding,
+                        self.field_limits,
+                    )
+                })
+                .map_err(|e| Provider::reject_frame(&self.metrics, &self.health, &e))
+                .ok()
+                .and_then(|(msg, corr_id)| {
+                    Some((self.pipeline.run(&self.session_id, msg)?, corr_id))
+                }),
+            // Raw bytes can't carry a signature
+            Err(e) => match self
+                .signer
+                .as_ref()
+                .map_or(Ok(()), MessageSigner::verify_unsigned)
+            {
+                Ok(()) => {
+                    debug!(
+                        "Binary frame is not UTF-8 ({}), forwarding as raw binary",
+                        e
+                    );
+                    Some((Provider::binary_message(data, &self.session_id), None))
+                }
+                Err(e) => {
+                    Provider::reject_frame(&self.metrics, &self.health, &e);
+                    None
+                }
+            },
+        };
+        if let Some((msg, corr_id)) = parsed {
+            self.deliver(msg, corr_id).await;
+        }
+    }
+
+    /// Complete the request `msg` answers, or pass it through the inbound rules on to
+    /// the handlers
+    async fn deliver(&mut self, msg: BrokerMessage, corr_id: Option<String>) {
+        let Some(msg) = Provider::complete_request(
+            &self.pending_requests,
+            &self.connection_id,
+            msg,
+            corr_id.as_deref(),
+        )
+        .filter(|msg| self.inbound_filter.admit(&self.session_id, &msg.subject))
+        .filter(|msg| self.deadlines.admit_inbound(&self.session_id, None, msg)) else {
+            return;
+        };
+        self.legacy_hex.observe(&msg, &self.session_id);
+        self.body_streams.open_descriptor(&msg, &self.connection_id);
+        self.message_metrics.record(
+            ConnectionMode::Client,
+            LinkRole::Handler,
+            &msg.subject,
+            msg.body.len(),
+            Some(&self.component_id),
+        );
+        if let Some(batch) = self.batcher.push(msg) {
+            self.forward(batch).await;
+        }
+    }
+
+    /// Forward a batch to the handler components, notifying the drops
+    async fn forward(&mut self, batch: Vec<BrokerMessage>) {
+        let dropped = Provider::forward_to_handlers(
+            &self.handler_components,
+            self.route_delivery,
+            self.handler_overflow,
+            &self.handler_dispatch,
+            batch,
+        )
+        .await;
+        Provider::notify_drops(&mut self.drop_notices, dropped, self.envelope.current());
+    }
+
+    /// Signal a resume if writing has drained the queue to its low watermark
+    fn observe_drain(&self) {
+        let Some(tx) = self.flow_tx.upgrade() else {
+            return;
+        };
+        let queue_used = tx.max_capacity() - tx.capacity();
+        if let Some(signal) = self.flow.observe(queue_used) {
+            Provider::dispatch_flow_signal(
+                &self.message_handler,
+                &self.flow,
+                signal,
+                &self.session_id,
+                &self.component_id,
+                queue_used,
+            );
+        }
+    }
+
+    /// Dial the link again after its connection dropped, backing off between attempts;
+    /// false once `RECONNECT_MAX_RETRIES` attempts failed
+    async fn redial(&mut self, policy: ReconnectPolicy) -> bool {
+        let what = format!("component {}", self.component_id);
+        let redialled = policy
+            .retry(&what, |_| {
+                Provider::dial_any(
+                    &self.transport,
+                    self.token_provider.as_ref(),
+                    &self.config,
+                    &self.endpoints,
+                    true,
+                    &self.component_id,
+                )
+            })
+            .await;
+        let Some(connection) = redialled else {
+            error!(
+                "Giving up on component {} after {} reconnect attempts",
+                self.component_id, self.config.reconnect_max_retries
+            );
+            return false;
+        };
+        info!(
+            "Reconnected component {} (session {}) to {}",
+            self.component_id,
+            self.session_id,
+            self.endpoints.active()
+        );
+        self.writer.sink = connection.sink;
+        self.stream = connection.stream;
+        self.reconnect_buffer.reconnected();
+        self.awaiting_server_session = !self.config.server_session_field.is_empty();
+        self.heartbeat = ClientHeartbeat::from_config(&self.config);
+        if !self.config.heartbeat_interval.is_zero() {
+            let hello = Message::Text(heartbeat::hello(self.config.heartbeat_interval));
+            let _ = self.writer.write_frame(hello).await;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::channel::mpsc;
+
+    #[tokio::test]
+    async fn test_write_frame_signs_and_encodes_every_text_frame() {
+        let map = HashMap::from([
+            ("MESSAGE_HMAC_KEY".to_string(), "secret".to_string()),
+            ("CODEC".to_string(), "msgpack".to_string()),
+        ]);
+        let config = ConnectionConfig::from_map(&map).unwrap();
+        let (tx, mut rx) = mpsc::unbounded::<Message>();
+        let sink: WsSink = Box::pin(tx.sink_map_err(|_| WsError::ConnectionClosed));
+        let mut writer = FrameWriter::new(sink, &config, None);
+
+        // A control envelope such as an upgrade reply gets the same treatment as a publish
+        let len = writer
+            .write_frame(Message::Text(
+                r#"{"op":"upgrade_ack","version":2}"#.to_string(),
+            ))
+            .await
+            .unwrap();
+        let Some(Message::Binary(data)) = rx.next().await else {
+            panic!("expected a MessagePack frame");
+        };
+        assert_eq!(len, data.len());
+        let json: serde_json::Value =
+            serde_json::from_str(&config.codec.decode(&data).unwrap()).unwrap();
+        assert_eq!(json["op"], "upgrade_ack");
+        assert!(json["sig"].is_string());
+
+        // Frames without an envelope pass unchanged
+        writer.feed_frame(Message::Ping(vec![1])).await.unwrap();
+        writer.flush().await.unwrap();
+        assert_eq!(rx.next().await, Some(Message::Ping(vec![1])));
+    }
+}
@@ -0,0 +1,149 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tokio::time::Instant;
+use tracing::warn;
+
+/// How far behind the guarded time a reading must be to count as the clock being set
+/// back, rather than the wall and monotonic clocks ticking at slightly different rates
+const BACKWARD_JUMP_TOLERANCE: Duration = Duration::from_secs(1);
+
+type ClockFn = dyn Fn() -> SystemTime + Send + Sync;
+
+/// The last guarded reading
+#[derive(Debug, Clone, Copy)]
+struct Reading {
+    at: Instant,
+    unix_ms: u64,
+    /// Whether the wall clock was behind it, set back and not yet caught up
+    behind: bool,
+}
+
+/// The wall clock, for the features defined against it (`deadline_unix_ms`), guarded
+/// against being set back
+///
+/// Everything the provider schedules itself (TTLs, timeouts, backoff, rate limits) runs
+/// on monotonic time and is unaffected by the wall clock. Readings taken here are held
+/// against the previous one advanced by the monotonic time since: when the wall clock
+/// is set back (an NTP step, a resumed VM), time keeps counting forward from where it
+/// was until the wall clock catches up, so messages already past their deadline don't
+/// come back to life. A clock set forward is taken as it is.
+#[derive(Clone)]
+pub(crate) struct WallClock {
+    read: Arc<ClockFn>,
+    last: Arc<Mutex<Option<Reading>>>,
+}
+
+impl std::fmt::Debug for WallClock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WallClock")
+            .field("last", &self.last)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for WallClock {
+    fn default() -> Self {
+        Self::new(SystemTime::now)
+    }
+}
+
+impl WallClock {
+    /// Guard readings of `read` instead of the system clock
+    pub(crate) fn new(read: impl Fn() -> SystemTime + Send + Sync + 'static) -> Self {
+        Self {
+            read: Arc::new(read),
+            last: Arc::default(),
+        }
+    }
+
+    /// Milliseconds since the Unix epoch, never behind the previous reading advanced by
+    /// the monotonic time since
+    pub(crate) fn now_unix_ms(&self) -> u64 {
+        let read = (self.read)()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis() as u64);
+        let at = Instant::now();
+        let mut last = self.last.lock().unwrap();
+        let reading = match *last {
+            Some(previous) => {
+                let floor = previous.unix_ms + (at - previous.at).as_millis() as u64;
+                let behind = floor.saturating_sub(read);
+                if behind > BACKWARD_JUMP_TOLERANCE.as_millis() as u64 {
+                    if !previous.behind {
+                        warn!(
+                            "Wall clock went back {} ms; deadlines are checked against monotonic time until it catches up",
+                            behind
+                        );
+                    }
+                    Reading {
+                        at,
+                        unix_ms: floor,
+                        behind: true,
+                    }
+                } else {
+                    Reading {
+                        at,
+                        unix_ms: read,
+                        behind: false,
+                    }
+                }
+            }
+            None => Reading {
+                at,
+                unix_ms: read,
+                behind: false,
+            },
+        };
+        *last = Some(reading);
+        reading.unix_ms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    const HOUR_MS: u64 = 60 * 60 * 1000;
+    const START_MS: u64 = 1_700_000_000_000;
+
+    fn mock() -> (WallClock, Arc<AtomicU64>) {
+        let wall = Arc::new(AtomicU64::new(START_MS));
+        let read = Arc::clone(&wall);
+        let clock =
+            WallClock::new(move || UNIX_EPOCH + Duration::from_millis(read.load(Ordering::SeqCst)));
+        (clock, wall)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_backward_jump_keeps_counting_forward() {
+        let (clock, wall) = mock();
+        assert_eq!(clock.now_unix_ms(), START_MS);
+
+        wall.fetch_sub(HOUR_MS, Ordering::SeqCst);
+        assert_eq!(clock.now_unix_ms(), START_MS);
+        tokio::time::advance(Duration::from_secs(10)).await;
+        wall.fetch_add(10_000, Ordering::SeqCst);
+        assert_eq!(clock.now_unix_ms(), START_MS + 10_000);
+
+        // The wall clock is taken again once it has caught up
+        tokio::time::advance(Duration::from_secs(5)).await;
+        wall.store(START_MS + 20_000, Ordering::SeqCst);
+        assert_eq!(clock.now_unix_ms(), START_MS + 20_000);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_forward_jump_and_drift() {
+        let (clock, wall) = mock();
+        assert_eq!(clock.now_unix_ms(), START_MS);
+
+        wall.fetch_add(HOUR_MS, Ordering::SeqCst);
+        assert_eq!(clock.now_unix_ms(), START_MS + HOUR_MS);
+
+        // A wall clock a little behind the monotonic one is still followed
+        tokio::time::advance(Duration::from_secs(10)).await;
+        wall.fetch_add(9_500, Ordering::SeqCst);
+        assert_eq!(clock.now_unix_ms(), START_MS + HOUR_MS + 9_500);
+    }
+}
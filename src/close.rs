@@ -0,0 +1,146 @@
+use axum::extract::ws::{CloseFrame, Message};
+
+use crate::budget::ClientSender;
+
+/// Why the provider closed a client's connection
+///
+/// Each reason maps to a fixed close code so client applications can react to it. The
+/// standard codes are used where one fits; provider-specific reasons are assigned codes in
+/// the private-use range 4000–4099, which are reserved for this provider:
+///
+/// | Reason              | Code | Wire reason               |
+/// |---------------------|------|---------------------------|
+/// | `Normal`            | 1000 | `normal closure`          |
+/// | `Shutdown`          | 1001 | `server shutting down`    |
+/// | `Handoff`           | 1001 | `handoff`                 |
+/// | `InvalidFrames`     | 1008 | `too many invalid frames` |
+/// | `MessageTooBig`     | 1009 | `message too big`         |
+/// | `Overloaded`        | 1013 | `server overloaded`       |
+/// | `SlowConsumer`      | 4000 | `slow consumer`           |
+/// | `AuthFailed`        | 4001 | `authentication failed`   |
+/// | `RateLimited`       | 4002 | `rate limited`            |
+/// | `IdleTimeout`       | 4003 | `idle timeout`            |
+/// | `Draining`          | 4004 | `draining`                |
+/// | `Banned`            | 4005 | `banned`                  |
+/// | `SessionBufferFull` | 4006 | `session buffer full`     |
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum CloseReason {
+    Normal,
+    Shutdown,
+    Handoff,
+    InvalidFrames,
+    MessageTooBig,
+    Overloaded,
+    SlowConsumer,
+    AuthFailed,
+    RateLimited,
+    IdleTimeout,
+    Draining,
+    Banned,
+    SessionBufferFull,
+}
+
+impl CloseReason {
+    /// Close code sent to the client
+    pub fn code(self) -> u16 {
+        match self {
+            Self::Normal => 1000,
+            Self::Shutdown | Self::Handoff => 1001,
+            Self::InvalidFrames => 1008,
+            Self::MessageTooBig => 1009,
+            Self::Overloaded => 1013,
+            Self::SlowConsumer => 4000,
+            Self::AuthFailed => 4001,
+            Self::RateLimited => 4002,
+            Self::IdleTimeout => 4003,
+            Self::Draining => 4004,
+            Self::Banned => 4005,
+            Self::SessionBufferFull => 4006,
+        }
+    }
+
+    /// Reason string sent in the close frame
+    pub fn reason(self) -> &'static str {
+        match self {
+            Self::Normal => "normal closure",
+            Self::Shutdown => "server shutting down",
+            Self::Handoff => "handoff",
+            Self::InvalidFrames => "too many invalid frames",
+            Self::MessageTooBig => "message too big",
+            Self::Overloaded => "server overloaded",
+            Self::SlowConsumer => "slow consumer",
+            Self::AuthFailed => "authentication failed",
+            Self::RateLimited => "rate limited",
+            Self::IdleTimeout => "idle timeout",
+            Self::Draining => "draining",
+            Self::Banned => "banned",
+            Self::SessionBufferFull => "session buffer full",
+        }
+    }
+
+    /// Short name used as a log field and metrics key
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::Shutdown => "shutdown",
+            Self::Handoff => "handoff",
+            Self::InvalidFrames => "invalid_frames",
+            Self::MessageTooBig => "message_too_big",
+            Self::Overloaded => "overloaded",
+            Self::SlowConsumer => "slow_consumer",
+            Self::AuthFailed => "auth_failed",
+            Self::RateLimited => "rate_limited",
+            Self::IdleTimeout => "idle_timeout",
+            Self::Draining => "draining",
+            Self::Banned => "banned",
+            Self::SessionBufferFull => "session_buffer_full",
+        }
+    }
+
+    /// The close frame for this reason
+    pub(crate) fn frame(self) -> Message {
+        Message::Close(Some(CloseFrame {
+            code: self.code(),
+            reason: self.reason().into(),
+        }))
+    }
+}
+
+/// Queue the close frame for `reason` on a client's outbound channel
+///
+/// Returns whether the frame was queued; the client's writer stops after sending it.
+pub(crate) fn close_with_reason(tx: &ClientSender, reason: CloseReason) -> bool {
+    tx.send(reason.frame()).is_ok()
+}
+
+/// Discard a client's queued data and send the close frame for `reason` ahead of anything
+/// still queued
+///
+/// Returns how many frames were discarded, or `None` if the writer has gone away.
+pub(crate) fn close_now(tx: &ClientSender, reason: CloseReason) -> Option<usize> {
+    let discarded = tx.discard_queued();
+    tx.send_urgent(reason.frame()).ok().map(|()| discarded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_codes_in_reserved_range() {
+        let reasons = [
+            CloseReason::SlowConsumer,
+            CloseReason::AuthFailed,
+            CloseReason::RateLimited,
+            CloseReason::IdleTimeout,
+            CloseReason::Draining,
+            CloseReason::Banned,
+            CloseReason::SessionBufferFull,
+        ];
+        for reason in reasons {
+            assert!((4000..=4099).contains(&reason.code()), "{:?}", reason);
+        }
+        assert_eq!(CloseReason::InvalidFrames.code(), 1008);
+    }
+}
@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::warn;
+
+/// Wire format of the envelopes on a client connection
+///
+/// The envelope is the same either way; the codec only decides how it is framed, so
+/// signing, versioning and body compression work unchanged under both.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Codec {
+    /// Envelopes are JSON text frames
+    #[default]
+    Json,
+    /// Envelopes are MessagePack binary frames
+    Msgpack,
+}
+
+impl Codec {
+    /// Parse a `CODEC` value (`json` or `msgpack`, case-insensitive)
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "json" => Some(Self::Json),
+            "msgpack" | "messagepack" => Some(Self::Msgpack),
+            _ => None,
+        }
+    }
+
+    /// Reframe an outbound text frame holding a JSON object; other frames pass unchanged
+    pub(crate) fn encode(self, frame: Message) -> Message {
+        let Self::Msgpack = self else {
+            return frame;
+        };
+        let Message::Text(text) = &frame else {
+            return frame;
+        };
+        let Ok(envelope @ Value::Object(_)) = serde_json::from_str::<Value>(text) else {
+            return frame;
+        };
+        match rmp_serde::to_vec_named(&envelope) {
+            Ok(bytes) => Message::Binary(bytes),
+            Err(e) => {
+                warn!(
+                    "Failed to encode envelope as MessagePack, sending JSON: {}",
+                    e
+                );
+                frame
+            }
+        }
+    }
+
+    /// JSON text of an inbound binary frame holding an envelope in this codec
+    ///
+    /// `None` for JSON, and for frames that don't decode to an envelope, which are left
+    /// to the usual binary handling.
+    pub(crate) fn decode(self, data: &[u8]) -> Option<String> {
+        let Self::Msgpack = self else {
+            return None;
+        };
+        match rmp_serde::from_slice::<Value>(data) {
+            Ok(envelope @ Value::Object(_)) => Some(envelope.to_string()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Codec::parse("JSON"), Some(Codec::Json));
+        assert_eq!(Codec::parse("msgpack"), Some(Codec::Msgpack));
+        assert_eq!(Codec::parse("cbor"), None);
+    }
+
+    #[test]
+    fn test_msgpack_round_trip() {
+        let text = r#"{"subject":"orders.new","body":"aGk=","reply_to":null}"#;
+        let Message::Binary(bytes) = Codec::Msgpack.encode(Message::Text(text.to_string())) else {
+            panic!("envelope should be reframed as binary");
+        };
+        let decoded = Codec::Msgpack.decode(&bytes).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Value>(&decoded).unwrap(),
+            serde_json::from_str::<Value>(text).unwrap()
+        );
+
+        // Only JSON objects are reframed
+        let ping = Message::Ping(vec![1]);
+        assert_eq!(Codec::Msgpack.encode(ping.clone()), ping);
+        let plain = Message::Text("not json".to_string());
+        assert_eq!(Codec::Msgpack.encode(plain.clone()), plain);
+        assert_eq!(Codec::Msgpack.decode(&[0x01]), None);
+        assert_eq!(Codec::Json.decode(&bytes), None);
+    }
+}
@@ -0,0 +1,388 @@
+use std::borrow::Cow;
+use std::io::{Read, Write};
+use std::sync::Mutex;
+
+use anyhow::{bail, Context, Result};
+use bytes::Bytes;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::connection::ConnectionConfig;
+use crate::BrokerMessage;
+
+/// Header marking a compressed body
+pub(crate) const CONTENT_ENCODING: &str = "content-encoding";
+
+/// zstd level used for bodies; favours speed since compression sits on the send path
+const ZSTD_LEVEL: i32 = 3;
+
+/// Compressed-to-original size ratio at or above which adaptive compression pauses
+const INCOMPRESSIBLE_RATIO: f64 = 0.9;
+
+/// Bodies compressed before adaptive compression may pause
+const ADAPTIVE_MIN_SAMPLES: u32 = 4;
+
+/// While paused, one eligible body in this many is compressed to re-check the ratio
+pub(crate) const ADAPTIVE_PROBE_INTERVAL: u32 = 32;
+
+/// Weight of the newest body in the running ratio
+const RATIO_WEIGHT: f64 = 0.25;
+
+/// Application-level compression applied to message bodies inside the envelope
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BodyCompression {
+    /// Bodies are sent as-is
+    #[default]
+    None,
+    /// Bodies above the threshold are zstd-compressed
+    Zstd,
+    /// Bodies above the threshold are gzip-compressed, for peers without zstd
+    Gzip,
+    /// Like `Zstd`, but paused on a connection while its bodies turn out incompressible
+    Adaptive,
+}
+
+impl BodyCompression {
+    /// Parse a `BODY_COMPRESSION` value (`none`, `zstd`, `gzip` or `adaptive`,
+    /// case-insensitive)
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "none" => Some(Self::None),
+            "zstd" => Some(Self::Zstd),
+            "gzip" => Some(Self::Gzip),
+            "adaptive" => Some(Self::Adaptive),
+            _ => None,
+        }
+    }
+}
+
+/// Per-connection state of `BODY_COMPRESSION=adaptive`
+///
+/// Keeps a running compression ratio over the bodies it compresses. Once that ratio shows
+/// the bodies are incompressible (e.g. already-compressed media), compression pauses; every
+/// `ADAPTIVE_PROBE_INTERVAL`th eligible body is still compressed to notice when
+/// compressible data flows again.
+#[derive(Debug, Default)]
+pub(crate) struct AdaptiveCompression {
+    state: Mutex<AdaptiveState>,
+}
+
+#[derive(Debug, Default)]
+struct AdaptiveState {
+    ratio: Option<f64>,
+    samples: u32,
+    paused: bool,
+    /// Eligible bodies sent uncompressed since the last probe
+    skipped: u32,
+}
+
+impl AdaptiveCompression {
+    /// Whether the next eligible body should be compressed
+    fn should_compress(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if !state.paused {
+            return true;
+        }
+        state.skipped += 1;
+        if state.skipped < ADAPTIVE_PROBE_INTERVAL {
+            return false;
+        }
+        state.skipped = 0;
+        true
+    }
+
+    /// Fold the sizes of a body compressed from `original` to `compressed` bytes into the
+    /// ratio, pausing or resuming compression as it crosses `INCOMPRESSIBLE_RATIO`
+    fn record(&self, original: usize, compressed: usize) {
+        let sample = (compressed as f64 / original.max(1) as f64).min(1.0);
+        let mut state = self.state.lock().unwrap();
+        let ratio = match state.ratio {
+            Some(ratio) => ratio + RATIO_WEIGHT * (sample - ratio),
+            None => sample,
+        };
+        state.ratio = Some(ratio);
+        state.samples = state.samples.saturating_add(1);
+
+        let incompressible = ratio >= INCOMPRESSIBLE_RATIO;
+        if !state.paused && incompressible && state.samples >= ADAPTIVE_MIN_SAMPLES {
+            debug!("Pausing body compression: ratio {:.2}", ratio);
+            state.paused = true;
+            state.skipped = 0;
+        } else if state.paused && !incompressible {
+            debug!("Resuming body compression: ratio {:.2}", ratio);
+            state.paused = false;
+        }
+    }
+
+    /// Whether eligible bodies are currently compressed, and the running ratio
+    pub(crate) fn decision(&self) -> (bool, Option<f64>) {
+        let state = self.state.lock().unwrap();
+        (!state.paused, state.ratio)
+    }
+}
+
+/// Compress the body of `msg` if the connection's config asks for it
+///
+/// Bodies at or below `COMPRESS_THRESHOLD_BYTES`, bodies that already carry a content
+/// encoding, and bodies that don't get smaller are left untouched. With `adaptive`
+/// compression, `adaptive` decides whether to try and learns from the result.
+pub(crate) fn compress<'a>(
+    msg: &'a BrokerMessage,
+    config: &ConnectionConfig,
+    adaptive: &AdaptiveCompression,
+) -> Cow<'a, BrokerMessage> {
+    if config.body_compression == BodyCompression::None
+        || msg.body.len() <= config.compress_threshold_bytes
+        || msg.headers.contains_key(CONTENT_ENCODING)
+    {
+        return Cow::Borrowed(msg);
+    }
+    let is_adaptive = config.body_compression == BodyCompression::Adaptive;
+    if is_adaptive && !adaptive.should_compress() {
+        return Cow::Borrowed(msg);
+    }
+    let (compressed, encoding) = match config.body_compression {
+        BodyCompression::Gzip => (gzip(&msg.body), "gzip"),
+        _ => (zstd::bulk::compress(&msg.body, ZSTD_LEVEL), "zstd"),
+    };
+    if is_adaptive {
+        if let Ok(compressed) = &compressed {
+            adaptive.record(msg.body.len(), compressed.len());
+        }
+    }
+    let compressed = match compressed {
+        Ok(compressed) if compressed.len() < msg.body.len() => compressed,
+        Ok(_) => return Cow::Borrowed(msg),
+        Err(e) => {
+            warn!(
+                "Failed to compress message body, sending it uncompressed: {}",
+                e
+            );
+            return Cow::Borrowed(msg);
+        }
+    };
+
+    let mut msg = msg.clone();
+    msg.body = Bytes::from(compressed);
+    msg.headers
+        .insert(CONTENT_ENCODING.to_string(), encoding.to_string());
+    Cow::Owned(msg)
+}
+
+/// gzip `body` at the default level
+fn gzip(body: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(body)?;
+    encoder.finish()
+}
+
+/// Undo `compress` on a parsed message, refusing to inflate beyond `max_bytes`
+/// (0 = no limit)
+///
+/// Messages without a `content-encoding` header pass through unchanged, so peers that
+/// don't compress interoperate as before.
+pub(crate) fn decompress(mut msg: BrokerMessage, max_bytes: usize) -> Result<BrokerMessage> {
+    let gzip = match msg.headers.get(CONTENT_ENCODING).map(String::as_str) {
+        None => return Ok(msg),
+        Some("zstd") => false,
+        Some("gzip") => true,
+        Some(other) => bail!("Unsupported content-encoding: {}", other),
+    };
+
+    let limit = if max_bytes > 0 {
+        max_bytes as u64 + 1
+    } else {
+        u64::MAX
+    };
+    let decoder: Box<dyn Read + '_> = if gzip {
+        Box::new(GzDecoder::new(msg.body.as_ref()))
+    } else {
+        Box::new(zstd::stream::read::Decoder::new(msg.body.as_ref())?)
+    };
+    let mut body = Vec::new();
+    decoder
+        .take(limit)
+        .read_to_end(&mut body)
+        .context("Failed to decompress message body")?;
+    if max_bytes > 0 && body.len() > max_bytes {
+        bail!(
+            "Decompressed body exceeds MAX_DECOMPRESSED_BYTES ({} bytes)",
+            max_bytes
+        );
+    }
+
+    msg.body = Bytes::from(body);
+    msg.headers.remove(CONTENT_ENCODING);
+    Ok(msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::WebSocketMessagingProvider;
+    use std::collections::HashMap;
+    use tokio_tungstenite::tungstenite::Message;
+
+    fn zstd_config(max_decompressed: &str) -> ConnectionConfig {
+        let map = HashMap::from([
+            ("BODY_COMPRESSION".to_string(), "zstd".to_string()),
+            ("COMPRESS_THRESHOLD_BYTES".to_string(), "64".to_string()),
+            (
+                "MAX_DECOMPRESSED_BYTES".to_string(),
+                max_decompressed.to_string(),
+            ),
+        ]);
+        ConnectionConfig::from_map(&map).unwrap()
+    }
+
+    fn message(body: Vec<u8>) -> BrokerMessage {
+        BrokerMessage {
+            subject: "data".to_string(),
+            body: Bytes::from(body),
+            reply_to: None,
+            headers: HashMap::new(),
+        }
+    }
+
+    /// Encode as the sender would and parse as the receiver would
+    fn round_trip(msg: &BrokerMessage, config: &ConnectionConfig) -> Result<BrokerMessage> {
+        let encoded = WebSocketMessagingProvider::encode_message_static(&compress(
+            msg,
+            config,
+            &AdaptiveCompression::default(),
+        ))?;
+        let Message::Text(text) = encoded else {
+            unreachable!("envelopes are text frames")
+        };
+        let parsed = WebSocketMessagingProvider::parse_message_static(&text, "s")?;
+        decompress(parsed, config.max_decompressed_bytes)
+    }
+
+    /// Bytes that zstd can't shrink
+    fn noise(len: usize) -> Vec<u8> {
+        let mut state: u64 = 0x9e3779b97f4a7c15;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_compressible_body_round_trips() {
+        let config = zstd_config("1048576");
+        let body = br#"{"reading":42,"unit":"celsius"}"#.repeat(100);
+        let msg = message(body.clone());
+
+        let compressed = compress(&msg, &config, &AdaptiveCompression::default());
+        assert!(compressed.body.len() < body.len());
+        assert_eq!(compressed.headers[CONTENT_ENCODING], "zstd");
+
+        let parsed = round_trip(&msg, &config).unwrap();
+        assert_eq!(parsed.body, Bytes::from(body));
+        assert!(!parsed.headers.contains_key(CONTENT_ENCODING));
+    }
+
+    #[test]
+    fn test_gzip_body_round_trips() {
+        let mut config = zstd_config("1048576");
+        config.body_compression = BodyCompression::Gzip;
+        let body = br#"{"reading":42,"unit":"celsius"}"#.repeat(100);
+        let msg = message(body.clone());
+
+        let compressed = compress(&msg, &config, &AdaptiveCompression::default());
+        assert!(compressed.body.len() < body.len());
+        assert_eq!(compressed.headers[CONTENT_ENCODING], "gzip");
+
+        let parsed = round_trip(&msg, &config).unwrap();
+        assert_eq!(parsed.body, Bytes::from(body));
+        assert!(!parsed.headers.contains_key(CONTENT_ENCODING));
+    }
+
+    #[test]
+    fn test_incompressible_body_sent_as_is() {
+        let config = zstd_config("1048576");
+        let msg = message(noise(4096));
+        assert!(matches!(
+            compress(&msg, &config, &AdaptiveCompression::default()),
+            Cow::Borrowed(_)
+        ));
+    }
+
+    #[test]
+    fn test_decompression_bomb_rejected() {
+        let sender = zstd_config("104857600");
+        let receiver = zstd_config("65536");
+        let msg = message(vec![0; 8 << 20]);
+
+        let Cow::Owned(compressed) = compress(&msg, &sender, &AdaptiveCompression::default())
+        else {
+            panic!("zeros should compress");
+        };
+        let encoded = WebSocketMessagingProvider::encode_message_static(&compressed).unwrap();
+        let Message::Text(text) = encoded else {
+            unreachable!("envelopes are text frames")
+        };
+        let parsed = WebSocketMessagingProvider::parse_message_static(&text, "s").unwrap();
+        let err = decompress(parsed, receiver.max_decompressed_bytes).unwrap_err();
+        assert!(
+            err.to_string().contains("MAX_DECOMPRESSED_BYTES"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_uncompressed_peer_interop() {
+        // A peer that doesn't compress sends plain bodies, which pass through untouched
+        let parsed = WebSocketMessagingProvider::parse_message_static(
+            r#"{"subject":"data","body":"plain"}"#,
+            "s",
+        )
+        .unwrap();
+        let msg = decompress(parsed, 1024).unwrap();
+        assert_eq!(msg.body, Bytes::from("plain"));
+
+        // Small bodies stay uncompressed, so such a peer can read them
+        let config = zstd_config("1048576");
+        let small = message(b"tiny".to_vec());
+        assert!(matches!(
+            compress(&small, &config, &AdaptiveCompression::default()),
+            Cow::Borrowed(_)
+        ));
+    }
+
+    #[test]
+    fn test_adaptive_pauses_on_incompressible_bodies() {
+        let mut config = zstd_config("1048576");
+        config.body_compression = BodyCompression::Adaptive;
+        let adaptive = AdaptiveCompression::default();
+
+        for _ in 0..ADAPTIVE_MIN_SAMPLES {
+            assert!(adaptive.decision().0);
+            compress(&message(noise(4096)), &config, &adaptive);
+        }
+        let (compressing, ratio) = adaptive.decision();
+        assert!(!compressing);
+        assert!(ratio.unwrap() >= INCOMPRESSIBLE_RATIO);
+
+        // Compressible bodies go out as-is until the next probe, which resumes compression
+        let text = br#"{"reading":42,"unit":"celsius"}"#.repeat(100);
+        for _ in 1..ADAPTIVE_PROBE_INTERVAL {
+            let msg = message(text.clone());
+            assert!(matches!(
+                compress(&msg, &config, &adaptive),
+                Cow::Borrowed(_)
+            ));
+        }
+        let msg = message(text);
+        assert!(matches!(compress(&msg, &config, &adaptive), Cow::Owned(_)));
+        assert!(adaptive.decision().0);
+    }
+}
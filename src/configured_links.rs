@@ -0,0 +1,115 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::{bail, Result};
+
+use crate::connection::configured_link_key;
+use crate::link_status::LinkRole;
+
+/// A client link declared in the provider's own config with `LINK_<name>_<KEY>` keys,
+/// started by `start_configured_links` as if component `<name>` had linked
+#[derive(Clone)]
+pub(crate) struct ConfiguredLink {
+    /// Stands in for the component id
+    pub(crate) name: String,
+    pub(crate) role: LinkRole,
+    /// The link config, keys without the `LINK_<name>_` prefix and `ROLE`
+    pub(crate) config: HashMap<String, String>,
+}
+
+/// Collect the links declared in `config`, by name
+///
+/// `LINK_<name>_ROLE` is `handler` (the default) or `consumer`, and every link needs a
+/// `LINK_<name>_URI`.
+pub(crate) fn parse(config: &HashMap<String, String>) -> Result<Vec<ConfiguredLink>> {
+    let mut links: BTreeMap<&str, HashMap<String, String>> = BTreeMap::new();
+    for (key, value) in config {
+        if let Some((name, key)) = configured_link_key(key) {
+            links
+                .entry(name)
+                .or_default()
+                .insert(key.to_string(), value.clone());
+        }
+    }
+
+    links
+        .into_iter()
+        .map(|(name, mut config)| {
+            let role = match config
+                .remove("ROLE")
+                .map(|role| role.to_lowercase())
+                .as_deref()
+            {
+                None | Some("handler") => LinkRole::Handler,
+                Some("consumer") => LinkRole::Consumer,
+                Some(other) => bail!(
+                    "LINK_{}_ROLE must be handler or consumer, got {}",
+                    name,
+                    other
+                ),
+            };
+            if !config.contains_key("URI") {
+                bail!("LINK_{}_URI is required", name);
+            }
+            Ok(ConfiguredLink {
+                name: name.to_string(),
+                role,
+                config,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_links_grouped_by_name() {
+        let links = parse(&config(&[
+            ("URI", "ws://default:8080"),
+            ("LINK_orders_URI", "ws://orders:8080"),
+            ("LINK_orders_SUBSCRIPTIONS", "orders.>"),
+            ("LINK_orders_CONNECT_TIMEOUT_SEC", "5"),
+            ("LINK_audit_URI", "ws://audit:8080"),
+            ("LINK_audit_ROLE", "Consumer"),
+        ]))
+        .unwrap();
+
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].name, "audit");
+        assert_eq!(links[0].role, LinkRole::Consumer);
+        assert_eq!(links[0].config, config(&[("URI", "ws://audit:8080")]));
+        assert_eq!(links[1].name, "orders");
+        assert_eq!(links[1].role, LinkRole::Handler);
+        assert_eq!(links[1].config["CONNECT_TIMEOUT_SEC"], "5");
+        assert_eq!(links[1].config.len(), 3);
+    }
+
+    #[test]
+    fn test_invalid_links_rejected() {
+        let error = parse(&config(&[("LINK_orders_SUBSCRIPTIONS", "orders.>")]))
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("LINK_orders_URI"), "{}", error);
+
+        let error = parse(&config(&[
+            ("LINK_orders_URI", "ws://orders:8080"),
+            ("LINK_orders_ROLE", "both"),
+        ]))
+        .err()
+        .unwrap();
+        assert!(error.to_string().contains("LINK_orders_ROLE"), "{}", error);
+
+        // No name or no key isn't a link key
+        assert!(parse(&config(&[("LINK__URI", "x"), ("LINK_orders", "x")]))
+            .unwrap()
+            .is_empty());
+    }
+}
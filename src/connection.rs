@@ -1,7 +1,27 @@
 use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::budget::SessionBufferPolicy;
+use crate::byte_array::ByteArrayPolicy;
+use crate::codec::Codec;
+use crate::compression::BodyCompression;
+use crate::duration::{self, DurationConfig};
+use crate::failover::{self, FailoverStrategy};
+use crate::handoff::HandoffPlan;
+use crate::legacy_hex::LegacyHexPolicy;
+use crate::parse_guard::ParseFailurePolicy;
+use crate::platform;
+use crate::proxy::{self, Proxy};
+use crate::rate_limit::ServerRatePolicy;
+use crate::routing::{HandlerOverflow, RouteDelivery};
+use crate::subject::{self, parse_pattern_list, subject_matches};
+use crate::tenant;
+use crate::transport::{handshake_header, AuthScheme};
 
 /// Connection mode for the provider
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
@@ -15,115 +35,2536 @@ pub enum ConnectionMode {
 }
 
 /// Configuration for WebSocket connections
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 pub struct ConnectionConfig {
     /// Connection mode: client or server
     #[serde(default)]
     pub mode: ConnectionMode,
 
-    /// WebSocket server URI for client mode (e.g., "ws://localhost:8080" or "wss://example.com/ws")
+    /// WebSocket server URI for client mode (e.g., "ws://localhost:8080" or "wss://example.com/ws"),
+    /// or a comma-separated list of them to fail over between
     /// Or bind address for server mode (e.g., "0.0.0.0:8080")
     #[serde(default = "default_uri")]
     pub uri: String,
 
-    /// Optional authentication token to send with connection (client mode)
-    #[serde(default)]
-    pub auth_token: Option<String>,
+    /// Which URI of a `URI` list is dialled first (client mode)
+    #[serde(default)]
+    pub failover_strategy: FailoverStrategy,
+
+    /// Optional authentication token to send with connection (client mode)
+    #[serde(default)]
+    pub auth_token: Option<String>,
+
+    /// How the auth token is sent: as a bearer or basic `Authorization` header, or as-is
+    /// in a named header
+    #[serde(default)]
+    pub auth_scheme: AuthScheme,
+
+    /// Key for signing outbound envelopes with HMAC-SHA256 and verifying inbound ones
+    /// (unset = no signing)
+    #[serde(default)]
+    pub message_hmac_key: Option<String>,
+
+    /// Accept inbound messages without a signature while `message_hmac_key` is set
+    #[serde(default)]
+    pub message_hmac_allow_unsigned: bool,
+
+    /// Timeout for connection
+    #[serde(default = "default_connect_timeout")]
+    pub connect_timeout: Duration,
+
+    /// Idle time before TCP keepalive probes are sent on client connections (0 = OS default)
+    #[serde(default)]
+    pub tcp_keepalive: Duration,
+
+    /// HTTP (`http://`) or SOCKS5 (`socks5://`) proxy client connections are tunnelled
+    /// through
+    #[serde(default)]
+    pub proxy_url: Option<String>,
+
+    /// PEM file of CA certificates trusted for `wss://` connections besides the built-in
+    /// web roots
+    #[serde(default)]
+    pub tls_ca_file: Option<String>,
+
+    /// PEM certificate chain presented to `wss://` servers asking for a client certificate
+    #[serde(default)]
+    pub tls_client_cert_file: Option<String>,
+
+    /// PEM private key of `tls_client_cert_file`
+    #[serde(default)]
+    pub tls_client_key_file: Option<String>,
+
+    /// Accept any server certificate on `wss://` connections (testing only)
+    #[serde(default)]
+    pub tls_insecure_skip_verify: bool,
+
+    /// PEM certificate chain the server presents, serving `wss://` (server mode)
+    #[serde(default)]
+    pub tls_cert_path: Option<String>,
+
+    /// PEM private key of `tls_cert_path`
+    #[serde(default)]
+    pub tls_key_path: Option<String>,
+
+    /// Subprotocols offered in `Sec-WebSocket-Protocol`, in order of preference
+    #[serde(default)]
+    pub subprotocols: Vec<String>,
+
+    /// Redial a client connection that drops instead of leaving the link without one
+    #[serde(default)]
+    pub reconnect: bool,
+
+    /// Redial attempts per outage before the link is given up (0 = no limit)
+    #[serde(default = "default_reconnect_max_retries")]
+    pub reconnect_max_retries: u32,
+
+    /// Delay before the first redial, doubled after every failed attempt
+    #[serde(default = "default_reconnect_base_delay")]
+    pub reconnect_base_delay: Duration,
+
+    /// Frames queued before a redial and older than this are dropped instead of written
+    /// on the new connection (zero = keep them all)
+    #[serde(default)]
+    pub reconnect_buffer_max_age: Duration,
+
+    /// Enable session tracking
+    #[serde(default = "default_session_tracking")]
+    pub enable_session_tracking: bool,
+
+    /// Custom headers to send with WebSocket upgrade request (client mode)
+    #[serde(default)]
+    pub custom_headers: HashMap<String, String>,
+
+    /// Maximum number of inbound messages forwarded to handlers as one batch (1 disables batching)
+    #[serde(default = "default_handler_batch_size")]
+    pub handler_batch_size: usize,
+
+    /// Maximum time a batch waits for more messages before it is flushed
+    #[serde(default = "default_handler_batch_window")]
+    pub handler_batch_window: Duration,
+
+    /// Subject patterns whose last message is retained and replayed to new clients (server mode)
+    #[serde(default)]
+    pub retain_subjects: Vec<String>,
+
+    /// Time a retained message stays valid (0 = never expires)
+    #[serde(default)]
+    pub retain_ttl: Duration,
+
+    /// Clear a retained subject when an empty-body message is published on it
+    #[serde(default)]
+    pub retain_clear_on_empty: bool,
+
+    /// Maximum number of retained subjects kept at once
+    #[serde(default = "default_retain_max_entries")]
+    pub retain_max_entries: usize,
+
+    /// Consecutive unparseable frames from a session before its frames are skipped (0 = off)
+    #[serde(default = "default_parse_fail_threshold")]
+    pub parse_fail_threshold: u32,
+
+    /// How long frames are skipped once the threshold is reached
+    #[serde(default = "default_parse_fail_cooldown")]
+    pub parse_fail_cooldown: Duration,
+
+    /// Consecutive unparseable frames after which the session is disconnected (0 = never)
+    #[serde(default)]
+    pub parse_fail_disconnect_threshold: u32,
+
+    /// Subject patterns a handler link receives (empty = every subject)
+    #[serde(default)]
+    pub subscriptions: Vec<String>,
+
+    /// Priority of a handler link when several match a subject (higher wins)
+    #[serde(default)]
+    pub route_priority: i32,
+
+    /// Whether inbound messages go to all matching handlers or only the first
+    #[serde(default)]
+    pub route_delivery: RouteDelivery,
+
+    /// Tenant whose clients a handler link receives, `*` for tenants no other handler
+    /// declares (server mode)
+    #[serde(default)]
+    pub tenant: Option<String>,
+
+    /// Upgrade query parameter naming a client's tenant (server mode)
+    #[serde(default = "default_tenant_param")]
+    pub tenant_param: String,
+
+    /// Bearer token of each tenant; when set, a client's tenant is the one its token was
+    /// given to (server mode)
+    #[serde(default)]
+    pub tenant_tokens: HashMap<String, String>,
+
+    /// Clients sent a reconnect frame per handoff wave (server mode)
+    #[serde(default = "default_handoff_batch_size")]
+    pub handoff_batch_size: usize,
+
+    /// Pause between handoff waves
+    #[serde(default = "default_handoff_batch_interval")]
+    pub handoff_batch_interval: Duration,
+
+    /// How long a client has to acknowledge its reconnect frame
+    #[serde(default = "default_handoff_ack_timeout")]
+    pub handoff_ack_timeout: Duration,
+
+    /// Field of the first inbound message carrying the server's session id (client mode)
+    #[serde(default)]
+    pub server_session_field: String,
+
+    /// Upgrade response header carrying the server's session id (client mode)
+    #[serde(default)]
+    pub server_session_header: String,
+
+    /// Prefix of request-reply inbox subjects, reserved for the provider
+    #[serde(default = "default_inbox_prefix")]
+    pub inbox_prefix: String,
+
+    /// Let the linked component publish on reserved subjects (`$SYS.`, inbox prefix)
+    #[serde(default)]
+    pub allow_reserved_subjects: bool,
+
+    /// Send `$SYS.drop` notifications back over this connection for messages it
+    /// delivered that were dropped
+    #[serde(default)]
+    pub drop_notifications: bool,
+
+    /// Dispatch `$SYS.flow.pause` to the linked component when its outbound queue fills
+    /// past the high watermark, and `$SYS.flow.resume` once it drains below the low one
+    #[serde(default)]
+    pub flow_signals: bool,
+
+    /// Subject patterns the linked component may publish on (empty = any)
+    #[serde(default)]
+    pub publish_allow: Vec<String>,
+
+    /// Subject patterns the linked component may never publish on
+    #[serde(default)]
+    pub publish_deny: Vec<String>,
+
+    /// Subject patterns the remote server may push to handlers (empty = any not denied)
+    #[serde(default)]
+    pub inbound_allow: Vec<String>,
+
+    /// Subject patterns pushed by the remote server that are always dropped
+    #[serde(default)]
+    pub inbound_deny: Vec<String>,
+
+    /// Log each inbound message dropped by `INBOUND_ALLOW` / `INBOUND_DENY`
+    #[serde(default)]
+    pub log_inbound_denied: bool,
+
+    /// Subject patterns anything sent on the connection may use (empty = any not denied)
+    #[serde(default)]
+    pub outbound_allow: Vec<String>,
+
+    /// Subject patterns nothing may be sent on over the connection
+    #[serde(default)]
+    pub outbound_deny: Vec<String>,
+
+    /// Messages queued per connection before it counts as full
+    #[serde(default = "default_outbound_queue_capacity")]
+    pub outbound_queue_capacity: usize,
+
+    /// Longest a server-mode message sink may take to accept a message
+    #[serde(default = "default_delivery_timeout")]
+    pub delivery_timeout: Duration,
+
+    /// Per-subject overrides of `delivery_timeout` as `(pattern, timeout)`, first match wins
+    #[serde(default)]
+    pub handler_timeouts: Vec<(String, Duration)>,
+
+    /// Messages buffered in server mode until a message sink is installed
+    #[serde(default = "default_message_buffer_capacity")]
+    pub message_buffer_capacity: usize,
+
+    /// Largest encoded outbound message accepted for sending (0 = no limit)
+    #[serde(default = "default_max_message_bytes")]
+    pub max_message_bytes: usize,
+
+    /// Largest request body accepted on a server-mode upgrade request (0 = no limit)
+    #[serde(default = "default_max_upgrade_body_bytes")]
+    pub max_upgrade_body_bytes: usize,
+
+    /// Largest total size of an upgrade request's headers in server mode (0 = no limit)
+    #[serde(default = "default_max_upgrade_header_bytes")]
+    pub max_upgrade_header_bytes: usize,
+
+    /// Time between the janitor's reconciliation passes (0 = no janitor)
+    #[serde(default = "default_reconcile_interval")]
+    pub reconcile_interval: Duration,
+
+    /// Background job intervals by lowercase job name, from `JOB_<NAME>_INTERVAL`
+    /// (0 = only run on demand)
+    #[serde(default)]
+    pub job_intervals: HashMap<String, Duration>,
+
+    /// Groups a server-mode session may be in at once (0 = no limit)
+    #[serde(default = "default_max_groups_per_session")]
+    pub max_groups_per_session: usize,
+
+    /// Members a server-mode group may have (0 = no limit)
+    #[serde(default = "default_max_group_size")]
+    pub max_group_size: usize,
+
+    /// Groups that may exist at once across all server-mode sessions (0 = no limit)
+    #[serde(default)]
+    pub max_groups: usize,
+
+    /// Metadata entries a server-mode session may hold (0 = no limit)
+    #[serde(default = "default_max_metadata_entries")]
+    pub max_metadata_entries: usize,
+
+    /// Bytes a session metadata key may have (0 = no limit)
+    #[serde(default = "default_max_metadata_key_len")]
+    pub max_metadata_key_len: usize,
+
+    /// Bytes a session metadata value may have (0 = no limit)
+    #[serde(default = "default_max_metadata_value_len")]
+    pub max_metadata_value_len: usize,
+
+    /// Bytes that frames queued for server clients may take up before broadcasts are
+    /// shed (0 = no limit)
+    #[serde(default)]
+    pub server_memory_budget_bytes: usize,
+
+    /// Bytes one server client may have buffered across its outbound queue, pending
+    /// body-stream chunks and the startup message buffer (0 = no limit)
+    #[serde(default)]
+    pub max_session_buffer_bytes: usize,
+
+    /// What happens to a server client that reaches `max_session_buffer_bytes`
+    #[serde(default)]
+    pub session_buffer_policy: SessionBufferPolicy,
+
+    /// Inbound messages per second all server clients together may deliver to the
+    /// message sink (0 = no limit)
+    #[serde(default)]
+    pub server_max_messages_per_sec: u64,
+
+    /// What happens to server messages beyond `server_max_messages_per_sec`
+    #[serde(default)]
+    pub server_rate_policy: ServerRatePolicy,
+
+    /// Extra attempts at binding the server's listener while its address is in use
+    #[serde(default)]
+    pub bind_retry_attempts: u32,
+
+    /// Delay between bind attempts
+    #[serde(default = "default_bind_retry_delay")]
+    pub bind_retry_delay: Duration,
+
+    /// Set `SO_REUSEADDR` on the server's listener
+    #[serde(default)]
+    pub bind_reuse_addr: bool,
+
+    /// Bind the server's listener again when its task stops while the provider is running
+    #[serde(default)]
+    pub server_restart: bool,
+
+    /// Delay before the first rebind after the server stops, doubled after each failed
+    /// attempt
+    #[serde(default = "default_server_restart_delay")]
+    pub server_restart_delay: Duration,
+
+    /// Percentage of `outbound_queue_capacity` frames a server client's queue must stay
+    /// above to count as a slow consumer
+    #[serde(default = "default_slow_consumer_high_water_pct")]
+    pub slow_consumer_high_water_pct: u32,
+
+    /// How long a server client's queue must stay above the high-water mark before
+    /// `SlowConsumer` is raised (0 = no detection)
+    #[serde(default)]
+    pub slow_consumer_after: Duration,
+
+    /// Send each server client a welcome frame with its session id and the server's limits
+    #[serde(default)]
+    pub welcome_frame: bool,
+
+    /// Dispatch `$SYS.session.connected` before a server client's first message and
+    /// `$SYS.session.disconnected` after its last
+    #[serde(default)]
+    pub session_events: bool,
+
+    /// Heartbeat interval: the most a server client may propose, advertised in the welcome
+    /// frame; a client connection's own proposal and ping interval (0 = none)
+    #[serde(default)]
+    pub heartbeat_interval: Duration,
+
+    /// Silence after which a server client is closed with `IdleTimeout`, scaled per
+    /// session to its negotiated heartbeat interval (0 = never)
+    #[serde(default)]
+    pub idle_timeout: Duration,
+
+    /// Time between keepalive pings on a client connection when `heartbeat_interval` is
+    /// unset (0 = none)
+    #[serde(default)]
+    pub ping_interval: Duration,
+
+    /// How long a client connection waits for the pong to a ping before it is treated as
+    /// dead (0 = wait forever)
+    #[serde(default = "default_pong_timeout")]
+    pub pong_timeout: Duration,
+
+    /// Bytes of a body stream kept for a subscriber that hasn't resolved it yet
+    #[serde(default = "default_body_stream_prefix_bytes")]
+    pub body_stream_prefix_bytes: usize,
+
+    /// Subject tokens kept in the `subject` label of message metrics (0 = no subject)
+    #[serde(default = "default_metric_subject_depth")]
+    pub metric_subject_depth: usize,
+
+    /// Label sets each message metric may have before new ones are counted under
+    /// `subject="other"` (0 = no limit)
+    #[serde(default = "default_metric_max_series")]
+    pub metric_max_series: usize,
+
+    /// Label message metrics with the component or session as well
+    #[serde(default)]
+    pub high_cardinality_metrics: bool,
+
+    /// How long a body stream with a full prefix waits for a subscriber before it is
+    /// cancelled
+    #[serde(default = "default_body_stream_subscribe_timeout")]
+    pub body_stream_subscribe_timeout: Duration,
+
+    /// What to do when a handler's outbound queue is full during a broadcast
+    #[serde(default)]
+    pub handler_overflow: HandlerOverflow,
+
+    /// How many handler sends may wait for queue room at once under
+    /// `HANDLER_OVERFLOW=wait`, without holding up the receive loop (0 = wait inline)
+    #[serde(default)]
+    pub handler_concurrency: usize,
+
+    /// What happens to out-of-range elements of an inbound JSON byte-array body
+    #[serde(default)]
+    pub byte_array_policy: ByteArrayPolicy,
+
+    /// Whether inbound string bodies that look hex-encoded are decoded as hex
+    #[serde(default)]
+    pub legacy_hex_bodies: LegacyHexPolicy,
+
+    /// Reject an inbound string body whose envelope doesn't name its `body_encoding`
+    #[serde(default)]
+    pub require_body_encoding: bool,
+
+    /// Bytes an inbound envelope's subject may have (0 = no limit)
+    #[serde(default = "default_max_subject_len")]
+    pub max_subject_len: usize,
+
+    /// Bytes an inbound envelope's reply_to may have (0 = no limit)
+    #[serde(default = "default_max_reply_to_len")]
+    pub max_reply_to_len: usize,
+
+    /// Inbound pipeline stages to run, in order (empty = `decode` plus custom stages)
+    #[serde(default)]
+    pub inbound_stages: Vec<String>,
+
+    /// How long the `dedup` stage remembers a message id
+    #[serde(default = "default_dedup_window")]
+    pub dedup_window: Duration,
+
+    /// Wire format of the envelopes on a client connection
+    #[serde(default)]
+    pub codec: Codec,
+
+    /// Offer the `permessage-deflate` extension to compress a client connection's frames
+    #[serde(default)]
+    pub permessage_deflate: bool,
+
+    /// Compression applied to outbound message bodies
+    #[serde(default)]
+    pub body_compression: BodyCompression,
+
+    /// Bodies at or below this size are never compressed
+    #[serde(default = "default_compress_threshold_bytes")]
+    pub compress_threshold_bytes: usize,
+
+    /// Largest body a compressed inbound message may inflate to (0 = no limit)
+    #[serde(default = "default_max_decompressed_bytes")]
+    pub max_decompressed_bytes: usize,
+
+    /// How long frames in the previous envelope version are still accepted after an
+    /// in-band upgrade
+    #[serde(default = "default_envelope_upgrade_grace")]
+    pub envelope_upgrade_grace: Duration,
+
+    /// File client connections append their frames to as NDJSON (empty = no capture)
+    #[serde(default)]
+    pub capture_path: String,
+
+    /// File `shutdown` appends messages it would otherwise drop to as NDJSON (empty = none)
+    #[serde(default)]
+    pub shutdown_spill_path: String,
+
+    /// Label (e.g. a tenant) attached to the connection's spans and metrics
+    #[serde(default)]
+    pub connection_label: String,
+
+    /// Accept server-mode links whose server-level keys differ from the running server
+    #[serde(default)]
+    pub allow_server_override: bool,
+}
+
+/// Placeholder reported instead of secret configuration values
+pub(crate) const REDACTED: &str = "[REDACTED]";
+
+/// Keys understood by `ConnectionConfig::from_map`, besides the `HEADER_*` prefix and
+/// `JOB_<NAME>_INTERVAL`
+const CONFIG_KEYS: &[&str] = &[
+    "MODE",
+    "URI",
+    "FAILOVER_STRATEGY",
+    "AUTH_TOKEN",
+    "AUTH_SCHEME",
+    "MESSAGE_HMAC_KEY",
+    "MESSAGE_HMAC_ALLOW_UNSIGNED",
+    "CONNECT_TIMEOUT",
+    "CONNECT_TIMEOUT_SEC",
+    "TCP_KEEPALIVE",
+    "TCP_KEEPALIVE_SEC",
+    "PROXY_URL",
+    "TLS_CA_FILE",
+    "TLS_CLIENT_CERT_FILE",
+    "TLS_CLIENT_KEY_FILE",
+    "TLS_INSECURE_SKIP_VERIFY",
+    "TLS_CERT_PATH",
+    "TLS_KEY_PATH",
+    "SUBPROTOCOLS",
+    "RECONNECT",
+    "RECONNECT_MAX_RETRIES",
+    "RECONNECT_BASE_DELAY",
+    "RECONNECT_BASE_DELAY_MS",
+    "RECONNECT_BUFFER_MAX_AGE",
+    "RECONNECT_BUFFER_MAX_AGE_MS",
+    "ENABLE_SESSION_TRACKING",
+    "HANDLER_BATCH_SIZE",
+    "HANDLER_BATCH_WINDOW",
+    "HANDLER_BATCH_WINDOW_MS",
+    "RETAIN_SUBJECTS",
+    "RETAIN_TTL",
+    "RETAIN_TTL_SEC",
+    "RETAIN_CLEAR_ON_EMPTY",
+    "RETAIN_MAX_ENTRIES",
+    "PARSE_FAIL_THRESHOLD",
+    "PARSE_FAIL_COOLDOWN",
+    "PARSE_FAIL_COOLDOWN_MS",
+    "PARSE_FAIL_DISCONNECT_THRESHOLD",
+    "SUBSCRIPTIONS",
+    "ROUTE_PRIORITY",
+    "ROUTE_DELIVERY",
+    "TENANT",
+    "TENANT_PARAM",
+    "TENANT_TOKENS",
+    "HANDOFF_BATCH_SIZE",
+    "HANDOFF_BATCH_INTERVAL",
+    "HANDOFF_BATCH_INTERVAL_MS",
+    "HANDOFF_ACK_TIMEOUT",
+    "HANDOFF_ACK_TIMEOUT_MS",
+    "SERVER_SESSION_FIELD",
+    "SERVER_SESSION_HEADER",
+    "INBOX_PREFIX",
+    "ALLOW_RESERVED_SUBJECTS",
+    "DROP_NOTIFICATIONS",
+    "FLOW_SIGNALS",
+    "PUBLISH_ALLOW",
+    "PUBLISH_DENY",
+    "INBOUND_ALLOW",
+    "INBOUND_DENY",
+    "LOG_INBOUND_DENIED",
+    "OUTBOUND_ALLOW",
+    "OUTBOUND_DENY",
+    "OUTBOUND_QUEUE_CAPACITY",
+    "MAX_MESSAGE_BYTES",
+    "MAX_UPGRADE_BODY_BYTES",
+    "MAX_UPGRADE_HEADER_BYTES",
+    "MAX_GROUPS_PER_SESSION",
+    "MAX_GROUP_SIZE",
+    "MAX_GROUPS",
+    "MAX_METADATA_ENTRIES",
+    "MAX_METADATA_KEY_LEN",
+    "MAX_METADATA_VALUE_LEN",
+    "SERVER_MEMORY_BUDGET_BYTES",
+    "MAX_SESSION_BUFFER_BYTES",
+    "SESSION_BUFFER_POLICY",
+    "SERVER_MAX_MESSAGES_PER_SEC",
+    "SERVER_RATE_POLICY",
+    "BIND_RETRY_ATTEMPTS",
+    "BIND_RETRY_DELAY",
+    "BIND_RETRY_DELAY_MS",
+    "BIND_REUSE_ADDR",
+    "SERVER_RESTART",
+    "SERVER_RESTART_DELAY",
+    "SERVER_RESTART_DELAY_MS",
+    "SLOW_CONSUMER_HIGH_WATER_PCT",
+    "SLOW_CONSUMER_AFTER",
+    "SLOW_CONSUMER_AFTER_MS",
+    "WELCOME_FRAME",
+    "SESSION_EVENTS",
+    "HEARTBEAT_INTERVAL",
+    "HEARTBEAT_INTERVAL_MS",
+    "IDLE_TIMEOUT",
+    "IDLE_TIMEOUT_MS",
+    "PING_INTERVAL",
+    "PING_INTERVAL_SEC",
+    "PONG_TIMEOUT",
+    "PONG_TIMEOUT_SEC",
+    "BODY_STREAM_PREFIX_BYTES",
+    "METRIC_SUBJECT_DEPTH",
+    "METRIC_MAX_SERIES",
+    "HIGH_CARDINALITY_METRICS",
+    "BODY_STREAM_SUBSCRIBE_TIMEOUT",
+    "BODY_STREAM_SUBSCRIBE_TIMEOUT_MS",
+    "RECONCILE_INTERVAL",
+    "RECONCILE_INTERVAL_SEC",
+    "DELIVERY_TIMEOUT",
+    "DELIVERY_TIMEOUT_MS",
+    "HANDLER_TIMEOUT_MS",
+    "MESSAGE_BUFFER_CAPACITY",
+    "HANDLER_OVERFLOW",
+    "HANDLER_CONCURRENCY",
+    "BYTE_ARRAY_POLICY",
+    "LEGACY_HEX_BODIES",
+    "REQUIRE_BODY_ENCODING",
+    "MAX_SUBJECT_LEN",
+    "MAX_REPLY_TO_LEN",
+    "INBOUND_STAGES",
+    "DEDUP_WINDOW",
+    "DEDUP_WINDOW_MS",
+    "CODEC",
+    "PERMESSAGE_DEFLATE",
+    "BODY_COMPRESSION",
+    "COMPRESS_THRESHOLD_BYTES",
+    "MAX_DECOMPRESSED_BYTES",
+    "ENVELOPE_UPGRADE_GRACE",
+    "ENVELOPE_UPGRADE_GRACE_MS",
+    "CAPTURE_PATH",
+    "SHUTDOWN_SPILL_PATH",
+    "LABEL",
+    "ALLOW_SERVER_OVERRIDE",
+    "STRICT_CONFIG",
+];
+
+/// Job name of a `JOB_<NAME>_INTERVAL` or `JOB_<NAME>_INTERVAL_SEC` key
+fn job_interval_name(key: &str) -> Option<&str> {
+    let key = key.strip_prefix("JOB_")?;
+    key.strip_suffix("_INTERVAL")
+        .or_else(|| key.strip_suffix("_INTERVAL_SEC"))
+        .filter(|name| !name.is_empty())
+}
+
+/// The entries of a comma-separated `SUBPROTOCOLS` value, each of which must be an HTTP
+/// token
+fn parse_subprotocols(value: &str) -> Result<Vec<String>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|protocol| !protocol.is_empty())
+        .map(|protocol| {
+            let is_token = protocol
+                .bytes()
+                .all(|b| b.is_ascii_graphic() && !b"()<>@,;:\\\"/[]?={}".contains(&b));
+            if !is_token {
+                bail!(
+                    "SUBPROTOCOLS entry {:?} is not a valid protocol name",
+                    protocol
+                );
+            }
+            Ok(protocol.to_string())
+        })
+        .collect()
+}
+
+/// Link name and key of a `LINK_<name>_<KEY>` key; names can't contain `_`
+pub(crate) fn configured_link_key(key: &str) -> Option<(&str, &str)> {
+    let (name, key) = key.strip_prefix("LINK_")?.split_once('_')?;
+    (!name.is_empty() && !key.is_empty()).then_some((name, key))
+}
+
+/// Old key names still accepted, with the key that replaced them
+const DEPRECATED_KEYS: &[(&str, &str)] =
+    &[("WS_URI", "URI"), ("TIMEOUT_SEC", "CONNECT_TIMEOUT_SEC")];
+
+/// Old key whose replacement depends on its value
+const COMPRESSION: &str = "COMPRESSION";
+
+/// The key and value a deprecated `COMPRESSION` value stands for: body compression for
+/// `zstd` and `gzip`, the frame extension for `permessage-deflate`
+fn compression_alias(value: &str) -> Option<(&'static str, String)> {
+    match value.to_lowercase().as_str() {
+        "none" => Some(("PERMESSAGE_DEFLATE", "false".to_string())),
+        "permessage-deflate" => Some(("PERMESSAGE_DEFLATE", "true".to_string())),
+        body @ ("zstd" | "gzip") => Some(("BODY_COMPRESSION", body.to_string())),
+        _ => None,
+    }
+}
+
+/// The current name of `key` set to `value`, following a deprecated alias
+fn canonical_key<'a>(key: &'a str, value: &str) -> &'a str {
+    if key == COMPRESSION {
+        return compression_alias(value).map_or(key, |(new, _)| new);
+    }
+    DEPRECATED_KEYS
+        .iter()
+        .find(|(old, _)| *old == key)
+        .map_or(key, |(_, new)| new)
+}
+
+/// Keys of a config map that `from_map` doesn't take as given
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigReport {
+    /// Keys that are neither known nor deprecated aliases; they are ignored
+    pub unknown: Vec<String>,
+    /// Deprecated keys in use, each with the key that replaces it
+    pub deprecated: Vec<(String, String)>,
+}
+
+impl ConfigReport {
+    /// Whether the map only uses current keys
+    pub fn is_clean(&self) -> bool {
+        self.unknown.is_empty() && self.deprecated.is_empty()
+    }
+}
+
+/// Settings a client connection reads for every message rather than when it connects,
+/// by field name; a re-delivered link changing only these keeps its connection
+const IN_PLACE_FIELDS: &[&str] = &[
+    "subscriptions",
+    "route_priority",
+    "tenant",
+    "publish_allow",
+    "publish_deny",
+    "outbound_allow",
+    "outbound_deny",
+    "allow_reserved_subjects",
+    "inbox_prefix",
+    "max_message_bytes",
+    "body_compression",
+    "compress_threshold_bytes",
+];
+
+/// Keys configuring the single server-mode listener, which can't differ per link
+const SERVER_KEYS: &[&str] = &[
+    "RETAIN_SUBJECTS",
+    "RETAIN_TTL_SEC",
+    "RETAIN_TTL",
+    "RETAIN_CLEAR_ON_EMPTY",
+    "RETAIN_MAX_ENTRIES",
+    "PARSE_FAIL_THRESHOLD",
+    "PARSE_FAIL_COOLDOWN_MS",
+    "PARSE_FAIL_COOLDOWN",
+    "PARSE_FAIL_DISCONNECT_THRESHOLD",
+    "HANDOFF_BATCH_SIZE",
+    "HANDOFF_BATCH_INTERVAL_MS",
+    "HANDOFF_BATCH_INTERVAL",
+    "HANDOFF_ACK_TIMEOUT_MS",
+    "HANDOFF_ACK_TIMEOUT",
+    "DELIVERY_TIMEOUT_MS",
+    "DELIVERY_TIMEOUT",
+    "HANDLER_TIMEOUT_MS",
+    "MESSAGE_BUFFER_CAPACITY",
+    "MAX_UPGRADE_BODY_BYTES",
+    "MAX_UPGRADE_HEADER_BYTES",
+    "MAX_GROUPS_PER_SESSION",
+    "MAX_GROUP_SIZE",
+    "MAX_GROUPS",
+    "MAX_METADATA_ENTRIES",
+    "MAX_METADATA_KEY_LEN",
+    "MAX_METADATA_VALUE_LEN",
+    "BYTE_ARRAY_POLICY",
+    "LEGACY_HEX_BODIES",
+    "REQUIRE_BODY_ENCODING",
+    "MAX_SUBJECT_LEN",
+    "MAX_REPLY_TO_LEN",
+    "SERVER_MEMORY_BUDGET_BYTES",
+    "MAX_SESSION_BUFFER_BYTES",
+    "SESSION_BUFFER_POLICY",
+    "SERVER_MAX_MESSAGES_PER_SEC",
+    "SERVER_RATE_POLICY",
+    "BIND_RETRY_ATTEMPTS",
+    "BIND_RETRY_DELAY_MS",
+    "BIND_RETRY_DELAY",
+    "BIND_REUSE_ADDR",
+    "SERVER_RESTART",
+    "SERVER_RESTART_DELAY_MS",
+    "SERVER_RESTART_DELAY",
+    "SLOW_CONSUMER_HIGH_WATER_PCT",
+    "SLOW_CONSUMER_AFTER_MS",
+    "SLOW_CONSUMER_AFTER",
+    "WELCOME_FRAME",
+    "SESSION_EVENTS",
+    "IDLE_TIMEOUT_MS",
+    "IDLE_TIMEOUT",
+    "OUTBOUND_ALLOW",
+    "OUTBOUND_DENY",
+    "TENANT_PARAM",
+    "TENANT_TOKENS",
+    "TLS_CERT_PATH",
+    "TLS_KEY_PATH",
+];
+
+fn default_uri() -> String {
+    "ws://127.0.0.1:8080".to_string()
+}
+
+fn default_connect_timeout() -> Duration {
+    Duration::from_secs(30)
+}
+
+fn default_session_tracking() -> bool {
+    true
+}
+
+fn default_handler_batch_size() -> usize {
+    1
+}
+
+fn default_handler_batch_window() -> Duration {
+    Duration::from_millis(10)
+}
+
+fn default_retain_max_entries() -> usize {
+    1000
+}
+
+fn default_parse_fail_threshold() -> u32 {
+    10
+}
+
+fn default_parse_fail_cooldown() -> Duration {
+    Duration::from_secs(1)
+}
+
+fn default_handoff_batch_size() -> usize {
+    100
+}
+
+fn default_handoff_batch_interval() -> Duration {
+    Duration::from_secs(1)
+}
+
+fn default_handoff_ack_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
+fn default_tenant_param() -> String {
+    "tenant".to_string()
+}
+
+fn default_inbox_prefix() -> String {
+    "_INBOX".to_string()
+}
+
+fn default_outbound_queue_capacity() -> usize {
+    1024
+}
+
+fn default_delivery_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
+fn default_message_buffer_capacity() -> usize {
+    1024
+}
+
+fn default_max_message_bytes() -> usize {
+    // tungstenite's default max_message_size
+    64 << 20
+}
+
+fn default_max_upgrade_body_bytes() -> usize {
+    // Upgrade requests are GETs; anything beyond a small body is abuse
+    4096
+}
+
+fn default_max_upgrade_header_bytes() -> usize {
+    16 << 10
+}
+
+fn default_reconcile_interval() -> Duration {
+    Duration::from_secs(60)
+}
+
+fn default_max_groups_per_session() -> usize {
+    64
+}
+
+fn default_max_group_size() -> usize {
+    10_000
+}
+
+fn default_max_metadata_entries() -> usize {
+    32
+}
+
+fn default_max_metadata_key_len() -> usize {
+    64
+}
+
+fn default_max_metadata_value_len() -> usize {
+    1024
+}
+
+fn default_max_subject_len() -> usize {
+    subject::DEFAULT_MAX_SUBJECT_LEN
+}
+
+fn default_max_reply_to_len() -> usize {
+    subject::DEFAULT_MAX_REPLY_TO_LEN
+}
+
+fn default_dedup_window() -> Duration {
+    Duration::from_secs(60)
+}
+
+fn default_reconnect_max_retries() -> u32 {
+    10
+}
+
+fn default_reconnect_base_delay() -> Duration {
+    Duration::from_millis(500)
+}
+
+fn default_pong_timeout() -> Duration {
+    Duration::from_secs(10)
+}
+
+fn default_bind_retry_delay() -> Duration {
+    Duration::from_millis(500)
+}
+
+fn default_server_restart_delay() -> Duration {
+    Duration::from_secs(1)
+}
+
+fn default_slow_consumer_high_water_pct() -> u32 {
+    80
+}
+
+fn default_metric_subject_depth() -> usize {
+    1
+}
+
+fn default_metric_max_series() -> usize {
+    1000
+}
+
+fn default_body_stream_prefix_bytes() -> usize {
+    1024 * 1024
+}
+
+fn default_body_stream_subscribe_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
+fn default_compress_threshold_bytes() -> usize {
+    1024
+}
+
+fn default_max_decompressed_bytes() -> usize {
+    default_max_message_bytes()
+}
+
+fn default_envelope_upgrade_grace() -> Duration {
+    Duration::from_secs(5)
+}
+
+/// Parse `HANDLER_TIMEOUT_MS`: comma-separated `pattern=timeout` entries, where a bare
+/// `timeout` applies to every subject; timeouts are milliseconds or humantime values
+fn parse_handler_timeouts(value: &str) -> Result<Vec<(String, Duration)>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (pattern, timeout) = entry.rsplit_once('=').unwrap_or((">", entry));
+            match duration::parse_in(timeout, 1) {
+                Ok(timeout) if !timeout.is_zero() => Ok((pattern.trim().to_string(), timeout)),
+                _ => bail!("Invalid HANDLER_TIMEOUT_MS entry: {}", entry),
+            }
+        })
+        .collect()
+}
+
+impl ConnectionConfig {
+    /// Create a ConnectionConfig from a HashMap of configuration values
+    ///
+    /// Unknown keys are logged in a single warning, or rejected when `STRICT_CONFIG=true` or
+    /// the crate is built with the `strict-config` feature. Deprecated aliases still take
+    /// effect with a warning; the current key wins if both are set.
+    pub fn from_map(config: &HashMap<String, String>) -> Result<Self> {
+        if let Some(value) = config.get(COMPRESSION) {
+            if compression_alias(value).is_none() {
+                bail!(
+                    "COMPRESSION must be none, zstd, gzip or permessage-deflate, got {}",
+                    value
+                );
+            }
+        }
+        let report = Self::validate_map(config);
+        let strict = cfg!(feature = "strict-config")
+            || config
+                .get("STRICT_CONFIG")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or_default();
+        if !report.unknown.is_empty() {
+            if strict {
+                bail!("Unknown config keys: {}", report.unknown.join(", "));
+            }
+            warn!(unknown_keys = ?report.unknown, "Ignoring unknown config keys");
+        }
+        let mut resolved;
+        let config = if report.deprecated.is_empty() {
+            config
+        } else {
+            resolved = config.clone();
+            for (old, new) in &report.deprecated {
+                warn!("Config key {} is deprecated, use {} instead", old, new);
+                if let Some(value) = resolved.remove(old) {
+                    let value = if old == COMPRESSION {
+                        compression_alias(&value).map_or(value, |(_, value)| value)
+                    } else {
+                        value
+                    };
+                    resolved.entry(new.clone()).or_insert(value);
+                }
+            }
+            &resolved
+        };
+        let durations = DurationConfig::new(config);
+
+        let mode = config
+            .get("MODE")
+            .and_then(|s| match s.to_lowercase().as_str() {
+                "server" => Some(ConnectionMode::Server),
+                "client" => Some(ConnectionMode::Client),
+                _ => None,
+            })
+            .unwrap_or_default();
+
+        let uri = config.get("URI").cloned().unwrap_or_else(default_uri);
+        let uris = failover::split_uris(&uri);
+        if uris.len() > 1 && mode == ConnectionMode::Server {
+            bail!(
+                "URI {} lists several URIs, which only client mode can fail over between",
+                uri
+            );
+        }
+        for uri in &uris {
+            platform::check_uri(uri)?;
+        }
+
+        let failover_strategy = match config.get("FAILOVER_STRATEGY") {
+            Some(value) => match FailoverStrategy::parse(value) {
+                Some(strategy) => strategy,
+                None => bail!("FAILOVER_STRATEGY must be ordered or random, got {}", value),
+            },
+            None => FailoverStrategy::default(),
+        };
+
+        let auth_token = config.get("AUTH_TOKEN").cloned();
+        let auth_scheme = match config.get("AUTH_SCHEME") {
+            Some(scheme) => AuthScheme::parse(scheme)?,
+            None => AuthScheme::default(),
+        };
+
+        let message_hmac_key = config
+            .get("MESSAGE_HMAC_KEY")
+            .filter(|key| !key.is_empty())
+            .cloned();
+        let message_hmac_allow_unsigned: bool = config
+            .get("MESSAGE_HMAC_ALLOW_UNSIGNED")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_default();
+
+        let connect_timeout = durations.get("CONNECT_TIMEOUT", default_connect_timeout())?;
+
+        let tcp_keepalive = durations.get("TCP_KEEPALIVE", Duration::ZERO)?;
+
+        let proxy_url = config
+            .get("PROXY_URL")
+            .filter(|url| !url.is_empty())
+            .cloned();
+        if let Some(url) = &proxy_url {
+            Proxy::parse(url)?;
+        }
+
+        let tls_file = |key: &str| config.get(key).filter(|path| !path.is_empty()).cloned();
+        let tls_ca_file = tls_file("TLS_CA_FILE");
+        let tls_client_cert_file = tls_file("TLS_CLIENT_CERT_FILE");
+        let tls_client_key_file = tls_file("TLS_CLIENT_KEY_FILE");
+        let tls_insecure_skip_verify: bool = config
+            .get("TLS_INSECURE_SKIP_VERIFY")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_default();
+        let tls_cert_path = tls_file("TLS_CERT_PATH");
+        let tls_key_path = tls_file("TLS_KEY_PATH");
+        match (&tls_cert_path, &tls_key_path) {
+            (Some(_), None) => bail!("TLS_CERT_PATH is set without TLS_KEY_PATH"),
+            (None, Some(_)) => bail!("TLS_KEY_PATH is set without TLS_CERT_PATH"),
+            _ => {}
+        }
+
+        let subprotocols = match config.get("SUBPROTOCOLS") {
+            Some(value) => parse_subprotocols(value)?,
+            None => Vec::new(),
+        };
+
+        let reconnect: bool = config
+            .get("RECONNECT")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_default();
+
+        let reconnect_max_retries = config
+            .get("RECONNECT_MAX_RETRIES")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_else(default_reconnect_max_retries);
+
+        let reconnect_base_delay =
+            durations.get("RECONNECT_BASE_DELAY", default_reconnect_base_delay())?;
+
+        let reconnect_buffer_max_age = durations.get("RECONNECT_BUFFER_MAX_AGE", Duration::ZERO)?;
+
+        let enable_session_tracking = config
+            .get("ENABLE_SESSION_TRACKING")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_else(default_session_tracking);
+
+        let handler_batch_size = config
+            .get("HANDLER_BATCH_SIZE")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_else(default_handler_batch_size);
+
+        let handler_batch_window =
+            durations.get("HANDLER_BATCH_WINDOW", default_handler_batch_window())?;
+
+        let retain_subjects = config
+            .get("RETAIN_SUBJECTS")
+            .map(|s| parse_pattern_list(s))
+            .unwrap_or_default();
+
+        let retain_ttl = durations.get("RETAIN_TTL", Duration::ZERO)?;
+
+        let retain_clear_on_empty: bool = config
+            .get("RETAIN_CLEAR_ON_EMPTY")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_default();
+
+        let retain_max_entries = config
+            .get("RETAIN_MAX_ENTRIES")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_else(default_retain_max_entries);
+
+        let parse_fail_threshold = config
+            .get("PARSE_FAIL_THRESHOLD")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_else(default_parse_fail_threshold);
+
+        let parse_fail_cooldown =
+            durations.get("PARSE_FAIL_COOLDOWN", default_parse_fail_cooldown())?;
+
+        let parse_fail_disconnect_threshold: u32 = config
+            .get("PARSE_FAIL_DISCONNECT_THRESHOLD")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_default();
+
+        let subscriptions = config
+            .get("SUBSCRIPTIONS")
+            .map(|s| parse_pattern_list(s))
+            .unwrap_or_default();
+
+        let route_priority: i32 = config
+            .get("ROUTE_PRIORITY")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_default();
+
+        let route_delivery = config
+            .get("ROUTE_DELIVERY")
+            .and_then(|s| RouteDelivery::parse(s))
+            .unwrap_or_default();
+
+        let tenant = config
+            .get("TENANT")
+            .filter(|tenant| !tenant.is_empty())
+            .cloned();
+
+        let tenant_param = config
+            .get("TENANT_PARAM")
+            .filter(|param| !param.is_empty())
+            .cloned()
+            .unwrap_or_else(default_tenant_param);
+
+        let tenant_tokens = match config.get("TENANT_TOKENS") {
+            Some(value) => tenant::parse_tenant_tokens(value)?,
+            None => HashMap::new(),
+        };
+
+        let handoff_batch_size = config
+            .get("HANDOFF_BATCH_SIZE")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_else(default_handoff_batch_size);
+
+        let handoff_batch_interval =
+            durations.get("HANDOFF_BATCH_INTERVAL", default_handoff_batch_interval())?;
+
+        let handoff_ack_timeout =
+            durations.get("HANDOFF_ACK_TIMEOUT", default_handoff_ack_timeout())?;
+
+        let server_session_field = config
+            .get("SERVER_SESSION_FIELD")
+            .cloned()
+            .unwrap_or_default();
+
+        let server_session_header = config
+            .get("SERVER_SESSION_HEADER")
+            .cloned()
+            .unwrap_or_default();
+
+        let capture_path = config.get("CAPTURE_PATH").cloned().unwrap_or_default();
+
+        let shutdown_spill_path = config
+            .get("SHUTDOWN_SPILL_PATH")
+            .cloned()
+            .unwrap_or_default();
+
+        let connection_label = config.get("LABEL").cloned().unwrap_or_default();
+
+        let inbox_prefix = config
+            .get("INBOX_PREFIX")
+            .cloned()
+            .unwrap_or_else(default_inbox_prefix);
+
+        let allow_reserved_subjects: bool = config
+            .get("ALLOW_RESERVED_SUBJECTS")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_default();
+
+        let drop_notifications: bool = config
+            .get("DROP_NOTIFICATIONS")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_default();
+
+        let flow_signals: bool = config
+            .get("FLOW_SIGNALS")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_default();
+
+        let publish_allow = config
+            .get("PUBLISH_ALLOW")
+            .map(|s| parse_pattern_list(s))
+            .unwrap_or_default();
+
+        let publish_deny = config
+            .get("PUBLISH_DENY")
+            .map(|s| parse_pattern_list(s))
+            .unwrap_or_default();
+
+        let inbound_allow = config
+            .get("INBOUND_ALLOW")
+            .map(|s| parse_pattern_list(s))
+            .unwrap_or_default();
+
+        let inbound_deny = config
+            .get("INBOUND_DENY")
+            .map(|s| parse_pattern_list(s))
+            .unwrap_or_default();
+
+        let outbound_allow = config
+            .get("OUTBOUND_ALLOW")
+            .map(|s| parse_pattern_list(s))
+            .unwrap_or_default();
+
+        let outbound_deny = config
+            .get("OUTBOUND_DENY")
+            .map(|s| parse_pattern_list(s))
+            .unwrap_or_default();
+
+        let log_inbound_denied: bool = config
+            .get("LOG_INBOUND_DENIED")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_default();
+
+        let outbound_queue_capacity = config
+            .get("OUTBOUND_QUEUE_CAPACITY")
+            .and_then(|s| s.parse().ok())
+            .filter(|capacity| *capacity > 0)
+            .unwrap_or_else(default_outbound_queue_capacity);
+
+        let delivery_timeout = match durations.get("DELIVERY_TIMEOUT", Duration::ZERO)? {
+            timeout if timeout.is_zero() => default_delivery_timeout(),
+            timeout => timeout,
+        };
+
+        let handler_timeouts = config
+            .get("HANDLER_TIMEOUT_MS")
+            .map(|s| parse_handler_timeouts(s))
+            .transpose()?
+            .unwrap_or_default();
+
+        let message_buffer_capacity = config
+            .get("MESSAGE_BUFFER_CAPACITY")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_else(default_message_buffer_capacity);
+
+        let max_message_bytes = config
+            .get("MAX_MESSAGE_BYTES")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_else(default_max_message_bytes);
+
+        let max_upgrade_body_bytes = config
+            .get("MAX_UPGRADE_BODY_BYTES")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_else(default_max_upgrade_body_bytes);
+
+        let max_upgrade_header_bytes = config
+            .get("MAX_UPGRADE_HEADER_BYTES")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_else(default_max_upgrade_header_bytes);
+
+        let reconcile_interval =
+            durations.get("RECONCILE_INTERVAL", default_reconcile_interval())?;
+
+        let mut job_intervals = HashMap::new();
+        for (key, value) in config.iter() {
+            let Some(name) = job_interval_name(key) else {
+                continue;
+            };
+            // Bare numbers are seconds under the old key, which loses to the new one
+            let interval = if key.ends_with("_SEC") {
+                if config.contains_key(&format!("JOB_{}_INTERVAL", name)) {
+                    continue;
+                }
+                duration::parse_in(value, 1000)
+            } else {
+                duration::parse(value)
+            }
+            .with_context(|| format!("Invalid {}", key))?;
+            job_intervals.insert(name.to_lowercase(), interval);
+        }
+
+        let max_groups_per_session = config
+            .get("MAX_GROUPS_PER_SESSION")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_else(default_max_groups_per_session);
+
+        let max_group_size = config
+            .get("MAX_GROUP_SIZE")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_else(default_max_group_size);
+
+        let max_groups: usize = config
+            .get("MAX_GROUPS")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_default();
+
+        let max_metadata_entries = config
+            .get("MAX_METADATA_ENTRIES")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_else(default_max_metadata_entries);
+
+        let max_metadata_key_len = config
+            .get("MAX_METADATA_KEY_LEN")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_else(default_max_metadata_key_len);
+
+        let max_metadata_value_len = config
+            .get("MAX_METADATA_VALUE_LEN")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_else(default_max_metadata_value_len);
+
+        let server_memory_budget_bytes = config
+            .get("SERVER_MEMORY_BUDGET_BYTES")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_default();
+
+        let max_session_buffer_bytes = config
+            .get("MAX_SESSION_BUFFER_BYTES")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_default();
+
+        let session_buffer_policy = config
+            .get("SESSION_BUFFER_POLICY")
+            .and_then(|s| SessionBufferPolicy::parse(s))
+            .unwrap_or_default();
+
+        let server_max_messages_per_sec = config
+            .get("SERVER_MAX_MESSAGES_PER_SEC")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_default();
+
+        let server_rate_policy = config
+            .get("SERVER_RATE_POLICY")
+            .and_then(|s| ServerRatePolicy::parse(s))
+            .unwrap_or_default();
+
+        let bind_retry_attempts = config
+            .get("BIND_RETRY_ATTEMPTS")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_default();
+
+        let bind_retry_delay = durations.get("BIND_RETRY_DELAY", default_bind_retry_delay())?;
+
+        let bind_reuse_addr: bool = config
+            .get("BIND_REUSE_ADDR")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_default();
+
+        let server_restart: bool = config
+            .get("SERVER_RESTART")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_default();
+
+        let server_restart_delay =
+            durations.get("SERVER_RESTART_DELAY", default_server_restart_delay())?;
+
+        let slow_consumer_high_water_pct = config
+            .get("SLOW_CONSUMER_HIGH_WATER_PCT")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_else(default_slow_consumer_high_water_pct);
+
+        let slow_consumer_after = durations.get("SLOW_CONSUMER_AFTER", Duration::ZERO)?;
+
+        let welcome_frame: bool = config
+            .get("WELCOME_FRAME")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_default();
+
+        let session_events: bool = config
+            .get("SESSION_EVENTS")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_default();
+
+        let heartbeat_interval = durations.get("HEARTBEAT_INTERVAL", Duration::ZERO)?;
+
+        let idle_timeout = durations.get("IDLE_TIMEOUT", Duration::ZERO)?;
+
+        let ping_interval = durations.get("PING_INTERVAL", Duration::ZERO)?;
+
+        let pong_timeout = durations.get("PONG_TIMEOUT", default_pong_timeout())?;
+
+        let metric_subject_depth = config
+            .get("METRIC_SUBJECT_DEPTH")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_else(default_metric_subject_depth);
+
+        let metric_max_series = config
+            .get("METRIC_MAX_SERIES")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_else(default_metric_max_series);
+
+        let high_cardinality_metrics: bool = config
+            .get("HIGH_CARDINALITY_METRICS")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_default();
+
+        let body_stream_prefix_bytes = config
+            .get("BODY_STREAM_PREFIX_BYTES")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_else(default_body_stream_prefix_bytes);
+
+        let body_stream_subscribe_timeout = durations.get(
+            "BODY_STREAM_SUBSCRIBE_TIMEOUT",
+            default_body_stream_subscribe_timeout(),
+        )?;
+
+        let handler_overflow = config
+            .get("HANDLER_OVERFLOW")
+            .and_then(|s| HandlerOverflow::parse(s))
+            .unwrap_or_default();
+
+        let handler_concurrency = config
+            .get("HANDLER_CONCURRENCY")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_default();
+
+        let byte_array_policy = config
+            .get("BYTE_ARRAY_POLICY")
+            .and_then(|s| ByteArrayPolicy::parse(s))
+            .unwrap_or_default();
+
+        let legacy_hex_bodies = config
+            .get("LEGACY_HEX_BODIES")
+            .and_then(|s| LegacyHexPolicy::parse(s))
+            .unwrap_or_default();
+
+        let require_body_encoding: bool = config
+            .get("REQUIRE_BODY_ENCODING")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_default();
+
+        let max_subject_len = config
+            .get("MAX_SUBJECT_LEN")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_else(default_max_subject_len);
+
+        let max_reply_to_len = config
+            .get("MAX_REPLY_TO_LEN")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_else(default_max_reply_to_len);
+
+        let inbound_stages = config
+            .get("INBOUND_STAGES")
+            .map(|s| parse_pattern_list(s))
+            .unwrap_or_default();
+
+        let dedup_window = durations.get("DEDUP_WINDOW", default_dedup_window())?;
+
+        let codec = match config.get("CODEC") {
+            Some(value) => match Codec::parse(value) {
+                Some(codec) => codec,
+                None => bail!("CODEC must be json or msgpack, got {}", value),
+            },
+            None => Codec::default(),
+        };
+
+        let permessage_deflate: bool = config
+            .get("PERMESSAGE_DEFLATE")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_default();
+
+        let body_compression = config
+            .get("BODY_COMPRESSION")
+            .and_then(|s| BodyCompression::parse(s))
+            .unwrap_or_default();
+
+        let compress_threshold_bytes = config
+            .get("COMPRESS_THRESHOLD_BYTES")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_else(default_compress_threshold_bytes);
+
+        let max_decompressed_bytes = config
+            .get("MAX_DECOMPRESSED_BYTES")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_else(default_max_decompressed_bytes);
+
+        let envelope_upgrade_grace =
+            durations.get("ENVELOPE_UPGRADE_GRACE", default_envelope_upgrade_grace())?;
+
+        let allow_server_override: bool = config
+            .get("ALLOW_SERVER_OVERRIDE")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_default();
+
+        let mut custom_headers = HashMap::new();
+        for (key, value) in config.iter() {
+            if key.starts_with("HEADER_") {
+                let header_name = key.strip_prefix("HEADER_").unwrap();
+                // Rejected here so a bad header fails the link, not each connection attempt
+                handshake_header(header_name, value)?;
+                custom_headers.insert(header_name.to_string(), value.clone());
+            }
+        }
+
+        Ok(ConnectionConfig {
+            mode,
+            uri,
+            failover_strategy,
+            auth_token,
+            auth_scheme,
+            message_hmac_key,
+            message_hmac_allow_unsigned,
+            connect_timeout,
+            tcp_keepalive,
+            proxy_url,
+            tls_ca_file,
+            tls_client_cert_file,
+            tls_client_key_file,
+            tls_insecure_skip_verify,
+            tls_cert_path,
+            tls_key_path,
+            subprotocols,
+            reconnect,
+            reconnect_max_retries,
+            reconnect_base_delay,
+            reconnect_buffer_max_age,
+            enable_session_tracking,
+            custom_headers,
+            handler_batch_size,
+            handler_batch_window,
+            retain_subjects,
+            retain_ttl,
+            retain_clear_on_empty,
+            retain_max_entries,
+            parse_fail_threshold,
+            parse_fail_cooldown,
+            parse_fail_disconnect_threshold,
+            subscriptions,
+            route_priority,
+            route_delivery,
+            tenant,
+            tenant_param,
+            tenant_tokens,
+            handoff_batch_size,
+            handoff_batch_interval,
+            handoff_ack_timeout,
+            server_session_field,
+            server_session_header,
+            inbox_prefix,
+            allow_reserved_subjects,
+            drop_notifications,
+            flow_signals,
+            publish_allow,
+            publish_deny,
+            inbound_allow,
+            inbound_deny,
+            log_inbound_denied,
+            outbound_allow,
+            outbound_deny,
+            outbound_queue_capacity,
+            delivery_timeout,
+            handler_timeouts,
+            message_buffer_capacity,
+            max_message_bytes,
+            max_upgrade_body_bytes,
+            max_upgrade_header_bytes,
+            reconcile_interval,
+            job_intervals,
+            max_groups_per_session,
+            max_group_size,
+            max_groups,
+            max_metadata_entries,
+            max_metadata_key_len,
+            max_metadata_value_len,
+            server_memory_budget_bytes,
+            max_session_buffer_bytes,
+            session_buffer_policy,
+            server_max_messages_per_sec,
+            server_rate_policy,
+            bind_retry_attempts,
+            bind_retry_delay,
+            bind_reuse_addr,
+            server_restart,
+            server_restart_delay,
+            slow_consumer_high_water_pct,
+            slow_consumer_after,
+            welcome_frame,
+            session_events,
+            heartbeat_interval,
+            idle_timeout,
+            ping_interval,
+            pong_timeout,
+            body_stream_prefix_bytes,
+            metric_subject_depth,
+            metric_max_series,
+            high_cardinality_metrics,
+            body_stream_subscribe_timeout,
+            handler_overflow,
+            handler_concurrency,
+            byte_array_policy,
+            legacy_hex_bodies,
+            require_body_encoding,
+            max_subject_len,
+            max_reply_to_len,
+            inbound_stages,
+            dedup_window,
+            codec,
+            permessage_deflate,
+            body_compression,
+            compress_threshold_bytes,
+            max_decompressed_bytes,
+            envelope_upgrade_grace,
+            capture_path,
+            shutdown_spill_path,
+            connection_label,
+            allow_server_override,
+        })
+    }
+
+    /// Report the unknown and deprecated keys of a config map, sorted by key
+    pub fn validate_map(config: &HashMap<String, String>) -> ConfigReport {
+        let mut report = ConfigReport::default();
+        for (key, value) in config {
+            let canonical = canonical_key(key, value);
+            if canonical != key {
+                report.deprecated.push((key.clone(), canonical.to_string()));
+            } else if !CONFIG_KEYS.contains(&key.as_str())
+                && !key.starts_with("HEADER_")
+                && job_interval_name(key).is_none()
+                && configured_link_key(key).is_none()
+            {
+                report.unknown.push(key.clone());
+            }
+        }
+        report.unknown.sort();
+        report.deprecated.sort();
+        report
+    }
+
+    /// The connection label, if one is configured
+    pub(crate) fn label(&self) -> Option<&str> {
+        (!self.connection_label.is_empty()).then_some(self.connection_label.as_str())
+    }
+
+    /// Timeout for handing a server-mode message to the sink
+    pub(crate) fn delivery_timeout(&self) -> Duration {
+        if self.delivery_timeout.is_zero() {
+            default_delivery_timeout()
+        } else {
+            self.delivery_timeout
+        }
+    }
+
+    /// Timeout for handing a message on `subject` to the sink: the first matching
+    /// `HANDLER_TIMEOUT_MS` entry, otherwise `delivery_timeout`
+    pub(crate) fn delivery_timeout_for(&self, subject: &str) -> Duration {
+        self.handler_timeouts
+            .iter()
+            .find(|(pattern, _)| subject_matches(pattern, subject))
+            .map_or_else(|| self.delivery_timeout(), |(_, timeout)| *timeout)
+    }
+
+    /// Idle time before TCP keepalive probes, if enabled
+    pub(crate) fn tcp_keepalive(&self) -> Option<Duration> {
+        (!self.tcp_keepalive.is_zero()).then_some(self.tcp_keepalive)
+    }
+
+    /// Pacing of a handoff of connected clients to another instance
+    pub(crate) fn handoff_plan(&self) -> HandoffPlan {
+        HandoffPlan {
+            batch_size: self.handoff_batch_size,
+            batch_interval: self.handoff_batch_interval,
+            ack_timeout: self.handoff_ack_timeout,
+        }
+    }
+
+    /// Policy applied to sessions that keep sending unparseable frames
+    pub(crate) fn parse_failure_policy(&self) -> ParseFailurePolicy {
+        ParseFailurePolicy {
+            threshold: self.parse_fail_threshold,
+            cooldown: self.parse_fail_cooldown,
+            disconnect_after: self.parse_fail_disconnect_threshold,
+        }
+    }
+
+    /// Secret values hidden by `redacted`, for scrubbing them from anything else written out
+    pub(crate) fn secret_values(&self) -> Vec<String> {
+        self.auth_token
+            .iter()
+            .chain(self.message_hmac_key.iter())
+            .chain(
+                self.proxy_url
+                    .iter()
+                    .filter(|url| proxy::has_credentials(url)),
+            )
+            .chain(self.custom_headers.values())
+            .chain(self.tenant_tokens.values())
+            .filter(|value| !value.is_empty())
+            .cloned()
+            .collect()
+    }
+
+    /// Copy of this config that is safe to log or expose: the auth token, the message
+    /// signing key, a proxy URL with credentials, header values and tenant tokens are
+    /// replaced with a placeholder
+    pub fn redacted(&self) -> Self {
+        let mut config = self.clone();
+        if config.auth_token.is_some() {
+            config.auth_token = Some(REDACTED.to_string());
+        }
+        if config.message_hmac_key.is_some() {
+            config.message_hmac_key = Some(REDACTED.to_string());
+        }
+        if config
+            .proxy_url
+            .as_deref()
+            .is_some_and(proxy::has_credentials)
+        {
+            config.proxy_url = Some(REDACTED.to_string());
+        }
+        for value in config
+            .custom_headers
+            .values_mut()
+            .chain(config.tenant_tokens.values_mut())
+        {
+            *value = REDACTED.to_string();
+        }
+        config
+    }
+
+    /// Merge two configs, with values from `other` taking precedence
+    pub fn merge(&self, other: &Self) -> Self {
+        let mut custom_headers = self.custom_headers.clone();
+        custom_headers.extend(other.custom_headers.clone());
+        let mut job_intervals = self.job_intervals.clone();
+        job_intervals.extend(other.job_intervals.clone());
+
+        ConnectionConfig {
+            mode: if other.mode != ConnectionMode::default() {
+                other.mode.clone()
+            } else {
+                self.mode.clone()
+            },
+            uri: if other.uri != default_uri() {
+                other.uri.clone()
+            } else {
+                self.uri.clone()
+            },
+            failover_strategy: if other.failover_strategy != FailoverStrategy::default() {
+                other.failover_strategy
+            } else {
+                self.failover_strategy
+            },
+            auth_token: other.auth_token.clone().or_else(|| self.auth_token.clone()),
+            auth_scheme: if other.auth_scheme != AuthScheme::default() {
+                other.auth_scheme.clone()
+            } else {
+                self.auth_scheme.clone()
+            },
+            message_hmac_key: other
+                .message_hmac_key
+                .clone()
+                .or_else(|| self.message_hmac_key.clone()),
+            message_hmac_allow_unsigned: other.message_hmac_allow_unsigned
+                || self.message_hmac_allow_unsigned,
+            connect_timeout: if other.connect_timeout != default_connect_timeout() {
+                other.connect_timeout
+            } else {
+                self.connect_timeout
+            },
+            tcp_keepalive: if !other.tcp_keepalive.is_zero() {
+                other.tcp_keepalive
+            } else {
+                self.tcp_keepalive
+            },
+            proxy_url: other.proxy_url.clone().or_else(|| self.proxy_url.clone()),
+            tls_ca_file: other
+                .tls_ca_file
+                .clone()
+                .or_else(|| self.tls_ca_file.clone()),
+            tls_client_cert_file: other
+                .tls_client_cert_file
+                .clone()
+                .or_else(|| self.tls_client_cert_file.clone()),
+            tls_client_key_file: other
+                .tls_client_key_file
+                .clone()
+                .or_else(|| self.tls_client_key_file.clone()),
+            tls_insecure_skip_verify: other.tls_insecure_skip_verify
+                || self.tls_insecure_skip_verify,
+            tls_cert_path: other
+                .tls_cert_path
+                .clone()
+                .or_else(|| self.tls_cert_path.clone()),
+            tls_key_path: other
+                .tls_key_path
+                .clone()
+                .or_else(|| self.tls_key_path.clone()),
+            subprotocols: if !other.subprotocols.is_empty() {
+                other.subprotocols.clone()
+            } else {
+                self.subprotocols.clone()
+            },
+            reconnect: other.reconnect || self.reconnect,
+            reconnect_max_retries: if other.reconnect_max_retries != default_reconnect_max_retries()
+            {
+                other.reconnect_max_retries
+            } else {
+                self.reconnect_max_retries
+            },
+            reconnect_base_delay: if other.reconnect_base_delay != default_reconnect_base_delay() {
+                other.reconnect_base_delay
+            } else {
+                self.reconnect_base_delay
+            },
+            reconnect_buffer_max_age: if !other.reconnect_buffer_max_age.is_zero() {
+                other.reconnect_buffer_max_age
+            } else {
+                self.reconnect_buffer_max_age
+            },
+            enable_session_tracking: other.enable_session_tracking,
+            custom_headers,
+            handler_batch_size: if other.handler_batch_size != default_handler_batch_size() {
+                other.handler_batch_size
+            } else {
+                self.handler_batch_size
+            },
+            handler_batch_window: if other.handler_batch_window != default_handler_batch_window() {
+                other.handler_batch_window
+            } else {
+                self.handler_batch_window
+            },
+            retain_subjects: if !other.retain_subjects.is_empty() {
+                other.retain_subjects.clone()
+            } else {
+                self.retain_subjects.clone()
+            },
+            retain_ttl: if !other.retain_ttl.is_zero() {
+                other.retain_ttl
+            } else {
+                self.retain_ttl
+            },
+            retain_clear_on_empty: other.retain_clear_on_empty || self.retain_clear_on_empty,
+            retain_max_entries: if other.retain_max_entries != default_retain_max_entries() {
+                other.retain_max_entries
+            } else {
+                self.retain_max_entries
+            },
+            parse_fail_threshold: if other.parse_fail_threshold != default_parse_fail_threshold() {
+                other.parse_fail_threshold
+            } else {
+                self.parse_fail_threshold
+            },
+            parse_fail_cooldown: if other.parse_fail_cooldown != default_parse_fail_cooldown() {
+                other.parse_fail_cooldown
+            } else {
+                self.parse_fail_cooldown
+            },
+            parse_fail_disconnect_threshold: if other.parse_fail_disconnect_threshold != 0 {
+                other.parse_fail_disconnect_threshold
+            } else {
+                self.parse_fail_disconnect_threshold
+            },
+            subscriptions: if !other.subscriptions.is_empty() {
+                other.subscriptions.clone()
+            } else {
+                self.subscriptions.clone()
+            },
+            route_priority: if other.route_priority != 0 {
+                other.route_priority
+            } else {
+                self.route_priority
+            },
+            route_delivery: if other.route_delivery != RouteDelivery::default() {
+                other.route_delivery
+            } else {
+                self.route_delivery
+            },
+            tenant: other.tenant.clone().or_else(|| self.tenant.clone()),
+            tenant_param: if other.tenant_param != default_tenant_param() {
+                other.tenant_param.clone()
+            } else {
+                self.tenant_param.clone()
+            },
+            tenant_tokens: if !other.tenant_tokens.is_empty() {
+                other.tenant_tokens.clone()
+            } else {
+                self.tenant_tokens.clone()
+            },
+            handoff_batch_size: if other.handoff_batch_size != default_handoff_batch_size() {
+                other.handoff_batch_size
+            } else {
+                self.handoff_batch_size
+            },
+            handoff_batch_interval: if other.handoff_batch_interval
+                != default_handoff_batch_interval()
+            {
+                other.handoff_batch_interval
+            } else {
+                self.handoff_batch_interval
+            },
+            handoff_ack_timeout: if other.handoff_ack_timeout != default_handoff_ack_timeout() {
+                other.handoff_ack_timeout
+            } else {
+                self.handoff_ack_timeout
+            },
+            server_session_field: if !other.server_session_field.is_empty() {
+                other.server_session_field.clone()
+            } else {
+                self.server_session_field.clone()
+            },
+            server_session_header: if !other.server_session_header.is_empty() {
+                other.server_session_header.clone()
+            } else {
+                self.server_session_header.clone()
+            },
+            inbox_prefix: if other.inbox_prefix != default_inbox_prefix() {
+                other.inbox_prefix.clone()
+            } else {
+                self.inbox_prefix.clone()
+            },
+            allow_reserved_subjects: other.allow_reserved_subjects || self.allow_reserved_subjects,
+            drop_notifications: other.drop_notifications || self.drop_notifications,
+            flow_signals: other.flow_signals || self.flow_signals,
+            publish_allow: if !other.publish_allow.is_empty() {
+                other.publish_allow.clone()
+            } else {
+                self.publish_allow.clone()
+            },
+            publish_deny: if !other.publish_deny.is_empty() {
+                other.publish_deny.clone()
+            } else {
+                self.publish_deny.clone()
+            },
+            inbound_allow: if !other.inbound_allow.is_empty() {
+                other.inbound_allow.clone()
+            } else {
+                self.inbound_allow.clone()
+            },
+            inbound_deny: if !other.inbound_deny.is_empty() {
+                other.inbound_deny.clone()
+            } else {
+                self.inbound_deny.clone()
+            },
+            log_inbound_denied: other.log_inbound_denied || self.log_inbound_denied,
+            outbound_allow: if !other.outbound_allow.is_empty() {
+                other.outbound_allow.clone()
+            } else {
+                self.outbound_allow.clone()
+            },
+            outbound_deny: if !other.outbound_deny.is_empty() {
+                other.outbound_deny.clone()
+            } else {
+                self.outbound_deny.clone()
+            },
+            outbound_queue_capacity: if other.outbound_queue_capacity
+                != default_outbound_queue_capacity()
+            {
+                other.outbound_queue_capacity
+            } else {
+                self.outbound_queue_capacity
+            },
+            delivery_timeout: if other.delivery_timeout != default_delivery_timeout() {
+                other.delivery_timeout
+            } else {
+                self.delivery_timeout
+            },
+            handler_timeouts: if !other.handler_timeouts.is_empty() {
+                other.handler_timeouts.clone()
+            } else {
+                self.handler_timeouts.clone()
+            },
+            message_buffer_capacity: if other.message_buffer_capacity
+                != default_message_buffer_capacity()
+            {
+                other.message_buffer_capacity
+            } else {
+                self.message_buffer_capacity
+            },
+            max_message_bytes: if other.max_message_bytes != default_max_message_bytes() {
+                other.max_message_bytes
+            } else {
+                self.max_message_bytes
+            },
+            max_upgrade_body_bytes: if other.max_upgrade_body_bytes
+                != default_max_upgrade_body_bytes()
+            {
+                other.max_upgrade_body_bytes
+            } else {
+                self.max_upgrade_body_bytes
+            },
+            max_upgrade_header_bytes: if other.max_upgrade_header_bytes
+                != default_max_upgrade_header_bytes()
+            {
+                other.max_upgrade_header_bytes
+            } else {
+                self.max_upgrade_header_bytes
+            },
+            reconcile_interval: if other.reconcile_interval != default_reconcile_interval() {
+                other.reconcile_interval
+            } else {
+                self.reconcile_interval
+            },
+            job_intervals,
+            max_groups_per_session: if other.max_groups_per_session
+                != default_max_groups_per_session()
+            {
+                other.max_groups_per_session
+            } else {
+                self.max_groups_per_session
+            },
+            max_group_size: if other.max_group_size != default_max_group_size() {
+                other.max_group_size
+            } else {
+                self.max_group_size
+            },
+            max_groups: if other.max_groups != 0 {
+                other.max_groups
+            } else {
+                self.max_groups
+            },
+            max_metadata_entries: if other.max_metadata_entries != default_max_metadata_entries() {
+                other.max_metadata_entries
+            } else {
+                self.max_metadata_entries
+            },
+            max_metadata_key_len: if other.max_metadata_key_len != default_max_metadata_key_len() {
+                other.max_metadata_key_len
+            } else {
+                self.max_metadata_key_len
+            },
+            max_metadata_value_len: if other.max_metadata_value_len
+                != default_max_metadata_value_len()
+            {
+                other.max_metadata_value_len
+            } else {
+                self.max_metadata_value_len
+            },
+            server_memory_budget_bytes: if other.server_memory_budget_bytes != 0 {
+                other.server_memory_budget_bytes
+            } else {
+                self.server_memory_budget_bytes
+            },
+            max_session_buffer_bytes: if other.max_session_buffer_bytes != 0 {
+                other.max_session_buffer_bytes
+            } else {
+                self.max_session_buffer_bytes
+            },
+            session_buffer_policy: if other.session_buffer_policy != SessionBufferPolicy::default()
+            {
+                other.session_buffer_policy
+            } else {
+                self.session_buffer_policy
+            },
+            server_max_messages_per_sec: if other.server_max_messages_per_sec != 0 {
+                other.server_max_messages_per_sec
+            } else {
+                self.server_max_messages_per_sec
+            },
+            server_rate_policy: if other.server_rate_policy != ServerRatePolicy::default() {
+                other.server_rate_policy
+            } else {
+                self.server_rate_policy
+            },
+            bind_retry_attempts: if other.bind_retry_attempts != 0 {
+                other.bind_retry_attempts
+            } else {
+                self.bind_retry_attempts
+            },
+            bind_retry_delay: if other.bind_retry_delay != default_bind_retry_delay() {
+                other.bind_retry_delay
+            } else {
+                self.bind_retry_delay
+            },
+            bind_reuse_addr: other.bind_reuse_addr || self.bind_reuse_addr,
+            server_restart: other.server_restart || self.server_restart,
+            server_restart_delay: if other.server_restart_delay != default_server_restart_delay() {
+                other.server_restart_delay
+            } else {
+                self.server_restart_delay
+            },
+            slow_consumer_high_water_pct: if other.slow_consumer_high_water_pct
+                != default_slow_consumer_high_water_pct()
+            {
+                other.slow_consumer_high_water_pct
+            } else {
+                self.slow_consumer_high_water_pct
+            },
+            slow_consumer_after: if !other.slow_consumer_after.is_zero() {
+                other.slow_consumer_after
+            } else {
+                self.slow_consumer_after
+            },
+            welcome_frame: other.welcome_frame || self.welcome_frame,
+            session_events: other.session_events || self.session_events,
+            heartbeat_interval: if !other.heartbeat_interval.is_zero() {
+                other.heartbeat_interval
+            } else {
+                self.heartbeat_interval
+            },
+            idle_timeout: if !other.idle_timeout.is_zero() {
+                other.idle_timeout
+            } else {
+                self.idle_timeout
+            },
+            ping_interval: if !other.ping_interval.is_zero() {
+                other.ping_interval
+            } else {
+                self.ping_interval
+            },
+            pong_timeout: if other.pong_timeout != default_pong_timeout() {
+                other.pong_timeout
+            } else {
+                self.pong_timeout
+            },
+            body_stream_prefix_bytes: if other.body_stream_prefix_bytes
+                != default_body_stream_prefix_bytes()
+            {
+                other.body_stream_prefix_bytes
+            } else {
+                self.body_stream_prefix_bytes
+            },
+            metric_subject_depth: if other.metric_subject_depth != default_metric_subject_depth() {
+                other.metric_subject_depth
+            } else {
+                self.metric_subject_depth
+            },
+            metric_max_series: if other.metric_max_series != default_metric_max_series() {
+                other.metric_max_series
+            } else {
+                self.metric_max_series
+            },
+            high_cardinality_metrics: other.high_cardinality_metrics
+                || self.high_cardinality_metrics,
+            body_stream_subscribe_timeout: if other.body_stream_subscribe_timeout
+                != default_body_stream_subscribe_timeout()
+            {
+                other.body_stream_subscribe_timeout
+            } else {
+                self.body_stream_subscribe_timeout
+            },
+            handler_overflow: if other.handler_overflow != HandlerOverflow::default() {
+                other.handler_overflow
+            } else {
+                self.handler_overflow
+            },
+            handler_concurrency: if other.handler_concurrency != 0 {
+                other.handler_concurrency
+            } else {
+                self.handler_concurrency
+            },
+            byte_array_policy: if other.byte_array_policy != ByteArrayPolicy::default() {
+                other.byte_array_policy
+            } else {
+                self.byte_array_policy
+            },
+            legacy_hex_bodies: if other.legacy_hex_bodies != LegacyHexPolicy::default() {
+                other.legacy_hex_bodies
+            } else {
+                self.legacy_hex_bodies
+            },
+            require_body_encoding: other.require_body_encoding || self.require_body_encoding,
+            max_subject_len: if other.max_subject_len != default_max_subject_len() {
+                other.max_subject_len
+            } else {
+                self.max_subject_len
+            },
+            max_reply_to_len: if other.max_reply_to_len != default_max_reply_to_len() {
+                other.max_reply_to_len
+            } else {
+                self.max_reply_to_len
+            },
+            inbound_stages: if !other.inbound_stages.is_empty() {
+                other.inbound_stages.clone()
+            } else {
+                self.inbound_stages.clone()
+            },
+            dedup_window: if other.dedup_window != default_dedup_window() {
+                other.dedup_window
+            } else {
+                self.dedup_window
+            },
+            codec: if other.codec != Codec::default() {
+                other.codec
+            } else {
+                self.codec
+            },
+            permessage_deflate: other.permessage_deflate || self.permessage_deflate,
+            body_compression: if other.body_compression != BodyCompression::default() {
+                other.body_compression
+            } else {
+                self.body_compression
+            },
+            compress_threshold_bytes: if other.compress_threshold_bytes
+                != default_compress_threshold_bytes()
+            {
+                other.compress_threshold_bytes
+            } else {
+                self.compress_threshold_bytes
+            },
+            max_decompressed_bytes: if other.max_decompressed_bytes
+                != default_max_decompressed_bytes()
+            {
+                other.max_decompressed_bytes
+            } else {
+                self.max_decompressed_bytes
+            },
+            envelope_upgrade_grace: if other.envelope_upgrade_grace
+                != default_envelope_upgrade_grace()
+            {
+                other.envelope_upgrade_grace
+            } else {
+                self.envelope_upgrade_grace
+            },
+            capture_path: if !other.capture_path.is_empty() {
+                other.capture_path.clone()
+            } else {
+                self.capture_path.clone()
+            },
+            shutdown_spill_path: if !other.shutdown_spill_path.is_empty() {
+                other.shutdown_spill_path.clone()
+            } else {
+                self.shutdown_spill_path.clone()
+            },
+            connection_label: if !other.connection_label.is_empty() {
+                other.connection_label.clone()
+            } else {
+                self.connection_label.clone()
+            },
+            allow_server_override: other.allow_server_override || self.allow_server_override,
+        }
+    }
+
+    /// Server-level keys set by a link (`link_map`, parsed as `link`) to a different value
+    /// than this, the running server's configuration
+    pub(crate) fn server_conflicts(
+        &self,
+        link_map: &HashMap<String, String>,
+        link: &Self,
+    ) -> Vec<ConfigConflict> {
+        SERVER_KEYS
+            .iter()
+            .filter(|key| link_map.contains_key(**key))
+            .filter_map(|key| {
+                let server = self.server_setting(key);
+                let linked = link.server_setting(key);
+                (server != linked).then(|| ConfigConflict {
+                    key: key.to_string(),
+                    server,
+                    link: linked,
+                })
+            })
+            .collect()
+    }
 
-    /// Timeout for connection in seconds
-    #[serde(default = "default_timeout")]
-    pub connect_timeout_sec: u64,
+    /// Server-level duration setting `key`, by its unsuffixed name
+    fn server_duration(&self, key: &str) -> Option<Duration> {
+        Some(match key {
+            "RETAIN_TTL" => self.retain_ttl,
+            "PARSE_FAIL_COOLDOWN" => self.parse_fail_cooldown,
+            "HANDOFF_BATCH_INTERVAL" => self.handoff_batch_interval,
+            "HANDOFF_ACK_TIMEOUT" => self.handoff_ack_timeout,
+            "DELIVERY_TIMEOUT" => self.delivery_timeout,
+            "BIND_RETRY_DELAY" => self.bind_retry_delay,
+            "SERVER_RESTART_DELAY" => self.server_restart_delay,
+            "SLOW_CONSUMER_AFTER" => self.slow_consumer_after,
+            "IDLE_TIMEOUT" => self.idle_timeout,
+            _ => return None,
+        })
+    }
 
-    /// Enable session tracking
-    #[serde(default = "default_session_tracking")]
-    pub enable_session_tracking: bool,
+    /// Value of one of the `SERVER_KEYS`, as it would be written in a config map
+    fn server_setting(&self, key: &str) -> String {
+        if let Some(value) = self.server_duration(key) {
+            return duration::format(value);
+        }
+        // A unit-suffixed key reads back as a bare number in its unit where that is exact
+        if let Some((current, unit_ms)) = duration::current_key(key) {
+            if let Some(value) = self.server_duration(current) {
+                let ms = value.as_millis() as u64;
+                return if ms % unit_ms == 0 {
+                    (ms / unit_ms).to_string()
+                } else {
+                    duration::format(value)
+                };
+            }
+        }
+        match key {
+            "RETAIN_SUBJECTS" => self.retain_subjects.join(","),
+            "RETAIN_CLEAR_ON_EMPTY" => self.retain_clear_on_empty.to_string(),
+            "RETAIN_MAX_ENTRIES" => self.retain_max_entries.to_string(),
+            "PARSE_FAIL_THRESHOLD" => self.parse_fail_threshold.to_string(),
+            "PARSE_FAIL_DISCONNECT_THRESHOLD" => self.parse_fail_disconnect_threshold.to_string(),
+            "HANDOFF_BATCH_SIZE" => self.handoff_batch_size.to_string(),
+            "HANDLER_TIMEOUT_MS" => self
+                .handler_timeouts
+                .iter()
+                .map(|(pattern, timeout)| format!("{}={}", pattern, timeout.as_millis()))
+                .collect::<Vec<_>>()
+                .join(","),
+            "MESSAGE_BUFFER_CAPACITY" => self.message_buffer_capacity.to_string(),
+            "MAX_UPGRADE_BODY_BYTES" => self.max_upgrade_body_bytes.to_string(),
+            "MAX_UPGRADE_HEADER_BYTES" => self.max_upgrade_header_bytes.to_string(),
+            "MAX_GROUPS_PER_SESSION" => self.max_groups_per_session.to_string(),
+            "MAX_GROUP_SIZE" => self.max_group_size.to_string(),
+            "MAX_GROUPS" => self.max_groups.to_string(),
+            "MAX_METADATA_ENTRIES" => self.max_metadata_entries.to_string(),
+            "MAX_METADATA_KEY_LEN" => self.max_metadata_key_len.to_string(),
+            "MAX_METADATA_VALUE_LEN" => self.max_metadata_value_len.to_string(),
+            "SERVER_MEMORY_BUDGET_BYTES" => self.server_memory_budget_bytes.to_string(),
+            "MAX_SESSION_BUFFER_BYTES" => self.max_session_buffer_bytes.to_string(),
+            "SESSION_BUFFER_POLICY" => self.session_buffer_policy.as_str().to_string(),
+            "SERVER_MAX_MESSAGES_PER_SEC" => self.server_max_messages_per_sec.to_string(),
+            "SERVER_RATE_POLICY" => self.server_rate_policy.as_str().to_string(),
+            "BIND_RETRY_ATTEMPTS" => self.bind_retry_attempts.to_string(),
+            "BIND_REUSE_ADDR" => self.bind_reuse_addr.to_string(),
+            "SERVER_RESTART" => self.server_restart.to_string(),
+            "SLOW_CONSUMER_HIGH_WATER_PCT" => self.slow_consumer_high_water_pct.to_string(),
+            "WELCOME_FRAME" => self.welcome_frame.to_string(),
+            "SESSION_EVENTS" => self.session_events.to_string(),
+            "OUTBOUND_ALLOW" => self.outbound_allow.join(","),
+            "OUTBOUND_DENY" => self.outbound_deny.join(","),
+            "TENANT_PARAM" => self.tenant_param.clone(),
+            // Tenants only, so a conflict report never carries a token
+            "TENANT_TOKENS" => {
+                let mut tenants: Vec<&str> =
+                    self.tenant_tokens.keys().map(String::as_str).collect();
+                tenants.sort_unstable();
+                tenants
+                    .iter()
+                    .map(|tenant| format!("{}={}", tenant, REDACTED))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            }
+            "TLS_CERT_PATH" => self.tls_cert_path.clone().unwrap_or_default(),
+            "TLS_KEY_PATH" => self.tls_key_path.clone().unwrap_or_default(),
+            "BYTE_ARRAY_POLICY" => match self.byte_array_policy {
+                ByteArrayPolicy::Reject => "reject".to_string(),
+                ByteArrayPolicy::Clamp => "clamp".to_string(),
+            },
+            "LEGACY_HEX_BODIES" => self.legacy_hex_bodies.as_str().to_string(),
+            "REQUIRE_BODY_ENCODING" => self.require_body_encoding.to_string(),
+            "MAX_SUBJECT_LEN" => self.max_subject_len.to_string(),
+            "MAX_REPLY_TO_LEN" => self.max_reply_to_len.to_string(),
+            other => unreachable!("{} is not a server-level key", other),
+        }
+    }
+}
 
-    /// Custom headers to send with WebSocket upgrade request (client mode)
-    #[serde(default)]
-    pub custom_headers: HashMap<String, String>,
+/// A server-level key a link set differently from the running server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigConflict {
+    pub key: String,
+    /// Value the server is running with
+    pub server: String,
+    /// Value the link asked for
+    pub link: String,
 }
 
-fn default_uri() -> String {
-    "ws://127.0.0.1:8080".to_string()
+/// Error returned when a server-mode link sets server-level keys that conflict with the
+/// running server and `ALLOW_SERVER_OVERRIDE` is not set
+///
+/// Server-level keys hold no secrets, so the values are reported as configured.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerConfigConflict {
+    pub component_id: String,
+    pub conflicts: Vec<ConfigConflict>,
 }
 
-fn default_timeout() -> u64 {
-    30
+impl fmt::Display for ServerConfigConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let conflicts: Vec<String> = self
+            .conflicts
+            .iter()
+            .map(|c| format!("{} (server: {:?}, link: {:?})", c.key, c.server, c.link))
+            .collect();
+        write!(
+            f,
+            "Link config for {} conflicts with the running server: {}",
+            self.component_id,
+            conflicts.join(", ")
+        )
+    }
 }
 
-fn default_session_tracking() -> bool {
-    true
+impl std::error::Error for ServerConfigConflict {}
+
+/// Effective configuration of a link after merging the link values over the defaults
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkConfigSummary {
+    /// Component the link was established for
+    pub component_id: String,
+    /// Merged configuration the connection uses, redacted
+    pub effective: ConnectionConfig,
+    /// Keys supplied by the link configuration
+    pub from_link: Vec<String>,
+    /// Keys left at the provider defaults
+    pub from_default: Vec<String>,
+    /// What happened to the component's connection
+    pub update: LinkUpdate,
 }
 
-impl ConnectionConfig {
-    /// Create a ConnectionConfig from a HashMap of configuration values
-    pub fn from_map(config: &HashMap<String, String>) -> Result<Self> {
-        let mode = config
-            .get("MODE")
-            .and_then(|s| match s.to_lowercase().as_str() {
-                "server" => Some(ConnectionMode::Server),
-                "client" => Some(ConnectionMode::Client),
-                _ => None,
+impl LinkConfigSummary {
+    pub(crate) fn new(
+        component_id: &str,
+        link_config: &HashMap<String, String>,
+        effective: &ConnectionConfig,
+    ) -> Self {
+        let mut from_link: Vec<String> = link_config
+            .iter()
+            .map(|(key, value)| canonical_key(key, value).to_string())
+            .filter(|key| {
+                CONFIG_KEYS.contains(&key.as_str())
+                    || key.starts_with("HEADER_")
+                    || job_interval_name(key).is_some()
             })
-            .unwrap_or_default();
+            .collect();
+        from_link.sort();
+        from_link.dedup();
 
-        let uri = config.get("URI").cloned().unwrap_or_else(default_uri);
+        let from_default = CONFIG_KEYS
+            .iter()
+            .filter(|key| !from_link.iter().any(|linked| linked == *key))
+            .map(|key| key.to_string())
+            .collect();
 
-        let auth_token = config.get("AUTH_TOKEN").cloned();
+        Self {
+            component_id: component_id.to_string(),
+            effective: effective.redacted(),
+            from_link,
+            from_default,
+            update: LinkUpdate::default(),
+        }
+    }
+}
 
-        let connect_timeout_sec = config
-            .get("CONNECT_TIMEOUT_SEC")
-            .and_then(|s| s.parse().ok())
-            .unwrap_or_else(default_timeout);
+impl fmt::Display for LinkConfigSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "uri={} mode={:?} session_tracking={} from_link=[{}]",
+            self.effective.uri,
+            self.effective.mode,
+            self.effective.enable_session_tracking,
+            self.from_link.join(",")
+        )
+    }
+}
 
-        let enable_session_tracking = config
-            .get("ENABLE_SESSION_TRACKING")
-            .and_then(|s| s.parse().ok())
-            .unwrap_or_else(default_session_tracking);
+/// How a link's connection was brought in line with its config
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkUpdateAction {
+    /// The component had no connection in this role, so one was opened
+    #[default]
+    Connected,
+    /// The link was re-delivered with the same config; the connection was kept
+    Unchanged,
+    /// Only per-message settings changed; they were applied to the open connection
+    InPlace,
+    /// A setting the connection was opened with changed; a new connection took over once
+    /// established, and the old one was closed after writing what it had queued
+    Reconnected,
+}
 
-        let mut custom_headers = HashMap::new();
-        for (key, value) in config.iter() {
-            if key.starts_with("HEADER_") {
-                let header_name = key.strip_prefix("HEADER_").unwrap();
-                custom_headers.insert(header_name.to_string(), value.clone());
-            }
-        }
+/// Settings a re-delivered link changed and what was done about them
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct LinkUpdate {
+    pub action: LinkUpdateAction,
+    /// `ConnectionConfig` fields whose values changed, sorted
+    pub changed: Vec<String>,
+}
 
-        Ok(ConnectionConfig {
-            mode,
-            uri,
-            auth_token,
-            connect_timeout_sec,
-            enable_session_tracking,
-            custom_headers,
-        })
-    }
+impl LinkUpdate {
+    /// Compare the config a connection was opened with to the one its link now asks for
+    pub(crate) fn between(current: &ConnectionConfig, new: &ConnectionConfig) -> Self {
+        let (Ok(serde_json::Value::Object(current)), Ok(serde_json::Value::Object(new))) =
+            (serde_json::to_value(current), serde_json::to_value(new))
+        else {
+            // Not expected for a plain config; reconnecting is always safe
+            return Self {
+                action: LinkUpdateAction::Reconnected,
+                changed: Vec::new(),
+            };
+        };
+        let mut changed: Vec<String> = current
+            .keys()
+            .chain(new.keys())
+            .filter(|field| current.get(*field) != new.get(*field))
+            .cloned()
+            .collect();
+        changed.sort();
+        changed.dedup();
 
-    /// Merge two configs, with values from `other` taking precedence
-    pub fn merge(&self, other: &Self) -> Self {
-        let mut custom_headers = self.custom_headers.clone();
-        custom_headers.extend(other.custom_headers.clone());
+        let action = if changed.is_empty() {
+            LinkUpdateAction::Unchanged
+        } else if changed
+            .iter()
+            .all(|field| IN_PLACE_FIELDS.contains(&field.as_str()))
+        {
+            LinkUpdateAction::InPlace
+        } else {
+            LinkUpdateAction::Reconnected
+        };
+        Self { action, changed }
+    }
+}
 
-        ConnectionConfig {
-            mode: if other.mode != ConnectionMode::default() {
-                other.mode.clone()
-            } else {
-                self.mode.clone()
-            },
-            uri: if other.uri != default_uri() {
-                other.uri.clone()
-            } else {
-                self.uri.clone()
-            },
-            auth_token: other.auth_token.clone().or_else(|| self.auth_token.clone()),
-            connect_timeout_sec: if other.connect_timeout_sec != default_timeout() {
-                other.connect_timeout_sec
-            } else {
-                self.connect_timeout_sec
-            },
-            enable_session_tracking: other.enable_session_tracking,
-            custom_headers,
-        }
+impl fmt::Display for LinkUpdate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} [{}]", self.action, self.changed.join(","))
     }
 }
 
@@ -135,9 +2576,29 @@ mod tests {
     fn test_from_map_default() {
         let config = ConnectionConfig::from_map(&HashMap::new()).unwrap();
         assert_eq!(config.uri, "ws://127.0.0.1:8080");
-        assert_eq!(config.connect_timeout_sec, 30);
+        assert_eq!(config.connect_timeout, Duration::from_secs(30));
         assert!(config.enable_session_tracking);
         assert_eq!(config.mode, ConnectionMode::Client);
+        assert_eq!(config.handler_batch_size, 1);
+        assert_eq!(config.handler_batch_window, Duration::from_millis(10));
+        assert!(config.retain_subjects.is_empty());
+    }
+
+    #[test]
+    fn test_from_map_retention() {
+        let mut map = HashMap::new();
+        map.insert(
+            "RETAIN_SUBJECTS".to_string(),
+            "metrics.>, status.*".to_string(),
+        );
+        map.insert("RETAIN_TTL_SEC".to_string(), "30".to_string());
+        map.insert("RETAIN_CLEAR_ON_EMPTY".to_string(), "true".to_string());
+
+        let config = ConnectionConfig::from_map(&map).unwrap();
+        assert_eq!(config.retain_subjects, vec!["metrics.>", "status.*"]);
+        assert_eq!(config.retain_ttl, Duration::from_secs(30));
+        assert!(config.retain_clear_on_empty);
+        assert_eq!(config.retain_max_entries, 1000);
     }
 
     #[test]
@@ -156,39 +2617,356 @@ mod tests {
         assert_eq!(config.mode, ConnectionMode::Server);
         assert_eq!(config.uri, "0.0.0.0:9090");
         assert_eq!(config.auth_token, Some("secret123".to_string()));
-        assert_eq!(config.connect_timeout_sec, 60);
+        assert_eq!(config.connect_timeout, Duration::from_secs(60));
         assert_eq!(
             config.custom_headers.get("Authorization"),
             Some(&"Bearer token".to_string())
         );
     }
 
+    #[test]
+    fn test_from_map_rejects_invalid_headers() {
+        let map = HashMap::from([("HEADER_X Tenant".to_string(), "acme".to_string())]);
+        let error = ConnectionConfig::from_map(&map).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "HEADER_X Tenant is not a valid HTTP header name"
+        );
+
+        let map = HashMap::from([("HEADER_X-Tenant-Id".to_string(), "a\r\nb".to_string())]);
+        let error = ConnectionConfig::from_map(&map).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Value of HEADER_X-Tenant-Id is not a valid HTTP header value"
+        );
+    }
+
+    #[test]
+    fn test_from_map_subprotocols() {
+        let map = HashMap::from([(
+            "SUBPROTOCOLS".to_string(),
+            " graphql-transport-ws, ,acme.v2 ".to_string(),
+        )]);
+        let config = ConnectionConfig::from_map(&map).unwrap();
+        assert_eq!(config.subprotocols, vec!["graphql-transport-ws", "acme.v2"]);
+
+        let map = HashMap::from([("SUBPROTOCOLS".to_string(), "acme v2".to_string())]);
+        let error = ConnectionConfig::from_map(&map).unwrap_err();
+        assert!(error.to_string().contains("\"acme v2\""), "{}", error);
+    }
+
+    #[test]
+    fn test_codec_per_link() {
+        let defaults = ConnectionConfig::from_map(&HashMap::from([(
+            "CODEC".to_string(),
+            "msgpack".to_string(),
+        )]))
+        .unwrap();
+        assert_eq!(defaults.codec, Codec::Msgpack);
+
+        // A link that doesn't set CODEC keeps the provider's
+        let link = ConnectionConfig::from_map(&HashMap::new()).unwrap();
+        assert_eq!(defaults.merge(&link).codec, Codec::Msgpack);
+        let link = ConnectionConfig::from_map(&HashMap::from([(
+            "BODY_COMPRESSION".to_string(),
+            "zstd".to_string(),
+        )]))
+        .unwrap();
+        let merged = ConnectionConfig::default().merge(&link);
+        assert_eq!(merged.codec, Codec::Json);
+        assert_eq!(merged.body_compression, BodyCompression::Zstd);
+
+        // COMPRESSION names body compression or the frame extension, per link
+        for (value, body_compression, permessage_deflate) in [
+            ("gzip", BodyCompression::Gzip, false),
+            ("zstd", BodyCompression::Zstd, false),
+            ("permessage-deflate", BodyCompression::None, true),
+        ] {
+            let link = ConnectionConfig::from_map(&HashMap::from([(
+                "COMPRESSION".to_string(),
+                value.to_string(),
+            )]))
+            .unwrap();
+            let merged = defaults.merge(&link);
+            assert_eq!(merged.codec, Codec::Msgpack);
+            assert_eq!(merged.body_compression, body_compression, "{}", value);
+            assert_eq!(merged.permessage_deflate, permessage_deflate, "{}", value);
+        }
+
+        let map = HashMap::from([("CODEC".to_string(), "cbor".to_string())]);
+        let error = ConnectionConfig::from_map(&map).unwrap_err();
+        assert_eq!(error.to_string(), "CODEC must be json or msgpack, got cbor");
+    }
+
+    #[test]
+    fn test_permessage_deflate_per_link() {
+        let defaults = ConnectionConfig::from_map(&HashMap::new()).unwrap();
+        assert!(!defaults.permessage_deflate);
+        let map = HashMap::from([("PERMESSAGE_DEFLATE".to_string(), "true".to_string())]);
+        let link = ConnectionConfig::from_map(&map).unwrap();
+        let merged = defaults.merge(&link);
+        assert!(merged.permessage_deflate);
+        assert_eq!(
+            LinkUpdate::between(&defaults, &merged).action,
+            LinkUpdateAction::Reconnected
+        );
+
+        // The deprecated COMPRESSION=permessage-deflate still enables it
+        let map = HashMap::from([("COMPRESSION".to_string(), "permessage-deflate".to_string())]);
+        assert!(ConnectionConfig::from_map(&map).unwrap().permessage_deflate);
+        assert_eq!(
+            ConnectionConfig::validate_map(&map).deprecated,
+            vec![("COMPRESSION".to_string(), "PERMESSAGE_DEFLATE".to_string())]
+        );
+
+        let map = HashMap::from([("COMPRESSION".to_string(), "brotli".to_string())]);
+        let error = ConnectionConfig::from_map(&map).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "COMPRESSION must be none, zstd, gzip or permessage-deflate, got brotli"
+        );
+    }
+
+    #[test]
+    fn test_uri_list() {
+        let map = HashMap::from([
+            ("URI".to_string(), "ws://a/ws,ws://b/ws".to_string()),
+            ("FAILOVER_STRATEGY".to_string(), "Random".to_string()),
+        ]);
+        let config = ConnectionConfig::from_map(&map).unwrap();
+        assert_eq!(config.uri, "ws://a/ws,ws://b/ws");
+        assert_eq!(config.failover_strategy, FailoverStrategy::Random);
+
+        let map = HashMap::from([("FAILOVER_STRATEGY".to_string(), "nearest".to_string())]);
+        let error = ConnectionConfig::from_map(&map).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "FAILOVER_STRATEGY must be ordered or random, got nearest"
+        );
+
+        let map = HashMap::from([
+            ("MODE".to_string(), "server".to_string()),
+            ("URI".to_string(), "0.0.0.0:1,0.0.0.0:2".to_string()),
+        ]);
+        assert!(ConnectionConfig::from_map(&map).is_err());
+    }
+
     #[test]
     fn test_merge() {
         let config1 = ConnectionConfig {
             mode: ConnectionMode::Client,
             uri: "ws://localhost:8080".to_string(),
             auth_token: Some("token1".to_string()),
-            connect_timeout_sec: 30,
+            connect_timeout: Duration::from_secs(30),
             enable_session_tracking: true,
             custom_headers: HashMap::from([("X-Custom".to_string(), "value1".to_string())]),
+            ..Default::default()
         };
 
         let config2 = ConnectionConfig {
             mode: ConnectionMode::Server,
             uri: "0.0.0.0:9090".to_string(),
             auth_token: None,
-            connect_timeout_sec: 60,
+            connect_timeout: Duration::from_secs(60),
             enable_session_tracking: false,
             custom_headers: HashMap::from([("X-Other".to_string(), "value2".to_string())]),
+            ..Default::default()
         };
 
         let merged = config1.merge(&config2);
         assert_eq!(merged.mode, ConnectionMode::Server);
         assert_eq!(merged.uri, "0.0.0.0:9090");
         assert_eq!(merged.auth_token, Some("token1".to_string()));
-        assert_eq!(merged.connect_timeout_sec, 60);
+        assert_eq!(merged.connect_timeout, Duration::from_secs(60));
         assert!(!merged.enable_session_tracking);
         assert_eq!(merged.custom_headers.len(), 2);
     }
+
+    #[test]
+    fn test_redacted_hides_secrets() {
+        let mut map = HashMap::new();
+        map.insert("AUTH_TOKEN".to_string(), "secret123".to_string());
+        map.insert(
+            "HEADER_Authorization".to_string(),
+            "Bearer token".to_string(),
+        );
+        map.insert("MESSAGE_HMAC_KEY".to_string(), "hmac-key".to_string());
+        map.insert("TENANT_TOKENS".to_string(), "acme=tenant-token".to_string());
+
+        let redacted = ConnectionConfig::from_map(&map).unwrap().redacted();
+        assert_eq!(redacted.auth_token.as_deref(), Some(REDACTED));
+        assert_eq!(redacted.message_hmac_key.as_deref(), Some(REDACTED));
+        assert_eq!(
+            redacted
+                .custom_headers
+                .get("Authorization")
+                .map(String::as_str),
+            Some(REDACTED)
+        );
+        assert!(!format!("{:?}", redacted).contains("secret123"));
+        assert!(!format!("{:?}", redacted).contains("hmac-key"));
+        assert_eq!(redacted.tenant_tokens["acme"], REDACTED);
+    }
+
+    #[test]
+    fn test_link_summary_key_sources() {
+        let mut link = HashMap::new();
+        link.insert("URI".to_string(), "ws://link:9000".to_string());
+        link.insert("HEADER_X-Trace".to_string(), "1".to_string());
+        link.insert("UNRELATED".to_string(), "ignored".to_string());
+
+        let effective = ConnectionConfig::from_map(&HashMap::new())
+            .unwrap()
+            .merge(&ConnectionConfig::from_map(&link).unwrap());
+        let summary = LinkConfigSummary::new("comp", &link, &effective);
+
+        assert_eq!(summary.from_link, vec!["HEADER_X-Trace", "URI"]);
+        assert!(summary.from_default.contains(&"MODE".to_string()));
+        assert!(!summary.from_default.contains(&"URI".to_string()));
+        assert_eq!(summary.effective.uri, "ws://link:9000");
+        assert!(summary.to_string().contains("uri=ws://link:9000"));
+    }
+
+    #[test]
+    fn test_validate_map_reports_unknown_and_deprecated() {
+        let map = HashMap::from([
+            ("URI".to_string(), "ws://a:1".to_string()),
+            ("TIMEOUT_SEC".to_string(), "5".to_string()),
+            ("HEADER_X-Trace".to_string(), "1".to_string()),
+            ("JOB_RECONCILE_INTERVAL_SEC".to_string(), "5".to_string()),
+            ("JOB__INTERVAL_SEC".to_string(), "5".to_string()),
+            ("LINK_orders_URI".to_string(), "ws://b:1".to_string()),
+            ("RETRY_COUNT".to_string(), "3".to_string()),
+        ]);
+        let report = ConnectionConfig::validate_map(&map);
+        assert_eq!(report.unknown, vec!["JOB__INTERVAL_SEC", "RETRY_COUNT"]);
+        assert_eq!(
+            report.deprecated,
+            vec![("TIMEOUT_SEC".to_string(), "CONNECT_TIMEOUT_SEC".to_string())]
+        );
+        assert!(!report.is_clean());
+
+        // Unknown keys are ignored unless strict
+        #[cfg(not(feature = "strict-config"))]
+        assert!(ConnectionConfig::from_map(&map).is_ok());
+    }
+
+    #[test]
+    fn test_strict_config_rejects_unknown_keys() {
+        let map = HashMap::from([
+            ("STRICT_CONFIG".to_string(), "true".to_string()),
+            ("RETRY_COUNT".to_string(), "3".to_string()),
+        ]);
+        let err = ConnectionConfig::from_map(&map).unwrap_err();
+        assert!(err.to_string().contains("RETRY_COUNT"), "{}", err);
+    }
+
+    #[test]
+    fn test_deprecated_alias_takes_effect() {
+        let map = HashMap::from([
+            ("WS_URI".to_string(), "ws://old:1".to_string()),
+            ("TIMEOUT_SEC".to_string(), "5".to_string()),
+            ("CONNECT_TIMEOUT_SEC".to_string(), "7".to_string()),
+            ("STRICT_CONFIG".to_string(), "true".to_string()),
+        ]);
+        let config = ConnectionConfig::from_map(&map).unwrap();
+        assert_eq!(config.uri, "ws://old:1");
+        // The current key wins over its alias
+        assert_eq!(config.connect_timeout, Duration::from_secs(7));
+
+        let summary = LinkConfigSummary::new("comp", &map, &config);
+        assert!(summary.from_link.contains(&"URI".to_string()));
+    }
+
+    #[test]
+    fn test_handler_timeouts_per_subject() {
+        let map = HashMap::from([
+            ("DELIVERY_TIMEOUT_MS".to_string(), "1000".to_string()),
+            (
+                "HANDLER_TIMEOUT_MS".to_string(),
+                "reports.>=30000, ping=50".to_string(),
+            ),
+        ]);
+        let config = ConnectionConfig::from_map(&map).unwrap();
+        assert_eq!(
+            config.delivery_timeout_for("reports.daily"),
+            Duration::from_millis(30000)
+        );
+        assert_eq!(
+            config.delivery_timeout_for("ping"),
+            Duration::from_millis(50)
+        );
+        assert_eq!(
+            config.delivery_timeout_for("orders.new"),
+            Duration::from_millis(1000)
+        );
+
+        let bare = HashMap::from([("HANDLER_TIMEOUT_MS".to_string(), "200".to_string())]);
+        let config = ConnectionConfig::from_map(&bare).unwrap();
+        assert_eq!(
+            config.delivery_timeout_for("any.thing"),
+            Duration::from_millis(200)
+        );
+
+        let invalid = HashMap::from([("HANDLER_TIMEOUT_MS".to_string(), "ping=soon".to_string())]);
+        assert!(ConnectionConfig::from_map(&invalid).is_err());
+
+        // Entries may also be humantime values
+        let humantime = HashMap::from([(
+            "HANDLER_TIMEOUT_MS".to_string(),
+            "reports.>=30s".to_string(),
+        )]);
+        let config = ConnectionConfig::from_map(&humantime).unwrap();
+        assert_eq!(
+            config.delivery_timeout_for("reports.daily"),
+            Duration::from_secs(30)
+        );
+    }
+
+    #[test]
+    fn test_duration_keys() {
+        let map = HashMap::from([
+            ("CONNECT_TIMEOUT".to_string(), "500ms".to_string()),
+            ("IDLE_TIMEOUT".to_string(), "5m".to_string()),
+            ("HEARTBEAT_INTERVAL_MS".to_string(), "1500".to_string()),
+            ("RETAIN_TTL_SEC".to_string(), "90".to_string()),
+            ("JOB_RECONCILE_INTERVAL".to_string(), "30s".to_string()),
+            ("JOB_CLEANUP_INTERVAL_SEC".to_string(), "2".to_string()),
+            ("STRICT_CONFIG".to_string(), "true".to_string()),
+        ]);
+        let config = ConnectionConfig::from_map(&map).unwrap();
+        assert_eq!(config.connect_timeout, Duration::from_millis(500));
+        assert_eq!(config.idle_timeout, Duration::from_secs(300));
+        assert_eq!(config.heartbeat_interval, Duration::from_millis(1500));
+        assert_eq!(config.retain_ttl, Duration::from_secs(90));
+        assert_eq!(config.job_intervals["reconcile"], Duration::from_secs(30));
+        assert_eq!(config.job_intervals["cleanup"], Duration::from_secs(2));
+
+        let map = HashMap::from([("IDLE_TIMEOUT".to_string(), "30".to_string())]);
+        let error = ConnectionConfig::from_map(&map).unwrap_err();
+        assert_eq!(error.to_string(), "Invalid IDLE_TIMEOUT");
+    }
+
+    #[test]
+    fn test_link_update_classification() {
+        let current = ConnectionConfig::default();
+        let update = |pairs: &[(&str, &str)]| {
+            let map = pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect();
+            LinkUpdate::between(&current, &ConnectionConfig::from_map(&map).unwrap())
+        };
+
+        assert_eq!(update(&[]).action, LinkUpdateAction::Unchanged);
+
+        let in_place = update(&[("PUBLISH_DENY", "secret.>"), ("ROUTE_PRIORITY", "5")]);
+        assert_eq!(in_place.action, LinkUpdateAction::InPlace);
+        assert_eq!(in_place.changed, vec!["publish_deny", "route_priority"]);
+
+        // One socket-level change among them forces a new connection
+        let reconnect = update(&[("PUBLISH_DENY", "secret.>"), ("HEADER_X-Team", "a")]);
+        assert_eq!(reconnect.action, LinkUpdateAction::Reconnected);
+        assert_eq!(reconnect.changed, vec!["custom_headers", "publish_deny"]);
+    }
 }
@@ -0,0 +1,256 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use tokio_tungstenite::tungstenite::Message;
+use tracing::warn;
+
+use crate::clock::WallClock;
+use crate::events::{EventBus, ProviderEvent};
+use crate::tenant::DeadLetterReason;
+use crate::BrokerMessage;
+
+/// Header carrying the time after which a message is no longer worth delivering, in
+/// milliseconds since the Unix epoch
+pub const DEADLINE_HEADER: &str = "deadline_unix_ms";
+
+/// Error returned when a message is published after its `deadline_unix_ms`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadlineExceeded {
+    pub subject: String,
+    pub deadline_unix_ms: u64,
+    pub now_unix_ms: u64,
+}
+
+impl std::fmt::Display for DeadlineExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Message on {} expired {} ms ago",
+            self.subject,
+            self.now_unix_ms.saturating_sub(self.deadline_unix_ms)
+        )
+    }
+}
+
+impl std::error::Error for DeadlineExceeded {}
+
+/// The deadline a message carries, if its header holds a valid one
+pub(crate) fn deadline_of(msg: &BrokerMessage) -> Option<u64> {
+    msg.headers.get(DEADLINE_HEADER)?.trim().parse().ok()
+}
+
+/// The deadline in an encoded envelope, if any
+///
+/// Only envelopes mentioning the header are parsed, so frames without a deadline cost a
+/// substring search.
+fn envelope_deadline(text: &str) -> Option<u64> {
+    if !text.contains(DEADLINE_HEADER) {
+        return None;
+    }
+    let json: serde_json::Value = serde_json::from_str(text).ok()?;
+    json.get("headers")?
+        .get(DEADLINE_HEADER)?
+        .as_str()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Enforces message deadlines against the provider's clock and counts what expired
+///
+/// The clock is a `WallClock`, so setting the system clock back doesn't revive expired
+/// messages.
+#[derive(Debug, Clone)]
+pub(crate) struct Deadlines {
+    clock: WallClock,
+    expired: Arc<AtomicU64>,
+    events: EventBus,
+}
+
+impl Default for Deadlines {
+    fn default() -> Self {
+        Self::new(EventBus::default())
+    }
+}
+
+impl Deadlines {
+    /// Deadlines on the system clock, dead-lettering expired inbound messages on `events`
+    pub(crate) fn new(events: EventBus) -> Self {
+        Self {
+            clock: WallClock::default(),
+            expired: Arc::default(),
+            events,
+        }
+    }
+
+    /// Check deadlines against `clock` instead of the system clock
+    pub(crate) fn with_clock(
+        mut self,
+        clock: impl Fn() -> SystemTime + Send + Sync + 'static,
+    ) -> Self {
+        self.clock = WallClock::new(clock);
+        self
+    }
+
+    fn now_unix_ms(&self) -> u64 {
+        self.clock.now_unix_ms()
+    }
+
+    /// Messages dropped so far for being past their deadline
+    pub(crate) fn expired_count(&self) -> u64 {
+        self.expired.load(Ordering::Relaxed)
+    }
+
+    /// Whether a message with `deadline` is past it
+    fn is_past(&self, deadline: Option<u64>) -> bool {
+        deadline.is_some_and(|deadline| deadline <= self.now_unix_ms())
+    }
+
+    /// Whether `msg` is past its deadline
+    pub(crate) fn is_expired(&self, msg: &BrokerMessage) -> bool {
+        self.is_past(deadline_of(msg))
+    }
+
+    /// Fail with `DeadlineExceeded` if `msg` is already past its deadline
+    pub(crate) fn check(&self, msg: &BrokerMessage) -> Result<(), DeadlineExceeded> {
+        let Some(deadline_unix_ms) = deadline_of(msg) else {
+            return Ok(());
+        };
+        let now_unix_ms = self.now_unix_ms();
+        if deadline_unix_ms <= now_unix_ms {
+            self.expired.fetch_add(1, Ordering::Relaxed);
+            return Err(DeadlineExceeded {
+                subject: msg.subject.clone(),
+                deadline_unix_ms,
+                now_unix_ms,
+            });
+        }
+        Ok(())
+    }
+
+    /// Whether a queued envelope should still be written; counts it if not
+    pub(crate) fn admit_envelope(&self, text: &str) -> bool {
+        if self.is_past(envelope_deadline(text)) {
+            self.expired.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        true
+    }
+
+    /// Whether a queued frame should still be written; counts it if not
+    pub(crate) fn admit_frame(&self, frame: &Message) -> bool {
+        match frame {
+            Message::Text(text) => self.admit_envelope(text),
+            _ => true,
+        }
+    }
+
+    /// Whether an inbound message should still be delivered; counts and dead-letters it
+    /// if not
+    pub(crate) fn admit_inbound(
+        &self,
+        session_id: &str,
+        tenant: Option<&str>,
+        msg: &BrokerMessage,
+    ) -> bool {
+        if !self.is_expired(msg) {
+            return true;
+        }
+        self.expired.fetch_add(1, Ordering::Relaxed);
+        let reason = DeadLetterReason::DeadlineExceeded;
+        warn!(
+            "Dead-lettered message on {} from {}: {}",
+            msg.subject,
+            session_id,
+            reason.as_str()
+        );
+        self.events.emit(ProviderEvent::DeadLetter {
+            session_id: session_id.to_string(),
+            tenant: tenant.map(str::to_string),
+            subject: msg.subject.clone(),
+            reason,
+        });
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use std::collections::HashMap;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn message(deadline: Option<&str>) -> BrokerMessage {
+        BrokerMessage {
+            subject: "jobs.run".to_string(),
+            body: Bytes::from("x"),
+            reply_to: None,
+            headers: deadline
+                .map(|deadline| {
+                    HashMap::from([(DEADLINE_HEADER.to_string(), deadline.to_string())])
+                })
+                .unwrap_or_default(),
+        }
+    }
+
+    fn at(unix_ms: u64) -> Deadlines {
+        Deadlines::default().with_clock(move || UNIX_EPOCH + Duration::from_millis(unix_ms))
+    }
+
+    #[test]
+    fn test_check_against_clock() {
+        let deadlines = at(1_000);
+        assert!(deadlines.check(&message(None)).is_ok());
+        assert!(deadlines.check(&message(Some("1001"))).is_ok());
+        // Unparseable deadlines are ignored
+        assert!(deadlines.check(&message(Some("soon"))).is_ok());
+        assert_eq!(deadlines.expired_count(), 0);
+
+        let err = deadlines.check(&message(Some("1000"))).unwrap_err();
+        assert_eq!(err.deadline_unix_ms, 1_000);
+        assert_eq!(err.now_unix_ms, 1_000);
+        assert_eq!(deadlines.expired_count(), 1);
+    }
+
+    #[test]
+    fn test_queued_frame_deadline() {
+        let frame = |msg: &BrokerMessage| {
+            crate::WebSocketMessagingProvider::encode_message_static(msg).unwrap()
+        };
+        let envelope = |msg: &BrokerMessage| frame(msg).into_text().unwrap();
+        assert_eq!(
+            envelope_deadline(&envelope(&message(Some("1500")))),
+            Some(1_500)
+        );
+        assert_eq!(envelope_deadline(&envelope(&message(None))), None);
+
+        let deadlines = at(2_000);
+        assert!(!deadlines.admit_frame(&frame(&message(Some("1500")))));
+        assert!(deadlines.admit_frame(&frame(&message(Some("2500")))));
+        assert!(deadlines.admit_frame(&Message::Binary(vec![1, 2])));
+        assert_eq!(deadlines.expired_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_expired_inbound_dead_lettered() {
+        let events = EventBus::default();
+        let mut rx = events.subscribe();
+        let deadlines =
+            Deadlines::new(events).with_clock(|| UNIX_EPOCH + Duration::from_millis(5_000));
+
+        assert!(deadlines.admit_inbound("s1", None, &message(Some("6000"))));
+        assert!(!deadlines.admit_inbound("s1", Some("acme"), &message(Some("4000"))));
+        assert_eq!(deadlines.expired_count(), 1);
+        assert_eq!(
+            rx.recv().await.unwrap(),
+            ProviderEvent::DeadLetter {
+                session_id: "s1".to_string(),
+                tenant: Some("acme".to_string()),
+                subject: "jobs.run".to_string(),
+                reason: DeadLetterReason::DeadlineExceeded,
+            }
+        );
+    }
+}
@@ -0,0 +1,746 @@
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::close::CloseReason;
+use crate::connection::ConnectionConfig;
+
+/// Name of the extension in `Sec-WebSocket-Extensions`
+pub(crate) const PERMESSAGE_DEFLATE: &str = "permessage-deflate";
+
+/// Trailer of a sync-flushed deflate block, left off compressed messages (RFC 7692 7.2.1)
+const SYNC_TAIL: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+/// Processed outbound bytes held before writes wait for the socket
+const WRITE_HIGH_WATER: usize = 1 << 20;
+
+const READ_CHUNK: usize = 16 * 1024;
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+
+/// Sizes a `DeflateStream` lets inbound messages reach before closing with 1009
+/// (0 = no limit)
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct DeflateLimits {
+    /// Largest frame, and largest compressed message summed over its fragments
+    pub(crate) max_frame_bytes: usize,
+    /// Largest message a compressed one may inflate to
+    pub(crate) max_inflated_bytes: usize,
+}
+
+impl DeflateLimits {
+    /// Frames are held to `MAX_MESSAGE_BYTES`, and inflated messages to the smaller of
+    /// it and `MAX_DECOMPRESSED_BYTES`
+    pub(crate) fn from_config(config: &ConnectionConfig) -> Self {
+        let max_inflated_bytes = match (config.max_message_bytes, config.max_decompressed_bytes) {
+            (0, limit) | (limit, 0) => limit,
+            (message, decompressed) => message.min(decompressed),
+        };
+        Self {
+            max_frame_bytes: config.max_message_bytes,
+            max_inflated_bytes,
+        }
+    }
+}
+
+fn exceeds(limit: usize, len: usize) -> bool {
+    limit > 0 && len > limit
+}
+
+/// Whether the server accepted `permessage-deflate` in its upgrade response headers
+pub(crate) fn negotiated(extensions: Option<&str>) -> bool {
+    extensions.is_some_and(|value| {
+        value
+            .split(',')
+            .any(|extension| extension_name(extension) == PERMESSAGE_DEFLATE)
+    })
+}
+
+fn extension_name(extension: &str) -> &str {
+    extension.split(';').next().unwrap_or_default().trim()
+}
+
+/// Compression state of a connection that negotiated `permessage-deflate`
+struct Deflate {
+    deflate: Compress,
+    inflate: Decompress,
+    /// The server asked us to compress every message on its own
+    client_no_context_takeover: bool,
+    server_no_context_takeover: bool,
+}
+
+impl Deflate {
+    /// Compression agreed in a `Sec-WebSocket-Extensions` response value; fails on
+    /// parameters that weren't offered, since the connection can't honour them
+    fn accept(value: &str) -> io::Result<Option<Self>> {
+        let Some(extension) = value
+            .split(',')
+            .find(|extension| extension_name(extension) == PERMESSAGE_DEFLATE)
+        else {
+            return Ok(None);
+        };
+        let mut deflate = Self {
+            deflate: Compress::new(Compression::default(), false),
+            inflate: Decompress::new(false),
+            client_no_context_takeover: false,
+            server_no_context_takeover: false,
+        };
+        for param in extension.split(';').skip(1) {
+            let name = param.split('=').next().unwrap_or_default().trim();
+            match name {
+                "client_no_context_takeover" => deflate.client_no_context_takeover = true,
+                "server_no_context_takeover" => deflate.server_no_context_takeover = true,
+                // A smaller server window still inflates with the full one
+                "server_max_window_bits" => {}
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "Server accepted {} with {}, which was not offered",
+                            PERMESSAGE_DEFLATE, name
+                        ),
+                    ))
+                }
+            }
+        }
+        Ok(Some(deflate))
+    }
+
+    fn compress(&mut self, data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut out = Vec::with_capacity(data.len() / 2 + 64);
+        let mut offset = 0;
+        loop {
+            let before = self.deflate.total_in();
+            self.deflate
+                .compress_vec(&data[offset..], &mut out, FlushCompress::Sync)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            offset += (self.deflate.total_in() - before) as usize;
+            if offset >= data.len() && out.len() < out.capacity() {
+                break;
+            }
+            out.reserve(out.capacity().max(READ_CHUNK));
+        }
+        if out.ends_with(&SYNC_TAIL) {
+            out.truncate(out.len() - SYNC_TAIL.len());
+        }
+        if self.client_no_context_takeover {
+            self.deflate.reset();
+        }
+        Ok(out)
+    }
+
+    /// The inflated message, or `None` once it grows past `limit` (0 = no limit)
+    fn decompress(&mut self, data: &[u8], limit: usize) -> io::Result<Option<Vec<u8>>> {
+        let mut input = Vec::with_capacity(data.len() + SYNC_TAIL.len());
+        input.extend_from_slice(data);
+        input.extend_from_slice(&SYNC_TAIL);
+        let mut out = Vec::with_capacity(input.len() * 4);
+        let mut offset = 0;
+        loop {
+            let (before_in, before_out) = (self.inflate.total_in(), self.inflate.total_out());
+            self.inflate
+                .decompress_vec(&input[offset..], &mut out, FlushDecompress::Sync)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            offset += (self.inflate.total_in() - before_in) as usize;
+            if exceeds(limit, out.len()) {
+                return Ok(None);
+            }
+            let stalled =
+                self.inflate.total_in() == before_in && self.inflate.total_out() == before_out;
+            if out.len() < out.capacity() && (offset >= input.len() || stalled) {
+                break;
+            }
+            out.reserve(out.capacity().max(READ_CHUNK));
+        }
+        if self.server_no_context_takeover {
+            self.inflate.reset(false);
+        }
+        Ok(Some(out))
+    }
+}
+
+/// A frame header, with the length of the header itself
+struct Header {
+    fin: bool,
+    rsv1: bool,
+    opcode: u8,
+    mask: Option<[u8; 4]>,
+    len: usize,
+    payload_len: usize,
+}
+
+impl Header {
+    /// The header at the start of `buf`, if all of it has arrived
+    fn parse(buf: &[u8]) -> Option<Self> {
+        let (&first, &second) = (buf.first()?, buf.get(1)?);
+        let (payload_len, mut len) = match second & 0x7f {
+            126 => (
+                u16::from_be_bytes(buf.get(2..4)?.try_into().ok()?) as usize,
+                4,
+            ),
+            127 => (
+                u64::from_be_bytes(buf.get(2..10)?.try_into().ok()?) as usize,
+                10,
+            ),
+            short => (short as usize, 2),
+        };
+        let mask = if second & 0x80 != 0 {
+            let mask = buf.get(len..len + 4)?.try_into().ok()?;
+            len += 4;
+            Some(mask)
+        } else {
+            None
+        };
+        Some(Self {
+            fin: first & 0x80 != 0,
+            rsv1: first & 0x40 != 0,
+            opcode: first & 0x0f,
+            mask,
+            len,
+            payload_len,
+        })
+    }
+}
+
+fn apply_mask(payload: &mut [u8], mask: [u8; 4]) {
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+}
+
+fn write_frame(
+    out: &mut Vec<u8>,
+    fin: bool,
+    rsv1: bool,
+    opcode: u8,
+    mask: Option<[u8; 4]>,
+    payload: &[u8],
+) {
+    out.push((u8::from(fin) << 7) | (u8::from(rsv1) << 6) | opcode);
+    let mask_bit = if mask.is_some() { 0x80 } else { 0 };
+    match payload.len() {
+        len @ 0..=125 => out.push(mask_bit | len as u8),
+        len @ 126..=0xffff => {
+            out.push(mask_bit | 126);
+            out.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            out.push(mask_bit | 127);
+            out.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    match mask {
+        Some(mask) => {
+            out.extend_from_slice(&mask);
+            let start = out.len();
+            out.extend_from_slice(payload);
+            apply_mask(&mut out[start..], mask);
+        }
+        None => out.extend_from_slice(payload),
+    }
+}
+
+/// Length of the HTTP head at the start of `buf`, including its blank line
+fn head_len(buf: &[u8]) -> Option<usize> {
+    buf.windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .map(|at| at + 4)
+}
+
+/// A client connection's socket, speaking `permessage-deflate` under tungstenite
+///
+/// tungstenite has no extension support and rejects frames with RSV1 set, so this sits
+/// between it and the socket: it reads the `Sec-WebSocket-Extensions` the server
+/// accepted from the upgrade response as it passes, then compresses outbound data
+/// frames and inflates inbound compressed messages into plain frames. A server that
+/// doesn't accept the extension leaves the bytes untouched. A frame or message over
+/// `limits` is never buffered: the stream sends the server a 1009 close and hands
+/// tungstenite the same close in its place, then discards everything read or written
+/// after it.
+pub(crate) struct DeflateStream<S> {
+    inner: S,
+    deflate: Option<Deflate>,
+    limits: DeflateLimits,
+    /// A message over the limits was replaced with a close
+    too_big: bool,
+    request_sent: bool,
+    response_read: bool,
+    /// Bytes read from the socket and not yet processed
+    read_raw: Vec<u8>,
+    /// Processed bytes waiting for tungstenite
+    read_ready: Vec<u8>,
+    read_pos: usize,
+    /// Opcode and payload of a compressed inbound message still missing fragments
+    fragments: Option<(u8, Vec<u8>)>,
+    /// Bytes written by tungstenite and not yet processed
+    write_raw: Vec<u8>,
+    /// Processed bytes waiting for the socket
+    write_ready: Vec<u8>,
+}
+
+impl<S> DeflateStream<S> {
+    pub(crate) fn new(inner: S, limits: DeflateLimits) -> Self {
+        Self {
+            inner,
+            deflate: None,
+            limits,
+            too_big: false,
+            request_sent: false,
+            response_read: false,
+            read_raw: Vec::new(),
+            read_ready: Vec::new(),
+            read_pos: 0,
+            fragments: None,
+            write_raw: Vec::new(),
+            write_ready: Vec::new(),
+        }
+    }
+
+    /// Move what can be processed of `read_raw` to `read_ready`
+    fn process_read(&mut self) -> io::Result<()> {
+        if self.too_big {
+            self.read_raw.clear();
+            return Ok(());
+        }
+        if !self.response_read {
+            let Some(len) = head_len(&self.read_raw) else {
+                return Ok(());
+            };
+            let head: Vec<u8> = self.read_raw.drain(..len).collect();
+            self.deflate = response_extensions(&head)
+                .map(|value| Deflate::accept(&value))
+                .transpose()?
+                .flatten();
+            self.read_ready.extend_from_slice(&head);
+            self.response_read = true;
+        }
+        let Some(deflate) = self.deflate.as_mut() else {
+            self.read_ready.append(&mut self.read_raw);
+            return Ok(());
+        };
+        let limits = self.limits;
+        let mut too_big = false;
+        let mut consumed = 0;
+        while let Some(header) = Header::parse(&self.read_raw[consumed..]) {
+            if exceeds(limits.max_frame_bytes, header.payload_len) {
+                too_big = true;
+                break;
+            }
+            let start = consumed + header.len;
+            let Some(end) = start.checked_add(header.payload_len) else {
+                break;
+            };
+            if end > self.read_raw.len() {
+                break;
+            }
+            let frame = &self.read_raw[consumed..end];
+            let mut payload = self.read_raw[start..end].to_vec();
+            if let Some(mask) = header.mask {
+                apply_mask(&mut payload, mask);
+            }
+            match header.opcode {
+                OP_TEXT | OP_BINARY if header.rsv1 && self.fragments.is_none() => {
+                    self.fragments = Some((header.opcode, payload));
+                }
+                OP_CONTINUATION if self.fragments.is_some() => {
+                    if let Some((_, message)) = self.fragments.as_mut() {
+                        message.extend_from_slice(&payload);
+                        if exceeds(limits.max_frame_bytes, message.len()) {
+                            too_big = true;
+                            break;
+                        }
+                    }
+                }
+                // Control frames, and messages the server sent uncompressed
+                _ => {
+                    self.read_ready.extend_from_slice(frame);
+                    consumed = end;
+                    continue;
+                }
+            }
+            if header.fin {
+                if let Some((opcode, message)) = self.fragments.take() {
+                    let Some(message) = deflate.decompress(&message, limits.max_inflated_bytes)?
+                    else {
+                        too_big = true;
+                        break;
+                    };
+                    write_frame(&mut self.read_ready, true, false, opcode, None, &message);
+                }
+            }
+            consumed = end;
+        }
+        if too_big {
+            self.close_too_big();
+        } else {
+            self.read_raw.drain(..consumed);
+        }
+        Ok(())
+    }
+
+    /// Close with 1009 in both directions in place of the rest of the stream
+    fn close_too_big(&mut self) {
+        let reason = CloseReason::MessageTooBig;
+        let mut payload = reason.code().to_be_bytes().to_vec();
+        payload.extend_from_slice(reason.reason().as_bytes());
+        write_frame(&mut self.read_ready, true, false, OP_CLOSE, None, &payload);
+        // Frames from a client are masked; the close is ours, so it needs a key of its own
+        let uuid = uuid::Uuid::new_v4();
+        let mask = uuid.as_bytes()[..4].try_into().ok();
+        write_frame(&mut self.write_ready, true, false, OP_CLOSE, mask, &payload);
+        self.read_raw.clear();
+        self.write_raw.clear();
+        self.fragments = None;
+        self.too_big = true;
+    }
+
+    /// Move what can be processed of `write_raw` to `write_ready`
+    fn process_write(&mut self) -> io::Result<()> {
+        if self.too_big {
+            self.write_raw.clear();
+            return Ok(());
+        }
+        if !self.request_sent {
+            let Some(len) = head_len(&self.write_raw) else {
+                return Ok(());
+            };
+            self.write_ready.extend(self.write_raw.drain(..len));
+            self.request_sent = true;
+        }
+        // tungstenite only writes frames once it has read the response
+        let Some(deflate) = self.deflate.as_mut() else {
+            self.write_ready.append(&mut self.write_raw);
+            return Ok(());
+        };
+        let mut consumed = 0;
+        while let Some(header) = Header::parse(&self.write_raw[consumed..]) {
+            let start = consumed + header.len;
+            let Some(end) = start.checked_add(header.payload_len) else {
+                break;
+            };
+            if end > self.write_raw.len() {
+                break;
+            }
+            let compressible = matches!(header.opcode, OP_TEXT | OP_BINARY)
+                && header.fin
+                && !header.rsv1
+                && header.payload_len > 0;
+            if compressible {
+                let mut payload = self.write_raw[start..end].to_vec();
+                if let Some(mask) = header.mask {
+                    apply_mask(&mut payload, mask);
+                }
+                let compressed = deflate.compress(&payload)?;
+                write_frame(
+                    &mut self.write_ready,
+                    true,
+                    true,
+                    header.opcode,
+                    header.mask,
+                    &compressed,
+                );
+            } else {
+                // Control frames and fragmented messages go out as they are
+                self.write_ready
+                    .extend_from_slice(&self.write_raw[consumed..end]);
+            }
+            consumed = end;
+        }
+        self.write_raw.drain(..consumed);
+        Ok(())
+    }
+}
+
+/// The `Sec-WebSocket-Extensions` values of an HTTP response head, joined
+fn response_extensions(head: &[u8]) -> Option<String> {
+    let head = String::from_utf8_lossy(head);
+    let values: Vec<&str> = head
+        .split("\r\n")
+        .skip(1)
+        .filter_map(|line| line.split_once(':'))
+        .filter(|(name, _)| name.trim().eq_ignore_ascii_case("sec-websocket-extensions"))
+        .map(|(_, value)| value.trim())
+        .collect();
+    (!values.is_empty()).then(|| values.join(", "))
+}
+
+impl<S: AsyncWrite + Unpin> DeflateStream<S> {
+    /// Write `write_ready` to the socket
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.write_ready.is_empty() {
+            match Pin::new(&mut self.inner).poll_write(cx, &self.write_ready) {
+                Poll::Ready(Ok(0)) => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+                Poll::Ready(Ok(written)) => {
+                    self.write_ready.drain(..written);
+                }
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncRead for DeflateStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            if this.read_pos < this.read_ready.len() {
+                let ready = &this.read_ready[this.read_pos..];
+                let len = ready.len().min(buf.remaining());
+                buf.put_slice(&ready[..len]);
+                this.read_pos += len;
+                if this.read_pos == this.read_ready.len() {
+                    this.read_ready.clear();
+                    this.read_pos = 0;
+                }
+                return Poll::Ready(Ok(()));
+            }
+
+            let mut chunk = [0u8; READ_CHUNK];
+            let mut chunk = ReadBuf::new(&mut chunk);
+            match Pin::new(&mut this.inner).poll_read(cx, &mut chunk) {
+                Poll::Ready(Ok(())) if chunk.filled().is_empty() => {
+                    // End of stream; tungstenite reports any partial frame left over
+                    if this.read_raw.is_empty() {
+                        return Poll::Ready(Ok(()));
+                    }
+                    this.read_ready.append(&mut this.read_raw);
+                }
+                Poll::Ready(Ok(())) => {
+                    this.read_raw.extend_from_slice(chunk.filled());
+                    this.process_read()?;
+                    if this.too_big {
+                        // The connection is dropped once tungstenite sees the close, so send ours now
+                        let _ = this.poll_drain(cx);
+                    }
+                }
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for DeflateStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.write_ready.len() >= WRITE_HIGH_WATER {
+            if let Poll::Ready(Err(e)) = this.poll_drain(cx) {
+                return Poll::Ready(Err(e));
+            }
+            if this.write_ready.len() >= WRITE_HIGH_WATER {
+                return Poll::Pending;
+            }
+        }
+        this.write_raw.extend_from_slice(buf);
+        this.process_write()?;
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        match this.poll_drain(cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut this.inner).poll_flush(cx),
+            other => other,
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        match this.poll_drain(cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut this.inner).poll_shutdown(cx),
+            other => other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(value: &str) -> Deflate {
+        Deflate::accept(value)
+            .unwrap()
+            .expect("extension should be accepted")
+    }
+
+    #[test]
+    fn test_negotiation() {
+        assert!(negotiated(Some(
+            "permessage-deflate; server_max_window_bits=10"
+        )));
+        assert!(!negotiated(Some("x-webkit-deflate-frame")));
+        assert!(!negotiated(None));
+
+        let deflate = session("permessage-deflate; client_no_context_takeover");
+        assert!(deflate.client_no_context_takeover);
+        assert!(Deflate::accept("x-custom").unwrap().is_none());
+        let error = Deflate::accept("permessage-deflate; client_max_window_bits=9")
+            .err()
+            .expect("unoffered parameter should fail");
+        assert!(
+            error.to_string().contains("client_max_window_bits"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_compress_round_trip() {
+        // Both ends keep their context, so later messages can refer back to earlier ones
+        let mut client = session("permessage-deflate");
+        let mut server = session("permessage-deflate");
+        for _ in 0..3 {
+            let message = "{\"subject\":\"orders.new\"}".repeat(500);
+            let compressed = client.compress(message.as_bytes()).unwrap();
+            assert!(compressed.len() < message.len() / 10);
+            assert!(!compressed.ends_with(&SYNC_TAIL));
+            assert_eq!(
+                server.decompress(&compressed, 0).unwrap().unwrap(),
+                message.as_bytes()
+            );
+        }
+    }
+
+    #[test]
+    fn test_frames_rewritten() {
+        let mut stream = DeflateStream::new(Vec::<u8>::new(), DeflateLimits::default());
+        let head = b"HTTP/1.1 101 Switching Protocols\r\nSec-WebSocket-Extensions: permessage-deflate\r\n\r\n";
+        stream.read_raw.extend_from_slice(head);
+
+        // A compressed text message split over two frames, with a ping in between
+        let message = b"hello hello hello hello";
+        let compressed = session("permessage-deflate").compress(message).unwrap();
+        let (first, rest) = compressed.split_at(compressed.len() / 2);
+        write_frame(&mut stream.read_raw, false, true, OP_TEXT, None, first);
+        write_frame(&mut stream.read_raw, true, false, 0x9, None, b"p");
+        write_frame(
+            &mut stream.read_raw,
+            true,
+            false,
+            OP_CONTINUATION,
+            None,
+            rest,
+        );
+        stream.process_read().unwrap();
+
+        let mut expected = head.to_vec();
+        write_frame(&mut expected, true, false, 0x9, None, b"p");
+        write_frame(&mut expected, true, false, OP_TEXT, None, message);
+        assert_eq!(stream.read_ready, expected);
+        assert!(stream.read_raw.is_empty());
+
+        // Outbound data frames are compressed and keep their mask
+        stream
+            .write_raw
+            .extend_from_slice(b"GET / HTTP/1.1\r\n\r\n");
+        let mask = [1, 2, 3, 4];
+        write_frame(
+            &mut stream.write_raw,
+            true,
+            false,
+            OP_BINARY,
+            Some(mask),
+            message,
+        );
+        stream.process_write().unwrap();
+        let frames = &stream.write_ready[b"GET / HTTP/1.1\r\n\r\n".len()..];
+        let header = Header::parse(frames).unwrap();
+        assert!(header.rsv1 && header.fin);
+        assert_eq!(header.mask, Some(mask));
+        let mut payload = frames[header.len..].to_vec();
+        apply_mask(&mut payload, mask);
+        assert_eq!(
+            session("permessage-deflate")
+                .decompress(&payload, 0)
+                .unwrap()
+                .unwrap(),
+            message
+        );
+    }
+
+    #[test]
+    fn test_oversized_messages_closed() {
+        let head = b"HTTP/1.1 101 Switching Protocols\r\nSec-WebSocket-Extensions: permessage-deflate\r\n\r\n";
+        let limits = DeflateLimits {
+            max_frame_bytes: 64,
+            max_inflated_bytes: 1024,
+        };
+        let mut close = head.to_vec();
+        let mut payload = 1009u16.to_be_bytes().to_vec();
+        payload.extend_from_slice(b"message too big");
+        write_frame(&mut close, true, false, OP_CLOSE, None, &payload);
+
+        // A frame over the limit is refused from its header, before its payload arrives
+        let mut stream = DeflateStream::new(Vec::<u8>::new(), limits);
+        stream.read_raw.extend_from_slice(head);
+        let mut frame = Vec::new();
+        write_frame(&mut frame, true, false, OP_TEXT, None, &[b'x'; 65]);
+        stream.read_raw.extend_from_slice(&frame[..4]);
+        stream.process_read().unwrap();
+        assert_eq!(stream.read_ready, close);
+        stream.read_raw.extend_from_slice(&frame[4..]);
+        stream.process_read().unwrap();
+        assert!(stream.read_raw.is_empty());
+        assert_eq!(stream.read_ready, close);
+
+        // The server is sent the same close, masked, and nothing after it
+        let header = Header::parse(&stream.write_ready).unwrap();
+        assert_eq!(header.opcode, OP_CLOSE);
+        let mut sent = stream.write_ready[header.len..].to_vec();
+        apply_mask(&mut sent, header.mask.expect("client frames are masked"));
+        assert_eq!(sent, payload);
+        let before = stream.write_ready.len();
+        write_frame(
+            &mut stream.write_raw,
+            true,
+            false,
+            OP_CLOSE,
+            Some([0; 4]),
+            &payload,
+        );
+        stream.process_write().unwrap();
+        assert_eq!(stream.write_ready.len(), before);
+
+        // Fragments that each fit but together don't
+        let mut stream = DeflateStream::new(Vec::<u8>::new(), limits);
+        stream.read_raw.extend_from_slice(head);
+        write_frame(&mut stream.read_raw, false, true, OP_TEXT, None, &[0; 40]);
+        write_frame(
+            &mut stream.read_raw,
+            false,
+            false,
+            OP_CONTINUATION,
+            None,
+            &[0; 40],
+        );
+        stream.process_read().unwrap();
+        assert_eq!(stream.read_ready, close);
+
+        // A small compressed message inflating past its limit
+        let mut stream = DeflateStream::new(Vec::<u8>::new(), limits);
+        stream.read_raw.extend_from_slice(head);
+        let compressed = session("permessage-deflate")
+            .compress(&[b'a'; 4096])
+            .unwrap();
+        assert!(compressed.len() <= 64);
+        write_frame(&mut stream.read_raw, true, true, OP_TEXT, None, &compressed);
+        stream.process_read().unwrap();
+        assert_eq!(stream.read_ready, close);
+    }
+}
@@ -0,0 +1,76 @@
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::time::SystemTime;
+
+use serde::Serialize;
+
+use crate::connection::{ConnectionConfig, ConnectionMode};
+use crate::link_status::LinkStatus;
+use crate::metrics::MessageStats;
+use crate::tasks::RuntimeHealth;
+use crate::telemetry::LabelStats;
+
+/// Everything the provider tracks at one point in time, from `diagnostics`, for attaching
+/// to a support escalation
+///
+/// Configurations are redacted: auth tokens, proxy credentials and custom header values
+/// read `[REDACTED]`. The component maps, session entries and server clients are read
+/// while holding the component locks, so they agree with each other; counters are read
+/// without locking and may be a few messages ahead.
+#[derive(Debug, Clone, Serialize)]
+pub struct DiagnosticsSnapshot {
+    pub taken_at: SystemTime,
+    pub mode: ConnectionMode,
+    /// Address the server is bound to; `None` in client mode and while it is stopped
+    pub server_addr: Option<SocketAddr>,
+    /// Provider-wide configuration, redacted
+    pub config: ConnectionConfig,
+    /// Components linked with the provider as target, sorted
+    pub consumers: Vec<String>,
+    /// Components linked with the provider as source, sorted
+    pub handlers: Vec<String>,
+    /// Every client-mode connection, by component and role
+    pub connections: Vec<ConnectionDiagnostics>,
+    /// Links whose last connection attempt failed
+    pub failed_links: Vec<LinkStatus>,
+    /// Component sessions as `(session_id, component_id)`, sorted by session
+    pub sessions: Vec<(String, String)>,
+    /// Subject patterns of each handler component; an empty list receives every subject
+    pub subscriptions: BTreeMap<String, Vec<String>>,
+    /// Connected server clients, sorted by session (server mode)
+    pub server_clients: Vec<ServerClientDiagnostics>,
+    /// Bytes queued for server clients against `SERVER_MEMORY_BUDGET_BYTES`
+    pub server_queued_bytes: usize,
+    pub message_stats: MessageStats,
+    pub label_metrics: HashMap<String, LabelStats>,
+    pub close_counts: HashMap<String, u64>,
+    pub runtime_health: RuntimeHealth,
+}
+
+/// One client-mode connection in a `DiagnosticsSnapshot`
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionDiagnostics {
+    /// Role, state, session and traffic, as `list_links` reports them
+    pub link: LinkStatus,
+    /// Frames waiting in the outbound queue
+    pub queued_frames: usize,
+    /// `OUTBOUND_QUEUE_CAPACITY` of the connection
+    pub queue_capacity: usize,
+    /// Inbound messages skipped because the queue was full
+    pub dropped_messages: u64,
+    pub envelope_version: u8,
+    /// Effective configuration of the connection, redacted
+    pub config: ConnectionConfig,
+}
+
+/// One connected server client in a `DiagnosticsSnapshot`
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerClientDiagnostics {
+    pub session_id: String,
+    pub connected_at: SystemTime,
+    /// Frames waiting to be written to the client
+    pub queued_frames: usize,
+    /// Groups the session belongs to, sorted
+    pub groups: Vec<String>,
+    pub metadata: HashMap<String, String>,
+}
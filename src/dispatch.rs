@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{mpsc, Semaphore};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{debug, error};
+
+use crate::reconnect::QueuedFrame;
+use crate::tasks::TaskRegistry;
+
+/// Messages a handler may have waiting for its worker before the receive loop waits too
+pub(crate) const DISPATCH_BACKLOG: usize = 1024;
+
+/// A frame for one handler connection
+#[derive(Debug)]
+struct Delivery {
+    tx: mpsc::Sender<QueuedFrame>,
+    frame: Message,
+}
+
+/// Feeds handler queues from one worker per handler, so under `HANDLER_OVERFLOW=wait` a
+/// handler with a full queue holds up neither the others nor the receive loop
+///
+/// At most `HANDLER_CONCURRENCY` sends wait for queue room at a time, and each handler
+/// gets its messages in the order they were dispatched.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct HandlerDispatch {
+    /// `None` when `HANDLER_CONCURRENCY` is 0 and sends are awaited inline
+    permits: Option<Arc<Semaphore>>,
+    workers: Arc<Mutex<HashMap<String, mpsc::Sender<Delivery>>>>,
+    tasks: TaskRegistry,
+}
+
+impl HandlerDispatch {
+    pub(crate) fn new(concurrency: usize, tasks: TaskRegistry) -> Self {
+        Self {
+            permits: (concurrency > 0).then(|| Arc::new(Semaphore::new(concurrency))),
+            workers: Arc::default(),
+            tasks,
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.permits.is_some()
+    }
+
+    /// Queue `frame` for `component_id`'s connection behind the frames already dispatched
+    /// to it, waiting only while its backlog is full
+    pub(crate) async fn enqueue(
+        &self,
+        component_id: &str,
+        tx: mpsc::Sender<QueuedFrame>,
+        frame: Message,
+    ) {
+        let Some(permits) = &self.permits else {
+            return;
+        };
+        let worker = self
+            .workers
+            .lock()
+            .unwrap()
+            .entry(component_id.to_string())
+            .or_insert_with(|| self.spawn_worker(component_id, Arc::clone(permits)))
+            .clone();
+        if worker.send(Delivery { tx, frame }).await.is_err() {
+            error!("Dispatch worker of component {} has stopped", component_id);
+        }
+    }
+
+    fn spawn_worker(&self, component_id: &str, permits: Arc<Semaphore>) -> mpsc::Sender<Delivery> {
+        let (worker_tx, mut worker_rx) = mpsc::channel::<Delivery>(DISPATCH_BACKLOG);
+        let component = component_id.to_string();
+        self.tasks
+            .spawn("handler-dispatch", component_id, async move {
+                while let Some(Delivery { tx, frame }) = worker_rx.recv().await {
+                    let Ok(_permit) = permits.acquire().await else {
+                        break;
+                    };
+                    match tx.send(frame.into()).await {
+                        Ok(()) => debug!("Forwarded message to component {}", component),
+                        Err(e) => {
+                            error!(
+                                "Failed to forward message to component {}: {}",
+                                component, e
+                            )
+                        }
+                    }
+                }
+            });
+        worker_tx
+    }
+
+    /// Stop `component_id`'s worker once it has sent its backlog
+    pub(crate) fn remove(&self, component_id: &str) {
+        self.workers.lock().unwrap().remove(component_id);
+    }
+
+    /// Stop every worker once it has sent its backlog
+    pub(crate) fn clear(&self) {
+        self.workers.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn text(n: usize) -> Message {
+        Message::Text(n.to_string())
+    }
+
+    #[tokio::test]
+    async fn test_full_handler_holds_up_only_itself() {
+        let dispatch = HandlerDispatch::new(2, TaskRegistry::default());
+        let (slow_tx, mut slow_rx) = mpsc::channel(1);
+        let (fast_tx, mut fast_rx) = mpsc::channel(100);
+
+        for n in 0..10 {
+            dispatch.enqueue("slow", slow_tx.clone(), text(n)).await;
+            dispatch.enqueue("fast", fast_tx.clone(), text(n)).await;
+        }
+        for n in 0..10 {
+            let frame = tokio::time::timeout(Duration::from_secs(1), fast_rx.recv()).await;
+            assert_eq!(frame.unwrap(), Some(text(n)));
+        }
+        // The slow handler still gets everything, in order
+        for n in 0..10 {
+            assert_eq!(slow_rx.recv().await, Some(text(n)));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_disabled_without_concurrency() {
+        let dispatch = HandlerDispatch::new(0, TaskRegistry::default());
+        assert!(!dispatch.is_enabled());
+        let (tx, mut rx) = mpsc::channel(1);
+        dispatch.enqueue("a", tx, text(0)).await;
+        assert!(rx.try_recv().is_err());
+    }
+}
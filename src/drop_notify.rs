@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+use serde_json::json;
+use tokio::time::Instant;
+
+use crate::acl::SYSTEM_PREFIX;
+use crate::BrokerMessage;
+
+/// Header carrying the id a publisher gave its message; only such messages are reported
+pub(crate) const MSG_ID_HEADER: &str = "msg-id";
+
+/// Individual notifications sent per window before the rest are summarized
+const NOTICE_LIMIT: usize = 10;
+
+/// Rate-limiting window for drop notifications
+const NOTICE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Message ids listed in a summary notification
+const SUMMARY_SAMPLE: usize = 10;
+
+/// Subject of drop notifications
+pub(crate) fn drop_subject() -> String {
+    format!("{}drop", SYSTEM_PREFIX)
+}
+
+/// Why the provider dropped a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DropReason {
+    /// The handler component's outbound queue was full (`HANDLER_OVERFLOW=drop`)
+    HandlerOverflow,
+}
+
+impl DropReason {
+    fn label(self) -> &'static str {
+        match self {
+            Self::HandlerOverflow => "handler_overflow",
+        }
+    }
+}
+
+/// A dropped message that carried a `msg-id`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DroppedMessage {
+    pub msg_id: String,
+    pub subject: String,
+    /// Component the message was dropped for
+    pub component_id: String,
+    pub reason: DropReason,
+}
+
+/// Turns drops into `$SYS.drop` notifications for the publisher, rate-limited per window
+///
+/// The first `NOTICE_LIMIT` drops of a window get one notification each; the rest are
+/// counted and reported in a single summary once the window ends.
+#[derive(Debug)]
+pub(crate) struct DropNotifier {
+    window_start: Option<Instant>,
+    sent: usize,
+    /// Drops not notified individually in the current window, per reason
+    suppressed: HashMap<&'static str, Vec<String>>,
+}
+
+impl DropNotifier {
+    pub(crate) fn new() -> Self {
+        Self {
+            window_start: None,
+            sent: 0,
+            suppressed: HashMap::new(),
+        }
+    }
+
+    /// Notifications due for a drop: its own while under the limit, plus the summary of
+    /// the previous window if that hasn't been taken yet
+    pub(crate) fn record(&mut self, dropped: DroppedMessage, now: Instant) -> Vec<BrokerMessage> {
+        let mut notices = Vec::new();
+        if !matches!(self.window_start, Some(start) if now < start + NOTICE_WINDOW) {
+            notices.extend(self.take_summary());
+            self.window_start = Some(now);
+            self.sent = 0;
+        }
+
+        if self.sent < NOTICE_LIMIT {
+            self.sent += 1;
+            notices.push(notice(json!({
+                "msg_id": dropped.msg_id,
+                "subject": dropped.subject,
+                "component_id": dropped.component_id,
+                "reason": dropped.reason.label(),
+                "timestamp_ms": timestamp_ms(),
+            })));
+        } else {
+            self.suppressed
+                .entry(dropped.reason.label())
+                .or_default()
+                .push(dropped.msg_id);
+        }
+        notices
+    }
+
+    /// When the summary of suppressed drops becomes due, if any were suppressed
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        if self.suppressed.is_empty() {
+            return None;
+        }
+        self.window_start.map(|start| start + NOTICE_WINDOW)
+    }
+
+    /// Summaries of the drops suppressed so far, one per reason
+    pub(crate) fn take_summary(&mut self) -> Vec<BrokerMessage> {
+        let mut summaries: Vec<_> = self.suppressed.drain().collect();
+        summaries.sort();
+        summaries
+            .into_iter()
+            .map(|(reason, msg_ids)| {
+                notice(json!({
+                    "reason": reason,
+                    "dropped": msg_ids.len(),
+                    "msg_ids": &msg_ids[..msg_ids.len().min(SUMMARY_SAMPLE)],
+                    "summary": true,
+                    "timestamp_ms": timestamp_ms(),
+                }))
+            })
+            .collect()
+    }
+}
+
+fn notice(body: serde_json::Value) -> BrokerMessage {
+    BrokerMessage {
+        subject: drop_subject(),
+        body: Bytes::from(body.to_string()),
+        reply_to: None,
+        headers: HashMap::new(),
+    }
+}
+
+fn timestamp_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dropped(n: usize) -> DroppedMessage {
+        DroppedMessage {
+            msg_id: format!("m{}", n),
+            subject: "orders.new".to_string(),
+            component_id: "stalled".to_string(),
+            reason: DropReason::HandlerOverflow,
+        }
+    }
+
+    fn body(msg: &BrokerMessage) -> serde_json::Value {
+        serde_json::from_slice(&msg.body).unwrap()
+    }
+
+    #[test]
+    fn test_flood_summarized() {
+        let mut notifier = DropNotifier::new();
+        let start = Instant::now();
+
+        let mut individual = Vec::new();
+        for n in 0..25 {
+            individual.extend(notifier.record(dropped(n), start));
+        }
+        assert_eq!(individual.len(), NOTICE_LIMIT);
+        assert_eq!(body(&individual[0])["msg_id"], "m0");
+        assert_eq!(body(&individual[0])["reason"], "handler_overflow");
+        assert_eq!(notifier.deadline(), Some(start + NOTICE_WINDOW));
+
+        let summary = notifier.take_summary();
+        assert_eq!(summary.len(), 1);
+        assert_eq!(summary[0].subject, "$SYS.drop");
+        let summary = body(&summary[0]);
+        assert_eq!(summary["dropped"], 15);
+        assert_eq!(summary["msg_ids"][0], "m10");
+        assert_eq!(notifier.deadline(), None);
+    }
+
+    #[test]
+    fn test_pending_summary_sent_with_next_window() {
+        let mut notifier = DropNotifier::new();
+        let start = Instant::now();
+        for n in 0..NOTICE_LIMIT + 1 {
+            notifier.record(dropped(n), start);
+        }
+
+        let notices = notifier.record(dropped(99), start + NOTICE_WINDOW);
+        assert_eq!(notices.len(), 2);
+        assert_eq!(body(&notices[0])["dropped"], 1);
+        assert_eq!(body(&notices[1])["msg_id"], "m99");
+    }
+}
@@ -0,0 +1,298 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+
+const MILLISECOND: u64 = 1;
+const SECOND: u64 = 1000;
+const MINUTE: u64 = 60 * SECOND;
+const HOUR: u64 = 60 * MINUTE;
+
+/// Duration settings that predate humantime values, with the unit-suffixed key they
+/// keep accepting and the unit of its bare numbers, in milliseconds
+const LEGACY_KEYS: &[(&str, &str, u64)] = &[
+    ("CONNECT_TIMEOUT", "CONNECT_TIMEOUT_SEC", SECOND),
+    ("TCP_KEEPALIVE", "TCP_KEEPALIVE_SEC", SECOND),
+    (
+        "RECONNECT_BASE_DELAY",
+        "RECONNECT_BASE_DELAY_MS",
+        MILLISECOND,
+    ),
+    (
+        "RECONNECT_BUFFER_MAX_AGE",
+        "RECONNECT_BUFFER_MAX_AGE_MS",
+        MILLISECOND,
+    ),
+    (
+        "HANDLER_BATCH_WINDOW",
+        "HANDLER_BATCH_WINDOW_MS",
+        MILLISECOND,
+    ),
+    ("RETAIN_TTL", "RETAIN_TTL_SEC", SECOND),
+    ("PARSE_FAIL_COOLDOWN", "PARSE_FAIL_COOLDOWN_MS", MILLISECOND),
+    (
+        "HANDOFF_BATCH_INTERVAL",
+        "HANDOFF_BATCH_INTERVAL_MS",
+        MILLISECOND,
+    ),
+    ("HANDOFF_ACK_TIMEOUT", "HANDOFF_ACK_TIMEOUT_MS", MILLISECOND),
+    ("BIND_RETRY_DELAY", "BIND_RETRY_DELAY_MS", MILLISECOND),
+    (
+        "SERVER_RESTART_DELAY",
+        "SERVER_RESTART_DELAY_MS",
+        MILLISECOND,
+    ),
+    ("SLOW_CONSUMER_AFTER", "SLOW_CONSUMER_AFTER_MS", MILLISECOND),
+    ("HEARTBEAT_INTERVAL", "HEARTBEAT_INTERVAL_MS", MILLISECOND),
+    ("IDLE_TIMEOUT", "IDLE_TIMEOUT_MS", MILLISECOND),
+    ("PING_INTERVAL", "PING_INTERVAL_SEC", SECOND),
+    ("PONG_TIMEOUT", "PONG_TIMEOUT_SEC", SECOND),
+    (
+        "BODY_STREAM_SUBSCRIBE_TIMEOUT",
+        "BODY_STREAM_SUBSCRIBE_TIMEOUT_MS",
+        MILLISECOND,
+    ),
+    ("RECONCILE_INTERVAL", "RECONCILE_INTERVAL_SEC", SECOND),
+    ("DELIVERY_TIMEOUT", "DELIVERY_TIMEOUT_MS", MILLISECOND),
+    ("DEDUP_WINDOW", "DEDUP_WINDOW_MS", MILLISECOND),
+    (
+        "ENVELOPE_UPGRADE_GRACE",
+        "ENVELOPE_UPGRADE_GRACE_MS",
+        MILLISECOND,
+    ),
+];
+
+/// Duration settings of a config map
+///
+/// Values are humantime-style: whole numbers with a unit of `ms`, `s`, `m` or `h`, one or
+/// several in a row (`500ms`, `30s`, `1m30s`), and a bare `0` for none. A setting that
+/// predates this also keeps its unit-suffixed key, e.g. `CONNECT_TIMEOUT_SEC` next to
+/// `CONNECT_TIMEOUT`, where a bare number is in that unit; the unsuffixed key wins if
+/// both are set.
+pub(crate) struct DurationConfig<'a> {
+    config: &'a HashMap<String, String>,
+}
+
+impl<'a> DurationConfig<'a> {
+    pub(crate) fn new(config: &'a HashMap<String, String>) -> Self {
+        Self { config }
+    }
+
+    /// The setting `key`, or `default` if it isn't set
+    pub(crate) fn get(&self, key: &str, default: Duration) -> Result<Duration> {
+        Ok(self.lookup(key)?.unwrap_or(default))
+    }
+
+    /// The setting `key`, from its own key or its unit-suffixed one
+    pub(crate) fn lookup(&self, key: &str) -> Result<Option<Duration>> {
+        if let Some(value) = self.config.get(key) {
+            return parse(value)
+                .with_context(|| format!("Invalid {}", key))
+                .map(Some);
+        }
+        let Some((legacy, unit_ms)) = legacy_key(key) else {
+            return Ok(None);
+        };
+        self.config
+            .get(legacy)
+            .map(|value| parse_in(value, unit_ms).with_context(|| format!("Invalid {}", legacy)))
+            .transpose()
+    }
+}
+
+/// The unit-suffixed key a duration setting keeps accepting, and its unit in milliseconds
+pub(crate) fn legacy_key(key: &str) -> Option<(&'static str, u64)> {
+    LEGACY_KEYS
+        .iter()
+        .find(|(current, ..)| *current == key)
+        .map(|(_, legacy, unit_ms)| (*legacy, *unit_ms))
+}
+
+/// The unsuffixed key of a unit-suffixed duration key, and the unit in milliseconds
+pub(crate) fn current_key(legacy: &str) -> Option<(&'static str, u64)> {
+    LEGACY_KEYS
+        .iter()
+        .find(|(_, old, _)| *old == legacy)
+        .map(|(current, _, unit_ms)| (*current, *unit_ms))
+}
+
+/// Parse a humantime-style duration
+pub(crate) fn parse(value: &str) -> Result<Duration> {
+    let invalid = || anyhow!("{:?} is not a duration like 500ms, 30s or 5m", value);
+    let mut rest = value.trim();
+    if rest == "0" {
+        return Ok(Duration::ZERO);
+    }
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+    let mut total_ms: u64 = 0;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let count: u64 = rest[..digits].parse().map_err(|_| invalid())?;
+        rest = rest[digits..].trim_start();
+        let letters = rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+        let unit_ms = match &rest[..letters] {
+            "ms" => MILLISECOND,
+            "s" => SECOND,
+            "m" => MINUTE,
+            "h" => HOUR,
+            _ => return Err(invalid()),
+        };
+        total_ms = count
+            .checked_mul(unit_ms)
+            .and_then(|ms| total_ms.checked_add(ms))
+            .ok_or_else(invalid)?;
+        rest = rest[letters..].trim_start();
+    }
+    Ok(Duration::from_millis(total_ms))
+}
+
+/// Parse a duration whose bare numbers are in `unit_ms`, as unit-suffixed keys take them
+pub(crate) fn parse_in(value: &str, unit_ms: u64) -> Result<Duration> {
+    match value.trim().parse::<u64>() {
+        Ok(count) => match count.checked_mul(unit_ms) {
+            Some(ms) => Ok(Duration::from_millis(ms)),
+            None => bail!("{} is too long a duration", value),
+        },
+        Err(_) => parse(value),
+    }
+}
+
+/// `duration` the way `parse` reads it, in the largest unit that represents it exactly
+pub(crate) fn format(duration: Duration) -> String {
+    let ms = duration.as_millis() as u64;
+    match ms {
+        0 => "0".to_string(),
+        ms if ms % HOUR == 0 => format!("{}h", ms / HOUR),
+        ms if ms % MINUTE == 0 => format!("{}m", ms / MINUTE),
+        ms if ms % SECOND == 0 => format!("{}s", ms / SECOND),
+        ms => format!("{}ms", ms),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_accepted_formats() {
+        assert_eq!(parse("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse("5m").unwrap(), Duration::from_secs(300));
+        assert_eq!(parse("2h").unwrap(), Duration::from_secs(7200));
+        assert_eq!(parse("1m30s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse(" 1s 250ms ").unwrap(), Duration::from_millis(1250));
+        assert_eq!(parse("0").unwrap(), Duration::ZERO);
+        assert_eq!(parse("0s").unwrap(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_parse_rejects_garbage() {
+        for value in [
+            "",
+            "30",
+            "s",
+            "-5s",
+            "1.5s",
+            "30sec",
+            "5 minutes",
+            "10d",
+            "ms500",
+            "1s?",
+        ] {
+            let error = parse(value).unwrap_err();
+            assert!(error.to_string().contains("is not a duration"), "{}", error);
+        }
+        assert!(parse("18446744073709551615h").is_err());
+    }
+
+    #[test]
+    fn test_parse_in_unit() {
+        assert_eq!(parse_in("30", SECOND).unwrap(), Duration::from_secs(30));
+        assert_eq!(
+            parse_in("250", MILLISECOND).unwrap(),
+            Duration::from_millis(250)
+        );
+        assert_eq!(parse_in("2m", SECOND).unwrap(), Duration::from_secs(120));
+        assert!(parse_in("soon", SECOND).is_err());
+    }
+
+    #[test]
+    fn test_legacy_keys() {
+        let durations = config(&[
+            ("CONNECT_TIMEOUT_SEC", "7"),
+            ("IDLE_TIMEOUT_MS", "1500"),
+            ("RETAIN_TTL_SEC", "1m"),
+            ("PONG_TIMEOUT", "2s"),
+            ("PONG_TIMEOUT_SEC", "9"),
+        ]);
+        let durations = DurationConfig::new(&durations);
+        assert_eq!(
+            durations.lookup("CONNECT_TIMEOUT").unwrap(),
+            Some(Duration::from_secs(7))
+        );
+        assert_eq!(
+            durations.lookup("IDLE_TIMEOUT").unwrap(),
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(
+            durations.lookup("RETAIN_TTL").unwrap(),
+            Some(Duration::from_secs(60))
+        );
+        // The unsuffixed key wins
+        assert_eq!(
+            durations.lookup("PONG_TIMEOUT").unwrap(),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(
+            durations
+                .get("HANDOFF_ACK_TIMEOUT", Duration::from_secs(5))
+                .unwrap(),
+            Duration::from_secs(5)
+        );
+
+        // Every unit-suffixed key maps onto its setting in its unit
+        for (key, legacy, unit_ms) in LEGACY_KEYS {
+            let map = config(&[(*legacy, "3")]);
+            assert_eq!(
+                DurationConfig::new(&map).lookup(key).unwrap(),
+                Some(Duration::from_millis(3 * unit_ms)),
+                "{}",
+                legacy
+            );
+        }
+
+        let map = config(&[("IDLE_TIMEOUT_MS", "soon")]);
+        let error = DurationConfig::new(&map)
+            .lookup("IDLE_TIMEOUT")
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Invalid IDLE_TIMEOUT_MS");
+        let map = config(&[("IDLE_TIMEOUT", "1500")]);
+        let error = DurationConfig::new(&map)
+            .lookup("IDLE_TIMEOUT")
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Invalid IDLE_TIMEOUT");
+    }
+
+    #[test]
+    fn test_format_round_trips() {
+        for ms in [0, 250, 1000, 1500, 90_000, 120_000, 7_200_000] {
+            let duration = Duration::from_millis(ms);
+            assert_eq!(parse(&format(duration)).unwrap(), duration);
+        }
+        assert_eq!(format(Duration::from_secs(90)), "90s");
+        assert_eq!(format(Duration::from_secs(3600)), "1h");
+    }
+}
@@ -0,0 +1,369 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::Result;
+use tokio::time::Instant;
+use tracing::{debug, info};
+
+/// Envelope the provider has always spoken; frames carry no version field
+pub const ENVELOPE_V1: u8 = 1;
+
+/// Envelope tagged with its version in `v`; otherwise laid out like v1
+pub const ENVELOPE_V2: u8 = 2;
+
+/// Envelope versions this provider can encode and decode
+pub const SUPPORTED_ENVELOPE_VERSIONS: &[u8] = &[ENVELOPE_V1, ENVELOPE_V2];
+
+/// Control op asking the peer to move the session to one of `versions`
+pub(crate) const UPGRADE_OP: &str = "upgrade";
+
+/// Control op accepting an upgrade, naming the version the session now uses
+pub(crate) const UPGRADE_ACK_OP: &str = "upgrade_ack";
+
+/// Control op declining an upgrade, naming the version the session stays on
+pub(crate) const UPGRADE_REFUSED_OP: &str = "upgrade_refused";
+
+/// Error for an inbound envelope in a version the session doesn't accept
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsupportedEnvelopeVersion {
+    /// Version the frame was encoded with
+    pub version: u8,
+    /// Version the session has negotiated
+    pub session_version: u8,
+}
+
+impl fmt::Display for UnsupportedEnvelopeVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Envelope version {} is not accepted on a version {} session",
+            self.version, self.session_version
+        )
+    }
+}
+
+impl std::error::Error for UnsupportedEnvelopeVersion {}
+
+/// An envelope upgrade control frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum UpgradeOp {
+    /// `{"op":"upgrade","versions":[...]}`
+    Request(Vec<u8>),
+    /// `{"op":"upgrade_ack","version":...}`
+    Ack(u8),
+    /// `{"op":"upgrade_refused","version":...}`
+    Refused(u8),
+}
+
+impl UpgradeOp {
+    pub(crate) fn parse(text: &str) -> Option<Self> {
+        // Skip decoding ordinary envelopes twice
+        if !text.contains(r#""op""#) {
+            return None;
+        }
+        let json: serde_json::Value = serde_json::from_str(text).ok()?;
+        let version = |v: &serde_json::Value| u8::try_from(v.as_u64()?).ok();
+        match json.get("op")?.as_str()? {
+            UPGRADE_OP => Some(Self::Request(
+                json.get("versions")?
+                    .as_array()?
+                    .iter()
+                    .filter_map(version)
+                    .collect(),
+            )),
+            UPGRADE_ACK_OP => Some(Self::Ack(version(json.get("version")?)?)),
+            UPGRADE_REFUSED_OP => Some(Self::Refused(version(json.get("version")?)?)),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn to_json(&self) -> String {
+        match self {
+            Self::Request(versions) => serde_json::json!({"op": UPGRADE_OP, "versions": versions}),
+            Self::Ack(version) => serde_json::json!({"op": UPGRADE_ACK_OP, "version": version}),
+            Self::Refused(version) => {
+                serde_json::json!({"op": UPGRADE_REFUSED_OP, "version": version})
+            }
+        }
+        .to_string()
+    }
+}
+
+/// Version of an inbound envelope: its `v` field, or v1 when it has none
+fn version_of(json: &serde_json::Value) -> u8 {
+    match json.get("v") {
+        None => ENVELOPE_V1,
+        Some(v) => v
+            .as_u64()
+            .and_then(|v| u8::try_from(v).ok())
+            .unwrap_or(u8::MAX),
+    }
+}
+
+/// Tag an encoded envelope, or a batch and each envelope in it, with `version`
+///
+/// For frames encoded before knowing which session they go to, e.g. broadcasts. Frames
+/// that aren't envelopes, such as control replies, pass unchanged, as does everything
+/// while `version` is v1.
+pub(crate) fn tag_outbound(text: String, version: u8) -> String {
+    if version == ENVELOPE_V1 {
+        return text;
+    }
+    let Ok(mut json) = serde_json::from_str::<serde_json::Value>(&text) else {
+        return text;
+    };
+    let Some(object) = json.as_object_mut() else {
+        return text;
+    };
+    if let Some(batch) = object.get_mut("batch").and_then(|b| b.as_array_mut()) {
+        for envelope in batch.iter_mut().filter_map(|e| e.as_object_mut()) {
+            envelope.insert("v".to_string(), version.into());
+        }
+    } else if !object.contains_key("subject") {
+        return text;
+    }
+    object.insert("v".to_string(), version.into());
+    json.to_string()
+}
+
+#[derive(Debug, Default)]
+struct UpgradeState {
+    /// Version the session used before the last switch and until when it is still accepted
+    previous: Option<(u8, Instant)>,
+    /// Version this side asked for and is waiting to have acknowledged
+    requested: Option<u8>,
+}
+
+/// Envelope version negotiated on one connection
+///
+/// Encoders read the current version for every frame, so a switch applies from the next
+/// frame encoded. Inbound frames in the previous version are still accepted for `grace`
+/// after a switch, to tolerate frames the peer sent before it saw the acknowledgment.
+#[derive(Debug)]
+pub(crate) struct EnvelopeVersion {
+    current: AtomicU8,
+    state: Mutex<UpgradeState>,
+    grace: Duration,
+}
+
+impl EnvelopeVersion {
+    pub(crate) fn new(grace: Duration) -> Self {
+        Self {
+            current: AtomicU8::new(ENVELOPE_V1),
+            state: Mutex::new(UpgradeState::default()),
+            grace,
+        }
+    }
+
+    pub(crate) fn current(&self) -> u8 {
+        self.current.load(Ordering::Acquire)
+    }
+
+    /// Highest of the `offered` versions this provider supports, or `None` to refuse
+    pub(crate) fn negotiate(offered: &[u8]) -> Option<u8> {
+        offered
+            .iter()
+            .copied()
+            .filter(|v| SUPPORTED_ENVELOPE_VERSIONS.contains(v))
+            .max()
+    }
+
+    /// Remember that this side asked the peer for `version`
+    pub(crate) fn request(&self, version: u8) {
+        self.state.lock().unwrap().requested = Some(version);
+    }
+
+    /// Whether an ack for `version` answers an outstanding request, clearing it
+    pub(crate) fn take_request(&self, version: u8) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.requested == Some(version) {
+            state.requested = None;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Forget an outstanding request the peer refused
+    pub(crate) fn clear_request(&self) {
+        self.state.lock().unwrap().requested = None;
+    }
+
+    /// Move the session to `version`, opening the grace window for the old one
+    pub(crate) fn switch(&self, version: u8) {
+        let mut state = self.state.lock().unwrap();
+        let previous = self.current.swap(version, Ordering::AcqRel);
+        if previous != version {
+            state.previous = Some((previous, Instant::now() + self.grace));
+        }
+    }
+
+    /// Apply an upgrade op from the peer, returning the reply to send, if any
+    ///
+    /// A request is acknowledged after switching, so every frame encoded from then on is
+    /// written after the ack; frames already queued keep the old version and fall in the
+    /// peer's grace window.
+    pub(crate) fn handle(&self, op: UpgradeOp, session_id: &str) -> Option<UpgradeOp> {
+        match op {
+            UpgradeOp::Request(offered) => match Self::negotiate(&offered) {
+                Some(version) => {
+                    self.switch(version);
+                    info!(
+                        "Session {} upgraded to envelope version {} at the peer's request",
+                        session_id, version
+                    );
+                    Some(UpgradeOp::Ack(version))
+                }
+                None => {
+                    info!(
+                        "Refused envelope upgrade of session {} to {:?}",
+                        session_id, offered
+                    );
+                    Some(UpgradeOp::Refused(self.current()))
+                }
+            },
+            UpgradeOp::Ack(version) => {
+                if self.take_request(version) {
+                    self.switch(version);
+                    info!(
+                        "Session {} upgraded to envelope version {}",
+                        session_id, version
+                    );
+                } else {
+                    debug!(
+                        "Ignoring unsolicited envelope upgrade ack on session {}",
+                        session_id
+                    );
+                }
+                None
+            }
+            UpgradeOp::Refused(_) => {
+                self.clear_request();
+                info!(
+                    "Peer refused envelope upgrade; session {} stays on version {}",
+                    session_id,
+                    self.current()
+                );
+                None
+            }
+        }
+    }
+
+    /// Check the version of an inbound frame
+    ///
+    /// Text that isn't a JSON object is left to the parser. Sessions that never upgraded
+    /// accept everything, as they did before versions existed.
+    pub(crate) fn check_inbound(&self, text: &str) -> Result<()> {
+        let session_version = self.current();
+        let previous = self.state.lock().unwrap().previous;
+        if session_version == ENVELOPE_V1 && previous.is_none() {
+            return Ok(());
+        }
+        let Ok(json) = serde_json::from_str::<serde_json::Value>(text) else {
+            return Ok(());
+        };
+        if !json.is_object() {
+            return Ok(());
+        }
+        let version = version_of(&json);
+        let in_grace =
+            previous.is_some_and(|(previous, until)| version == previous && Instant::now() < until);
+        if version == session_version || in_grace {
+            return Ok(());
+        }
+        Err(UnsupportedEnvelopeVersion {
+            version,
+            session_version,
+        }
+        .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upgrade_ops_round_trip() {
+        for op in [
+            UpgradeOp::Request(vec![2]),
+            UpgradeOp::Ack(2),
+            UpgradeOp::Refused(1),
+        ] {
+            assert_eq!(UpgradeOp::parse(&op.to_json()), Some(op));
+        }
+        assert_eq!(UpgradeOp::parse(r#"{"op":"reconnect_ack"}"#), None);
+        assert_eq!(UpgradeOp::parse(r#"{"subject":"a","body":"x"}"#), None);
+    }
+
+    #[test]
+    fn test_negotiate_picks_highest_supported() {
+        assert_eq!(EnvelopeVersion::negotiate(&[1, 2]), Some(ENVELOPE_V2));
+        assert_eq!(EnvelopeVersion::negotiate(&[2, 9]), Some(ENVELOPE_V2));
+        assert_eq!(EnvelopeVersion::negotiate(&[9]), None);
+        assert_eq!(EnvelopeVersion::negotiate(&[]), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_grace_window_accepts_previous_version() {
+        let envelope = EnvelopeVersion::new(Duration::from_millis(100));
+        let v1 = r#"{"subject":"a","body":"x"}"#;
+        let v2 = r#"{"v":2,"subject":"a","body":"x"}"#;
+        assert!(envelope.check_inbound(v1).is_ok());
+
+        envelope.switch(ENVELOPE_V2);
+        assert_eq!(envelope.current(), ENVELOPE_V2);
+        assert!(envelope.check_inbound(v1).is_ok());
+        assert!(envelope.check_inbound(v2).is_ok());
+
+        tokio::time::advance(Duration::from_millis(150)).await;
+        let err = envelope.check_inbound(v1).unwrap_err();
+        assert!(err.is::<UnsupportedEnvelopeVersion>());
+        assert!(envelope.check_inbound(v2).is_ok());
+        assert!(envelope.check_inbound("plain text").is_ok());
+    }
+
+    #[test]
+    fn test_tag_outbound() {
+        let envelope = r#"{"subject":"a","body":"x"}"#.to_string();
+        assert_eq!(tag_outbound(envelope.clone(), ENVELOPE_V1), envelope);
+        let tagged: serde_json::Value =
+            serde_json::from_str(&tag_outbound(envelope, ENVELOPE_V2)).unwrap();
+        assert_eq!(tagged["v"], 2);
+
+        let batch = r#"{"batch":[{"subject":"a","body":"x"}]}"#.to_string();
+        let tagged: serde_json::Value =
+            serde_json::from_str(&tag_outbound(batch, ENVELOPE_V2)).unwrap();
+        assert_eq!(tagged["v"], 2);
+        assert_eq!(tagged["batch"][0]["v"], 2);
+
+        let ack = UpgradeOp::Ack(ENVELOPE_V2).to_json();
+        assert_eq!(tag_outbound(ack.clone(), ENVELOPE_V2), ack);
+    }
+
+    #[test]
+    fn test_handle_upgrade_ops() {
+        let envelope = EnvelopeVersion::new(Duration::from_secs(5));
+        assert_eq!(
+            envelope.handle(UpgradeOp::Request(vec![9]), "s"),
+            Some(UpgradeOp::Refused(ENVELOPE_V1))
+        );
+        assert_eq!(envelope.current(), ENVELOPE_V1);
+
+        // Only an ack for an outstanding request switches
+        assert_eq!(envelope.handle(UpgradeOp::Ack(ENVELOPE_V2), "s"), None);
+        assert_eq!(envelope.current(), ENVELOPE_V1);
+        envelope.request(ENVELOPE_V2);
+        envelope.handle(UpgradeOp::Ack(ENVELOPE_V2), "s");
+        assert_eq!(envelope.current(), ENVELOPE_V2);
+
+        let peer = EnvelopeVersion::new(Duration::from_secs(5));
+        assert_eq!(
+            peer.handle(UpgradeOp::Request(vec![1, 2]), "s"),
+            Some(UpgradeOp::Ack(ENVELOPE_V2))
+        );
+        assert_eq!(peer.current(), ENVELOPE_V2);
+    }
+}
@@ -0,0 +1,90 @@
+use std::net::SocketAddr;
+
+use tokio::sync::broadcast;
+
+use crate::close::CloseReason;
+use crate::tenant::DeadLetterReason;
+
+/// Capacity of the event channel; slow subscribers miss the oldest events beyond this
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+/// Notable provider-level occurrences that embedders can observe
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum ProviderEvent {
+    /// A session sent too many consecutive unparseable frames and is being skipped
+    ParseFailureCooldown { session_id: String, failures: u32 },
+    /// A session was disconnected for sending too many consecutive unparseable frames
+    ParseFailureDisconnect { session_id: String, failures: u32 },
+    /// A handoff of all connected clients to another instance started
+    HandoffStarted { target_url: String, clients: usize },
+    /// A wave of clients was handed off
+    HandoffProgress {
+        notified: usize,
+        acknowledged: usize,
+        timed_out: usize,
+        total: usize,
+    },
+    /// Every client present at the start of the handoff was closed
+    HandoffCompleted {
+        acknowledged: usize,
+        timed_out: usize,
+    },
+    /// The provider closed a server client's connection
+    ClientClosed {
+        session_id: String,
+        reason: CloseReason,
+    },
+    /// A broadcast or group send was not queued for `shed` clients because the frames
+    /// queued across all clients had reached `SERVER_MEMORY_BUDGET_BYTES`
+    BackpressureShed {
+        shed: usize,
+        queued_bytes: usize,
+        budget_bytes: usize,
+    },
+    /// A server client's queue stayed above `SLOW_CONSUMER_HIGH_WATER_PCT` of
+    /// `OUTBOUND_QUEUE_CAPACITY` frames for `SLOW_CONSUMER_AFTER`
+    SlowConsumer {
+        session_id: String,
+        queue_depth: usize,
+    },
+    /// A received message was dropped instead of delivered
+    DeadLetter {
+        session_id: String,
+        tenant: Option<String>,
+        subject: String,
+        reason: DeadLetterReason,
+    },
+    /// The server task stopped while the provider was running; its clients were dropped
+    /// and it no longer accepts connections
+    ServerStopped { error: String },
+    /// The server was bound again on `addr` after stopping (`SERVER_RESTART`)
+    ServerRestarted { addr: SocketAddr },
+}
+
+/// Fan-out channel for provider events
+///
+/// Emitting never blocks and is a no-op when nobody is subscribed.
+#[derive(Debug, Clone)]
+pub(crate) struct EventBus {
+    tx: broadcast::Sender<ProviderEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        let (tx, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        Self { tx }
+    }
+}
+
+impl EventBus {
+    /// Publish an event to all current subscribers
+    pub(crate) fn emit(&self, event: ProviderEvent) {
+        let _ = self.tx.send(event);
+    }
+
+    /// Subscribe to events emitted from now on
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<ProviderEvent> {
+        self.tx.subscribe()
+    }
+}
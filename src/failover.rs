@@ -0,0 +1,159 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+use crate::connection::ConnectionConfig;
+
+/// Order in which the URIs of a `URI` list are tried
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FailoverStrategy {
+    /// The first URI is dialled first, then the others in the order given
+    #[default]
+    Ordered,
+    /// A random URI is dialled first, then the ones after it in turn, so providers
+    /// sharing a list spread across its endpoints
+    Random,
+}
+
+impl FailoverStrategy {
+    /// Parse a `FAILOVER_STRATEGY` value (`ordered` or `random`, case-insensitive)
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "ordered" => Some(Self::Ordered),
+            "random" => Some(Self::Random),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Ordered => "ordered",
+            Self::Random => "random",
+        }
+    }
+}
+
+/// The URIs of a comma-separated `URI` value, dropping empty entries
+pub(crate) fn split_uris(uri: &str) -> Vec<String> {
+    uri.split(',')
+        .map(str::trim)
+        .filter(|uri| !uri.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// The URIs a client connection can be made to, and which one it is connected to
+#[derive(Debug)]
+pub(crate) struct Endpoints {
+    uris: Vec<String>,
+    active: AtomicUsize,
+}
+
+impl Endpoints {
+    pub(crate) fn from_config(config: &ConnectionConfig) -> Self {
+        let mut uris = split_uris(&config.uri);
+        if uris.is_empty() {
+            uris.push(config.uri.clone());
+        }
+        let active = match config.failover_strategy {
+            FailoverStrategy::Ordered => 0,
+            FailoverStrategy::Random => {
+                (uuid::Uuid::new_v4().as_u128() % uris.len() as u128) as usize
+            }
+        };
+        Self {
+            uris,
+            active: AtomicUsize::new(active),
+        }
+    }
+
+    pub(crate) fn uris(&self) -> &[String] {
+        &self.uris
+    }
+
+    /// Whether `URI` listed more than one endpoint
+    pub(crate) fn is_list(&self) -> bool {
+        self.uris.len() > 1
+    }
+
+    /// The URI connected to, or to be dialled first
+    pub(crate) fn active(&self) -> &str {
+        &self.uris[self.active.load(Ordering::Relaxed)]
+    }
+
+    /// Every URI once, as `(index, uri)`: from the active one on, or from the one after
+    /// it when the active one has just failed
+    pub(crate) fn candidates(&self, after_failure: bool) -> Vec<(usize, &str)> {
+        let start = self.active.load(Ordering::Relaxed) + usize::from(after_failure);
+        (0..self.uris.len())
+            .map(|offset| {
+                let index = (start + offset) % self.uris.len();
+                (index, self.uris[index].as_str())
+            })
+            .collect()
+    }
+
+    /// Record that the URI at `index` is now connected
+    pub(crate) fn set_active(&self, index: usize) {
+        self.active.store(index, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn endpoints(entries: &[(&str, &str)]) -> Endpoints {
+        let map: HashMap<String, String> = entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        Endpoints::from_config(&ConnectionConfig::from_map(&map).unwrap())
+    }
+
+    #[test]
+    fn test_split_uris() {
+        assert_eq!(
+            split_uris(" ws://a/ws, ws://b/ws ,,"),
+            vec!["ws://a/ws".to_string(), "ws://b/ws".to_string()]
+        );
+        assert_eq!(split_uris("ws://a/ws"), vec!["ws://a/ws".to_string()]);
+    }
+
+    #[test]
+    fn test_candidates_rotate() {
+        let list = endpoints(&[("URI", "ws://a/ws,ws://b/ws,ws://c/ws")]);
+        assert!(list.is_list());
+        assert_eq!(list.active(), "ws://a/ws");
+        assert_eq!(
+            list.candidates(false),
+            vec![(0, "ws://a/ws"), (1, "ws://b/ws"), (2, "ws://c/ws")]
+        );
+
+        list.set_active(2);
+        assert_eq!(list.active(), "ws://c/ws");
+        assert_eq!(
+            list.candidates(true),
+            vec![(0, "ws://a/ws"), (1, "ws://b/ws"), (2, "ws://c/ws")]
+        );
+        assert_eq!(list.candidates(false)[0], (2, "ws://c/ws"));
+
+        let single = endpoints(&[("URI", "ws://a/ws")]);
+        assert!(!single.is_list());
+        assert_eq!(single.candidates(true), vec![(0, "ws://a/ws")]);
+    }
+
+    #[test]
+    fn test_random_start_is_in_range() {
+        for _ in 0..20 {
+            let list = endpoints(&[
+                ("URI", "ws://a/ws,ws://b/ws"),
+                ("FAILOVER_STRATEGY", "random"),
+            ]);
+            assert_eq!(list.candidates(false).len(), 2);
+            assert!(["ws://a/ws", "ws://b/ws"].contains(&list.active()));
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+
+use bytes::Bytes;
+use serde::Serialize;
+
+use crate::BrokerMessage;
+
+/// Dispatched to a component with `FLOW_SIGNALS` when its outbound queue fills past the
+/// high watermark
+pub const FLOW_PAUSE_SUBJECT: &str = "$SYS.flow.pause";
+
+/// Dispatched to a paused component once its outbound queue drains below the low watermark
+pub const FLOW_RESUME_SUBJECT: &str = "$SYS.flow.resume";
+
+/// Percentage of the queue's capacity at or above which a component is paused
+const HIGH_WATER_PCT: usize = 80;
+
+/// Percentage of the queue's capacity at or below which a paused component resumes
+const LOW_WATER_PCT: usize = 25;
+
+/// Assumed time to write one frame until the connection has written some
+const DEFAULT_FRAME_COST: Duration = Duration::from_millis(1);
+
+/// How much room a component has to publish, from `get_flow_control`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct FlowControl {
+    /// Frames the connection's outbound queue holds (`OUTBOUND_QUEUE_CAPACITY`)
+    pub queue_capacity: usize,
+    /// Frames queued and not yet written
+    pub queue_used: usize,
+    /// Messages the component may still publish before a rate limit applies, or `None`
+    /// if its publishes are not rate limited
+    pub rate_limit_remaining: Option<u64>,
+    /// How long to hold off before publishing more, for the queue to drain to the low
+    /// watermark at the connection's recent write rate; 0 below it
+    pub suggested_delay_ms: u64,
+}
+
+/// Pause or resume, as crossed by a queue depth
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FlowSignal {
+    Pause,
+    Resume,
+}
+
+/// Fill level of one connection's outbound queue against its watermarks
+#[derive(Debug)]
+pub(crate) struct FlowGauge {
+    capacity: usize,
+    high_water: usize,
+    low_water: usize,
+    /// Whether crossings are signalled (`FLOW_SIGNALS`)
+    signals: bool,
+    paused: AtomicBool,
+    /// Moving average of the time a frame takes to write, in microseconds (0 = none yet)
+    frame_cost_us: AtomicU64,
+}
+
+impl FlowGauge {
+    pub(crate) fn new(capacity: usize, signals: bool) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            capacity,
+            high_water: (capacity * HIGH_WATER_PCT).div_ceil(100).max(1),
+            low_water: capacity * LOW_WATER_PCT / 100,
+            signals,
+            paused: AtomicBool::new(false),
+            frame_cost_us: AtomicU64::new(0),
+        }
+    }
+
+    /// Fold the time one frame took to write into the write rate
+    pub(crate) fn record_write(&self, elapsed: Duration) {
+        let sample = (elapsed.as_micros() as u64).max(1);
+        // Racing updates only lose a sample
+        let previous = self.frame_cost_us.load(Ordering::Relaxed);
+        let average = match previous {
+            0 => sample,
+            previous => (previous * 7 + sample) / 8,
+        };
+        self.frame_cost_us.store(average, Ordering::Relaxed);
+    }
+
+    /// The flow control figures for a queue holding `queue_used` frames
+    pub(crate) fn snapshot(&self, queue_used: usize) -> FlowControl {
+        let frame_cost = match self.frame_cost_us.load(Ordering::Relaxed) {
+            0 => DEFAULT_FRAME_COST,
+            us => Duration::from_micros(us),
+        };
+        let excess = queue_used.saturating_sub(self.low_water) as u32;
+        let delay = frame_cost.saturating_mul(excess);
+        FlowControl {
+            queue_capacity: self.capacity,
+            queue_used,
+            rate_limit_remaining: None,
+            suggested_delay_ms: (delay.as_micros() as u64).div_ceil(1000),
+        }
+    }
+
+    /// The signal due now that the queue holds `queue_used` frames, if a watermark was
+    /// crossed; each crossing is signalled once
+    pub(crate) fn observe(&self, queue_used: usize) -> Option<FlowSignal> {
+        if !self.signals {
+            return None;
+        }
+        if queue_used >= self.high_water {
+            (!self.paused.swap(true, Ordering::AcqRel)).then_some(FlowSignal::Pause)
+        } else if queue_used <= self.low_water {
+            self.paused
+                .swap(false, Ordering::AcqRel)
+                .then_some(FlowSignal::Resume)
+        } else {
+            None
+        }
+    }
+}
+
+/// The message dispatched to `component_id` for `signal`, carrying the figures at the
+/// crossing
+pub(crate) fn signal_message(
+    signal: FlowSignal,
+    component_id: &str,
+    flow: &FlowControl,
+) -> BrokerMessage {
+    let subject = match signal {
+        FlowSignal::Pause => FLOW_PAUSE_SUBJECT,
+        FlowSignal::Resume => FLOW_RESUME_SUBJECT,
+    };
+    let body = serde_json::json!({
+        "component_id": component_id,
+        "queue_capacity": flow.queue_capacity,
+        "queue_used": flow.queue_used,
+        "suggested_delay_ms": flow.suggested_delay_ms,
+    });
+    BrokerMessage {
+        subject: subject.to_string(),
+        body: Bytes::from(body.to_string()),
+        reply_to: None,
+        headers: HashMap::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signals_at_watermarks() {
+        let gauge = FlowGauge::new(10, true);
+        assert_eq!(gauge.observe(7), None);
+        assert_eq!(gauge.observe(8), Some(FlowSignal::Pause));
+        // Once per crossing
+        assert_eq!(gauge.observe(10), None);
+        assert_eq!(gauge.observe(5), None);
+        assert_eq!(gauge.observe(2), Some(FlowSignal::Resume));
+        assert_eq!(gauge.observe(0), None);
+        assert_eq!(gauge.observe(9), Some(FlowSignal::Pause));
+
+        let quiet = FlowGauge::new(10, false);
+        assert_eq!(quiet.observe(10), None);
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let gauge = FlowGauge::new(10, false);
+        let idle = gauge.snapshot(2);
+        assert_eq!(idle.queue_capacity, 10);
+        assert_eq!(idle.queue_used, 2);
+        assert_eq!(idle.rate_limit_remaining, None);
+        assert_eq!(idle.suggested_delay_ms, 0);
+
+        // 8 frames above the low watermark at the default cost
+        assert_eq!(gauge.snapshot(10).suggested_delay_ms, 8);
+        gauge.record_write(Duration::from_millis(5));
+        assert_eq!(gauge.snapshot(10).suggested_delay_ms, 40);
+        gauge.record_write(Duration::from_micros(1500));
+        // (5000 * 7 + 1500) / 8 = 4562us per frame
+        assert_eq!(gauge.snapshot(4).suggested_delay_ms, 10);
+    }
+}
@@ -0,0 +1,286 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+use serde_json::json;
+
+/// Which limit a group join ran into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupLimit {
+    /// The session is already in `MAX_GROUPS_PER_SESSION` groups
+    GroupsPerSession,
+    /// The group already has `MAX_GROUP_SIZE` members
+    GroupSize,
+    /// The group doesn't exist yet and `MAX_GROUPS` groups already do
+    GroupCount,
+}
+
+/// Error returned when adding a session to a group would exceed a configured limit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupLimitExceeded {
+    pub session_id: String,
+    pub group: String,
+    pub limit: GroupLimit,
+    /// The configured maximum that was reached
+    pub max: usize,
+}
+
+impl fmt::Display for GroupLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.limit {
+            GroupLimit::GroupsPerSession => write!(
+                f,
+                "Session {} cannot join {}: already in {} groups (MAX_GROUPS_PER_SESSION)",
+                self.session_id, self.group, self.max
+            ),
+            GroupLimit::GroupSize => write!(
+                f,
+                "Session {} cannot join {}: group has {} members (MAX_GROUP_SIZE)",
+                self.session_id, self.group, self.max
+            ),
+            GroupLimit::GroupCount => write!(
+                f,
+                "Session {} cannot create group {}: {} groups exist (MAX_GROUPS)",
+                self.session_id, self.group, self.max
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GroupLimitExceeded {}
+
+/// Group membership of server-mode sessions, indexed both ways
+#[derive(Debug, Default)]
+pub(crate) struct GroupRegistry {
+    /// Sessions in each group
+    members: HashMap<String, BTreeSet<String>>,
+    /// Groups of each session
+    memberships: HashMap<String, BTreeSet<String>>,
+    /// 0 = no limit
+    max_groups_per_session: usize,
+    /// 0 = no limit
+    max_group_size: usize,
+    /// 0 = no limit
+    max_groups: usize,
+}
+
+impl GroupRegistry {
+    pub(crate) fn new(
+        max_groups_per_session: usize,
+        max_group_size: usize,
+        max_groups: usize,
+    ) -> Self {
+        Self {
+            max_groups_per_session,
+            max_group_size,
+            max_groups,
+            ..Self::default()
+        }
+    }
+
+    /// Add a session to a group, returning whether it wasn't a member already
+    pub(crate) fn join(
+        &mut self,
+        session_id: &str,
+        group: &str,
+    ) -> Result<bool, GroupLimitExceeded> {
+        let joined = self.memberships.get(session_id);
+        if joined.is_some_and(|groups| groups.contains(group)) {
+            return Ok(false);
+        }
+        let exceeded = |limit, max| GroupLimitExceeded {
+            session_id: session_id.to_string(),
+            group: group.to_string(),
+            limit,
+            max,
+        };
+        if self.max_groups_per_session > 0
+            && joined.map_or(0, BTreeSet::len) >= self.max_groups_per_session
+        {
+            return Err(exceeded(
+                GroupLimit::GroupsPerSession,
+                self.max_groups_per_session,
+            ));
+        }
+        if self.max_group_size > 0
+            && self.members.get(group).map_or(0, BTreeSet::len) >= self.max_group_size
+        {
+            return Err(exceeded(GroupLimit::GroupSize, self.max_group_size));
+        }
+        if self.max_groups > 0
+            && !self.members.contains_key(group)
+            && self.members.len() >= self.max_groups
+        {
+            return Err(exceeded(GroupLimit::GroupCount, self.max_groups));
+        }
+
+        self.members
+            .entry(group.to_string())
+            .or_default()
+            .insert(session_id.to_string());
+        self.memberships
+            .entry(session_id.to_string())
+            .or_default()
+            .insert(group.to_string());
+        Ok(true)
+    }
+
+    /// Remove a session from a group, returning whether it was a member
+    pub(crate) fn leave(&mut self, session_id: &str, group: &str) -> bool {
+        let Some(groups) = self.memberships.get_mut(session_id) else {
+            return false;
+        };
+        if !groups.remove(group) {
+            return false;
+        }
+        if groups.is_empty() {
+            self.memberships.remove(session_id);
+        }
+        self.remove_member(group, session_id);
+        true
+    }
+
+    /// Remove a session from every group, returning how many it was in
+    pub(crate) fn remove_session(&mut self, session_id: &str) -> usize {
+        let Some(groups) = self.memberships.remove(session_id) else {
+            return 0;
+        };
+        for group in &groups {
+            self.remove_member(group, session_id);
+        }
+        groups.len()
+    }
+
+    /// Sessions in a group, sorted
+    pub(crate) fn members(&self, group: &str) -> Vec<String> {
+        self.members
+            .get(group)
+            .map(|members| members.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Groups a session is in, sorted
+    pub(crate) fn groups_of(&self, session_id: &str) -> Vec<String> {
+        self.memberships
+            .get(session_id)
+            .map(|groups| groups.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Sessions in at least one group
+    pub(crate) fn sessions(&self) -> Vec<String> {
+        self.memberships.keys().cloned().collect()
+    }
+
+    fn remove_member(&mut self, group: &str, session_id: &str) {
+        if let Some(members) = self.members.get_mut(group) {
+            members.remove(session_id);
+            // Empty groups are dropped so the registry doesn't grow with every name used
+            if members.is_empty() {
+                self.members.remove(group);
+            }
+        }
+    }
+}
+
+/// A group control frame sent by a client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum GroupOp {
+    /// `{"op":"join","group":...}`
+    Join(String),
+    /// `{"op":"leave","group":...}`
+    Leave(String),
+}
+
+/// Recognise a `join`/`leave` control frame
+pub(crate) fn parse_group_op(text: &str) -> Option<GroupOp> {
+    // Regular envelopes don't carry an `op`, so skip the parse for them
+    if !text.contains("\"op\"") {
+        return None;
+    }
+    let json: serde_json::Value = serde_json::from_str(text).ok()?;
+    let group = json.get("group")?.as_str()?.to_string();
+    match json.get("op")?.as_str()? {
+        "join" => Some(GroupOp::Join(group)),
+        "leave" => Some(GroupOp::Leave(group)),
+        _ => None,
+    }
+}
+
+/// Control frame answering a client's group op
+///
+/// `{"op":"joined"|"left","group":...}` on success, `{"op":"join_failed","group":...,
+/// "error":...}` when a join was refused.
+pub(crate) fn group_op_reply(op: &GroupOp, error: Option<String>) -> String {
+    match (op, error) {
+        (GroupOp::Join(group), None) => json!({"op": "joined", "group": group}),
+        (GroupOp::Join(group), Some(error)) => {
+            json!({"op": "join_failed", "group": group, "error": error})
+        }
+        (GroupOp::Leave(group), _) => json!({"op": "left", "group": group}),
+    }
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_join_leave_and_cleanup() {
+        let mut registry = GroupRegistry::new(0, 0, 0);
+        assert!(registry.join("a", "room-7").unwrap());
+        assert!(!registry.join("a", "room-7").unwrap());
+        registry.join("b", "room-7").unwrap();
+        registry.join("a", "lobby").unwrap();
+        assert_eq!(registry.members("room-7"), vec!["a", "b"]);
+        assert_eq!(registry.groups_of("a"), vec!["lobby", "room-7"]);
+
+        assert!(registry.leave("b", "room-7"));
+        assert!(!registry.leave("b", "room-7"));
+        assert_eq!(registry.remove_session("a"), 2);
+        assert!(registry.members("room-7").is_empty());
+        assert!(registry.members.is_empty() && registry.memberships.is_empty());
+    }
+
+    #[test]
+    fn test_limits() {
+        let mut registry = GroupRegistry::new(1, 1, 0);
+        registry.join("a", "room-1").unwrap();
+        let err = registry.join("a", "room-2").unwrap_err();
+        assert_eq!(err.limit, GroupLimit::GroupsPerSession);
+        let err = registry.join("b", "room-1").unwrap_err();
+        assert_eq!(err.limit, GroupLimit::GroupSize);
+        // Rejoining an existing group is not a new membership
+        assert!(!registry.join("a", "room-1").unwrap());
+    }
+
+    #[test]
+    fn test_group_count_limit() {
+        let mut registry = GroupRegistry::new(0, 0, 2);
+        registry.join("a", "room-1").unwrap();
+        registry.join("a", "room-2").unwrap();
+        let err = registry.join("b", "room-3").unwrap_err();
+        assert_eq!(err.limit, GroupLimit::GroupCount);
+        // Existing groups can still be joined
+        assert!(registry.join("b", "room-1").unwrap());
+
+        // An emptied group no longer counts
+        assert!(registry.leave("a", "room-2"));
+        assert!(!registry.members.contains_key("room-2"));
+        assert!(registry.join("b", "room-3").unwrap());
+    }
+
+    #[test]
+    fn test_parse_group_op() {
+        assert_eq!(
+            parse_group_op(r#"{"op":"join","group":"room-7"}"#),
+            Some(GroupOp::Join("room-7".to_string()))
+        );
+        assert_eq!(
+            parse_group_op(r#"{"op":"leave","group":"room-7"}"#),
+            Some(GroupOp::Leave("room-7".to_string()))
+        );
+        assert_eq!(parse_group_op(r#"{"subject":"a","body":"b"}"#), None);
+        assert_eq!(parse_group_op(r#"{"op":"reconnect_ack"}"#), None);
+    }
+}
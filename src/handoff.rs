@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::extract::ws::Message;
+use serde::Serialize;
+use tokio::sync::oneshot;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::close::CloseReason;
+use crate::events::ProviderEvent;
+use crate::server::ServerState;
+
+/// Control op sent to clients asked to move to another instance
+pub(crate) const RECONNECT_OP: &str = "reconnect";
+
+/// Control op clients send back once they have received the reconnect frame
+pub(crate) const RECONNECT_ACK_OP: &str = "reconnect_ack";
+
+/// Query parameter carrying the resume token when reconnecting to the target
+pub(crate) const RESUME_TOKEN_PARAM: &str = "resume_token";
+
+/// Resume tokens shared between provider instances taking part in a handoff
+///
+/// The draining instance issues a token per session; the target instance redeems it when
+/// the client reconnects and keeps the original session id. Instances must share the same
+/// registry (for example in-process, or behind shared storage) for sessions to resume.
+#[derive(Debug, Clone, Default)]
+pub struct ResumeRegistry {
+    tokens: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl ResumeRegistry {
+    /// Issue a single-use token resuming `session_id`
+    pub fn issue(&self, session_id: &str) -> String {
+        let token = Uuid::new_v4().to_string();
+        self.tokens
+            .lock()
+            .unwrap()
+            .insert(token.clone(), session_id.to_string());
+        token
+    }
+
+    /// Redeem a token, returning the session id it resumes
+    pub fn redeem(&self, token: &str) -> Option<String> {
+        self.tokens.lock().unwrap().remove(token)
+    }
+}
+
+/// Phase of a handoff
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HandoffPhase {
+    /// No handoff has been started
+    #[default]
+    Idle,
+    /// Clients are being notified and closed in waves
+    Draining,
+    /// Every client present at the start has been handed off
+    Completed,
+}
+
+/// Progress of the current (or last) handoff
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct HandoffStatus {
+    pub phase: HandoffPhase,
+    /// URL clients are sent to
+    pub target_url: Option<String>,
+    /// Clients connected when the handoff started
+    pub total: usize,
+    /// Clients sent a reconnect frame so far
+    pub notified: usize,
+    /// Clients that acknowledged the reconnect frame
+    pub acknowledged: usize,
+    /// Clients closed without acknowledging before the timeout
+    pub timed_out: usize,
+}
+
+/// Pacing of a handoff
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct HandoffPlan {
+    pub batch_size: usize,
+    pub batch_interval: Duration,
+    pub ack_timeout: Duration,
+}
+
+/// Per-server handoff bookkeeping
+#[derive(Clone, Default)]
+pub(crate) struct HandoffTracker {
+    status: Arc<Mutex<HandoffStatus>>,
+    pending_acks: Arc<Mutex<HashMap<String, oneshot::Sender<()>>>>,
+}
+
+impl HandoffTracker {
+    pub(crate) fn status(&self) -> HandoffStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    fn update(&self, f: impl FnOnce(&mut HandoffStatus)) -> HandoffStatus {
+        let mut status = self.status.lock().unwrap();
+        f(&mut status);
+        status.clone()
+    }
+
+    /// Whether a reconnect frame sent to this session is still unacknowledged
+    pub(crate) fn awaiting_ack(&self, session_id: &str) -> bool {
+        self.pending_acks.lock().unwrap().contains_key(session_id)
+    }
+
+    /// Record a client's acknowledgement of its reconnect frame
+    pub(crate) fn acknowledge(&self, session_id: &str) {
+        if let Some(tx) = self.pending_acks.lock().unwrap().remove(session_id) {
+            let _ = tx.send(());
+        }
+    }
+
+    fn expect_ack(&self, session_id: &str) -> oneshot::Receiver<()> {
+        let (tx, rx) = oneshot::channel();
+        self.pending_acks
+            .lock()
+            .unwrap()
+            .insert(session_id.to_string(), tx);
+        rx
+    }
+
+    fn forget_ack(&self, session_id: &str) {
+        self.pending_acks.lock().unwrap().remove(session_id);
+    }
+}
+
+/// Whether a text frame is a client's reconnect acknowledgement
+pub(crate) fn is_reconnect_ack(text: &str) -> bool {
+    serde_json::from_str::<serde_json::Value>(text)
+        .ok()
+        .and_then(|json| json.get("op")?.as_str().map(|op| op == RECONNECT_ACK_OP))
+        .unwrap_or(false)
+}
+
+/// Start moving every connected client to `target_url`
+///
+/// Fails if a handoff is already draining.
+pub(crate) fn start(
+    state: ServerState,
+    target_url: String,
+    plan: HandoffPlan,
+) -> anyhow::Result<()> {
+    let tracker = state.handoff.clone();
+    {
+        let mut status = tracker.status.lock().unwrap();
+        if status.phase == HandoffPhase::Draining {
+            anyhow::bail!("A handoff is already in progress");
+        }
+        *status = HandoffStatus {
+            phase: HandoffPhase::Draining,
+            target_url: Some(target_url.clone()),
+            ..Default::default()
+        };
+    }
+
+    let tasks = state.tasks.clone();
+    tasks.spawn("handoff", &format!("target={}", target_url), async move {
+        let mut sessions = state.list_client_sessions().await;
+        sessions.sort();
+        tracker.update(|status| status.total = sessions.len());
+        info!(
+            "Handing off {} clients to {} in waves of {}",
+            sessions.len(),
+            target_url,
+            plan.batch_size
+        );
+        state.events.emit(ProviderEvent::HandoffStarted {
+            target_url: target_url.clone(),
+            clients: sessions.len(),
+        });
+
+        let waves: Vec<_> = sessions.chunks(plan.batch_size.max(1)).collect();
+        for (wave_idx, wave) in waves.iter().enumerate() {
+            if wave_idx > 0 {
+                tokio::time::sleep(plan.batch_interval).await;
+            }
+            let handoffs = wave
+                .iter()
+                .map(|session_id| hand_off(&state, session_id, &target_url, plan.ack_timeout));
+            futures::future::join_all(handoffs).await;
+
+            let status = tracker.status();
+            state.events.emit(ProviderEvent::HandoffProgress {
+                notified: status.notified,
+                acknowledged: status.acknowledged,
+                timed_out: status.timed_out,
+                total: status.total,
+            });
+        }
+
+        let status = tracker.update(|status| status.phase = HandoffPhase::Completed);
+        info!(
+            "Handoff to {} complete: {} acknowledged, {} timed out",
+            target_url, status.acknowledged, status.timed_out
+        );
+        state.events.emit(ProviderEvent::HandoffCompleted {
+            acknowledged: status.acknowledged,
+            timed_out: status.timed_out,
+        });
+    });
+    Ok(())
+}
+
+/// Send one client its reconnect frame, wait for the ack, then close it with 1001
+async fn hand_off(state: &ServerState, session_id: &str, target_url: &str, ack_timeout: Duration) {
+    let token = match &state.resume_registry {
+        Some(registry) => registry.issue(session_id),
+        None => Uuid::new_v4().to_string(),
+    };
+    let frame = serde_json::json!({
+        "op": RECONNECT_OP,
+        "url": target_url,
+        "resume_token": token,
+    });
+
+    let ack = state.handoff.expect_ack(session_id);
+    if let Err(e) = state
+        .send_to_client(session_id, Message::Text(frame.to_string()))
+        .await
+    {
+        // Already gone; nothing left to hand off
+        debug!("Skipping handoff of {}: {}", session_id, e);
+        state.handoff.forget_ack(session_id);
+        return;
+    }
+    state.handoff.update(|status| status.notified += 1);
+
+    let acknowledged = matches!(tokio::time::timeout(ack_timeout, ack).await, Ok(Ok(())));
+    state.handoff.forget_ack(session_id);
+    state.handoff.update(|status| {
+        if acknowledged {
+            status.acknowledged += 1;
+        } else {
+            status.timed_out += 1;
+        }
+    });
+    if !acknowledged {
+        warn!("Client {} did not acknowledge handoff in time", session_id);
+    }
+
+    state.close_client(session_id, CloseReason::Handoff).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resume_token_is_single_use() {
+        let registry = ResumeRegistry::default();
+        let token = registry.issue("session-1");
+        assert_eq!(registry.redeem(&token).as_deref(), Some("session-1"));
+        assert_eq!(registry.redeem(&token), None);
+        assert_eq!(registry.redeem("unknown"), None);
+    }
+
+    #[test]
+    fn test_is_reconnect_ack() {
+        assert!(is_reconnect_ack(r#"{"op":"reconnect_ack"}"#));
+        assert!(!is_reconnect_ack(r#"{"op":"reconnect"}"#));
+        assert!(!is_reconnect_ack(r#"{"subject":"a","body":"b"}"#));
+        assert!(!is_reconnect_ack("not json"));
+    }
+}
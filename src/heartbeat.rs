@@ -0,0 +1,357 @@
+use std::time::Duration;
+
+use serde_json::json;
+use tokio::time::Instant;
+
+use crate::connection::ConnectionConfig;
+
+/// `op` of the frame a client sends to propose its heartbeat interval
+pub(crate) const HELLO_OP: &str = "hello";
+
+/// `op` of the server's answer to a hello, confirming the session's interval
+pub(crate) const HELLO_ACK_OP: &str = "hello_ack";
+
+/// Metadata key holding a server session's negotiated heartbeat interval
+pub(crate) const HEARTBEAT_METADATA: &str = "heartbeat_interval_ms";
+
+fn millis(ms: u64) -> Option<Duration> {
+    nonzero(Duration::from_millis(ms))
+}
+
+fn nonzero(duration: Duration) -> Option<Duration> {
+    (!duration.is_zero()).then_some(duration)
+}
+
+/// How often a server client is expected to send something and how long it may stay
+/// silent before it is closed with `IdleTimeout`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SessionHeartbeat {
+    pub(crate) interval: Option<Duration>,
+    pub(crate) idle_timeout: Option<Duration>,
+}
+
+impl SessionHeartbeat {
+    /// The server's defaults, from `HEARTBEAT_INTERVAL` and `IDLE_TIMEOUT`
+    pub(crate) fn from_config(config: &ConnectionConfig) -> Self {
+        Self {
+            interval: nonzero(config.heartbeat_interval),
+            idle_timeout: nonzero(config.idle_timeout),
+        }
+    }
+
+    /// Settle the interval a client proposed in its hello (0 = no preference)
+    ///
+    /// The server's interval is the most it accepts, so a longer proposal gets the
+    /// server's. The idle timeout is scaled by the same factor as the interval, so a
+    /// session is allowed as many missed heartbeats as the defaults allow.
+    pub(crate) fn negotiate(&self, proposed_ms: u64) -> Self {
+        let interval = match (self.interval, millis(proposed_ms)) {
+            (Some(max), Some(proposed)) => Some(proposed.min(max)),
+            (server, proposed) => server.or(proposed),
+        };
+        let idle_timeout = match (self.idle_timeout, self.interval, interval) {
+            (Some(idle), Some(default), Some(interval)) => {
+                Some(idle.mul_f64(interval.as_secs_f64() / default.as_secs_f64()))
+            }
+            (idle, ..) => idle,
+        };
+        Self {
+            interval,
+            idle_timeout,
+        }
+    }
+
+    /// `heartbeat` object of the welcome frame, if the server has anything to advertise
+    pub(crate) fn advertisement(&self) -> Option<serde_json::Value> {
+        (self.interval.is_some() || self.idle_timeout.is_some()).then(|| {
+            json!({
+                "interval_ms": self.interval_ms(),
+                "idle_timeout_ms": self.idle_timeout.map_or(0, |idle| idle.as_millis() as u64),
+            })
+        })
+    }
+
+    /// Negotiated interval in milliseconds, 0 if there is none
+    pub(crate) fn interval_ms(&self) -> u64 {
+        self.interval
+            .map_or(0, |interval| interval.as_millis() as u64)
+    }
+
+    /// `hello_ack` frame confirming this heartbeat to the client
+    pub(crate) fn ack(&self) -> String {
+        json!({
+            "op": HELLO_ACK_OP,
+            "heartbeat_interval_ms": self.interval_ms(),
+            "idle_timeout_ms": self.idle_timeout.map_or(0, |idle| idle.as_millis() as u64),
+        })
+        .to_string()
+    }
+}
+
+/// The interval proposed by a client's `{"op":"hello","heartbeat_interval_ms":...}` frame
+pub(crate) fn parse_hello(text: &str) -> Option<u64> {
+    // Regular envelopes don't carry an `op`, so skip the parse for them
+    if !text.contains("\"op\"") {
+        return None;
+    }
+    let json: serde_json::Value = serde_json::from_str(text).ok()?;
+    if json.get("op")?.as_str()? != HELLO_OP {
+        return None;
+    }
+    Some(
+        json.get("heartbeat_interval_ms")
+            .and_then(serde_json::Value::as_u64)
+            .unwrap_or(0),
+    )
+}
+
+/// `hello` frame proposing `interval`
+pub(crate) fn hello(interval: Duration) -> String {
+    let interval_ms = interval.as_millis() as u64;
+    json!({ "op": HELLO_OP, "heartbeat_interval_ms": interval_ms }).to_string()
+}
+
+/// The heartbeat interval a server announces, from its welcome frame
+/// (`heartbeat.interval_ms`) or its `hello_ack`, and whether the frame is a `hello_ack`
+/// (which is never dispatched)
+pub(crate) fn server_interval(text: &str) -> Option<(u64, bool)> {
+    if !text.contains("heartbeat") {
+        return None;
+    }
+    let json: serde_json::Value = serde_json::from_str(text).ok()?;
+    if json.get("op").and_then(serde_json::Value::as_str) == Some(HELLO_ACK_OP) {
+        return Some((json.get("heartbeat_interval_ms")?.as_u64()?, true));
+    }
+    Some((json.get("heartbeat")?.get("interval_ms")?.as_u64()?, false))
+}
+
+/// When a client connection pings next, at the interval the server asked for or, until
+/// it does, `HEARTBEAT_INTERVAL` (`PING_INTERVAL` without one), and by when the
+/// server must have answered with a pong
+#[derive(Debug)]
+pub(crate) struct ClientHeartbeat {
+    interval: Option<Duration>,
+    next: Instant,
+    pong_timeout: Option<Duration>,
+    /// Deadline of the oldest ping still waiting for its pong
+    pong_due: Option<Instant>,
+}
+
+impl ClientHeartbeat {
+    pub(crate) fn new(interval: Duration) -> Self {
+        let interval = nonzero(interval);
+        Self {
+            interval,
+            next: Instant::now() + interval.unwrap_or_default(),
+            pong_timeout: None,
+            pong_due: None,
+        }
+    }
+
+    /// A client connection's heartbeat, from `HEARTBEAT_INTERVAL` or `PING_INTERVAL` and
+    /// `PONG_TIMEOUT`
+    pub(crate) fn from_config(config: &ConnectionConfig) -> Self {
+        let interval = nonzero(config.heartbeat_interval).unwrap_or(config.ping_interval);
+        Self {
+            pong_timeout: nonzero(config.pong_timeout),
+            ..Self::new(interval)
+        }
+    }
+
+    /// When the next ping is due, if pings are sent at all
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.interval.map(|_| self.next)
+    }
+
+    /// Schedule the ping after the one just sent, and expect its pong
+    pub(crate) fn sent(&mut self) {
+        let now = Instant::now();
+        self.next = now + self.interval.unwrap_or_default();
+        if self.pong_due.is_none() {
+            self.pong_due = self.pong_timeout.map(|timeout| now + timeout);
+        }
+    }
+
+    /// Note a pong from the server
+    pub(crate) fn pong(&mut self) {
+        self.pong_due = None;
+    }
+
+    /// When the connection counts as dead unless a pong arrives first
+    pub(crate) fn pong_deadline(&self) -> Option<Instant> {
+        self.pong_due
+    }
+
+    /// Ping at the interval the server announced from now on; 0 leaves it unchanged
+    pub(crate) fn adopt(&mut self, interval_ms: u64) {
+        if let Some(interval) = millis(interval_ms) {
+            self.interval = Some(interval);
+            self.sent();
+        }
+    }
+}
+
+/// Closes a server session once it has been silent for its idle timeout
+#[derive(Debug)]
+pub(crate) struct IdleTimer {
+    idle_timeout: Option<Duration>,
+    last_frame: Instant,
+}
+
+impl IdleTimer {
+    pub(crate) fn new(heartbeat: &SessionHeartbeat) -> Self {
+        Self {
+            idle_timeout: heartbeat.idle_timeout,
+            last_frame: Instant::now(),
+        }
+    }
+
+    /// Use a newly negotiated heartbeat's idle timeout
+    pub(crate) fn set(&mut self, heartbeat: &SessionHeartbeat) {
+        self.idle_timeout = heartbeat.idle_timeout;
+    }
+
+    /// Note a frame from the client
+    pub(crate) fn touch(&mut self) {
+        self.last_frame = Instant::now();
+    }
+
+    /// When the session counts as idle, if it ever does
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.idle_timeout.map(|idle| self.last_frame + idle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server(interval_ms: u64, idle_timeout_ms: u64) -> SessionHeartbeat {
+        SessionHeartbeat {
+            interval: millis(interval_ms),
+            idle_timeout: millis(idle_timeout_ms),
+        }
+    }
+
+    #[test]
+    fn test_negotiate() {
+        let defaults = server(10_000, 30_000);
+        // A longer proposal gets the server's maximum
+        assert_eq!(defaults.negotiate(60_000), defaults);
+        assert_eq!(defaults.negotiate(0), defaults);
+        // A shorter one is accepted, with the idle timeout scaled to match
+        assert_eq!(defaults.negotiate(5_000), server(5_000, 15_000));
+
+        // Without a server interval the client's is taken as is
+        assert_eq!(server(0, 30_000).negotiate(5_000), server(5_000, 30_000));
+        assert_eq!(server(0, 0).negotiate(0), server(0, 0));
+    }
+
+    #[test]
+    fn test_hello_frames() {
+        assert_eq!(parse_hello(&hello(Duration::from_secs(5))), Some(5_000));
+        assert_eq!(parse_hello(r#"{"op":"hello"}"#), Some(0));
+        assert_eq!(parse_hello(r#"{"op":"join","group":"a"}"#), None);
+        assert_eq!(parse_hello(r#"{"subject":"hello","body":""}"#), None);
+
+        let ack = server(10_000, 30_000).negotiate(5_000).ack();
+        assert_eq!(server_interval(&ack), Some((5_000, true)));
+        let welcome = json!({
+            "session_id": "s1",
+            "heartbeat": server(10_000, 30_000).advertisement(),
+        });
+        assert_eq!(server_interval(&welcome.to_string()), Some((10_000, false)));
+        assert_eq!(server(0, 0).advertisement(), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_idle_timer_uses_negotiated_timeout() {
+        let defaults = server(10_000, 30_000);
+        let mut idle = IdleTimer::new(&defaults);
+        let start = Instant::now();
+        assert_eq!(idle.deadline(), Some(start + Duration::from_secs(30)));
+
+        idle.set(&defaults.negotiate(2_000));
+        tokio::time::advance(Duration::from_secs(4)).await;
+        assert_eq!(idle.deadline(), Some(start + Duration::from_secs(6)));
+        idle.touch();
+        assert_eq!(
+            idle.deadline(),
+            Some(start + Duration::from_secs(4) + Duration::from_secs(6))
+        );
+
+        assert_eq!(IdleTimer::new(&server(10_000, 0)).deadline(), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_client_adopts_server_interval() {
+        let mut heartbeat = ClientHeartbeat::new(Duration::ZERO);
+        assert_eq!(heartbeat.deadline(), None);
+
+        heartbeat.adopt(1_000);
+        assert_eq!(
+            heartbeat.deadline(),
+            Some(Instant::now() + Duration::from_secs(1))
+        );
+        tokio::time::advance(Duration::from_secs(1)).await;
+        heartbeat.sent();
+        assert_eq!(
+            heartbeat.deadline(),
+            Some(Instant::now() + Duration::from_secs(1))
+        );
+        heartbeat.adopt(0);
+        assert_eq!(
+            heartbeat.deadline(),
+            Some(Instant::now() + Duration::from_secs(1))
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_client_pong_deadline() {
+        let config = |entries: &[(&str, &str)]| {
+            let map = entries
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect();
+            ConnectionConfig::from_map(&map).unwrap()
+        };
+        let mut heartbeat = ClientHeartbeat::from_config(&config(&[
+            ("PING_INTERVAL_SEC", "5"),
+            ("PONG_TIMEOUT", "3s"),
+        ]));
+        let start = Instant::now();
+        assert_eq!(heartbeat.deadline(), Some(start + Duration::from_secs(5)));
+        assert_eq!(heartbeat.pong_deadline(), None);
+
+        tokio::time::advance(Duration::from_secs(5)).await;
+        heartbeat.sent();
+        assert_eq!(
+            heartbeat.pong_deadline(),
+            Some(start + Duration::from_secs(8))
+        );
+        heartbeat.pong();
+        assert_eq!(heartbeat.pong_deadline(), None);
+
+        // A later ping doesn't push back the deadline of one still unanswered
+        heartbeat.sent();
+        tokio::time::advance(Duration::from_secs(1)).await;
+        heartbeat.sent();
+        assert_eq!(
+            heartbeat.pong_deadline(),
+            Some(start + Duration::from_secs(8))
+        );
+
+        // HEARTBEAT_INTERVAL_MS takes precedence, and a 0 timeout never expires
+        let mut heartbeat = ClientHeartbeat::from_config(&config(&[
+            ("HEARTBEAT_INTERVAL_MS", "1000"),
+            ("PING_INTERVAL_SEC", "5"),
+            ("PONG_TIMEOUT_SEC", "0"),
+        ]));
+        assert_eq!(
+            heartbeat.deadline(),
+            Some(Instant::now() + Duration::from_secs(1))
+        );
+        heartbeat.sent();
+        assert_eq!(heartbeat.pong_deadline(), None);
+    }
+}
@@ -0,0 +1,297 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, Result};
+use futures::future::BoxFuture;
+use serde::Serialize;
+use tokio::task::JoinHandle;
+use tokio::time::{Instant, MissedTickBehavior};
+use tracing::debug;
+
+use crate::tasks::TaskRegistry;
+
+/// Name of the janitor's reconciliation job
+pub(crate) const RECONCILE_JOB: &str = "reconcile";
+
+/// A periodic background job's schedule and most recent run, from `background_jobs`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BackgroundJob {
+    pub name: String,
+    /// Time between scheduled runs; zero when the job only runs with `run_job_now`
+    pub interval: Duration,
+    /// Completed runs, scheduled or manual
+    pub runs: u64,
+    /// When the last completed run started
+    pub last_run: Option<SystemTime>,
+    pub last_duration: Option<Duration>,
+    /// Items the last completed run processed, as counted by the job
+    pub last_items: usize,
+    /// Whether a run is in progress
+    pub running: bool,
+}
+
+type JobFn = Box<dyn Fn() -> BoxFuture<'static, usize> + Send + Sync>;
+
+struct Job {
+    run: JobFn,
+    /// Held for the length of a run, so a job never overlaps with itself
+    running: tokio::sync::Mutex<()>,
+    status: Mutex<BackgroundJob>,
+}
+
+impl Job {
+    /// Run once, after any run already in progress, returning the items processed
+    async fn run_once(&self) -> usize {
+        let _running = self.running.lock().await;
+        let started_at = SystemTime::now();
+        let started = Instant::now();
+        self.status.lock().unwrap().running = true;
+        let items = (self.run)().await;
+
+        let mut status = self.status.lock().unwrap();
+        status.running = false;
+        status.runs += 1;
+        status.last_run = Some(started_at);
+        status.last_duration = Some(started.elapsed());
+        status.last_items = items;
+        items
+    }
+}
+
+/// The provider's periodic jobs, each on its own schedule
+///
+/// Intervals can be overridden per job with `JOB_<NAME>_INTERVAL`.
+#[derive(Clone, Default)]
+pub(crate) struct JobRegistry {
+    inner: Arc<RegistryState>,
+}
+
+#[derive(Default)]
+struct RegistryState {
+    /// `JOB_<NAME>_INTERVAL` values by lowercase job name
+    intervals: HashMap<String, Duration>,
+    jobs: Mutex<HashMap<String, (Arc<Job>, Option<JoinHandle<()>>)>>,
+}
+
+impl JobRegistry {
+    pub(crate) fn new(intervals: HashMap<String, Duration>) -> Self {
+        Self {
+            inner: Arc::new(RegistryState {
+                intervals,
+                jobs: Mutex::default(),
+            }),
+        }
+    }
+
+    /// Whether a job is registered under `name`
+    pub(crate) fn contains(&self, name: &str) -> bool {
+        self.inner.jobs.lock().unwrap().contains_key(name)
+    }
+
+    /// Run `run` every `interval` (or its configured override) until `stop`, the first
+    /// time one interval from now; a zero interval only runs it through `run_now`
+    ///
+    /// `run` returns how many items it processed. Registering a name again replaces the
+    /// job.
+    pub(crate) fn register<F, Fut>(
+        &self,
+        tasks: &TaskRegistry,
+        name: &str,
+        interval: Duration,
+        run: F,
+    ) where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = usize> + Send + 'static,
+    {
+        let interval = self
+            .inner
+            .intervals
+            .get(name)
+            .map_or(interval, |configured| *configured);
+        let job = Arc::new(Job {
+            run: Box::new(move || Box::pin(run())),
+            running: tokio::sync::Mutex::new(()),
+            status: Mutex::new(BackgroundJob {
+                name: name.to_string(),
+                interval,
+                runs: 0,
+                last_run: None,
+                last_duration: None,
+                last_items: 0,
+                running: false,
+            }),
+        });
+
+        let handle = (!interval.is_zero()).then(|| {
+            let job = Arc::clone(&job);
+            tasks.spawn("job", name, async move {
+                let mut ticks = tokio::time::interval_at(Instant::now() + interval, interval);
+                // A run longer than the interval delays the next one instead of bunching
+                ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                loop {
+                    ticks.tick().await;
+                    job.run_once().await;
+                }
+            })
+        });
+        debug!("Registered background job {} every {:?}", name, interval);
+        let replaced = self
+            .inner
+            .jobs
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), (job, handle));
+        if let Some((_, Some(handle))) = replaced {
+            handle.abort();
+        }
+    }
+
+    /// Run a job now, after its current run if one is in progress, returning the items it
+    /// processed
+    pub(crate) async fn run_now(&self, name: &str) -> Result<usize> {
+        let job = self
+            .inner
+            .jobs
+            .lock()
+            .unwrap()
+            .get(name)
+            .map(|(job, _)| Arc::clone(job))
+            .ok_or_else(|| anyhow!("No background job named {}", name))?;
+        Ok(job.run_once().await)
+    }
+
+    /// Every registered job, by name
+    pub(crate) fn list(&self) -> Vec<BackgroundJob> {
+        let mut jobs: Vec<BackgroundJob> = self
+            .inner
+            .jobs
+            .lock()
+            .unwrap()
+            .values()
+            .map(|(job, _)| job.status.lock().unwrap().clone())
+            .collect();
+        jobs.sort_by(|a, b| a.name.cmp(&b.name));
+        jobs
+    }
+
+    /// Stop every job's schedule
+    pub(crate) fn stop(&self) {
+        for (_, (_, handle)) in self.inner.jobs.lock().unwrap().drain() {
+            if let Some(handle) = handle {
+                handle.abort();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Register a job counting its runs, which takes `duration` to finish; also returns
+    /// the most runs ever in progress at once
+    fn counting_job(
+        registry: &JobRegistry,
+        interval: Duration,
+        duration: Duration,
+    ) -> (Arc<AtomicUsize>, Arc<AtomicUsize>) {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let most_concurrent = Arc::new(AtomicUsize::new(0));
+        let concurrent = Arc::new(AtomicUsize::new(0));
+        let (job_runs, job_most) = (Arc::clone(&runs), Arc::clone(&most_concurrent));
+        registry.register(&TaskRegistry::default(), "count", interval, move || {
+            let runs = Arc::clone(&job_runs);
+            let most = Arc::clone(&job_most);
+            let concurrent = Arc::clone(&concurrent);
+            async move {
+                most.fetch_max(
+                    concurrent.fetch_add(1, Ordering::SeqCst) + 1,
+                    Ordering::SeqCst,
+                );
+                tokio::time::sleep(duration).await;
+                concurrent.fetch_sub(1, Ordering::SeqCst);
+                runs.fetch_add(1, Ordering::SeqCst) + 1
+            }
+        });
+        (runs, most_concurrent)
+    }
+
+    /// Let spawned tasks run up to the current (paused) time
+    async fn settle() {
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_runs_on_schedule() {
+        let registry = JobRegistry::default();
+        let (runs, _) = counting_job(&registry, Duration::from_secs(10), Duration::ZERO);
+
+        tokio::time::advance(Duration::from_secs(9)).await;
+        settle().await;
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+
+        for expected in 1..=3 {
+            tokio::time::advance(Duration::from_secs(10)).await;
+            settle().await;
+            assert_eq!(runs.load(Ordering::SeqCst), expected);
+        }
+        let status = &registry.list()[0];
+        assert_eq!(status.name, "count");
+        assert_eq!(status.runs, 3);
+        assert_eq!(status.last_items, 3);
+        assert!(status.last_run.is_some());
+
+        registry.stop();
+        tokio::time::advance(Duration::from_secs(30)).await;
+        settle().await;
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_run_now_and_interval_override() {
+        let registry = JobRegistry::new(HashMap::from([("count".to_string(), Duration::ZERO)]));
+        let (runs, _) = counting_job(&registry, Duration::from_secs(10), Duration::ZERO);
+
+        // Overridden to manual-only
+        tokio::time::advance(Duration::from_secs(60)).await;
+        settle().await;
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+        assert_eq!(registry.list()[0].interval, Duration::ZERO);
+
+        assert_eq!(registry.run_now("count").await.unwrap(), 1);
+        assert_eq!(registry.list()[0].runs, 1);
+        assert!(registry.run_now("missing").await.is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_runs_never_overlap() {
+        let registry = JobRegistry::default();
+        // Each run outlasts the interval
+        let (runs, most_concurrent) =
+            counting_job(&registry, Duration::from_secs(1), Duration::from_secs(5));
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        settle().await;
+        assert!(registry.list()[0].running);
+
+        // A manual run waits for the scheduled one instead of running alongside it
+        let manual = tokio::spawn({
+            let registry = registry.clone();
+            async move { registry.run_now("count").await.unwrap() }
+        });
+        for _ in 0..20 {
+            tokio::time::advance(Duration::from_secs(1)).await;
+            settle().await;
+        }
+        assert!(manual.await.unwrap() >= 2);
+        assert!(runs.load(Ordering::SeqCst) >= 2);
+        assert_eq!(most_concurrent.load(Ordering::SeqCst), 1);
+        assert!(registry.list()[0].last_duration >= Some(Duration::from_secs(5)));
+        registry.stop();
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::BrokerMessage;
+
+/// Header marking a body that arrived hex-encoded from a peer predating base64 bodies
+pub const LEGACY_HEX_HEADER: &str = "x-legacy-hex";
+
+/// How often the deprecation warning is repeated for the same session
+const WARN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How inbound string bodies that look hex-encoded are treated
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LegacyHexPolicy {
+    /// Decode as hex when the string is even-length hex and not meaningful base64
+    #[default]
+    Accept,
+    /// Never decode as hex
+    Reject,
+    /// Decode every even-length hex string as hex, even if it is also valid base64
+    Prefer,
+}
+
+impl LegacyHexPolicy {
+    /// Parse a `LEGACY_HEX_BODIES` value (`accept`, `reject` or `prefer`, case-insensitive)
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "accept" => Some(Self::Accept),
+            "reject" => Some(Self::Reject),
+            "prefer" => Some(Self::Prefer),
+            _ => None,
+        }
+    }
+
+    /// Name as written in the config
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Accept => "accept",
+            Self::Reject => "reject",
+            Self::Prefer => "prefer",
+        }
+    }
+}
+
+/// The bytes of `body` if `policy` takes it for a legacy hex encoding
+///
+/// Every even-length hex string whose length is a multiple of four is also valid base64.
+/// `Accept` settles those by content: hex wins only if it decodes to text and the base64
+/// reading doesn't.
+pub(crate) fn decode(body: &str, policy: LegacyHexPolicy) -> Option<Vec<u8>> {
+    if policy == LegacyHexPolicy::Reject {
+        return None;
+    }
+    let hex = decode_hex(body)?;
+    if policy == LegacyHexPolicy::Prefer {
+        return Some(hex);
+    }
+    match STANDARD.decode(body) {
+        Err(_) => Some(hex),
+        Ok(base64) if !is_text(&base64) && is_text(&hex) => Some(hex),
+        Ok(_) => None,
+    }
+}
+
+fn decode_hex(body: &str) -> Option<Vec<u8>> {
+    if body.is_empty() || body.len() % 2 != 0 {
+        return None;
+    }
+    body.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+        })
+        .collect()
+}
+
+/// Whether `bytes` read as UTF-8 text without control characters other than whitespace
+fn is_text(bytes: &[u8]) -> bool {
+    std::str::from_utf8(bytes).is_ok_and(|text| {
+        text.chars()
+            .all(|c| !c.is_control() || c.is_ascii_whitespace())
+    })
+}
+
+/// Counts legacy hex bodies and periodically names the sessions still sending them
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LegacyHexTracker {
+    inner: Arc<TrackerState>,
+}
+
+#[derive(Debug, Default)]
+struct TrackerState {
+    bodies: AtomicU64,
+    /// When each session was last warned about
+    warned: Mutex<HashMap<String, Instant>>,
+}
+
+impl LegacyHexTracker {
+    /// Count `msg` if its body was decoded as legacy hex, warning about `session_id` at
+    /// most once per interval
+    pub(crate) fn observe(&self, msg: &BrokerMessage, session_id: &str) {
+        if !msg.headers.contains_key(LEGACY_HEX_HEADER) {
+            return;
+        }
+        self.inner.bodies.fetch_add(1, Ordering::Relaxed);
+
+        let now = Instant::now();
+        let mut warned = self.inner.warned.lock().unwrap();
+        if warned
+            .get(session_id)
+            .is_some_and(|last| now.duration_since(*last) < WARN_INTERVAL)
+        {
+            return;
+        }
+        warned.retain(|_, last| now.duration_since(*last) < WARN_INTERVAL);
+        warned.insert(session_id.to_string(), now);
+        warn!(
+            "Session {} sends hex-encoded bodies, which are deprecated; its peer should move to base64",
+            session_id
+        );
+    }
+
+    /// Legacy hex bodies received so far
+    pub(crate) fn count(&self) -> u64 {
+        self.inner.bodies.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+
+    const POLICIES: [LegacyHexPolicy; 3] = [
+        LegacyHexPolicy::Accept,
+        LegacyHexPolicy::Reject,
+        LegacyHexPolicy::Prefer,
+    ];
+
+    #[test]
+    fn test_clean_base64_never_hex() {
+        for policy in POLICIES {
+            assert_eq!(decode("aGVsbG8=", policy), None, "{:?}", policy);
+        }
+    }
+
+    #[test]
+    fn test_clean_hex() {
+        // 10 characters, so not base64
+        let expected = Some(b"hello".to_vec());
+        assert_eq!(decode("68656c6c6f", LegacyHexPolicy::Accept), expected);
+        assert_eq!(decode("68656C6C6F", LegacyHexPolicy::Prefer), expected);
+        assert_eq!(decode("68656c6c6f", LegacyHexPolicy::Reject), None);
+        assert_eq!(decode("abc", LegacyHexPolicy::Prefer), None);
+    }
+
+    #[test]
+    fn test_ambiguous_strings() {
+        // Hex for "test", and valid base64 decoding to binary
+        let text_hex = "74657374";
+        assert_eq!(
+            decode(text_hex, LegacyHexPolicy::Accept),
+            Some(b"test".to_vec())
+        );
+        assert_eq!(decode(text_hex, LegacyHexPolicy::Reject), None);
+        assert_eq!(
+            decode(text_hex, LegacyHexPolicy::Prefer),
+            Some(b"test".to_vec())
+        );
+
+        // Reads as binary either way, so `Accept` keeps it as base64
+        let binary = "00ff00ff";
+        assert_eq!(decode(binary, LegacyHexPolicy::Accept), None);
+        assert_eq!(decode(binary, LegacyHexPolicy::Reject), None);
+        assert_eq!(
+            decode(binary, LegacyHexPolicy::Prefer),
+            Some(vec![0, 0xff, 0, 0xff])
+        );
+    }
+
+    #[test]
+    fn test_tracker_counts_tagged_messages() {
+        let tracker = LegacyHexTracker::default();
+        let mut msg = BrokerMessage {
+            subject: "s".to_string(),
+            body: Bytes::new(),
+            reply_to: None,
+            headers: HashMap::new(),
+        };
+        tracker.observe(&msg, "a");
+        assert_eq!(tracker.count(), 0);
+
+        msg.headers
+            .insert(LEGACY_HEX_HEADER.to_string(), "true".to_string());
+        tracker.observe(&msg, "a");
+        tracker.observe(&msg, "a");
+        tracker.observe(&msg, "b");
+        assert_eq!(tracker.count(), 3);
+        assert_eq!(tracker.inner.warned.lock().unwrap().len(), 2);
+    }
+}
@@ -1,36 +1,257 @@
 use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::{anyhow, bail, Context as AnyhowContext, Result};
+use arc_swap::ArcSwap;
 use axum::extract::ws::Message as AxumMessage;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
 use bytes::Bytes;
-use futures::{SinkExt, StreamExt};
-use tokio::sync::{mpsc, RwLock};
+use futures::future::BoxFuture;
+use futures::{Stream, StreamExt};
+use tokio::sync::{mpsc, oneshot, RwLock};
 use tokio::task::JoinHandle;
-use tokio_tungstenite::{connect_async, tungstenite::Message};
-use tracing::{debug, error, info, instrument};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{debug, debug_span, error, info, instrument, warn, Instrument, Span};
 use url::Url;
 
+mod acl;
+mod batch;
+mod body_encoding;
+mod body_stream;
+mod budget;
+mod bulk;
+mod byte_array;
+mod capture;
+mod client_task;
+mod clock;
+mod close;
+mod codec;
+mod compression;
+mod configured_links;
 mod connection;
+mod deadline;
+mod deflate;
+mod diagnostics;
+mod dispatch;
+mod drop_notify;
+mod duration;
+mod envelope;
+mod events;
+mod failover;
+mod flow;
+mod groups;
+mod handoff;
+mod heartbeat;
+mod jobs;
+mod legacy_hex;
+mod link_status;
+mod metadata;
+mod metrics;
+mod parse_guard;
+mod pipeline;
+mod platform;
+mod proxy;
+mod rate_limit;
+mod reconcile;
+mod reconnect;
+mod retain;
+mod routing;
 mod server;
-
-use connection::{ConnectionConfig, ConnectionMode};
+mod session_events;
+mod session_store;
+mod signing;
+mod sink;
+mod slow_consumer;
+mod spill;
+mod subject;
+mod supervisor;
+mod tasks;
+mod telemetry;
+mod tenant;
+#[cfg(feature = "test-util")]
+pub mod testing;
+mod tls;
+mod transport;
+
+use batch::MessageBatcher;
+use body_encoding::{BodyDecoding, BASE64, BODY_ENCODING_FIELD};
+use body_stream::{BodyStreams, OutgoingStream, StreamFrame, CONNECTION_LOST, FRAME_CHUNK_BYTES};
+use capture::FrameCapture;
+use client_task::{ClientTask, FrameWriter};
+use compression::AdaptiveCompression;
+use configured_links::ConfiguredLink;
+use connection::{ConnectionConfig, ConnectionMode, LinkConfigSummary};
+use deadline::Deadlines;
+use dispatch::HandlerDispatch;
+use drop_notify::{DropNotifier, DropReason, DroppedMessage};
+use envelope::{EnvelopeVersion, UpgradeOp, UPGRADE_OP};
+use events::EventBus;
+use failover::Endpoints;
+use flow::{FlowGauge, FlowSignal};
+use handoff::ResumeRegistry;
+use heartbeat::ClientHeartbeat;
+use jobs::{JobRegistry, RECONCILE_JOB};
+use legacy_hex::LegacyHexTracker;
+use link_status::{FailedLinks, LinkHealth};
+use metrics::MessageMetrics;
+use parse_guard::ParseFailureReason;
+use pipeline::{NamedStage, Pipeline};
+use reconcile::{Reconciler, Suspect};
+use reconnect::{QueuedFrame, ReconnectBuffer, ReconnectPolicy};
+use retain::RetainedStore;
+use routing::{HandlerOverflow, Route, RouteDelivery};
 use server::{start_server, ServerState};
+use session_store::{SessionRegistration, Sessions};
+use signing::MessageSigner;
+use spill::{Spill, SpillingReceiver};
+use subject::FieldLimits;
+use supervisor::{ServeTask, ServerSupervisor};
+use tasks::TaskRegistry;
+use telemetry::{LabelMetrics, SessionCounters};
 
 // Re-export for main binary
+pub use acl::{ForbiddenReason, ForbiddenSubject, SubjectNotPermitted};
+pub use body_stream::{BodyStreamAborted, STREAM_ID_HEADER, STREAM_LENGTH_HEADER};
+pub use budget::{SessionBufferPolicy, SessionMemory};
+pub use bulk::{BatchReport, SendOutcome};
+pub use byte_array::{ByteArrayPolicy, InvalidByteArray};
+pub use capture::{read_capture, CapturedFrame, FrameDirection};
+pub use close::CloseReason;
+pub use codec::Codec;
+pub use compression::BodyCompression;
 pub use connection::ConnectionConfig as WsConnectionConfig;
+pub use connection::ConnectionMode as WsConnectionMode;
+pub use connection::{ConfigConflict, ServerConfigConflict};
+pub use connection::{ConfigReport, LinkConfigSummary, LinkUpdate, LinkUpdateAction};
+pub use deadline::{DeadlineExceeded, DEADLINE_HEADER};
+pub use diagnostics::{ConnectionDiagnostics, DiagnosticsSnapshot, ServerClientDiagnostics};
+pub use envelope::{
+    UnsupportedEnvelopeVersion, ENVELOPE_V1, ENVELOPE_V2, SUPPORTED_ENVELOPE_VERSIONS,
+};
+pub use events::ProviderEvent;
+pub use failover::FailoverStrategy;
+pub use flow::{FlowControl, FLOW_PAUSE_SUBJECT, FLOW_RESUME_SUBJECT};
+pub use groups::{GroupLimit, GroupLimitExceeded};
+pub use handoff::{HandoffPhase, HandoffStatus, ResumeRegistry as WsResumeRegistry};
+pub use jobs::BackgroundJob;
+pub use legacy_hex::{LegacyHexPolicy, LEGACY_HEX_HEADER};
+pub use link_status::{LinkConnectionStatus, LinkRole, LinkStatus};
+pub use metadata::{MetadataRejected, MetadataRejection, RESERVED_METADATA_PREFIX};
+pub use metrics::{MessageStats, TrafficStats, OVERFLOW_SUBJECT};
+pub use pipeline::{Stage, DECODE_STAGE, DEDUP_STAGE, NORMALIZE_STAGE, VALIDATE_STAGE};
+pub use rate_limit::ServerRatePolicy;
+pub use reconcile::ConsistencyReport;
+pub use routing::HandlerOverflow as WsHandlerOverflow;
+pub use routing::RouteDelivery as WsRouteDelivery;
+pub use server::Capabilities;
+pub use session_events::{
+    SESSION_CONNECTED_SUBJECT, SESSION_DISCONNECTED_SUBJECT, SESSION_EVENT_PREFIX,
+};
+pub use session_store::{MemorySessionStore, SessionStore};
+pub use signing::{InvalidSignature, SIGNATURE_FIELD};
+pub use sink::MessageSink;
+pub use spill::{read_spill, ShutdownReport, SpillReason, SpilledMessage};
+pub use tasks::RuntimeHealth;
+pub use telemetry::LabelStats;
+pub use tenant::{DeadLetterReason, DEFAULT_TENANT, TENANT_HANDLER_HEADER, TENANT_HEADER};
+#[cfg(feature = "test-util")]
+pub use transport::mock;
+pub use transport::{
+    AuthScheme, TungsteniteTransport, WsConnection, WsSink, WsStream, WsTransport,
+};
 
 /// Type alias for message handler callback
 type MessageHandler = Arc<dyn Fn(String, BrokerMessage) -> Result<()> + Send + Sync>;
 
+/// Requests made with `request` awaiting a reply, keyed by the `connection_id` of the
+/// connection the request went out on and its `corr_id`
+type PendingRequests = Arc<std::sync::Mutex<HashMap<(String, String), PendingRequest>>>;
+
+/// A `request` call waiting for its reply
+struct PendingRequest {
+    /// Subject the reply has to arrive on, besides echoing the `corr_id`
+    reply_to: String,
+    reply_tx: oneshot::Sender<BrokerMessage>,
+}
+
+/// Fetches the auth token presented when a client-mode connection is made
+pub type TokenProvider = Arc<dyn Fn() -> BoxFuture<'static, Result<String>> + Send + Sync>;
+
 /// Message type for internal communication
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct BrokerMessage {
     pub subject: String,
     pub body: Bytes,
     pub reply_to: Option<String>,
+    /// Optional string headers carried in the envelope (omitted on the wire when empty)
+    pub headers: HashMap<String, String>,
+}
+
+/// Error returned by provider operations attempted after `shutdown` was called
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProviderShutDown;
+
+impl std::fmt::Display for ProviderShutDown {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "WebSocket provider has been shut down")
+    }
+}
+
+impl std::error::Error for ProviderShutDown {}
+
+/// Error returned when an outbound message exceeds `MAX_MESSAGE_BYTES`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MessageTooLarge {
+    /// Size of the message in bytes (the body, or the whole encoded frame)
+    pub size: usize,
+    pub limit: usize,
+}
+
+impl std::fmt::Display for MessageTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Message of {} bytes exceeds the {} byte limit",
+            self.size, self.limit
+        )
+    }
+}
+
+impl std::error::Error for MessageTooLarge {}
+
+/// Error returned when `flush_component` runs out of time before the queue is written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlushTimeout {
+    pub component_id: String,
+    pub timeout: Duration,
+}
+
+impl std::fmt::Display for FlushTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Outbound queue of component {} not flushed within {:?}",
+            self.component_id, self.timeout
+        )
+    }
+}
+
+impl std::error::Error for FlushTimeout {}
+
+/// How long `publish_sync` waits for its frame to be written before failing
+pub const PUBLISH_SYNC_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Fail with `MessageTooLarge` if `size` exceeds `limit` (0 means no limit)
+fn check_message_size(size: usize, limit: usize) -> Result<()> {
+    if limit > 0 && size > limit {
+        return Err(MessageTooLarge { size, limit }.into());
+    }
+    Ok(())
 }
 
 /// Session information for a WebSocket connection
@@ -42,11 +263,116 @@ pub struct SessionInfo {
 }
 
 /// WebSocket client bundle containing connection and session info
+///
+/// Shared between the consumer and handler maps when a component links in both roles
+/// with the same effective config; the connection closes once neither holds it.
 #[derive(Debug)]
 pub struct WebSocketClientBundle {
-    pub tx: mpsc::UnboundedSender<Message>,
+    /// Bounded queue of frames waiting to be written to the socket
+    pub(crate) tx: mpsc::Sender<QueuedFrame>,
     pub session_info: SessionInfo,
     pub handle: JoinHandle<()>,
+    /// Effective configuration of the connection, redacted; per-message settings are
+    /// swapped in place when the link is re-delivered
+    pub config: ArcSwap<ConnectionConfig>,
+    /// Unredacted configuration the connection is running with, for `migrate_link` and
+    /// link updates
+    connect_config: ArcSwap<ConnectionConfig>,
+    /// Long-lived span covering the connection; per-message spans are its children
+    pub span: Span,
+    /// Inbound messages skipped for this handler because its queue was full
+    dropped: AtomicU64,
+    /// Asks the connection task to write and flush everything queued, then confirm
+    flush_tx: mpsc::UnboundedSender<oneshot::Sender<()>>,
+    /// Whether `BODY_COMPRESSION=adaptive` currently compresses on this connection
+    compression: AdaptiveCompression,
+    /// Traffic and last failure, for `list_links`
+    health: Arc<LinkHealth>,
+    /// Envelope version negotiated with the remote server, shared with the task
+    envelope: Arc<EnvelopeVersion>,
+    /// Component the connection was opened for, as counted in `message_metrics`
+    component_id: String,
+    message_metrics: MessageMetrics,
+    /// Fill of the outbound queue against its watermarks, shared with the task
+    flow: Arc<FlowGauge>,
+    /// Where `FLOW_SIGNALS` pauses and resumes are dispatched
+    client_message_handler: Arc<std::sync::RwLock<Option<MessageHandler>>>,
+    /// URIs the connection fails over between, shared with the task that redials them
+    endpoints: Arc<Endpoints>,
+    /// Connection replies to this connection's requests have to arrive on
+    connection_id: String,
+}
+
+impl WebSocketClientBundle {
+    /// Queue a message on this connection inside a per-message span of the session
+    fn send(&self, msg: Message, subject: &str) -> Result<()> {
+        let sent =
+            debug_span!(parent: &self.span, "message", direction = "outbound", subject = %subject)
+                .in_scope(|| self.tx.try_send(msg.into()))
+                .context("Failed to send message to WebSocket");
+        if let Err(e) = &sent {
+            self.health.record_error(format!("{:#}", e));
+        }
+        sent
+    }
+
+    /// Queue a message the component publishes, counting it once it is queued
+    fn publish(&self, frame: Message, msg: &BrokerMessage) -> Result<()> {
+        self.send(frame, &msg.subject)?;
+        self.message_metrics.record(
+            ConnectionMode::Client,
+            LinkRole::Consumer,
+            &msg.subject,
+            msg.body.len(),
+            Some(&self.component_id),
+        );
+        let queue_used = self.queue_depth();
+        if let Some(signal) = self.flow.observe(queue_used) {
+            WebSocketMessagingProvider::dispatch_flow_signal(
+                &self.client_message_handler,
+                &self.flow,
+                signal,
+                &self.session_info.session_id,
+                &self.component_id,
+                queue_used,
+            );
+        }
+        Ok(())
+    }
+
+    /// The session, with `connected_uri` naming the URI the connection is on now
+    fn current_session_info(&self) -> SessionInfo {
+        let mut info = self.session_info.clone();
+        info.metadata.insert(
+            "connected_uri".to_string(),
+            self.endpoints.active().to_string(),
+        );
+        info
+    }
+
+    /// Frames queued and not yet written
+    fn queue_depth(&self) -> usize {
+        self.tx.max_capacity() - self.tx.capacity()
+    }
+
+    /// Number of inbound messages skipped because this connection's queue was full
+    pub fn dropped_messages(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// Per-connection statistics of a client-mode link, from `client_stats`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientStats {
+    /// Inbound messages skipped because the connection's queue was full
+    pub dropped_messages: u64,
+    /// The connection's `BODY_COMPRESSION` mode
+    pub body_compression: BodyCompression,
+    /// Whether bodies above the threshold are compressed right now; `false` while
+    /// adaptive compression is paused on incompressible data
+    pub compressing: bool,
+    /// Running compressed-to-original size ratio seen by adaptive compression
+    pub compression_ratio: Option<f64>,
 }
 
 impl Drop for WebSocketClientBundle {
@@ -59,34 +385,107 @@ impl Drop for WebSocketClientBundle {
 #[derive(Clone)]
 pub struct WebSocketMessagingProvider {
     /// Components that can receive messages (consumers)
-    consumer_components: Arc<RwLock<HashMap<String, WebSocketClientBundle>>>,
+    consumer_components: Arc<RwLock<HashMap<String, Arc<WebSocketClientBundle>>>>,
     /// Components that can handle messages (handlers)
-    handler_components: Arc<RwLock<HashMap<String, WebSocketClientBundle>>>,
+    handler_components: Arc<RwLock<HashMap<String, Arc<WebSocketClientBundle>>>>,
     /// Default configuration
     default_config: ConnectionConfig,
     /// Session storage for tracking WebSocket connections by session ID
-    session_storage: Arc<RwLock<HashMap<String, String>>>, // session_id -> component_id
+    sessions: Sessions, // session_id -> component_id
     /// Server state for server mode
     server_state: Option<Arc<ServerState>>,
     /// Server handle for cleanup
     server_handle: Arc<RwLock<Option<JoinHandle<Result<()>>>>>,
     /// Server address when running in server mode
     server_addr: Arc<RwLock<Option<SocketAddr>>>,
+    /// The task serving the listener, watched by the task in `server_handle`
+    serve_task: ServeTask,
     /// Message handler for broadcasting messages from remote WS server to components (client mode)
-    client_message_handler: Arc<RwLock<Option<MessageHandler>>>,
+    client_message_handler: Arc<std::sync::RwLock<Option<MessageHandler>>>,
+    /// Provider events (parse-failure cooldowns, disconnects, ...)
+    events: EventBus,
+    /// Transport used to dial client-mode connections
+    transport: Arc<dyn WsTransport>,
+    /// Set once shutdown has been initiated; operations fail with `ProviderShutDown`
+    shutdown: Arc<AtomicBool>,
+    /// Outcome of the teardown, run by the first `shutdown` call of any clone
+    shutdown_report: Arc<tokio::sync::OnceCell<ShutdownReport>>,
+    /// Resume tokens shared with other instances for client handoff
+    resume_registry: Option<ResumeRegistry>,
+    /// Publishes rejected by a link's subject rules
+    forbidden_publishes: Arc<AtomicU64>,
+    /// Inbound messages dropped by `INBOUND_ALLOW` / `INBOUND_DENY`
+    inbound_denied: Arc<AtomicU64>,
+    /// Queued frames dropped for outliving `RECONNECT_BUFFER_MAX_AGE`
+    stale_reconnect_frames: Arc<AtomicU64>,
+    /// Traffic counters per connection label
+    metrics: LabelMetrics,
+    /// Counts inbound bodies decoded as legacy hex
+    legacy_hex: LegacyHexTracker,
+    /// Cross-checks connection state and counts what it finds
+    reconciler: Arc<Reconciler>,
+    /// Periodic maintenance jobs, such as the janitor's reconciliation pass
+    jobs: JobRegistry,
+    /// Streamed bodies being received and published
+    body_streams: BodyStreams,
+    /// Where `shutdown` accounts for the messages it drops
+    spill: Spill,
+    /// Custom inbound pipeline stages, in the order they were added
+    inbound_stages: Vec<NamedStage>,
+    /// Live spawned tasks and dispatcher queues, for `runtime_health`
+    tasks: TaskRegistry,
+    /// Links whose last connection attempt failed, for `list_links`
+    failed_links: FailedLinks,
+    /// Clock and counter for `deadline_unix_ms` headers
+    deadlines: Deadlines,
+    /// Source of a fresh auth token for every connection attempt, overriding `AUTH_TOKEN`
+    token_provider: Option<TokenProvider>,
+    /// Requests waiting for the reply on their `_INBOX` subject
+    pending_requests: PendingRequests,
+    /// Message counters by mode, link role and subject prefix, for `metrics_text`
+    message_metrics: MessageMetrics,
+    /// Workers feeding handler queues under `HANDLER_CONCURRENCY`
+    handler_dispatch: HandlerDispatch,
+    /// Client links declared with `LINK_<name>_*` keys, for `start_configured_links`
+    configured_links: Vec<ConfiguredLink>,
 }
 
 impl Default for WebSocketMessagingProvider {
     fn default() -> Self {
+        let events = EventBus::default();
         Self {
             consumer_components: Arc::new(RwLock::new(HashMap::new())),
             handler_components: Arc::new(RwLock::new(HashMap::new())),
             default_config: ConnectionConfig::default(),
-            session_storage: Arc::new(RwLock::new(HashMap::new())),
+            sessions: Sessions::default(),
             server_state: None,
             server_handle: Arc::new(RwLock::new(None)),
             server_addr: Arc::new(RwLock::new(None)),
-            client_message_handler: Arc::new(RwLock::new(None)),
+            serve_task: ServeTask::default(),
+            client_message_handler: Arc::new(std::sync::RwLock::new(None)),
+            events: events.clone(),
+            transport: Arc::new(TungsteniteTransport),
+            shutdown: Arc::new(AtomicBool::new(false)),
+            shutdown_report: Arc::default(),
+            resume_registry: None,
+            forbidden_publishes: Arc::new(AtomicU64::new(0)),
+            inbound_denied: Arc::new(AtomicU64::new(0)),
+            stale_reconnect_frames: Arc::new(AtomicU64::new(0)),
+            metrics: LabelMetrics::default(),
+            legacy_hex: LegacyHexTracker::default(),
+            reconciler: Arc::default(),
+            jobs: JobRegistry::default(),
+            body_streams: BodyStreams::default(),
+            spill: Spill::default(),
+            inbound_stages: Vec::new(),
+            tasks: TaskRegistry::default(),
+            failed_links: FailedLinks::default(),
+            deadlines: Deadlines::new(events),
+            token_provider: None,
+            pending_requests: PendingRequests::default(),
+            message_metrics: MessageMetrics::default(),
+            handler_dispatch: HandlerDispatch::default(),
+            configured_links: Vec::new(),
         }
     }
 }
@@ -100,12 +499,86 @@ impl WebSocketMessagingProvider {
     /// Create provider from configuration map
     pub fn from_config(config: HashMap<String, String>) -> Result<Self> {
         let default_config = ConnectionConfig::from_map(&config)?;
+        let body_streams = BodyStreams::new(
+            default_config.body_stream_prefix_bytes,
+            default_config.body_stream_subscribe_timeout,
+        );
+        let spill = Spill::new(&default_config.shutdown_spill_path);
+        let jobs = JobRegistry::new(default_config.job_intervals.clone());
+        let message_metrics = MessageMetrics::new(&default_config);
+        let tasks = TaskRegistry::default();
+        let handler_dispatch =
+            HandlerDispatch::new(default_config.handler_concurrency, tasks.clone());
+        let configured_links = configured_links::parse(&config)?;
         Ok(Self {
+            handler_dispatch,
+            tasks,
             default_config,
+            body_streams,
+            spill,
+            jobs,
+            message_metrics,
+            inbound_stages: Vec::new(),
+            configured_links,
             ..Default::default()
         })
     }
 
+    /// Dial client-mode connections through the given transport instead of real sockets
+    pub fn with_transport(mut self, transport: impl WsTransport) -> Self {
+        self.transport = Arc::new(transport);
+        self
+    }
+
+    /// Fetch the auth token with `provider` before every connection attempt, including
+    /// reconnects after a link update or `migrate_link`, instead of using `AUTH_TOKEN`
+    ///
+    /// Meant for short-lived credentials that would be stale by the time a link
+    /// reconnects. A failed fetch fails the connection attempt.
+    pub fn with_token_provider<F, Fut>(mut self, provider: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = Result<String>> + Send + 'static,
+    {
+        self.token_provider = Some(Arc::new(move || Box::pin(provider())));
+        self
+    }
+
+    /// Check `deadline_unix_ms` headers against `clock` instead of the system clock
+    ///
+    /// Readings that go back in time by more than a second are held to the last one
+    /// advanced by monotonic time, as with the system clock.
+    pub fn with_clock(
+        mut self,
+        clock: impl Fn() -> std::time::SystemTime + Send + Sync + 'static,
+    ) -> Self {
+        self.deadlines = self.deadlines.with_clock(clock);
+        self
+    }
+
+    /// Record client-mode sessions in the given store instead of in memory
+    pub fn with_session_store(mut self, store: impl SessionStore) -> Self {
+        self.sessions = Sessions::new(store);
+        self
+    }
+
+    /// Add a custom stage to the inbound pipeline under `name`
+    ///
+    /// Unless `INBOUND_STAGES` places it, the stage runs after the built-in ones, in the
+    /// order stages were added. Stages apply to server connections and to client
+    /// connections opened after this call.
+    pub fn with_inbound_stage(mut self, name: &str, stage: impl Stage) -> Self {
+        self.inbound_stages
+            .push((name.to_string(), Arc::new(stage)));
+        self
+    }
+
+    /// Share resume tokens with other instances so handed-off clients keep their sessions
+    pub fn with_resume_registry(mut self, registry: ResumeRegistry) -> Self {
+        self.resume_registry = Some(registry);
+        self
+    }
+
     /// Start WebSocket server if in server mode
     pub async fn start_server_if_needed(&mut self) -> Result<()> {
         if self.default_config.mode == ConnectionMode::Server {
@@ -114,66 +587,444 @@ impl WebSocketMessagingProvider {
                 self.default_config.uri
             );
 
-            // Create a clone of self for the message handler
-            let _session_storage = Arc::clone(&self.session_storage);
-            let _handler_components = Arc::clone(&self.handler_components);
-
-            // Create server state with message handler
-            let server_state = ServerState::new(move |session_id, msg| {
-                // This handler will be called when server receives messages from clients
-                debug!(
-                    "Server received message from session {}: subject={}",
-                    session_id, msg.subject
-                );
-                // In a full implementation, this would invoke the handler component
-                // For now, we just log it
-                Ok(())
-            });
+            // Messages from clients are buffered until `set_message_sink` installs a sink
+            let server_state = ServerState::buffering(self.default_config.message_buffer_capacity)
+                .with_retention(RetainedStore::new(
+                    self.default_config.retain_subjects.clone(),
+                    (!self.default_config.retain_ttl.is_zero())
+                        .then_some(self.default_config.retain_ttl),
+                    self.default_config.retain_clear_on_empty,
+                    self.default_config.retain_max_entries,
+                ))
+                .with_config(self.default_config.clone())
+                .with_metrics(self.metrics.clone())
+                .with_message_metrics(self.message_metrics.clone())
+                .with_legacy_hex(self.legacy_hex.clone())
+                .with_deadlines(self.deadlines.clone())
+                .with_pipeline(Pipeline::new(&self.default_config, &self.inbound_stages))
+                .with_events(self.events.clone())
+                .with_body_streams(self.body_streams.clone())
+                .with_tasks(self.tasks.clone())
+                .with_spill(self.spill.clone());
+            let server_state = match &self.resume_registry {
+                Some(registry) => server_state.with_resume_registry(registry.clone()),
+                None => server_state,
+            };
 
             self.server_state = Some(Arc::new(server_state.clone()));
 
             // Start server
-            let (addr, handle) = start_server(&self.default_config.uri, server_state).await?;
+            let (addr, serve) =
+                start_server(&self.default_config.uri, server_state.clone()).await?;
 
             let mut server_addr = self.server_addr.write().await;
             *server_addr = Some(addr);
-
+            drop(server_addr);
+
+            // Watched, so a server task that dies clears the address and drops its clients
+            let supervisor = ServerSupervisor {
+                state: server_state,
+                server_addr: Arc::clone(&self.server_addr),
+                shutdown: Arc::clone(&self.shutdown),
+                serve_task: Arc::clone(&self.serve_task),
+            };
             let mut server_handle = self.server_handle.write().await;
-            *server_handle = Some(handle);
+            *server_handle = Some(supervisor.spawn(addr, serve));
 
             info!("WebSocket server started on {}", addr);
         }
         Ok(())
     }
 
+    /// Fail with `ProviderShutDown` once shutdown has been initiated
+    fn ensure_running(&self) -> Result<()> {
+        if self.shutdown.load(Ordering::SeqCst) {
+            return Err(ProviderShutDown.into());
+        }
+        Ok(())
+    }
+
+    /// Reject a publish the link's subject rules forbid, counting and logging it
+    fn check_publish(
+        &self,
+        component_id: &str,
+        bundle: &WebSocketClientBundle,
+        subject: &str,
+    ) -> Result<()> {
+        let Some(reason) = acl::check_publish(&bundle.config.load(), subject) else {
+            return Ok(());
+        };
+        self.forbidden_publishes.fetch_add(1, Ordering::Relaxed);
+        warn!(
+            "Rejected publish from component {} on {}: {:?}",
+            component_id, subject, reason
+        );
+        Err(ForbiddenSubject {
+            component_id: component_id.to_string(),
+            subject: subject.to_string(),
+            reason,
+        }
+        .into())
+    }
+
+    /// Bytes of frames queued for server clients, counted against
+    /// `SERVER_MEMORY_BUDGET_BYTES` (server mode)
+    pub fn server_queued_bytes(&self) -> usize {
+        self.server_state
+            .as_ref()
+            .map_or(0, |state| state.queued_bytes())
+    }
+
+    /// The `n` server clients holding the most buffered bytes, largest first, counted
+    /// against `MAX_SESSION_BUFFER_BYTES` (server mode)
+    pub fn top_sessions_by_memory(&self, n: usize) -> Vec<SessionMemory> {
+        self.server_state
+            .as_ref()
+            .map_or_else(Vec::new, |state| state.top_sessions_by_memory(n))
+    }
+
+    /// Bodies received hex-encoded from peers predating base64 bodies
+    /// (`legacy_hex_bodies_total`)
+    pub fn legacy_hex_body_count(&self) -> u64 {
+        self.legacy_hex.count()
+    }
+
+    /// Live tasks per label and how long each dispatcher has been stuck on its current
+    /// message, for diagnosing a wedged provider
+    pub fn runtime_health(&self) -> RuntimeHealth {
+        self.tasks.health()
+    }
+
+    /// Number of publishes rejected by link subject rules since startup
+    pub fn forbidden_publish_count(&self) -> u64 {
+        self.forbidden_publishes.load(Ordering::Relaxed)
+    }
+
+    /// Number of messages from remote servers dropped by link inbound subject rules
+    /// since startup
+    pub fn inbound_denied_count(&self) -> u64 {
+        self.inbound_denied.load(Ordering::Relaxed)
+    }
+
+    /// Frames queued before a reconnect and dropped instead of written for being older
+    /// than `RECONNECT_BUFFER_MAX_AGE` since startup
+    pub fn stale_reconnect_frame_count(&self) -> u64 {
+        self.stale_reconnect_frames.load(Ordering::Relaxed)
+    }
+
+    /// Messages dropped for being past their `deadline_unix_ms`: rejected publishes,
+    /// queued frames expiring before they were written and expired inbound messages
+    pub fn expired_message_count(&self) -> u64 {
+        self.deadlines.expired_count()
+    }
+
+    /// Inbound frames rejected instead of dispatched since startup, keyed by reason
+    /// (`invalid_utf8`, `invalid_json`, `invalid_subject`, `field_too_long`, `invalid_body`,
+    /// `unsupported_version`, `invalid_signature`)
+    pub fn parse_failure_counts(&self) -> HashMap<String, u64> {
+        self.metrics.parse_failure_counts()
+    }
+
+    /// Traffic counters of every connection label seen so far (connections without a
+    /// `LABEL` are not included)
+    pub fn label_metrics(&self) -> HashMap<String, LabelStats> {
+        self.metrics.snapshot()
+    }
+
+    /// Server-mode messages whose handler (message sink) timed out so far
+    pub fn handler_timeout_count(&self) -> u64 {
+        self.server_state
+            .as_ref()
+            .map_or(0, |state| state.sink.timeout_count())
+    }
+
+    /// Server-mode messages shed or held back so far because all clients together exceeded
+    /// `SERVER_MAX_MESSAGES_PER_SEC` (`server_rate_limited_total`)
+    pub fn server_rate_limited_count(&self) -> u64 {
+        self.server_state
+            .as_ref()
+            .map_or(0, |state| state.rate_limiter.limited_count())
+    }
+
+    /// Server client connections closed by the provider so far, keyed by
+    /// `CloseReason::label`
+    pub fn close_counts(&self) -> HashMap<String, u64> {
+        self.metrics.close_counts()
+    }
+
+    /// Inconsistencies found by reconciliation passes so far (`state_inconsistencies_total`)
+    pub fn state_inconsistencies_total(&self) -> u64 {
+        self.reconciler.inconsistencies_total()
+    }
+
+    /// Cross-check the component connections, session storage and server clients
+    ///
+    /// Orphan session entries, stale server clients and group memberships of disconnected
+    /// sessions are removed; components whose connection ended, or that track sessions but
+    /// have none, are reported and need a relink. Orphan sessions and stale clients are only
+    /// removed once two consecutive passes see them, so connections being set up or torn
+    /// down aren't mistaken for inconsistencies.
+    pub async fn consistency_report(&self) -> ConsistencyReport {
+        let mut report = ConsistencyReport::default();
+        {
+            let consumers = self.consumer_components.read().await;
+            let handlers = self.handler_components.read().await;
+            let sessions = self.sessions.list().await;
+
+            let orphans = sessions
+                .iter()
+                .filter(|(_, cid)| !consumers.contains_key(cid) && !handlers.contains_key(cid))
+                .map(|(sid, _)| sid.clone())
+                .collect();
+            for session_id in self.reconciler.confirm(Suspect::OrphanSession, orphans) {
+                self.sessions.remove(&session_id).await;
+                report.orphan_sessions.push(session_id);
+            }
+
+            // A connection shared by both roles is checked once
+            let separate_handlers = handlers.iter().filter(|(component_id, bundle)| {
+                !consumers
+                    .get(*component_id)
+                    .is_some_and(|consumer| Arc::ptr_eq(consumer, bundle))
+            });
+            for (component_id, bundle) in consumers.iter().chain(separate_handlers) {
+                if bundle.handle.is_finished() {
+                    report.dead_connections.push(component_id.clone());
+                } else if bundle.config.load().enable_session_tracking
+                    && !sessions.iter().any(|(_, cid)| cid == component_id)
+                {
+                    report.untracked_connections.push(component_id.clone());
+                }
+            }
+        }
+
+        if let Some(ref server_state) = self.server_state {
+            let closed = server_state.closed_clients().await;
+            for session_id in self.reconciler.confirm(Suspect::StaleClient, closed) {
+                server_state.remove_client(&session_id).await;
+                report.stale_clients.push(session_id);
+            }
+            report.orphan_memberships = server_state.prune_orphan_memberships().await;
+
+            // A mismatch that survives two passes unchanged is a leaked charge, not a
+            // frame caught between the two counters
+            let (sessions, budget) = server_state.queued_bytes_accounting();
+            let mismatch = (sessions != budget)
+                .then(|| format!("sessions {} bytes, budget {} bytes", sessions, budget));
+            report.buffer_accounting = self
+                .reconciler
+                .confirm(Suspect::BufferAccounting, mismatch.into_iter().collect());
+        }
+
+        self.reconciler.finish(report)
+    }
+
+    /// Start the janitor, which runs a reconciliation pass every `RECONCILE_INTERVAL`
+    ///
+    /// The pass is the `reconcile` background job, so `JOB_RECONCILE_INTERVAL` takes
+    /// precedence. Does nothing if the interval is 0 or the janitor is already running; it
+    /// stops on `shutdown`.
+    pub fn start_janitor(&self) {
+        let interval = self
+            .default_config
+            .job_intervals
+            .get(RECONCILE_JOB)
+            .copied()
+            .unwrap_or(self.default_config.reconcile_interval);
+        if interval.is_zero() || self.jobs.contains(RECONCILE_JOB) {
+            return;
+        }
+        let provider = self.clone();
+        self.jobs
+            .register(&self.tasks, RECONCILE_JOB, interval, move || {
+                let provider = provider.clone();
+                async move { provider.consistency_report().await.total() }
+            });
+        debug!("Janitor started, reconciling every {:?}", interval);
+    }
+
+    /// Schedule and most recent run of every background job, by name
+    pub fn background_jobs(&self) -> Vec<BackgroundJob> {
+        self.jobs.list()
+    }
+
+    /// Run a background job now, after its current run if one is in progress, returning
+    /// the number of items it processed
+    ///
+    /// Fails if no job is registered under `name`.
+    pub async fn run_job_now(&self, name: &str) -> Result<usize> {
+        self.ensure_running()?;
+        self.jobs.run_now(name).await
+    }
+
+    /// Subscribe to provider events emitted from now on
+    pub fn subscribe_events(&self) -> tokio::sync::broadcast::Receiver<ProviderEvent> {
+        self.events.subscribe()
+    }
+
     /// Get server address if running in server mode
+    ///
+    /// `None` while the server is stopped, e.g. after its task died and until
+    /// `SERVER_RESTART` binds it again.
     pub async fn get_server_addr(&self) -> Option<SocketAddr> {
         let addr = self.server_addr.read().await;
         *addr
     }
 
+    /// Whether the server is accepting connections (server mode)
+    ///
+    /// Turns false when the server task stops while the provider is running, which also
+    /// raises `ProviderEvent::ServerStopped`.
+    pub async fn is_server_running(&self) -> bool {
+        self.server_addr.read().await.is_some()
+    }
+
     /// Send message to a specific WebSocket client (server mode)
     pub async fn send_to_ws_client(&self, session_id: &str, message: BrokerMessage) -> Result<()> {
+        self.ensure_running()?;
+        self.deadlines.check(&message)?;
         if let Some(ref server_state) = self.server_state {
-            let msg = self.encode_message_to_axum(&message)?;
-            server_state.send_to_client(session_id, msg).await?;
+            server_state
+                .send_to_client_with(session_id, || {
+                    Self::encode_axum_checked(
+                        &message,
+                        &self.default_config,
+                        &server_state.compression,
+                    )
+                })
+                .await?;
+            self.record_server_send(&message, Some(session_id));
             Ok(())
         } else {
             bail!("Provider is not in server mode")
         }
     }
 
-    /// Broadcast message to all WebSocket clients (server mode)
-    pub async fn broadcast_to_clients(&self, message: BrokerMessage) -> Result<()> {
+    /// Close a WebSocket client's connection with `reason`, either after or ahead of the
+    /// frames still queued for it (server mode)
+    ///
+    /// A `graceful` close is queued behind the client's data, so everything sent before it
+    /// is delivered first. Otherwise the queued data frames are discarded and the close
+    /// frame is written next, so a client with a deep queue is disconnected at once.
+    /// Returns how many frames were discarded.
+    pub async fn close_session(
+        &self,
+        session_id: &str,
+        reason: CloseReason,
+        graceful: bool,
+    ) -> Result<usize> {
+        self.ensure_running()?;
+        let Some(ref server_state) = self.server_state else {
+            bail!("Provider is not in server mode")
+        };
+        let closed = if graceful {
+            server_state
+                .close_client(session_id, reason)
+                .await
+                .then_some(0)
+        } else {
+            server_state.close_client_now(session_id, reason).await
+        };
+        closed.with_context(|| format!("Client session not found: {}", session_id))
+    }
+
+    /// Close a WebSocket client's connection with the code and reason of `reason` (server mode)
+    ///
+    /// Same as a graceful `close_session`.
+    pub async fn disconnect_ws_client(&self, session_id: &str, reason: CloseReason) -> Result<()> {
+        self.ensure_running()?;
         if let Some(ref server_state) = self.server_state {
-            let msg = self.encode_message_to_axum(&message)?;
-            server_state.broadcast(msg).await?;
+            if !server_state.close_client(session_id, reason).await {
+                bail!("Client session not found: {}", session_id);
+            }
             Ok(())
         } else {
             bail!("Provider is not in server mode")
         }
     }
 
+    /// Send a request to a specific WebSocket client and wait for its reply (server mode)
+    ///
+    /// The request carries a generated `reply_to` under `INBOX_PREFIX` and a `corr_id`; the
+    /// first message the client publishes on that subject echoing the same `corr_id` is
+    /// returned instead of reaching the handler.
+    pub async fn request_from_client(
+        &self,
+        session_id: &str,
+        subject: String,
+        body: Bytes,
+        timeout: Duration,
+    ) -> Result<BrokerMessage> {
+        self.ensure_running()?;
+        if let Some(ref server_state) = self.server_state {
+            server_state
+                .request_client(session_id, subject, body, timeout)
+                .await
+        } else {
+            bail!("Provider is not in server mode")
+        }
+    }
+
+    /// Broadcast message to all WebSocket clients (server mode)
+    ///
+    /// Returns the number of clients the message was queued for; with no clients connected
+    /// this returns 0 without encoding the message.
+    pub async fn broadcast_to_clients(&self, message: BrokerMessage) -> Result<usize> {
+        self.ensure_running()?;
+        self.deadlines.check(&message)?;
+        if let Some(ref server_state) = self.server_state {
+            // Compressed bodies are size-checked once encoded
+            if self.default_config.body_compression == BodyCompression::None {
+                check_message_size(message.body.len(), self.default_config.max_message_bytes)?;
+            }
+            let sent = server_state.broadcast_and_retain(&message).await?;
+            self.record_server_send(&message, None);
+            Ok(sent)
+        } else {
+            bail!("Provider is not in server mode")
+        }
+    }
+
+    /// Install the sink receiving messages from WebSocket clients (server mode)
+    ///
+    /// Until a sink is installed, messages are buffered (up to `MESSAGE_BUFFER_CAPACITY`);
+    /// they are flushed to the new sink in arrival order before it receives anything else.
+    /// Installing another sink later replaces the current one.
+    pub async fn set_message_sink(&self, sink: impl MessageSink) -> Result<()> {
+        self.ensure_running()?;
+        let Some(ref server_state) = self.server_state else {
+            bail!("Provider is not in server mode");
+        };
+        server_state.set_message_sink(Arc::new(sink)).await;
+        Ok(())
+    }
+
+    /// Move every connected client to another instance at `target_url` (server mode)
+    ///
+    /// Clients get a `{"op":"reconnect","url":...,"resume_token":...}` frame in waves of
+    /// `HANDOFF_BATCH_SIZE`, and each is closed with code 1001 once it acknowledges with
+    /// `{"op":"reconnect_ack"}` or `HANDOFF_ACK_TIMEOUT` passes. Progress is reported
+    /// through `handoff_status` and provider events.
+    pub async fn initiate_handoff(&self, target_url: &str) -> Result<()> {
+        self.ensure_running()?;
+        let Some(ref server_state) = self.server_state else {
+            bail!("Provider is not in server mode");
+        };
+        handoff::start(
+            ServerState::clone(server_state),
+            target_url.to_string(),
+            self.default_config.handoff_plan(),
+        )
+    }
+
+    /// Progress of the current or last handoff
+    pub fn handoff_status(&self) -> HandoffStatus {
+        self.server_state
+            .as_ref()
+            .map(|state| state.handoff.status())
+            .unwrap_or_default()
+    }
+
     /// List all connected WebSocket client sessions (server mode)
     pub async fn list_ws_clients(&self) -> Result<Vec<String>> {
         if let Some(ref server_state) = self.server_state {
@@ -183,26 +1034,202 @@ impl WebSocketMessagingProvider {
         }
     }
 
-    /// Encode a broker message into an Axum WebSocket message (for server mode)
-    fn encode_message_to_axum(&self, msg: &BrokerMessage) -> Result<AxumMessage> {
-        let json = serde_json::json!({
-            "subject": msg.subject,
-            "body": base64::encode(&msg.body),
-            "reply_to": msg.reply_to,
-        });
+    /// Get the session info of a WebSocket client, including its groups (server mode)
+    pub async fn ws_client_info(&self, session_id: &str) -> Result<Option<SessionInfo>> {
+        if let Some(ref server_state) = self.server_state {
+            Ok(server_state.session_info(session_id).await)
+        } else {
+            bail!("Provider is not in server mode")
+        }
+    }
+
+    /// Store a metadata entry on a WebSocket client's session (server mode)
+    ///
+    /// Fails with `MetadataRejected` for keys with the reserved `_wcp_` prefix and when
+    /// `MAX_METADATA_ENTRIES`, `MAX_METADATA_KEY_LEN` or `MAX_METADATA_VALUE_LEN` would be
+    /// exceeded.
+    pub async fn set_session_metadata(
+        &self,
+        session_id: &str,
+        key: &str,
+        value: impl Into<String>,
+    ) -> Result<()> {
+        self.ensure_running()?;
+        if let Some(ref server_state) = self.server_state {
+            server_state
+                .set_session_metadata(session_id, key, value.into())
+                .await
+        } else {
+            bail!("Provider is not in server mode")
+        }
+    }
+
+    /// Add a WebSocket client to a group (server mode)
+    ///
+    /// Clients can also join and leave groups themselves with `{"op":"join","group":...}` and
+    /// `{"op":"leave","group":...}` frames. Fails with `GroupLimitExceeded` when
+    /// `MAX_GROUPS_PER_SESSION`, `MAX_GROUP_SIZE` or `MAX_GROUPS` would be exceeded.
+    pub async fn add_ws_client_to_group(&self, session_id: &str, group: &str) -> Result<()> {
+        self.ensure_running()?;
+        if let Some(ref server_state) = self.server_state {
+            server_state.add_to_group(session_id, group).await?;
+            Ok(())
+        } else {
+            bail!("Provider is not in server mode")
+        }
+    }
+
+    /// Remove a WebSocket client from a group, returning whether it was a member (server mode)
+    pub async fn remove_ws_client_from_group(&self, session_id: &str, group: &str) -> Result<bool> {
+        if let Some(ref server_state) = self.server_state {
+            Ok(server_state.remove_from_group(session_id, group))
+        } else {
+            bail!("Provider is not in server mode")
+        }
+    }
+
+    /// List the WebSocket clients in a group (server mode)
+    pub async fn list_ws_group_members(&self, group: &str) -> Result<Vec<String>> {
+        if let Some(ref server_state) = self.server_state {
+            Ok(server_state.list_group_members(group))
+        } else {
+            bail!("Provider is not in server mode")
+        }
+    }
+
+    /// Send each message to its own WebSocket client in one call, e.g. a personalized
+    /// notification per session (server mode)
+    ///
+    /// Returns one `SendOutcome` per message, in order; a message that can't be sent
+    /// doesn't stop the rest. The clients lock is taken once per few hundred messages
+    /// instead of once per message, which makes this much cheaper than calling
+    /// `send_to_ws_client` in a loop for thousands of sessions.
+    pub async fn send_batch(&self, messages: Vec<(String, BrokerMessage)>) -> Result<BatchReport> {
+        self.ensure_running()?;
+        let Some(ref server_state) = self.server_state else {
+            bail!("Provider is not in server mode")
+        };
+        let report = server_state
+            .send_batch(messages, |message| {
+                self.deadlines.check(message)?;
+                Self::encode_axum_checked(message, &self.default_config, &server_state.compression)
+            })
+            .await;
+        debug!(
+            "Sent batch of {} messages, {} not sent",
+            report.outcomes.len(),
+            report.failed()
+        );
+        Ok(report)
+    }
+
+    /// Send a message to every WebSocket client in a group (server mode)
+    ///
+    /// Returns the number of clients the message was queued for.
+    pub async fn send_to_ws_group(&self, group: &str, message: BrokerMessage) -> Result<usize> {
+        self.ensure_running()?;
+        self.deadlines.check(&message)?;
+        if let Some(ref server_state) = self.server_state {
+            let sent = server_state.send_to_group(group, &message).await?;
+            self.record_server_send(&message, None);
+            Ok(sent)
+        } else {
+            bail!("Provider is not in server mode")
+        }
+    }
+
+    /// Count a message sent to server clients in the message metrics
+    fn record_server_send(&self, message: &BrokerMessage, session_id: Option<&str>) {
+        self.message_metrics.record(
+            ConnectionMode::Server,
+            LinkRole::Consumer,
+            &message.subject,
+            message.body.len(),
+            session_id,
+        );
+    }
+
+    /// Message counters in the Prometheus text format, as served on `/metrics` in server
+    /// mode
+    ///
+    /// Series are labelled by `mode`, `role` and the first `METRIC_SUBJECT_DEPTH` subject
+    /// tokens. Once `METRIC_MAX_SERIES` label sets exist, new ones are counted under the
+    /// subject `other`. Components and sessions are only labels with
+    /// `HIGH_CARDINALITY_METRICS`; otherwise see `message_stats`.
+    pub fn metrics_text(&self) -> String {
+        self.message_metrics.render()
+    }
+
+    /// Messages and bytes by client-mode component and by connected server session
+    pub fn message_stats(&self) -> MessageStats {
+        self.message_metrics.stats()
+    }
+
+    /// Encode a broker message into an Axum WebSocket message (static version for the server)
+    pub(crate) fn encode_message_to_axum_static(msg: &BrokerMessage) -> Result<AxumMessage> {
+        Ok(AxumMessage::Text(Self::envelope_json(msg).to_string()))
+    }
+
+    /// Encode a request to a server client, carrying the `corr_id` its reply must echo
+    pub(crate) fn encode_request_to_axum(
+        msg: &BrokerMessage,
+        corr_id: &str,
+    ) -> Result<AxumMessage> {
+        let mut json = Self::envelope_json(msg);
+        json["corr_id"] = serde_json::json!(corr_id);
         Ok(AxumMessage::Text(json.to_string()))
     }
 
     /// Set message handler for client mode - receives messages from remote WS server
+    ///
+    /// Also receives the `$SYS.flow.pause` and `$SYS.flow.resume` signals of links with
+    /// `FLOW_SIGNALS`, with the session id of the publishing component's connection.
     pub async fn set_client_message_handler<F>(&self, handler: F)
     where
         F: Fn(String, BrokerMessage) -> Result<()> + Send + Sync + 'static,
     {
-        let mut h = self.client_message_handler.write().await;
-        *h = Some(Arc::new(handler));
+        *self.client_message_handler.write().unwrap() = Some(Arc::new(handler));
         info!("Client message handler registered");
     }
 
+    /// Dispatch a flow signal crossed at `queue_used` frames to the client message handler
+    fn dispatch_flow_signal(
+        handler: &std::sync::RwLock<Option<MessageHandler>>,
+        gauge: &FlowGauge,
+        signal: FlowSignal,
+        session_id: &str,
+        component_id: &str,
+        queue_used: usize,
+    ) {
+        let msg = flow::signal_message(signal, component_id, &gauge.snapshot(queue_used));
+        debug!(
+            "Queue of component {} at {} frames, dispatching {}",
+            component_id, queue_used, msg.subject
+        );
+        let Some(handler) = handler.read().unwrap().clone() else {
+            debug!("No client message handler registered for {}", msg.subject);
+            return;
+        };
+        if let Err(e) = handler(session_id.to_string(), msg) {
+            warn!(
+                "Client message handler failed on flow signal for {}: {}",
+                component_id, e
+            );
+        }
+    }
+
+    /// How much room `component_id` has to publish through its connection, or `None` if
+    /// it isn't linked as a consumer
+    ///
+    /// Lets a component pace a large batch instead of running into a full queue:
+    /// `suggested_delay_ms` is how long the queue should take to drain to its low
+    /// watermark (a quarter of its capacity) at the connection's recent write rate.
+    pub async fn get_flow_control(&self, component_id: &str) -> Option<FlowControl> {
+        let consumers = self.consumer_components.read().await;
+        let bundle = consumers.get(component_id)?;
+        Some(bundle.flow.snapshot(bundle.queue_depth()))
+    }
+
     /// Broadcast message to all handler components (called when message arrives from remote WS server)
     #[allow(dead_code)]
     async fn broadcast_to_handlers(&self, session_id: &str, msg: BrokerMessage) -> Result<()> {
@@ -217,7 +1244,7 @@ impl WebSocketMessagingProvider {
         let mut broadcast_count = 0;
 
         for (component_id, bundle) in handlers.iter() {
-            if let Err(e) = bundle.tx.send(encoded_msg.clone()) {
+            if let Err(e) = bundle.tx.try_send(encoded_msg.clone().into()) {
                 error!(
                     "Failed to broadcast message to component {}: {}",
                     component_id, e
@@ -236,56 +1263,246 @@ impl WebSocketMessagingProvider {
     }
 
     /// Parse incoming message from remote WebSocket server (static version for async tasks)
+    ///
+    /// A body given as a JSON array must hold bytes (0-255); anything else fails with
+    /// `InvalidByteArray`. A subject or reply_to longer than the default
+    /// `MAX_SUBJECT_LEN` or `MAX_REPLY_TO_LEN` is rejected.
     pub fn parse_message_static(text: &str, session_id: &str) -> Result<BrokerMessage> {
-        // Try to parse as JSON first
-        if let Ok(json) = serde_json::from_str::<serde_json::Value>(text) {
+        Self::parse_message_with(
+            text,
+            session_id,
+            ByteArrayPolicy::default(),
+            BodyDecoding::default(),
+            FieldLimits::default(),
+        )
+        .map(|(msg, _)| msg)
+    }
+
+    /// Parse an incoming message and the envelope's `corr_id`, if any, treating
+    /// out-of-range byte-array elements per `policy` and string bodies per `decoding`,
+    /// and rejecting a subject or reply_to over `limits`
+    pub(crate) fn parse_message_with(
+        text: &str,
+        session_id: &str,
+        policy: ByteArrayPolicy,
+        decoding: BodyDecoding,
+        limits: FieldLimits,
+    ) -> Result<(BrokerMessage, Option<String>)> {
+        // Try to parse as JSON first; escapes that aren't valid Unicode are not plain text
+        let parsed = serde_json::from_str::<serde_json::Value>(text);
+        if let Err(e) = &parsed {
+            if parse_guard::is_invalid_unicode_escape(e) {
+                return Err(parse_guard::InvalidUtf8(e.to_string()).into());
+            }
+        }
+        if let Ok(json) = parsed {
             let subject = json
                 .get("subject")
                 .and_then(|v| v.as_str())
-                .unwrap_or("default")
-                .to_string();
+                .unwrap_or("default");
+            let reply_to = json.get("reply_to").and_then(|v| v.as_str());
+            limits.check(subject, reply_to)?;
+            let subject = subject.to_string();
+            subject::check_inbound(&subject)?;
 
+            let mut headers = Self::parse_headers(&json);
             let body = if let Some(body_str) = json.get("body").and_then(|v| v.as_str()) {
-                Bytes::from(body_str.as_bytes().to_vec())
+                body_encoding::decode(&json, body_str, decoding, &mut headers)?
             } else if let Some(body_arr) = json.get("body").and_then(|v| v.as_array()) {
-                let bytes: Vec<u8> = body_arr
-                    .iter()
-                    .filter_map(|v| v.as_u64().map(|n| n as u8))
-                    .collect();
-                Bytes::from(bytes)
+                byte_array::parse_byte_array(body_arr, policy)?
             } else {
                 Bytes::from(text.as_bytes().to_vec())
             };
 
-            let reply_to = json
-                .get("reply_to")
-                .and_then(|v| v.as_str())
+            let reply_to = reply_to
                 .map(|s| s.to_string())
                 .or_else(|| Some(session_id.to_string()));
 
-            Ok(BrokerMessage {
-                subject,
-                body,
-                reply_to,
-            })
+            let corr_id = json
+                .get("corr_id")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string());
+
+            Ok((
+                BrokerMessage {
+                    subject,
+                    body,
+                    reply_to,
+                    headers,
+                },
+                corr_id,
+            ))
         } else {
             // Plain text message
-            Ok(BrokerMessage {
-                subject: "message".to_string(),
-                body: Bytes::from(text.as_bytes().to_vec()),
-                reply_to: Some(session_id.to_string()),
-            })
+            Ok((
+                BrokerMessage {
+                    subject: "message".to_string(),
+                    body: Bytes::from(text.as_bytes().to_vec()),
+                    reply_to: Some(session_id.to_string()),
+                    headers: HashMap::new(),
+                },
+                None,
+            ))
+        }
+    }
+
+    /// Wrap a binary frame that isn't UTF-8 text, keeping its bytes as the body
+    pub(crate) fn binary_message(data: Vec<u8>, session_id: &str) -> BrokerMessage {
+        BrokerMessage {
+            subject: "binary.message".to_string(),
+            body: Bytes::from(data),
+            reply_to: Some(session_id.to_string()),
+            headers: HashMap::new(),
         }
     }
 
     /// Encode message (static version for async tasks)
     pub fn encode_message_static(msg: &BrokerMessage) -> Result<Message> {
-        let json = serde_json::json!({
+        Self::encode_message_versioned(msg, ENVELOPE_V1)
+    }
+
+    /// Encode a message in envelope `version`
+    pub(crate) fn encode_message_versioned(msg: &BrokerMessage, version: u8) -> Result<Message> {
+        let mut json = Self::envelope_json(msg);
+        Self::tag_version(&mut json, version);
+        Ok(Message::Text(json.to_string()))
+    }
+
+    /// Encode several messages as a single batch envelope: `{"batch": [<envelope>, ...]}`
+    pub fn encode_batch_static(msgs: &[BrokerMessage]) -> Result<Message> {
+        Self::encode_batch_versioned(msgs, ENVELOPE_V1)
+    }
+
+    /// Encode a batch envelope in `version`; the batch and each envelope carry the tag
+    pub(crate) fn encode_batch_versioned(msgs: &[BrokerMessage], version: u8) -> Result<Message> {
+        let batch: Vec<serde_json::Value> = msgs
+            .iter()
+            .map(|msg| {
+                let mut json = Self::envelope_json(msg);
+                Self::tag_version(&mut json, version);
+                json
+            })
+            .collect();
+        let mut json = serde_json::json!({ "batch": batch });
+        Self::tag_version(&mut json, version);
+        Ok(Message::Text(json.to_string()))
+    }
+
+    /// Mark an envelope with its version; v1 envelopes stay untagged
+    fn tag_version(json: &mut serde_json::Value, version: u8) {
+        if version != ENVELOPE_V1 {
+            json["v"] = serde_json::json!(version);
+        }
+    }
+
+    /// JSON envelope for a single broker message
+    fn envelope_json(msg: &BrokerMessage) -> serde_json::Value {
+        let mut json = serde_json::json!({
             "subject": msg.subject,
-            "body": base64::encode(&msg.body),
+            "body": STANDARD.encode(&msg.body),
+            BODY_ENCODING_FIELD: BASE64,
             "reply_to": msg.reply_to,
         });
-        Ok(Message::Text(json.to_string()))
+        if !msg.headers.is_empty() {
+            json["headers"] = serde_json::json!(msg.headers);
+        }
+        json
+    }
+
+    /// Rebuild a broker message from its JSON envelope
+    fn message_from_envelope(json: &serde_json::Value) -> Result<BrokerMessage> {
+        let subject = json
+            .get("subject")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow::anyhow!("Envelope has no subject"))?;
+        let body = json.get("body").and_then(|v| v.as_str()).unwrap_or("");
+        Ok(BrokerMessage {
+            subject: subject.to_string(),
+            body: Bytes::from(
+                STANDARD
+                    .decode(body)
+                    .context("Envelope body is not base64")?,
+            ),
+            reply_to: json
+                .get("reply_to")
+                .and_then(|v| v.as_str())
+                .map(String::from),
+            headers: Self::parse_headers(json),
+        })
+    }
+
+    /// Read the optional `headers` object of an envelope, ignoring non-string values
+    pub(crate) fn parse_headers(json: &serde_json::Value) -> HashMap<String, String> {
+        json.get("headers")
+            .and_then(|v| v.as_object())
+            .map(|headers| {
+                headers
+                    .iter()
+                    .filter_map(|(k, v)| v.as_str().map(|v| (k.clone(), v.to_string())))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Open a connection to each URI of `endpoints` in turn until one succeeds, and mark
+    /// it active; after the active URI has failed, the one after it is tried first
+    async fn dial_any(
+        transport: &Arc<dyn WsTransport>,
+        token_provider: Option<&TokenProvider>,
+        config: &ConnectionConfig,
+        endpoints: &Endpoints,
+        after_failure: bool,
+        component_id: &str,
+    ) -> Result<WsConnection> {
+        let mut failures = Vec::new();
+        for (index, uri) in endpoints.candidates(after_failure) {
+            match Self::dial(transport, token_provider, config, uri, component_id).await {
+                Ok(connection) => {
+                    endpoints.set_active(index);
+                    return Ok(connection);
+                }
+                Err(e) if !endpoints.is_list() => return Err(e),
+                Err(e) => {
+                    warn!(
+                        "Failed to connect component {} to {}: {:#}",
+                        component_id, uri, e
+                    );
+                    failures.push(format!("{}: {:#}", uri, e));
+                }
+            }
+        }
+        bail!(
+            "No URI of {} could be connected to ({})",
+            config.uri,
+            failures.join("; ")
+        )
+    }
+
+    /// Open a connection to `uri` through `transport` within `CONNECT_TIMEOUT`
+    async fn dial(
+        transport: &Arc<dyn WsTransport>,
+        token_provider: Option<&TokenProvider>,
+        config: &ConnectionConfig,
+        uri: &str,
+        component_id: &str,
+    ) -> Result<WsConnection> {
+        // A fresh token for this attempt; the stored config keeps the static one, so it
+        // still compares equal for connection sharing and link updates
+        let mut dial_config = config.clone();
+        dial_config.uri = uri.to_string();
+        if let Some(token_provider) = token_provider {
+            let token = token_provider()
+                .await
+                .with_context(|| format!("Failed to fetch auth token for {}", component_id))?;
+            dial_config.auth_token = Some(token);
+        }
+
+        let connection =
+            tokio::time::timeout(config.connect_timeout, transport.connect(&dial_config))
+                .await
+                .context("Connection timeout")??;
+        transport::selected_subprotocol(config, &connection.response_headers)?;
+        Ok(connection)
     }
 
     /// Connect to a WebSocket server
@@ -295,177 +1512,608 @@ impl WebSocketMessagingProvider {
         config: ConnectionConfig,
         component_id: &str,
     ) -> Result<WebSocketClientBundle> {
-        let url = Url::parse(&config.uri)
-            .with_context(|| format!("Invalid WebSocket URI: {}", config.uri))?;
+        let endpoints = Arc::new(Endpoints::from_config(&config));
+        for uri in endpoints.uris() {
+            Url::parse(uri).with_context(|| format!("Invalid WebSocket URI: {}", uri))?;
+        }
+        let redacted_config = config.redacted();
+        let connect_config = config.clone();
+        let capture = FrameCapture::open(&config)?;
 
-        info!("Connecting to WebSocket at {}", url);
+        let connection_id = telemetry::next_connection_id();
+        if endpoints.is_list() {
+            info!(
+                connection_id = %connection_id,
+                "Connecting to WebSocket at one of {} ({} failover)",
+                config.uri,
+                config.failover_strategy.as_str()
+            );
+        } else {
+            info!(connection_id = %connection_id, "Connecting to WebSocket at {}", endpoints.active());
+        }
 
-        // Create WebSocket connection with timeout
-        let ws_stream = tokio::time::timeout(
-            Duration::from_secs(config.connect_timeout_sec),
-            connect_async(url.clone()),
+        let WsConnection {
+            sink,
+            stream,
+            response_headers,
+        } = Self::dial_any(
+            &self.transport,
+            self.token_provider.as_ref(),
+            &config,
+            &endpoints,
+            false,
+            component_id,
         )
-        .await
-        .context("Connection timeout")?
-        .context("Failed to connect to WebSocket")?
-        .0;
+        .await?;
+        let connected_uri = endpoints.active().to_string();
 
-        info!("WebSocket connected successfully");
+        info!(connection_id = %connection_id, "WebSocket connected successfully to {}", connected_uri);
 
         // Create channel for sending messages
-        let (tx, mut rx) = mpsc::unbounded_channel::<Message>();
+        let queue_capacity = config.outbound_queue_capacity.max(1);
+        let (tx, rx) = mpsc::channel::<QueuedFrame>(queue_capacity);
+        let (flush_tx, flush_rx) = mpsc::unbounded_channel::<oneshot::Sender<()>>();
+
+        // Use the server's own session id when it sends one in the upgrade response
+        let server_session = if config.server_session_header.is_empty() {
+            None
+        } else {
+            response_headers
+                .get(&config.server_session_header.to_lowercase())
+                .cloned()
+        };
+        let awaiting_server_session =
+            server_session.is_none() && !config.server_session_field.is_empty();
 
         // Create session info
-        let session_id = uuid::Uuid::new_v4().to_string();
+        let session_id = server_session.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let mut metadata = HashMap::from([
+            ("connection_id".to_string(), connection_id.clone()),
+            ("connected_uri".to_string(), connected_uri.clone()),
+        ]);
+        // Checked against `SUBPROTOCOLS` when dialling
+        if let Some(subprotocol) = transport::selected_subprotocol(&config, &response_headers)? {
+            metadata.insert("subprotocol".to_string(), subprotocol);
+        }
+        if let Some(compression) = transport::negotiated_compression(&config, &response_headers) {
+            metadata.insert("compression".to_string(), compression.to_string());
+        }
         let session_info = SessionInfo {
             session_id: session_id.clone(),
             connected_at: std::time::SystemTime::now(),
-            metadata: HashMap::new(),
+            metadata,
         };
 
-        // Store session mapping if tracking is enabled
-        if config.enable_session_tracking {
-            let mut sessions = self.session_storage.write().await;
-            sessions.insert(session_id.clone(), component_id.to_string());
-            debug!(
-                "Session {} registered for component {}",
-                session_id, component_id
-            );
-        }
+        // Store session mapping if tracking is enabled; the task removes it when it ends
+        let registration = if config.enable_session_tracking {
+            Some(SessionRegistration::register(&self.sessions, &session_id, component_id).await)
+        } else {
+            None
+        };
 
-        // Split WebSocket stream
-        let (mut ws_tx, mut ws_rx) = ws_stream.split();
+        // Long-lived span for the whole connection
+        let session_span = telemetry::session_span(
+            &connection_id,
+            &session_id,
+            "client",
+            &connected_uri,
+            Some(component_id),
+            config.label(),
+        );
+
+        let health = Arc::new(LinkHealth::default());
+        let envelope = Arc::new(EnvelopeVersion::new(config.envelope_upgrade_grace));
+        // Measures writes and signals the resume once the queue drains
+        let flow = Arc::new(FlowGauge::new(queue_capacity, config.flow_signals));
+        let client_message_handler = Arc::clone(&self.client_message_handler);
+        // Pings at `HEARTBEAT_INTERVAL`, proposed to the server in a hello, until the
+        // server announces an interval of its own
+        if !config.heartbeat_interval.is_zero() {
+            let _ = tx.try_send(Message::Text(heartbeat::hello(config.heartbeat_interval)).into());
+        }
 
         // Spawn task to handle bidirectional communication
-        let component_id = component_id.to_string();
-        let session_storage = Arc::clone(&self.session_storage);
-        let handler_components = Arc::clone(&self.handler_components);
-        let session_id_for_handler = session_id.clone();
+        let task_detail = format!("component={} session={}", component_id, session_id);
+        let task = ClientTask {
+            component_id: component_id.to_string(),
+            session_id: session_id.clone(),
+            connection_id: connection_id.clone(),
+            // Frames still queued when shutdown tears the connection down are spilled
+            rx: SpillingReceiver::new(rx, &self.spill, component_id, &session_id),
+            flush_rx,
+            queue_capacity,
+            writer: FrameWriter::new(sink, &config, capture),
+            stream,
+            signer: MessageSigner::from_config(&config),
+            codec: config.codec,
+            counters: SessionCounters::start(
+                config.label().map(|label| self.metrics.counters(label)),
+            ),
+            health: Arc::clone(&health),
+            dispatcher: self.tasks.dispatcher(format!("component:{}", component_id)),
+            reconnect_buffer: ReconnectBuffer::new(
+                &config,
+                Arc::clone(&self.stale_reconnect_frames),
+            ),
+            deadlines: self.deadlines.clone(),
+            flow: Arc::clone(&flow),
+            flow_tx: tx.downgrade(),
+            message_handler: Arc::clone(&client_message_handler),
+            heartbeat: ClientHeartbeat::from_config(&config),
+            envelope: Arc::clone(&envelope),
+            awaiting_server_session,
+            registration,
+            pipeline: Pipeline::new(&config, &self.inbound_stages),
+            inbound_filter: acl::InboundFilter::new(&config, Arc::clone(&self.inbound_denied)),
+            byte_array_policy: config.byte_array_policy,
+            body_decoding: BodyDecoding::from_config(&config),
+            field_limits: FieldLimits::from_config(&config),
+            legacy_hex: self.legacy_hex.clone(),
+            pending_requests: Arc::clone(&self.pending_requests),
+            body_streams: self.body_streams.clone(),
+            stream_tx: tx.downgrade(),
+            metrics: self.metrics.clone(),
+            message_metrics: self.message_metrics.clone(),
+            batcher: MessageBatcher::new(config.handler_batch_size, config.handler_batch_window),
+            handler_components: Arc::clone(&self.handler_components),
+            consumer_components: Arc::clone(&self.consumer_components),
+            route_delivery: self.default_config.route_delivery,
+            handler_overflow: self.default_config.handler_overflow,
+            handler_dispatch: self.handler_dispatch.clone(),
+            drop_notices: config
+                .drop_notifications
+                .then(|| (DropNotifier::new(), tx.downgrade())),
+            spill: self.spill.clone(),
+            reconnect: ReconnectPolicy::from_config(&config),
+            transport: Arc::clone(&self.transport),
+            token_provider: self.token_provider.clone(),
+            endpoints: Arc::clone(&endpoints),
+            failed_links: self.failed_links.clone(),
+            closing: false,
+            config,
+        };
+        let handle = self.tasks.spawn(
+            "client-connection",
+            &task_detail,
+            task.run(session_span.clone())
+                .instrument(session_span.clone()),
+        );
 
-        let handle = tokio::spawn(async move {
-            loop {
-                tokio::select! {
-                    // Handle outgoing messages
-                    Some(msg) = rx.recv() => {
-                        if let Err(e) = ws_tx.send(msg).await {
-                            error!("Failed to send WebSocket message: {}", e);
-                            break;
+        Ok(WebSocketClientBundle {
+            tx,
+            session_info,
+            handle,
+            config: ArcSwap::from_pointee(redacted_config),
+            connect_config: ArcSwap::from_pointee(connect_config),
+            span: session_span,
+            dropped: AtomicU64::new(0),
+            flush_tx,
+            compression: AdaptiveCompression::default(),
+            health,
+            envelope,
+            component_id: component_id.to_string(),
+            message_metrics: self.message_metrics.clone(),
+            flow,
+            client_message_handler,
+            endpoints,
+            connection_id,
+        })
+    }
+
+    /// Hand a message to the request it answers, or give it back if it answers none
+    ///
+    /// A reply has to arrive on the connection the request went out on, echo its
+    /// `corr_id` and use its reply subject; anything else on an inbox subject is an
+    /// ordinary message and the request keeps waiting. Replies skip the inbound subject
+    /// rules, which are written for the subjects handlers subscribe to rather than for
+    /// generated `_INBOX` subjects.
+    fn complete_request(
+        pending: &PendingRequests,
+        connection_id: &str,
+        msg: BrokerMessage,
+        corr_id: Option<&str>,
+    ) -> Option<BrokerMessage> {
+        let Some(corr_id) = corr_id else {
+            return Some(msg);
+        };
+        let key = (connection_id.to_string(), corr_id.to_string());
+        let mut pending = pending.lock().unwrap();
+        match pending.get(&key) {
+            Some(waiting) if waiting.reply_to == msg.subject => {}
+            Some(_) => {
+                debug!(
+                    "Message with the corr_id of a pending request arrived on {}, not its reply subject",
+                    msg.subject
+                );
+                return Some(msg);
+            }
+            None => return Some(msg),
+        }
+        if let Some(waiting) = pending.remove(&key) {
+            let _ = waiting.reply_tx.send(msg);
+        }
+        None
+    }
+
+    /// Count and log an inbound frame from the remote server that won't be dispatched
+    fn reject_frame(metrics: &LabelMetrics, health: &LinkHealth, error: &anyhow::Error) {
+        let reason = ParseFailureReason::of(error);
+        metrics.record_parse_failure(reason);
+        health.record_error(format!(
+            "Rejected inbound frame ({}): {}",
+            reason.label(),
+            error
+        ));
+        warn!(
+            "Dropping message from remote server ({}): {}",
+            reason.label(),
+            error
+        );
+    }
+
+    /// Session id announced by the server in `field` of a message, and whether the
+    /// message is a pure welcome (no `subject`) that should not be forwarded
+    fn server_session_id(text: &str, field: &str) -> Option<(String, bool)> {
+        let json: serde_json::Value = serde_json::from_str(text).ok()?;
+        let server_id = json.get(field)?.as_str()?.to_string();
+        Some((server_id, json.get("subject").is_none()))
+    }
+
+    /// Forward messages received from the remote server to the handler components
+    ///
+    /// Each message is routed to the handlers whose subscriptions match its subject,
+    /// highest priority first, and to all of them or only the first depending on
+    /// `delivery`. A handler receiving a single message gets a plain envelope, several
+    /// a batch envelope. A handler with a full queue is handled per `overflow` without
+    /// affecting delivery to the others.
+    ///
+    /// Returns the dropped messages that carried a `msg-id` header.
+    async fn forward_to_handlers(
+        handler_components: &RwLock<HashMap<String, Arc<WebSocketClientBundle>>>,
+        delivery: RouteDelivery,
+        overflow: HandlerOverflow,
+        dispatch: &HandlerDispatch,
+        batch: Vec<BrokerMessage>,
+    ) -> Vec<DroppedMessage> {
+        let span = debug_span!(
+            "message",
+            direction = "inbound",
+            subject = %batch[0].subject,
+            batch_size = batch.len(),
+            bytes = batch.iter().map(|m| m.body.len()).sum::<usize>(),
+        );
+        // Routed against a snapshot taken per batch, so a handler linked before the batch
+        // is flushed receives it, and the lock isn't held while `Wait` sends block (which
+        // would stall linking and unlinking behind a full queue)
+        let handlers = handler_components.read().await.clone();
+        // Loaded up front so the routes can borrow the subscriptions
+        let configs: Vec<_> = handlers
+            .iter()
+            .map(|(comp_id, bundle)| (comp_id, bundle.config.load_full()))
+            .collect();
+        let deliveries = span.in_scope(|| {
+            let mut routes: Vec<Route<'_>> = configs
+                .iter()
+                .map(|(comp_id, config)| Route {
+                    component_id: comp_id,
+                    priority: config.route_priority,
+                    subscriptions: &config.subscriptions,
+                })
+                .collect();
+            routing::sort_routes(&mut routes);
+
+            let mut per_route: Vec<Vec<&BrokerMessage>> = vec![Vec::new(); routes.len()];
+            for msg in &batch {
+                let targets = routing::select(&routes, &msg.subject, delivery);
+                if targets.is_empty() {
+                    debug!("No handler component subscribed to {}", msg.subject);
+                }
+                for idx in targets {
+                    per_route[idx].push(msg);
+                }
+            }
+
+            let mut deliveries = Vec::new();
+            for (route, msgs) in routes.iter().zip(per_route) {
+                if msgs.is_empty() {
+                    continue;
+                }
+                let version = handlers[route.component_id].envelope.current();
+                let encoded = if msgs.len() == 1 {
+                    Self::encode_message_versioned(msgs[0], version)
+                } else {
+                    Self::encode_batch_versioned(
+                        &msgs.iter().map(|m| (*m).clone()).collect::<Vec<_>>(),
+                        version,
+                    )
+                };
+                match encoded {
+                    Ok(msg) => deliveries.push((
+                        &handlers[route.component_id],
+                        route.component_id,
+                        msg,
+                        msgs,
+                    )),
+                    Err(e) => error!("Failed to encode message for handler components: {}", e),
+                }
+            }
+            deliveries
+        });
+
+        match overflow {
+            HandlerOverflow::Drop => span.in_scope(|| {
+                let mut dropped_msgs = Vec::new();
+                for (bundle, comp_id, msg, msgs) in deliveries {
+                    match bundle.tx.try_send(msg.into()) {
+                        Ok(()) => debug!("Forwarded message to component {}", comp_id),
+                        Err(mpsc::error::TrySendError::Full(_)) => {
+                            let dropped = bundle.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+                            debug!(
+                                "Queue of component {} is full, dropped message ({} so far)",
+                                comp_id, dropped
+                            );
+                            dropped_msgs.extend(msgs.into_iter().filter_map(|m| {
+                                Some(DroppedMessage {
+                                    msg_id: m.headers.get(drop_notify::MSG_ID_HEADER)?.clone(),
+                                    subject: m.subject.clone(),
+                                    component_id: comp_id.to_string(),
+                                    reason: DropReason::HandlerOverflow,
+                                })
+                            }));
+                        }
+                        Err(e) => {
+                            error!("Failed to forward message to component {}: {}", comp_id, e)
                         }
                     }
-                    // Handle incoming messages from remote WebSocket server
-                    Some(msg_result) = ws_rx.next() => {
-                        match msg_result {
-                            Ok(Message::Text(text)) => {
-                                debug!("Received text message from remote server: {}", text);
-
-                                // Parse the message
-                                if let Ok(broker_msg) = Self::parse_message_static(&text, &session_id_for_handler) {
-                                    // Broadcast to all handler components
-                                    let handlers = handler_components.read().await;
-                                    for (comp_id, bundle) in handlers.iter() {
-                                        let encoded = Self::encode_message_static(&broker_msg);
-                                        if let Ok(msg) = encoded {
-                                            if let Err(e) = bundle.tx.send(msg) {
-                                                error!("Failed to forward message to component {}: {}", comp_id, e);
-                                            } else {
-                                                debug!("Forwarded message to component {}", comp_id);
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                            Ok(Message::Binary(data)) => {
-                                debug!("Received binary message from remote server: {} bytes", data.len());
-
-                                // Try to convert to text and parse
-                                if let Ok(text) = String::from_utf8(data.clone()) {
-                                    if let Ok(broker_msg) = Self::parse_message_static(&text, &session_id_for_handler) {
-                                        let handlers = handler_components.read().await;
-                                        for (comp_id, bundle) in handlers.iter() {
-                                            let encoded = Self::encode_message_static(&broker_msg);
-                                            if let Ok(msg) = encoded {
-                                                if let Err(e) = bundle.tx.send(msg) {
-                                                    error!("Failed to forward message to component {}: {}", comp_id, e);
-                                                } else {
-                                                    debug!("Forwarded message to component {}", comp_id);
-                                                }
-                                            }
-                                        }
-                                    }
-                                } else {
-                                    // Handle as raw binary
-                                    let broker_msg = BrokerMessage {
-                                        subject: "binary.message".to_string(),
-                                        body: Bytes::from(data),
-                                        reply_to: Some(session_id_for_handler.clone()),
-                                    };
-
-                                    let handlers = handler_components.read().await;
-                                    for (comp_id, bundle) in handlers.iter() {
-                                        let encoded = Self::encode_message_static(&broker_msg);
-                                        if let Ok(msg) = encoded {
-                                            if let Err(e) = bundle.tx.send(msg) {
-                                                error!("Failed to forward message to component {}: {}", comp_id, e);
-                                            } else {
-                                                debug!("Forwarded binary message to component {}", comp_id);
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                            Ok(Message::Close(_)) => {
-                                info!("WebSocket connection closed");
-                                break;
-                            }
-                            Ok(Message::Ping(data)) => {
-                                if let Err(e) = ws_tx.send(Message::Pong(data)).await {
-                                    error!("Failed to send pong: {}", e);
-                                    break;
-                                }
-                            }
-                            Ok(_) => {}
+                }
+                dropped_msgs
+            }),
+            // Handed to the per-handler workers, so only a full backlog makes this wait
+            HandlerOverflow::Wait if dispatch.is_enabled() => {
+                for (bundle, comp_id, msg, _) in deliveries {
+                    dispatch
+                        .enqueue(comp_id, bundle.tx.clone(), msg)
+                        .instrument(span.clone())
+                        .await;
+                }
+                Vec::new()
+            }
+            HandlerOverflow::Wait => {
+                let sends = deliveries
+                    .into_iter()
+                    .map(|(bundle, comp_id, msg, _)| async move {
+                        match bundle.tx.send(msg.into()).await {
+                            Ok(()) => debug!("Forwarded message to component {}", comp_id),
                             Err(e) => {
-                                error!("WebSocket error: {}", e);
-                                break;
+                                error!("Failed to forward message to component {}: {}", comp_id, e)
                             }
                         }
+                    });
+                futures::future::join_all(sends).instrument(span).await;
+                Vec::new()
+            }
+        }
+    }
+
+    /// Turn drops into notifications and queue them on the connection the messages
+    /// arrived on, if that link enabled `DROP_NOTIFICATIONS`
+    fn notify_drops(
+        notices: &mut Option<(DropNotifier, mpsc::WeakSender<QueuedFrame>)>,
+        dropped: Vec<DroppedMessage>,
+        version: u8,
+    ) {
+        let Some((notifier, tx)) = notices else {
+            return;
+        };
+        let now = tokio::time::Instant::now();
+        let pending: Vec<BrokerMessage> = dropped
+            .into_iter()
+            .flat_map(|d| notifier.record(d, now))
+            .collect();
+        Self::send_drop_notices(tx, pending, version);
+    }
+
+    /// When the summary of rate-limited drop notifications is due
+    fn drop_summary_deadline(
+        notices: &Option<(DropNotifier, mpsc::WeakSender<QueuedFrame>)>,
+    ) -> Option<tokio::time::Instant> {
+        notices
+            .as_ref()
+            .and_then(|(notifier, _)| notifier.deadline())
+    }
+
+    /// Send the summary of drops that were not notified individually
+    fn flush_drop_summary(
+        notices: &mut Option<(DropNotifier, mpsc::WeakSender<QueuedFrame>)>,
+        version: u8,
+    ) {
+        if let Some((notifier, tx)) = notices {
+            let summary = notifier.take_summary();
+            Self::send_drop_notices(tx, summary, version);
+        }
+    }
+
+    fn send_drop_notices(
+        tx: &mpsc::WeakSender<QueuedFrame>,
+        notices: Vec<BrokerMessage>,
+        version: u8,
+    ) {
+        if notices.is_empty() {
+            return;
+        }
+        // The connection is going away if nothing else holds its sender
+        let Some(tx) = tx.upgrade() else {
+            return;
+        };
+        for notice in notices {
+            match Self::encode_message_versioned(&notice, version) {
+                Ok(msg) => {
+                    if tx.try_send(msg.into()).is_err() {
+                        debug!("Outbound queue full, drop notification not sent");
                     }
-                    else => break,
                 }
+                Err(e) => error!("Failed to encode drop notification: {}", e),
             }
+        }
+    }
 
-            // Cleanup session on disconnect
-            let mut sessions = session_storage.write().await;
-            sessions.retain(|_, cid| cid != &component_id);
-            info!(
-                "WebSocket connection handler terminated for component {}",
+    /// Wait until everything queued on a component's connections has been written to the
+    /// socket and flushed, e.g. before unlinking it
+    ///
+    /// Messages queued after the call may or may not be included. Fails with
+    /// `FlushTimeout` if the queue isn't written within `timeout`, or with an error if the
+    /// component isn't linked or its connection ends first.
+    pub async fn flush_component(&self, component_id: &str, timeout: Duration) -> Result<()> {
+        self.ensure_running()?;
+        let mut bundles: Vec<Arc<WebSocketClientBundle>> = Vec::new();
+        for map in [&self.consumer_components, &self.handler_components] {
+            if let Some(bundle) = map.read().await.get(component_id) {
+                // A component linked in both roles may share one connection
+                if !bundles.iter().any(|b| Arc::ptr_eq(b, bundle)) {
+                    bundles.push(Arc::clone(bundle));
+                }
+            }
+        }
+        if bundles.is_empty() {
+            bail!("Component not linked: {}", component_id);
+        }
+
+        let mut confirmations = Vec::with_capacity(bundles.len());
+        for bundle in &bundles {
+            let (flushed, confirmation) = oneshot::channel();
+            bundle
+                .flush_tx
+                .send(flushed)
+                .map_err(|_| anyhow!("Connection of component {} has ended", component_id))?;
+            confirmations.push(confirmation);
+        }
+        let confirmed = tokio::time::timeout(timeout, futures::future::join_all(confirmations))
+            .await
+            .map_err(|_| FlushTimeout {
+                component_id: component_id.to_string(),
+                timeout,
+            })?;
+        if confirmed.iter().any(Result::is_err) {
+            bail!(
+                "Connection of component {} ended before its queue was flushed",
                 component_id
             );
-        });
+        }
+        Ok(())
+    }
 
-        Ok(WebSocketClientBundle {
-            tx,
-            session_info,
-            handle,
+    /// Number of inbound messages dropped for a handler component because its queue
+    /// was full
+    pub async fn handler_dropped_count(&self, component_id: &str) -> Option<u64> {
+        self.handler_components
+            .read()
+            .await
+            .get(component_id)
+            .map(|bundle| bundle.dropped_messages())
+    }
+
+    /// Statistics of a component's client-mode connection, consumer link first
+    pub async fn client_stats(&self, component_id: &str) -> Option<ClientStats> {
+        let bundle = self.client_bundle(component_id).await?;
+        let body_compression = bundle.config.load().body_compression;
+        let (adaptive_compressing, compression_ratio) = bundle.compression.decision();
+        Some(ClientStats {
+            dropped_messages: bundle.dropped_messages(),
+            body_compression,
+            compressing: match body_compression {
+                BodyCompression::None => false,
+                BodyCompression::Zstd | BodyCompression::Gzip => true,
+                BodyCompression::Adaptive => adaptive_compressing,
+            },
+            compression_ratio,
         })
     }
 
+    /// Session info of a component's client-mode connection, consumer link first
+    ///
+    /// `metadata` holds the `connection_id`; the `connected_uri`, which changes as the
+    /// connection fails over between the URIs of a `URI` list; if the server selected one
+    /// of `SUBPROTOCOLS`, the `subprotocol`; and with `PERMESSAGE_DEFLATE` set, the
+    /// `compression` the server agreed to (`none` if it declined).
+    pub async fn client_session_info(&self, component_id: &str) -> Option<SessionInfo> {
+        Some(
+            self.client_bundle(component_id)
+                .await?
+                .current_session_info(),
+        )
+    }
+
+    /// Connection of a component, looked up among consumers first, then handlers
+    async fn client_bundle(&self, component_id: &str) -> Option<Arc<WebSocketClientBundle>> {
+        match self.consumer_components.read().await.get(component_id) {
+            Some(bundle) => Some(Arc::clone(bundle)),
+            None => self
+                .handler_components
+                .read()
+                .await
+                .get(component_id)
+                .cloned(),
+        }
+    }
+
+    /// Ask the remote server to move a component's connection to envelope `version`
+    ///
+    /// Sends `{"op":"upgrade","versions":[version]}`. The connection switches once the
+    /// server answers `upgrade_ack` and stays on its current version if it answers
+    /// `upgrade_refused`. Either side may start an upgrade; see `envelope_version`.
+    pub async fn upgrade_envelope(&self, component_id: &str, version: u8) -> Result<()> {
+        self.ensure_running()?;
+        if !SUPPORTED_ENVELOPE_VERSIONS.contains(&version) {
+            bail!("Unsupported envelope version {}", version);
+        }
+        let bundle = self
+            .client_bundle(component_id)
+            .await
+            .ok_or_else(|| anyhow!("Component not linked: {}", component_id))?;
+        bundle.envelope.request(version);
+        let request = UpgradeOp::Request(vec![version]).to_json();
+        bundle.send(Message::Text(request), UPGRADE_OP)
+    }
+
+    /// Envelope version a component's connection currently encodes frames with
+    pub async fn envelope_version(&self, component_id: &str) -> Option<u8> {
+        let bundle = self.client_bundle(component_id).await?;
+        Some(bundle.envelope.current())
+    }
+
     /// Get a session by session ID
     pub async fn get_session(&self, session_id: &str) -> Option<String> {
-        let sessions = self.session_storage.read().await;
-        sessions.get(session_id).cloned()
+        self.sessions.get(session_id).await
     }
 
     /// List all active sessions (both component sessions and WS client sessions)
+    ///
+    /// A component session whose `URI` lists several URIs is labelled with the one it is
+    /// connected to, as `component:<id>@<uri>`.
     pub async fn list_sessions(&self) -> Vec<(String, String)> {
         let mut sessions = Vec::new();
 
-        // Get component sessions (client mode)
-        let component_sessions = self.session_storage.read().await;
-        for (sid, cid) in component_sessions.iter() {
-            sessions.push((sid.clone(), format!("component:{}", cid)));
+        // Get component sessions (client mode), tagged with their role when the component
+        // has separate consumer and handler connections
+        let consumers = self.consumer_components.read().await;
+        let handlers = self.handler_components.read().await;
+        for (sid, cid) in self.sessions.list().await {
+            let role = Self::session_role(&consumers, &handlers, &cid, &sid);
+            let mut label = match role {
+                Some(role) => format!("component:{}/{}", cid, role),
+                None => format!("component:{}", cid),
+            };
+            let bundle = match role {
+                Some("handler") => handlers.get(&cid),
+                _ => consumers.get(&cid).or_else(|| handlers.get(&cid)),
+            };
+            if let Some(bundle) = bundle.filter(|bundle| bundle.endpoints.is_list()) {
+                label = format!("{}@{}", label, bundle.endpoints.active());
+            }
+            sessions.push((sid, label));
         }
-        drop(component_sessions);
+        drop(handlers);
+        drop(consumers);
 
         // Get WS client sessions (server mode)
         if let Some(ref server_state) = self.server_state {
@@ -480,54 +2128,128 @@ impl WebSocketMessagingProvider {
     /// Send a message through a specific session
     /// Works for both client mode (component sessions) and server mode (WS client sessions)
     pub async fn send_to_session(&self, session_id: &str, message: BrokerMessage) -> Result<()> {
+        self.ensure_running()?;
+        self.deadlines.check(&message)?;
+
         // First, try to find in component sessions (client mode)
         if let Some(component_id) = self.get_session(session_id).await {
-            // Try to find the component in either consumer or handler maps
+            // Prefer the connection carrying this session when the component has one per
+            // role, otherwise take its consumer or handler connection
             let consumers = self.consumer_components.read().await;
-            if let Some(bundle) = consumers.get(&component_id) {
-                let msg = self.encode_message(&message)?;
-                bundle.tx.send(msg).context("Failed to send message")?;
-                return Ok(());
-            }
-            drop(consumers);
-
             let handlers = self.handler_components.read().await;
-            if let Some(bundle) = handlers.get(&component_id) {
-                let msg = self.encode_message(&message)?;
-                bundle.tx.send(msg).context("Failed to send message")?;
+            let bundles = [consumers.get(&component_id), handlers.get(&component_id)];
+            let bundle = bundles
+                .iter()
+                .flatten()
+                .find(|bundle| bundle.session_info.session_id == session_id)
+                .or_else(|| bundles.iter().flatten().next());
+            if let Some(bundle) = bundle {
+                let msg = Self::encode_checked(&message, bundle)?;
+                bundle.publish(msg, &message)?;
                 return Ok(());
             }
-            drop(handlers);
         }
 
         // If not found in component sessions, try server mode (WS clients)
         if let Some(ref server_state) = self.server_state {
-            let msg = self.encode_message_to_axum(&message)?;
+            let config = &self.default_config;
             server_state
-                .send_to_client(session_id, msg)
+                .send_to_client_with(session_id, || {
+                    Self::encode_axum_checked(&message, config, &server_state.compression)
+                })
                 .await
                 .context("Failed to send to WebSocket client")?;
+            self.record_server_send(&message, Some(session_id));
             return Ok(());
         }
 
         bail!("Session not found: {}", session_id)
     }
 
+    /// Role of a component session, if the component has separate consumer and handler
+    /// connections and the session belongs to one of them
+    fn session_role(
+        consumers: &HashMap<String, Arc<WebSocketClientBundle>>,
+        handlers: &HashMap<String, Arc<WebSocketClientBundle>>,
+        component_id: &str,
+        session_id: &str,
+    ) -> Option<&'static str> {
+        let (consumer, handler) = (consumers.get(component_id)?, handlers.get(component_id)?);
+        if Arc::ptr_eq(consumer, handler) {
+            return None;
+        }
+        if consumer.session_info.session_id == session_id {
+            Some("consumer")
+        } else if handler.session_info.session_id == session_id {
+            Some("handler")
+        } else {
+            None
+        }
+    }
+
+    /// Encode a message for a connection, compressing its body if `BODY_COMPRESSION` asks
+    /// for it and rejecting it with `MessageTooLarge` if it does not fit in
+    /// `MAX_MESSAGE_BYTES`, or with `SubjectNotPermitted` if `OUTBOUND_ALLOW` /
+    /// `OUTBOUND_DENY` rule out its subject
+    ///
+    /// The (possibly compressed) body is checked before encoding (the frame is never
+    /// smaller), so oversized bodies are rejected without being serialized.
+    fn encode_checked(msg: &BrokerMessage, bundle: &WebSocketClientBundle) -> Result<Message> {
+        Self::encode_request_checked(msg, bundle, None)
+    }
+
+    /// `encode_checked`, carrying the `corr_id` the reply to a request has to echo
+    fn encode_request_checked(
+        msg: &BrokerMessage,
+        bundle: &WebSocketClientBundle,
+        corr_id: Option<&str>,
+    ) -> Result<Message> {
+        let config = bundle.config.load();
+        acl::check_outbound(&config, &msg.subject)?;
+        let limit = config.max_message_bytes;
+        let msg = compression::compress(msg, &config, &bundle.compression);
+        check_message_size(msg.body.len(), limit)?;
+        let mut json = Self::envelope_json(&msg);
+        Self::tag_version(&mut json, bundle.envelope.current());
+        if let Some(corr_id) = corr_id {
+            json["corr_id"] = serde_json::json!(corr_id);
+        }
+        let frame = Message::Text(json.to_string());
+        check_message_size(frame.len(), limit)?;
+        Ok(frame)
+    }
+
+    /// Server-mode counterpart of `encode_checked`
+    pub(crate) fn encode_axum_checked(
+        msg: &BrokerMessage,
+        config: &ConnectionConfig,
+        adaptive: &AdaptiveCompression,
+    ) -> Result<AxumMessage> {
+        acl::check_outbound(config, &msg.subject)?;
+        let limit = config.max_message_bytes;
+        let msg = compression::compress(msg, config, adaptive);
+        check_message_size(msg.body.len(), limit)?;
+        let frame = Self::encode_message_to_axum_static(&msg)?;
+        if let AxumMessage::Text(text) = &frame {
+            check_message_size(text.len(), limit)?;
+        }
+        Ok(frame)
+    }
+
     /// Encode a broker message into a WebSocket message
     fn encode_message(&self, msg: &BrokerMessage) -> Result<Message> {
         // Simple JSON encoding for demonstration
         // In production, you might want to use a more efficient binary format
-        let json = serde_json::json!({
-            "subject": msg.subject,
-            "body": base64::encode(&msg.body),
-            "reply_to": msg.reply_to,
-        });
-        Ok(Message::Text(json.to_string()))
+        Ok(Message::Text(Self::envelope_json(msg).to_string()))
     }
 
     /// Publish a message for a specific component
+    ///
+    /// Fails with `DeadlineExceeded` if the message's `deadline_unix_ms` has passed; one
+    /// that passes while the message is queued drops it before it is written.
     #[instrument(skip(self, msg))]
     pub async fn publish(&self, component_id: &str, msg: BrokerMessage) -> Result<()> {
+        self.ensure_running()?;
         debug!(
             "Publishing message to component {}: subject={}",
             component_id, msg.subject
@@ -537,25 +2259,181 @@ impl WebSocketMessagingProvider {
         let bundle = consumers
             .get(component_id)
             .ok_or_else(|| anyhow!("Component not linked: {}", component_id))?;
+        self.check_publish(component_id, bundle, &msg.subject)?;
+        self.deadlines.check(&msg)?;
 
-        let ws_msg = self.encode_message(&msg)?;
+        let ws_msg = Self::encode_checked(&msg, bundle)?;
+        bundle.publish(ws_msg, &msg)?;
+
+        Ok(())
+    }
+
+    /// Publish a message and wait until it has been written to the socket and flushed
+    ///
+    /// Meant for the few control messages that must be on the wire before the caller
+    /// moves on, e.g. a shutdown notice. Frames queued before `msg` are written first.
+    /// Fails with `FlushTimeout` if the write isn't confirmed within
+    /// `PUBLISH_SYNC_TIMEOUT`, or with an error if the connection ends first; the
+    /// message may still be written after a timeout.
+    pub async fn publish_sync(&self, component_id: &str, msg: BrokerMessage) -> Result<()> {
+        self.ensure_running()?;
+        let bundle = self
+            .consumer_components
+            .read()
+            .await
+            .get(component_id)
+            .cloned()
+            .ok_or_else(|| anyhow!("Component not linked: {}", component_id))?;
+        self.check_publish(component_id, &bundle, &msg.subject)?;
+        self.deadlines.check(&msg)?;
+
+        let ws_msg = Self::encode_checked(&msg, &bundle)?;
+        bundle.publish(ws_msg, &msg)?;
+        let (flushed, confirmation) = oneshot::channel();
+        bundle
+            .flush_tx
+            .send(flushed)
+            .map_err(|_| anyhow!("Connection of component {} has ended", component_id))?;
+        tokio::time::timeout(PUBLISH_SYNC_TIMEOUT, confirmation)
+            .await
+            .map_err(|_| FlushTimeout {
+                component_id: component_id.to_string(),
+                timeout: PUBLISH_SYNC_TIMEOUT,
+            })?
+            .map_err(|_| {
+                anyhow!(
+                    "Connection of component {} ended before {} was written",
+                    component_id,
+                    msg.subject
+                )
+            })
+    }
+
+    /// Publish a body too large for one envelope as a stream of binary frames
+    ///
+    /// A descriptor envelope goes first, carrying `headers` plus `x-body-stream-id` and an
+    /// empty body (set `x-body-length` in `headers` to declare the length); the receiver
+    /// resolves the chunks with `subscribe_body_stream`. Chunks are split into frames of at
+    /// most 64 KiB and queued as the connection drains, so a slow receiver slows `body`
+    /// down. Fails with `BodyStreamAborted` if the receiver cancels or the connection is
+    /// lost before the end; dropping the future cancels the stream at the receiver.
+    #[instrument(skip(self, body, headers))]
+    pub async fn publish_stream<S>(
+        &self,
+        component_id: &str,
+        subject: &str,
+        body: S,
+        mut headers: HashMap<String, String>,
+    ) -> Result<()>
+    where
+        S: Stream<Item = Bytes>,
+    {
+        self.ensure_running()?;
+        let bundle = self
+            .consumer_components
+            .read()
+            .await
+            .get(component_id)
+            .cloned()
+            .ok_or_else(|| anyhow!("Component not linked: {}", component_id))?;
+        self.check_publish(component_id, &bundle, subject)?;
+
+        let stream_id = uuid::Uuid::new_v4().to_string();
+        headers.insert(STREAM_ID_HEADER.to_string(), stream_id.clone());
+        let descriptor = BrokerMessage {
+            subject: subject.to_string(),
+            body: Bytes::new(),
+            reply_to: None,
+            headers,
+        };
+        self.deadlines.check(&descriptor)?;
+        debug!(
+            "Publishing body stream {} to component {}: subject={}",
+            stream_id, component_id, subject
+        );
+        let frame = Self::encode_checked(&descriptor, bundle)?;
+
+        let aborted =
+            |reason: &str| -> anyhow::Error { BodyStreamAborted::new(&stream_id, reason).into() };
+        let cancel_reason =
+            |reason: Result<String, _>| reason.unwrap_or_else(|_| CONNECTION_LOST.to_string());
+        let (outgoing, mut cancelled) = OutgoingStream::start(
+            &self.body_streams,
+            &stream_id,
+            &bundle.connection_id,
+            &bundle.tx,
+        );
         bundle
             .tx
-            .send(ws_msg)
-            .context("Failed to send message to WebSocket")?;
+            .send(frame.into())
+            .await
+            .map_err(|_| aborted(CONNECTION_LOST))?;
+
+        let mut body = std::pin::pin!(body);
+        loop {
+            let chunk = tokio::select! {
+                chunk = body.next() => chunk,
+                reason = &mut cancelled => return Err(aborted(&cancel_reason(reason))),
+                _ = bundle.tx.closed() => return Err(aborted(CONNECTION_LOST)),
+            };
+            let Some(mut chunk) = chunk else {
+                break;
+            };
+            while !chunk.is_empty() {
+                let piece = chunk.split_to(chunk.len().min(FRAME_CHUNK_BYTES));
+                let frame = body_stream::encode_frame(&stream_id, &StreamFrame::Data(piece));
+                tokio::select! {
+                    sent = bundle.tx.send(Message::Binary(frame).into()) => {
+                        sent.map_err(|_| aborted(CONNECTION_LOST))?;
+                    }
+                    reason = &mut cancelled => return Err(aborted(&cancel_reason(reason))),
+                }
+            }
+        }
 
+        let end = body_stream::encode_frame(&stream_id, &StreamFrame::End);
+        bundle
+            .tx
+            .send(Message::Binary(end).into())
+            .await
+            .map_err(|_| aborted(CONNECTION_LOST))?;
+        outgoing.finish();
         Ok(())
     }
 
+    /// Resolve the body of a descriptor message carrying `x-body-stream-id`
+    ///
+    /// Chunks that arrived before the call are replayed first; a stream nobody has
+    /// subscribed to keeps up to `BODY_STREAM_PREFIX_BYTES`, then waits
+    /// `BODY_STREAM_SUBSCRIBE_TIMEOUT` for a subscriber before it is cancelled. The
+    /// stream ends after the last chunk, or yields a `BodyStreamAborted` error if the
+    /// publisher cancels or its connection is lost; dropping it cancels the publisher.
+    /// Read it outside the handler call that delivered the descriptor, since the
+    /// connection waits for that call before reading further frames.
+    pub fn subscribe_body_stream(
+        &self,
+        stream_id: &str,
+    ) -> Result<impl Stream<Item = Result<Bytes>> + Send + Unpin + 'static> {
+        self.ensure_running()?;
+        self.body_streams.subscribe(stream_id)
+    }
+
     /// Perform a request-reply operation
+    ///
+    /// The request goes out on the component's consumer connection with a fresh
+    /// `<INBOX_PREFIX>.<uuid>` reply subject and a `corr_id`. The first message that
+    /// same connection receives on that subject echoing the `corr_id` is returned instead
+    /// of being forwarded to handlers; one on another connection or without the
+    /// `corr_id` is an ordinary message. Fails once `timeout` passes without a reply.
     #[instrument(skip(self, body))]
     pub async fn request(
         &self,
         component_id: &str,
         subject: String,
         body: Bytes,
-        timeout_ms: u32,
+        timeout: Duration,
     ) -> Result<BrokerMessage> {
+        self.ensure_running()?;
         debug!(
             "Request from component {}: subject={}",
             component_id, subject
@@ -565,91 +2443,565 @@ impl WebSocketMessagingProvider {
         let bundle = consumers
             .get(component_id)
             .ok_or_else(|| anyhow!("Component not linked: {}", component_id))?;
+        self.check_publish(component_id, bundle, &subject)?;
 
         // Generate a reply subject
-        let reply_to = format!("_INBOX.{}", uuid::Uuid::new_v4());
+        let reply_to = format!(
+            "{}.{}",
+            bundle.config.load().inbox_prefix,
+            uuid::Uuid::new_v4()
+        );
 
         let msg = BrokerMessage {
             subject,
             body: body.clone(),
             reply_to: Some(reply_to.clone()),
+            headers: HashMap::new(),
         };
 
-        let ws_msg = self.encode_message(&msg)?;
-        bundle
-            .tx
-            .send(ws_msg)
-            .context("Failed to send request to WebSocket")?;
-
-        // TODO: Implement proper request-reply pattern with response waiting
-        // For now, return a timeout error as this needs more sophisticated handling
-        tokio::time::sleep(Duration::from_millis(timeout_ms as u64)).await;
+        let corr_id = uuid::Uuid::new_v4().to_string();
+        let ws_msg = Self::encode_request_checked(&msg, bundle, Some(&corr_id))?;
+
+        // Registered before sending so a quick reply can't arrive unclaimed
+        let key = (bundle.connection_id.clone(), corr_id);
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.pending_requests.lock().unwrap().insert(
+            key.clone(),
+            PendingRequest {
+                reply_to: reply_to.clone(),
+                reply_tx,
+            },
+        );
+        let sent = bundle.publish(ws_msg, &msg);
+        drop(consumers);
+
+        let result: Result<BrokerMessage> = async {
+            sent?;
+            match tokio::time::timeout(timeout, reply_rx).await {
+                Ok(Ok(reply)) => Ok(reply),
+                Ok(Err(_)) => bail!("Request on {} was abandoned", msg.subject),
+                Err(_) => bail!("Request on {} timed out after {:?}", msg.subject, timeout),
+            }
+        }
+        .await;
+        self.pending_requests.lock().unwrap().remove(&key);
+        result
+    }
 
-        Err(anyhow!("Request-reply not fully implemented yet"))
+    /// Perform a request-reply operation with a timeout in milliseconds
+    #[deprecated(note = "use `request`, which takes the timeout as a `Duration`")]
+    pub async fn request_ms(
+        &self,
+        component_id: &str,
+        subject: String,
+        body: Bytes,
+        timeout_ms: u32,
+    ) -> Result<BrokerMessage> {
+        self.request(
+            component_id,
+            subject,
+            body,
+            Duration::from_millis(timeout_ms.into()),
+        )
+        .await
     }
 
     /// Handle a new link configuration (component linking to this provider)
+    ///
+    /// Returns the effective configuration the link ended up with after merging, and in
+    /// `update` whether an existing connection was kept, updated in place or replaced.
     #[instrument(skip(self, config))]
     pub async fn receive_link_config_as_target(
         &self,
         source_id: &str,
         config: HashMap<String, String>,
-    ) -> Result<()> {
+    ) -> Result<LinkConfigSummary> {
+        self.ensure_running()?;
         info!("Receiving link config for source component: {}", source_id);
 
-        let config = if config.is_empty() {
-            self.default_config.clone()
-        } else {
-            let new_config = ConnectionConfig::from_map(&config)?;
-            self.default_config.merge(&new_config)
-        };
-
-        let bundle = self.connect(config, source_id).await?;
-
-        let mut components = self.consumer_components.write().await;
-        components.insert(source_id.to_string(), bundle);
-
-        info!("Successfully linked component: {}", source_id);
-        Ok(())
+        let effective = self.link_effective_config(source_id, &config)?;
+        let mut summary = LinkConfigSummary::new(source_id, &config, &effective);
+        let update = self
+            .update_link(
+                &self.consumer_components,
+                &self.handler_components,
+                source_id,
+                effective,
+            )
+            .await;
+        self.record_link_attempt(source_id, LinkRole::Consumer, &update)
+            .await;
+        summary.update = update?;
+
+        info!("Successfully linked component: {} ({})", source_id, summary);
+        Ok(summary)
     }
 
     /// Handle link configuration when provider is the source
+    ///
+    /// Returns the effective configuration the link ended up with after merging, and in
+    /// `update` whether an existing connection was kept, updated in place or replaced.
+    /// In server mode, a `TENANT` in the config makes the component the handler of that
+    /// tenant's clients.
     #[instrument(skip(self, config))]
     pub async fn receive_link_config_as_source(
         &self,
         target_id: &str,
         config: HashMap<String, String>,
-    ) -> Result<()> {
+    ) -> Result<LinkConfigSummary> {
+        self.ensure_running()?;
         info!("Receiving link config for target component: {}", target_id);
 
-        let config = if config.is_empty() {
-            self.default_config.clone()
-        } else {
-            let new_config = ConnectionConfig::from_map(&config)?;
-            self.default_config.merge(&new_config)
+        let effective = self.link_effective_config(target_id, &config)?;
+        let mut summary = LinkConfigSummary::new(target_id, &config, &effective);
+        let tenant = effective.tenant.clone();
+        let update = self
+            .update_link(
+                &self.handler_components,
+                &self.consumer_components,
+                target_id,
+                effective,
+            )
+            .await;
+        self.record_link_attempt(target_id, LinkRole::Handler, &update)
+            .await;
+        summary.update = update?;
+        if let Some(ref server_state) = self.server_state {
+            server_state.tenants.set(target_id, tenant.as_deref());
+        }
+
+        info!("Successfully linked component: {} ({})", target_id, summary);
+        Ok(summary)
+    }
+
+    /// Connect the client links declared with `LINK_<name>_<KEY>` keys in the provider's
+    /// own config, each as if component `<name>` had linked with those keys
+    ///
+    /// Lets the provider run as a standalone bridge without a host delivering links. A
+    /// link that fails to connect is listed as failed by `list_links`; with `RECONNECT` in
+    /// its config it is retried in the background with the same backoff as a dropped
+    /// connection, until it connects, the attempts run out or the provider shuts down.
+    pub async fn start_configured_links(&self) {
+        for link in &self.configured_links {
+            let Err(e) = self.link_configured(link).await else {
+                continue;
+            };
+            warn!("Configured link {} failed to connect: {:#}", link.name, e);
+            let Some(policy) = self
+                .link_effective_config(&link.name, &link.config)
+                .ok()
+                .and_then(|config| ReconnectPolicy::from_config(&config))
+            else {
+                continue;
+            };
+            let provider = self.clone();
+            let link = link.clone();
+            self.tasks
+                .spawn("configured-link", &link.name.clone(), async move {
+                    let what = format!("configured link {}", link.name);
+                    let linked = policy
+                        .retry(&what, |_| async {
+                            match provider.link_configured(&link).await {
+                                Ok(_) => Ok(true),
+                                // Nothing left to retry for
+                                Err(e) if e.is::<ProviderShutDown>() => Ok(false),
+                                Err(e) => Err(e),
+                            }
+                        })
+                        .await;
+                    match linked {
+                        Some(true) => info!("Configured link {} connected", link.name),
+                        Some(false) => {}
+                        None => error!("Giving up on configured link {}", link.name),
+                    }
+                });
+        }
+    }
+
+    async fn link_configured(&self, link: &ConfiguredLink) -> Result<LinkConfigSummary> {
+        match link.role {
+            LinkRole::Consumer => {
+                self.receive_link_config_as_target(&link.name, link.config.clone())
+                    .await
+            }
+            LinkRole::Handler => {
+                self.receive_link_config_as_source(&link.name, link.config.clone())
+                    .await
+            }
+        }
+    }
+
+    /// Note how connecting a link went for `list_links`: a failure is charged to the link's
+    /// running connection if it kept one, and otherwise listed as a failed link
+    async fn record_link_attempt(
+        &self,
+        component_id: &str,
+        role: LinkRole,
+        update: &Result<LinkUpdate>,
+    ) {
+        let Err(e) = update else {
+            self.failed_links.clear(component_id, role);
+            return;
+        };
+        match self.components(role).read().await.get(component_id) {
+            Some(bundle) => bundle.health.record_error(format!("{:#}", e)),
+            None => self
+                .failed_links
+                .record(component_id, role, format!("{:#}", e)),
+        }
+    }
+
+    fn components(&self, role: LinkRole) -> &RwLock<HashMap<String, Arc<WebSocketClientBundle>>> {
+        match role {
+            LinkRole::Consumer => &self.consumer_components,
+            LinkRole::Handler => &self.handler_components,
+        }
+    }
+
+    /// Every client-mode link with its role, connection state, traffic and most recent
+    /// failure, including links whose connection attempt failed, by component
+    pub async fn list_links(&self) -> Vec<LinkStatus> {
+        let mut links = self.failed_links.statuses();
+        for role in [LinkRole::Consumer, LinkRole::Handler] {
+            for (component_id, bundle) in self.components(role).read().await.iter() {
+                links.push(Self::link_status(component_id, role, bundle));
+            }
+        }
+        links.sort_by(|a, b| (&a.component_id, a.role).cmp(&(&b.component_id, b.role)));
+        links
+    }
+
+    fn link_status(
+        component_id: &str,
+        role: LinkRole,
+        bundle: &WebSocketClientBundle,
+    ) -> LinkStatus {
+        let (messages_in, messages_out) = bundle.health.counts();
+        LinkStatus {
+            component_id: component_id.to_string(),
+            label: bundle.config.load().label().map(str::to_string),
+            role,
+            connection_status: if bundle.handle.is_finished() {
+                LinkConnectionStatus::Closed
+            } else {
+                LinkConnectionStatus::Connected
+            },
+            session_id: Some(bundle.session_info.session_id.clone()),
+            last_error: bundle.health.last_error(),
+            messages_in,
+            messages_out,
+        }
+    }
+
+    /// Snapshot of the provider's links, sessions, server clients and counters, with
+    /// secrets redacted, for support escalations
+    ///
+    /// Locks are taken in the order `consumer_components`, `handler_components`, session
+    /// storage, server clients, the same as `consistency_report`, and the component locks
+    /// are held until the server clients have been read, so the parts agree.
+    pub async fn diagnostics(&self) -> DiagnosticsSnapshot {
+        let consumers = self.consumer_components.read().await;
+        let handlers = self.handler_components.read().await;
+        let mut sessions = self.sessions.list().await;
+        sessions.sort();
+        let server_clients = match self.server_state {
+            Some(ref server_state) => server_state.client_diagnostics().await,
+            None => Vec::new(),
         };
 
-        let bundle = self.connect(config, target_id).await?;
+        let mut connections = Vec::new();
+        for (role, components) in [
+            (LinkRole::Consumer, &*consumers),
+            (LinkRole::Handler, &*handlers),
+        ] {
+            for (component_id, bundle) in components {
+                connections.push(ConnectionDiagnostics {
+                    link: Self::link_status(component_id, role, bundle),
+                    queued_frames: bundle.queue_depth(),
+                    queue_capacity: bundle.tx.max_capacity(),
+                    dropped_messages: bundle.dropped_messages(),
+                    envelope_version: bundle.envelope.current(),
+                    config: ConnectionConfig::clone(&bundle.config.load()),
+                });
+            }
+        }
+        connections.sort_by(|a, b| {
+            (&a.link.component_id, a.link.role).cmp(&(&b.link.component_id, b.link.role))
+        });
+        let subscriptions = handlers
+            .iter()
+            .map(|(component_id, bundle)| {
+                (
+                    component_id.clone(),
+                    bundle.config.load().subscriptions.clone(),
+                )
+            })
+            .collect();
+        let mut consumer_ids: Vec<_> = consumers.keys().cloned().collect();
+        consumer_ids.sort();
+        let mut handler_ids: Vec<_> = handlers.keys().cloned().collect();
+        handler_ids.sort();
+        drop(handlers);
+        drop(consumers);
+
+        DiagnosticsSnapshot {
+            taken_at: std::time::SystemTime::now(),
+            mode: self.default_config.mode.clone(),
+            server_addr: self.get_server_addr().await,
+            config: self.default_config.redacted(),
+            consumers: consumer_ids,
+            handlers: handler_ids,
+            connections,
+            failed_links: self.failed_links.statuses(),
+            sessions,
+            subscriptions,
+            server_clients,
+            server_queued_bytes: self.server_queued_bytes(),
+            message_stats: self.message_stats(),
+            label_metrics: self.label_metrics(),
+            close_counts: self.close_counts(),
+            runtime_health: self.runtime_health(),
+        }
+    }
 
-        let mut components = self.handler_components.write().await;
-        components.insert(target_id.to_string(), bundle);
+    /// Close code and reason the remote server sent when it last closed one of the
+    /// component's connections, consumer first
+    ///
+    /// `None` until a close frame arrives, and when the connection ended without one.
+    pub async fn last_close_reason(&self, component_id: &str) -> Option<(u16, String)> {
+        for role in [LinkRole::Consumer, LinkRole::Handler] {
+            let close = self
+                .components(role)
+                .read()
+                .await
+                .get(component_id)
+                .and_then(|bundle| bundle.health.last_close());
+            if close.is_some() {
+                return close;
+            }
+        }
+        None
+    }
 
-        info!("Successfully linked component: {}", target_id);
+    /// Bring a component's connection in `role` in line with its `effective` link config
+    ///
+    /// A new link gets a connection from `link_connection`. A re-delivered link keeps its
+    /// connection if nothing changed, or if only per-message settings did, which are
+    /// applied in place. Otherwise (or if the connection is shared with `other_role` or
+    /// has died) a new connection is opened and swapped in before the old one writes out
+    /// its queue and closes, as in `migrate_link`.
+    async fn update_link(
+        &self,
+        role: &RwLock<HashMap<String, Arc<WebSocketClientBundle>>>,
+        other_role: &RwLock<HashMap<String, Arc<WebSocketClientBundle>>>,
+        component_id: &str,
+        effective: ConnectionConfig,
+    ) -> Result<LinkUpdate> {
+        let current = role.read().await.get(component_id).cloned();
+        let Some(current) = current else {
+            let bundle = self
+                .link_connection(other_role, component_id, effective)
+                .await?;
+            role.write().await.insert(component_id.to_string(), bundle);
+            return Ok(LinkUpdate::default());
+        };
+
+        let mut update = LinkUpdate::between(&current.connect_config.load(), &effective);
+        let alive = !current.handle.is_finished();
+        let shared = other_role
+            .read()
+            .await
+            .get(component_id)
+            .is_some_and(|bundle| Arc::ptr_eq(bundle, &current));
+        match update.action {
+            LinkUpdateAction::Unchanged if alive => {}
+            // The other role's link didn't change, so it can't see the new settings
+            LinkUpdateAction::InPlace if alive && !shared => {
+                current.config.store(Arc::new(effective.redacted()));
+                current.connect_config.store(Arc::new(effective));
+            }
+            _ => {
+                update.action = LinkUpdateAction::Reconnected;
+                let bundle = self
+                    .link_connection(other_role, component_id, effective)
+                    .await?;
+                let old = role.write().await.insert(component_id.to_string(), bundle);
+                drop(current);
+                // A shared old connection stays open for the other role
+                if let Some(Ok(old)) = old.map(Arc::try_unwrap) {
+                    Self::drain_and_close(old).await;
+                }
+            }
+        }
+        info!(
+            "Re-delivered link for component {}: {}",
+            component_id, update
+        );
+        Ok(update)
+    }
+
+    /// Connection for a component being linked: the one it already has in its other role
+    /// (`other_role`) if that was made with the same effective config, otherwise a new one
+    ///
+    /// Sharing keeps a single socket and session id for a component linked as both
+    /// consumer and handler.
+    async fn link_connection(
+        &self,
+        other_role: &RwLock<HashMap<String, Arc<WebSocketClientBundle>>>,
+        component_id: &str,
+        effective: ConnectionConfig,
+    ) -> Result<Arc<WebSocketClientBundle>> {
+        if let Some(bundle) = other_role.read().await.get(component_id) {
+            if **bundle.connect_config.load() == effective && !bundle.handle.is_finished() {
+                debug!(
+                    "Component {} shares session {} between consumer and handler links",
+                    component_id, bundle.session_info.session_id
+                );
+                return Ok(Arc::clone(bundle));
+            }
+        }
+        Ok(Arc::new(self.connect(effective, component_id).await?))
+    }
+
+    /// Move a linked component's connection to `new_uri` without losing messages
+    ///
+    /// The new connection is opened first while publishes keep flowing over the old one.
+    /// Once it is up it replaces the old connection, so later publishes go to `new_uri`; the
+    /// old connection then writes out whatever it still has queued and is closed with a
+    /// close frame, rather than being dropped. A connection shared by both
+    /// roles moves once and stays shared.
+    #[instrument(skip(self))]
+    pub async fn migrate_link(&self, component_id: &str, new_uri: &str) -> Result<()> {
+        self.ensure_running()?;
+        let mut moved = Vec::new();
+        for components in [&self.consumer_components, &self.handler_components] {
+            if let Some(migration) = self
+                .migrate_connection(components, component_id, new_uri, &moved)
+                .await?
+            {
+                moved.push(migration);
+            }
+        }
+        if moved.is_empty() {
+            bail!("Component not linked: {}", component_id);
+        }
+        for (old, _) in moved {
+            // The second role of a shared connection holds the old one until both moved
+            if let Ok(old) = Arc::try_unwrap(old) {
+                Self::drain_and_close(old).await;
+            }
+        }
+        info!("Migrated component {} to {}", component_id, new_uri);
         Ok(())
     }
 
+    /// Replace a component's connection in `components` with one to `new_uri`, returning
+    /// the old and new connection if the component had one there
+    ///
+    /// A connection already moved for the other role (in `moved`) is replaced by the same
+    /// new connection instead of dialing again.
+    async fn migrate_connection(
+        &self,
+        components: &RwLock<HashMap<String, Arc<WebSocketClientBundle>>>,
+        component_id: &str,
+        new_uri: &str,
+        moved: &[(Arc<WebSocketClientBundle>, Arc<WebSocketClientBundle>)],
+    ) -> Result<Option<(Arc<WebSocketClientBundle>, Arc<WebSocketClientBundle>)>> {
+        let Some(current) = components.read().await.get(component_id).cloned() else {
+            return Ok(None);
+        };
+        let bundle = match moved.iter().find(|(old, _)| Arc::ptr_eq(old, &current)) {
+            Some((_, new)) => Arc::clone(new),
+            None => {
+                let mut config = current.connect_config.load_full().as_ref().clone();
+                config.uri = new_uri.to_string();
+                Arc::new(self.connect(config, component_id).await?)
+            }
+        };
+        drop(current);
+
+        let mut components = components.write().await;
+        // The link may have been deleted while connecting; the new connection goes with it
+        let Some(slot) = components.get_mut(component_id) else {
+            return Ok(None);
+        };
+        let old = std::mem::replace(slot, Arc::clone(&bundle));
+        Ok(Some((old, bundle)))
+    }
+
+    /// Close a connection after the frames already queued on it have been written
+    async fn drain_and_close(mut bundle: WebSocketClientBundle) {
+        let grace = bundle.connect_config.load().connect_timeout;
+        let session_id = bundle.session_info.session_id.clone();
+        // The close frame queues behind pending frames; the task ends once the server answers
+        let closed = async {
+            bundle.tx.send(Message::Close(None).into()).await.ok()?;
+            (&mut bundle.handle).await.ok()
+        };
+        if tokio::time::timeout(grace, closed).await.is_err() {
+            warn!(
+                "Old connection {} did not close within {:?} after migration, dropping it",
+                session_id, grace
+            );
+        }
+    }
+
+    /// Merge a link's configuration over the provider defaults
+    ///
+    /// In server mode, server-level keys set to something other than what the server runs
+    /// with are rejected with `ServerConfigConflict`, unless the provider was started with
+    /// `ALLOW_SERVER_OVERRIDE`.
+    fn link_effective_config(
+        &self,
+        component_id: &str,
+        config: &HashMap<String, String>,
+    ) -> Result<ConnectionConfig> {
+        if config.is_empty() {
+            return Ok(self.default_config.clone());
+        }
+        let new_config = ConnectionConfig::from_map(config)?;
+        if self.default_config.mode == ConnectionMode::Server {
+            let conflicts = self.default_config.server_conflicts(config, &new_config);
+            if !conflicts.is_empty() {
+                let conflict = ServerConfigConflict {
+                    component_id: component_id.to_string(),
+                    conflicts,
+                };
+                if !self.default_config.allow_server_override {
+                    return Err(conflict.into());
+                }
+                warn!("{} (allowed by ALLOW_SERVER_OVERRIDE)", conflict);
+            }
+        }
+        Ok(self.default_config.merge(&new_config))
+    }
+
+    /// Configuration a linked component's connection actually runs with
+    ///
+    /// This is the provider defaults merged with the link's values, as last applied by a
+    /// link update, with the auth token and header values redacted.
+    pub async fn get_link_config(&self, component_id: &str) -> Option<ConnectionConfig> {
+        if let Some(bundle) = self.consumer_components.read().await.get(component_id) {
+            return Some(bundle.config.load_full().as_ref().clone());
+        }
+        self.handler_components
+            .read()
+            .await
+            .get(component_id)
+            .map(|bundle| bundle.config.load_full().as_ref().clone())
+    }
+
     /// Handle link deletion (component unlinking from provider)
     #[instrument(skip(self))]
     pub async fn delete_link_as_target(&self, source_id: &str) -> Result<()> {
         info!("Deleting link for source component: {}", source_id);
+        self.failed_links.clear(source_id, LinkRole::Consumer);
 
         let mut components = self.consumer_components.write().await;
         if let Some(bundle) = components.remove(source_id) {
-            // The bundle will be dropped here, aborting the task and closing the connection
-            debug!(
-                "Removed WebSocket connection for component {} (session: {})",
-                source_id, bundle.session_info.session_id
-            );
+            // The bundle will be dropped here, aborting the task and closing the connection,
+            // unless the component's handler link shares it
+            Self::log_removed(source_id, &bundle);
         }
 
         Ok(())
@@ -659,23 +3011,66 @@ impl WebSocketMessagingProvider {
     #[instrument(skip(self))]
     pub async fn delete_link_as_source(&self, target_id: &str) -> Result<()> {
         info!("Deleting link for target component: {}", target_id);
+        self.failed_links.clear(target_id, LinkRole::Handler);
+        if let Some(ref server_state) = self.server_state {
+            server_state.tenants.remove(target_id);
+        }
 
         let mut components = self.handler_components.write().await;
         if let Some(bundle) = components.remove(target_id) {
+            Self::log_removed(target_id, &bundle);
+        }
+        self.handler_dispatch.remove(target_id);
+
+        Ok(())
+    }
+
+    fn log_removed(component_id: &str, bundle: &Arc<WebSocketClientBundle>) {
+        if Arc::strong_count(bundle) > 1 {
+            debug!(
+                "Unlinked one role of component {}, its other link keeps session {}",
+                component_id, bundle.session_info.session_id
+            );
+        } else {
             debug!(
                 "Removed WebSocket connection for component {} (session: {})",
-                target_id, bundle.session_info.session_id
+                component_id, bundle.session_info.session_id
             );
         }
-
-        Ok(())
     }
 
     /// Shutdown the provider, closing all connections
-    pub async fn shutdown(&self) -> Result<()> {
+    ///
+    /// Messages still queued for a socket or waiting for a message sink are dropped;
+    /// the report counts them and, with `SHUTDOWN_SPILL_PATH` set, they are written
+    /// there for `import_spill`.
+    ///
+    /// Safe to call more than once and from several clones at the same time: the first
+    /// call tears down, later and concurrent calls wait for it and return the same report.
+    pub async fn shutdown(&self) -> Result<ShutdownReport> {
+        let report = self.shutdown_report.get_or_init(|| self.teardown()).await;
+        Ok(report.clone())
+    }
+
+    async fn teardown(&self) -> ShutdownReport {
         info!("Shutting down WebSocket messaging provider");
+        self.shutdown.store(true, Ordering::SeqCst);
+        self.spill.arm();
+        self.jobs.stop();
+        self.handler_dispatch.clear();
 
-        // Stop server if running
+        // Tell connected clients why they are going away, then stop the server
+        if let Some(ref server_state) = self.server_state {
+            server_state.close_all_clients(CloseReason::Shutdown).await;
+            for (session_id, msg) in server_state.sink.take_buffered().await {
+                self.spill.record(&SpilledMessage {
+                    reason: SpillReason::SinkBuffer,
+                    component_id: None,
+                    session_id,
+                    envelope: Self::envelope_json(&msg),
+                });
+            }
+        }
         let mut server_handle = self.server_handle.write().await;
         if let Some(handle) = server_handle.take() {
             handle.abort();
@@ -683,35 +3078,165 @@ impl WebSocketMessagingProvider {
         }
 
         let mut consumers = self.consumer_components.write().await;
-        consumers.clear();
-
         let mut handlers = self.handler_components.write().await;
-        handlers.clear();
 
-        let mut sessions = self.session_storage.write().await;
-        sessions.clear();
+        // Only this provider's sessions; a shared store may hold other instances' too
+        for (session_id, component_id) in self.sessions.list().await {
+            if consumers.contains_key(&component_id) || handlers.contains_key(&component_id) {
+                self.sessions.remove(&session_id).await;
+            }
+        }
+
+        // Stop the connection tasks and wait for them so their queues are spilled
+        let bundles: Vec<_> = consumers
+            .drain()
+            .chain(handlers.drain())
+            .map(|(_, bundle)| bundle)
+            .collect();
+        drop(consumers);
+        drop(handlers);
+        for bundle in &bundles {
+            bundle.handle.abort();
+        }
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(1);
+        while bundles.iter().any(|bundle| !bundle.handle.is_finished())
+            && tokio::time::Instant::now() < deadline
+        {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        drop(bundles);
 
+        let report = self.spill.report();
+        if report.unsent > 0 {
+            warn!(
+                "Shutdown dropped {} unsent messages ({} spilled)",
+                report.unsent, report.spilled
+            );
+        }
+        if report.inbound_dropped > 0 {
+            info!(
+                "Shutdown dropped {} inbound messages received while tearing down",
+                report.inbound_dropped
+            );
+        }
         info!("WebSocket messaging provider shutdown complete");
-        Ok(())
+        report
+    }
+
+    /// Re-enqueue the messages a previous `shutdown` wrote to a spill file
+    ///
+    /// Outbound messages go back on the queue of the component they were published
+    /// from, which must be linked again; sink-buffer messages are delivered to this
+    /// server's message sink. Entries repeating an earlier `msg-id` header are skipped.
+    /// Returns how many messages were re-enqueued; the file is left in place.
+    pub async fn import_spill(&self, path: impl AsRef<Path>) -> Result<usize> {
+        let mut seen = std::collections::HashSet::new();
+        let mut imported = 0;
+        for entry in read_spill(path)? {
+            let msg_id = entry
+                .envelope
+                .get("headers")
+                .and_then(|headers| headers.get(drop_notify::MSG_ID_HEADER))
+                .and_then(|v| v.as_str());
+            if let Some(msg_id) = msg_id {
+                if !seen.insert(msg_id.to_string()) {
+                    debug!("Skipping duplicate spilled message {}", msg_id);
+                    continue;
+                }
+            }
+
+            match entry.reason {
+                SpillReason::OutboundQueue => {
+                    let component_id = entry.component_id.as_deref().unwrap_or_default();
+                    let bundle = match self.consumer_components.read().await.get(component_id) {
+                        Some(bundle) => Some(Arc::clone(bundle)),
+                        None => self
+                            .handler_components
+                            .read()
+                            .await
+                            .get(component_id)
+                            .cloned(),
+                    };
+                    let bundle = bundle.ok_or_else(|| {
+                        anyhow::anyhow!("No WebSocket connection for component {}", component_id)
+                    })?;
+                    let frame = match entry.envelope {
+                        serde_json::Value::String(text) => text,
+                        envelope => envelope.to_string(),
+                    };
+                    bundle
+                        .tx
+                        .send(Message::Text(frame).into())
+                        .await
+                        .context("Failed to re-enqueue spilled message")?;
+                }
+                SpillReason::SinkBuffer => {
+                    let server_state = self.server_state.as_ref().ok_or_else(|| {
+                        anyhow::anyhow!("Sink-buffer messages can only be imported in server mode")
+                    })?;
+                    let msg = Self::message_from_envelope(&entry.envelope)?;
+                    server_state
+                        .sink
+                        .deliver(
+                            entry.session_id,
+                            msg,
+                            server_state.config.delivery_timeout(),
+                        )
+                        .await?;
+                }
+            }
+            imported += 1;
+        }
+        Ok(imported)
     }
 }
 
-// uuid is used in the implementation via uuid::Uuid::new_v4()
+/// Hooks for putting the provider's state out of sync in tests of the reconciler
+#[cfg(feature = "test-util")]
+impl WebSocketMessagingProvider {
+    /// Abort the task serving the listener, as if it had died, leaving its supervisor running
+    pub fn abort_server_task(&self) {
+        if let Some(serve) = self.serve_task.lock().unwrap().as_ref() {
+            serve.abort();
+        }
+    }
 
-// Base64 encoding for message payload
-mod base64 {
-    use bytes::Bytes;
+    /// Add a session entry for `component_id` without a connection behind it
+    pub async fn inject_session(&self, session_id: &str, component_id: &str) {
+        self.sessions.insert(session_id, component_id).await;
+    }
 
-    pub fn encode(data: &Bytes) -> String {
-        data.iter()
-            .flat_map(|&b| {
-                let hex = format!("{:02x}", b);
-                hex.chars().collect::<Vec<_>>()
-            })
-            .collect()
+    /// Remove a session entry, leaving its connection in place
+    pub async fn forget_session(&self, session_id: &str) {
+        self.sessions.remove(session_id).await;
+    }
+
+    /// Stop a linked component's connection task while keeping the link
+    pub async fn abort_link_task(&self, component_id: &str) {
+        for components in [&self.consumer_components, &self.handler_components] {
+            if let Some(bundle) = components.read().await.get(component_id) {
+                bundle.handle.abort();
+            }
+        }
+    }
+
+    /// Register a server client whose connection has already ended (server mode)
+    pub async fn inject_stale_ws_client(&self, session_id: &str) {
+        if let Some(ref server_state) = self.server_state {
+            server_state.insert_stale_client(session_id).await;
+        }
+    }
+
+    /// Put a session in a group without checking that it is connected (server mode)
+    pub fn inject_group_membership(&self, session_id: &str, group: &str) {
+        if let Some(ref server_state) = self.server_state {
+            server_state.insert_membership(session_id, group);
+        }
     }
 }
 
+// uuid is used in the implementation via uuid::Uuid::new_v4()
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -741,4 +3266,80 @@ mod tests {
 
         // Session tracking is tested through the connect method
     }
+
+    #[test]
+    fn test_message_round_trips() {
+        let msg = BrokerMessage {
+            subject: "data.raw".to_string(),
+            body: Bytes::from((0..=255u8).collect::<Vec<_>>()),
+            reply_to: Some("inbox.1".to_string()),
+            headers: HashMap::from([("k".to_string(), "v".to_string())]),
+        };
+        let Message::Text(text) = WebSocketMessagingProvider::encode_message_static(&msg).unwrap()
+        else {
+            panic!("envelopes are text frames");
+        };
+        let json: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(json["body"], STANDARD.encode(&msg.body));
+
+        let parsed = WebSocketMessagingProvider::parse_message_static(&text, "s").unwrap();
+        assert_eq!(parsed.subject, msg.subject);
+        assert_eq!(parsed.body, msg.body);
+        assert_eq!(parsed.reply_to, msg.reply_to);
+        assert_eq!(parsed.headers, msg.headers);
+
+        let AxumMessage::Text(text) =
+            WebSocketMessagingProvider::encode_message_to_axum_static(&msg).unwrap()
+        else {
+            panic!("envelopes are text frames");
+        };
+        let parsed = WebSocketMessagingProvider::parse_message_static(&text, "s").unwrap();
+        assert_eq!(parsed.body, msg.body);
+    }
+
+    #[test]
+    fn test_plain_text_body_kept() {
+        let parsed = WebSocketMessagingProvider::parse_message_static(
+            r#"{"subject":"chat","body":"hello world"}"#,
+            "s",
+        )
+        .unwrap();
+        assert_eq!(parsed.body, Bytes::from("hello world"));
+    }
+
+    #[test]
+    fn test_over_length_fields_rejected() {
+        let envelope = |subject: &str, reply_to: &str| {
+            serde_json::json!({"subject": subject, "body": "aGk=", "reply_to": reply_to})
+                .to_string()
+        };
+        let parsed =
+            WebSocketMessagingProvider::parse_message_static(&envelope("orders.1", "r.1"), "s")
+                .unwrap();
+        assert_eq!(parsed.subject, "orders.1");
+
+        let long_subject = "x".repeat(subject::DEFAULT_MAX_SUBJECT_LEN + 1);
+        let e =
+            WebSocketMessagingProvider::parse_message_static(&envelope(&long_subject, "r.1"), "s")
+                .unwrap_err();
+        assert_eq!(ParseFailureReason::of(&e), ParseFailureReason::FieldTooLong);
+
+        let limits = FieldLimits {
+            max_subject_len: 16,
+            max_reply_to_len: 4,
+        };
+        let parse = |text: &str| {
+            WebSocketMessagingProvider::parse_message_with(
+                text,
+                "s",
+                ByteArrayPolicy::default(),
+                BodyDecoding::default(),
+                limits,
+            )
+        };
+        assert!(parse(&envelope("orders.1", "r.1")).is_ok());
+        let e = parse(&envelope("orders.1", "r.12345")).unwrap_err();
+        assert_eq!(ParseFailureReason::of(&e), ParseFailureReason::FieldTooLong);
+        assert!(e.to_string().contains("MAX_REPLY_TO_LEN"));
+    }
 }
@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use serde::Serialize;
+
+/// Which side of a wasmCloud link a client-mode connection serves
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkRole {
+    /// The provider is the link's target; the component publishes through the connection
+    Consumer,
+    /// The provider is the link's source; the component receives inbound messages
+    Handler,
+}
+
+/// State of a link's connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkConnectionStatus {
+    /// The connection task is running
+    Connected,
+    /// The connection was established and has since ended
+    Closed,
+    /// The last attempt to connect the link failed; see `last_error`
+    Failed,
+}
+
+/// One link of a component as seen by `list_links`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LinkStatus {
+    pub component_id: String,
+    /// The link's `LABEL`, if it has one; wasmCloud link names don't reach the provider
+    pub label: Option<String>,
+    pub role: LinkRole,
+    pub connection_status: LinkConnectionStatus,
+    /// Session of the connection; `None` when the link never connected
+    pub session_id: Option<String>,
+    /// Most recent failure attributed to the link and when it happened
+    pub last_error: Option<(SystemTime, String)>,
+    /// Frames received on the connection
+    pub messages_in: u64,
+    /// Frames written to the connection
+    pub messages_out: u64,
+}
+
+/// Traffic and failures of one connection, updated by its task and by publishes
+#[derive(Debug, Default)]
+pub(crate) struct LinkHealth {
+    messages_in: AtomicU64,
+    messages_out: AtomicU64,
+    last_error: Mutex<Option<(SystemTime, String)>>,
+    last_close: Mutex<Option<(u16, String)>>,
+}
+
+impl LinkHealth {
+    pub(crate) fn record_in(&self) {
+        self.messages_in.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_out(&self) {
+        self.messages_out.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Remember `error` as the link's most recent failure
+    pub(crate) fn record_error(&self, error: impl Display) {
+        *self.last_error.lock().unwrap() = Some((SystemTime::now(), error.to_string()));
+    }
+
+    pub(crate) fn last_error(&self) -> Option<(SystemTime, String)> {
+        self.last_error.lock().unwrap().clone()
+    }
+
+    /// Remember the code and reason of the close frame the remote ended the connection with
+    pub(crate) fn record_close(&self, code: u16, reason: impl Into<String>) {
+        *self.last_close.lock().unwrap() = Some((code, reason.into()));
+    }
+
+    pub(crate) fn last_close(&self) -> Option<(u16, String)> {
+        self.last_close.lock().unwrap().clone()
+    }
+
+    pub(crate) fn counts(&self) -> (u64, u64) {
+        (
+            self.messages_in.load(Ordering::Relaxed),
+            self.messages_out.load(Ordering::Relaxed),
+        )
+    }
+}
+
+/// Links whose last connection attempt failed, so they show up although they have no
+/// connection
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FailedLinks {
+    inner: Arc<Mutex<HashMap<(String, LinkRole), (SystemTime, String)>>>,
+}
+
+impl FailedLinks {
+    pub(crate) fn record(&self, component_id: &str, role: LinkRole, error: impl Display) {
+        self.inner.lock().unwrap().insert(
+            (component_id.to_string(), role),
+            (SystemTime::now(), error.to_string()),
+        );
+    }
+
+    /// Forget a link once it connects or is deleted
+    pub(crate) fn clear(&self, component_id: &str, role: LinkRole) {
+        self.inner
+            .lock()
+            .unwrap()
+            .remove(&(component_id.to_string(), role));
+    }
+
+    pub(crate) fn statuses(&self) -> Vec<LinkStatus> {
+        self.inner
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|((component_id, role), error)| LinkStatus {
+                component_id: component_id.clone(),
+                label: None,
+                role: *role,
+                connection_status: LinkConnectionStatus::Failed,
+                session_id: None,
+                last_error: Some(error.clone()),
+                messages_in: 0,
+                messages_out: 0,
+            })
+            .collect()
+    }
+}
@@ -1,5 +1,7 @@
 use anyhow::Result;
+use std::collections::HashMap;
 use tracing::info;
+use tracing_subscriber::prelude::*;
 use tracing_subscriber::EnvFilter;
 
 use wasmcloud_provider_messaging_websocket::WebSocketMessagingProvider;
@@ -7,31 +9,74 @@ use wasmcloud_provider_messaging_websocket::WebSocketMessagingProvider;
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize tracing
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
-        )
-        .init();
+    let logs = tracing_subscriber::fmt::layer()
+        .with_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")));
+    let registry = tracing_subscriber::registry().with(logs);
+    // The console layer sees tokio's task events regardless of RUST_LOG
+    #[cfg(feature = "console")]
+    let registry = registry.with(console_subscriber::spawn());
+    registry.init();
 
     info!("Starting WebSocket Messaging Provider");
 
-    // Create provider instance
-    let provider = WebSocketMessagingProvider::new();
+    // Provider config from `WS_<KEY>` environment variables
+    let config: HashMap<String, String> = std::env::vars()
+        .filter_map(|(key, value)| Some((key.strip_prefix("WS_")?.to_string(), value)))
+        .collect();
+    let provider = WebSocketMessagingProvider::from_config(config)?;
+    provider.start_janitor();
 
     // In a real wasmCloud integration, this would:
     // 1. Register with wasmCloud host
     // 2. Listen for link configurations
     // 3. Handle component invocations
-    // For now, this is a standalone demonstration
+    // Until then, links declared with `WS_LINK_<name>_*` make it a standalone bridge
+    provider.start_configured_links().await;
 
     info!("WebSocket Messaging Provider initialized");
     info!("Waiting for shutdown signal...");
 
-    // Wait for Ctrl+C
-    tokio::signal::ctrl_c().await?;
+    shutdown_signal().await?;
 
     info!("Shutdown signal received");
     provider.shutdown().await?;
 
     Ok(())
 }
+
+/// Wait for Ctrl+C or SIGTERM
+#[cfg(unix)]
+async fn shutdown_signal() -> Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = signal(SignalKind::terminate())?;
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result?,
+        _ = terminate.recv() => {}
+    }
+    Ok(())
+}
+
+/// Wait for Ctrl+C, Ctrl+Break or a console/system shutdown
+#[cfg(windows)]
+async fn shutdown_signal() -> Result<()> {
+    use tokio::signal::windows::{ctrl_break, ctrl_close, ctrl_shutdown};
+
+    let mut brk = ctrl_break()?;
+    let mut close = ctrl_close()?;
+    let mut shutdown = ctrl_shutdown()?;
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result?,
+        _ = brk.recv() => {}
+        _ = close.recv() => {}
+        _ = shutdown.recv() => {}
+    }
+    Ok(())
+}
+
+/// Wait for Ctrl+C
+#[cfg(not(any(unix, windows)))]
+async fn shutdown_signal() -> Result<()> {
+    tokio::signal::ctrl_c().await?;
+    Ok(())
+}
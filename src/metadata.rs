@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use tracing::warn;
+
+use crate::connection::ConnectionConfig;
+
+/// Prefix of metadata keys only the provider itself may write
+pub const RESERVED_METADATA_PREFIX: &str = "_wcp_";
+
+/// Why a metadata entry was not stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetadataRejection {
+    /// The key starts with `_wcp_`, which is reserved for the provider
+    ReservedKey,
+    /// The session already has `MAX_METADATA_ENTRIES` entries
+    TooManyEntries { max: usize },
+    /// The key is longer than `MAX_METADATA_KEY_LEN` bytes
+    KeyTooLong { max: usize },
+    /// The value is longer than `MAX_METADATA_VALUE_LEN` bytes
+    ValueTooLong { max: usize },
+}
+
+/// Error returned when a session metadata entry is reserved or exceeds a configured limit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataRejected {
+    pub session_id: String,
+    pub key: String,
+    pub reason: MetadataRejection,
+}
+
+impl fmt::Display for MetadataRejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.reason {
+            MetadataRejection::ReservedKey => write!(
+                f,
+                "Metadata key {} of session {} uses the reserved prefix {}",
+                self.key, self.session_id, RESERVED_METADATA_PREFIX
+            ),
+            MetadataRejection::TooManyEntries { max } => write!(
+                f,
+                "Session {} cannot store metadata {}: already has {} entries (MAX_METADATA_ENTRIES)",
+                self.session_id, self.key, max
+            ),
+            MetadataRejection::KeyTooLong { max } => write!(
+                f,
+                "Metadata key {} of session {} is longer than {} bytes (MAX_METADATA_KEY_LEN)",
+                self.key, self.session_id, max
+            ),
+            MetadataRejection::ValueTooLong { max } => write!(
+                f,
+                "Metadata value of {} on session {} is longer than {} bytes (MAX_METADATA_VALUE_LEN)",
+                self.key, self.session_id, max
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MetadataRejected {}
+
+/// Bounds on what a session's metadata may hold (0 = no limit)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct MetadataLimits {
+    pub(crate) max_entries: usize,
+    pub(crate) max_key_len: usize,
+    pub(crate) max_value_len: usize,
+}
+
+impl MetadataLimits {
+    pub(crate) fn from_config(config: &ConnectionConfig) -> Self {
+        Self {
+            max_entries: config.max_metadata_entries,
+            max_key_len: config.max_metadata_key_len,
+            max_value_len: config.max_metadata_value_len,
+        }
+    }
+
+    /// Whether `key` = `value` may be stored next to `metadata` by anyone but the provider
+    ///
+    /// Replacing an existing key doesn't count as a new entry.
+    pub(crate) fn check(
+        &self,
+        metadata: &HashMap<String, String>,
+        key: &str,
+        value: &str,
+    ) -> Result<(), MetadataRejection> {
+        if key.starts_with(RESERVED_METADATA_PREFIX) {
+            return Err(MetadataRejection::ReservedKey);
+        }
+        if self.max_key_len > 0 && key.len() > self.max_key_len {
+            return Err(MetadataRejection::KeyTooLong {
+                max: self.max_key_len,
+            });
+        }
+        if self.max_value_len > 0 && value.len() > self.max_value_len {
+            return Err(MetadataRejection::ValueTooLong {
+                max: self.max_value_len,
+            });
+        }
+        if self.max_entries > 0 && !metadata.contains_key(key) && metadata.len() >= self.max_entries
+        {
+            return Err(MetadataRejection::TooManyEntries {
+                max: self.max_entries,
+            });
+        }
+        Ok(())
+    }
+
+    /// Store an entry taken from the client's upgrade request, skipping it with a warning
+    /// if it breaks a limit
+    pub(crate) fn insert_upgrade(
+        &self,
+        session_id: &str,
+        metadata: &mut HashMap<String, String>,
+        key: &str,
+        value: String,
+    ) {
+        match self.check(metadata, key, &value) {
+            Ok(()) => {
+                metadata.insert(key.to_string(), value);
+            }
+            Err(reason) => warn!(
+                "Skipped upgrade metadata: {}",
+                MetadataRejected {
+                    session_id: session_id.to_string(),
+                    key: key.to_string(),
+                    reason,
+                }
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits() -> MetadataLimits {
+        MetadataLimits {
+            max_entries: 2,
+            max_key_len: 8,
+            max_value_len: 4,
+        }
+    }
+
+    #[test]
+    fn test_check_limits() {
+        let mut metadata = HashMap::from([("a".to_string(), "1".to_string())]);
+        assert_eq!(limits().check(&metadata, "b", "2"), Ok(()));
+        assert_eq!(
+            limits().check(&metadata, "_wcp_role", "x"),
+            Err(MetadataRejection::ReservedKey)
+        );
+        assert_eq!(
+            limits().check(&metadata, "much_too_long", "x"),
+            Err(MetadataRejection::KeyTooLong { max: 8 })
+        );
+        assert_eq!(
+            limits().check(&metadata, "b", "12345"),
+            Err(MetadataRejection::ValueTooLong { max: 4 })
+        );
+
+        metadata.insert("b".to_string(), "2".to_string());
+        assert_eq!(
+            limits().check(&metadata, "c", "3"),
+            Err(MetadataRejection::TooManyEntries { max: 2 })
+        );
+        // Replacing an entry is fine at the limit
+        assert_eq!(limits().check(&metadata, "a", "9"), Ok(()));
+
+        let unlimited = MetadataLimits {
+            max_entries: 0,
+            max_key_len: 0,
+            max_value_len: 0,
+        };
+        assert_eq!(unlimited.check(&metadata, "c", &"x".repeat(10_000)), Ok(()));
+    }
+
+    #[test]
+    fn test_upgrade_entries_skipped() {
+        let mut metadata = HashMap::new();
+        limits().insert_upgrade("s1", &mut metadata, "tenant", "acme".to_string());
+        limits().insert_upgrade("s1", &mut metadata, "region", "eu-west".to_string());
+        limits().insert_upgrade("s1", &mut metadata, "_wcp_x", "1".to_string());
+        assert_eq!(
+            metadata,
+            HashMap::from([("tenant".to_string(), "acme".to_string())])
+        );
+    }
+}
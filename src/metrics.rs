@@ -0,0 +1,289 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+
+use crate::connection::{ConnectionConfig, ConnectionMode};
+use crate::link_status::LinkRole;
+
+/// Subject label of the series that messages beyond `METRIC_MAX_SERIES` are counted in
+pub const OVERFLOW_SUBJECT: &str = "other";
+
+/// Most overflow series there can be, one per mode and role; `METRIC_MAX_SERIES` always
+/// leaves room for them
+const OVERFLOW_SERIES: usize = 4;
+
+/// Messages and payload bytes counted for one component or session
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct TrafficStats {
+    pub messages: u64,
+    pub bytes: u64,
+}
+
+/// Per-component and per-session traffic, from `message_stats`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct MessageStats {
+    /// Messages published and received by each client-mode component
+    pub components: HashMap<String, TrafficStats>,
+    /// Messages sent to and received from each connected server client
+    pub sessions: HashMap<String, TrafficStats>,
+}
+
+/// Labels of one series
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct SeriesKey {
+    mode: &'static str,
+    role: LinkRole,
+    subject: String,
+    /// Component (client mode) or session (server mode), with `HIGH_CARDINALITY_METRICS`
+    source: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct MetricsInner {
+    series: BTreeMap<SeriesKey, TrafficStats>,
+    /// Series in `series` other than the overflow ones
+    regular_series: usize,
+    components: HashMap<String, TrafficStats>,
+    sessions: HashMap<String, TrafficStats>,
+}
+
+/// Message counters labelled by mode, link role and subject prefix, bounded to
+/// `METRIC_MAX_SERIES` label sets; component and session detail is kept aside for
+/// `message_stats` unless `HIGH_CARDINALITY_METRICS` puts it in the labels
+#[derive(Debug, Clone, Default)]
+pub(crate) struct MessageMetrics {
+    inner: Arc<Mutex<MetricsInner>>,
+    subject_depth: usize,
+    max_series: usize,
+    high_cardinality: bool,
+}
+
+impl MessageMetrics {
+    pub(crate) fn new(config: &ConnectionConfig) -> Self {
+        Self {
+            inner: Arc::default(),
+            subject_depth: config.metric_subject_depth,
+            max_series: config.metric_max_series,
+            high_cardinality: config.high_cardinality_metrics,
+        }
+    }
+
+    /// The first `METRIC_SUBJECT_DEPTH` tokens of `subject`
+    fn subject_prefix(&self, subject: &str) -> String {
+        subject
+            .split('.')
+            .take(self.subject_depth)
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    /// Count a message of `bytes` payload bytes sent or received by `source`, a component
+    /// in client mode or a session in server mode
+    pub(crate) fn record(
+        &self,
+        mode: ConnectionMode,
+        role: LinkRole,
+        subject: &str,
+        bytes: usize,
+        source: Option<&str>,
+    ) {
+        let mode = match mode {
+            ConnectionMode::Client => "client",
+            ConnectionMode::Server => "server",
+        };
+        let mut key = SeriesKey {
+            mode,
+            role,
+            subject: self.subject_prefix(subject),
+            source: source.filter(|_| self.high_cardinality).map(str::to_string),
+        };
+        let bytes = bytes as u64;
+        let mut inner = self.inner.lock().unwrap();
+        if !inner.series.contains_key(&key) {
+            let limit = self.max_series.saturating_sub(OVERFLOW_SERIES);
+            if self.max_series > 0 && inner.regular_series >= limit {
+                key.subject = OVERFLOW_SUBJECT.to_string();
+                key.source = None;
+            } else {
+                inner.regular_series += 1;
+            }
+        }
+        add(inner.series.entry(key).or_default(), bytes);
+        if let Some(source) = source {
+            let detail = match mode {
+                "client" => &mut inner.components,
+                _ => &mut inner.sessions,
+            };
+            add(detail.entry(source.to_string()).or_default(), bytes);
+        }
+    }
+
+    /// Drop the detail of a server session that disconnected
+    pub(crate) fn forget_session(&self, session_id: &str) {
+        self.inner.lock().unwrap().sessions.remove(session_id);
+    }
+
+    pub(crate) fn stats(&self) -> MessageStats {
+        let inner = self.inner.lock().unwrap();
+        MessageStats {
+            components: inner.components.clone(),
+            sessions: inner.sessions.clone(),
+        }
+    }
+
+    /// The counters in the Prometheus text exposition format
+    pub(crate) fn render(&self) -> String {
+        let inner = self.inner.lock().unwrap();
+        let mut out = String::new();
+        for (name, help, value) in [
+            (
+                "messages_total",
+                "Messages sent and received, by mode, link role and subject prefix",
+                (|stats: &TrafficStats| stats.messages) as fn(&TrafficStats) -> u64,
+            ),
+            (
+                "message_bytes_total",
+                "Payload bytes of messages sent and received, by mode, link role and subject prefix",
+                |stats: &TrafficStats| stats.bytes,
+            ),
+        ] {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            for (key, stats) in &inner.series {
+                let _ = write!(
+                    out,
+                    "{}{{mode=\"{}\",role=\"{}\",subject=\"{}\"",
+                    name,
+                    key.mode,
+                    role_label(key.role),
+                    escape(&key.subject)
+                );
+                if let Some(source) = &key.source {
+                    let label = if key.mode == "client" {
+                        "component"
+                    } else {
+                        "session"
+                    };
+                    let _ = write!(out, ",{}=\"{}\"", label, escape(source));
+                }
+                let _ = writeln!(out, "}} {}", value(stats));
+            }
+        }
+        out
+    }
+}
+
+fn add(stats: &mut TrafficStats, bytes: u64) {
+    stats.messages += 1;
+    stats.bytes += bytes;
+}
+
+fn role_label(role: LinkRole) -> &'static str {
+    match role {
+        LinkRole::Consumer => "consumer",
+        LinkRole::Handler => "handler",
+    }
+}
+
+/// Escape a label value for the text exposition format
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metrics(depth: usize, max_series: usize, high_cardinality: bool) -> MessageMetrics {
+        MessageMetrics {
+            subject_depth: depth,
+            max_series,
+            high_cardinality,
+            ..MessageMetrics::default()
+        }
+    }
+
+    #[test]
+    fn test_subject_prefix_depth() {
+        assert_eq!(
+            metrics(1, 0, false).subject_prefix("orders.eu.created"),
+            "orders"
+        );
+        assert_eq!(
+            metrics(2, 0, false).subject_prefix("orders.eu.created"),
+            "orders.eu"
+        );
+        assert_eq!(metrics(5, 0, false).subject_prefix("orders"), "orders");
+        assert_eq!(metrics(0, 0, false).subject_prefix("orders"), "");
+    }
+
+    #[test]
+    fn test_overflow_collapses_into_other() {
+        let metrics = metrics(1, 6, false);
+        for i in 0..10 {
+            let subject = format!("s{}.x", i);
+            metrics.record(
+                ConnectionMode::Server,
+                LinkRole::Handler,
+                &subject,
+                10,
+                Some("a"),
+            );
+        }
+        let inner = metrics.inner.lock().unwrap();
+        // Two regular series, the rest in the overflow bucket
+        assert_eq!(inner.series.len(), 3);
+        let other = inner
+            .series
+            .iter()
+            .find(|(key, _)| key.subject == OVERFLOW_SUBJECT)
+            .map(|(_, stats)| *stats);
+        assert_eq!(
+            other,
+            Some(TrafficStats {
+                messages: 8,
+                bytes: 80
+            })
+        );
+        assert_eq!(
+            inner.sessions["a"],
+            TrafficStats {
+                messages: 10,
+                bytes: 100
+            }
+        );
+    }
+
+    #[test]
+    fn test_render_labels() {
+        let low = metrics(1, 0, false);
+        low.record(
+            ConnectionMode::Client,
+            LinkRole::Consumer,
+            "orders.new",
+            3,
+            Some("c1"),
+        );
+        assert!(low
+            .render()
+            .contains("messages_total{mode=\"client\",role=\"consumer\",subject=\"orders\"} 1\n"));
+
+        let high = metrics(1, 0, true);
+        high.record(
+            ConnectionMode::Client,
+            LinkRole::Consumer,
+            "orders.new",
+            3,
+            Some("c1"),
+        );
+        assert!(high.render().contains(
+            "message_bytes_total{mode=\"client\",role=\"consumer\",subject=\"orders\",component=\"c1\"} 3\n"
+        ));
+        assert_eq!(escape("a\"b\\"), "a\\\"b\\\\");
+    }
+}
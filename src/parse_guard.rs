@@ -0,0 +1,293 @@
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::envelope::UnsupportedEnvelopeVersion;
+use crate::signing::InvalidSignature;
+use crate::subject::{FieldTooLong, InvalidSubject};
+
+/// Limits applied to sessions that keep sending frames which fail to parse
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ParseFailurePolicy {
+    /// Consecutive failures before frames are skipped without parsing (0 disables)
+    pub threshold: u32,
+    /// How long frames are skipped once the threshold is reached
+    pub cooldown: Duration,
+    /// Consecutive failures after which the session is disconnected (0 disables)
+    pub disconnect_after: u32,
+}
+
+/// What to do with an incoming text frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FrameVerdict {
+    /// Parse the frame normally
+    Parse,
+    /// Drop the frame without parsing it (session is in cooldown)
+    Skip,
+}
+
+/// Consequence of recording a parse failure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FailureOutcome {
+    /// Below all thresholds
+    Counted,
+    /// The threshold was reached and a cooldown has started
+    CooldownStarted { failures: u32 },
+    /// The disconnect threshold was reached
+    Disconnect { failures: u32 },
+}
+
+/// Why an inbound frame was rejected, counted in `parse_failure_counts`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum ParseFailureReason {
+    /// Text that isn't valid Unicode, including JSON `\u` escapes of unpaired surrogates
+    InvalidUtf8,
+    /// Text that isn't a JSON envelope
+    InvalidJson,
+    /// An envelope whose subject fails `subject::check_inbound`
+    InvalidSubject,
+    /// An envelope whose subject or reply_to exceeds `MAX_SUBJECT_LEN` or
+    /// `MAX_REPLY_TO_LEN`
+    FieldTooLong,
+    /// An envelope with an unusable body (e.g. a byte array out of range)
+    InvalidBody,
+    /// An envelope in a version the session has not negotiated
+    UnsupportedVersion,
+    /// A message whose `MESSAGE_HMAC_KEY` signature is missing or doesn't match
+    InvalidSignature,
+}
+
+impl ParseFailureReason {
+    /// Classify the error a frame failed to parse with
+    pub(crate) fn of(error: &anyhow::Error) -> Self {
+        if error.is::<InvalidUtf8>() {
+            Self::InvalidUtf8
+        } else if error.is::<InvalidSubject>() {
+            Self::InvalidSubject
+        } else if error.is::<FieldTooLong>() {
+            Self::FieldTooLong
+        } else if error.is::<UnsupportedEnvelopeVersion>() {
+            Self::UnsupportedVersion
+        } else if error.is::<InvalidSignature>() {
+            Self::InvalidSignature
+        } else if let Some(e) = error.downcast_ref::<serde_json::Error>() {
+            if is_invalid_unicode_escape(e) {
+                Self::InvalidUtf8
+            } else {
+                Self::InvalidJson
+            }
+        } else {
+            Self::InvalidBody
+        }
+    }
+
+    /// Key in `parse_failure_counts`
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Self::InvalidUtf8 => "invalid_utf8",
+            Self::InvalidJson => "invalid_json",
+            Self::InvalidSubject => "invalid_subject",
+            Self::FieldTooLong => "field_too_long",
+            Self::InvalidBody => "invalid_body",
+            Self::UnsupportedVersion => "unsupported_version",
+            Self::InvalidSignature => "invalid_signature",
+        }
+    }
+}
+
+/// Text frame or JSON string that doesn't decode to valid Unicode
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct InvalidUtf8(pub String);
+
+impl std::fmt::Display for InvalidUtf8 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid UTF-8: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidUtf8 {}
+
+/// Whether serde_json rejected a `\u` escape that doesn't form a Unicode scalar value
+///
+/// serde_json reports these as plain syntax errors, so they are told apart by message.
+pub(crate) fn is_invalid_unicode_escape(error: &serde_json::Error) -> bool {
+    let message = error.to_string();
+    message.contains("hex escape") || message.contains("unicode code point")
+}
+
+/// Per-session streak of consecutive parse failures
+#[derive(Debug)]
+pub(crate) struct ParseFailureGuard {
+    policy: ParseFailurePolicy,
+    streak: u32,
+    cooldown_until: Option<Instant>,
+    skipped: u64,
+}
+
+impl ParseFailureGuard {
+    pub(crate) fn new(policy: ParseFailurePolicy) -> Self {
+        Self {
+            policy,
+            streak: 0,
+            cooldown_until: None,
+            skipped: 0,
+        }
+    }
+
+    /// Decide whether the next frame should be parsed
+    pub(crate) fn check(&mut self) -> FrameVerdict {
+        match self.cooldown_until {
+            Some(until) if Instant::now() < until => {
+                self.skipped += 1;
+                FrameVerdict::Skip
+            }
+            Some(_) => {
+                self.cooldown_until = None;
+                FrameVerdict::Parse
+            }
+            None => FrameVerdict::Parse,
+        }
+    }
+
+    /// A frame parsed successfully; the streak starts over
+    pub(crate) fn record_success(&mut self) {
+        self.streak = 0;
+    }
+
+    /// A frame failed to parse
+    pub(crate) fn record_failure(&mut self) -> FailureOutcome {
+        self.streak += 1;
+
+        if self.policy.disconnect_after > 0 && self.streak >= self.policy.disconnect_after {
+            return FailureOutcome::Disconnect {
+                failures: self.streak,
+            };
+        }
+        if self.policy.threshold > 0 && self.streak >= self.policy.threshold {
+            self.cooldown_until = Some(Instant::now() + self.policy.cooldown);
+            return FailureOutcome::CooldownStarted {
+                failures: self.streak,
+            };
+        }
+        FailureOutcome::Counted
+    }
+
+    /// Number of frames dropped without parsing so far
+    pub(crate) fn skipped(&self) -> u64 {
+        self.skipped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(threshold: u32, disconnect_after: u32) -> ParseFailurePolicy {
+        ParseFailurePolicy {
+            threshold,
+            cooldown: Duration::from_millis(500),
+            disconnect_after,
+        }
+    }
+
+    /// Feed `frames` garbage frames, returning how many were actually parsed
+    fn flood(guard: &mut ParseFailureGuard, frames: usize) -> usize {
+        let mut parsed = 0;
+        for _ in 0..frames {
+            if guard.check() == FrameVerdict::Parse {
+                parsed += 1;
+                guard.record_failure();
+            }
+        }
+        parsed
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_parse_attempts_stop_after_threshold() {
+        let mut guard = ParseFailureGuard::new(policy(5, 0));
+        assert_eq!(flood(&mut guard, 1000), 5);
+        assert_eq!(guard.skipped(), 995);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_parsing_resumes_after_cooldown() {
+        let mut guard = ParseFailureGuard::new(policy(3, 0));
+        assert_eq!(flood(&mut guard, 10), 3);
+
+        tokio::time::advance(Duration::from_millis(501)).await;
+        assert_eq!(guard.check(), FrameVerdict::Parse);
+
+        // Still failing: the streak continues and the cooldown restarts immediately
+        assert_eq!(
+            guard.record_failure(),
+            FailureOutcome::CooldownStarted { failures: 4 }
+        );
+        assert_eq!(guard.check(), FrameVerdict::Skip);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_valid_frame_resets_streak() {
+        let mut guard = ParseFailureGuard::new(policy(3, 0));
+        assert_eq!(guard.record_failure(), FailureOutcome::Counted);
+        assert_eq!(guard.record_failure(), FailureOutcome::Counted);
+        guard.record_success();
+        assert_eq!(guard.record_failure(), FailureOutcome::Counted);
+        assert_eq!(guard.check(), FrameVerdict::Parse);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_escalates_to_disconnect() {
+        let mut guard = ParseFailureGuard::new(policy(2, 4));
+        assert_eq!(flood(&mut guard, 2), 2);
+        tokio::time::advance(Duration::from_millis(501)).await;
+        assert_eq!(guard.check(), FrameVerdict::Parse);
+        assert_eq!(
+            guard.record_failure(),
+            FailureOutcome::CooldownStarted { failures: 3 }
+        );
+        tokio::time::advance(Duration::from_millis(501)).await;
+        assert_eq!(guard.check(), FrameVerdict::Parse);
+        assert_eq!(
+            guard.record_failure(),
+            FailureOutcome::Disconnect { failures: 4 }
+        );
+    }
+
+    #[test]
+    fn test_disabled_policy_never_skips() {
+        let mut guard = ParseFailureGuard::new(policy(0, 0));
+        assert_eq!(flood(&mut guard, 100), 100);
+    }
+
+    #[test]
+    fn test_failure_reasons() {
+        let json_err = |text: &str| -> anyhow::Error {
+            serde_json::from_str::<serde_json::Value>(text)
+                .unwrap_err()
+                .into()
+        };
+        assert_eq!(
+            ParseFailureReason::of(&json_err(r#"{"subject":"\ud800"}"#)),
+            ParseFailureReason::InvalidUtf8
+        );
+        assert_eq!(
+            ParseFailureReason::of(&json_err(r#"{"subject":"\udc00x"}"#)),
+            ParseFailureReason::InvalidUtf8
+        );
+        assert_eq!(
+            ParseFailureReason::of(&json_err("{not json")),
+            ParseFailureReason::InvalidJson
+        );
+        let subject_err = anyhow::Error::from(InvalidSubject {
+            subject: "a\u{0}".to_string(),
+        });
+        assert_eq!(
+            ParseFailureReason::of(&subject_err),
+            ParseFailureReason::InvalidSubject
+        );
+        assert_eq!(
+            ParseFailureReason::of(&anyhow::anyhow!("bad body")),
+            ParseFailureReason::InvalidBody
+        );
+    }
+}
@@ -0,0 +1,300 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Result};
+use tracing::{debug, warn};
+
+use crate::compression;
+use crate::connection::ConnectionConfig;
+use crate::drop_notify::MSG_ID_HEADER;
+use crate::BrokerMessage;
+
+/// Decompresses bodies marked with a `content-encoding`
+pub const DECODE_STAGE: &str = "decode";
+/// Trims the subject and drops empty tokens (`" a..b. "` becomes `"a.b"`)
+pub const NORMALIZE_STAGE: &str = "normalize";
+/// Drops messages whose subject is empty, contains whitespace or uses a wildcard token
+pub const VALIDATE_STAGE: &str = "validate";
+/// Drops messages repeating the subject and `msg-id` of one seen within `DEDUP_WINDOW`
+pub const DEDUP_STAGE: &str = "dedup";
+
+/// Message ids the dedup stage remembers at most
+const DEDUP_CAPACITY: usize = 10_000;
+
+/// One step of the inbound pipeline, run on every message before it is routed
+///
+/// Plain `Fn(&str, BrokerMessage) -> Result<Option<BrokerMessage>>` closures are stages
+/// too. Returning `Ok(None)` drops the message quietly; an error drops it with a warning.
+pub trait Stage: Send + Sync + 'static {
+    /// Process one message received on the session with the given id
+    fn process(&self, session_id: &str, msg: BrokerMessage) -> Result<Option<BrokerMessage>>;
+}
+
+impl<F> Stage for F
+where
+    F: Fn(&str, BrokerMessage) -> Result<Option<BrokerMessage>> + Send + Sync + 'static,
+{
+    fn process(&self, session_id: &str, msg: BrokerMessage) -> Result<Option<BrokerMessage>> {
+        self(session_id, msg)
+    }
+}
+
+/// A stage added with `with_inbound_stage`, by name
+pub(crate) type NamedStage = (String, Arc<dyn Stage>);
+
+/// The ordered stages an inbound message passes through between parsing and routing
+///
+/// `INBOUND_STAGES` lists the stages that run, in order, by name: the built-in `decode`,
+/// `normalize`, `validate` and `dedup`, or a custom stage. Without it, `decode` runs
+/// followed by the custom stages in the order they were added.
+#[derive(Clone)]
+pub(crate) struct Pipeline {
+    stages: Vec<NamedStage>,
+}
+
+impl Pipeline {
+    /// Build the pipeline `config` asks for out of the built-in and `custom` stages
+    pub(crate) fn new(config: &ConnectionConfig, custom: &[NamedStage]) -> Self {
+        let names: Vec<String> = if config.inbound_stages.is_empty() {
+            std::iter::once(DECODE_STAGE.to_string())
+                .chain(custom.iter().map(|(name, _)| name.clone()))
+                .collect()
+        } else {
+            config.inbound_stages.clone()
+        };
+
+        let mut stages = Vec::with_capacity(names.len());
+        for name in names {
+            let stage: Arc<dyn Stage> = match name.as_str() {
+                DECODE_STAGE => Arc::new(DecodeStage {
+                    max_decompressed_bytes: config.max_decompressed_bytes,
+                }),
+                NORMALIZE_STAGE => Arc::new(NormalizeStage),
+                VALIDATE_STAGE => Arc::new(ValidateStage),
+                DEDUP_STAGE => Arc::new(DedupStage::new(config.dedup_window)),
+                _ => match custom.iter().find(|(custom_name, _)| *custom_name == name) {
+                    Some((_, stage)) => Arc::clone(stage),
+                    None => {
+                        warn!("INBOUND_STAGES names unknown stage {}, skipping it", name);
+                        continue;
+                    }
+                },
+            };
+            stages.push((name, stage));
+        }
+        Self { stages }
+    }
+
+    /// Names of the stages that run, in order
+    #[cfg(test)]
+    pub(crate) fn names(&self) -> Vec<&str> {
+        self.stages.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    /// Pass `msg` through every stage, returning it unless a stage dropped it
+    pub(crate) fn run(&self, session_id: &str, mut msg: BrokerMessage) -> Option<BrokerMessage> {
+        for (name, stage) in &self.stages {
+            msg = match stage.process(session_id, msg) {
+                Ok(Some(msg)) => msg,
+                Ok(None) => {
+                    debug!(
+                        "Inbound stage {} dropped a message from {}",
+                        name, session_id
+                    );
+                    return None;
+                }
+                Err(e) => {
+                    warn!(
+                        "Inbound stage {} dropped a message from {}: {}",
+                        name, session_id, e
+                    );
+                    return None;
+                }
+            };
+        }
+        Some(msg)
+    }
+}
+
+struct DecodeStage {
+    max_decompressed_bytes: usize,
+}
+
+impl Stage for DecodeStage {
+    fn process(&self, _session_id: &str, msg: BrokerMessage) -> Result<Option<BrokerMessage>> {
+        compression::decompress(msg, self.max_decompressed_bytes).map(Some)
+    }
+}
+
+struct NormalizeStage;
+
+impl Stage for NormalizeStage {
+    fn process(&self, _session_id: &str, mut msg: BrokerMessage) -> Result<Option<BrokerMessage>> {
+        msg.subject = msg
+            .subject
+            .split('.')
+            .map(str::trim)
+            .filter(|token| !token.is_empty())
+            .collect::<Vec<_>>()
+            .join(".");
+        Ok(Some(msg))
+    }
+}
+
+struct ValidateStage;
+
+impl Stage for ValidateStage {
+    fn process(&self, _session_id: &str, msg: BrokerMessage) -> Result<Option<BrokerMessage>> {
+        if msg.subject.is_empty() {
+            bail!("Empty subject");
+        }
+        if msg.subject.chars().any(char::is_whitespace) {
+            bail!("Subject {:?} contains whitespace", msg.subject);
+        }
+        if msg
+            .subject
+            .split('.')
+            .any(|token| token == "*" || token == ">")
+        {
+            bail!("Subject {} contains a wildcard", msg.subject);
+        }
+        Ok(Some(msg))
+    }
+}
+
+struct DedupStage {
+    window: Duration,
+    seen: Mutex<SeenIds>,
+}
+
+#[derive(Default)]
+struct SeenIds {
+    /// When each subject and message id was first seen
+    at: HashMap<(String, String), Instant>,
+    /// The same keys, oldest first
+    order: VecDeque<(String, String)>,
+}
+
+impl DedupStage {
+    fn new(window: Duration) -> Self {
+        Self {
+            window,
+            seen: Mutex::default(),
+        }
+    }
+}
+
+impl Stage for DedupStage {
+    fn process(&self, _session_id: &str, msg: BrokerMessage) -> Result<Option<BrokerMessage>> {
+        let Some(msg_id) = msg.headers.get(MSG_ID_HEADER) else {
+            return Ok(Some(msg));
+        };
+        let key = (msg.subject.clone(), msg_id.clone());
+        let now = Instant::now();
+        let mut seen = self.seen.lock().unwrap();
+
+        while let Some(oldest) = seen.order.front() {
+            let expired = seen
+                .at
+                .get(oldest)
+                .is_none_or(|at| now.duration_since(*at) >= self.window);
+            if !expired && seen.order.len() < DEDUP_CAPACITY {
+                break;
+            }
+            if let Some(oldest) = seen.order.pop_front() {
+                seen.at.remove(&oldest);
+            }
+        }
+
+        if seen.at.contains_key(&key) {
+            return Ok(None);
+        }
+        seen.at.insert(key.clone(), now);
+        seen.order.push_back(key);
+        Ok(Some(msg))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+
+    fn config(stages: &str) -> ConnectionConfig {
+        let map = HashMap::from([("INBOUND_STAGES".to_string(), stages.to_string())]);
+        ConnectionConfig::from_map(&map).unwrap()
+    }
+
+    fn message(subject: &str, msg_id: &str) -> BrokerMessage {
+        BrokerMessage {
+            subject: subject.to_string(),
+            body: Bytes::from("x"),
+            reply_to: None,
+            headers: HashMap::from([(MSG_ID_HEADER.to_string(), msg_id.to_string())]),
+        }
+    }
+
+    fn subjects(pipeline: &Pipeline, msgs: &[BrokerMessage]) -> Vec<String> {
+        msgs.iter()
+            .filter_map(|msg| pipeline.run("s", msg.clone()))
+            .map(|msg| msg.subject)
+            .collect()
+    }
+
+    #[test]
+    fn test_default_pipeline() {
+        let custom: Vec<NamedStage> = vec![("audit".to_string(), Arc::new(NormalizeStage))];
+        let pipeline = Pipeline::new(&ConnectionConfig::default(), &custom);
+        assert_eq!(pipeline.names(), vec!["decode", "audit"]);
+    }
+
+    #[test]
+    fn test_normalize_before_dedup() {
+        let msgs = [
+            message("orders.created", "1"),
+            message(" orders..created", "1"),
+        ];
+
+        let pipeline = Pipeline::new(&config("normalize,dedup"), &[]);
+        assert_eq!(subjects(&pipeline, &msgs), vec!["orders.created"]);
+
+        // Deduplicating first compares the raw subjects, so both get through
+        let pipeline = Pipeline::new(&config("dedup,normalize"), &[]);
+        assert_eq!(
+            subjects(&pipeline, &msgs),
+            vec!["orders.created", "orders.created"]
+        );
+    }
+
+    #[test]
+    fn test_unlisted_stage_skipped() {
+        let msgs = [message("a..b", "1"), message("c.*", "2")];
+
+        let pipeline = Pipeline::new(&config("normalize,validate"), &[]);
+        assert_eq!(subjects(&pipeline, &msgs), vec!["a.b"]);
+
+        let pipeline = Pipeline::new(&config("validate"), &[]);
+        assert_eq!(subjects(&pipeline, &msgs), vec!["a..b"]);
+
+        let pipeline = Pipeline::new(&config("decode,unknown"), &[]);
+        assert_eq!(pipeline.names(), vec!["decode"]);
+    }
+
+    #[test]
+    fn test_custom_stage_position() {
+        let label = |_: &str, mut msg: BrokerMessage| -> Result<Option<BrokerMessage>> {
+            msg.subject.push_str(" (relayed)");
+            Ok(Some(msg))
+        };
+        let custom: Vec<NamedStage> = vec![("label".to_string(), Arc::new(label))];
+        let msgs = [message("a", "1")];
+
+        let pipeline = Pipeline::new(&config("validate,label"), &custom);
+        assert_eq!(subjects(&pipeline, &msgs), vec!["a (relayed)"]);
+
+        // Validated after labelling, the subject now has whitespace
+        let pipeline = Pipeline::new(&config("label,validate"), &custom);
+        assert!(subjects(&pipeline, &msgs).is_empty());
+    }
+}
@@ -0,0 +1,83 @@
+//! OS-specific behaviour, kept behind `cfg` gates so unsupported features fail at config
+//! time with a clear message instead of at runtime
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+
+/// URI scheme of Unix domain socket endpoints
+const UNIX_SCHEME: &str = "unix://";
+
+/// Reject URIs this build cannot dial or listen on
+pub(crate) fn check_uri(uri: &str) -> Result<()> {
+    if uri.starts_with(UNIX_SCHEME) {
+        #[cfg(windows)]
+        bail!(
+            "URI {} uses unix://, which is not supported on this platform; use ws://, wss:// or host:port",
+            uri
+        );
+        #[cfg(not(windows))]
+        bail!(
+            "URI {} uses unix://, which is not supported yet; use ws://, wss:// or host:port",
+            uri
+        );
+    }
+    Ok(())
+}
+
+/// Enable TCP keepalive on a connected socket, probing after `idle` without traffic
+pub(crate) fn set_tcp_keepalive(stream: &tokio::net::TcpStream, idle: Duration) -> Result<()> {
+    let keepalive = socket2::TcpKeepalive::new().with_time(idle);
+    socket2::SockRef::from(stream).set_tcp_keepalive(&keepalive)?;
+    Ok(())
+}
+
+/// Bind a listening socket on `addr`, with `SO_REUSEADDR` set if `reuse_addr`
+///
+/// Without `reuse_addr` this is a plain tokio bind, which already sets the option on Unix;
+/// setting it explicitly matters on Windows, where it is off by default.
+pub(crate) async fn bind_listener(
+    addr: SocketAddr,
+    reuse_addr: bool,
+) -> std::io::Result<tokio::net::TcpListener> {
+    if !reuse_addr {
+        return tokio::net::TcpListener::bind(addr).await;
+    }
+    let socket = socket2::Socket::new(
+        socket2::Domain::for_address(addr),
+        socket2::Type::STREAM,
+        Some(socket2::Protocol::TCP),
+    )?;
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+    tokio::net::TcpListener::from_std(socket.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_network_uris_accepted() {
+        assert!(check_uri("ws://localhost:8080").is_ok());
+        assert!(check_uri("wss://example.com/ws").is_ok());
+        assert!(check_uri("0.0.0.0:9090").is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unix_uri_rejected_on_unix() {
+        let err = check_uri("unix:///tmp/ws.sock").unwrap_err().to_string();
+        assert!(err.contains("not supported yet"), "{}", err);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_unix_uri_rejected_on_windows() {
+        let err = check_uri("unix:///tmp/ws.sock").unwrap_err().to_string();
+        assert!(err.contains("not supported on this platform"), "{}", err);
+    }
+}
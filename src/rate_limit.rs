@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+
+const WINDOW: Duration = Duration::from_secs(1);
+
+/// What happens to inbound server messages beyond `SERVER_MAX_MESSAGES_PER_SEC`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServerRatePolicy {
+    /// Drop the message; each sending session gets an equal share of the second
+    #[default]
+    Shed,
+    /// Hold the sending session until the message's turn comes up, first come first served
+    Queue,
+}
+
+impl ServerRatePolicy {
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        match value {
+            "shed" => Some(Self::Shed),
+            "queue" => Some(Self::Queue),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Shed => "shed",
+            Self::Queue => "queue",
+        }
+    }
+}
+
+/// Messages admitted in the current one-second window, by session
+#[derive(Debug)]
+struct RateWindow {
+    started: Instant,
+    admitted: u64,
+    per_session: HashMap<String, u64>,
+    /// Sessions that sent during the previous window, so a busy sender can't take the
+    /// whole second before the others show up
+    previous_senders: usize,
+}
+
+/// Caps how many messages from all server clients together reach the message sink
+#[derive(Debug)]
+pub(crate) struct ServerRateLimiter {
+    /// Messages per second (0 = no limit)
+    rate: u64,
+    policy: ServerRatePolicy,
+    window: Mutex<RateWindow>,
+    /// When the next queued message may go through
+    next_slot: tokio::sync::Mutex<Instant>,
+    /// Messages shed or held back so far
+    limited: AtomicU64,
+}
+
+impl ServerRateLimiter {
+    pub(crate) fn new(rate: u64, policy: ServerRatePolicy) -> Self {
+        let now = Instant::now();
+        Self {
+            rate,
+            policy,
+            window: Mutex::new(RateWindow {
+                started: now,
+                admitted: 0,
+                per_session: HashMap::new(),
+                previous_senders: 0,
+            }),
+            next_slot: tokio::sync::Mutex::new(now),
+            limited: AtomicU64::new(0),
+        }
+    }
+
+    /// Messages shed or held back so far (`server_rate_limited_total`)
+    pub(crate) fn limited_count(&self) -> u64 {
+        self.limited.load(Ordering::Relaxed)
+    }
+
+    /// Whether a message from `session_id` may be delivered, waiting for its turn under
+    /// `Queue`
+    pub(crate) async fn admit(&self, session_id: &str) -> bool {
+        if self.rate == 0 {
+            return true;
+        }
+        match self.policy {
+            ServerRatePolicy::Shed => self.admit_share(session_id),
+            ServerRatePolicy::Queue => {
+                self.wait_turn().await;
+                true
+            }
+        }
+    }
+
+    /// Admit the message if the second has room left and the session hasn't used up its
+    /// share of it
+    fn admit_share(&self, session_id: &str) -> bool {
+        let now = Instant::now();
+        let mut window = self.window.lock().unwrap();
+        if now.duration_since(window.started) >= WINDOW {
+            window.previous_senders = window.per_session.len();
+            window.started = now;
+            window.admitted = 0;
+            window.per_session.clear();
+        }
+        let senders =
+            window.per_session.len() + usize::from(!window.per_session.contains_key(session_id));
+        let senders = senders.max(window.previous_senders) as u64;
+        let share = self.rate.div_ceil(senders);
+        let sent = window.per_session.get(session_id).copied().unwrap_or(0);
+        if window.admitted >= self.rate || sent >= share {
+            self.limited.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        window.admitted += 1;
+        *window
+            .per_session
+            .entry(session_id.to_string())
+            .or_default() += 1;
+        true
+    }
+
+    /// Wait for the next free slot; slots are handed out in arrival order, one every
+    /// 1/rate seconds
+    async fn wait_turn(&self) {
+        let now = Instant::now();
+        let slot = {
+            let mut next_slot = self.next_slot.lock().await;
+            let slot = (*next_slot).max(now);
+            *next_slot = slot + Duration::from_nanos(1_000_000_000 / self.rate);
+            slot
+        };
+        if slot > now {
+            self.limited.fetch_add(1, Ordering::Relaxed);
+            tokio::time::sleep_until(slot).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_shed_splits_second_between_senders() {
+        let limiter = ServerRateLimiter::new(10, ServerRatePolicy::Shed);
+        // Alone, a session may use the whole second
+        let admitted = (0..15).filter(|_| limiter.admit_share("a")).count();
+        assert_eq!(admitted, 10);
+        assert_eq!(limiter.limited_count(), 5);
+
+        // Once two sessions sent, each gets half of the next second
+        tokio::time::advance(WINDOW).await;
+        assert!(limiter.admit_share("a"));
+        assert!(limiter.admit_share("b"));
+        tokio::time::advance(WINDOW).await;
+        let a = (0..10).filter(|_| limiter.admit_share("a")).count();
+        let b = (0..10).filter(|_| limiter.admit_share("b")).count();
+        assert_eq!((a, b), (5, 5));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_queue_paces_messages() {
+        let limiter = ServerRateLimiter::new(4, ServerRatePolicy::Queue);
+        let start = Instant::now();
+        for _ in 0..4 {
+            assert!(limiter.admit("a").await);
+        }
+        assert_eq!(Instant::now() - start, Duration::from_millis(750));
+        assert_eq!(limiter.limited_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_unlimited() {
+        let limiter = ServerRateLimiter::new(0, ServerRatePolicy::Shed);
+        for _ in 0..1_000 {
+            assert!(limiter.admit("a").await);
+        }
+        assert_eq!(limiter.limited_count(), 0);
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use tracing::{debug, warn};
+
+/// Result of a reconciliation pass over the provider's connection state
+///
+/// The first three categories are repaired by the pass itself; the rest are only reported.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConsistencyReport {
+    /// Session entries whose component has no connection; removed
+    pub orphan_sessions: Vec<String>,
+    /// Server clients whose connection has ended but which were still registered; removed
+    pub stale_clients: Vec<String>,
+    /// Group memberships, as `(session_id, group)`, of sessions no longer connected; removed
+    pub orphan_memberships: Vec<(String, String)>,
+    /// Linked components whose connection task has ended
+    pub dead_connections: Vec<String>,
+    /// Linked components with session tracking but no session entry
+    pub untracked_connections: Vec<String>,
+    /// Server queue bytes charged to sessions that disagree with the memory budget, as
+    /// `"sessions <n> bytes, budget <m> bytes"`
+    pub buffer_accounting: Vec<String>,
+}
+
+impl ConsistencyReport {
+    /// Whether the pass found nothing wrong
+    pub fn is_consistent(&self) -> bool {
+        self.total() == 0
+    }
+
+    /// Number of inconsistencies found, repaired or not
+    pub fn total(&self) -> usize {
+        self.orphan_sessions.len()
+            + self.stale_clients.len()
+            + self.orphan_memberships.len()
+            + self.dead_connections.len()
+            + self.untracked_connections.len()
+            + self.buffer_accounting.len()
+    }
+
+    fn sort(&mut self) {
+        self.orphan_sessions.sort();
+        self.stale_clients.sort();
+        self.orphan_memberships.sort();
+        self.dead_connections.sort();
+        self.untracked_connections.sort();
+    }
+}
+
+/// Entries that can briefly look inconsistent while a connection is set up or torn down
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Suspect {
+    /// A session is registered just before its component's connection is stored
+    OrphanSession,
+    /// A server client is unregistered just after its connection ends
+    StaleClient,
+    /// A frame is charged to its session just before the memory budget
+    BufferAccounting,
+}
+
+/// Remembers suspects between passes and counts what the passes found
+#[derive(Debug, Default)]
+pub(crate) struct Reconciler {
+    suspects: Mutex<HashSet<(Suspect, String)>>,
+    inconsistencies: AtomicU64,
+}
+
+impl Reconciler {
+    /// The entries of `found` that were already suspects in the previous pass
+    ///
+    /// Only those are repaired, so state that is merely in transition is left alone; the
+    /// rest become this pass's suspects.
+    pub(crate) fn confirm(&self, kind: Suspect, found: Vec<String>) -> Vec<String> {
+        let mut suspects = self.suspects.lock().unwrap();
+        let (confirmed, pending): (Vec<_>, Vec<_>) = found
+            .into_iter()
+            .partition(|id| suspects.contains(&(kind, id.clone())));
+        suspects.retain(|(suspect_kind, _)| *suspect_kind != kind);
+        suspects.extend(pending.into_iter().map(|id| (kind, id)));
+        confirmed
+    }
+
+    /// Sort the report, count and log what it found
+    pub(crate) fn finish(&self, mut report: ConsistencyReport) -> ConsistencyReport {
+        report.sort();
+        let total = report.total();
+        if total == 0 {
+            debug!("State reconciliation found no inconsistencies");
+            return report;
+        }
+        self.inconsistencies
+            .fetch_add(total as u64, Ordering::Relaxed);
+        if !report.orphan_sessions.is_empty() {
+            warn!("Removed orphan sessions: {:?}", report.orphan_sessions);
+        }
+        if !report.stale_clients.is_empty() {
+            warn!("Removed stale server clients: {:?}", report.stale_clients);
+        }
+        if !report.orphan_memberships.is_empty() {
+            warn!(
+                "Removed group memberships of disconnected sessions: {:?}",
+                report.orphan_memberships
+            );
+        }
+        if !report.dead_connections.is_empty() {
+            warn!(
+                "Components linked to ended connections, relink needed: {:?}",
+                report.dead_connections
+            );
+        }
+        if !report.untracked_connections.is_empty() {
+            warn!(
+                "Components with session tracking but no session: {:?}",
+                report.untracked_connections
+            );
+        }
+        if !report.buffer_accounting.is_empty() {
+            warn!(
+                "Per-session buffer accounting disagrees with the memory budget: {:?}",
+                report.buffer_accounting
+            );
+        }
+        report
+    }
+
+    /// Total inconsistencies found by all passes so far
+    pub(crate) fn inconsistencies_total(&self) -> u64 {
+        self.inconsistencies.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_suspects_confirmed_on_second_pass() {
+        let reconciler = Reconciler::default();
+        let found = vec!["a".to_string(), "b".to_string()];
+        assert!(reconciler
+            .confirm(Suspect::OrphanSession, found.clone())
+            .is_empty());
+        // "b" resolved itself in between
+        assert_eq!(
+            reconciler.confirm(Suspect::OrphanSession, vec!["a".to_string()]),
+            vec!["a"]
+        );
+        assert!(reconciler
+            .confirm(Suspect::OrphanSession, vec!["b".to_string()])
+            .is_empty());
+        // Kinds are tracked separately
+        assert!(reconciler
+            .confirm(Suspect::StaleClient, vec!["b".to_string()])
+            .is_empty());
+    }
+}
@@ -0,0 +1,220 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use tokio::time::Instant;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{debug, warn};
+
+use crate::connection::ConnectionConfig;
+
+/// Longest wait between two redial attempts
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+/// How a client connection that dropped is redialled (`RECONNECT`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ReconnectPolicy {
+    /// Attempts per outage before giving up, 0 for no limit
+    max_retries: u32,
+    base_delay: Duration,
+}
+
+impl ReconnectPolicy {
+    /// `None` unless `RECONNECT` is set
+    pub(crate) fn from_config(config: &ConnectionConfig) -> Option<Self> {
+        config.reconnect.then(|| Self {
+            max_retries: config.reconnect_max_retries,
+            base_delay: config.reconnect_base_delay,
+        })
+    }
+
+    /// Wait before attempt `attempt` (from 0): the base delay doubled per earlier
+    /// attempt, up to `MAX_RECONNECT_DELAY`
+    fn delay(&self, attempt: u32) -> Duration {
+        self.base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(MAX_RECONNECT_DELAY)
+    }
+
+    /// Run `dial` with backoff until it succeeds, or `None` once the attempts run out
+    ///
+    /// `dial` gets the 1-based number of the attempt.
+    pub(crate) async fn retry<T, F, Fut>(&self, what: &str, mut dial: F) -> Option<T>
+    where
+        F: FnMut(u32) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempt = 0;
+        while self.max_retries == 0 || attempt < self.max_retries {
+            let delay = self.delay(attempt);
+            attempt += 1;
+            warn!("Reconnecting {} in {:?} (attempt {})", what, delay, attempt);
+            tokio::time::sleep(delay).await;
+            match dial(attempt).await {
+                Ok(connected) => return Some(connected),
+                Err(e) => warn!("Reconnect attempt {} for {} failed: {:#}", attempt, what, e),
+            }
+        }
+        None
+    }
+}
+
+/// A frame on a client connection's outbound queue, stamped with when it was queued
+#[derive(Debug)]
+pub(crate) struct QueuedFrame {
+    pub(crate) frame: Message,
+    pub(crate) queued_at: Instant,
+}
+
+impl From<Message> for QueuedFrame {
+    fn from(frame: Message) -> Self {
+        Self {
+            frame,
+            queued_at: Instant::now(),
+        }
+    }
+}
+
+/// Drops the frames that waited out an outage for longer than `RECONNECT_BUFFER_MAX_AGE`
+/// instead of writing them on the redialled connection, counting them
+#[derive(Debug)]
+pub(crate) struct ReconnectBuffer {
+    max_age: Duration,
+    /// When the connection was last redialled, until a frame queued since comes up
+    reconnected_at: Option<Instant>,
+    stale: Arc<AtomicU64>,
+}
+
+impl ReconnectBuffer {
+    pub(crate) fn new(config: &ConnectionConfig, stale: Arc<AtomicU64>) -> Self {
+        Self {
+            max_age: config.reconnect_buffer_max_age,
+            reconnected_at: None,
+            stale,
+        }
+    }
+
+    /// The connection has been redialled; frames queued until now are checked for age
+    pub(crate) fn reconnected(&mut self) {
+        if !self.max_age.is_zero() {
+            self.reconnected_at = Some(Instant::now());
+        }
+    }
+
+    /// The frame to write, or `None` if it was queued before the redial and has grown
+    /// too old since
+    ///
+    /// Control frames are always written. The queue is in order, so once a frame queued
+    /// after the redial comes up no older one is left.
+    pub(crate) fn admit(&mut self, queued: QueuedFrame) -> Option<Message> {
+        let Some(reconnected_at) = self.reconnected_at else {
+            return Some(queued.frame);
+        };
+        if queued.queued_at >= reconnected_at {
+            self.reconnected_at = None;
+            return Some(queued.frame);
+        }
+        let data = matches!(queued.frame, Message::Text(_) | Message::Binary(_));
+        if data && queued.queued_at.elapsed() > self.max_age {
+            self.stale.fetch_add(1, Ordering::Relaxed);
+            debug!(
+                "Dropped frame queued {:?} ago, before the reconnect",
+                queued.queued_at.elapsed()
+            );
+            return None;
+        }
+        Some(queued.frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::bail;
+    use std::collections::HashMap;
+
+    fn policy(entries: &[(&str, &str)]) -> Option<ReconnectPolicy> {
+        let map: HashMap<String, String> = entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        ReconnectPolicy::from_config(&ConnectionConfig::from_map(&map).unwrap())
+    }
+
+    #[test]
+    fn test_off_by_default() {
+        assert!(policy(&[]).is_none());
+        assert!(policy(&[("RECONNECT_MAX_RETRIES", "3")]).is_none());
+    }
+
+    #[test]
+    fn test_delay_doubles_up_to_cap() {
+        let policy = policy(&[("RECONNECT", "true"), ("RECONNECT_BASE_DELAY_MS", "100")]).unwrap();
+        assert_eq!(policy.delay(0), Duration::from_millis(100));
+        assert_eq!(policy.delay(1), Duration::from_millis(200));
+        assert_eq!(policy.delay(3), Duration::from_millis(800));
+        assert_eq!(policy.delay(40), MAX_RECONNECT_DELAY);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_stops_at_max_retries() {
+        let policy = policy(&[("RECONNECT", "true"), ("RECONNECT_MAX_RETRIES", "3")]).unwrap();
+        let mut attempts = Vec::new();
+        let connected: Option<()> = policy
+            .retry("test", |attempt| {
+                attempts.push(attempt);
+                async { bail!("refused") }
+            })
+            .await;
+        assert!(connected.is_none());
+        assert_eq!(attempts, vec![1, 2, 3]);
+
+        let connected = policy
+            .retry("test", |attempt| async move {
+                if attempt < 2 {
+                    bail!("refused");
+                }
+                Ok(attempt)
+            })
+            .await;
+        assert_eq!(connected, Some(2));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_reconnect_buffer_drops_only_stale_frames() {
+        let map = HashMap::from([(
+            "RECONNECT_BUFFER_MAX_AGE_MS".to_string(),
+            "1000".to_string(),
+        )]);
+        let stale = Arc::new(AtomicU64::new(0));
+        let mut buffer = ReconnectBuffer::new(
+            &ConnectionConfig::from_map(&map).unwrap(),
+            Arc::clone(&stale),
+        );
+        let text = |body: &str| QueuedFrame::from(Message::Text(body.to_string()));
+
+        // Nothing is checked before a reconnect
+        let early = text("early");
+        tokio::time::advance(Duration::from_secs(2)).await;
+        assert!(buffer.admit(early).is_some());
+
+        let old = text("old");
+        let close = QueuedFrame::from(Message::Close(None));
+        tokio::time::advance(Duration::from_millis(1500)).await;
+        let recent = text("recent");
+        tokio::time::advance(Duration::from_millis(100)).await;
+        buffer.reconnected();
+        let after = text("after");
+        let late = text("late");
+
+        assert!(buffer.admit(old).is_none());
+        assert!(buffer.admit(close).is_some());
+        assert!(buffer.admit(recent).is_some());
+        assert!(buffer.admit(after).is_some());
+        tokio::time::advance(Duration::from_secs(2)).await;
+        assert!(buffer.admit(late).is_some());
+        assert_eq!(stale.load(Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::subject::matches_any;
+use crate::BrokerMessage;
+
+/// Header set on retained messages when they are replayed to a new subscriber
+pub(crate) const RETAINED_HEADER: &str = "retained";
+
+/// Last-value cache of messages for subjects configured for retention
+///
+/// Only subjects matching one of `patterns` are retained. Each subject keeps its most
+/// recent message until it expires (`ttl`), is cleared by an empty-body message (when
+/// `clear_on_empty` is set) or is evicted to keep the cache within `max_entries`.
+#[derive(Debug, Default)]
+pub(crate) struct RetainedStore {
+    patterns: Vec<String>,
+    ttl: Option<Duration>,
+    clear_on_empty: bool,
+    max_entries: usize,
+    entries: HashMap<String, (BrokerMessage, Instant)>,
+}
+
+impl RetainedStore {
+    pub(crate) fn new(
+        patterns: Vec<String>,
+        ttl: Option<Duration>,
+        clear_on_empty: bool,
+        max_entries: usize,
+    ) -> Self {
+        Self {
+            patterns,
+            ttl,
+            clear_on_empty,
+            max_entries,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Whether any subject is configured for retention
+    pub(crate) fn is_enabled(&self) -> bool {
+        !self.patterns.is_empty()
+    }
+
+    /// Record a published message if its subject is retained
+    pub(crate) fn retain(&mut self, msg: &BrokerMessage) {
+        if !matches_any(&self.patterns, &msg.subject) {
+            return;
+        }
+
+        if self.clear_on_empty && msg.body.is_empty() {
+            self.entries.remove(&msg.subject);
+            return;
+        }
+
+        if !self.entries.contains_key(&msg.subject) && self.entries.len() >= self.max_entries {
+            self.evict_oldest();
+        }
+        if self.max_entries > 0 {
+            self.entries
+                .insert(msg.subject.clone(), (msg.clone(), Instant::now()));
+        }
+    }
+
+    /// Current retained messages, marked with the `retained` header, oldest first
+    pub(crate) fn snapshot(&mut self) -> Vec<BrokerMessage> {
+        self.purge_expired();
+
+        let mut retained: Vec<_> = self.entries.values().collect();
+        retained.sort_by_key(|(_, stored_at)| *stored_at);
+        retained
+            .into_iter()
+            .map(|(msg, _)| {
+                let mut msg = msg.clone();
+                msg.headers
+                    .insert(RETAINED_HEADER.to_string(), "true".to_string());
+                msg
+            })
+            .collect()
+    }
+
+    fn purge_expired(&mut self) {
+        if let Some(ttl) = self.ttl {
+            self.entries
+                .retain(|_, (_, stored_at)| stored_at.elapsed() < ttl);
+        }
+    }
+
+    fn evict_oldest(&mut self) {
+        let oldest = self
+            .entries
+            .iter()
+            .min_by_key(|(_, (_, stored_at))| *stored_at)
+            .map(|(subject, _)| subject.clone());
+        if let Some(subject) = oldest {
+            self.entries.remove(&subject);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+
+    fn message(subject: &str, body: &'static str) -> BrokerMessage {
+        BrokerMessage {
+            subject: subject.to_string(),
+            body: Bytes::from(body),
+            reply_to: None,
+            headers: HashMap::new(),
+        }
+    }
+
+    fn metrics_store(ttl: Option<Duration>, clear_on_empty: bool) -> RetainedStore {
+        RetainedStore::new(vec!["metrics.>".to_string()], ttl, clear_on_empty, 100)
+    }
+
+    #[test]
+    fn test_retains_latest_value_per_subject() {
+        let mut store = metrics_store(None, false);
+        store.retain(&message("metrics.cpu", "10"));
+        store.retain(&message("metrics.cpu", "20"));
+        store.retain(&message("other.subject", "ignored"));
+
+        let retained = store.snapshot();
+        assert_eq!(retained.len(), 1);
+        assert_eq!(retained[0].body, Bytes::from("20"));
+        assert_eq!(
+            retained[0].headers.get(RETAINED_HEADER),
+            Some(&"true".to_string())
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retained_entries_expire() {
+        let mut store = metrics_store(Some(Duration::from_secs(5)), false);
+        store.retain(&message("metrics.cpu", "10"));
+
+        tokio::time::advance(Duration::from_secs(4)).await;
+        assert_eq!(store.snapshot().len(), 1);
+
+        tokio::time::advance(Duration::from_secs(2)).await;
+        assert!(store.snapshot().is_empty());
+    }
+
+    #[test]
+    fn test_empty_body_clears_when_enabled() {
+        let mut store = metrics_store(None, true);
+        store.retain(&message("metrics.cpu", "10"));
+        store.retain(&message("metrics.cpu", ""));
+        assert!(store.snapshot().is_empty());
+
+        let mut store = metrics_store(None, false);
+        store.retain(&message("metrics.cpu", "10"));
+        store.retain(&message("metrics.cpu", ""));
+        let retained = store.snapshot();
+        assert_eq!(retained.len(), 1);
+        assert!(retained[0].body.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_bounded_evicts_oldest() {
+        let mut store = RetainedStore::new(vec!["metrics.>".to_string()], None, false, 2);
+        for (subject, body) in [("metrics.a", "1"), ("metrics.b", "2"), ("metrics.c", "3")] {
+            store.retain(&message(subject, body));
+            tokio::time::advance(Duration::from_millis(1)).await;
+        }
+
+        let subjects: Vec<_> = store.snapshot().into_iter().map(|m| m.subject).collect();
+        assert_eq!(subjects, vec!["metrics.b", "metrics.c"]);
+    }
+}
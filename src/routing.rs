@@ -0,0 +1,190 @@
+use serde::{Deserialize, Serialize};
+
+use crate::subject::matches_any;
+
+/// How an inbound message is delivered when several handler routes match its subject
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RouteDelivery {
+    /// Fan out to every matching handler
+    #[default]
+    All,
+    /// Deliver only to the highest-priority matching handler
+    First,
+}
+
+impl RouteDelivery {
+    /// Parse a `ROUTE_DELIVERY` value (`all` or `first`, case-insensitive)
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "all" => Some(Self::All),
+            "first" => Some(Self::First),
+            _ => None,
+        }
+    }
+}
+
+/// What happens to a message for a handler whose outbound queue is full
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum HandlerOverflow {
+    /// Skip that handler for this message and count the drop; others still receive it
+    #[default]
+    Drop,
+    /// Wait for room in every full queue (delivery to the other handlers is not held up,
+    /// but the receive loop is)
+    Wait,
+}
+
+impl HandlerOverflow {
+    /// Parse a `HANDLER_OVERFLOW` value (`drop` or `wait`, case-insensitive)
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "drop" => Some(Self::Drop),
+            "wait" => Some(Self::Wait),
+            _ => None,
+        }
+    }
+}
+
+/// A handler component's subscription as seen by the router
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Route<'a> {
+    pub component_id: &'a str,
+    pub priority: i32,
+    /// Subject patterns the handler subscribed to; empty means every subject
+    pub subscriptions: &'a [String],
+}
+
+impl Route<'_> {
+    fn matches(&self, subject: &str) -> bool {
+        self.subscriptions.is_empty() || matches_any(self.subscriptions, subject)
+    }
+}
+
+/// Order routes deterministically: highest priority first, ties broken by component id
+pub(crate) fn sort_routes(routes: &mut [Route<'_>]) {
+    routes.sort_by(|a, b| {
+        b.priority
+            .cmp(&a.priority)
+            .then_with(|| a.component_id.cmp(b.component_id))
+    });
+}
+
+/// Indices of the routes (already sorted) that should receive a message on `subject`
+pub(crate) fn select(routes: &[Route<'_>], subject: &str, delivery: RouteDelivery) -> Vec<usize> {
+    let mut matching = routes
+        .iter()
+        .enumerate()
+        .filter(|(_, route)| route.matches(subject))
+        .map(|(idx, _)| idx);
+
+    match delivery {
+        RouteDelivery::All => matching.collect(),
+        RouteDelivery::First => matching.next().into_iter().collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patterns(list: &[&str]) -> Vec<String> {
+        list.iter().map(|p| p.to_string()).collect()
+    }
+
+    fn selected<'a>(routes: &[Route<'a>], subject: &str, delivery: RouteDelivery) -> Vec<&'a str> {
+        select(routes, subject, delivery)
+            .into_iter()
+            .map(|idx| routes[idx].component_id)
+            .collect()
+    }
+
+    #[test]
+    fn test_first_delivers_to_highest_priority_match() {
+        let wide = patterns(&["orders.>"]);
+        let narrow = patterns(&["orders.*"]);
+        let other = patterns(&["billing.>"]);
+        let mut routes = vec![
+            Route {
+                component_id: "low",
+                priority: 1,
+                subscriptions: &narrow,
+            },
+            Route {
+                component_id: "high",
+                priority: 10,
+                subscriptions: &wide,
+            },
+            Route {
+                component_id: "unrelated",
+                priority: 100,
+                subscriptions: &other,
+            },
+        ];
+        sort_routes(&mut routes);
+
+        assert_eq!(
+            selected(&routes, "orders.new", RouteDelivery::First),
+            vec!["high"]
+        );
+        // Only the wide pattern matches deeper subjects
+        assert_eq!(
+            selected(&routes, "orders.new.eu", RouteDelivery::First),
+            vec!["high"]
+        );
+        assert!(selected(&routes, "inventory.new", RouteDelivery::First).is_empty());
+    }
+
+    #[test]
+    fn test_all_reaches_every_match_in_order() {
+        let wide = patterns(&["orders.>"]);
+        let everything: Vec<String> = Vec::new();
+        let mut routes = vec![
+            Route {
+                component_id: "b",
+                priority: 0,
+                subscriptions: &wide,
+            },
+            Route {
+                component_id: "catch-all",
+                priority: -1,
+                subscriptions: &everything,
+            },
+            Route {
+                component_id: "a",
+                priority: 0,
+                subscriptions: &wide,
+            },
+            Route {
+                component_id: "top",
+                priority: 5,
+                subscriptions: &wide,
+            },
+        ];
+        sort_routes(&mut routes);
+
+        assert_eq!(
+            selected(&routes, "orders.new", RouteDelivery::All),
+            vec!["top", "a", "b", "catch-all"]
+        );
+        assert_eq!(
+            selected(&routes, "inventory.new", RouteDelivery::All),
+            vec!["catch-all"]
+        );
+    }
+
+    #[test]
+    fn test_parse_overflow() {
+        assert_eq!(HandlerOverflow::parse("Drop"), Some(HandlerOverflow::Drop));
+        assert_eq!(HandlerOverflow::parse("wait"), Some(HandlerOverflow::Wait));
+        assert_eq!(HandlerOverflow::parse("block"), None);
+    }
+
+    #[test]
+    fn test_parse_delivery() {
+        assert_eq!(RouteDelivery::parse("FIRST"), Some(RouteDelivery::First));
+        assert_eq!(RouteDelivery::parse("all"), Some(RouteDelivery::All));
+        assert_eq!(RouteDelivery::parse("some"), None);
+    }
+}
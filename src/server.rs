@@ -1,32 +1,84 @@
 use std::collections::HashMap;
+use std::future::IntoFuture;
 use std::net::SocketAddr;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use anyhow::{Context, Result};
 use axum::{
     extract::{
         ws::{Message, WebSocket},
-        State, WebSocketUpgrade,
+        ConnectInfo, Query, Request, State, WebSocketUpgrade,
     },
-    response::Response,
+    http::{header, HeaderMap, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
     routing::get,
-    Router,
+    Json, Router,
 };
+use axum_server::tls_rustls::RustlsConfig;
 use bytes::Bytes;
+use futures::future::BoxFuture;
 use futures::{SinkExt, StreamExt};
-use tokio::sync::{mpsc, RwLock};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{oneshot, RwLock};
 use tokio::task::JoinHandle;
-use tracing::{debug, error, info, warn};
+use tower_http::limit::RequestBodyLimitLayer;
+use tracing::{debug, debug_span, error, info, warn, Instrument, Span};
 use uuid::Uuid;
 
-use crate::{BrokerMessage, SessionInfo};
+use crate::body_encoding::{self, BodyDecoding};
+use crate::body_stream::{self, BodyStreams};
+use crate::budget::{self, ClientSender, MemoryBudget, SessionLedger, SessionMemory};
+use crate::bulk::{BatchReport, SendOutcome, SEND_BATCH_CHUNK};
+use crate::byte_array::{parse_byte_array, ByteArrayPolicy};
+use crate::close::{close_now, close_with_reason, CloseReason};
+use crate::compression::AdaptiveCompression;
+use crate::connection::{ConnectionConfig, ConnectionMode};
+use crate::deadline::Deadlines;
+use crate::diagnostics::ServerClientDiagnostics;
+use crate::envelope::{self, EnvelopeVersion, UpgradeOp, ENVELOPE_V1};
+use crate::events::{EventBus, ProviderEvent};
+use crate::groups::{self, GroupOp, GroupRegistry};
+use crate::handoff::{self, HandoffTracker, ResumeRegistry, RESUME_TOKEN_PARAM};
+use crate::heartbeat::{self, IdleTimer, SessionHeartbeat, HEARTBEAT_METADATA};
+use crate::legacy_hex::LegacyHexTracker;
+use crate::link_status::LinkRole;
+use crate::metadata::{MetadataLimits, MetadataRejected};
+use crate::metrics::MessageMetrics;
+use crate::parse_guard::{FailureOutcome, FrameVerdict, ParseFailureGuard, ParseFailureReason};
+use crate::pipeline::Pipeline;
+use crate::platform;
+use crate::rate_limit::{ServerRateLimiter, ServerRatePolicy};
+use crate::retain::RetainedStore;
+use crate::session_events;
+use crate::signing::{self, MessageSigner};
+use crate::sink::{MessageSink, SinkSlot};
+use crate::slow_consumer;
+use crate::spill::Spill;
+use crate::subject::{self, FieldLimits};
+use crate::tasks::TaskRegistry;
+use crate::telemetry::{self, LabelMetrics, SessionCounters};
+use crate::tenant::{
+    self, DeadLetterReason, TenantIndex, TenantRejection, TenantRoute, TENANT_HANDLER_HEADER,
+    TENANT_HEADER,
+};
+use crate::tls;
+use crate::{BrokerMessage, SessionInfo, WebSocketMessagingProvider};
+
+/// How long a queued close frame gets to reach the client before its writer is aborted
+const CLOSE_FLUSH_GRACE: Duration = Duration::from_millis(250);
 
 /// Client connection state for server mode
 #[derive(Debug)]
 pub struct ServerClientConnection {
-    pub tx: mpsc::UnboundedSender<Message>,
-    #[allow(dead_code)]
+    pub(crate) tx: ClientSender,
     pub session_info: SessionInfo,
+    /// Long-lived span covering the connection; per-message spans are its children
+    pub span: Span,
+    /// Stats of the session, reported on `span` when it ends
+    pub(crate) counters: Arc<SessionCounters>,
 }
 
 /// WebSocket server state
@@ -37,8 +89,110 @@ pub struct ServerState {
     /// Component ID that handles incoming messages
     #[allow(dead_code)]
     pub component_id: Arc<RwLock<Option<String>>>,
-    /// Callback for handling incoming messages
-    pub message_handler: Arc<dyn Fn(String, BrokerMessage) -> Result<()> + Send + Sync>,
+    /// Where incoming messages are delivered; replaceable while the server runs
+    pub(crate) sink: SinkSlot,
+    /// Number of entries in `clients`, readable without taking the lock
+    client_count: Arc<AtomicUsize>,
+    /// Last-value cache replayed to newly connected clients
+    retained: Arc<RwLock<RetainedStore>>,
+    /// Whether `retained` has any subjects configured
+    retention_enabled: bool,
+    /// Number of live per-connection send/receive tasks
+    connection_tasks: Arc<AtomicUsize>,
+    /// Server-wide configuration
+    pub(crate) config: Arc<ConnectionConfig>,
+    /// Channel for provider events raised by connections
+    pub(crate) events: EventBus,
+    /// Progress of handing clients off to another instance
+    pub(crate) handoff: HandoffTracker,
+    /// Resume tokens shared with other instances, if configured
+    pub(crate) resume_registry: Option<ResumeRegistry>,
+    /// Traffic counters per connection label
+    metrics: LabelMetrics,
+    /// Message counters by mode, link role and subject prefix, served on `/metrics`
+    message_metrics: MessageMetrics,
+    /// Counts bodies decoded as legacy hex
+    legacy_hex: LegacyHexTracker,
+    /// Drops messages past their `deadline_unix_ms`
+    deadlines: Deadlines,
+    /// Stages every client message passes through before it is delivered
+    pipeline: Pipeline,
+    /// Requests sent to clients awaiting a reply, keyed by reply subject
+    pending_replies: Arc<Mutex<HashMap<String, PendingReply>>>,
+    /// Group membership of connected sessions
+    groups: Arc<Mutex<GroupRegistry>>,
+    /// Bytes queued across all clients' outbound queues
+    budget: MemoryBudget,
+    /// Bytes each session holds across its buffers, against `MAX_SESSION_BUFFER_BYTES`
+    ledger: SessionLedger,
+    /// Streamed bodies arriving from clients
+    pub(crate) body_streams: BodyStreams,
+    /// Live spawned tasks and per-session dispatch, for `runtime_health`
+    pub(crate) tasks: TaskRegistry,
+    /// `BODY_COMPRESSION=adaptive` state; broadcasts are encoded once for every client, so
+    /// the decision is shared by the whole server
+    pub(crate) compression: Arc<AdaptiveCompression>,
+    /// Handler component per tenant, maintained as handler links come and go
+    pub(crate) tenants: TenantIndex,
+    /// Caps the messages all clients together deliver, per `SERVER_MAX_MESSAGES_PER_SEC`
+    pub(crate) rate_limiter: Arc<ServerRateLimiter>,
+    /// Counts client messages that arrive after shutdown began instead of delivering them
+    spill: Spill,
+}
+
+/// Counts a per-connection task as live until it finishes or is aborted
+struct TaskGuard(Arc<AtomicUsize>);
+
+impl TaskGuard {
+    fn new(counter: &Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Self(Arc::clone(counter))
+    }
+}
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Document served at `GET /capabilities` so clients can discover what the server accepts
+///
+/// Built from the server's config; it never includes secrets.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Capabilities {
+    /// Provider version
+    pub version: String,
+    /// WebSocket subprotocols the server negotiates
+    pub subprotocols: Vec<String>,
+    /// Message envelope formats understood on text and binary frames
+    pub codecs: Vec<String>,
+    /// Body `content-encoding`s the server decompresses
+    pub content_encodings: Vec<String>,
+    /// Largest message the server sends or accepts (0 = no limit)
+    pub max_message_bytes: usize,
+    /// Whether an auth token or tenant tokens are configured for the server
+    pub auth_required: bool,
+}
+
+impl Capabilities {
+    pub(crate) fn from_config(config: &ConnectionConfig) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            subprotocols: Vec::new(),
+            codecs: vec!["json".to_string()],
+            content_encodings: vec!["zstd".to_string(), "gzip".to_string()],
+            max_message_bytes: config.max_message_bytes,
+            auth_required: config.auth_token.is_some() || !config.tenant_tokens.is_empty(),
+        }
+    }
+}
+
+/// A `request_client` call waiting for its reply
+struct PendingReply {
+    /// Token the reply has to echo as `corr_id`, besides using the reply subject
+    corr_id: String,
+    reply_tx: oneshot::Sender<BrokerMessage>,
 }
 
 impl ServerState {
@@ -46,11 +200,168 @@ impl ServerState {
     where
         F: Fn(String, BrokerMessage) -> Result<()> + Send + Sync + 'static,
     {
+        Self::with_sink_slot(SinkSlot::new(message_handler))
+    }
+
+    /// State buffering incoming messages until a sink is installed with `set_message_sink`
+    pub(crate) fn buffering(capacity: usize) -> Self {
+        Self::with_sink_slot(SinkSlot::buffering(capacity))
+    }
+
+    fn with_sink_slot(sink: SinkSlot) -> Self {
         Self {
             clients: Arc::new(RwLock::new(HashMap::new())),
             component_id: Arc::new(RwLock::new(None)),
-            message_handler: Arc::new(message_handler),
+            sink,
+            client_count: Arc::new(AtomicUsize::new(0)),
+            retained: Arc::new(RwLock::new(RetainedStore::default())),
+            retention_enabled: false,
+            connection_tasks: Arc::new(AtomicUsize::new(0)),
+            config: Arc::new(ConnectionConfig::default()),
+            events: EventBus::default(),
+            handoff: HandoffTracker::default(),
+            resume_registry: None,
+            metrics: LabelMetrics::default(),
+            message_metrics: MessageMetrics::default(),
+            legacy_hex: LegacyHexTracker::default(),
+            deadlines: Deadlines::default(),
+            pipeline: Pipeline::new(&ConnectionConfig::default(), &[]),
+            pending_replies: Arc::default(),
+            groups: Arc::default(),
+            budget: MemoryBudget::default(),
+            ledger: SessionLedger::default(),
+            body_streams: BodyStreams::default(),
+            tasks: TaskRegistry::default(),
+            compression: Arc::default(),
+            tenants: TenantIndex::default(),
+            rate_limiter: Arc::new(ServerRateLimiter::new(0, ServerRatePolicy::default())),
+            spill: Spill::default(),
+        }
+    }
+
+    /// Deliver incoming messages to `sink` from now on, after flushing any buffered ones
+    pub(crate) async fn set_message_sink(&self, sink: Arc<dyn MessageSink>) {
+        self.sink
+            .install(sink, self.config.delivery_timeout())
+            .await;
+    }
+
+    /// Issue and redeem resume tokens through the given registry
+    pub(crate) fn with_resume_registry(mut self, registry: ResumeRegistry) -> Self {
+        self.resume_registry = Some(registry);
+        self
+    }
+
+    /// Use the given server-wide configuration
+    pub(crate) fn with_config(mut self, config: ConnectionConfig) -> Self {
+        self.groups = Arc::new(Mutex::new(GroupRegistry::new(
+            config.max_groups_per_session,
+            config.max_group_size,
+            config.max_groups,
+        )));
+        self.budget = MemoryBudget::new(config.server_memory_budget_bytes);
+        self.ledger = SessionLedger::new(
+            config.max_session_buffer_bytes,
+            config.session_buffer_policy,
+        );
+        self.sink.attach_ledger(&self.ledger);
+        self.body_streams = self.body_streams.with_ledger(self.ledger.clone());
+        self.pipeline = Pipeline::new(&config, &[]);
+        self.rate_limiter = Arc::new(ServerRateLimiter::new(
+            config.server_max_messages_per_sec,
+            config.server_rate_policy,
+        ));
+        self.config = Arc::new(config);
+        self
+    }
+
+    /// Count labelled connections in the given metrics
+    pub(crate) fn with_metrics(mut self, metrics: LabelMetrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Count client messages in the given message metrics
+    pub(crate) fn with_message_metrics(mut self, message_metrics: MessageMetrics) -> Self {
+        self.message_metrics = message_metrics;
+        self
+    }
+
+    /// Count legacy hex bodies in the given tracker
+    pub(crate) fn with_legacy_hex(mut self, legacy_hex: LegacyHexTracker) -> Self {
+        self.legacy_hex = legacy_hex;
+        self
+    }
+
+    /// Check message deadlines with the given clock and counter
+    pub(crate) fn with_deadlines(mut self, deadlines: Deadlines) -> Self {
+        self.deadlines = deadlines;
+        self
+    }
+
+    /// Pass client messages through the given pipeline
+    pub(crate) fn with_pipeline(mut self, pipeline: Pipeline) -> Self {
+        self.pipeline = pipeline;
+        self
+    }
+
+    /// Hand streamed bodies to the given registry, charging their prefixes to this
+    /// state's sessions
+    pub(crate) fn with_body_streams(mut self, body_streams: BodyStreams) -> Self {
+        self.body_streams = body_streams.with_ledger(self.ledger.clone());
+        self
+    }
+
+    /// Spawn and account for tasks in the given registry
+    pub(crate) fn with_tasks(mut self, tasks: TaskRegistry) -> Self {
+        self.tasks = tasks;
+        self
+    }
+
+    /// Stop delivering client messages once the given spill is armed by shutdown
+    pub(crate) fn with_spill(mut self, spill: Spill) -> Self {
+        self.spill = spill;
+        self
+    }
+
+    /// Raise events on the given bus
+    pub(crate) fn with_events(mut self, events: EventBus) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Bytes of frames currently queued for clients
+    pub fn queued_bytes(&self) -> usize {
+        self.budget.used()
+    }
+
+    /// Warn about and report broadcast frames shed for lack of budget
+    fn report_shed(&self, shed: usize) {
+        if shed == 0 {
+            return;
         }
+        let (queued_bytes, budget_bytes) = (self.budget.used(), self.budget.limit());
+        warn!(
+            "Shed a broadcast for {} clients: {} bytes queued, budget {}",
+            shed, queued_bytes, budget_bytes
+        );
+        self.events.emit(ProviderEvent::BackpressureShed {
+            shed,
+            queued_bytes,
+            budget_bytes,
+        });
+    }
+
+    /// Number of per-connection send/receive tasks currently alive
+    pub fn active_connection_tasks(&self) -> usize {
+        self.connection_tasks.load(Ordering::SeqCst)
+    }
+
+    /// Use the given last-value cache for retained subjects
+    pub(crate) fn with_retention(mut self, store: RetainedStore) -> Self {
+        self.retention_enabled = store.is_enabled();
+        self.retained = Arc::new(RwLock::new(store));
+        self
     }
 
     /// Set the component that will handle messages from clients
@@ -66,35 +377,541 @@ impl ServerState {
         clients.keys().cloned().collect()
     }
 
+    /// Frames queued for each client session
+    pub(crate) async fn client_queue_depths(&self) -> Vec<(String, usize)> {
+        let clients = self.clients.read().await;
+        clients
+            .iter()
+            .map(|(session_id, client)| (session_id.clone(), client.tx.queued_frames()))
+            .collect()
+    }
+
+    /// Session, queue depth and groups of every client, sorted by session
+    pub(crate) async fn client_diagnostics(&self) -> Vec<ServerClientDiagnostics> {
+        let clients = self.clients.read().await;
+        let mut diagnostics: Vec<_> = clients
+            .values()
+            .map(|client| {
+                let session_id = client.session_info.session_id.clone();
+                let mut groups = self.list_session_groups(&session_id);
+                groups.sort();
+                ServerClientDiagnostics {
+                    session_id,
+                    connected_at: client.session_info.connected_at,
+                    queued_frames: client.tx.queued_frames(),
+                    groups,
+                    metadata: client.session_info.metadata.clone(),
+                }
+            })
+            .collect();
+        diagnostics.sort_by(|a, b| a.session_id.cmp(&b.session_id));
+        diagnostics
+    }
+
     /// Send message to a specific client session
     pub async fn send_to_client(&self, session_id: &str, msg: Message) -> Result<()> {
+        self.send_to_client_with(session_id, || Ok(msg)).await
+    }
+
+    /// Send a message to a client session, building the frame only once the session is
+    /// known to exist
+    pub(crate) async fn send_to_client_with(
+        &self,
+        session_id: &str,
+        build: impl FnOnce() -> Result<Message>,
+    ) -> Result<()> {
         let clients = self.clients.read().await;
-        if let Some(client) = clients.get(session_id) {
-            client
-                .tx
-                .send(msg)
-                .context("Failed to send message to client")?;
-            Ok(())
-        } else {
+        let Some(client) = clients.get(session_id) else {
             anyhow::bail!("Client session not found: {}", session_id)
+        };
+        let msg = build()?;
+        debug_span!(parent: &client.span, "message", direction = "outbound")
+            .in_scope(|| client.tx.send(msg))
+            .context("Failed to send message to client")?;
+        Ok(())
+    }
+
+    /// Queue each message for its session, in order, encoding it with `encode`
+    ///
+    /// The clients lock is taken once per `SEND_BATCH_CHUNK` messages and released in
+    /// between. Like `send_to_client`, this ignores `SERVER_MEMORY_BUDGET_BYTES` but not
+    /// the session's `MAX_SESSION_BUFFER_BYTES`.
+    pub(crate) async fn send_batch(
+        &self,
+        messages: Vec<(String, BrokerMessage)>,
+        encode: impl Fn(&BrokerMessage) -> Result<Message>,
+    ) -> BatchReport {
+        let mut report = BatchReport::default();
+        let mut messages = messages.into_iter().peekable();
+        while messages.peek().is_some() {
+            let clients = self.clients.read().await;
+            for (session_id, broker_msg) in messages.by_ref().take(SEND_BATCH_CHUNK) {
+                let outcome = match clients.get(&session_id) {
+                    Some(client) => self.send_batched(client, &session_id, &broker_msg, &encode),
+                    None => SendOutcome::UnknownSession,
+                };
+                report.outcomes.push((session_id, outcome));
+            }
+            drop(clients);
+            tokio::task::yield_now().await;
+        }
+        report
+    }
+
+    fn send_batched(
+        &self,
+        client: &ServerClientConnection,
+        session_id: &str,
+        broker_msg: &BrokerMessage,
+        encode: impl Fn(&BrokerMessage) -> Result<Message>,
+    ) -> SendOutcome {
+        let msg = match encode(broker_msg) {
+            Ok(msg) => msg,
+            Err(e) => return SendOutcome::Rejected(format!("{:#}", e)),
+        };
+        let idle = client.tx.queued_frames() == 0;
+        let sent = debug_span!(parent: &client.span, "message", direction = "outbound")
+            .in_scope(|| client.tx.send(msg));
+        match sent {
+            Ok(()) => {
+                self.message_metrics.record(
+                    ConnectionMode::Server,
+                    LinkRole::Consumer,
+                    &broker_msg.subject,
+                    broker_msg.body.len(),
+                    Some(session_id),
+                );
+                if idle {
+                    SendOutcome::Delivered
+                } else {
+                    SendOutcome::Queued
+                }
+            }
+            // The connection ended and is about to be unregistered
+            Err(_) if client.tx.is_closed() => SendOutcome::UnknownSession,
+            Err(_) => SendOutcome::QueueFull,
         }
     }
 
-    /// Broadcast message to all connected clients
-    pub async fn broadcast(&self, msg: Message) -> Result<()> {
+    /// Close a client's connection with `reason`
+    ///
+    /// The reason is recorded on the session's stats, counted in the close metrics and
+    /// reported as a `ClientClosed` event. Returns whether the close frame was queued.
+    pub(crate) async fn close_client(&self, session_id: &str, reason: CloseReason) -> bool {
+        self.close_client_with(session_id, reason, true)
+            .await
+            .is_some()
+    }
+
+    /// Close a client's connection with `reason` without waiting for its queued data
+    ///
+    /// The queued frames are discarded and the close frame is written next. Returns how
+    /// many frames were discarded, or `None` if the client isn't connected.
+    pub(crate) async fn close_client_now(
+        &self,
+        session_id: &str,
+        reason: CloseReason,
+    ) -> Option<usize> {
+        self.close_client_with(session_id, reason, false).await
+    }
+
+    /// Close a client after (`graceful`) or ahead of its queued data, returning how many
+    /// frames were discarded if the close frame was queued
+    async fn close_client_with(
+        &self,
+        session_id: &str,
+        reason: CloseReason,
+        graceful: bool,
+    ) -> Option<usize> {
         let clients = self.clients.read().await;
-        for (session_id, client) in clients.iter() {
-            if let Err(e) = client.tx.send(msg.clone()) {
-                warn!("Failed to send to session {}: {}", session_id, e);
+        let client = clients.get(session_id)?;
+        client.counters.record_close_reason(reason);
+        let queued = if graceful {
+            close_with_reason(&client.tx, reason).then_some(0)
+        } else {
+            close_now(&client.tx, reason)
+        };
+        drop(clients);
+
+        info!(
+            "Closing client {} ({}, code {}, {} queued frames discarded)",
+            session_id,
+            reason.reason(),
+            reason.code(),
+            queued.unwrap_or_default()
+        );
+        self.metrics.record_close(reason);
+        self.events.emit(ProviderEvent::ClientClosed {
+            session_id: session_id.to_string(),
+            reason,
+        });
+        queued
+    }
+
+    /// Close every connected client with `reason` ahead of its queued data, returning how
+    /// many were closed
+    pub(crate) async fn close_all_clients(&self, reason: CloseReason) -> usize {
+        let sessions = self.list_client_sessions().await;
+        let mut closed = 0;
+        for session_id in sessions {
+            if self.close_client_now(&session_id, reason).await.is_some() {
+                closed += 1;
+            }
+        }
+        closed
+    }
+
+    /// Close every client and forget its session even if its connection task is gone,
+    /// returning how many there were
+    pub(crate) async fn drop_all_clients(&self, reason: CloseReason) -> usize {
+        let sessions = self.list_client_sessions().await;
+        for session_id in &sessions {
+            self.close_client_now(session_id, reason).await;
+            self.remove_client(session_id).await;
+        }
+        sessions.len()
+    }
+
+    /// Add a connected session to a group, returning whether it wasn't a member already
+    ///
+    /// Fails with `GroupLimitExceeded` when `MAX_GROUPS_PER_SESSION`, `MAX_GROUP_SIZE` or
+    /// `MAX_GROUPS` would be exceeded.
+    pub async fn add_to_group(&self, session_id: &str, group: &str) -> Result<bool> {
+        // Holding the clients lock keeps a concurrent disconnect from missing the new membership
+        let clients = self.clients.read().await;
+        if !clients.contains_key(session_id) {
+            anyhow::bail!("Client session not found: {}", session_id);
+        }
+        let joined = self.groups.lock().unwrap().join(session_id, group)?;
+        if joined {
+            debug!("Session {} joined group {}", session_id, group);
+        }
+        Ok(joined)
+    }
+
+    /// Remove a session from a group, returning whether it was a member
+    pub fn remove_from_group(&self, session_id: &str, group: &str) -> bool {
+        let left = self.groups.lock().unwrap().leave(session_id, group);
+        if left {
+            debug!("Session {} left group {}", session_id, group);
+        }
+        left
+    }
+
+    /// Sessions in a group, sorted
+    pub fn list_group_members(&self, group: &str) -> Vec<String> {
+        self.groups.lock().unwrap().members(group)
+    }
+
+    /// Groups a session is in, sorted
+    pub fn list_session_groups(&self, session_id: &str) -> Vec<String> {
+        self.groups.lock().unwrap().groups_of(session_id)
+    }
+
+    /// Send a message to every member of a group, returning how many it was queued for
+    ///
+    /// The message is only encoded when the group has members. Like broadcasts, it is shed
+    /// for members once `SERVER_MEMORY_BUDGET_BYTES` is used up.
+    pub async fn send_to_group(&self, group: &str, broker_msg: &BrokerMessage) -> Result<usize> {
+        let members = self.list_group_members(group);
+        if members.is_empty() {
+            return Ok(0);
+        }
+        let msg = WebSocketMessagingProvider::encode_axum_checked(
+            broker_msg,
+            &self.config,
+            &self.compression,
+        )?;
+        let clients = self.clients.read().await;
+        let (mut recipients, mut shed) = (0, 0);
+        for session_id in &members {
+            let Some(client) = clients.get(session_id) else {
+                continue;
+            };
+            match client.tx.send_within_budget(msg.clone()) {
+                Ok(true) => recipients += 1,
+                Ok(false) => shed += 1,
+                Err(e) => warn!("Failed to send to session {}: {}", session_id, e),
             }
         }
+        drop(clients);
+        self.report_shed(shed);
+        Ok(recipients)
+    }
+
+    /// Apply a client's group control frame and build the reply sent back to it
+    async fn apply_group_op(&self, session_id: &str, op: GroupOp) -> String {
+        let error = match &op {
+            GroupOp::Join(group) => self
+                .add_to_group(session_id, group)
+                .await
+                .err()
+                .map(|e| e.to_string()),
+            GroupOp::Leave(group) => {
+                self.remove_from_group(session_id, group);
+                None
+            }
+        };
+        groups::group_op_reply(&op, error)
+    }
+
+    /// Record a metadata entry the provider owns on a connected client's session info
+    pub(crate) async fn set_provider_metadata(&self, session_id: &str, key: &str, value: String) {
+        if let Some(client) = self.clients.write().await.get_mut(session_id) {
+            client.session_info.metadata.insert(key.to_string(), value);
+        }
+    }
+
+    /// Record a metadata entry on a connected client's session info, within the
+    /// `MAX_METADATA_*` limits and outside the reserved `_wcp_` prefix
+    pub async fn set_session_metadata(
+        &self,
+        session_id: &str,
+        key: &str,
+        value: String,
+    ) -> Result<()> {
+        let mut clients = self.clients.write().await;
+        let Some(client) = clients.get_mut(session_id) else {
+            anyhow::bail!("Client session not found: {}", session_id);
+        };
+        MetadataLimits::from_config(&self.config)
+            .check(&client.session_info.metadata, key, &value)
+            .map_err(|reason| MetadataRejected {
+                session_id: session_id.to_string(),
+                key: key.to_string(),
+                reason,
+            })?;
+        client.session_info.metadata.insert(key.to_string(), value);
         Ok(())
     }
 
+    /// Session info of a connected client, with its groups as comma-separated `groups`
+    /// metadata
+    pub async fn session_info(&self, session_id: &str) -> Option<SessionInfo> {
+        let mut info = self
+            .clients
+            .read()
+            .await
+            .get(session_id)?
+            .session_info
+            .clone();
+        let groups = self.list_session_groups(session_id);
+        if !groups.is_empty() {
+            info.metadata.insert("groups".to_string(), groups.join(","));
+        }
+        Some(info)
+    }
+
+    /// Send a request to a client and wait for the message it publishes on the reply subject
+    pub(crate) async fn request_client(
+        &self,
+        session_id: &str,
+        subject: String,
+        body: Bytes,
+        timeout: Duration,
+    ) -> Result<BrokerMessage> {
+        let reply_to = format!("{}.{}", self.config.inbox_prefix, Uuid::new_v4());
+        let msg = BrokerMessage {
+            subject,
+            body,
+            reply_to: Some(reply_to.clone()),
+            headers: HashMap::new(),
+        };
+        let corr_id = Uuid::new_v4().to_string();
+        let encoded = WebSocketMessagingProvider::encode_request_to_axum(&msg, &corr_id)?;
+
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.pending_replies
+            .lock()
+            .unwrap()
+            .insert(reply_to.clone(), PendingReply { corr_id, reply_tx });
+        let result: Result<BrokerMessage> = async {
+            self.send_to_client(session_id, encoded).await?;
+            match tokio::time::timeout(timeout, reply_rx).await {
+                Ok(Ok(reply)) => Ok(reply),
+                Ok(Err(_)) => anyhow::bail!("Request to client {} was abandoned", session_id),
+                Err(_) => anyhow::bail!(
+                    "Request to client {} on {} timed out after {:?}",
+                    session_id,
+                    msg.subject,
+                    timeout
+                ),
+            }
+        }
+        .await;
+        self.pending_replies.lock().unwrap().remove(&reply_to);
+        result
+    }
+
+    /// Hand a message to the request waiting on its subject, or give it back if none is
+    ///
+    /// The message must also carry the request's `corr_id`; one that only matches the
+    /// subject is treated as an ordinary message and the request keeps waiting.
+    fn complete_reply(&self, msg: BrokerMessage, corr_id: Option<&str>) -> Option<BrokerMessage> {
+        let mut pending = self.pending_replies.lock().unwrap();
+        let Some(waiting) = pending.get(&msg.subject) else {
+            return Some(msg);
+        };
+        if corr_id != Some(waiting.corr_id.as_str()) {
+            debug!(
+                "Message on pending reply subject {} has a mismatched corr_id, not completing the request",
+                msg.subject
+            );
+            return Some(msg);
+        }
+        if let Some(waiting) = pending.remove(&msg.subject) {
+            let _ = waiting.reply_tx.send(msg);
+        }
+        None
+    }
+
+    /// Broadcast message to all connected clients, returning how many it was queued for
+    ///
+    /// Once the frames queued across all clients reach `SERVER_MEMORY_BUDGET_BYTES`, the
+    /// broadcast is shed for the clients it no longer fits for and a `BackpressureShed`
+    /// event is raised; direct sends and control frames are never shed.
+    pub async fn broadcast(&self, msg: Message) -> Result<usize> {
+        let clients = self.clients.read().await;
+        let (mut recipients, mut shed) = (0, 0);
+        for (session_id, client) in clients.iter() {
+            match client.tx.send_within_budget(msg.clone()) {
+                Ok(true) => recipients += 1,
+                Ok(false) => shed += 1,
+                Err(e) => warn!("Failed to send to session {}: {}", session_id, e),
+            }
+        }
+        drop(clients);
+        self.report_shed(shed);
+        Ok(recipients)
+    }
+
+    /// Broadcast message to all clients, remembering it if its subject is retained
+    ///
+    /// Returns how many clients the message was queued for. It is only encoded once at
+    /// least one client is connected, and the retained lock is only taken when retention
+    /// is enabled; it is then held across the broadcast so a client connecting concurrently
+    /// sees the message either in its retained replay or as a live broadcast.
+    pub async fn broadcast_and_retain(&self, broker_msg: &BrokerMessage) -> Result<usize> {
+        if !self.retention_enabled {
+            return self.broadcast_encoded(broker_msg).await;
+        }
+        let mut retained = self.retained.write().await;
+        retained.retain(broker_msg);
+        self.broadcast_encoded(broker_msg).await
+    }
+
+    async fn broadcast_encoded(&self, broker_msg: &BrokerMessage) -> Result<usize> {
+        if self.client_count.load(Ordering::SeqCst) == 0 {
+            return Ok(0);
+        }
+        let msg = WebSocketMessagingProvider::encode_axum_checked(
+            broker_msg,
+            &self.config,
+            &self.compression,
+        )?;
+        self.broadcast(msg).await
+    }
+
+    /// Queue retained messages for a new client, then register it
+    async fn register_client(&self, session_id: String, client: ServerClientConnection) {
+        let mut retained = self.retained.write().await;
+        if retained.is_enabled() {
+            // A retained message stops being replayed at its deadline or TTL, whichever
+            // comes first
+            for msg in retained.snapshot() {
+                if self.deadlines.is_expired(&msg) {
+                    continue;
+                }
+                match WebSocketMessagingProvider::encode_message_to_axum_static(&msg) {
+                    Ok(encoded) => {
+                        let _ = client.tx.send(encoded);
+                    }
+                    Err(e) => warn!("Failed to encode retained message: {}", e),
+                }
+            }
+        }
+
+        let mut clients = self.clients.write().await;
+        clients.insert(session_id, client);
+        self.client_count.store(clients.len(), Ordering::SeqCst);
+    }
+
+    /// Registered clients whose outbound channel is closed, i.e. whose connection ended
+    pub(crate) async fn closed_clients(&self) -> Vec<String> {
+        let clients = self.clients.read().await;
+        clients
+            .iter()
+            .filter(|(_, client)| client.tx.is_closed())
+            .map(|(session_id, _)| session_id.clone())
+            .collect()
+    }
+
+    /// Drop the group memberships of sessions that are no longer connected
+    ///
+    /// Returns the removed memberships as `(session_id, group)`.
+    pub(crate) async fn prune_orphan_memberships(&self) -> Vec<(String, String)> {
+        // Joins and disconnects take the clients lock too, so nothing changes meanwhile
+        let clients = self.clients.read().await;
+        let mut groups = self.groups.lock().unwrap();
+        let mut pruned = Vec::new();
+        for session_id in groups.sessions() {
+            if clients.contains_key(&session_id) {
+                continue;
+            }
+            for group in groups.groups_of(&session_id) {
+                pruned.push((session_id.clone(), group));
+            }
+            groups.remove_session(&session_id);
+        }
+        pruned
+    }
+
+    /// The `n` sessions holding the most buffered bytes, largest first
+    pub(crate) fn top_sessions_by_memory(&self, n: usize) -> Vec<SessionMemory> {
+        self.ledger.top(n)
+    }
+
+    /// Bytes the sessions' ledgers say are queued, and bytes the memory budget holds
+    ///
+    /// Every queued frame is charged to both, so the two only differ if a charge leaked.
+    pub(crate) fn queued_bytes_accounting(&self) -> (usize, usize) {
+        (self.ledger.queued_total(), self.budget.used())
+    }
+
+    /// Register a client whose connection has already ended
+    #[cfg(feature = "test-util")]
+    pub(crate) async fn insert_stale_client(&self, session_id: &str) {
+        let (tx, _) = budget::client_channel(&self.budget, Arc::default());
+        let client = ServerClientConnection {
+            tx,
+            session_info: SessionInfo {
+                session_id: session_id.to_string(),
+                connected_at: std::time::SystemTime::now(),
+                metadata: HashMap::new(),
+            },
+            span: Span::none(),
+            counters: Arc::new(SessionCounters::start(None)),
+        };
+        let mut clients = self.clients.write().await;
+        clients.insert(session_id.to_string(), client);
+        self.client_count.store(clients.len(), Ordering::SeqCst);
+    }
+
+    /// Record a group membership without checking that the session is connected
+    #[cfg(feature = "test-util")]
+    pub(crate) fn insert_membership(&self, session_id: &str, group: &str) {
+        let _ = self.groups.lock().unwrap().join(session_id, group);
+    }
+
     /// Remove a client session
-    async fn remove_client(&self, session_id: &str) {
+    pub(crate) async fn remove_client(&self, session_id: &str) {
         let mut clients = self.clients.write().await;
         clients.remove(session_id);
+        self.client_count.store(clients.len(), Ordering::SeqCst);
+        self.groups.lock().unwrap().remove_session(session_id);
+        self.body_streams.fail_connection(session_id);
+        self.ledger.remove(session_id);
+        self.message_metrics.forget_session(session_id);
         info!("Client disconnected: {}", session_id);
     }
 }
@@ -104,206 +921,1074 @@ pub async fn start_server(
     bind_addr: &str,
     state: ServerState,
 ) -> Result<(SocketAddr, JoinHandle<Result<()>>)> {
-    let app = Router::new()
+    let mut app = Router::new()
         .route("/ws", get(ws_handler))
+        .route("/capabilities", get(capabilities_handler))
+        .route("/metrics", get(metrics_handler))
         .with_state(state.clone());
 
+    // Oversized upgrade requests are turned away before reaching the upgrade handler
+    let body_limit = state.config.max_upgrade_body_bytes;
+    if body_limit > 0 {
+        app = app.layer(RequestBodyLimitLayer::new(body_limit));
+    }
+    let header_limit = state.config.max_upgrade_header_bytes;
+    if header_limit > 0 {
+        app = app.layer(middleware::from_fn_with_state(
+            header_limit,
+            limit_header_size,
+        ));
+    }
+
     // Parse bind address
     let addr: SocketAddr = bind_addr.parse().context("Invalid bind address")?;
 
-    // Create TCP listener
-    let listener = tokio::net::TcpListener::bind(addr)
-        .await
-        .context("Failed to bind to address")?;
+    // With TLS_CERT_PATH and TLS_KEY_PATH the server speaks `wss://`; a bad pair fails
+    // the start before anything is bound
+    let tls = match tls::server_identity(&state.config)? {
+        Some((cert, key)) => Some(
+            RustlsConfig::from_pem(cert, key)
+                .await
+                .context("Failed to load TLS_CERT_PATH and TLS_KEY_PATH")?,
+        ),
+        None => None,
+    };
+
+    // Create TCP listener, retrying while a previous listener's socket lingers
+    let config = &state.config;
+    let mut attempt = 0;
+    let listener = loop {
+        match platform::bind_listener(addr, config.bind_reuse_addr).await {
+            Ok(listener) => break listener,
+            Err(e)
+                if e.kind() == std::io::ErrorKind::AddrInUse
+                    && attempt < config.bind_retry_attempts =>
+            {
+                attempt += 1;
+                warn!(
+                    "Address {} in use, retrying bind ({}/{}) in {:?}",
+                    addr, attempt, config.bind_retry_attempts, config.bind_retry_delay
+                );
+                tokio::time::sleep(config.bind_retry_delay).await;
+            }
+            Err(e) => return Err(e).context("Failed to bind to address"),
+        }
+    };
 
     let local_addr = listener.local_addr()?;
-    info!("WebSocket server listening on {}", local_addr);
+    info!(
+        "WebSocket server listening on {}://{}",
+        if tls.is_some() { "wss" } else { "ws" },
+        local_addr
+    );
 
-    // Spawn server task
-    let handle = tokio::spawn(async move {
-        axum::serve(listener, app).await.context("Server error")?;
-        Ok(())
-    });
+    let make_service = app.into_make_service_with_connect_info::<SocketAddr>();
+    let serve: BoxFuture<'static, std::io::Result<()>> = match tls {
+        Some(tls) => {
+            Box::pin(axum_server::from_tcp_rustls(listener.into_std()?, tls).serve(make_service))
+        }
+        None => Box::pin(axum::serve(listener, make_service).into_future()),
+    };
+
+    // Spawn server task; the slow-consumer monitor runs and stops with it
+    let monitor = slow_consumer::monitor(state.clone());
+    let handle = state
+        .tasks
+        .spawn("server", &local_addr.to_string(), async move {
+            tokio::select! {
+                result = serve => result.context("Server error")?,
+                () = monitor => {}
+            }
+            Ok(())
+        });
 
     Ok((local_addr, handle))
 }
 
+/// Reject requests whose headers add up to more than `limit` bytes with 431
+async fn limit_header_size(State(limit): State<usize>, req: Request, next: Next) -> Response {
+    let size: usize = req
+        .headers()
+        .iter()
+        // Name, ": ", value and CRLF as sent on the wire
+        .map(|(name, value)| name.as_str().len() + value.len() + 4)
+        .sum();
+    if size > limit {
+        warn!(
+            "Rejecting upgrade request with {} bytes of headers (limit {})",
+            size, limit
+        );
+        return StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE.into_response();
+    }
+    next.run(req).await
+}
+
+/// Serve the capabilities document; unauthenticated since it carries no secrets
+async fn capabilities_handler(State(state): State<ServerState>) -> Json<Capabilities> {
+    Json(Capabilities::from_config(&state.config))
+}
+
+async fn metrics_handler(State(state): State<ServerState>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.message_metrics.render(),
+    )
+}
+
 /// WebSocket upgrade handler
-async fn ws_handler(ws: WebSocketUpgrade, State(state): State<ServerState>) -> Response {
-    ws.on_upgrade(|socket| handle_socket(socket, state))
+async fn ws_handler(
+    ws: WebSocketUpgrade,
+    ConnectInfo(remote): ConnectInfo<SocketAddr>,
+    Query(params): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    State(state): State<ServerState>,
+) -> Response {
+    // A client handed off by another instance resumes its original session id
+    let resumed = params
+        .get(RESUME_TOKEN_PARAM)
+        .and_then(|token| state.resume_registry.as_ref()?.redeem(token));
+    let claimed = params
+        .get(&state.config.tenant_param)
+        .filter(|tenant| !tenant.is_empty());
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let tenant = match tenant::session_tenant(
+        &state.config.tenant_tokens,
+        bearer,
+        claimed.map(String::as_str),
+    ) {
+        Ok(tenant) => tenant,
+        Err(TenantRejection::Unauthenticated) => {
+            warn!(
+                "Rejecting upgrade from {} without a valid tenant token",
+                remote
+            );
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+        Err(TenantRejection::Mismatch {
+            claimed,
+            authenticated,
+        }) => {
+            warn!(
+                "Rejecting upgrade from {} naming tenant {} with the token of {}",
+                remote, claimed, authenticated
+            );
+            return StatusCode::FORBIDDEN.into_response();
+        }
+    };
+    // Inbound frames are held to the same limit as outbound ones
+    let ws = match state.config.max_message_bytes {
+        0 => ws,
+        limit => ws.max_message_size(limit),
+    };
+    ws.on_upgrade(move |socket| handle_socket(socket, remote, resumed, tenant, state))
+}
+
+/// First frame sent to a client when `WELCOME_FRAME` is set: its session id, the limits
+/// it has to stay within and, if configured, the heartbeat the server expects
+fn welcome_frame(session_id: &str, config: &ConnectionConfig) -> Message {
+    let mut welcome = serde_json::json!({
+        "session_id": session_id,
+        "limits": welcome_limits(config),
+    });
+    if let Some(heartbeat) = SessionHeartbeat::from_config(config).advertisement() {
+        welcome["heartbeat"] = heartbeat;
+    }
+    Message::Text(welcome.to_string())
+}
+
+/// Every limit the server holds a client's frames to, named after its config key (0 = no
+/// limit)
+fn welcome_limits(config: &ConnectionConfig) -> serde_json::Value {
+    serde_json::json!({
+        "max_message_bytes": config.max_message_bytes,
+        "max_decompressed_bytes": config.max_decompressed_bytes,
+        "max_subject_len": config.max_subject_len,
+        "max_reply_to_len": config.max_reply_to_len,
+        "server_max_messages_per_sec": config.server_max_messages_per_sec,
+        "server_rate_policy": config.server_rate_policy.as_str(),
+        "max_session_buffer_bytes": config.max_session_buffer_bytes,
+        "session_buffer_policy": config.session_buffer_policy.as_str(),
+        "max_groups_per_session": config.max_groups_per_session,
+        "max_group_size": config.max_group_size,
+        "max_groups": config.max_groups,
+        "parse_fail_threshold": config.parse_fail_threshold,
+        "parse_fail_disconnect_threshold": config.parse_fail_disconnect_threshold,
+    })
+}
+
+/// Length of the payload carried by a data frame, used for session stats and the
+/// memory budget
+pub(crate) fn frame_len(msg: &Message) -> usize {
+    match msg {
+        Message::Text(text) => text.len(),
+        Message::Binary(data) => data.len(),
+        _ => 0,
+    }
 }
 
 /// Handle individual WebSocket connection
-async fn handle_socket(socket: WebSocket, state: ServerState) {
-    let session_id = Uuid::new_v4().to_string();
-    info!("New WebSocket client connected: {}", session_id);
+async fn handle_socket(
+    socket: WebSocket,
+    remote: SocketAddr,
+    resumed: Option<String>,
+    tenant: Option<String>,
+    state: ServerState,
+) {
+    let is_resumed = resumed.is_some();
+    let session_id = resumed.unwrap_or_else(|| Uuid::new_v4().to_string());
+    let connection_id = telemetry::next_connection_id();
+
+    let component_id = state.component_id.read().await.clone();
+    let session_span = telemetry::session_span(
+        &connection_id,
+        &session_id,
+        "server",
+        &remote.to_string(),
+        component_id.as_deref(),
+        state.config.label(),
+    );
+    if is_resumed {
+        info!(parent: &session_span, "WebSocket client resumed session: {}", session_id);
+    } else {
+        info!(parent: &session_span, "New WebSocket client connected: {}", session_id);
+    }
+    let label_counters = state
+        .config
+        .label()
+        .map(|label| state.metrics.counters(label));
+    let counters = Arc::new(SessionCounters::start(label_counters));
 
     let (mut ws_tx, mut ws_rx) = socket.split();
-    let (tx, mut rx) = mpsc::unbounded_channel::<Message>();
+    let session_usage = state.ledger.register(&session_id);
+    let (tx, mut rx) = budget::client_channel(&state.budget, Arc::clone(&session_usage));
 
     // Create session info
+    let mut metadata = HashMap::from([("connection_id".to_string(), connection_id)]);
+    if let Some(tenant) = &tenant {
+        MetadataLimits::from_config(&state.config).insert_upgrade(
+            &session_id,
+            &mut metadata,
+            "tenant",
+            tenant.clone(),
+        );
+    }
+    // Replaced by the negotiated interval if the client sends a hello
+    let default_heartbeat = SessionHeartbeat::from_config(&state.config);
+    if default_heartbeat.interval.is_some() {
+        metadata.insert(
+            HEARTBEAT_METADATA.to_string(),
+            default_heartbeat.interval_ms().to_string(),
+        );
+    }
     let session_info = SessionInfo {
         session_id: session_id.clone(),
         connected_at: std::time::SystemTime::now(),
-        metadata: HashMap::new(),
+        metadata,
     };
 
-    // Register client
-    {
-        let mut clients = state.clients.write().await;
-        clients.insert(
+    if state.config.welcome_frame {
+        let _ = tx.send(welcome_frame(&session_id, &state.config));
+    }
+
+    // Register client, replaying retained messages first
+    state
+        .register_client(
             session_id.clone(),
             ServerClientConnection {
                 tx: tx.clone(),
                 session_info,
+                span: session_span.clone(),
+                counters: Arc::clone(&counters),
             },
-        );
-    }
+        )
+        .await;
 
     // Clone for the tasks
     let session_id_send = session_id.clone();
+    let deadlines_send = state.deadlines.clone();
     let session_id_recv = session_id.clone();
     let state_recv = state.clone();
     let state_cleanup = state.clone();
+    let counters_send = Arc::clone(&counters);
+    let counters_recv = Arc::clone(&counters);
+    let signer_send = MessageSigner::from_config(&state.config);
+    let signer_recv = signer_send.clone();
+    let tenant_cleanup = tenant.clone();
+    // Envelope version the client negotiates in band, checked against its frames
+    let envelope_recv = Arc::new(EnvelopeVersion::new(state.config.envelope_upgrade_grace));
+    // Set once `$SYS.session.connected` is on its way, so `disconnected` never comes alone
+    let connected_sent = Arc::new(AtomicBool::new(false));
+    let connected_recv = Arc::clone(&connected_sent);
 
     // Spawn task to send messages to client
-    let send_handle = tokio::spawn(async move {
-        while let Some(msg) = rx.recv().await {
-            if let Err(e) = ws_tx.send(msg).await {
-                error!("Failed to send to client {}: {}", session_id_send, e);
-                break;
+    let send_guard = TaskGuard::new(&state.connection_tasks);
+    let mut send_handle = state.tasks.spawn(
+        "server-send",
+        &format!("session={}", session_id),
+        async move {
+            let _guard = send_guard;
+            // Envelopes written after an upgrade ack carry the version it names
+            let mut version = ENVELOPE_V1;
+            // A frame's budget charge is given back once it has been written
+            while let Some((msg, _charge)) = rx.recv().await {
+                // A message can expire while it waits in the queue
+                if matches!(&msg, Message::Text(text) if !deadlines_send.admit_envelope(text)) {
+                    continue;
+                }
+                let msg = match msg {
+                    Message::Text(text) => {
+                        if let Some(UpgradeOp::Ack(acked)) = UpgradeOp::parse(&text) {
+                            version = acked;
+                            Message::Text(text)
+                        } else {
+                            Message::Text(envelope::tag_outbound(text, version))
+                        }
+                    }
+                    msg => msg,
+                };
+                let msg = match &signer_send {
+                    Some(signer) => signer.sign(msg),
+                    None => msg,
+                };
+                counters_send.record_frame(frame_len(&msg));
+                let is_close = matches!(msg, Message::Close(_));
+                if let Err(e) = ws_tx.send(msg).await {
+                    error!("Failed to send to client {}: {}", session_id_send, e);
+                    break;
+                }
+                if is_close {
+                    break;
+                }
             }
         }
-    });
+        .instrument(session_span.clone()),
+    );
 
     // Handle incoming messages from client
-    let recv_handle = tokio::spawn(async move {
-        while let Some(msg_result) = ws_rx.next().await {
-            match msg_result {
-                Ok(Message::Text(text)) => {
-                    debug!("Received text message from {}: {}", session_id_recv, text);
-
-                    // Parse message and forward to handler
-                    if let Ok(broker_msg) = parse_broker_message(&text, &session_id_recv) {
-                        if let Err(e) =
-                            (state_recv.message_handler)(session_id_recv.clone(), broker_msg)
+    let recv_guard = TaskGuard::new(&state.connection_tasks);
+    // Delivering a message to the sink holds up the session's later ones
+    let dispatcher = state.tasks.dispatcher(format!("session:{}", session_id));
+    let mut recv_handle = state.tasks.spawn(
+        "server-recv",
+        &format!("session={}", session_id),
+        async move {
+            let _guard = recv_guard;
+            let mut parse_guard = ParseFailureGuard::new(state_recv.config.parse_failure_policy());
+            let mut close_queued = false;
+            let mut idle = IdleTimer::new(&default_heartbeat);
+            // This task dispatches all of the session's messages in order, so the
+            // connected event goes out here before any of them
+            if state_recv.config.session_events {
+                connected_recv.store(true, Ordering::SeqCst);
+                let connected = session_events::connected(&session_id_recv, is_resumed);
+                dispatch_session_event(&state_recv, &session_id_recv, tenant.as_deref(), connected)
+                    .await;
+            }
+            loop {
+                let msg_result = tokio::select! {
+                    next = ws_rx.next() => match next {
+                        Some(msg_result) => msg_result,
+                        None => break,
+                    },
+                    _ = tokio::time::sleep_until(idle.deadline().unwrap_or_else(tokio::time::Instant::now)), if idle.deadline().is_some() => {
+                        info!("Client {} sent nothing within its idle timeout", session_id_recv);
+                        close_queued = state_recv
+                            .close_client(&session_id_recv, CloseReason::IdleTimeout)
+                            .await;
+                        break;
+                    }
+                };
+                idle.touch();
+                match msg_result {
+                    Ok(Message::Text(text)) => {
+                        counters_recv.record_frame(text.len());
+
+                        if state_recv.handoff.awaiting_ack(&session_id_recv)
+                            && handoff::is_reconnect_ack(&text)
                         {
-                            error!("Message handler error: {}", e);
+                            state_recv.handoff.acknowledge(&session_id_recv);
+                            continue;
+                        }
+
+                        // Sessions flooding unparseable frames are skipped without parsing
+                        if parse_guard.check() == FrameVerdict::Skip {
+                            continue;
+                        }
+                        debug!("Received text message from {}: {}", session_id_recv, text);
+
+                        if let Some(proposed) = heartbeat::parse_hello(&text) {
+                            let negotiated = default_heartbeat.negotiate(proposed);
+                            idle.set(&negotiated);
+                            state_recv
+                                .set_provider_metadata(
+                                    &session_id_recv,
+                                    HEARTBEAT_METADATA,
+                                    negotiated.interval_ms().to_string(),
+                                )
+                                .await;
+                            let _ = state_recv
+                                .send_to_client(&session_id_recv, Message::Text(negotiated.ack()))
+                                .await;
+                            continue;
+                        }
+
+                        if let Some(op) = groups::parse_group_op(&text) {
+                            let reply = state_recv.apply_group_op(&session_id_recv, op).await;
+                            let _ = state_recv
+                                .send_to_client(&session_id_recv, Message::Text(reply))
+                                .await;
+                            continue;
+                        }
+
+                        // Envelope upgrades are negotiated in band and never dispatched
+                        if let Some(op) = UpgradeOp::parse(&text) {
+                            if let Some(reply) = envelope_recv.handle(op, &session_id_recv) {
+                                let _ = tx.send(Message::Text(reply.to_json()));
+                            }
+                            continue;
+                        }
+
+                        // Parse message and forward to handler
+                        let parsed = envelope_recv
+                            .check_inbound(&text)
+                            .and_then(|()| signing::verify(signer_recv.as_ref(), &text))
+                            .and_then(|()| parse_broker_message(&text, &session_id_recv, state_recv.config.byte_array_policy, BodyDecoding::from_config(&state_recv.config), FieldLimits::from_config(&state_recv.config)));
+                        let reason = match parsed {
+                            Ok((broker_msg, corr_id)) => {
+                                parse_guard.record_success();
+                                let _in_flight = dispatcher.in_flight();
+                                dispatch_to_handler(
+                                    &state_recv,
+                                    &session_id_recv,
+                                    tenant.as_deref(),
+                                    broker_msg,
+                                    corr_id.as_deref(),
+                                )
+                                .await;
+                                continue;
+                            }
+                            Err(e) => ParseFailureReason::of(&e),
+                        };
+                        state_recv.metrics.record_parse_failure(reason);
+
+                        match parse_guard.record_failure() {
+                            FailureOutcome::Counted => {
+                                warn!(
+                                    "Failed to parse message from client ({})",
+                                    reason.label()
+                                );
+                            }
+                            FailureOutcome::CooldownStarted { failures } => {
+                                warn!(
+                                    "Session {} sent {} consecutive unparseable frames, skipping its frames for {:?}",
+                                    session_id_recv,
+                                    failures,
+                                    state_recv.config.parse_failure_policy().cooldown
+                                );
+                                state_recv.events.emit(ProviderEvent::ParseFailureCooldown {
+                                    session_id: session_id_recv.clone(),
+                                    failures,
+                                });
+                            }
+                            FailureOutcome::Disconnect { failures } => {
+                                warn!(
+                                    "Disconnecting session {} after {} consecutive unparseable frames",
+                                    session_id_recv, failures
+                                );
+                                state_recv.events.emit(ProviderEvent::ParseFailureDisconnect {
+                                    session_id: session_id_recv.clone(),
+                                    failures,
+                                });
+                                close_queued = state_recv
+                                    .close_client(&session_id_recv, CloseReason::InvalidFrames)
+                                    .await;
+                                break;
+                            }
                         }
-                    } else {
-                        warn!("Failed to parse message from client");
                     }
-                }
-                Ok(Message::Binary(data)) => {
-                    debug!(
-                        "Received binary message from {}: {} bytes",
-                        session_id_recv,
-                        data.len()
-                    );
-
-                    // Try to parse as JSON or handle as raw binary
-                    if let Ok(text) = String::from_utf8(data.clone()) {
-                        if let Ok(broker_msg) = parse_broker_message(&text, &session_id_recv) {
-                            if let Err(e) =
-                                (state_recv.message_handler)(session_id_recv.clone(), broker_msg)
-                            {
-                                error!("Message handler error: {}", e);
+                    Ok(Message::Binary(data)) => {
+                        counters_recv.record_frame(data.len());
+                        debug!(
+                            "Received binary message from {}: {} bytes",
+                            session_id_recv,
+                            data.len()
+                        );
+
+                        // Continuation frames of a streamed body go to its subscriber
+                        let data = match body_stream::decode_frame(data) {
+                            Ok((stream_id, frame)) => {
+                                if let Some(cancel) = state_recv
+                                    .body_streams
+                                    .receive(&session_id_recv, stream_id, frame)
+                                    .await
+                                {
+                                    let _ = state_recv
+                                        .send_to_client(&session_id_recv, Message::Binary(cancel))
+                                        .await;
+                                }
+                                continue;
+                            }
+                            Err(data) => data,
+                        };
+
+                        // Parse valid UTF-8 as JSON in place, otherwise hand on the raw bytes
+                        let parsed = match std::str::from_utf8(&data) {
+                            Ok(text) => envelope_recv
+                                .check_inbound(text)
+                                .and_then(|()| signing::verify(signer_recv.as_ref(), text))
+                                .and_then(|()| parse_broker_message(text, &session_id_recv, state_recv.config.byte_array_policy, BodyDecoding::from_config(&state_recv.config), FieldLimits::from_config(&state_recv.config)))
+                                .map_err(|e| {
+                                    let reason = ParseFailureReason::of(&e);
+                                    state_recv.metrics.record_parse_failure(reason);
+                                    warn!(
+                                        "Failed to parse binary message from {} ({}): {}",
+                                        session_id_recv,
+                                        reason.label(),
+                                        e
+                                    );
+                                })
+                                .ok(),
+                            // Raw bytes can't carry a signature
+                            Err(_) if signer_recv.as_ref().is_some_and(|signer| signer.verify_unsigned().is_err()) => {
+                                state_recv.metrics.record_parse_failure(ParseFailureReason::InvalidSignature);
+                                warn!("Dropped unsigned binary message from {}", session_id_recv);
+                                None
                             }
+                            Err(e) => {
+                                debug!(
+                                    "Binary frame from {} is not UTF-8 ({}), delivering as raw binary",
+                                    session_id_recv, e
+                                );
+                                Some((
+                                    WebSocketMessagingProvider::binary_message(
+                                        data,
+                                        &session_id_recv,
+                                    ),
+                                    None,
+                                ))
+                            }
+                        };
+                        if let Some((broker_msg, corr_id)) = parsed {
+                            let _in_flight = dispatcher.in_flight();
+                            dispatch_to_handler(
+                                &state_recv,
+                                &session_id_recv,
+                                tenant.as_deref(),
+                                broker_msg,
+                                corr_id.as_deref(),
+                            )
+                            .await;
                         }
                     }
-                }
-                Ok(Message::Close(_)) => {
-                    info!("Client {} closed connection", session_id_recv);
-                    break;
-                }
-                Ok(Message::Ping(data)) => {
-                    if let Err(e) = tx.send(Message::Pong(data)) {
-                        error!("Failed to send pong: {}", e);
+                    Ok(Message::Close(frame)) => {
+                        if let Some(frame) = frame {
+                            counters_recv.record_close(frame.code);
+                        }
+                        info!("Client {} closed connection", session_id_recv);
+                        break;
+                    }
+                    Ok(Message::Ping(data)) => {
+                        if let Err(e) = tx.send(Message::Pong(data)) {
+                            error!("Failed to send pong: {}", e);
+                            break;
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        error!("WebSocket error for client {}: {}", session_id_recv, e);
                         break;
                     }
-                }
-                Ok(_) => {}
-                Err(e) => {
-                    error!("WebSocket error for client {}: {}", session_id_recv, e);
-                    break;
                 }
             }
+            if parse_guard.skipped() > 0 {
+                debug!(
+                    "Skipped {} frames from session {} during parse-failure cooldowns",
+                    parse_guard.skipped(),
+                    session_id_recv
+                );
+            }
+            close_queued
         }
-    });
+        .instrument(session_span.clone()),
+    );
 
-    // Wait for either task to complete
-    tokio::select! {
-        _ = send_handle => {
-            debug!("Send task completed for {}", session_id);
+    async {
+        // Wait for either task to complete, then cancel the other so it can't linger
+        tokio::select! {
+            _ = &mut send_handle => {
+                debug!("Send task completed for {}", session_id);
+                recv_handle.abort();
+                // Wait out a delivery in progress before dispatching the disconnect
+                let _ = (&mut recv_handle).await;
+            }
+            close_queued = &mut recv_handle => {
+                debug!("Receive task completed for {}", session_id);
+                if matches!(close_queued, Ok(true)) {
+                    // Let the writer flush the close frame before cancelling it
+                    let _ = tokio::time::timeout(CLOSE_FLUSH_GRACE, &mut send_handle).await;
+                }
+                send_handle.abort();
+            }
+            _ = session_usage.exceeded() => {
+                warn!(
+                    "Session {} went over MAX_SESSION_BUFFER_BYTES, disconnecting",
+                    session_id
+                );
+                recv_handle.abort();
+                let _ = (&mut recv_handle).await;
+                if state_cleanup
+                    .close_client_now(&session_id, CloseReason::SessionBufferFull)
+                    .await
+                    .is_some()
+                {
+                    let _ = tokio::time::timeout(CLOSE_FLUSH_GRACE, &mut send_handle).await;
+                }
+                send_handle.abort();
+            }
         }
-        _ = recv_handle => {
-            debug!("Receive task completed for {}", session_id);
+
+        // Clean up client
+        state_cleanup.remove_client(&session_id).await;
+
+        // The receive task has ended, so nothing from the session follows this
+        if connected_sent.load(Ordering::SeqCst) {
+            let disconnected = session_events::disconnected(&session_id);
+            dispatch_session_event(
+                &state_cleanup,
+                &session_id,
+                tenant_cleanup.as_deref(),
+                disconnected,
+            )
+            .await;
         }
     }
+    .instrument(session_span.clone())
+    .await;
+    counters.finish(&session_span);
+}
+
+/// Deliver one inbound message to the sink inside a per-message span
+///
+/// Replies to requests made with `request_client` (matching subject and `corr_id`) go to the
+/// waiting request instead. The delivery is bounded by `HANDLER_TIMEOUT_MS` for the subject
+/// or `DELIVERY_TIMEOUT` so a stuck sink can't wedge the socket.
+async fn dispatch_to_handler(
+    state: &ServerState,
+    session_id: &str,
+    tenant: Option<&str>,
+    broker_msg: BrokerMessage,
+    corr_id: Option<&str>,
+) {
+    // The sink and handler links are being torn down
+    if state.spill.drop_inbound(1) {
+        debug!(
+            "Dropped message from {} received during shutdown",
+            session_id
+        );
+        return;
+    }
+    if state.config.session_events && session_events::is_reserved(&broker_msg.subject) {
+        warn!(
+            "Dropped message from {} on reserved subject {}",
+            session_id, broker_msg.subject
+        );
+        return;
+    }
+    let Some(broker_msg) = state.pipeline.run(session_id, broker_msg) else {
+        return;
+    };
+    if !state
+        .deadlines
+        .admit_inbound(session_id, tenant, &broker_msg)
+    {
+        return;
+    }
+    state.legacy_hex.observe(&broker_msg, session_id);
+    let Some(broker_msg) = state.complete_reply(broker_msg, corr_id) else {
+        debug!("Received reply from {}", session_id);
+        return;
+    };
+    let Some(broker_msg) = route_by_tenant(state, session_id, tenant, broker_msg) else {
+        return;
+    };
+    // Held back or shed once all clients together exceed SERVER_MAX_MESSAGES_PER_SEC
+    if !state.rate_limiter.admit(session_id).await {
+        debug!(
+            "Shed message on {} from {}: server message rate exceeded",
+            broker_msg.subject, session_id
+        );
+        return;
+    }
+    // Registered before delivery so the handler can subscribe to the body right away
+    state.body_streams.open_descriptor(&broker_msg, session_id);
+    state.message_metrics.record(
+        ConnectionMode::Server,
+        LinkRole::Handler,
+        &broker_msg.subject,
+        broker_msg.body.len(),
+        Some(session_id),
+    );
+    let span = debug_span!(
+        "message",
+        direction = "inbound",
+        subject = %broker_msg.subject,
+        bytes = broker_msg.body.len(),
+    );
+    // A handler stuck on one message times out so the client's next frames still flow
+    let timeout = state.config.delivery_timeout_for(&broker_msg.subject);
+    let delivered = state
+        .sink
+        .deliver(session_id.to_string(), broker_msg, timeout)
+        .instrument(span)
+        .await;
+    if let Err(e) = delivered {
+        error!("Message handler error: {}", e);
+    }
+}
+
+/// Deliver a `SESSION_EVENTS` lifecycle message, routed to the same handler as the
+/// session's messages but never expired, rate limited or passed through the pipeline
+async fn dispatch_session_event(
+    state: &ServerState,
+    session_id: &str,
+    tenant: Option<&str>,
+    event: BrokerMessage,
+) {
+    let Some(event) = route_by_tenant(state, session_id, tenant, event) else {
+        return;
+    };
+    let subject = event.subject.clone();
+    let timeout = state.config.delivery_timeout_for(&subject);
+    if let Err(e) = state
+        .sink
+        .deliver(session_id.to_string(), event, timeout)
+        .await
+    {
+        error!("Failed to deliver {} for {}: {}", subject, session_id, e);
+    }
+}
 
-    // Clean up client
-    state_cleanup.remove_client(&session_id).await;
+/// Tag a message with the handler of its session's tenant, or dead-letter it if there is
+/// none; untouched while no handler link declares a `TENANT`
+fn route_by_tenant(
+    state: &ServerState,
+    session_id: &str,
+    tenant: Option<&str>,
+    mut broker_msg: BrokerMessage,
+) -> Option<BrokerMessage> {
+    match state.tenants.route(tenant) {
+        TenantRoute::Untracked => Some(broker_msg),
+        TenantRoute::Handler(component_id) => {
+            if let Some(tenant) = tenant {
+                broker_msg
+                    .headers
+                    .insert(TENANT_HEADER.to_string(), tenant.to_string());
+            }
+            broker_msg
+                .headers
+                .insert(TENANT_HANDLER_HEADER.to_string(), component_id);
+            Some(broker_msg)
+        }
+        TenantRoute::Unrouted => {
+            let reason = DeadLetterReason::NoTenantHandler;
+            warn!(
+                "Dead-lettered message on {} from {} (tenant {:?}): {}",
+                broker_msg.subject,
+                session_id,
+                tenant,
+                reason.as_str()
+            );
+            state.events.emit(ProviderEvent::DeadLetter {
+                session_id: session_id.to_string(),
+                tenant: tenant.map(str::to_string),
+                subject: broker_msg.subject,
+                reason,
+            });
+            None
+        }
+    }
 }
 
-/// Parse a text message into a BrokerMessage
-fn parse_broker_message(text: &str, session_id: &str) -> Result<BrokerMessage> {
+/// Parse a text message into a BrokerMessage and the envelope's `corr_id`, if any,
+/// decoding a string body per `decoding` and rejecting a subject or reply_to over `limits`
+fn parse_broker_message(
+    text: &str,
+    session_id: &str,
+    policy: ByteArrayPolicy,
+    decoding: BodyDecoding,
+    limits: FieldLimits,
+) -> Result<(BrokerMessage, Option<String>)> {
     // Try to parse as JSON
     let json: serde_json::Value = serde_json::from_str(text)?;
 
     let subject = json
         .get("subject")
         .and_then(|v| v.as_str())
-        .unwrap_or("default")
-        .to_string();
+        .unwrap_or("default");
+    let reply_to = json.get("reply_to").and_then(|v| v.as_str());
+    limits.check(subject, reply_to)?;
+    let subject = subject.to_string();
+    subject::check_inbound(&subject)?;
 
+    let mut headers = crate::WebSocketMessagingProvider::parse_headers(&json);
     let body = if let Some(body_str) = json.get("body").and_then(|v| v.as_str()) {
-        // Try to decode from base64/hex
-        Bytes::from(body_str.as_bytes().to_vec())
+        body_encoding::decode(&json, body_str, decoding, &mut headers)?
     } else if let Some(body_arr) = json.get("body").and_then(|v| v.as_array()) {
         // Array of bytes
-        let bytes: Vec<u8> = body_arr
-            .iter()
-            .filter_map(|v| v.as_u64().map(|n| n as u8))
-            .collect();
-        Bytes::from(bytes)
+        parse_byte_array(body_arr, policy)?
     } else {
         Bytes::from(text.as_bytes().to_vec())
     };
 
     // If no reply_to is provided, use the session_id so component can reply
-    let reply_to = json
-        .get("reply_to")
-        .and_then(|v| v.as_str())
+    let reply_to = reply_to
         .map(|s| s.to_string())
         .or_else(|| Some(session_id.to_string()));
 
-    Ok(BrokerMessage {
-        subject,
-        body,
-        reply_to,
-    })
+    let corr_id = json
+        .get("corr_id")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+
+    Ok((
+        BrokerMessage {
+            subject,
+            body,
+            reply_to,
+            headers,
+        },
+        corr_id,
+    ))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::byte_array::InvalidByteArray;
+    use crate::legacy_hex::{LegacyHexPolicy, LEGACY_HEX_HEADER};
 
     #[test]
     fn test_parse_broker_message() {
         let json = r#"{"subject": "test.topic", "body": "hello", "reply_to": "session-123"}"#;
-        let msg = parse_broker_message(json, "sess-1").unwrap();
+        let (msg, _) = parse_broker_message(
+            json,
+            "sess-1",
+            ByteArrayPolicy::Reject,
+            BodyDecoding::default(),
+            FieldLimits::default(),
+        )
+        .unwrap();
         assert_eq!(msg.subject, "test.topic");
+        assert_eq!(msg.body, Bytes::from("hello"));
         assert_eq!(msg.reply_to, Some("session-123".to_string()));
     }
 
+    #[test]
+    fn test_parse_broker_message_over_length_fields() {
+        let limits = FieldLimits {
+            max_subject_len: 16,
+            max_reply_to_len: 8,
+        };
+        let parse = |subject: &str, reply_to: &str| {
+            let json =
+                serde_json::json!({"subject": subject, "body": "hello", "reply_to": reply_to});
+            parse_broker_message(
+                &json.to_string(),
+                "sess-1",
+                ByteArrayPolicy::Reject,
+                BodyDecoding::default(),
+                limits,
+            )
+        };
+        let (msg, _) = parse("test.topic", "sess-2").unwrap();
+        assert_eq!(msg.subject, "test.topic");
+
+        for (subject, reply_to) in [
+            ("test.topic.way.too.long", "sess-2"),
+            ("test.topic", "session-123"),
+        ] {
+            let e = parse(subject, reply_to).unwrap_err();
+            assert_eq!(ParseFailureReason::of(&e), ParseFailureReason::FieldTooLong);
+        }
+    }
+
     #[test]
     fn test_parse_broker_message_no_reply() {
         let json = r#"{"subject": "test.topic", "body": "hello"}"#;
-        let msg = parse_broker_message(json, "sess-1").unwrap();
+        let (msg, _) = parse_broker_message(
+            json,
+            "sess-1",
+            ByteArrayPolicy::Reject,
+            BodyDecoding::default(),
+            FieldLimits::default(),
+        )
+        .unwrap();
         assert_eq!(msg.subject, "test.topic");
         assert_eq!(msg.reply_to, Some("sess-1".to_string()));
     }
 
+    #[test]
+    fn test_parse_broker_message_rejects_invalid_byte_array() {
+        let json = r#"{"subject": "bin", "body": [104, 105]}"#;
+        let (msg, _) = parse_broker_message(
+            json,
+            "sess-1",
+            ByteArrayPolicy::Reject,
+            BodyDecoding::default(),
+            FieldLimits::default(),
+        )
+        .unwrap();
+        assert_eq!(msg.body, Bytes::from("hi"));
+
+        for body in ["[104, 256]", "[-1]", r#"[104, "105"]"#] {
+            let json = format!(r#"{{"subject": "bin", "body": {}}}"#, body);
+            let err = parse_broker_message(
+                &json,
+                "sess-1",
+                ByteArrayPolicy::Reject,
+                BodyDecoding::default(),
+                FieldLimits::default(),
+            )
+            .unwrap_err();
+            assert!(err.downcast_ref::<InvalidByteArray>().is_some(), "{}", body);
+        }
+    }
+
+    #[test]
+    fn test_parse_broker_message_round_trips_encoded_body() {
+        let msg = BrokerMessage {
+            subject: "data.raw".to_string(),
+            body: Bytes::from((0..=255u8).rev().collect::<Vec<_>>()),
+            reply_to: None,
+            headers: HashMap::new(),
+        };
+        let Message::Text(text) =
+            WebSocketMessagingProvider::encode_message_to_axum_static(&msg).unwrap()
+        else {
+            panic!("envelopes are text frames");
+        };
+        let (parsed, _) = parse_broker_message(
+            &text,
+            "sess-1",
+            ByteArrayPolicy::Reject,
+            BodyDecoding::default(),
+            FieldLimits::default(),
+        )
+        .unwrap();
+        assert_eq!(parsed.body, msg.body);
+        assert!(!parsed.headers.contains_key(LEGACY_HEX_HEADER));
+    }
+
+    #[test]
+    fn test_parse_broker_message_legacy_hex_body() {
+        let json = r#"{"subject": "old", "body": "68656c6c6f"}"#;
+        let (msg, _) = parse_broker_message(
+            json,
+            "sess-1",
+            ByteArrayPolicy::Reject,
+            BodyDecoding::default(),
+            FieldLimits::default(),
+        )
+        .unwrap();
+        assert_eq!(msg.body, Bytes::from("hello"));
+        assert_eq!(msg.headers[LEGACY_HEX_HEADER], "true");
+
+        let (msg, _) = parse_broker_message(
+            json,
+            "sess-1",
+            ByteArrayPolicy::Reject,
+            BodyDecoding {
+                legacy_hex: LegacyHexPolicy::Reject,
+                ..BodyDecoding::default()
+            },
+            FieldLimits::default(),
+        )
+        .unwrap();
+        assert_eq!(msg.body, Bytes::from("68656c6c6f"));
+        assert!(!msg.headers.contains_key(LEGACY_HEX_HEADER));
+
+        // A declared encoding is taken at its word
+        let json = r#"{"subject": "new", "body": "68656c6c", "body_encoding": "base64"}"#;
+        let (msg, _) = parse_broker_message(
+            json,
+            "sess-1",
+            ByteArrayPolicy::Reject,
+            BodyDecoding {
+                legacy_hex: LegacyHexPolicy::Prefer,
+                ..BodyDecoding::default()
+            },
+            FieldLimits::default(),
+        )
+        .unwrap();
+        assert!(!msg.headers.contains_key(LEGACY_HEX_HEADER));
+    }
+
+    #[tokio::test]
+    async fn test_connection_tasks_do_not_leak_under_churn() {
+        use futures::SinkExt;
+        use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+        let state = ServerState::new(|_session_id, _msg| Ok(()));
+        let (addr, handle) = start_server("127.0.0.1:0", state.clone()).await.unwrap();
+
+        for i in 0..50 {
+            let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws", addr))
+                .await
+                .unwrap();
+            if i % 2 == 0 {
+                // Polite close
+                let _ = ws.send(WsMessage::Close(None)).await;
+            }
+            // Odd iterations just drop the socket
+        }
+
+        let mut remaining = state.active_connection_tasks();
+        for _ in 0..100 {
+            if remaining == 0 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            remaining = state.active_connection_tasks();
+        }
+        assert_eq!(remaining, 0, "connection tasks leaked");
+        assert!(state.list_client_sessions().await.is_empty());
+
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_counts_recipients() {
+        let state = ServerState::new(|_session_id, _msg| Ok(()));
+        let msg = BrokerMessage {
+            subject: "news".to_string(),
+            body: Bytes::from("hello"),
+            reply_to: None,
+            headers: HashMap::new(),
+        };
+        assert_eq!(state.broadcast_and_retain(&msg).await.unwrap(), 0);
+
+        let (tx, mut rx) = budget::client_channel(&state.budget, Arc::default());
+        let client = ServerClientConnection {
+            tx,
+            session_info: SessionInfo {
+                session_id: "s1".to_string(),
+                connected_at: std::time::SystemTime::now(),
+                metadata: HashMap::new(),
+            },
+            span: Span::none(),
+            counters: Arc::new(SessionCounters::start(None)),
+        };
+        state.register_client("s1".to_string(), client).await;
+        assert_eq!(state.broadcast_and_retain(&msg).await.unwrap(), 1);
+        assert!(matches!(rx.try_recv(), Ok((Message::Text(text), _)) if text.contains("news")));
+
+        state.remove_client("s1").await;
+        assert_eq!(state.broadcast_and_retain(&msg).await.unwrap(), 0);
+    }
+
     #[tokio::test]
     async fn test_server_state() {
         let state = ServerState::new(|_session_id, _msg| Ok(()));
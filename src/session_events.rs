@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+
+use bytes::Bytes;
+
+use crate::BrokerMessage;
+
+/// Subjects of the lifecycle messages dispatched for server clients with `SESSION_EVENTS`;
+/// clients can't send messages on them themselves
+pub const SESSION_EVENT_PREFIX: &str = "$SYS.session.";
+
+/// Dispatched to the message sink before any message from a new server client
+pub const SESSION_CONNECTED_SUBJECT: &str = "$SYS.session.connected";
+
+/// Dispatched to the message sink once the last message from a server client has been
+/// handed to dispatch
+pub const SESSION_DISCONNECTED_SUBJECT: &str = "$SYS.session.disconnected";
+
+/// Whether a client message uses a subject reserved for lifecycle messages
+pub(crate) fn is_reserved(subject: &str) -> bool {
+    subject.starts_with(SESSION_EVENT_PREFIX)
+}
+
+/// `$SYS.session.connected` for a session, noting whether it resumed a handed-off one
+pub(crate) fn connected(session_id: &str, resumed: bool) -> BrokerMessage {
+    event(
+        SESSION_CONNECTED_SUBJECT,
+        serde_json::json!({ "session_id": session_id, "resumed": resumed }),
+    )
+}
+
+/// `$SYS.session.disconnected` for a session
+pub(crate) fn disconnected(session_id: &str) -> BrokerMessage {
+    event(
+        SESSION_DISCONNECTED_SUBJECT,
+        serde_json::json!({ "session_id": session_id }),
+    )
+}
+
+fn event(subject: &str, body: serde_json::Value) -> BrokerMessage {
+    BrokerMessage {
+        subject: subject.to_string(),
+        body: Bytes::from(body.to_string()),
+        reply_to: None,
+        headers: HashMap::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_messages() {
+        let msg = connected("s1", true);
+        assert_eq!(msg.subject, SESSION_CONNECTED_SUBJECT);
+        let body: serde_json::Value = serde_json::from_slice(&msg.body).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({"session_id": "s1", "resumed": true})
+        );
+
+        let msg = disconnected("s1");
+        assert_eq!(msg.subject, SESSION_DISCONNECTED_SUBJECT);
+        assert!(is_reserved(&msg.subject));
+        assert!(!is_reserved("$SYS.drop"));
+    }
+}
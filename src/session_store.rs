@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::Result;
+use futures::future::BoxFuture;
+use tokio::sync::RwLock;
+use tracing::{debug, warn};
+
+/// Where client-mode sessions are recorded, as `session_id -> component_id`
+///
+/// The provider keeps them in memory by default (`MemorySessionStore`); implement the
+/// trait to persist them elsewhere or share them between instances, and install it with
+/// `with_session_store`.
+pub trait SessionStore: Send + Sync + 'static {
+    /// Record that `session_id` belongs to `component_id`
+    fn insert(&self, session_id: String, component_id: String) -> BoxFuture<'_, Result<()>>;
+
+    /// Component the session belongs to, if it is recorded
+    fn get(&self, session_id: String) -> BoxFuture<'_, Result<Option<String>>>;
+
+    /// Forget a session, returning the component it belonged to
+    fn remove(&self, session_id: String) -> BoxFuture<'_, Result<Option<String>>>;
+
+    /// Every recorded session as `(session_id, component_id)`
+    fn list(&self) -> BoxFuture<'_, Result<Vec<(String, String)>>>;
+}
+
+/// Default `SessionStore`, lost when the provider stops
+#[derive(Debug, Default)]
+pub struct MemorySessionStore {
+    sessions: RwLock<HashMap<String, String>>,
+}
+
+impl SessionStore for MemorySessionStore {
+    fn insert(&self, session_id: String, component_id: String) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            self.sessions.write().await.insert(session_id, component_id);
+            Ok(())
+        })
+    }
+
+    fn get(&self, session_id: String) -> BoxFuture<'_, Result<Option<String>>> {
+        Box::pin(async move { Ok(self.sessions.read().await.get(&session_id).cloned()) })
+    }
+
+    fn remove(&self, session_id: String) -> BoxFuture<'_, Result<Option<String>>> {
+        Box::pin(async move { Ok(self.sessions.write().await.remove(&session_id)) })
+    }
+
+    fn list(&self) -> BoxFuture<'_, Result<Vec<(String, String)>>> {
+        Box::pin(async move {
+            let sessions = self.sessions.read().await;
+            Ok(sessions
+                .iter()
+                .map(|(sid, cid)| (sid.clone(), cid.clone()))
+                .collect())
+        })
+    }
+}
+
+/// The provider's session store; failures are logged rather than failing the caller
+#[derive(Clone)]
+pub(crate) struct Sessions(Arc<dyn SessionStore>);
+
+impl Default for Sessions {
+    fn default() -> Self {
+        Self(Arc::new(MemorySessionStore::default()))
+    }
+}
+
+impl Sessions {
+    pub(crate) fn new(store: impl SessionStore) -> Self {
+        Self(Arc::new(store))
+    }
+
+    pub(crate) async fn insert(&self, session_id: &str, component_id: &str) {
+        if let Err(e) = self
+            .0
+            .insert(session_id.to_string(), component_id.to_string())
+            .await
+        {
+            warn!("Failed to record session {}: {}", session_id, e);
+        }
+    }
+
+    pub(crate) async fn get(&self, session_id: &str) -> Option<String> {
+        self.0
+            .get(session_id.to_string())
+            .await
+            .unwrap_or_else(|e| {
+                warn!("Failed to look up session {}: {}", session_id, e);
+                None
+            })
+    }
+
+    pub(crate) async fn remove(&self, session_id: &str) -> Option<String> {
+        self.0
+            .remove(session_id.to_string())
+            .await
+            .unwrap_or_else(|e| {
+                warn!("Failed to remove session {}: {}", session_id, e);
+                None
+            })
+    }
+
+    pub(crate) async fn list(&self) -> Vec<(String, String)> {
+        self.0.list().await.unwrap_or_else(|e| {
+            warn!("Failed to list sessions: {}", e);
+            Vec::new()
+        })
+    }
+}
+
+/// A connection's session entry, removed when the connection's task ends or is aborted
+pub(crate) struct SessionRegistration {
+    sessions: Sessions,
+    session_id: String,
+    component_id: String,
+}
+
+impl SessionRegistration {
+    pub(crate) async fn register(
+        sessions: &Sessions,
+        session_id: &str,
+        component_id: &str,
+    ) -> Self {
+        sessions.insert(session_id, component_id).await;
+        debug!(
+            "Session {} registered for component {}",
+            session_id, component_id
+        );
+        Self {
+            sessions: sessions.clone(),
+            session_id: session_id.to_string(),
+            component_id: component_id.to_string(),
+        }
+    }
+
+    /// Move the entry to the id the server assigned
+    pub(crate) async fn rename(&mut self, session_id: &str) {
+        self.sessions.remove(&self.session_id).await;
+        self.sessions.insert(session_id, &self.component_id).await;
+        self.session_id = session_id.to_string();
+    }
+}
+
+impl Drop for SessionRegistration {
+    fn drop(&mut self) {
+        // Removal is async; without a runtime (provider torn down) there's nothing to clean
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let sessions = self.sessions.clone();
+        let session_id = std::mem::take(&mut self.session_id);
+        let component_id = std::mem::take(&mut self.component_id);
+        runtime.spawn(async move {
+            // A migrated link's new connection keeps its own entry
+            if sessions.get(&session_id).await.as_ref() == Some(&component_id) {
+                sessions.remove(&session_id).await;
+            }
+        });
+    }
+}
@@ -0,0 +1,227 @@
+use anyhow::Result;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use serde_json::Value;
+use sha2::Sha256;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::connection::ConnectionConfig;
+
+/// Envelope field carrying the base64 HMAC-SHA256 of the rest of the envelope
+pub const SIGNATURE_FIELD: &str = "sig";
+
+/// Error for an inbound message whose signature is missing or doesn't match
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidSignature {
+    /// Whether the message carried no signature at all
+    pub missing: bool,
+}
+
+impl std::fmt::Display for InvalidSignature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.missing {
+            write!(f, "Message is not signed")
+        } else {
+            write!(f, "Message signature does not match")
+        }
+    }
+}
+
+impl std::error::Error for InvalidSignature {}
+
+/// Signs outbound envelopes and verifies inbound ones with `MESSAGE_HMAC_KEY`
+///
+/// The signature covers the envelope without its `sig` field, serialized as compact JSON
+/// with object keys sorted, so a peer can recompute it from the parsed envelope.
+#[derive(Clone)]
+pub(crate) struct MessageSigner {
+    mac: Hmac<Sha256>,
+    /// `MESSAGE_HMAC_ALLOW_UNSIGNED`: accept messages without a signature, for rolling
+    /// signing out to peers one at a time
+    allow_unsigned: bool,
+}
+
+impl std::fmt::Debug for MessageSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MessageSigner")
+            .field("allow_unsigned", &self.allow_unsigned)
+            .finish_non_exhaustive()
+    }
+}
+
+impl MessageSigner {
+    /// `None` unless `MESSAGE_HMAC_KEY` is set
+    pub(crate) fn from_config(config: &ConnectionConfig) -> Option<Self> {
+        let key = config.message_hmac_key.as_ref()?;
+        Some(Self {
+            mac: Hmac::new_from_slice(key.as_bytes()).expect("HMAC takes keys of any length"),
+            allow_unsigned: config.message_hmac_allow_unsigned,
+        })
+    }
+
+    /// Add a `sig` to a text frame holding a JSON object; other frames pass unchanged
+    pub(crate) fn sign(&self, frame: Message) -> Message {
+        let Message::Text(text) = &frame else {
+            return frame;
+        };
+        match serde_json::from_str::<Value>(text) {
+            Ok(Value::Object(mut envelope)) => {
+                envelope.remove(SIGNATURE_FIELD);
+                let signature = self.signature(&Value::Object(envelope.clone()));
+                envelope.insert(SIGNATURE_FIELD.to_string(), Value::String(signature));
+                Message::Text(Value::Object(envelope).to_string())
+            }
+            _ => frame,
+        }
+    }
+
+    /// Check the signature of an inbound message before it is parsed
+    ///
+    /// Fails with `InvalidSignature` if the signature doesn't match, or if there is none
+    /// and unsigned messages aren't allowed.
+    pub(crate) fn verify(&self, text: &str) -> Result<()> {
+        let mut envelope = match serde_json::from_str::<Value>(text) {
+            Ok(Value::Object(envelope)) => envelope,
+            _ => return self.verify_unsigned(),
+        };
+        let Some(Value::String(signature)) = envelope.remove(SIGNATURE_FIELD) else {
+            return self.verify_unsigned();
+        };
+        let mut mac = self.mac.clone();
+        mac.update(canonical(&Value::Object(envelope)).as_bytes());
+        let matches = STANDARD
+            .decode(signature)
+            .is_ok_and(|signature| mac.verify_slice(&signature).is_ok());
+        if !matches {
+            return Err(InvalidSignature { missing: false }.into());
+        }
+        Ok(())
+    }
+
+    /// Check a message that carries no signature, such as a raw binary frame
+    pub(crate) fn verify_unsigned(&self) -> Result<()> {
+        if self.allow_unsigned {
+            return Ok(());
+        }
+        Err(InvalidSignature { missing: true }.into())
+    }
+
+    fn signature(&self, envelope: &Value) -> String {
+        let mut mac = self.mac.clone();
+        mac.update(canonical(envelope).as_bytes());
+        STANDARD.encode(mac.finalize().into_bytes())
+    }
+}
+
+/// Verify an inbound message with `signer` if signing is configured
+pub(crate) fn verify(signer: Option<&MessageSigner>, text: &str) -> Result<()> {
+    signer.map_or(Ok(()), |signer| signer.verify(text))
+}
+
+/// Compact JSON with object keys sorted at every level
+fn canonical(value: &Value) -> String {
+    let mut out = String::new();
+    write_canonical(value, &mut out);
+    out
+}
+
+fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            out.push('{');
+            for (n, (key, value)) in entries.into_iter().enumerate() {
+                if n > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push(':');
+                write_canonical(value, out);
+            }
+            out.push('}');
+        }
+        Value::Array(items) => {
+            out.push('[');
+            for (n, item) in items.iter().enumerate() {
+                if n > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(']');
+        }
+        other => out.push_str(&other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn signer(allow_unsigned: bool) -> MessageSigner {
+        let config = ConnectionConfig::from_map(&HashMap::from([
+            ("MESSAGE_HMAC_KEY".to_string(), "k3y".to_string()),
+            (
+                "MESSAGE_HMAC_ALLOW_UNSIGNED".to_string(),
+                allow_unsigned.to_string(),
+            ),
+        ]))
+        .unwrap();
+        MessageSigner::from_config(&config).unwrap()
+    }
+
+    fn signed(text: &str) -> String {
+        match signer(false).sign(Message::Text(text.to_string())) {
+            Message::Text(text) => text,
+            other => panic!("unexpected frame {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_signed_envelope_verifies() {
+        let text = signed(r#"{"subject":"orders","body":"aGk=","headers":{"b":"2","a":"1"}}"#);
+        assert!(text.contains(r#""sig":"#));
+        signer(false).verify(&text).unwrap();
+
+        // Key order doesn't matter, only content
+        let json: Value = serde_json::from_str(&text).unwrap();
+        let reordered = format!(
+            r#"{{"headers":{{"a":"1","b":"2"}},"sig":{},"body":"aGk=","subject":"orders"}}"#,
+            json["sig"]
+        );
+        signer(false).verify(&reordered).unwrap();
+    }
+
+    #[test]
+    fn test_tampered_envelope_rejected() {
+        let text = signed(r#"{"subject":"orders","body":"aGk="}"#).replace("aGk=", "aG8=");
+        let error = signer(false).verify(&text).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<InvalidSignature>(),
+            Some(&InvalidSignature { missing: false })
+        );
+    }
+
+    #[test]
+    fn test_unsigned_rejected_unless_allowed() {
+        for text in [r#"{"subject":"orders","body":"aGk="}"#, "plain text"] {
+            let error = signer(false).verify(text).unwrap_err();
+            assert_eq!(
+                error.downcast_ref::<InvalidSignature>(),
+                Some(&InvalidSignature { missing: true })
+            );
+            signer(true).verify(text).unwrap();
+        }
+        // A bad signature is rejected even when unsigned messages are allowed
+        let text = signed(r#"{"subject":"orders"}"#).replace("orders", "refunds");
+        assert!(signer(true).verify(&text).is_err());
+    }
+
+    #[test]
+    fn test_disabled_without_key() {
+        assert!(MessageSigner::from_config(&ConnectionConfig::default()).is_none());
+    }
+}
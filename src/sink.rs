@@ -0,0 +1,256 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use arc_swap::ArcSwap;
+use futures::future::BoxFuture;
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+use crate::budget::{SessionCharge, SessionLedger};
+use crate::BrokerMessage;
+
+/// Receives messages sent by server-mode clients
+///
+/// Plain `Fn(String, BrokerMessage) -> Result<()>` closures are sinks too, so synchronous
+/// handlers keep working; implement the trait directly to await inside delivery.
+pub trait MessageSink: Send + Sync + 'static {
+    /// Handle one message from the client with the given session id
+    fn deliver(&self, session_id: String, msg: BrokerMessage) -> BoxFuture<'_, Result<()>>;
+}
+
+impl<F> MessageSink for F
+where
+    F: Fn(String, BrokerMessage) -> Result<()> + Send + Sync + 'static,
+{
+    fn deliver(&self, session_id: String, msg: BrokerMessage) -> BoxFuture<'_, Result<()>> {
+        let result = self(session_id, msg);
+        Box::pin(async move { result })
+    }
+}
+
+/// Sink installed until a real one is available, holding up to `capacity` messages
+///
+/// Once the real sink is installed the buffered messages are flushed to it in arrival
+/// order; deliveries that raced with the installation are forwarded after them.
+pub(crate) struct BufferingSink {
+    capacity: usize,
+    state: Mutex<BufferState>,
+    /// Per-session buffer usage buffered bodies are charged to
+    ledger: ArcSwap<SessionLedger>,
+}
+
+#[derive(Default)]
+struct BufferState {
+    queue: VecDeque<(String, BrokerMessage, Option<SessionCharge>)>,
+    /// Sink the buffer was flushed to; later deliveries go straight there
+    target: Option<Arc<dyn MessageSink>>,
+}
+
+impl BufferingSink {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            state: Mutex::new(BufferState::default()),
+            ledger: ArcSwap::default(),
+        }
+    }
+}
+
+impl MessageSink for BufferingSink {
+    fn deliver(&self, session_id: String, msg: BrokerMessage) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            let mut state = self.state.lock().await;
+            if let Some(target) = &state.target {
+                return target.deliver(session_id, msg).await;
+            }
+            if state.queue.len() >= self.capacity {
+                bail!(
+                    "Message buffer full ({} messages), no message sink installed",
+                    self.capacity
+                );
+            }
+            let mut charge = None;
+            if !self
+                .ledger
+                .load()
+                .charge(&session_id, msg.body.len(), &mut charge)
+            {
+                bail!(
+                    "Session {} is at MAX_SESSION_BUFFER_BYTES, no message sink installed",
+                    session_id
+                );
+            }
+            debug!(
+                "Buffering message from session {} until a message sink is installed",
+                session_id
+            );
+            state.queue.push_back((session_id, msg, charge));
+            Ok(())
+        })
+    }
+}
+
+/// The current sink, replaceable while the server is running
+#[derive(Clone)]
+pub(crate) struct SinkSlot {
+    current: Arc<ArcSwap<Arc<dyn MessageSink>>>,
+    /// The startup buffer, until it has been flushed
+    buffer: Arc<std::sync::Mutex<Option<Arc<BufferingSink>>>>,
+    /// Deliveries abandoned because the sink didn't finish in time
+    timeouts: Arc<AtomicU64>,
+}
+
+impl SinkSlot {
+    /// Slot delivering to `sink`
+    pub(crate) fn new(sink: impl MessageSink) -> Self {
+        let sink: Arc<dyn MessageSink> = Arc::new(sink);
+        Self {
+            current: Arc::new(ArcSwap::from_pointee(sink)),
+            buffer: Arc::default(),
+            timeouts: Arc::default(),
+        }
+    }
+
+    /// Slot buffering up to `capacity` messages until a sink is installed
+    pub(crate) fn buffering(capacity: usize) -> Self {
+        let buffer = Arc::new(BufferingSink::new(capacity));
+        let sink: Arc<dyn MessageSink> = buffer.clone();
+        Self {
+            current: Arc::new(ArcSwap::from_pointee(sink)),
+            buffer: Arc::new(std::sync::Mutex::new(Some(buffer))),
+            timeouts: Arc::default(),
+        }
+    }
+
+    /// Deliver a message to the current sink, giving up after `timeout`
+    pub(crate) async fn deliver(
+        &self,
+        session_id: String,
+        msg: BrokerMessage,
+        timeout: Duration,
+    ) -> Result<()> {
+        let sink = self.current.load_full();
+        match tokio::time::timeout(timeout, sink.deliver(session_id, msg)).await {
+            Ok(result) => result,
+            Err(_) => {
+                self.timeouts.fetch_add(1, Ordering::Relaxed);
+                bail!(
+                    "Message sink did not accept the message within {:?}",
+                    timeout
+                )
+            }
+        }
+    }
+
+    /// Number of deliveries that timed out so far
+    pub(crate) fn timeout_count(&self) -> u64 {
+        self.timeouts.load(Ordering::Relaxed)
+    }
+
+    /// Charge messages held by the startup buffer to the sessions in `ledger`
+    pub(crate) fn attach_ledger(&self, ledger: &SessionLedger) {
+        if let Some(buffer) = self.buffer.lock().unwrap().as_ref() {
+            buffer.ledger.store(Arc::new(ledger.clone()));
+        }
+    }
+
+    /// Remove and return the messages still waiting for a sink to be installed
+    pub(crate) async fn take_buffered(&self) -> Vec<(String, BrokerMessage)> {
+        let buffer = self.buffer.lock().unwrap().clone();
+        let Some(buffer) = buffer else {
+            return Vec::new();
+        };
+        let mut state = buffer.state.lock().await;
+        state
+            .queue
+            .drain(..)
+            .map(|(session_id, msg, _)| (session_id, msg))
+            .collect()
+    }
+
+    /// Replace the sink, first flushing anything buffered before one was installed
+    pub(crate) async fn install(&self, sink: Arc<dyn MessageSink>, timeout: Duration) {
+        let buffer = self.buffer.lock().unwrap().take();
+        let Some(buffer) = buffer else {
+            self.current.store(Arc::new(sink));
+            return;
+        };
+
+        // Deliveries reaching the buffer meanwhile wait on its lock and are forwarded
+        // after the flush, so buffered messages keep their order
+        let mut state = buffer.state.lock().await;
+        let buffered = state.queue.len();
+        for (session_id, msg, _charge) in state.queue.drain(..) {
+            match tokio::time::timeout(timeout, sink.deliver(session_id, msg)).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => warn!("Message sink rejected a buffered message: {}", e),
+                Err(_) => warn!("Message sink timed out on a buffered message"),
+            }
+        }
+        if buffered > 0 {
+            debug!(
+                "Flushed {} buffered messages to the new message sink",
+                buffered
+            );
+        }
+        state.target = Some(Arc::clone(&sink));
+        self.current.store(Arc::new(sink));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use std::collections::HashMap;
+    use std::sync::Mutex as StdMutex;
+
+    fn message(subject: &str) -> BrokerMessage {
+        BrokerMessage {
+            subject: subject.to_string(),
+            body: Bytes::new(),
+            reply_to: None,
+            headers: HashMap::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_buffer_rejects_when_full() {
+        let slot = SinkSlot::buffering(1);
+        let timeout = Duration::from_secs(1);
+        slot.deliver("s".to_string(), message("a"), timeout)
+            .await
+            .unwrap();
+        assert!(slot
+            .deliver("s".to_string(), message("b"), timeout)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_buffered_messages_flushed_in_order() {
+        let slot = SinkSlot::buffering(10);
+        let timeout = Duration::from_secs(1);
+        for subject in ["a", "b", "c"] {
+            slot.deliver("s".to_string(), message(subject), timeout)
+                .await
+                .unwrap();
+        }
+
+        let received = Arc::new(StdMutex::new(Vec::new()));
+        let sink_received = Arc::clone(&received);
+        let sink = move |_session_id: String, msg: BrokerMessage| -> Result<()> {
+            sink_received.lock().unwrap().push(msg.subject);
+            Ok(())
+        };
+        slot.install(Arc::new(sink), timeout).await;
+        slot.deliver("s".to_string(), message("d"), timeout)
+            .await
+            .unwrap();
+
+        assert_eq!(*received.lock().unwrap(), vec!["a", "b", "c", "d"]);
+    }
+}
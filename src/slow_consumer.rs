@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use tracing::warn;
+
+use crate::events::ProviderEvent;
+use crate::server::ServerState;
+
+/// Most often the monitor looks at the queues
+const MIN_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Tracks how long each server client's queue has stayed above the high-water mark
+///
+/// A client is reported once per episode: it is reported again only after its queue has
+/// dropped below the mark and stayed above it for the whole duration once more.
+#[derive(Debug)]
+pub(crate) struct SlowConsumerDetector {
+    /// Queued frames at or above which a client counts as falling behind
+    high_water: usize,
+    after: Duration,
+    /// When each client's queue went above the mark, and whether it was reported
+    above_since: HashMap<String, (Instant, bool)>,
+}
+
+impl SlowConsumerDetector {
+    /// Detector with the high-water mark at `high_water_pct` percent of `capacity` frames
+    pub(crate) fn new(capacity: usize, high_water_pct: u32, after: Duration) -> Self {
+        let pct = high_water_pct.clamp(1, 100) as usize;
+        Self {
+            high_water: (capacity * pct / 100).max(1),
+            after,
+            above_since: HashMap::new(),
+        }
+    }
+
+    /// Record the current queue depth of every client, returning the clients that have
+    /// just been above the mark for long enough, with their depth
+    pub(crate) fn observe(
+        &mut self,
+        depths: &[(String, usize)],
+        now: Instant,
+    ) -> Vec<(String, usize)> {
+        let mut slow = Vec::new();
+        let mut above = HashMap::new();
+        for (session_id, depth) in depths {
+            if *depth < self.high_water {
+                continue;
+            }
+            let (since, reported) = self
+                .above_since
+                .get(session_id)
+                .copied()
+                .unwrap_or((now, false));
+            let report = !reported && now.duration_since(since) >= self.after;
+            if report {
+                slow.push((session_id.clone(), *depth));
+            }
+            above.insert(session_id.clone(), (since, reported || report));
+        }
+        // Clients below the mark or gone start over
+        self.above_since = above;
+        slow
+    }
+}
+
+/// Raise `SlowConsumer` for server clients whose queues stay above the high-water mark
+/// for `SLOW_CONSUMER_AFTER`; never returns, and never does anything when disabled
+pub(crate) async fn monitor(state: ServerState) {
+    let config = &state.config;
+    if config.slow_consumer_after.is_zero() {
+        return std::future::pending().await;
+    }
+    let after = config.slow_consumer_after;
+    let mut detector = SlowConsumerDetector::new(
+        config.outbound_queue_capacity,
+        config.slow_consumer_high_water_pct,
+        after,
+    );
+    let mut interval = tokio::time::interval((after / 4).max(MIN_CHECK_INTERVAL));
+    loop {
+        interval.tick().await;
+        let depths = state.client_queue_depths().await;
+        for (session_id, queue_depth) in detector.observe(&depths, Instant::now()) {
+            warn!(
+                "Client {} is a slow consumer: {} frames queued for over {:?}",
+                session_id, queue_depth, after
+            );
+            state.events.emit(ProviderEvent::SlowConsumer {
+                session_id,
+                queue_depth,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn depths(pairs: &[(&str, usize)]) -> Vec<(String, usize)> {
+        pairs
+            .iter()
+            .map(|(session_id, depth)| (session_id.to_string(), *depth))
+            .collect()
+    }
+
+    #[test]
+    fn test_reported_once_per_sustained_episode() {
+        let after = Duration::from_millis(100);
+        // 80% of 10 frames
+        let mut detector = SlowConsumerDetector::new(10, 80, after);
+        let start = Instant::now();
+
+        assert!(detector
+            .observe(&depths(&[("a", 8), ("b", 7)]), start)
+            .is_empty());
+        assert!(detector
+            .observe(&depths(&[("a", 9), ("b", 9)]), start + after / 2)
+            .is_empty());
+        assert_eq!(
+            detector.observe(&depths(&[("a", 9), ("b", 9)]), start + after),
+            depths(&[("a", 9)])
+        );
+        // `a` was already reported; `b` went above the mark later
+        assert_eq!(
+            detector.observe(&depths(&[("a", 10), ("b", 9)]), start + after * 2),
+            depths(&[("b", 9)])
+        );
+        assert!(detector
+            .observe(&depths(&[("a", 10), ("b", 9)]), start + after * 3)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_dip_below_mark_restarts_the_clock() {
+        let after = Duration::from_millis(100);
+        let mut detector = SlowConsumerDetector::new(10, 50, after);
+        let start = Instant::now();
+
+        detector.observe(&depths(&[("a", 5)]), start);
+        detector.observe(&depths(&[("a", 4)]), start + after / 2);
+        assert!(detector
+            .observe(&depths(&[("a", 6)]), start + after)
+            .is_empty());
+        assert_eq!(
+            detector.observe(&depths(&[("a", 6)]), start + after * 2),
+            depths(&[("a", 6)])
+        );
+    }
+}
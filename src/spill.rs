@@ -0,0 +1,310 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::warn;
+
+use crate::reconnect::QueuedFrame;
+
+/// Why a spilled message was still waiting when the provider shut down
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpillReason {
+    /// Queued on a client-mode connection but not yet written to its socket
+    OutboundQueue,
+    /// Received from a server-mode client while no message sink was installed
+    SinkBuffer,
+}
+
+/// One line of a spill file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpilledMessage {
+    pub reason: SpillReason,
+    /// Component whose connection the message was queued on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub component_id: Option<String>,
+    /// Session the message was queued on or received from
+    pub session_id: String,
+    /// The message's JSON envelope, as it would have gone on the wire
+    pub envelope: serde_json::Value,
+}
+
+/// Messages `shutdown` found still waiting to be sent or delivered
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShutdownReport {
+    /// Messages left in outbound queues and the server's startup buffer
+    pub unsent: usize,
+    /// How many of them were written to `SHUTDOWN_SPILL_PATH`
+    pub spilled: usize,
+    /// The spill file, when `SHUTDOWN_SPILL_PATH` is set
+    pub spill_path: Option<PathBuf>,
+    /// Inbound messages that arrived after shutdown began and were dropped instead of
+    /// being forwarded to handlers
+    pub inbound_dropped: usize,
+}
+
+/// Accounts for the messages shutdown drops, writing them to the spill file if there is one
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Spill {
+    inner: Arc<SpillState>,
+}
+
+#[derive(Debug, Default)]
+struct SpillState {
+    path: Option<PathBuf>,
+    /// Set once shutdown has begun; queues dropped before that are not spilled
+    armed: AtomicBool,
+    unsent: AtomicUsize,
+    spilled: AtomicUsize,
+    inbound_dropped: AtomicUsize,
+    /// Opened on the first spilled message
+    file: Mutex<Option<File>>,
+}
+
+impl Spill {
+    /// Spill to `path` (empty = only count what is dropped)
+    pub(crate) fn new(path: &str) -> Self {
+        Self {
+            inner: Arc::new(SpillState {
+                path: (!path.is_empty()).then(|| PathBuf::from(path)),
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Start accounting for dropped messages
+    pub(crate) fn arm(&self) {
+        self.inner.armed.store(true, Ordering::SeqCst);
+    }
+
+    fn is_armed(&self) -> bool {
+        self.inner.armed.load(Ordering::SeqCst)
+    }
+
+    /// Once shutdown has begun, count `messages` inbound messages as dropped and return
+    /// `true`, so the caller stops forwarding instead of racing the teardown
+    pub(crate) fn drop_inbound(&self, messages: usize) -> bool {
+        if !self.is_armed() {
+            return false;
+        }
+        self.inner
+            .inbound_dropped
+            .fetch_add(messages, Ordering::SeqCst);
+        true
+    }
+
+    /// Count a message that is being dropped, appending it to the spill file if configured
+    pub(crate) fn record(&self, entry: &SpilledMessage) {
+        self.inner.unsent.fetch_add(1, Ordering::SeqCst);
+        let Some(path) = &self.inner.path else {
+            return;
+        };
+        let mut file = self.inner.file.lock().unwrap();
+        match append(&mut file, path, entry) {
+            Ok(()) => {
+                self.inner.spilled.fetch_add(1, Ordering::SeqCst);
+            }
+            Err(e) => warn!("Failed to spill message: {}", e),
+        }
+    }
+
+    /// Count an outbound frame that is being dropped; only envelopes can be spilled
+    fn record_frame(&self, component_id: &str, session_id: &str, msg: Message) {
+        let text = match msg {
+            Message::Text(text) => text,
+            Message::Binary(data) => match String::from_utf8(data) {
+                Ok(text) => text,
+                Err(_) => {
+                    self.inner.unsent.fetch_add(1, Ordering::SeqCst);
+                    return;
+                }
+            },
+            _ => return,
+        };
+        let envelope = match serde_json::from_str(&text) {
+            Ok(envelope) => envelope,
+            Err(_) => serde_json::Value::String(text),
+        };
+        self.record(&SpilledMessage {
+            reason: SpillReason::OutboundQueue,
+            component_id: Some(component_id.to_string()),
+            session_id: session_id.to_string(),
+            envelope,
+        });
+    }
+
+    pub(crate) fn report(&self) -> ShutdownReport {
+        ShutdownReport {
+            unsent: self.inner.unsent.load(Ordering::SeqCst),
+            spilled: self.inner.spilled.load(Ordering::SeqCst),
+            spill_path: self.inner.path.clone(),
+            inbound_dropped: self.inner.inbound_dropped.load(Ordering::SeqCst),
+        }
+    }
+}
+
+/// Append one entry to the spill file, opening it on first use
+fn append(file: &mut Option<File>, path: &Path, entry: &SpilledMessage) -> Result<()> {
+    if file.is_none() {
+        let opened = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open spill file {}", path.display()))?;
+        *file = Some(opened);
+    }
+    if let Some(file) = file {
+        writeln!(file, "{}", serde_json::to_string(entry)?)?;
+    }
+    Ok(())
+}
+
+/// A client connection's outbound queue, spilled if shutdown tears the connection down
+/// with frames still in it
+pub(crate) struct SpillingReceiver {
+    rx: mpsc::Receiver<QueuedFrame>,
+    spill: Spill,
+    component_id: String,
+    /// Updated when the server assigns its own session id
+    pub(crate) session_id: String,
+}
+
+impl SpillingReceiver {
+    pub(crate) fn new(
+        rx: mpsc::Receiver<QueuedFrame>,
+        spill: &Spill,
+        component_id: &str,
+        session_id: &str,
+    ) -> Self {
+        Self {
+            rx,
+            spill: spill.clone(),
+            component_id: component_id.to_string(),
+            session_id: session_id.to_string(),
+        }
+    }
+}
+
+impl Deref for SpillingReceiver {
+    type Target = mpsc::Receiver<QueuedFrame>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.rx
+    }
+}
+
+impl DerefMut for SpillingReceiver {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.rx
+    }
+}
+
+impl Drop for SpillingReceiver {
+    fn drop(&mut self) {
+        if !self.spill.is_armed() {
+            return;
+        }
+        while let Ok(queued) = self.rx.try_recv() {
+            self.spill
+                .record_frame(&self.component_id, &self.session_id, queued.frame);
+        }
+    }
+}
+
+/// Read every message of a spill file
+pub fn read_spill(path: impl AsRef<Path>) -> Result<Vec<SpilledMessage>> {
+    let path = path.as_ref();
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read spill file {}", path.display()))?;
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .enumerate()
+        .map(|(idx, line)| {
+            serde_json::from_str(line)
+                .with_context(|| format!("Invalid entry on line {} of {}", idx + 1, path.display()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spill_path() -> PathBuf {
+        std::env::temp_dir().join(format!("spill-{}.ndjson", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn test_queue_spilled_only_once_armed() {
+        let path = spill_path();
+        let spill = Spill::new(&path.display().to_string());
+
+        let (tx, rx) = mpsc::channel(4);
+        tx.try_send(Message::Text(r#"{"subject":"a"}"#.to_string()).into())
+            .unwrap();
+        drop(SpillingReceiver::new(rx, &spill, "c", "s"));
+        assert_eq!(spill.report().unsent, 0);
+
+        spill.arm();
+        let (tx, rx) = mpsc::channel(4);
+        tx.try_send(Message::Text(r#"{"subject":"b"}"#.to_string()).into())
+            .unwrap();
+        tx.try_send(Message::Binary(vec![0xff]).into()).unwrap();
+        drop(SpillingReceiver::new(rx, &spill, "c", "s"));
+
+        let report = spill.report();
+        assert_eq!((report.unsent, report.spilled), (2, 1));
+        let entries = read_spill(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            entries,
+            vec![SpilledMessage {
+                reason: SpillReason::OutboundQueue,
+                component_id: Some("c".to_string()),
+                session_id: "s".to_string(),
+                envelope: serde_json::json!({"subject": "b"}),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_counts_without_spill_file() {
+        let spill = Spill::new("");
+        spill.arm();
+        spill.record(&SpilledMessage {
+            reason: SpillReason::SinkBuffer,
+            component_id: None,
+            session_id: "s".to_string(),
+            envelope: serde_json::json!({}),
+        });
+        assert_eq!(
+            spill.report(),
+            ShutdownReport {
+                unsent: 1,
+                spilled: 0,
+                spill_path: None,
+                inbound_dropped: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_inbound_dropped_only_once_armed() {
+        let spill = Spill::new("");
+        assert!(!spill.drop_inbound(1));
+        assert_eq!(spill.report().inbound_dropped, 0);
+
+        spill.arm();
+        assert!(spill.drop_inbound(1));
+        assert!(spill.drop_inbound(3));
+        assert_eq!(spill.report().inbound_dropped, 4);
+    }
+}
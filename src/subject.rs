@@ -0,0 +1,203 @@
+use crate::connection::ConnectionConfig;
+
+/// Match a subject against a NATS-style pattern
+///
+/// Subjects are `.`-separated tokens. In the pattern, `*` matches exactly one token and
+/// `>` (only meaningful as the last token) matches one or more remaining tokens.
+pub(crate) fn subject_matches(pattern: &str, subject: &str) -> bool {
+    let mut subject_tokens = subject.split('.');
+    for pattern_token in pattern.split('.') {
+        match (pattern_token, subject_tokens.next()) {
+            (">", Some(_)) => return true,
+            ("*", Some(_)) => {}
+            (p, Some(s)) if p == s => {}
+            _ => return false,
+        }
+    }
+    subject_tokens.next().is_none()
+}
+
+/// Whether a subject matches any of the given patterns
+pub(crate) fn matches_any(patterns: &[String], subject: &str) -> bool {
+    patterns.iter().any(|p| subject_matches(p, subject))
+}
+
+/// Subject of an inbound message that can't be routed or logged safely
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct InvalidSubject {
+    pub subject: String,
+}
+
+impl std::fmt::Display for InvalidSubject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Subject {:?} contains control or replacement characters",
+            self.subject
+        )
+    }
+}
+
+impl std::error::Error for InvalidSubject {}
+
+/// Reject inbound subjects with control characters or U+FFFD, the mark of a peer that
+/// decoded invalid UTF-8 lossily before sending it on
+pub(crate) fn check_inbound(subject: &str) -> Result<(), InvalidSubject> {
+    if subject
+        .chars()
+        .any(|c| c.is_control() || c == char::REPLACEMENT_CHARACTER)
+    {
+        return Err(InvalidSubject {
+            subject: subject.to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Default for `MAX_SUBJECT_LEN`
+pub(crate) const DEFAULT_MAX_SUBJECT_LEN: usize = 1024;
+
+/// Default for `MAX_REPLY_TO_LEN`
+pub(crate) const DEFAULT_MAX_REPLY_TO_LEN: usize = 1024;
+
+/// Inbound subject or reply_to longer than its configured limit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FieldTooLong {
+    /// `subject` or `reply_to`
+    pub field: &'static str,
+    /// The config key setting the limit
+    pub key: &'static str,
+    pub len: usize,
+    pub max: usize,
+}
+
+impl std::fmt::Display for FieldTooLong {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} of {} bytes is longer than {} bytes ({})",
+            self.field, self.len, self.max, self.key
+        )
+    }
+}
+
+impl std::error::Error for FieldTooLong {}
+
+/// Bounds on the subject and reply_to of an inbound envelope, in bytes (0 = no limit)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FieldLimits {
+    pub(crate) max_subject_len: usize,
+    pub(crate) max_reply_to_len: usize,
+}
+
+impl Default for FieldLimits {
+    fn default() -> Self {
+        Self {
+            max_subject_len: DEFAULT_MAX_SUBJECT_LEN,
+            max_reply_to_len: DEFAULT_MAX_REPLY_TO_LEN,
+        }
+    }
+}
+
+impl FieldLimits {
+    pub(crate) fn from_config(config: &ConnectionConfig) -> Self {
+        Self {
+            max_subject_len: config.max_subject_len,
+            max_reply_to_len: config.max_reply_to_len,
+        }
+    }
+
+    /// Reject a subject or reply_to over its limit, before either is copied out of the
+    /// envelope
+    pub(crate) fn check(&self, subject: &str, reply_to: Option<&str>) -> Result<(), FieldTooLong> {
+        let fields = [
+            (
+                "subject",
+                "MAX_SUBJECT_LEN",
+                Some(subject),
+                self.max_subject_len,
+            ),
+            (
+                "reply_to",
+                "MAX_REPLY_TO_LEN",
+                reply_to,
+                self.max_reply_to_len,
+            ),
+        ];
+        for (field, key, value, max) in fields {
+            let len = value.map_or(0, str::len);
+            if max > 0 && len > max {
+                return Err(FieldTooLong {
+                    field,
+                    key,
+                    len,
+                    max,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parse a comma-separated list of subject patterns, dropping empty entries
+pub(crate) fn parse_pattern_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subject_matches() {
+        assert!(subject_matches("metrics.cpu", "metrics.cpu"));
+        assert!(!subject_matches("metrics.cpu", "metrics.mem"));
+        assert!(subject_matches("metrics.*", "metrics.cpu"));
+        assert!(!subject_matches("metrics.*", "metrics.cpu.load"));
+        assert!(subject_matches("metrics.>", "metrics.cpu.load"));
+        assert!(!subject_matches("metrics.>", "metrics"));
+        assert!(!subject_matches("metrics.cpu.load", "metrics.cpu"));
+    }
+
+    #[test]
+    fn test_parse_pattern_list() {
+        assert_eq!(
+            parse_pattern_list("metrics.>, status.* ,,"),
+            vec!["metrics.>".to_string(), "status.*".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_check_inbound() {
+        assert!(check_inbound("orders.created").is_ok());
+        assert!(check_inbound("café.ünïcode").is_ok());
+        assert!(check_inbound("orders\u{0}created").is_err());
+        assert!(check_inbound("orders\ncreated").is_err());
+        assert!(check_inbound("orders.\u{fffd}").is_err());
+    }
+
+    #[test]
+    fn test_field_limits() {
+        let limits = FieldLimits {
+            max_subject_len: 8,
+            max_reply_to_len: 4,
+        };
+        assert!(limits.check("orders.1", Some("r.1")).is_ok());
+        assert!(limits.check("orders.1", None).is_ok());
+        let e = limits.check("orders.12", None).unwrap_err();
+        assert_eq!((e.field, e.len, e.max), ("subject", 9, 8));
+        let e = limits.check("orders", Some("r.123")).unwrap_err();
+        assert_eq!((e.field, e.len, e.max), ("reply_to", 5, 4));
+
+        let unbounded = FieldLimits {
+            max_subject_len: 0,
+            max_reply_to_len: 0,
+        };
+        assert!(unbounded.check(&"x".repeat(1 << 20), Some("y")).is_ok());
+    }
+}
@@ -0,0 +1,112 @@
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use tokio::sync::RwLock;
+use tokio::task::{AbortHandle, JoinHandle};
+use tracing::{error, info, warn};
+
+use crate::close::CloseReason;
+use crate::events::ProviderEvent;
+use crate::server::{start_server, ServerState};
+
+/// Longest wait between two rebind attempts
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(30);
+
+/// Abort handle of the task currently serving, replaced on every rebind
+pub(crate) type ServeTask = Arc<Mutex<Option<AbortHandle>>>;
+
+/// Aborts the serve task when the supervisor finishes or is aborted itself
+struct AbortOnDrop(ServeTask);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        if let Some(serve) = self.0.lock().unwrap().take() {
+            serve.abort();
+        }
+    }
+}
+
+/// Watches the server task, so a server that stops while the provider runs doesn't go
+/// on reporting an address nobody can connect to
+pub(crate) struct ServerSupervisor {
+    pub(crate) state: ServerState,
+    pub(crate) server_addr: Arc<RwLock<Option<SocketAddr>>>,
+    pub(crate) shutdown: Arc<AtomicBool>,
+    pub(crate) serve_task: ServeTask,
+}
+
+impl ServerSupervisor {
+    /// Watch `serve`, the server bound on `addr`; aborting the returned task stops both
+    pub(crate) fn spawn(
+        self,
+        addr: SocketAddr,
+        serve: JoinHandle<Result<()>>,
+    ) -> JoinHandle<Result<()>> {
+        let tasks = self.state.tasks.clone();
+        tasks.spawn(
+            "server-supervisor",
+            &addr.to_string(),
+            self.run(addr, serve),
+        )
+    }
+
+    async fn run(self, mut addr: SocketAddr, mut serve: JoinHandle<Result<()>>) -> Result<()> {
+        let _abort = AbortOnDrop(Arc::clone(&self.serve_task));
+        loop {
+            *self.serve_task.lock().unwrap() = Some(serve.abort_handle());
+            let outcome = serve.await;
+            if self.shutdown.load(Ordering::SeqCst) {
+                return Ok(());
+            }
+            let error = match outcome {
+                Ok(Ok(())) => "server stopped accepting connections".to_string(),
+                Ok(Err(e)) => format!("{:#}", e),
+                Err(e) if e.is_cancelled() => "server task was aborted".to_string(),
+                Err(e) => format!("server task panicked: {}", e),
+            };
+            error!("WebSocket server on {} stopped: {}", addr, error);
+            *self.server_addr.write().await = None;
+            // Their sockets went with the server, so nothing else would clean them up
+            let dropped = self.state.drop_all_clients(CloseReason::Shutdown).await;
+            info!("Dropped {} clients of the stopped server", dropped);
+            self.state
+                .events
+                .emit(ProviderEvent::ServerStopped { error });
+
+            if !self.state.config.server_restart {
+                return Ok(());
+            }
+            (addr, serve) = match self.rebind(addr).await {
+                Some(bound) => bound,
+                None => return Ok(()),
+            };
+            *self.server_addr.write().await = Some(addr);
+            info!("WebSocket server restarted on {}", addr);
+            self.state
+                .events
+                .emit(ProviderEvent::ServerRestarted { addr });
+        }
+    }
+
+    /// Bind `addr` again, first after `SERVER_RESTART_DELAY` and then backing off;
+    /// `None` once the provider shuts down
+    async fn rebind(&self, addr: SocketAddr) -> Option<(SocketAddr, JoinHandle<Result<()>>)> {
+        let mut delay = self.state.config.server_restart_delay;
+        loop {
+            tokio::time::sleep(delay).await;
+            if self.shutdown.load(Ordering::SeqCst) {
+                return None;
+            }
+            match start_server(&addr.to_string(), self.state.clone()).await {
+                Ok(bound) => return Some(bound),
+                Err(e) => {
+                    warn!("Failed to rebind WebSocket server on {}: {:#}", addr, e);
+                    delay = (delay * 2).min(MAX_RESTART_DELAY);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,218 @@
+//! Naming and accounting of the provider's spawned tasks
+//!
+//! Task names only reach tokio (and so tokio-console and panic messages) in builds with
+//! `--cfg tokio_unstable`; the counts and dispatcher ages behind `runtime_health` work in
+//! every build.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tokio::runtime::Handle;
+use tokio::task::JoinHandle;
+
+/// Spawn `future` on the current runtime under `name`
+pub(crate) fn spawn_named<F>(name: &str, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    spawn_named_on(name, future, &Handle::current())
+}
+
+/// Spawn `future` on `runtime` under `name`
+pub(crate) fn spawn_named_on<F>(name: &str, future: F, runtime: &Handle) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    #[cfg(tokio_unstable)]
+    {
+        tokio::task::Builder::new()
+            .name(name)
+            .spawn_on(future, runtime)
+            .expect("failed to spawn task")
+    }
+    #[cfg(not(tokio_unstable))]
+    {
+        let _ = name;
+        runtime.spawn(future)
+    }
+}
+
+/// Live task counts per label and the dispatchers' in-flight messages
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TaskRegistry {
+    inner: Arc<RegistryState>,
+}
+
+#[derive(Debug, Default)]
+struct RegistryState {
+    tasks: Mutex<HashMap<&'static str, usize>>,
+    next_dispatcher: AtomicU64,
+    /// Name and in-flight start of every live dispatcher, by a unique key
+    dispatchers: Mutex<HashMap<u64, (String, Arc<Mutex<Option<Instant>>>)>>,
+}
+
+impl TaskRegistry {
+    /// Spawn `future` as a task named `"{label} {detail}"`, counted under `label` while it
+    /// runs
+    pub(crate) fn spawn<F>(
+        &self,
+        label: &'static str,
+        detail: &str,
+        future: F,
+    ) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        *self.inner.tasks.lock().unwrap().entry(label).or_default() += 1;
+        let guard = LiveTask {
+            registry: Arc::clone(&self.inner),
+            label,
+        };
+        spawn_named(&format!("{} {}", label, detail), async move {
+            let _guard = guard;
+            future.await
+        })
+    }
+
+    /// Register a dispatcher reported under `name` until the returned handle is dropped
+    pub(crate) fn dispatcher(&self, name: String) -> Dispatcher {
+        let key = self.inner.next_dispatcher.fetch_add(1, Ordering::Relaxed);
+        let in_flight = Arc::new(Mutex::new(None));
+        self.inner
+            .dispatchers
+            .lock()
+            .unwrap()
+            .insert(key, (name, Arc::clone(&in_flight)));
+        Dispatcher {
+            registry: Arc::clone(&self.inner),
+            key,
+            in_flight,
+        }
+    }
+
+    pub(crate) fn health(&self) -> RuntimeHealth {
+        let tasks = self
+            .inner
+            .tasks
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, count)| **count > 0)
+            .map(|(label, count)| (label.to_string(), *count))
+            .collect();
+
+        let now = Instant::now();
+        let mut oldest_message_age: HashMap<String, Option<Duration>> = HashMap::new();
+        for (name, in_flight) in self.inner.dispatchers.lock().unwrap().values() {
+            let age = in_flight.lock().unwrap().map(|since| now - since);
+            let entry = oldest_message_age.entry(name.clone()).or_default();
+            *entry = (*entry).max(age);
+        }
+        RuntimeHealth {
+            tasks,
+            oldest_message_age,
+        }
+    }
+}
+
+/// Decrements its label's count when the task finishes or is aborted
+struct LiveTask {
+    registry: Arc<RegistryState>,
+    label: &'static str,
+}
+
+impl Drop for LiveTask {
+    fn drop(&mut self) {
+        if let Some(count) = self.registry.tasks.lock().unwrap().get_mut(self.label) {
+            *count = count.saturating_sub(1);
+        }
+    }
+}
+
+/// A loop handing messages on one at a time, reporting how long the current one has taken
+pub(crate) struct Dispatcher {
+    registry: Arc<RegistryState>,
+    key: u64,
+    in_flight: Arc<Mutex<Option<Instant>>>,
+}
+
+impl Dispatcher {
+    /// Mark a message as in flight until the returned guard is dropped
+    pub(crate) fn in_flight(&self) -> InFlight<'_> {
+        *self.in_flight.lock().unwrap() = Some(Instant::now());
+        InFlight(self)
+    }
+}
+
+impl Drop for Dispatcher {
+    fn drop(&mut self) {
+        self.registry.dispatchers.lock().unwrap().remove(&self.key);
+    }
+}
+
+/// Clears the dispatcher's in-flight message when dropped
+pub(crate) struct InFlight<'a>(&'a Dispatcher);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        *self.0.in_flight.lock().unwrap() = None;
+    }
+}
+
+/// Snapshot of the provider's tasks, for diagnosing a wedged instance
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RuntimeHealth {
+    /// Live tasks per label (`client-connection`, `handler-dispatch`, `server`,
+    /// `server-supervisor`, `server-send`, `server-recv`, `janitor`, `handoff`,
+    /// `configured-link`)
+    pub tasks: HashMap<String, usize>,
+    /// How long each dispatcher has been on its current message, `None` when idle;
+    /// client connections report as `component:<id>`, server sessions as `session:<id>`
+    pub oldest_message_age: HashMap<String, Option<Duration>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_tasks_counted_while_running() {
+        let registry = TaskRegistry::default();
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let handle = registry.spawn("worker", "a", async move {
+            let _ = rx.await;
+        });
+        assert_eq!(registry.health().tasks.get("worker"), Some(&1));
+
+        drop(tx);
+        handle.await.unwrap();
+        assert!(registry.health().tasks.is_empty());
+    }
+
+    #[test]
+    fn test_dispatcher_age() {
+        let registry = TaskRegistry::default();
+        let dispatcher = registry.dispatcher("component:a".to_string());
+        assert_eq!(
+            registry.health().oldest_message_age.get("component:a"),
+            Some(&None)
+        );
+
+        let in_flight = dispatcher.in_flight();
+        std::thread::sleep(Duration::from_millis(20));
+        let age = registry.health().oldest_message_age["component:a"];
+        assert!(age >= Some(Duration::from_millis(20)), "{:?}", age);
+
+        drop(in_flight);
+        assert_eq!(registry.health().oldest_message_age["component:a"], None);
+        drop(dispatcher);
+        assert!(registry.health().oldest_message_age.is_empty());
+    }
+}
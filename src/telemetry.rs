@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use serde::Serialize;
+use tracing::{field, info, info_span, Span};
+
+use crate::close::CloseReason;
+use crate::parse_guard::ParseFailureReason;
+
+/// Source of process-unique connection ids
+static NEXT_CONNECTION_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Allocate a short id for a newly accepted or dialled socket
+///
+/// Unlike the session id, which is a routing key exposed to components, the connection id
+/// only exists to correlate log lines belonging to the same underlying socket.
+pub(crate) fn next_connection_id() -> String {
+    format!(
+        "conn-{}",
+        NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Create the long-lived span covering a session from connect to disconnect
+///
+/// Per-message spans created while this span is current (or with it as explicit parent)
+/// become its children, so each connection maps to a single trace and every event logged
+/// inside it carries the `connection_id` field.
+pub(crate) fn session_span(
+    connection_id: &str,
+    session_id: &str,
+    mode: &'static str,
+    remote: &str,
+    component_id: Option<&str>,
+    label: Option<&str>,
+) -> Span {
+    let span = info_span!(
+        "session",
+        connection_id = %connection_id,
+        session_id = %session_id,
+        mode = mode,
+        remote = %remote,
+        component_id = field::Empty,
+        label = field::Empty,
+        messages = field::Empty,
+        bytes = field::Empty,
+        duration_ms = field::Empty,
+        close_code = field::Empty,
+        close_reason = field::Empty,
+    );
+    if let Some(component_id) = component_id {
+        span.record("component_id", component_id);
+    }
+    if let Some(label) = label {
+        span.record("label", label);
+    }
+    span
+}
+
+/// Traffic counters shared by every connection carrying the same label
+#[derive(Debug, Default)]
+pub(crate) struct LabelCounters {
+    connections: AtomicU64,
+    messages: AtomicU64,
+    bytes: AtomicU64,
+}
+
+/// Snapshot of the counters of one connection label
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct LabelStats {
+    /// Connections opened with this label
+    pub connections: u64,
+    /// Frames sent or received on those connections
+    pub messages: u64,
+    /// Payload bytes sent or received on those connections
+    pub bytes: u64,
+}
+
+/// Per-label metrics for connections configured with a `LABEL`, plus the number of
+/// connections the provider closed per `CloseReason` and of inbound frames rejected per
+/// `ParseFailureReason`
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LabelMetrics {
+    labels: Arc<Mutex<HashMap<String, Arc<LabelCounters>>>>,
+    closes: Arc<Mutex<HashMap<&'static str, u64>>>,
+    parse_failures: Arc<Mutex<HashMap<&'static str, u64>>>,
+}
+
+impl LabelMetrics {
+    /// Counters of `label`, created on first use
+    pub(crate) fn counters(&self, label: &str) -> Arc<LabelCounters> {
+        let mut labels = self.labels.lock().unwrap();
+        Arc::clone(labels.entry(label.to_string()).or_default())
+    }
+
+    /// Count a connection closed by the provider
+    pub(crate) fn record_close(&self, reason: CloseReason) {
+        *self
+            .closes
+            .lock()
+            .unwrap()
+            .entry(reason.label())
+            .or_default() += 1;
+    }
+
+    /// Connections closed by the provider so far, keyed by `CloseReason::label`
+    pub(crate) fn close_counts(&self) -> HashMap<String, u64> {
+        self.closes
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(label, count)| (label.to_string(), *count))
+            .collect()
+    }
+
+    /// Count an inbound frame that was rejected instead of dispatched
+    pub(crate) fn record_parse_failure(&self, reason: ParseFailureReason) {
+        *self
+            .parse_failures
+            .lock()
+            .unwrap()
+            .entry(reason.label())
+            .or_default() += 1;
+    }
+
+    /// Inbound frames rejected so far, keyed by `ParseFailureReason::label`
+    pub(crate) fn parse_failure_counts(&self) -> HashMap<String, u64> {
+        self.parse_failures
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(label, count)| (label.to_string(), *count))
+            .collect()
+    }
+
+    /// Current counters of every label seen so far
+    pub(crate) fn snapshot(&self) -> HashMap<String, LabelStats> {
+        self.labels
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(label, counters)| {
+                let stats = LabelStats {
+                    connections: counters.connections.load(Ordering::Relaxed),
+                    messages: counters.messages.load(Ordering::Relaxed),
+                    bytes: counters.bytes.load(Ordering::Relaxed),
+                };
+                (label.clone(), stats)
+            })
+            .collect()
+    }
+}
+
+/// Per-session counters reported on the session span when the connection closes
+#[derive(Debug)]
+pub(crate) struct SessionCounters {
+    messages: AtomicU64,
+    bytes: AtomicU64,
+    /// Close code received from the peer or sent by the provider, 0 if none was seen
+    close_code: AtomicU32,
+    /// Why the provider closed the session, if it did
+    close_reason: Mutex<Option<CloseReason>>,
+    started: Instant,
+    /// Counters of the connection's label, if it has one
+    label: Option<Arc<LabelCounters>>,
+}
+
+impl SessionCounters {
+    /// Start counting for a freshly established session, also feeding its label's counters
+    pub(crate) fn start(label: Option<Arc<LabelCounters>>) -> Self {
+        if let Some(label) = &label {
+            label.connections.fetch_add(1, Ordering::Relaxed);
+        }
+        Self {
+            messages: AtomicU64::new(0),
+            bytes: AtomicU64::new(0),
+            close_code: AtomicU32::new(0),
+            close_reason: Mutex::new(None),
+            started: Instant::now(),
+            label,
+        }
+    }
+
+    /// Account for one data frame sent or received on the session
+    pub(crate) fn record_frame(&self, len: usize) {
+        self.messages.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(len as u64, Ordering::Relaxed);
+        if let Some(label) = &self.label {
+            label.messages.fetch_add(1, Ordering::Relaxed);
+            label.bytes.fetch_add(len as u64, Ordering::Relaxed);
+        }
+    }
+
+    /// Remember the close code the peer sent
+    pub(crate) fn record_close(&self, code: u16) {
+        self.close_code.store(code as u32, Ordering::Relaxed);
+    }
+
+    /// Remember that the provider closed the session, and why
+    pub(crate) fn record_close_reason(&self, reason: CloseReason) {
+        self.record_close(reason.code());
+        *self.close_reason.lock().unwrap() = Some(reason);
+    }
+
+    /// The reason the provider closed the session with, if it did
+    pub(crate) fn close_reason(&self) -> Option<CloseReason> {
+        *self.close_reason.lock().unwrap()
+    }
+
+    /// Record the final stats on the session span and emit the closing event
+    pub(crate) fn finish(&self, span: &Span) {
+        let messages = self.messages.load(Ordering::Relaxed);
+        let bytes = self.bytes.load(Ordering::Relaxed);
+        let duration_ms = self.started.elapsed().as_millis() as u64;
+
+        span.record("messages", messages);
+        span.record("bytes", bytes);
+        span.record("duration_ms", duration_ms);
+        let close_code = self.close_code.load(Ordering::Relaxed);
+        if close_code != 0 {
+            span.record("close_code", close_code);
+        }
+        if let Some(reason) = self.close_reason() {
+            span.record("close_reason", reason.label());
+        }
+
+        info!(parent: span, messages, bytes, duration_ms, "Session closed");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_label_counters_are_independent() {
+        let metrics = LabelMetrics::default();
+        let a = SessionCounters::start(Some(metrics.counters("tenant-a")));
+        let b = SessionCounters::start(Some(metrics.counters("tenant-b")));
+        let unlabelled = SessionCounters::start(None);
+        a.record_frame(10);
+        a.record_frame(5);
+        b.record_frame(7);
+        unlabelled.record_frame(100);
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(
+            snapshot["tenant-a"],
+            LabelStats {
+                connections: 1,
+                messages: 2,
+                bytes: 15
+            }
+        );
+        assert_eq!(
+            snapshot["tenant-b"],
+            LabelStats {
+                connections: 1,
+                messages: 1,
+                bytes: 7
+            }
+        );
+    }
+}
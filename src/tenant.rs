@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// Header naming the tenant of the session a message came from
+pub const TENANT_HEADER: &str = "x-tenant";
+
+/// Header naming the handler component a message was routed to by tenant
+pub const TENANT_HANDLER_HEADER: &str = "x-tenant-handler";
+
+/// `TENANT` value of the handler receiving tenants no other handler declared
+pub const DEFAULT_TENANT: &str = "*";
+
+/// Why a received message was dropped instead of delivered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeadLetterReason {
+    /// Tenant routing is in use but neither the session's tenant nor `TENANT=*` has a
+    /// handler
+    NoTenantHandler,
+    /// The message arrived after its `deadline_unix_ms`
+    DeadlineExceeded,
+}
+
+impl DeadLetterReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::NoTenantHandler => "no_tenant_handler",
+            Self::DeadlineExceeded => "deadline_exceeded",
+        }
+    }
+}
+
+/// Where a client message goes under tenant routing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum TenantRoute {
+    /// No handler declares a tenant; the message is delivered as before
+    Untracked,
+    /// The handler component for the session's tenant, or the default one
+    Handler(String),
+    /// Nothing handles the tenant
+    Unrouted,
+}
+
+/// Handler component per tenant, from the `TENANT` key of handler links
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TenantIndex {
+    handlers: Arc<RwLock<HashMap<String, String>>>,
+}
+
+impl TenantIndex {
+    /// Record the tenant a handler link declares, replacing what it declared before
+    pub(crate) fn set(&self, component_id: &str, tenant: Option<&str>) {
+        let mut handlers = self.handlers.write().unwrap();
+        handlers.retain(|_, handler| handler != component_id);
+        let Some(tenant) = tenant else {
+            return;
+        };
+        if let Some(previous) = handlers.insert(tenant.to_string(), component_id.to_string()) {
+            warn!(
+                "Component {} replaces {} as the handler of tenant {}",
+                component_id, previous, tenant
+            );
+        }
+    }
+
+    /// Forget an unlinked handler
+    pub(crate) fn remove(&self, component_id: &str) {
+        self.set(component_id, None);
+    }
+
+    /// Handler for a session with the given tenant
+    pub(crate) fn route(&self, tenant: Option<&str>) -> TenantRoute {
+        let handlers = self.handlers.read().unwrap();
+        if handlers.is_empty() {
+            return TenantRoute::Untracked;
+        }
+        tenant
+            .filter(|tenant| *tenant != DEFAULT_TENANT)
+            .and_then(|tenant| handlers.get(tenant))
+            .or_else(|| handlers.get(DEFAULT_TENANT))
+            .map_or(TenantRoute::Unrouted, |handler| {
+                TenantRoute::Handler(handler.clone())
+            })
+    }
+}
+
+/// Client token of each tenant, from a `TENANT_TOKENS` value (`acme=token,globex=token`)
+///
+/// Errors never include a token.
+pub(crate) fn parse_tenant_tokens(value: &str) -> Result<HashMap<String, String>> {
+    let mut tokens: HashMap<String, String> = HashMap::new();
+    for entry in value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+    {
+        let Some((tenant, token)) = entry
+            .split_once('=')
+            .map(|(tenant, token)| (tenant.trim(), token.trim()))
+            .filter(|(tenant, token)| !tenant.is_empty() && !token.is_empty())
+        else {
+            bail!("TENANT_TOKENS entries must be tenant=token");
+        };
+        if tenant == DEFAULT_TENANT {
+            bail!(
+                "TENANT_TOKENS can't give a token to tenant {}",
+                DEFAULT_TENANT
+            );
+        }
+        if tokens.values().any(|given| given == token) {
+            bail!(
+                "TENANT_TOKENS gives tenant {} a token already given to another tenant",
+                tenant
+            );
+        }
+        if tokens
+            .insert(tenant.to_string(), token.to_string())
+            .is_some()
+        {
+            bail!("TENANT_TOKENS lists tenant {} twice", tenant);
+        }
+    }
+    Ok(tokens)
+}
+
+/// Why a client was refused under `TENANT_TOKENS`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum TenantRejection {
+    /// No bearer token, or one no tenant was given
+    Unauthenticated,
+    /// The tenant parameter names a tenant other than the token's
+    Mismatch {
+        claimed: String,
+        authenticated: String,
+    },
+}
+
+/// Tenant of a connecting client
+///
+/// Without `tokens` it is the tenant the client names, if any. With them the client has
+/// to present one of the tokens as a bearer token and its tenant is the one the token was
+/// given to; naming a different tenant is refused rather than trusted.
+pub(crate) fn session_tenant(
+    tokens: &HashMap<String, String>,
+    bearer: Option<&str>,
+    claimed: Option<&str>,
+) -> Result<Option<String>, TenantRejection> {
+    if tokens.is_empty() {
+        return Ok(claimed.map(str::to_string));
+    }
+    let authenticated = bearer
+        .and_then(|bearer| {
+            tokens
+                .iter()
+                .find(|(_, token)| secrets_equal(token.as_bytes(), bearer.as_bytes()))
+        })
+        .map(|(tenant, _)| tenant.clone())
+        .ok_or(TenantRejection::Unauthenticated)?;
+    match claimed {
+        Some(claimed) if claimed != authenticated => Err(TenantRejection::Mismatch {
+            claimed: claimed.to_string(),
+            authenticated,
+        }),
+        _ => Ok(Some(authenticated)),
+    }
+}
+
+/// Compare secrets without stopping at the first differing byte
+fn secrets_equal(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route_by_tenant() {
+        let index = TenantIndex::default();
+        assert_eq!(index.route(Some("acme")), TenantRoute::Untracked);
+
+        index.set("acme-handler", Some("acme"));
+        index.set("globex-handler", Some("globex"));
+        assert_eq!(
+            index.route(Some("acme")),
+            TenantRoute::Handler("acme-handler".to_string())
+        );
+        assert_eq!(index.route(Some("initech")), TenantRoute::Unrouted);
+        assert_eq!(index.route(None), TenantRoute::Unrouted);
+
+        index.set("fallback", Some(DEFAULT_TENANT));
+        assert_eq!(
+            index.route(Some("initech")),
+            TenantRoute::Handler("fallback".to_string())
+        );
+        // A client can't pick the default handler's entry by naming it
+        index.remove("fallback");
+        assert_eq!(index.route(Some(DEFAULT_TENANT)), TenantRoute::Unrouted);
+    }
+
+    #[test]
+    fn test_relinking_moves_handler() {
+        let index = TenantIndex::default();
+        index.set("handler", Some("acme"));
+        index.set("handler", Some("globex"));
+        assert_eq!(index.route(Some("acme")), TenantRoute::Unrouted);
+        assert_eq!(
+            index.route(Some("globex")),
+            TenantRoute::Handler("handler".to_string())
+        );
+
+        index.remove("handler");
+        assert_eq!(index.route(Some("globex")), TenantRoute::Untracked);
+    }
+
+    #[test]
+    fn test_parse_tenant_tokens() {
+        let tokens = parse_tenant_tokens(" acme=s3cret, globex = t0k=n ,").unwrap();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens["acme"], "s3cret");
+        assert_eq!(tokens["globex"], "t0k=n");
+
+        for (value, error) in [
+            ("acme", "TENANT_TOKENS entries must be tenant=token"),
+            ("acme=", "TENANT_TOKENS entries must be tenant=token"),
+            ("*=s3cret", "TENANT_TOKENS can't give a token to tenant *"),
+            ("acme=a,acme=b", "TENANT_TOKENS lists tenant acme twice"),
+            (
+                "acme=a,globex=a",
+                "TENANT_TOKENS gives tenant globex a token already given to another tenant",
+            ),
+        ] {
+            assert_eq!(parse_tenant_tokens(value).unwrap_err().to_string(), error);
+        }
+    }
+
+    #[test]
+    fn test_session_tenant() {
+        let open = HashMap::new();
+        assert_eq!(
+            session_tenant(&open, None, Some("acme")),
+            Ok(Some("acme".to_string()))
+        );
+        assert_eq!(session_tenant(&open, Some("x"), None), Ok(None));
+
+        let tokens = parse_tenant_tokens("acme=s3cret,globex=t0ken").unwrap();
+        assert_eq!(
+            session_tenant(&tokens, Some("s3cret"), None),
+            Ok(Some("acme".to_string()))
+        );
+        assert_eq!(
+            session_tenant(&tokens, Some("s3cret"), Some("acme")),
+            Ok(Some("acme".to_string()))
+        );
+        assert_eq!(
+            session_tenant(&tokens, Some("s3cret"), Some("globex")),
+            Err(TenantRejection::Mismatch {
+                claimed: "globex".to_string(),
+                authenticated: "acme".to_string(),
+            })
+        );
+        assert_eq!(
+            session_tenant(&tokens, None, Some("acme")),
+            Err(TenantRejection::Unauthenticated)
+        );
+        assert_eq!(
+            session_tenant(&tokens, Some("s3cre"), None),
+            Err(TenantRejection::Unauthenticated)
+        );
+    }
+}
@@ -0,0 +1,244 @@
+//! Loopback servers, server-mode providers and linking helpers for end-to-end tests,
+//! available with the `test-util` feature
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use futures::{SinkExt, StreamExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::{JoinHandle, JoinSet};
+use tokio::time::{sleep, timeout};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use crate::mock::{MockRemote, MockRemotes};
+use crate::{BrokerMessage, LinkRole, WebSocketMessagingProvider};
+
+/// A client connected to a server-mode provider
+pub type WsClient = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Subjects of the messages a server-mode provider's sink received, in order
+pub type Subjects = Arc<Mutex<Vec<String>>>;
+
+/// Stops a server started by [`spawn_echo_server`] or [`spawn_recording_server`]
+///
+/// Shutting down closes the listener and every connection it accepted; dropping the
+/// handle does the same without waiting for it.
+#[derive(Debug)]
+pub struct ServerShutdown {
+    stop: Option<oneshot::Sender<()>>,
+    task: JoinHandle<()>,
+}
+
+impl ServerShutdown {
+    /// Stop the server and wait until its connections are closed
+    pub async fn shutdown(mut self) {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+        let _ = (&mut self.task).await;
+    }
+}
+
+impl Drop for ServerShutdown {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Start a WebSocket server on loopback that echoes every text and binary frame back on
+/// the connection it arrived on
+///
+/// Link handler components elsewhere (e.g. to [`spawn_recording_server`]): every
+/// connection forwards what it receives to the handlers, so a handler linked here would
+/// have its own deliveries echoed back to it.
+pub async fn spawn_echo_server() -> Result<(SocketAddr, ServerShutdown)> {
+    spawn(|mut ws| async move {
+        while let Some(Ok(msg)) = ws.next().await {
+            if matches!(msg, Message::Text(_) | Message::Binary(_)) && ws.send(msg).await.is_err() {
+                break;
+            }
+        }
+    })
+    .await
+}
+
+/// Start a WebSocket server on loopback that answers nothing and reports every text and
+/// binary frame it receives, from any connection
+pub async fn spawn_recording_server(
+) -> Result<(SocketAddr, mpsc::UnboundedReceiver<Message>, ServerShutdown)> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let (addr, shutdown) = spawn(move |mut ws| {
+        let tx = tx.clone();
+        async move {
+            while let Some(Ok(msg)) = ws.next().await {
+                if matches!(msg, Message::Text(_) | Message::Binary(_)) && tx.send(msg).is_err() {
+                    break;
+                }
+            }
+        }
+    })
+    .await?;
+    Ok((addr, rx, shutdown))
+}
+
+/// Link `component_id` in `role` with its connection going to `uri`, returning once the
+/// link is established
+pub async fn link_client(
+    provider: &WebSocketMessagingProvider,
+    component_id: &str,
+    role: LinkRole,
+    uri: &str,
+) -> Result<()> {
+    let config = HashMap::from([("URI".to_string(), uri.to_string())]);
+    match role {
+        LinkRole::Consumer => {
+            provider
+                .receive_link_config_as_target(component_id, config)
+                .await?;
+        }
+        LinkRole::Handler => {
+            provider
+                .receive_link_config_as_source(component_id, config)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Config of a server-mode provider on a free loopback port, with `extra` on top
+pub fn server_config(extra: &[(&str, &str)]) -> HashMap<String, String> {
+    let mut config = HashMap::from([
+        ("MODE".to_string(), "server".to_string()),
+        ("URI".to_string(), "127.0.0.1:0".to_string()),
+    ]);
+    for (key, value) in extra {
+        config.insert(key.to_string(), value.to_string());
+    }
+    config
+}
+
+/// Start a server-mode provider on a free loopback port, with `extra` config on top
+pub async fn start_server(extra: &[(&str, &str)]) -> Result<WebSocketMessagingProvider> {
+    serve(WebSocketMessagingProvider::from_config(server_config(
+        extra,
+    ))?)
+    .await
+}
+
+/// Start the server of a provider built from [`server_config`], e.g. one given a clock
+/// or resume registry first
+pub async fn serve(mut provider: WebSocketMessagingProvider) -> Result<WebSocketMessagingProvider> {
+    provider.start_server_if_needed().await?;
+    Ok(provider)
+}
+
+/// `ws://` URL of a started server's WebSocket endpoint
+pub async fn server_url(provider: &WebSocketMessagingProvider) -> Result<String> {
+    let addr = provider
+        .get_server_addr()
+        .await
+        .context("server should be started")?;
+    Ok(format!("ws://{}/ws", addr))
+}
+
+/// Connect a client to a started server, returning it with the session id the server
+/// gave it
+pub async fn connect(provider: &WebSocketMessagingProvider) -> Result<(WsClient, String)> {
+    connect_with_query(provider, "").await
+}
+
+/// Connect a client with `query` (e.g. `?tenant=a`) appended to the server's URL,
+/// returning it with the session id the server gave it
+pub async fn connect_with_query(
+    provider: &WebSocketMessagingProvider,
+    query: &str,
+) -> Result<(WsClient, String)> {
+    let known = provider.list_ws_clients().await?;
+    let url = format!("{}{}", server_url(provider).await?, query);
+    let (ws, _) = tokio_tungstenite::connect_async(url).await?;
+    for _ in 0..100 {
+        let new = provider
+            .list_ws_clients()
+            .await?
+            .into_iter()
+            .find(|session_id| !known.contains(session_id));
+        if let Some(session_id) = new {
+            return Ok((ws, session_id));
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    bail!("client never registered")
+}
+
+/// Install a message sink on a server-mode provider recording the subject of every
+/// message it receives
+pub async fn record_subjects(provider: &WebSocketMessagingProvider) -> Result<Subjects> {
+    let subjects = Subjects::default();
+    let sink = Arc::clone(&subjects);
+    provider
+        .set_message_sink(
+            move |_session_id: String, msg: BrokerMessage| -> Result<()> {
+                sink.lock().unwrap().push(msg.subject);
+                Ok(())
+            },
+        )
+        .await?;
+    Ok(subjects)
+}
+
+/// The next connection made through a [`crate::mock::MockTransport`]
+///
+/// Panics if none is made within a second.
+pub async fn accept(remotes: &MockRemotes) -> MockRemote {
+    timeout(Duration::from_secs(1), remotes.accept())
+        .await
+        .expect("connection should be made")
+        .expect("transport should be alive")
+}
+
+type WebSocket = WebSocketStream<TcpStream>;
+
+/// Serve WebSocket connections on a loopback port with `serve`, until shut down
+async fn spawn<F, Fut>(serve: F) -> Result<(SocketAddr, ServerShutdown)>
+where
+    F: Fn(WebSocket) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let serve = Arc::new(serve);
+    let (stop, mut stopped) = oneshot::channel();
+    let task = tokio::spawn(async move {
+        // Dropped on the way out, which aborts the open connections
+        let mut connections = JoinSet::new();
+        loop {
+            tokio::select! {
+                _ = &mut stopped => break,
+                accepted = listener.accept() => {
+                    let Ok((stream, _)) = accepted else {
+                        break;
+                    };
+                    let serve = serve.clone();
+                    connections.spawn(async move {
+                        if let Ok(ws) = tokio_tungstenite::accept_async(stream).await {
+                            serve(ws).await;
+                        }
+                    });
+                }
+            }
+        }
+    });
+    Ok((
+        addr,
+        ServerShutdown {
+            stop: Some(stop),
+            task,
+        },
+    ))
+}
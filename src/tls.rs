@@ -0,0 +1,256 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{
+    ring, verify_tls12_signature, verify_tls13_signature, WebPkiSupportedAlgorithms,
+};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, ServerConfig, SignatureScheme};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::TlsConnector;
+use tokio_tungstenite::Connector;
+use tracing::warn;
+
+use crate::connection::ConnectionConfig;
+
+/// Connector for `wss://` connections built from the `TLS_*` settings, or `None` to use
+/// tokio-tungstenite's default (built-in web roots, no client certificate)
+///
+/// The files are read on every call, so a link picks up rotated certificates when it
+/// reconnects. A file that can't be read or holds no usable PEM fails with an error
+/// naming the setting and the path.
+pub(crate) fn connector(config: &ConnectionConfig) -> Result<Option<Connector>> {
+    if config.tls_ca_file.is_none()
+        && config.tls_client_cert_file.is_none()
+        && config.tls_client_key_file.is_none()
+        && !config.tls_insecure_skip_verify
+    {
+        return Ok(None);
+    }
+
+    let builder = if config.tls_insecure_skip_verify {
+        warn!(
+            "TLS_INSECURE_SKIP_VERIFY is set: server certificates of {} are not verified",
+            config.uri
+        );
+        ClientConfig::builder()
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(SkipServerVerification::new()))
+    } else {
+        let mut roots = RootCertStore::empty();
+        roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        if let Some(path) = &config.tls_ca_file {
+            for cert in read_certs("TLS_CA_FILE", path)? {
+                roots
+                    .add(cert)
+                    .with_context(|| format!("Invalid CA certificate in TLS_CA_FILE {}", path))?;
+            }
+        }
+        ClientConfig::builder().with_root_certificates(roots)
+    };
+
+    let tls = match (&config.tls_client_cert_file, &config.tls_client_key_file) {
+        (Some(cert_path), Some(key_path)) => {
+            let certs = read_certs("TLS_CLIENT_CERT_FILE", cert_path)?;
+            let key = read_key("TLS_CLIENT_KEY_FILE", key_path)?;
+            builder.with_client_auth_cert(certs, key).with_context(|| {
+                format!(
+                    "TLS_CLIENT_KEY_FILE {} does not match TLS_CLIENT_CERT_FILE {}",
+                    key_path, cert_path
+                )
+            })?
+        }
+        (None, None) => builder.with_no_client_auth(),
+        (Some(_), None) => bail!("TLS_CLIENT_CERT_FILE is set without TLS_CLIENT_KEY_FILE"),
+        (None, Some(_)) => bail!("TLS_CLIENT_KEY_FILE is set without TLS_CLIENT_CERT_FILE"),
+    };
+    Ok(Some(Connector::Rustls(Arc::new(tls))))
+}
+
+/// PEM certificate chain and private key the server presents on `wss://`, from
+/// `TLS_CERT_PATH` and `TLS_KEY_PATH`, or `None` to serve plain `ws://`
+///
+/// Both files are parsed here, before the server binds, so a missing or unusable pair
+/// fails the start with an error naming the setting and the path.
+pub(crate) fn server_identity(config: &ConnectionConfig) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+    let (cert_path, key_path) = match (&config.tls_cert_path, &config.tls_key_path) {
+        (Some(cert_path), Some(key_path)) => (cert_path, key_path),
+        (None, None) => return Ok(None),
+        (Some(_), None) => bail!("TLS_CERT_PATH is set without TLS_KEY_PATH"),
+        (None, Some(_)) => bail!("TLS_KEY_PATH is set without TLS_CERT_PATH"),
+    };
+    let certs = read_certs("TLS_CERT_PATH", cert_path)?;
+    let key = read_key("TLS_KEY_PATH", key_path)?;
+    ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .with_context(|| {
+            format!(
+                "TLS_KEY_PATH {} can't be used with TLS_CERT_PATH {}",
+                key_path, cert_path
+            )
+        })?;
+    let read = |key: &str, path: &str| {
+        std::fs::read(path).with_context(|| format!("Failed to read {} {}", key, path))
+    };
+    Ok(Some((
+        read("TLS_CERT_PATH", cert_path)?,
+        read("TLS_KEY_PATH", key_path)?,
+    )))
+}
+
+/// TLS to `host` over a socket the provider dialled, with the settings from `connector`
+///
+/// For connections whose frames pass through the provider on their way to tungstenite,
+/// which then has to be handed a stream that is already encrypted.
+pub(crate) async fn handshake<S>(
+    connector: Option<Connector>,
+    host: &str,
+    stream: S,
+) -> Result<TlsStream<S>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let tls = match connector {
+        Some(Connector::Rustls(tls)) => tls,
+        _ => {
+            let mut roots = RootCertStore::empty();
+            roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+            Arc::new(
+                ClientConfig::builder()
+                    .with_root_certificates(roots)
+                    .with_no_client_auth(),
+            )
+        }
+    };
+    let server_name = ServerName::try_from(host)
+        .with_context(|| format!("{} is not a valid TLS server name", host))?
+        .to_owned();
+    TlsConnector::from(tls)
+        .connect(server_name, stream)
+        .await
+        .context("TLS handshake failed")
+}
+
+fn open(key: &str, path: &str) -> Result<BufReader<File>> {
+    let file = File::open(path).with_context(|| format!("Failed to read {} {}", key, path))?;
+    Ok(BufReader::new(file))
+}
+
+/// Every certificate in a PEM file, failing if there are none
+fn read_certs(key: &str, path: &str) -> Result<Vec<CertificateDer<'static>>> {
+    let certs = rustls_pemfile::certs(&mut open(key, path)?)
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Failed to parse {} {}", key, path))?;
+    if certs.is_empty() {
+        bail!("{} {} holds no PEM certificates", key, path);
+    }
+    Ok(certs)
+}
+
+/// The first private key (PKCS#1, PKCS#8 or SEC1) in a PEM file
+fn read_key(key: &str, path: &str) -> Result<PrivateKeyDer<'static>> {
+    rustls_pemfile::private_key(&mut open(key, path)?)
+        .with_context(|| format!("Failed to parse {} {}", key, path))?
+        .with_context(|| format!("{} {} holds no PEM private key", key, path))
+}
+
+/// `TLS_INSECURE_SKIP_VERIFY`: accepts any certificate, but still checks handshake
+/// signatures so the connection is at least bound to the certificate presented
+#[derive(Debug)]
+struct SkipServerVerification {
+    algorithms: WebPkiSupportedAlgorithms,
+}
+
+impl SkipServerVerification {
+    fn new() -> Self {
+        Self {
+            algorithms: ring::default_provider().signature_verification_algorithms,
+        }
+    }
+}
+
+impl ServerCertVerifier for SkipServerVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn config(entries: &[(&str, &str)]) -> ConnectionConfig {
+        let map: HashMap<String, String> = entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        ConnectionConfig::from_map(&map).unwrap()
+    }
+
+    fn error(entries: &[(&str, &str)]) -> String {
+        match connector(&config(entries)) {
+            Ok(_) => panic!("{:?} should be rejected", entries),
+            Err(e) => format!("{:#}", e),
+        }
+    }
+
+    #[test]
+    fn test_default_without_tls_settings() {
+        assert!(connector(&config(&[])).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_missing_file_named() {
+        let message = error(&[("TLS_CA_FILE", "/nonexistent/ca.pem")]);
+        assert!(
+            message.contains("TLS_CA_FILE /nonexistent/ca.pem"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn test_client_cert_needs_key() {
+        let message = error(&[("TLS_CLIENT_CERT_FILE", "client.pem")]);
+        assert!(
+            message.contains("without TLS_CLIENT_KEY_FILE"),
+            "{}",
+            message
+        );
+    }
+}
@@ -0,0 +1,585 @@
+use std::collections::HashMap;
+use std::pin::Pin;
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use futures::future::BoxFuture;
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::handshake::client::{Request, Response};
+use tokio_tungstenite::tungstenite::http::header::{
+    HeaderName, HeaderValue, AUTHORIZATION, SEC_WEBSOCKET_EXTENSIONS, SEC_WEBSOCKET_PROTOCOL,
+};
+use tokio_tungstenite::tungstenite::{Error as WsError, Message};
+use tokio_tungstenite::{
+    client_async_tls_with_config, client_async_with_config, connect_async_tls_with_config,
+    MaybeTlsStream, WebSocketStream,
+};
+
+use crate::connection::ConnectionConfig;
+use crate::deflate::{self, DeflateLimits, DeflateStream};
+use crate::platform;
+use crate::proxy::Proxy;
+use crate::tls;
+
+/// Outgoing half of an established WebSocket connection
+pub type WsSink = Pin<Box<dyn Sink<Message, Error = WsError> + Send>>;
+
+/// Incoming half of an established WebSocket connection
+pub type WsStream = Pin<Box<dyn Stream<Item = Result<Message, WsError>> + Send>>;
+
+/// An established client-mode connection
+pub struct WsConnection {
+    pub sink: WsSink,
+    pub stream: WsStream,
+    /// Headers of the server's upgrade response, names lowercased
+    pub response_headers: HashMap<String, String>,
+}
+
+/// Establishes client-mode WebSocket connections
+///
+/// The provider dials every link through its transport, so swapping the transport lets
+/// tests drive the client side without binding or dialling real sockets.
+pub trait WsTransport: Send + Sync + 'static {
+    /// Open a connection to `config.uri`
+    fn connect<'a>(&'a self, config: &'a ConnectionConfig) -> BoxFuture<'a, Result<WsConnection>>;
+}
+
+/// Default transport dialling real sockets with tokio-tungstenite
+///
+/// `HEADER_*` entries are sent as headers of the upgrade request, and `AUTH_TOKEN` (or the
+/// provider's token provider) as the header `AUTH_SCHEME` calls for unless a `HEADER_*`
+/// entry sets that header explicitly. `wss://` connections verify the server against the
+/// built-in web roots plus `TLS_CA_FILE`, and present `TLS_CLIENT_CERT_FILE` if set.
+/// `PERMESSAGE_DEFLATE=true` offers the extension and, if the server accepts it, runs the
+/// connection through a `DeflateStream` holding inbound messages to `MAX_MESSAGE_BYTES`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TungsteniteTransport;
+
+impl WsTransport for TungsteniteTransport {
+    fn connect<'a>(&'a self, config: &'a ConnectionConfig) -> BoxFuture<'a, Result<WsConnection>> {
+        Box::pin(async move {
+            let keepalive = config.tcp_keepalive();
+            let deflate = config.permessage_deflate;
+            let request = handshake_request(config)?;
+            let connector = tls::connector(config)?;
+            if keepalive.is_none() && config.proxy_url.is_none() && !deflate {
+                let (ws_stream, response) =
+                    connect_async_tls_with_config(request, None, false, connector)
+                        .await
+                        .context("Failed to connect to WebSocket")?;
+                return Ok(established(ws_stream, &response));
+            }
+
+            // Dial the socket ourselves so it can go through the proxy, keepalive can be
+            // set before the handshake, and compressed frames can be translated
+            let host = request
+                .uri()
+                .host()
+                .context("WebSocket URI has no host")?
+                .trim_matches(|c| c == '[' || c == ']');
+            let secure = request.uri().scheme_str() == Some("wss");
+            let port = request
+                .uri()
+                .port_u16()
+                .unwrap_or(if secure { 443 } else { 80 });
+            let stream = match &config.proxy_url {
+                Some(proxy_url) => Proxy::parse(proxy_url)?
+                    .dial(host, port)
+                    .await
+                    .context("Failed to connect to WebSocket through proxy")?,
+                None => TcpStream::connect((host, port))
+                    .await
+                    .context("Failed to connect to WebSocket")?,
+            };
+            if let Some(idle) = keepalive {
+                platform::set_tcp_keepalive(&stream, idle)?;
+            }
+            if !deflate {
+                let (ws_stream, response) =
+                    client_async_tls_with_config(request, stream, None, connector)
+                        .await
+                        .context("Failed to connect to WebSocket")?;
+                return Ok(established(ws_stream, &response));
+            }
+
+            // The frames are translated inside TLS, so encrypt before tungstenite
+            let stream = if secure {
+                MaybeTlsStream::Rustls(tls::handshake(connector, host, stream).await?)
+            } else {
+                MaybeTlsStream::Plain(stream)
+            };
+            let stream = DeflateStream::new(stream, DeflateLimits::from_config(config));
+            let (ws_stream, response) = client_async_with_config(request, stream, None)
+                .await
+                .context("Failed to connect to WebSocket")?;
+            Ok(established(ws_stream, &response))
+        })
+    }
+}
+
+/// Split an established connection into the halves the provider drives
+fn established<S>(ws_stream: WebSocketStream<S>, response: &Response) -> WsConnection
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let response_headers = response
+        .headers()
+        .iter()
+        .filter_map(|(name, value)| {
+            Some((name.as_str().to_string(), value.to_str().ok()?.to_string()))
+        })
+        .collect();
+    let (sink, stream) = ws_stream.split();
+    WsConnection {
+        sink: Box::pin(sink),
+        stream: Box::pin(stream),
+        response_headers,
+    }
+}
+
+/// How `AUTH_TOKEN` is presented in the upgrade request
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthScheme {
+    /// `Authorization: Bearer <token>`
+    #[default]
+    Bearer,
+    /// `Authorization: Basic <base64 of token>`, for an `AUTH_TOKEN` of `user:password`
+    Basic,
+    /// The token as-is in the named header, e.g. `X-Api-Token: <token>`
+    Header(String),
+}
+
+impl AuthScheme {
+    /// Parse an `AUTH_SCHEME` value: `bearer`, `basic` (case-insensitive) or
+    /// `header:<name>`
+    pub(crate) fn parse(value: &str) -> Result<Self> {
+        if let Some(name) = value.strip_prefix("header:") {
+            HeaderName::from_bytes(name.as_bytes()).map_err(|_| {
+                anyhow!(
+                    "AUTH_SCHEME header {} is not a valid HTTP header name",
+                    name
+                )
+            })?;
+            return Ok(Self::Header(name.to_string()));
+        }
+        match value.to_lowercase().as_str() {
+            "bearer" => Ok(Self::Bearer),
+            "basic" => Ok(Self::Basic),
+            _ => bail!(
+                "Invalid AUTH_SCHEME {}: expected bearer, basic or header:<name>",
+                value
+            ),
+        }
+    }
+
+    /// Header carrying `token` under this scheme
+    fn header(&self, token: &str) -> Result<(HeaderName, HeaderValue)> {
+        let (name, value) = match self {
+            Self::Bearer => (AUTHORIZATION, format!("Bearer {}", token)),
+            Self::Basic => (AUTHORIZATION, format!("Basic {}", STANDARD.encode(token))),
+            Self::Header(name) => (HeaderName::from_bytes(name.as_bytes())?, token.to_string()),
+        };
+        let value =
+            HeaderValue::from_str(&value).context("Auth token is not a valid header value")?;
+        Ok((name, value))
+    }
+}
+
+/// Upgrade request for `config.uri` carrying the `HEADER_*` entries and the auth token
+///
+/// An explicit `HEADER_*` entry for the header `AUTH_SCHEME` puts the token in wins over
+/// `AUTH_TOKEN`.
+pub(crate) fn handshake_request(config: &ConnectionConfig) -> Result<Request> {
+    let mut request = config.uri.as_str().into_client_request()?;
+    for (name, value) in &config.custom_headers {
+        let (name, value) = handshake_header(name, value)?;
+        request.headers_mut().insert(name, value);
+    }
+    if let Some(token) = &config.auth_token {
+        let (name, value) = config.auth_scheme.header(token)?;
+        if !request.headers().contains_key(&name) {
+            request.headers_mut().insert(name, value);
+        }
+    }
+    if !config.subprotocols.is_empty() {
+        let protocols = HeaderValue::from_str(&config.subprotocols.join(", "))?;
+        request
+            .headers_mut()
+            .insert(SEC_WEBSOCKET_PROTOCOL, protocols);
+    }
+    if config.permessage_deflate {
+        request.headers_mut().insert(
+            SEC_WEBSOCKET_EXTENSIONS,
+            HeaderValue::from_static(deflate::PERMESSAGE_DEFLATE),
+        );
+    }
+    Ok(request)
+}
+
+/// The compression in effect on a connection, if `PERMESSAGE_DEFLATE` offered it: the
+/// extension when the server accepted it, `none` when it declined
+pub(crate) fn negotiated_compression(
+    config: &ConnectionConfig,
+    response_headers: &HashMap<String, String>,
+) -> Option<&'static str> {
+    if !config.permessage_deflate {
+        return None;
+    }
+    let extensions = response_headers
+        .get(SEC_WEBSOCKET_EXTENSIONS.as_str())
+        .map(String::as_str);
+    Some(if deflate::negotiated(extensions) {
+        deflate::PERMESSAGE_DEFLATE
+    } else {
+        "none"
+    })
+}
+
+/// The subprotocol the server selected in its upgrade response, which must be one of
+/// `SUBPROTOCOLS`
+///
+/// tungstenite doesn't check the selection, so a server picking a protocol that wasn't
+/// offered (or any protocol when none was) fails the connection here.
+pub(crate) fn selected_subprotocol(
+    config: &ConnectionConfig,
+    response_headers: &HashMap<String, String>,
+) -> Result<Option<String>> {
+    let Some(selected) = response_headers.get(SEC_WEBSOCKET_PROTOCOL.as_str()) else {
+        return Ok(None);
+    };
+    if !config
+        .subprotocols
+        .iter()
+        .any(|offered| offered == selected)
+    {
+        if config.subprotocols.is_empty() {
+            bail!(
+                "Server selected subprotocol {} although none was requested",
+                selected
+            );
+        }
+        bail!(
+            "Server selected subprotocol {} which was not requested (requested: {})",
+            selected,
+            config.subprotocols.join(", ")
+        );
+    }
+    Ok(Some(selected.clone()))
+}
+
+/// A `HEADER_<name>` entry as a header of the upgrade request
+pub(crate) fn handshake_header(name: &str, value: &str) -> Result<(HeaderName, HeaderValue)> {
+    let header_name = HeaderName::from_bytes(name.as_bytes())
+        .map_err(|_| anyhow!("HEADER_{} is not a valid HTTP header name", name))?;
+    let header_value = HeaderValue::from_str(value)
+        .map_err(|_| anyhow!("Value of HEADER_{} is not a valid HTTP header value", name))?;
+    Ok((header_name, header_value))
+}
+
+/// In-memory transport for tests, available with the `test-util` feature
+#[cfg(feature = "test-util")]
+pub mod mock {
+    use super::*;
+
+    use futures::channel::mpsc as fmpsc;
+    use std::path::Path;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::{mpsc, Mutex};
+
+    use crate::capture::{read_capture, CapturedFrame, FrameDirection};
+
+    /// Transport whose connections terminate in [`MockRemote`] handles instead of sockets
+    ///
+    /// Every successful `connect` hands the remote end of the new connection to the
+    /// paired [`MockRemotes`] receiver.
+    #[derive(Debug, Clone)]
+    pub struct MockTransport {
+        remotes: mpsc::UnboundedSender<MockRemote>,
+        refuse: Arc<AtomicBool>,
+        response_headers: Arc<std::sync::Mutex<HashMap<String, String>>>,
+        write_capacity: Arc<std::sync::Mutex<Option<usize>>>,
+    }
+
+    /// Receiver for the remote ends of connections made through a [`MockTransport`]
+    #[derive(Debug)]
+    pub struct MockRemotes {
+        rx: Mutex<mpsc::UnboundedReceiver<MockRemote>>,
+    }
+
+    /// The "server" end of one in-memory connection
+    #[derive(Debug)]
+    pub struct MockRemote {
+        uri: String,
+        auth_token: Option<String>,
+        to_client: fmpsc::UnboundedSender<Result<Message, WsError>>,
+        from_client: futures::stream::BoxStream<'static, Message>,
+    }
+
+    impl MockTransport {
+        /// Create a transport and the receiver for its connections' remote ends
+        pub fn new() -> (Self, MockRemotes) {
+            let (tx, rx) = mpsc::unbounded_channel();
+            (
+                Self {
+                    remotes: tx,
+                    refuse: Arc::new(AtomicBool::new(false)),
+                    response_headers: Arc::default(),
+                    write_capacity: Arc::default(),
+                },
+                MockRemotes { rx: Mutex::new(rx) },
+            )
+        }
+
+        /// Include a header in the upgrade response of subsequent connections
+        pub fn set_response_header(&self, name: &str, value: &str) {
+            self.response_headers
+                .lock()
+                .unwrap()
+                .insert(name.to_lowercase(), value.to_string());
+        }
+
+        /// Bound how many frames subsequent connections can write before the remote reads
+        /// them (`None` = unbounded), so a remote that doesn't read applies backpressure
+        pub fn set_write_capacity(&self, capacity: Option<usize>) {
+            *self.write_capacity.lock().unwrap() = capacity;
+        }
+
+        /// Make subsequent connection attempts fail (or succeed again)
+        pub fn refuse_connections(&self, refuse: bool) {
+            self.refuse.store(refuse, Ordering::SeqCst);
+        }
+    }
+
+    impl WsTransport for MockTransport {
+        fn connect<'a>(
+            &'a self,
+            config: &'a ConnectionConfig,
+        ) -> BoxFuture<'a, Result<WsConnection>> {
+            Box::pin(async move {
+                if self.refuse.load(Ordering::SeqCst) {
+                    anyhow::bail!("Connection refused by mock transport: {}", config.uri);
+                }
+
+                let (to_client, client_rx) = fmpsc::unbounded();
+                let (sink, from_client): (WsSink, _) = match *self.write_capacity.lock().unwrap() {
+                    Some(capacity) => {
+                        let (tx, rx) = fmpsc::channel(capacity);
+                        (
+                            Box::pin(tx.sink_map_err(|_| WsError::ConnectionClosed)),
+                            rx.boxed(),
+                        )
+                    }
+                    None => {
+                        let (tx, rx) = fmpsc::unbounded();
+                        (
+                            Box::pin(tx.sink_map_err(|_| WsError::ConnectionClosed)),
+                            rx.boxed(),
+                        )
+                    }
+                };
+                self.remotes
+                    .send(MockRemote {
+                        uri: config.uri.clone(),
+                        auth_token: config.auth_token.clone(),
+                        to_client,
+                        from_client,
+                    })
+                    .context("Mock transport remotes receiver dropped")?;
+
+                Ok(WsConnection {
+                    sink,
+                    stream: Box::pin(client_rx),
+                    response_headers: self.response_headers.lock().unwrap().clone(),
+                })
+            })
+        }
+    }
+
+    impl MockRemotes {
+        /// Wait for the next connection made through the transport
+        pub async fn accept(&self) -> Option<MockRemote> {
+            self.rx.lock().await.recv().await
+        }
+    }
+
+    impl MockRemote {
+        /// URI the client dialled
+        pub fn uri(&self) -> &str {
+            &self.uri
+        }
+
+        /// Auth token the client presented when it connected
+        pub fn auth_token(&self) -> Option<&str> {
+            self.auth_token.as_deref()
+        }
+
+        /// Deliver a frame to the client
+        pub fn send(&self, msg: Message) -> Result<()> {
+            self.to_client
+                .unbounded_send(Ok(msg))
+                .context("Client side of mock connection closed")
+        }
+
+        /// Fail the client's stream with a transport error
+        pub fn fail(&self, error: WsError) -> Result<()> {
+            self.to_client
+                .unbounded_send(Err(error))
+                .context("Client side of mock connection closed")
+        }
+
+        /// Next frame sent by the client, `None` once the client side is gone
+        pub async fn recv(&mut self) -> Option<Message> {
+            self.from_client.next().await
+        }
+
+        /// Drop the connection without a close frame, ending the client's stream
+        pub fn disconnect(self) {}
+    }
+
+    /// Feeds the inbound frames of a capture file to a [`MockRemote`]
+    ///
+    /// Outbound frames are what the provider sent when the capture was made; they are not
+    /// replayed but are available through [`FrameReplayer::outbound`] for comparison.
+    #[derive(Debug, Clone)]
+    pub struct FrameReplayer {
+        frames: Vec<CapturedFrame>,
+        original_timing: bool,
+    }
+
+    impl FrameReplayer {
+        /// Load a capture written with `CAPTURE_PATH`
+        pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+            Ok(Self::new(read_capture(path)?))
+        }
+
+        /// Replay the given frames, as fast as possible by default
+        pub fn new(frames: Vec<CapturedFrame>) -> Self {
+            Self {
+                frames,
+                original_timing: false,
+            }
+        }
+
+        /// Keep the original gaps between frames instead of sending them back to back
+        pub fn with_original_timing(mut self) -> Self {
+            self.original_timing = true;
+            self
+        }
+
+        /// Frames the provider sent in the capture
+        pub fn outbound(&self) -> impl Iterator<Item = &CapturedFrame> {
+            self.frames
+                .iter()
+                .filter(|frame| frame.direction == FrameDirection::Outbound)
+        }
+
+        /// Send every inbound frame to the client, returning how many were sent
+        pub async fn replay(&self, remote: &MockRemote) -> Result<usize> {
+            let mut sent = 0;
+            let mut last_ts = None;
+            for frame in &self.frames {
+                if frame.direction != FrameDirection::Inbound {
+                    continue;
+                }
+                if let (true, Some(last)) = (self.original_timing, last_ts) {
+                    let gap = frame.ts_ms.saturating_sub(last);
+                    tokio::time::sleep(Duration::from_millis(gap)).await;
+                }
+                last_ts = Some(frame.ts_ms);
+                remote.send(frame.to_message()?)?;
+                sent += 1;
+            }
+            Ok(sent)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(entries: &[(&str, &str)]) -> Request {
+        let mut map = HashMap::from([("URI".to_string(), "ws://example.com/ws".to_string())]);
+        for (key, value) in entries {
+            map.insert(key.to_string(), value.to_string());
+        }
+        handshake_request(&ConnectionConfig::from_map(&map).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_auth_token_sent_as_bearer() {
+        let request = request(&[("AUTH_TOKEN", "secret"), ("HEADER_X-Tenant-Id", "acme")]);
+        assert_eq!(request.headers()[AUTHORIZATION], "Bearer secret");
+        assert_eq!(request.headers()["x-tenant-id"], "acme");
+    }
+
+    #[test]
+    fn test_explicit_authorization_header_wins() {
+        let request = request(&[
+            ("AUTH_TOKEN", "secret"),
+            ("HEADER_Authorization", "Basic dXNlcjpwYXNz"),
+        ]);
+        let values: Vec<_> = request.headers().get_all(AUTHORIZATION).iter().collect();
+        assert_eq!(values, vec!["Basic dXNlcjpwYXNz"]);
+    }
+
+    #[test]
+    fn test_auth_schemes() {
+        let request = request(&[("AUTH_TOKEN", "user:pass"), ("AUTH_SCHEME", "Basic")]);
+        assert_eq!(request.headers()[AUTHORIZATION], "Basic dXNlcjpwYXNz");
+
+        let request = request_with_scheme("header:X-Api-Token");
+        assert_eq!(request.headers()["x-api-token"], "secret");
+        assert!(!request.headers().contains_key(AUTHORIZATION));
+    }
+
+    fn request_with_scheme(scheme: &str) -> Request {
+        request(&[("AUTH_TOKEN", "secret"), ("AUTH_SCHEME", scheme)])
+    }
+
+    #[test]
+    fn test_explicit_header_wins_for_custom_scheme() {
+        let request = request(&[
+            ("AUTH_TOKEN", "secret"),
+            ("AUTH_SCHEME", "header:X-Api-Token"),
+            ("HEADER_X-Api-Token", "explicit"),
+        ]);
+        assert_eq!(request.headers()["x-api-token"], "explicit");
+    }
+
+    #[test]
+    fn test_parse_auth_scheme() {
+        assert_eq!(AuthScheme::parse("BEARER").unwrap(), AuthScheme::Bearer);
+        assert_eq!(
+            AuthScheme::parse("header:X-Key").unwrap(),
+            AuthScheme::Header("X-Key".to_string())
+        );
+        assert!(AuthScheme::parse("digest").is_err());
+        assert!(AuthScheme::parse("header:X Key").is_err());
+    }
+
+    #[test]
+    fn test_no_authorization_without_token() {
+        assert!(!request(&[]).headers().contains_key(AUTHORIZATION));
+    }
+
+    #[test]
+    fn test_permessage_deflate_offered() {
+        let request = request(&[("PERMESSAGE_DEFLATE", "true")]);
+        assert_eq!(
+            request.headers()[SEC_WEBSOCKET_EXTENSIONS],
+            "permessage-deflate"
+        );
+        assert!(!request(&[])
+            .headers()
+            .contains_key(SEC_WEBSOCKET_EXTENSIONS));
+    }
+}
@@ -0,0 +1,111 @@
+use anyhow::Result;
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::mock::{MockRemote, MockTransport};
+use wasmcloud_provider_messaging_websocket::{
+    BodyCompression, BrokerMessage, WebSocketMessagingProvider,
+};
+
+const COMPONENT: &str = "test-consumer";
+
+async fn adaptive_link() -> Result<(WebSocketMessagingProvider, MockRemote)> {
+    let mut config = HashMap::new();
+    config.insert("MODE".to_string(), "client".to_string());
+    config.insert("URI".to_string(), "ws://mock.invalid/ws".to_string());
+    let (transport, remotes) = MockTransport::new();
+    let provider = WebSocketMessagingProvider::from_config(config)?.with_transport(transport);
+
+    let mut link = HashMap::new();
+    link.insert("BODY_COMPRESSION".to_string(), "adaptive".to_string());
+    link.insert("COMPRESS_THRESHOLD_BYTES".to_string(), "64".to_string());
+    provider
+        .receive_link_config_as_target(COMPONENT, link)
+        .await?;
+    let remote = timeout(Duration::from_secs(1), remotes.accept())
+        .await?
+        .expect("transport should be alive");
+    Ok((provider, remote))
+}
+
+/// Publish `body` and return whether it went out compressed
+async fn publish(
+    provider: &WebSocketMessagingProvider,
+    remote: &mut MockRemote,
+    body: Vec<u8>,
+) -> Result<bool> {
+    let msg = BrokerMessage {
+        subject: "data".to_string(),
+        body: Bytes::from(body),
+        reply_to: None,
+        headers: HashMap::new(),
+    };
+    provider.publish(COMPONENT, msg).await?;
+    let frame = timeout(Duration::from_secs(1), remote.recv())
+        .await?
+        .expect("client should still be connected");
+    let Message::Text(text) = frame else {
+        anyhow::bail!("expected a text frame, got {:?}", frame)
+    };
+    let json: serde_json::Value = serde_json::from_str(&text)?;
+    Ok(json["headers"]["content-encoding"] == "zstd")
+}
+
+/// Bytes that zstd can't shrink
+fn noise(len: usize, seed: u64) -> Vec<u8> {
+    let mut state = seed | 1;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+async fn compressing(provider: &WebSocketMessagingProvider) -> bool {
+    provider.client_stats(COMPONENT).await.unwrap().compressing
+}
+
+/// Test that adaptive compression pauses after sampling incompressible bodies and resumes
+/// once compressible bodies flow again
+#[tokio::test]
+async fn test_adaptive_compression_follows_payloads() -> Result<()> {
+    let (provider, mut remote) = adaptive_link().await?;
+    let stats = provider.client_stats(COMPONENT).await.unwrap();
+    assert_eq!(stats.body_compression, BodyCompression::Adaptive);
+    assert!(stats.compressing);
+    assert_eq!(stats.compression_ratio, None);
+
+    let mut sampled = 0;
+    while compressing(&provider).await {
+        sampled += 1;
+        assert!(sampled <= 16, "compression never paused");
+        assert!(!publish(&provider, &mut remote, noise(4096, sampled)).await?);
+    }
+    let ratio = provider
+        .client_stats(COMPONENT)
+        .await
+        .unwrap()
+        .compression_ratio
+        .unwrap();
+    assert!(ratio > 0.9, "{}", ratio);
+
+    // Compressible bodies go out as-is until a periodic probe notices them
+    let text = br#"{"reading":42,"unit":"celsius"}"#.repeat(100);
+    let mut sent_plain = 0;
+    while !publish(&provider, &mut remote, text.clone()).await? {
+        sent_plain += 1;
+        assert!(sent_plain <= 64, "compression never resumed");
+    }
+    assert!(sent_plain > 0);
+    assert!(compressing(&provider).await);
+    assert!(publish(&provider, &mut remote, text).await?);
+
+    provider.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,98 @@
+use anyhow::Result;
+use futures::StreamExt;
+use std::collections::HashMap;
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::StatusCode;
+
+use wasmcloud_provider_messaging_websocket::WebSocketMessagingProvider;
+
+/// Start a server that refuses every upgrade request without `header: value`, returning
+/// its URI
+async fn start_protected_server(header: &'static str, value: &'static str) -> Result<String> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let check = |request: &Request, response: Response| {
+                    if request.headers().get(header).is_some_and(|v| v == value) {
+                        return Ok(response);
+                    }
+                    let mut refusal = ErrorResponse::new(Some("missing credentials".into()));
+                    *refusal.status_mut() = StatusCode::UNAUTHORIZED;
+                    Err(refusal)
+                };
+                // Hold the connection open until the client goes away
+                if let Ok(mut ws) = tokio_tungstenite::accept_hdr_async(stream, check).await {
+                    while let Some(Ok(_)) = ws.next().await {}
+                }
+            });
+        }
+    });
+    Ok(format!("ws://{}/ws", addr))
+}
+
+async fn link(uri: &str, auth: &[(&str, &str)]) -> Result<()> {
+    let mut config = HashMap::from([("URI".to_string(), uri.to_string())]);
+    for (key, value) in auth {
+        config.insert(key.to_string(), value.to_string());
+    }
+    let provider = WebSocketMessagingProvider::from_config(config)?;
+    let linked = provider
+        .receive_link_config_as_target("publisher", HashMap::new())
+        .await
+        .map(|_| ());
+    provider.shutdown().await?;
+    linked
+}
+
+/// Test that a bearer token gets through a server requiring it, and nothing does without
+#[tokio::test]
+async fn test_bearer_token_required() -> Result<()> {
+    let uri = start_protected_server("authorization", "Bearer secret").await?;
+    link(&uri, &[("AUTH_TOKEN", "secret")]).await?;
+    assert!(link(&uri, &[]).await.is_err());
+    assert!(link(&uri, &[("AUTH_TOKEN", "wrong")]).await.is_err());
+    Ok(())
+}
+
+/// Test that `AUTH_SCHEME=basic` sends `user:password` as basic credentials
+#[tokio::test]
+async fn test_basic_scheme() -> Result<()> {
+    let uri = start_protected_server("authorization", "Basic dXNlcjpwYXNz").await?;
+    link(
+        &uri,
+        &[("AUTH_TOKEN", "user:pass"), ("AUTH_SCHEME", "basic")],
+    )
+    .await?;
+    assert!(link(&uri, &[("AUTH_TOKEN", "user:pass")]).await.is_err());
+    Ok(())
+}
+
+/// Test that `AUTH_SCHEME=header:<name>` sends the raw token in that header
+#[tokio::test]
+async fn test_custom_header_scheme() -> Result<()> {
+    let uri = start_protected_server("x-api-token", "secret").await?;
+    let auth = [
+        ("AUTH_TOKEN", "secret"),
+        ("AUTH_SCHEME", "header:X-Api-Token"),
+    ];
+    link(&uri, &auth).await?;
+    assert!(link(&uri, &[("AUTH_TOKEN", "secret")]).await.is_err());
+    Ok(())
+}
+
+/// Test that an unknown scheme fails the config instead of connecting without credentials
+#[tokio::test]
+async fn test_invalid_scheme_rejected() -> Result<()> {
+    let config = HashMap::from([
+        ("URI".to_string(), "ws://127.0.0.1:1/ws".to_string()),
+        ("AUTH_SCHEME".to_string(), "digest".to_string()),
+    ]);
+    let error = WebSocketMessagingProvider::from_config(config)
+        .err()
+        .expect("config should be rejected");
+    assert!(format!("{:#}", error).contains("Invalid AUTH_SCHEME digest"));
+    Ok(())
+}
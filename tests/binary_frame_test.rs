@@ -0,0 +1,97 @@
+use anyhow::Result;
+use bytes::Bytes;
+use futures::SinkExt;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::mock::MockTransport;
+use wasmcloud_provider_messaging_websocket::testing::{server_url, start_server};
+use wasmcloud_provider_messaging_websocket::{BrokerMessage, WebSocketMessagingProvider};
+
+const LARGE: usize = 1 << 20;
+
+/// A binary frame holding a JSON envelope with a large text body
+fn large_utf8_frame(subject: &str) -> Vec<u8> {
+    format!(
+        r#"{{"subject":"{}","body":"{}"}}"#,
+        subject,
+        "é".repeat(LARGE / 2)
+    )
+    .into_bytes()
+}
+
+/// A large binary frame that is not valid UTF-8
+fn large_invalid_frame() -> Vec<u8> {
+    let mut data = vec![b'a'; LARGE];
+    data[LARGE / 2] = 0xff;
+    data
+}
+
+/// Test that server clients' binary frames are parsed as text when valid UTF-8 and
+/// delivered byte-for-byte otherwise
+#[tokio::test]
+async fn test_server_binary_frames() -> Result<()> {
+    let provider = start_server(&[]).await?;
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    provider
+        .set_message_sink(
+            move |_session_id: String, msg: BrokerMessage| -> Result<()> {
+                tx.send(msg)?;
+                Ok(())
+            },
+        )
+        .await?;
+
+    let url = server_url(&provider).await?;
+    let (mut ws, _) = tokio_tungstenite::connect_async(url.as_str()).await?;
+    let invalid = large_invalid_frame();
+    ws.send(Message::Binary(large_utf8_frame("big.text")))
+        .await?;
+    ws.send(Message::Binary(invalid.clone())).await?;
+
+    let text = timeout(Duration::from_secs(2), rx.recv()).await?.unwrap();
+    assert_eq!(text.subject, "big.text");
+    assert_eq!(text.body.len(), LARGE);
+
+    let binary = timeout(Duration::from_secs(2), rx.recv()).await?.unwrap();
+    assert_eq!(binary.subject, "binary.message");
+    assert_eq!(binary.body, Bytes::from(invalid));
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that binary frames from a remote server reach handlers whether or not they are UTF-8
+#[tokio::test]
+async fn test_client_binary_frames() -> Result<()> {
+    let mut config = HashMap::new();
+    config.insert("URI".to_string(), "ws://mock.invalid/ws".to_string());
+    let (transport, remotes) = MockTransport::new();
+    let provider = WebSocketMessagingProvider::from_config(config)?.with_transport(transport);
+    provider
+        .receive_link_config_as_source("handler", HashMap::new())
+        .await?;
+    let mut remote = timeout(Duration::from_secs(1), remotes.accept())
+        .await?
+        .expect("connection should be made");
+
+    for (frame, subject) in [
+        (large_utf8_frame("big.text"), "big.text"),
+        (large_invalid_frame(), "binary.message"),
+    ] {
+        remote.send(Message::Binary(frame))?;
+        let forwarded = timeout(Duration::from_secs(2), remote.recv()).await?;
+        let Some(Message::Text(text)) = forwarded else {
+            panic!("expected a forwarded text frame, got {:?}", forwarded);
+        };
+        let json: serde_json::Value = serde_json::from_str(&text)?;
+        assert_eq!(json["subject"], subject);
+    }
+
+    provider.shutdown().await?;
+    Ok(())
+}
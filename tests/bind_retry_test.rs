@@ -0,0 +1,59 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::net::TcpListener;
+use std::time::Duration;
+
+use wasmcloud_provider_messaging_websocket::WebSocketMessagingProvider;
+
+fn server_config(addr: &str, extra: &[(&str, &str)]) -> HashMap<String, String> {
+    let mut config = HashMap::new();
+    config.insert("MODE".to_string(), "server".to_string());
+    config.insert("URI".to_string(), addr.to_string());
+    for (key, value) in extra {
+        config.insert(key.to_string(), value.to_string());
+    }
+    config
+}
+
+/// Test that the server binds once a briefly held port is released
+#[tokio::test]
+async fn test_bind_retried_until_port_frees() -> Result<()> {
+    let held = TcpListener::bind("127.0.0.1:0")?;
+    let addr = held.local_addr()?;
+    let releaser = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        drop(held);
+    });
+
+    let mut provider = WebSocketMessagingProvider::from_config(server_config(
+        &addr.to_string(),
+        &[
+            ("BIND_RETRY_ATTEMPTS", "40"),
+            ("BIND_RETRY_DELAY_MS", "50"),
+            ("BIND_REUSE_ADDR", "true"),
+        ],
+    ))?;
+    provider.start_server_if_needed().await?;
+    releaser.await?;
+    assert_eq!(provider.get_server_addr().await, Some(addr));
+
+    let (_ws, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws", addr)).await?;
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that without retries a port in use fails the start right away
+#[tokio::test]
+async fn test_bind_fails_without_retries() -> Result<()> {
+    let held = TcpListener::bind("127.0.0.1:0")?;
+    let addr = held.local_addr()?;
+
+    let mut provider =
+        WebSocketMessagingProvider::from_config(server_config(&addr.to_string(), &[]))?;
+    let error = provider
+        .start_server_if_needed()
+        .await
+        .expect_err("port is in use");
+    assert!(error.to_string().contains("Failed to bind"), "{:#}", error);
+    Ok(())
+}
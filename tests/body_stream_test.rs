@@ -0,0 +1,191 @@
+use anyhow::Result;
+use bytes::Bytes;
+use futures::{stream, Stream, StreamExt};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::{sleep, timeout};
+
+use wasmcloud_provider_messaging_websocket::testing::{server_url, start_server};
+use wasmcloud_provider_messaging_websocket::{
+    BodyStreamAborted, BrokerMessage, WebSocketMessagingProvider, STREAM_ID_HEADER,
+    STREAM_LENGTH_HEADER,
+};
+
+const TOTAL: usize = 50 * 1024 * 1024;
+/// Chunks handed to `publish_stream`; the provider splits them into smaller frames
+const CHUNK: usize = 256 * 1024;
+
+/// Byte at `offset` of the test body
+fn byte_at(offset: usize) -> u8 {
+    (offset % 251) as u8
+}
+
+fn body(total: usize) -> impl Stream<Item = Bytes> {
+    stream::iter((0..total).step_by(CHUNK)).map(move |start| {
+        let end = (start + CHUNK).min(total);
+        Bytes::from((start..end).map(byte_at).collect::<Vec<u8>>())
+    })
+}
+
+/// A server whose descriptors land on the returned channel, and a client linked to it
+async fn start() -> Result<(
+    WebSocketMessagingProvider,
+    WebSocketMessagingProvider,
+    mpsc::UnboundedReceiver<BrokerMessage>,
+)> {
+    let server = start_server(&[]).await?;
+
+    let (tx, rx) = mpsc::unbounded_channel();
+    server
+        .set_message_sink(
+            move |_session_id: String, msg: BrokerMessage| -> Result<()> {
+                tx.send(msg)?;
+                Ok(())
+            },
+        )
+        .await?;
+
+    let mut config = HashMap::new();
+    config.insert("MODE".to_string(), "client".to_string());
+    config.insert("URI".to_string(), server_url(&server).await?);
+    let client = WebSocketMessagingProvider::from_config(config)?;
+    client
+        .receive_link_config_as_target("uploader", HashMap::new())
+        .await?;
+    Ok((server, client, rx))
+}
+
+async fn next_descriptor(rx: &mut mpsc::UnboundedReceiver<BrokerMessage>) -> Result<BrokerMessage> {
+    Ok(timeout(Duration::from_secs(5), rx.recv())
+        .await?
+        .expect("descriptor message"))
+}
+
+fn aborted(error: &anyhow::Error) -> &BodyStreamAborted {
+    error
+        .downcast_ref::<BodyStreamAborted>()
+        .unwrap_or_else(|| panic!("expected BodyStreamAborted, got {}", error))
+}
+
+/// Test that 50 MB arrive intact through a local server while the reader lags behind
+#[tokio::test]
+async fn test_large_stream_with_slow_reader() -> Result<()> {
+    let (server, client, mut rx) = start().await?;
+
+    let publisher = client.clone();
+    let publish = tokio::spawn(async move {
+        let headers = HashMap::from([(STREAM_LENGTH_HEADER.to_string(), TOTAL.to_string())]);
+        publisher
+            .publish_stream("uploader", "files.upload", body(TOTAL), headers)
+            .await
+    });
+
+    let descriptor = next_descriptor(&mut rx).await?;
+    assert_eq!(descriptor.subject, "files.upload");
+    assert!(descriptor.body.is_empty());
+    assert_eq!(
+        descriptor.headers.get(STREAM_LENGTH_HEADER),
+        Some(&TOTAL.to_string())
+    );
+    // Resolved a little late, after the first chunks are already waiting
+    sleep(Duration::from_millis(20)).await;
+    let stream_id = &descriptor.headers[STREAM_ID_HEADER];
+    let mut chunks = server.subscribe_body_stream(stream_id)?;
+
+    let mut offset = 0;
+    let mut received = 0;
+    while let Some(chunk) = timeout(Duration::from_secs(10), chunks.next()).await? {
+        let chunk = chunk?;
+        assert!(
+            chunk
+                .iter()
+                .enumerate()
+                .all(|(i, byte)| *byte == byte_at(offset + i)),
+            "corrupt chunk at offset {}",
+            offset
+        );
+        offset += chunk.len();
+        received += 1;
+        // A reader that falls behind now and then
+        if received % 50 == 0 {
+            sleep(Duration::from_millis(5)).await;
+        }
+    }
+    assert_eq!(offset, TOTAL);
+    publish.await??;
+
+    client.shutdown().await?;
+    server.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a receiver dropping the stream fails the publish
+#[tokio::test]
+async fn test_receiver_cancel_aborts_publisher() -> Result<()> {
+    let (server, client, mut rx) = start().await?;
+
+    let publisher = client.clone();
+    let publish = tokio::spawn(async move {
+        publisher
+            .publish_stream("uploader", "files.upload", body(TOTAL), HashMap::new())
+            .await
+    });
+
+    let descriptor = next_descriptor(&mut rx).await?;
+    let mut chunks = server.subscribe_body_stream(&descriptor.headers[STREAM_ID_HEADER])?;
+    for _ in 0..3 {
+        chunks.next().await.expect("chunk")?;
+    }
+    drop(chunks);
+
+    let error = timeout(Duration::from_secs(5), publish)
+        .await??
+        .expect_err("publish should be cancelled");
+    assert_eq!(aborted(&error).reason, "cancelled by receiver");
+
+    client.shutdown().await?;
+    server.shutdown().await?;
+    Ok(())
+}
+
+/// Test that losing the connection mid-stream fails both the publish and the subscriber
+#[tokio::test]
+async fn test_connection_loss_aborts_both_ends() -> Result<()> {
+    let (server, client, mut rx) = start().await?;
+
+    // A source that never ends on its own
+    let endless = stream::repeat(()).then(|()| async {
+        sleep(Duration::from_millis(5)).await;
+        Bytes::from(vec![7u8; 1024])
+    });
+    let publisher = client.clone();
+    let publish = tokio::spawn(async move {
+        publisher
+            .publish_stream("uploader", "files.upload", endless, HashMap::new())
+            .await
+    });
+
+    let descriptor = next_descriptor(&mut rx).await?;
+    let mut chunks = server.subscribe_body_stream(&descriptor.headers[STREAM_ID_HEADER])?;
+    chunks.next().await.expect("chunk")?;
+
+    server.shutdown().await?;
+
+    let error = timeout(Duration::from_secs(5), publish)
+        .await??
+        .expect_err("publish should fail");
+    assert_eq!(aborted(&error).reason, "connection lost");
+
+    let error = loop {
+        match timeout(Duration::from_secs(5), chunks.next()).await? {
+            Some(Ok(_)) => continue,
+            Some(Err(e)) => break e,
+            None => panic!("stream ended without an error"),
+        }
+    };
+    assert_eq!(aborted(&error).reason, "connection lost");
+
+    client.shutdown().await?;
+    Ok(())
+}
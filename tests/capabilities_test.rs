@@ -0,0 +1,51 @@
+use anyhow::Result;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use wasmcloud_provider_messaging_websocket::testing::start_server;
+use wasmcloud_provider_messaging_websocket::{Capabilities, WebSocketMessagingProvider};
+
+/// Fetch `GET /capabilities` over a plain HTTP/1.1 connection
+async fn fetch_capabilities(provider: &WebSocketMessagingProvider) -> Result<Capabilities> {
+    let addr = provider.get_server_addr().await.unwrap();
+    let mut stream = TcpStream::connect(addr).await?;
+    let request = format!(
+        "GET /capabilities HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+        addr
+    );
+    stream.write_all(request.as_bytes()).await?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    let (_, body) = response.split_once("\r\n\r\n").unwrap();
+    Ok(serde_json::from_str(body)?)
+}
+
+/// Test that the capabilities document reports the configured limits and auth
+#[tokio::test]
+async fn test_capabilities_reports_config() -> Result<()> {
+    let provider =
+        start_server(&[("MAX_MESSAGE_BYTES", "65536"), ("AUTH_TOKEN", "s3cret")]).await?;
+
+    let capabilities = fetch_capabilities(&provider).await?;
+    assert_eq!(capabilities.max_message_bytes, 65536);
+    assert!(capabilities.auth_required);
+    assert!(capabilities.codecs.contains(&"json".to_string()));
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a server without an auth token reports auth as not required
+#[tokio::test]
+async fn test_capabilities_without_auth() -> Result<()> {
+    let provider = start_server(&[]).await?;
+
+    let capabilities = fetch_capabilities(&provider).await?;
+    assert!(!capabilities.auth_required);
+    assert_eq!(capabilities.max_message_bytes, 64 << 20);
+
+    provider.shutdown().await?;
+    Ok(())
+}
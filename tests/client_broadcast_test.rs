@@ -1,41 +1,66 @@
 use anyhow::Result;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
 use bytes::Bytes;
 use std::collections::HashMap;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
-use tokio::time::{sleep, Duration};
+use tokio::sync::mpsc;
+use tokio::time::{timeout, Duration};
+use tokio_tungstenite::tungstenite::Message;
 use tracing_subscriber;
 
-use wasmcloud_provider_messaging_websocket::{BrokerMessage, WebSocketMessagingProvider};
+use wasmcloud_provider_messaging_websocket::testing::{
+    link_client, spawn_echo_server, spawn_recording_server,
+};
+use wasmcloud_provider_messaging_websocket::{BrokerMessage, LinkRole, WebSocketMessagingProvider};
+
+fn client_provider(session_tracking: bool) -> Result<WebSocketMessagingProvider> {
+    let mut config = HashMap::new();
+    config.insert("MODE".to_string(), "client".to_string());
+    config.insert(
+        "ENABLE_SESSION_TRACKING".to_string(),
+        session_tracking.to_string(),
+    );
+    WebSocketMessagingProvider::from_config(config)
+}
+
+/// The next envelope a handler's connection delivered to the recording server
+async fn next_envelope(
+    delivered: &mut mpsc::UnboundedReceiver<Message>,
+) -> Result<serde_json::Value> {
+    let frame = timeout(Duration::from_secs(2), delivered.recv())
+        .await?
+        .expect("recording server should be running");
+    Ok(serde_json::from_str(frame.to_text()?)?)
+}
 
 /// Test client mode message broadcasting from remote WS server to handler components
-/// Note: This test requires network access to echo.websocket.org
 #[tokio::test]
-#[ignore = "requires network access to external server"]
 async fn test_client_mode_broadcast_to_handlers() -> Result<()> {
     let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let (echo_addr, echo) = spawn_echo_server().await?;
+    let (handler_addr, mut delivered, recorder) = spawn_recording_server().await?;
 
-    let mut config = HashMap::new();
-    config.insert("MODE".to_string(), "client".to_string());
-    config.insert("URI".to_string(), "ws://echo.websocket.org".to_string());
-    config.insert("ENABLE_SESSION_TRACKING".to_string(), "true".to_string());
-
-    let provider = WebSocketMessagingProvider::from_config(config)?;
+    let provider = client_provider(true)?;
 
-    // Link consumer component (sends messages)
+    // Consumer component (sends messages) publishes through the echo server
     let consumer_id = "test-consumer";
-    provider
-        .receive_link_config_as_target(consumer_id, HashMap::new())
-        .await?;
-
-    // Link handler component (receives messages)
+    link_client(
+        &provider,
+        consumer_id,
+        LinkRole::Consumer,
+        &format!("ws://{}/ws", echo_addr),
+    )
+    .await?;
+
+    // Handler component (receives messages) is delivered to over its own connection
     let handler_id = "test-handler";
-    provider
-        .receive_link_config_as_source(handler_id, HashMap::new())
-        .await?;
-
-    // Give time for connections
-    sleep(Duration::from_millis(500)).await;
+    link_client(
+        &provider,
+        handler_id,
+        LinkRole::Handler,
+        &format!("ws://{}/ws", handler_addr),
+    )
+    .await?;
 
     // Verify links were established
     let sessions = provider.list_sessions().await;
@@ -46,61 +71,76 @@ async fn test_client_mode_broadcast_to_handlers() -> Result<()> {
         subject: "test.broadcast".to_string(),
         body: Bytes::from("test message"),
         reply_to: None,
+        headers: HashMap::new(),
     };
 
     provider.publish(consumer_id, msg).await?;
 
-    // Wait for echo response
-    sleep(Duration::from_millis(1000)).await;
+    // The echo comes back on the consumer's connection and is forwarded to the handler
+    let envelope = next_envelope(&mut delivered).await?;
+    assert_eq!(envelope["subject"], "test.broadcast");
+    assert_eq!(
+        STANDARD.decode(envelope["body"].as_str().unwrap())?,
+        b"test message"
+    );
 
     // Clean up
     provider.delete_link_as_target(consumer_id).await?;
     provider.delete_link_as_source(handler_id).await?;
     provider.shutdown().await?;
+    echo.shutdown().await;
+    recorder.shutdown().await;
 
     Ok(())
 }
 
 /// Test that components can send replies back to remote server using session ID
-/// Note: This test requires network access to echo.websocket.org
 #[tokio::test]
-#[ignore = "requires network access to external server"]
 async fn test_component_reply_to_remote_server() -> Result<()> {
     let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let (echo_addr, _echo) = spawn_echo_server().await?;
+    let (handler_addr, mut delivered, _recorder) = spawn_recording_server().await?;
 
-    let mut config = HashMap::new();
-    config.insert("MODE".to_string(), "client".to_string());
-    config.insert("URI".to_string(), "ws://echo.websocket.org".to_string());
-
-    let provider = WebSocketMessagingProvider::from_config(config)?;
+    let provider = client_provider(true)?;
 
     // Link both consumer and handler
-    provider
-        .receive_link_config_as_target("consumer", HashMap::new())
-        .await?;
-    provider
-        .receive_link_config_as_source("handler", HashMap::new())
-        .await?;
-
-    sleep(Duration::from_millis(500)).await;
-
-    // Get session ID
+    link_client(
+        &provider,
+        "consumer",
+        LinkRole::Consumer,
+        &format!("ws://{}/ws", echo_addr),
+    )
+    .await?;
+    link_client(
+        &provider,
+        "handler",
+        LinkRole::Handler,
+        &format!("ws://{}/ws", handler_addr),
+    )
+    .await?;
+
+    // Get the consumer's session ID
     let sessions = provider.list_sessions().await;
-    assert!(!sessions.is_empty());
-
-    let (session_id, _) = &sessions[0];
+    let (session_id, _) = sessions
+        .iter()
+        .find(|(_, label)| label.contains("consumer"))
+        .expect("consumer should have a session");
 
     // Send message with reply-to
     let msg = BrokerMessage {
         subject: "test.request".to_string(),
         body: Bytes::from("request"),
         reply_to: Some(session_id.clone()),
+        headers: HashMap::new(),
     };
 
     // Component can reply using send_to_session with the reply-to session ID
     provider.send_to_session(session_id, msg).await?;
 
-    sleep(Duration::from_millis(500)).await;
+    // The remote server got it, as its echo reaches the handler
+    let envelope = next_envelope(&mut delivered).await?;
+    assert_eq!(envelope["subject"], "test.request");
+    assert_eq!(envelope["reply_to"], session_id.as_str());
 
     // Clean up
     provider.shutdown().await?;
@@ -142,6 +182,7 @@ async fn test_message_encoding() -> Result<()> {
         subject: "test.subject".to_string(),
         body: Bytes::from("test body"),
         reply_to: Some("session-xyz".to_string()),
+        headers: HashMap::new(),
     };
 
     let encoded = WebSocketMessagingProvider::encode_message_static(&msg)?;
@@ -162,46 +203,51 @@ async fn test_message_encoding() -> Result<()> {
 }
 
 /// Test multiple handler components receiving broadcast
-/// Note: This test requires network access to echo.websocket.org
 #[tokio::test]
-#[ignore = "requires network access to external server"]
 async fn test_multiple_handlers_broadcast() -> Result<()> {
     let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let (echo_addr, _echo) = spawn_echo_server().await?;
+    let (handler_addr, mut delivered, _recorder) = spawn_recording_server().await?;
 
-    let mut config = HashMap::new();
-    config.insert("MODE".to_string(), "client".to_string());
-    config.insert("URI".to_string(), "ws://echo.websocket.org".to_string());
-
-    let provider = WebSocketMessagingProvider::from_config(config)?;
+    let provider = client_provider(true)?;
 
     // Link one consumer
-    provider
-        .receive_link_config_as_target("consumer", HashMap::new())
-        .await?;
+    link_client(
+        &provider,
+        "consumer",
+        LinkRole::Consumer,
+        &format!("ws://{}/ws", echo_addr),
+    )
+    .await?;
 
     // Link multiple handlers
-    provider
-        .receive_link_config_as_source("handler-1", HashMap::new())
-        .await?;
-    provider
-        .receive_link_config_as_source("handler-2", HashMap::new())
+    for handler_id in ["handler-1", "handler-2", "handler-3"] {
+        link_client(
+            &provider,
+            handler_id,
+            LinkRole::Handler,
+            &format!("ws://{}/{}", handler_addr, handler_id),
+        )
         .await?;
-    provider
-        .receive_link_config_as_source("handler-3", HashMap::new())
-        .await?;
-
-    sleep(Duration::from_millis(500)).await;
+    }
 
     // Send message - should be broadcast to all handlers when echo returns
     let msg = BrokerMessage {
         subject: "broadcast.test".to_string(),
         body: Bytes::from("broadcast to all"),
         reply_to: None,
+        headers: HashMap::new(),
     };
 
     provider.publish("consumer", msg).await?;
 
-    sleep(Duration::from_millis(1000)).await;
+    for _ in 0..3 {
+        let envelope = next_envelope(&mut delivered).await?;
+        assert_eq!(envelope["subject"], "broadcast.test");
+    }
+    assert!(timeout(Duration::from_millis(200), delivered.recv())
+        .await
+        .is_err());
 
     // Clean up
     provider.shutdown().await?;
@@ -210,24 +256,15 @@ async fn test_multiple_handlers_broadcast() -> Result<()> {
 }
 
 /// Test client mode with session tracking enabled/disabled
-/// Note: This test requires network access to echo.websocket.org
 #[tokio::test]
-#[ignore = "requires network access to external server"]
 async fn test_client_session_tracking() -> Result<()> {
     let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let (echo_addr, _echo) = spawn_echo_server().await?;
+    let uri = format!("ws://{}/ws", echo_addr);
 
     // Test with session tracking enabled
-    let mut config_enabled = HashMap::new();
-    config_enabled.insert("MODE".to_string(), "client".to_string());
-    config_enabled.insert("URI".to_string(), "ws://echo.websocket.org".to_string());
-    config_enabled.insert("ENABLE_SESSION_TRACKING".to_string(), "true".to_string());
-
-    let provider_enabled = WebSocketMessagingProvider::from_config(config_enabled)?;
-    provider_enabled
-        .receive_link_config_as_target("comp1", HashMap::new())
-        .await?;
-
-    sleep(Duration::from_millis(300)).await;
+    let provider_enabled = client_provider(true)?;
+    link_client(&provider_enabled, "comp1", LinkRole::Consumer, &uri).await?;
 
     let sessions_enabled = provider_enabled.list_sessions().await;
     assert!(!sessions_enabled.is_empty(), "Should track sessions");
@@ -235,17 +272,8 @@ async fn test_client_session_tracking() -> Result<()> {
     provider_enabled.shutdown().await?;
 
     // Test with session tracking disabled
-    let mut config_disabled = HashMap::new();
-    config_disabled.insert("MODE".to_string(), "client".to_string());
-    config_disabled.insert("URI".to_string(), "ws://echo.websocket.org".to_string());
-    config_disabled.insert("ENABLE_SESSION_TRACKING".to_string(), "false".to_string());
-
-    let provider_disabled = WebSocketMessagingProvider::from_config(config_disabled)?;
-    provider_disabled
-        .receive_link_config_as_target("comp2", HashMap::new())
-        .await?;
-
-    sleep(Duration::from_millis(300)).await;
+    let provider_disabled = client_provider(false)?;
+    link_client(&provider_disabled, "comp2", LinkRole::Consumer, &uri).await?;
 
     let _sessions_disabled = provider_disabled.list_sessions().await;
     // Sessions might still be tracked at provider level but not stored
@@ -0,0 +1,101 @@
+use anyhow::Result;
+use futures::SinkExt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+use tokio::time::sleep;
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::testing::{
+    connect, record_subjects, serve, server_config, Subjects, WsClient,
+};
+use wasmcloud_provider_messaging_websocket::{WebSocketMessagingProvider, DEADLINE_HEADER};
+
+const START_MS: u64 = 1_700_000_000_000;
+const HOUR_MS: u64 = 60 * 60 * 1000;
+
+/// Start a server-mode provider with an idle timeout and a queueing rate limit, its
+/// deadlines checked against `clock` and the subject of every message its sink receives
+/// recorded
+async fn start_server(clock: &Arc<AtomicU64>) -> Result<(WebSocketMessagingProvider, Subjects)> {
+    let config = server_config(&[
+        ("IDLE_TIMEOUT_MS", "1000"),
+        ("SERVER_MAX_MESSAGES_PER_SEC", "20"),
+        ("SERVER_RATE_POLICY", "queue"),
+    ]);
+    let clock = Arc::clone(clock);
+    let provider = serve(
+        WebSocketMessagingProvider::from_config(config)?
+            .with_clock(move || UNIX_EPOCH + Duration::from_millis(clock.load(Ordering::SeqCst))),
+    )
+    .await?;
+    let deliveries = record_subjects(&provider).await?;
+    Ok((provider, deliveries))
+}
+
+async fn send(ws: &mut WsClient, subject: &str, deadline_unix_ms: u64) -> Result<()> {
+    let frame = serde_json::json!({
+        "subject": subject,
+        "body": "",
+        "headers": { DEADLINE_HEADER: deadline_unix_ms.to_string() },
+    });
+    ws.send(Message::Text(frame.to_string())).await?;
+    Ok(())
+}
+
+/// Send a second's worth of messages well within their deadline, a twentieth of the
+/// idle timeout apart, then wait for the sink to have them all
+async fn send_for_a_second(ws: &mut WsClient, deliveries: &Subjects, subject: &str) -> Result<()> {
+    let expected = deliveries.lock().unwrap().len() + 20;
+    for _ in 0..20 {
+        send(ws, subject, START_MS + 24 * HOUR_MS).await?;
+        sleep(Duration::from_millis(50)).await;
+    }
+    for _ in 0..100 {
+        if deliveries.lock().unwrap().len() >= expected {
+            return Ok(());
+        }
+        sleep(Duration::from_millis(20)).await;
+    }
+    anyhow::bail!(
+        "{} of {} messages delivered after {}",
+        deliveries.lock().unwrap().len(),
+        expected,
+        subject
+    )
+}
+
+/// Test that setting the wall clock back or forward an hour neither idles out a busy
+/// session nor stalls the rate limiter, and doesn't revive an expired message
+#[tokio::test]
+async fn test_clock_jumps() -> Result<()> {
+    let clock = Arc::new(AtomicU64::new(START_MS));
+    let (provider, deliveries) = start_server(&clock).await?;
+    let (mut ws, session_id) = connect(&provider).await?;
+
+    send_for_a_second(&mut ws, &deliveries, "before").await?;
+
+    clock.fetch_sub(HOUR_MS, Ordering::SeqCst);
+    send_for_a_second(&mut ws, &deliveries, "behind").await?;
+    assert_eq!(provider.list_ws_clients().await?, vec![session_id.clone()]);
+
+    // Past its deadline before the clock went back, so still past it
+    send(&mut ws, "late", START_MS - 1).await?;
+    send(&mut ws, "marker", START_MS + 60_000).await?;
+    sleep(Duration::from_millis(200)).await;
+    assert_eq!(provider.expired_message_count(), 1);
+    assert!(!deliveries.lock().unwrap().iter().any(|s| s == "late"));
+    assert!(deliveries.lock().unwrap().iter().any(|s| s == "marker"));
+
+    clock.fetch_add(2 * HOUR_MS, Ordering::SeqCst);
+    send_for_a_second(&mut ws, &deliveries, "ahead").await?;
+    assert_eq!(provider.list_ws_clients().await?, vec![session_id]);
+
+    // A clock set forward is taken as it is
+    send(&mut ws, "marker", START_MS + 60_000).await?;
+    sleep(Duration::from_millis(200)).await;
+    assert_eq!(provider.expired_message_count(), 2);
+
+    provider.shutdown().await?;
+    Ok(())
+}
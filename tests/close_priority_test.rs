@@ -0,0 +1,94 @@
+use anyhow::Result;
+use bytes::Bytes;
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::testing::{connect, start_server, WsClient};
+use wasmcloud_provider_messaging_websocket::{
+    BrokerMessage, CloseReason, WebSocketMessagingProvider,
+};
+
+/// Frames queued for the client; far more than the socket buffers hold
+const FRAMES: usize = 400;
+
+/// Queue `FRAMES` 64 KiB messages for a client that isn't reading
+async fn fill_queue(provider: &WebSocketMessagingProvider, session_id: &str) -> Result<()> {
+    for _ in 0..FRAMES {
+        provider
+            .send_to_ws_client(
+                session_id,
+                BrokerMessage {
+                    subject: "bulk".to_string(),
+                    body: Bytes::from(vec![b'x'; 64 * 1024]),
+                    reply_to: None,
+                    headers: HashMap::new(),
+                },
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+/// Read until the close frame, returning the data frames before it and the close code
+async fn read_until_close(ws: &mut WsClient) -> Result<(usize, u16)> {
+    let mut data_frames = 0;
+    loop {
+        let frame = timeout(Duration::from_secs(5), ws.next())
+            .await?
+            .expect("close frame")?;
+        match frame {
+            Message::Close(Some(close)) => return Ok((data_frames, close.code.into())),
+            Message::Text(_) | Message::Binary(_) => data_frames += 1,
+            _ => {}
+        }
+    }
+}
+
+/// Test that a non-graceful close discards the backlog and reaches the peer ahead of it
+#[tokio::test]
+async fn test_non_graceful_close_skips_queue() -> Result<()> {
+    let server = start_server(&[]).await?;
+    let (mut client, session_id) = connect(&server).await?;
+    fill_queue(&server, &session_id).await?;
+
+    let discarded = server
+        .close_session(&session_id, CloseReason::Banned, false)
+        .await?;
+    assert!(discarded > 0);
+
+    // Only frames already written to the socket arrive before the close frame
+    let (data_frames, code) = read_until_close(&mut client).await?;
+    assert_eq!(code, CloseReason::Banned.code());
+    assert!(data_frames + discarded <= FRAMES);
+    assert!(
+        data_frames < FRAMES / 2,
+        "{} frames preceded the close",
+        data_frames
+    );
+
+    server.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a graceful close waits for everything queued before it
+#[tokio::test]
+async fn test_graceful_close_drains_queue() -> Result<()> {
+    let server = start_server(&[]).await?;
+    let (mut client, session_id) = connect(&server).await?;
+    fill_queue(&server, &session_id).await?;
+
+    let discarded = server
+        .close_session(&session_id, CloseReason::Normal, true)
+        .await?;
+    assert_eq!(discarded, 0);
+
+    let (data_frames, code) = read_until_close(&mut client).await?;
+    assert_eq!(code, CloseReason::Normal.code());
+    assert_eq!(data_frames, FRAMES);
+
+    server.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use anyhow::Result;
+use futures::{SinkExt, StreamExt};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::time::{sleep, timeout};
+use tokio_tungstenite::tungstenite::protocol::{frame::coding::CloseCode, CloseFrame};
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::testing::{connect, start_server, WsClient};
+use wasmcloud_provider_messaging_websocket::{
+    CloseReason, ProviderEvent, WebSocketMessagingProvider,
+};
+
+/// Read frames until the close frame and return its code and reason
+async fn close_of(ws: &mut WsClient) -> Result<(u16, String)> {
+    loop {
+        let frame = timeout(Duration::from_secs(2), ws.next())
+            .await?
+            .expect("close frame")?;
+        if let Message::Close(Some(close)) = frame {
+            return Ok((close.code.into(), close.reason.to_string()));
+        }
+    }
+}
+
+/// Test that a parse-failure disconnect closes with 1008 and is counted and reported
+#[tokio::test]
+async fn test_invalid_frames_close_code() -> Result<()> {
+    let provider = start_server(&[
+        ("PARSE_FAIL_THRESHOLD", "0"),
+        ("PARSE_FAIL_DISCONNECT_THRESHOLD", "2"),
+    ])
+    .await?;
+    let mut events = provider.subscribe_events();
+    let (mut ws, session_id) = connect(&provider).await?;
+
+    for _ in 0..2 {
+        ws.send(Message::Text("garbage".to_string())).await?;
+    }
+    assert_eq!(
+        close_of(&mut ws).await?,
+        (1008, "too many invalid frames".to_string())
+    );
+
+    loop {
+        let event = timeout(Duration::from_secs(2), events.recv()).await??;
+        if let ProviderEvent::ClientClosed {
+            session_id: closed,
+            reason,
+        } = event
+        {
+            assert_eq!(closed, session_id);
+            assert_eq!(reason, CloseReason::InvalidFrames);
+            break;
+        }
+    }
+    assert_eq!(provider.close_counts()["invalid_frames"], 1);
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that disconnecting a client with a provider reason sends its reserved code
+#[tokio::test]
+async fn test_disconnect_with_reason() -> Result<()> {
+    let provider = start_server(&[]).await?;
+    let (mut ws, session_id) = connect(&provider).await?;
+
+    provider
+        .disconnect_ws_client(&session_id, CloseReason::Banned)
+        .await?;
+    assert_eq!(close_of(&mut ws).await?, (4005, "banned".to_string()));
+    assert_eq!(provider.close_counts()["banned"], 1);
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that shutting down closes connected clients with 1001
+#[tokio::test]
+async fn test_shutdown_close_code() -> Result<()> {
+    let provider = start_server(&[]).await?;
+    let (mut ws, _) = connect(&provider).await?;
+
+    provider.shutdown().await?;
+    assert_eq!(
+        close_of(&mut ws).await?,
+        (1001, "server shutting down".to_string())
+    );
+    assert_eq!(provider.close_counts()["shutdown"], 1);
+    Ok(())
+}
+
+/// Test that the code and reason a remote server closes a client-mode connection with are
+/// kept after the drop
+#[tokio::test]
+async fn test_last_close_reason_of_link() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await?;
+        let mut ws = tokio_tungstenite::accept_async(stream).await?;
+        ws.send(Message::Close(Some(CloseFrame {
+            code: CloseCode::Error,
+            reason: "backend unavailable".into(),
+        })))
+        .await?;
+        anyhow::Ok(())
+    });
+
+    let config = HashMap::from([("URI".to_string(), format!("ws://{}/ws", addr))]);
+    let provider = WebSocketMessagingProvider::from_config(config)?;
+    provider
+        .receive_link_config_as_target("publisher", HashMap::new())
+        .await?;
+    server.await??;
+
+    for _ in 0..50 {
+        if provider.last_close_reason("publisher").await.is_some() {
+            break;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(
+        provider.last_close_reason("publisher").await,
+        Some((1011, "backend unavailable".to_string()))
+    );
+    assert_eq!(provider.last_close_reason("unknown").await, None);
+
+    provider.shutdown().await?;
+    Ok(())
+}
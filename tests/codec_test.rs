@@ -0,0 +1,115 @@
+use anyhow::Result;
+use bytes::Bytes;
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::{BrokerMessage, WebSocketMessagingProvider};
+
+/// Accept one WebSocket connection and report the data frames it receives
+async fn local_server() -> Result<(SocketAddr, mpsc::UnboundedReceiver<Message>)> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let (frame_tx, frame_rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await?;
+        let mut ws = tokio_tungstenite::accept_async(stream).await?;
+        while let Some(Ok(msg)) = ws.next().await {
+            if matches!(msg, Message::Text(_) | Message::Binary(_)) {
+                let _ = frame_tx.send(msg);
+            }
+        }
+        anyhow::Ok(())
+    });
+    Ok((addr, frame_rx))
+}
+
+fn message(subject: &str) -> BrokerMessage {
+    BrokerMessage {
+        subject: subject.to_string(),
+        body: Bytes::from("order ".repeat(200)),
+        reply_to: None,
+        headers: HashMap::new(),
+    }
+}
+
+/// Test that links with different `CODEC` and `BODY_COMPRESSION` settings each send in
+/// their own format: JSON text to one server, zstd bodies in MessagePack to another, and
+/// gzip bodies in MessagePack, set through `COMPRESSION`, to a third
+#[tokio::test]
+async fn test_codec_per_link() -> Result<()> {
+    let (json_addr, mut json_frames) = local_server().await?;
+    let (msgpack_addr, mut msgpack_frames) = local_server().await?;
+    let (gzip_addr, mut gzip_frames) = local_server().await?;
+    let provider = WebSocketMessagingProvider::from_config(HashMap::from([(
+        "URI".to_string(),
+        format!("ws://{}/ws", json_addr),
+    )]))?;
+    provider
+        .receive_link_config_as_target("orders", HashMap::new())
+        .await?;
+    provider
+        .receive_link_config_as_target(
+            "audit",
+            HashMap::from([
+                ("URI".to_string(), format!("ws://{}/ws", msgpack_addr)),
+                ("CODEC".to_string(), "msgpack".to_string()),
+                ("BODY_COMPRESSION".to_string(), "zstd".to_string()),
+                ("COMPRESS_THRESHOLD_BYTES".to_string(), "0".to_string()),
+            ]),
+        )
+        .await?;
+    provider
+        .receive_link_config_as_target(
+            "billing",
+            HashMap::from([
+                ("URI".to_string(), format!("ws://{}/ws", gzip_addr)),
+                ("CODEC".to_string(), "msgpack".to_string()),
+                ("COMPRESSION".to_string(), "gzip".to_string()),
+                ("COMPRESS_THRESHOLD_BYTES".to_string(), "0".to_string()),
+            ]),
+        )
+        .await?;
+
+    provider.publish("orders", message("orders.new")).await?;
+    provider.publish("audit", message("audit.new")).await?;
+    provider.publish("billing", message("billing.new")).await?;
+
+    let frame = timeout(Duration::from_secs(2), json_frames.recv())
+        .await?
+        .expect("orders server should be running");
+    let Message::Text(text) = frame else {
+        panic!("orders should send JSON text, got {:?}", frame);
+    };
+    let envelope: serde_json::Value = serde_json::from_str(&text)?;
+    assert_eq!(envelope["subject"], "orders.new");
+    assert!(envelope.get("headers").is_none());
+
+    let frame = timeout(Duration::from_secs(2), msgpack_frames.recv())
+        .await?
+        .expect("audit server should be running");
+    let Message::Binary(bytes) = frame else {
+        panic!("audit should send MessagePack, got {:?}", frame);
+    };
+    let envelope: serde_json::Value = rmp_serde::from_slice(&bytes)?;
+    assert_eq!(envelope["subject"], "audit.new");
+    assert_eq!(envelope["headers"]["content-encoding"], "zstd");
+
+    let frame = timeout(Duration::from_secs(2), gzip_frames.recv())
+        .await?
+        .expect("billing server should be running");
+    let Message::Binary(bytes) = frame else {
+        panic!("billing should send MessagePack, got {:?}", frame);
+    };
+    let envelope: serde_json::Value = rmp_serde::from_slice(&bytes)?;
+    assert_eq!(envelope["subject"], "billing.new");
+    assert_eq!(envelope["headers"]["content-encoding"], "gzip");
+
+    provider.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,262 @@
+use anyhow::{bail, Result};
+use bytes::Bytes;
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress};
+use futures::{SinkExt, StreamExt};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::WebSocketMessagingProvider;
+
+const SYNC_TAIL: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+/// Read one frame: FIN, RSV1, opcode and the unmasked payload
+async fn read_frame(stream: &mut TcpStream) -> Result<(bool, bool, u8, Vec<u8>)> {
+    let mut head = [0u8; 2];
+    stream.read_exact(&mut head).await?;
+    let len = match head[1] & 0x7f {
+        126 => stream.read_u16().await? as usize,
+        127 => stream.read_u64().await? as usize,
+        len => len as usize,
+    };
+    let mut mask = [0u8; 4];
+    if head[1] & 0x80 != 0 {
+        stream.read_exact(&mut mask).await?;
+    }
+    let mut payload = vec![0u8; len];
+    stream.read_exact(&mut payload).await?;
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+    Ok((
+        head[0] & 0x80 != 0,
+        head[0] & 0x40 != 0,
+        head[0] & 0x0f,
+        payload,
+    ))
+}
+
+async fn write_compressed_text(stream: &mut TcpStream, payload: &[u8]) -> Result<()> {
+    let mut compress = Compress::new(Compression::default(), false);
+    let mut compressed = Vec::with_capacity(payload.len() + 64);
+    compress.compress_vec(payload, &mut compressed, FlushCompress::Sync)?;
+    compressed.truncate(compressed.len() - SYNC_TAIL.len());
+
+    let mut frame = vec![0x80 | 0x40 | 0x1];
+    match compressed.len() {
+        len @ 0..=125 => frame.push(len as u8),
+        len @ 126..=0xffff => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(&compressed);
+    stream.write_all(&frame).await?;
+    Ok(())
+}
+
+fn inflate(payload: &[u8]) -> Result<Vec<u8>> {
+    let mut input = payload.to_vec();
+    input.extend_from_slice(&SYNC_TAIL);
+    let mut out = Vec::with_capacity(1 << 20);
+    Decompress::new(false).decompress_vec(&input, &mut out, FlushDecompress::Sync)?;
+    Ok(out)
+}
+
+/// Accept a connection and complete its upgrade, accepting `permessage-deflate`
+async fn accept_deflate(listener: &TcpListener) -> Result<TcpStream> {
+    let (mut stream, _) = listener.accept().await?;
+    let mut request = Vec::new();
+    while !request.ends_with(b"\r\n\r\n") {
+        request.push(stream.read_u8().await?);
+    }
+    let request = String::from_utf8(request)?;
+    let header = |name: &str| {
+        request.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.eq_ignore_ascii_case(name)
+                .then(|| value.trim().to_string())
+        })
+    };
+    if header("sec-websocket-extensions").as_deref() != Some("permessage-deflate") {
+        bail!("permessage-deflate was not offered");
+    }
+    let key = header("sec-websocket-key").unwrap_or_default();
+    let response = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\nSec-WebSocket-Extensions: permessage-deflate; \
+         client_no_context_takeover; server_no_context_takeover\r\n\r\n",
+        derive_accept_key(key.as_bytes())
+    );
+    stream.write_all(response.as_bytes()).await?;
+    Ok(stream)
+}
+
+/// A server speaking `permessage-deflate` that answers each request with the request's
+/// body, reporting the compressed size of each envelope it receives
+async fn deflate_server() -> Result<(SocketAddr, mpsc::UnboundedReceiver<usize>)> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let (size_tx, size_rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let mut stream = accept_deflate(&listener).await?;
+        while let Ok((fin, rsv1, opcode, payload)) = read_frame(&mut stream).await {
+            if opcode != 0x1 {
+                continue;
+            }
+            assert!(fin && rsv1, "text frames should be compressed");
+            let _ = size_tx.send(payload.len());
+            let envelope: serde_json::Value = serde_json::from_slice(&inflate(&payload)?)?;
+            let reply = serde_json::json!({
+                "subject": envelope["reply_to"],
+                "body": envelope["body"],
+                "body_encoding": envelope["body_encoding"],
+                "corr_id": envelope["corr_id"],
+            });
+            write_compressed_text(&mut stream, reply.to_string().as_bytes()).await?;
+        }
+        anyhow::Ok(())
+    });
+    Ok((addr, size_rx))
+}
+
+/// Test that a 100 KB repetitive payload goes out compressed with
+/// `PERMESSAGE_DEFLATE=true` and its compressed echo round-trips intact
+#[tokio::test]
+async fn test_permessage_deflate_round_trip() -> Result<()> {
+    let (addr, mut sizes) = deflate_server().await?;
+    let provider = WebSocketMessagingProvider::from_config(HashMap::from([
+        ("URI".to_string(), format!("ws://{}/ws", addr)),
+        ("PERMESSAGE_DEFLATE".to_string(), "true".to_string()),
+    ]))?;
+    provider
+        .receive_link_config_as_target("documents", HashMap::new())
+        .await?;
+    let info = provider
+        .client_session_info("documents")
+        .await
+        .expect("documents should be linked");
+    assert_eq!(info.metadata["compression"], "permessage-deflate");
+
+    let document = "{\"id\":42,\"status\":\"shipped\",\"items\":[1,2,3]}\n"
+        .repeat(100 * 1024 / 44)
+        .into_bytes();
+    assert!(document.len() >= 100_000);
+    let reply = provider
+        .request(
+            "documents",
+            "documents.echo".to_string(),
+            Bytes::from(document.clone()),
+            Duration::from_secs(5),
+        )
+        .await?;
+    assert_eq!(reply.body, document);
+
+    let compressed = timeout(Duration::from_secs(1), sizes.recv())
+        .await?
+        .expect("server should be running");
+    assert!(compressed < document.len() / 10, "{} bytes", compressed);
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a server that doesn't accept the extension still gets uncompressed frames
+#[tokio::test]
+async fn test_falls_back_without_extension() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await?;
+        let mut ws = tokio_tungstenite::accept_async(stream).await?;
+        while let Some(Ok(msg)) = ws.next().await {
+            if let Message::Text(text) = msg {
+                let envelope: serde_json::Value = serde_json::from_str(&text)?;
+                let reply = serde_json::json!({
+                    "subject": envelope["reply_to"],
+                    "body": envelope["body"],
+                "body_encoding": envelope["body_encoding"],
+                    "corr_id": envelope["corr_id"],
+                });
+                ws.send(Message::Text(reply.to_string())).await?;
+            }
+        }
+        anyhow::Ok(())
+    });
+
+    let provider = WebSocketMessagingProvider::from_config(HashMap::from([
+        ("URI".to_string(), format!("ws://{}/ws", addr)),
+        ("PERMESSAGE_DEFLATE".to_string(), "true".to_string()),
+    ]))?;
+    provider
+        .receive_link_config_as_target("documents", HashMap::new())
+        .await?;
+    let info = provider
+        .client_session_info("documents")
+        .await
+        .expect("documents should be linked");
+    assert_eq!(info.metadata["compression"], "none");
+
+    let reply = provider
+        .request(
+            "documents",
+            "documents.echo".to_string(),
+            Bytes::from("plain"),
+            Duration::from_secs(5),
+        )
+        .await?;
+    assert_eq!(reply.body, Bytes::from("plain"));
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a compressed message inflating past `MAX_MESSAGE_BYTES` closes the
+/// connection with 1009 instead of being inflated in full
+#[tokio::test]
+async fn test_oversized_message_closes_with_1009() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let (close_tx, mut close_rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let mut stream = accept_deflate(&listener).await?;
+        // A quarter of a megabyte of one byte deflates to a few hundred bytes
+        let bomb = vec![b'a'; 256 * 1024];
+        write_compressed_text(&mut stream, &bomb).await?;
+        while let Ok((_, _, opcode, payload)) = read_frame(&mut stream).await {
+            if opcode == 0x8 {
+                let _ = close_tx.send(u16::from_be_bytes([payload[0], payload[1]]));
+                break;
+            }
+        }
+        anyhow::Ok(())
+    });
+
+    let provider = WebSocketMessagingProvider::from_config(HashMap::from([
+        ("URI".to_string(), format!("ws://{}/ws", addr)),
+        ("PERMESSAGE_DEFLATE".to_string(), "true".to_string()),
+        ("MAX_MESSAGE_BYTES".to_string(), "1024".to_string()),
+        ("RECONNECT".to_string(), "false".to_string()),
+    ]))?;
+    provider
+        .receive_link_config_as_target("documents", HashMap::new())
+        .await?;
+
+    let code = timeout(Duration::from_secs(5), close_rx.recv())
+        .await?
+        .expect("server should see a close frame");
+    assert_eq!(code, 1009);
+
+    provider.shutdown().await?;
+    Ok(())
+}
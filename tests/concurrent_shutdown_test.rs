@@ -0,0 +1,99 @@
+use anyhow::Result;
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::{sleep, timeout};
+
+use wasmcloud_provider_messaging_websocket::mock::MockTransport;
+use wasmcloud_provider_messaging_websocket::testing::{connect, start_server};
+use wasmcloud_provider_messaging_websocket::{
+    read_spill, BrokerMessage, CloseReason, ProviderEvent, ShutdownReport,
+    WebSocketMessagingProvider,
+};
+
+const CALLERS: usize = 16;
+
+/// Call `shutdown` on `CALLERS` clones at once, returning every report
+async fn shutdown_concurrently(
+    provider: &WebSocketMessagingProvider,
+) -> Result<Vec<ShutdownReport>> {
+    let calls: Vec<_> = (0..CALLERS)
+        .map(|_| {
+            let clone = provider.clone();
+            tokio::spawn(async move { clone.shutdown().await })
+        })
+        .collect();
+    let mut reports = Vec::new();
+    for call in calls {
+        reports.push(call.await??);
+    }
+    Ok(reports)
+}
+
+/// Test that concurrent shutdowns of a client-mode provider tear down once and all see
+/// the full report, with each queued message spilled exactly once
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_shutdown_spills_once() -> Result<()> {
+    let path = std::env::temp_dir().join(format!(
+        "concurrent-shutdown-{}.ndjson",
+        uuid::Uuid::new_v4()
+    ));
+    let mut config = HashMap::new();
+    config.insert("URI".to_string(), "ws://mock.invalid/ws".to_string());
+    config.insert(
+        "SHUTDOWN_SPILL_PATH".to_string(),
+        path.display().to_string(),
+    );
+    let (transport, remotes) = MockTransport::new();
+    // The socket stops accepting writes after the first frame, so publishes stay queued
+    transport.set_write_capacity(Some(0));
+    let provider = WebSocketMessagingProvider::from_config(config)?.with_transport(transport);
+    provider
+        .receive_link_config_as_target("publisher", HashMap::new())
+        .await?;
+    let _remote = remotes.accept().await.expect("connection");
+
+    for n in 0..5 {
+        let msg = BrokerMessage {
+            subject: format!("orders.{}", n),
+            body: Bytes::from("x"),
+            reply_to: None,
+            headers: HashMap::new(),
+        };
+        provider.publish("publisher", msg).await?;
+    }
+    sleep(Duration::from_millis(50)).await;
+
+    let reports = shutdown_concurrently(&provider).await?;
+    assert!(reports[0].unsent > 0, "{:?}", reports[0]);
+    assert!(reports.iter().all(|report| *report == reports[0]));
+    assert_eq!(read_spill(&path)?.len(), reports[0].spilled);
+
+    // Later calls keep returning the same report
+    assert_eq!(provider.shutdown().await?, reports[0]);
+    let _ = std::fs::remove_file(&path);
+    Ok(())
+}
+
+/// Test that concurrent shutdowns of a server close each client once
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_shutdown_closes_clients_once() -> Result<()> {
+    let provider = start_server(&[]).await?;
+    let mut events = provider.subscribe_events();
+
+    let (_ws, _) = connect(&provider).await?;
+
+    let reports = shutdown_concurrently(&provider).await?;
+    assert!(reports.iter().all(|report| *report == reports[0]));
+    assert_eq!(provider.close_counts().get("shutdown"), Some(&1));
+
+    let mut closed = 0;
+    while let Ok(Ok(event)) = timeout(Duration::from_millis(100), events.recv()).await {
+        if let ProviderEvent::ClientClosed { reason, .. } = event {
+            assert_eq!(reason, CloseReason::Shutdown);
+            closed += 1;
+        }
+    }
+    assert_eq!(closed, 1);
+    Ok(())
+}
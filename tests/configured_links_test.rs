@@ -0,0 +1,98 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+use wasmcloud_provider_messaging_websocket::{
+    LinkConnectionStatus, LinkRole, WebSocketMessagingProvider,
+};
+
+/// Accept one WebSocket connection and keep it open
+async fn local_server() -> Result<(SocketAddr, JoinHandle<Result<()>>)> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await?;
+        let _ws = tokio_tungstenite::accept_async(stream).await?;
+        std::future::pending::<()>().await;
+        Ok(())
+    });
+    Ok((addr, server))
+}
+
+/// Test that links declared in the provider config connect at startup and are listed
+/// like component links, with a link whose server is down listed as failed
+#[tokio::test]
+async fn test_configured_links_connect_at_startup() -> Result<()> {
+    let (orders_addr, orders_server) = local_server().await?;
+    let (audit_addr, audit_server) = local_server().await?;
+    let config = HashMap::from([
+        (
+            "LINK_orders_URI".to_string(),
+            format!("ws://{}/ws", orders_addr),
+        ),
+        (
+            "LINK_orders_SUBSCRIPTIONS".to_string(),
+            "orders.>".to_string(),
+        ),
+        (
+            "LINK_audit_URI".to_string(),
+            format!("ws://{}/ws", audit_addr),
+        ),
+        ("LINK_audit_ROLE".to_string(), "consumer".to_string()),
+        (
+            "LINK_down_URI".to_string(),
+            "ws://127.0.0.1:1/ws".to_string(),
+        ),
+        ("LINK_down_CONNECT_TIMEOUT_SEC".to_string(), "2".to_string()),
+    ]);
+    let provider = WebSocketMessagingProvider::from_config(config)?;
+    assert!(provider.list_links().await.is_empty());
+
+    provider.start_configured_links().await;
+
+    let links = provider.list_links().await;
+    let summary: Vec<_> = links
+        .iter()
+        .map(|link| {
+            (
+                link.component_id.as_str(),
+                link.role,
+                link.connection_status,
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("audit", LinkRole::Consumer, LinkConnectionStatus::Connected),
+            ("down", LinkRole::Handler, LinkConnectionStatus::Failed),
+            ("orders", LinkRole::Handler, LinkConnectionStatus::Connected),
+        ]
+    );
+    let config = provider
+        .get_link_config("orders")
+        .await
+        .expect("orders should be linked");
+    assert_eq!(config.uri, format!("ws://{}/ws", orders_addr));
+
+    provider.shutdown().await?;
+    orders_server.abort();
+    audit_server.abort();
+    Ok(())
+}
+
+/// Test that a malformed link declaration fails the provider config
+#[tokio::test]
+async fn test_configured_link_without_uri_rejected() -> Result<()> {
+    let config = HashMap::from([(
+        "LINK_orders_SUBSCRIPTIONS".to_string(),
+        "orders.>".to_string(),
+    )]);
+    let Err(error) = WebSocketMessagingProvider::from_config(config) else {
+        panic!("config without LINK_orders_URI should be rejected");
+    };
+    assert!(error.to_string().contains("LINK_orders_URI"), "{}", error);
+    Ok(())
+}
@@ -0,0 +1,124 @@
+use anyhow::Result;
+use futures::SinkExt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::sleep;
+use tokio_tungstenite::tungstenite::protocol::{frame::coding::CloseCode, CloseFrame};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+
+use wasmcloud_provider_messaging_websocket::testing::{server_url, start_server};
+
+/// An event as seen by the capturing layer, with the connection id of its enclosing span
+#[derive(Debug, Clone)]
+struct CapturedEvent {
+    message: String,
+    connection_id: Option<String>,
+}
+
+/// Connection id recorded on a span, stored in the span's extensions
+struct ConnectionId(String);
+
+#[derive(Default)]
+struct FieldVisitor {
+    message: Option<String>,
+    connection_id: Option<String>,
+}
+
+impl Visit for FieldVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        match field.name() {
+            "message" => self.message = Some(format!("{:?}", value)),
+            "connection_id" => self.connection_id = Some(format!("{:?}", value)),
+            _ => {}
+        }
+    }
+}
+
+/// Layer recording every event along with the nearest `connection_id` in scope
+#[derive(Clone, Default)]
+struct CaptureLayer {
+    events: Arc<Mutex<Vec<CapturedEvent>>>,
+}
+
+impl<S> Layer<S> for CaptureLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut visitor = FieldVisitor::default();
+        attrs.record(&mut visitor);
+        if let (Some(connection_id), Some(span)) = (visitor.connection_id, ctx.span(id)) {
+            span.extensions_mut().insert(ConnectionId(connection_id));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        let connection_id = visitor.connection_id.or_else(|| {
+            ctx.event_scope(event)?.find_map(|span| {
+                span.extensions()
+                    .get::<ConnectionId>()
+                    .map(|id| id.0.clone())
+            })
+        });
+        self.events.lock().unwrap().push(CapturedEvent {
+            message: visitor.message.unwrap_or_default(),
+            connection_id,
+        });
+    }
+}
+
+/// Test that one connection id threads through connect, message and disconnect events
+#[tokio::test]
+async fn test_connection_id_threads_through_lifecycle() -> Result<()> {
+    let layer = CaptureLayer::default();
+    let events = Arc::clone(&layer.events);
+    let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(layer));
+
+    let provider = start_server(&[]).await?;
+    let url = server_url(&provider).await?;
+
+    let (mut ws, _) = tokio_tungstenite::connect_async(url.as_str()).await?;
+    ws.send(Message::Text(
+        r#"{"subject":"test.conn","body":"hello"}"#.to_string(),
+    ))
+    .await?;
+    ws.send(Message::Close(Some(CloseFrame {
+        code: CloseCode::Normal,
+        reason: "done".into(),
+    })))
+    .await?;
+
+    sleep(Duration::from_millis(300)).await;
+
+    let events = events.lock().unwrap().clone();
+    let connection_id_of = |prefix: &str| {
+        events
+            .iter()
+            .find(|e| e.message.starts_with(prefix))
+            .unwrap_or_else(|| panic!("missing event: {}", prefix))
+            .connection_id
+            .clone()
+            .unwrap_or_else(|| panic!("event without connection_id: {}", prefix))
+    };
+
+    let connected = connection_id_of("New WebSocket client connected");
+    assert_eq!(connection_id_of("Received text message"), connected);
+    assert_eq!(connection_id_of("Client disconnected"), connected);
+    assert_eq!(connection_id_of("Session closed"), connected);
+
+    // Only one connection was made, so no other id appears
+    assert!(events
+        .iter()
+        .filter_map(|e| e.connection_id.as_ref())
+        .all(|id| *id == connected));
+
+    provider.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,142 @@
+use anyhow::Result;
+use axum::extract::{State, WebSocketUpgrade};
+use axum::http::HeaderMap;
+use axum::response::Response as AxumResponse;
+use axum::routing::get;
+use axum::Router;
+use std::collections::HashMap;
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, oneshot};
+use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+
+use wasmcloud_provider_messaging_websocket::WebSocketMessagingProvider;
+
+/// Test that `HEADER_*` entries reach the server in the upgrade request, with an explicit
+/// `HEADER_Authorization` winning over `AUTH_TOKEN`
+#[tokio::test]
+async fn test_custom_headers_sent_in_handshake() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let (headers_tx, headers_rx) = oneshot::channel();
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await?;
+        let ws = tokio_tungstenite::accept_hdr_async(stream, |request: &Request, response| {
+            let headers: HashMap<String, String> = request
+                .headers()
+                .iter()
+                .map(|(name, value)| {
+                    (
+                        name.as_str().to_string(),
+                        value.to_str().unwrap().to_string(),
+                    )
+                })
+                .collect();
+            let _ = headers_tx.send(headers);
+            Ok::<Response, _>(response)
+        })
+        .await?;
+        anyhow::Ok(ws)
+    });
+
+    let config = HashMap::from([
+        ("URI".to_string(), format!("ws://{}/ws", addr)),
+        ("AUTH_TOKEN".to_string(), "secret".to_string()),
+        ("HEADER_X-API-Key".to_string(), "key-1".to_string()),
+        ("HEADER_X-Client-ID".to_string(), "client-1".to_string()),
+        (
+            "HEADER_Authorization".to_string(),
+            "Basic explicit".to_string(),
+        ),
+    ]);
+    let provider = WebSocketMessagingProvider::from_config(config)?;
+    provider
+        .receive_link_config_as_target("publisher", HashMap::new())
+        .await?;
+
+    let headers = headers_rx.await?;
+    assert_eq!(headers["x-api-key"], "key-1");
+    assert_eq!(headers["x-client-id"], "client-1");
+    assert_eq!(headers["authorization"], "Basic explicit");
+    let _ws = server.await??;
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a header name or value that isn't valid HTTP fails the link with the key
+/// named, before any connection attempt
+#[tokio::test]
+async fn test_invalid_headers_rejected() -> Result<()> {
+    let provider = WebSocketMessagingProvider::from_config(HashMap::from([(
+        "URI".to_string(),
+        "ws://127.0.0.1:1/ws".to_string(),
+    )]))?;
+
+    let link = HashMap::from([("HEADER_X Bad".to_string(), "value".to_string())]);
+    let error = provider
+        .receive_link_config_as_target("bad-name", link)
+        .await
+        .expect_err("link should fail with an invalid header name");
+    assert!(
+        format!("{:#}", error).contains("HEADER_X Bad is not a valid HTTP header name"),
+        "{:#}",
+        error
+    );
+
+    let link = HashMap::from([("HEADER_X-Trace".to_string(), "a\nb".to_string())]);
+    let error = provider
+        .receive_link_config_as_target("bad-value", link)
+        .await
+        .expect_err("link should fail with an invalid header value");
+    assert!(
+        format!("{:#}", error).contains("Value of HEADER_X-Trace is not a valid HTTP header value"),
+        "{:#}",
+        error
+    );
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Upgrade handler of the axum server below, handing the request headers to the test
+async fn record_headers(
+    State(seen): State<mpsc::UnboundedSender<HeaderMap>>,
+    headers: HeaderMap,
+    ws: WebSocketUpgrade,
+) -> AxumResponse {
+    let _ = seen.send(headers);
+    ws.on_upgrade(|_socket| async {})
+}
+
+/// Test that an axum server behind an auth proxy sees the configured `Authorization` and
+/// `X-Tenant-Id` headers on the upgrade request
+#[tokio::test]
+async fn test_axum_server_sees_headers() -> Result<()> {
+    let (seen_tx, mut seen_rx) = mpsc::unbounded_channel();
+    let app = Router::new()
+        .route("/ws", get(record_headers))
+        .with_state(seen_tx);
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let config = HashMap::from([
+        ("URI".to_string(), format!("ws://{}/ws", addr)),
+        (
+            "HEADER_Authorization".to_string(),
+            "Bearer proxy-token".to_string(),
+        ),
+        ("HEADER_X-Tenant-Id".to_string(), "acme".to_string()),
+    ]);
+    let provider = WebSocketMessagingProvider::from_config(config)?;
+    provider
+        .receive_link_config_as_target("publisher", HashMap::new())
+        .await?;
+
+    let headers = seen_rx.recv().await.expect("upgrade request");
+    assert_eq!(headers["authorization"], "Bearer proxy-token");
+    assert_eq!(headers["x-tenant-id"], "acme");
+
+    provider.shutdown().await?;
+    Ok(())
+}
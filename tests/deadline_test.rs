@@ -0,0 +1,216 @@
+use anyhow::Result;
+use bytes::Bytes;
+use futures::stream;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+use tokio::time::{sleep, timeout};
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::mock::{MockRemote, MockTransport};
+use wasmcloud_provider_messaging_websocket::testing::accept;
+use wasmcloud_provider_messaging_websocket::{
+    BrokerMessage, DeadLetterReason, DeadlineExceeded, ProviderEvent, WebSocketMessagingProvider,
+    DEADLINE_HEADER,
+};
+
+const START_MS: u64 = 1_700_000_000_000;
+
+/// A provider whose deadlines are checked against `clock`, in Unix milliseconds
+fn provider(
+    transport: MockTransport,
+    clock: &Arc<AtomicU64>,
+) -> Result<WebSocketMessagingProvider> {
+    let mut config = HashMap::new();
+    config.insert("URI".to_string(), "ws://mock.invalid/ws".to_string());
+    let clock = Arc::clone(clock);
+    Ok(WebSocketMessagingProvider::from_config(config)?
+        .with_transport(transport)
+        .with_clock(move || UNIX_EPOCH + Duration::from_millis(clock.load(Ordering::SeqCst))))
+}
+
+fn message(subject: &str, deadline: Option<u64>) -> BrokerMessage {
+    let mut headers = HashMap::new();
+    if let Some(deadline) = deadline {
+        headers.insert(DEADLINE_HEADER.to_string(), deadline.to_string());
+    }
+    BrokerMessage {
+        subject: subject.to_string(),
+        body: Bytes::from("x"),
+        reply_to: None,
+        headers,
+    }
+}
+
+async fn next_subject(remote: &mut MockRemote) -> Result<String> {
+    let frame = timeout(Duration::from_secs(1), remote.recv())
+        .await?
+        .expect("frame");
+    let json: serde_json::Value = serde_json::from_str(frame.to_text()?)?;
+    Ok(json["subject"].as_str().unwrap_or_default().to_string())
+}
+
+/// Test that publishing a message already past its deadline fails without sending it
+#[tokio::test]
+async fn test_expired_at_publish() -> Result<()> {
+    let clock = Arc::new(AtomicU64::new(START_MS));
+    let (transport, remotes) = MockTransport::new();
+    let provider = provider(transport, &clock)?;
+    provider
+        .receive_link_config_as_target("publisher", HashMap::new())
+        .await?;
+    let mut remote = accept(&remotes).await;
+
+    let error = provider
+        .publish("publisher", message("jobs.late", Some(START_MS - 1)))
+        .await
+        .expect_err("expired message should be rejected");
+    assert_eq!(
+        error.downcast_ref::<DeadlineExceeded>(),
+        Some(&DeadlineExceeded {
+            subject: "jobs.late".to_string(),
+            deadline_unix_ms: START_MS - 1,
+            now_unix_ms: START_MS,
+        })
+    );
+    assert_eq!(provider.expired_message_count(), 1);
+
+    // A future deadline goes through untouched
+    provider
+        .publish("publisher", message("jobs.on_time", Some(START_MS + 1_000)))
+        .await?;
+    let frame = timeout(Duration::from_secs(1), remote.recv())
+        .await?
+        .expect("frame");
+    let json: serde_json::Value = serde_json::from_str(frame.to_text()?)?;
+    assert_eq!(json["subject"], "jobs.on_time");
+    assert_eq!(
+        json["headers"][DEADLINE_HEADER],
+        (START_MS + 1_000).to_string()
+    );
+    assert_eq!(provider.expired_message_count(), 1);
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a body stream whose descriptor is past its deadline fails before anything
+/// is sent or the body is read
+#[tokio::test]
+async fn test_expired_stream_at_publish() -> Result<()> {
+    let clock = Arc::new(AtomicU64::new(START_MS));
+    let (transport, remotes) = MockTransport::new();
+    let provider = provider(transport, &clock)?;
+    provider
+        .receive_link_config_as_target("publisher", HashMap::new())
+        .await?;
+    let mut remote = accept(&remotes).await;
+
+    let headers = HashMap::from([(DEADLINE_HEADER.to_string(), (START_MS - 1).to_string())]);
+    let body = stream::poll_fn(|_| -> std::task::Poll<Option<Bytes>> {
+        panic!("the body of an expired stream shouldn't be read")
+    });
+    let error = provider
+        .publish_stream("publisher", "files.late", body, headers)
+        .await
+        .expect_err("expired stream should be rejected");
+    assert_eq!(
+        error.downcast_ref::<DeadlineExceeded>(),
+        Some(&DeadlineExceeded {
+            subject: "files.late".to_string(),
+            deadline_unix_ms: START_MS - 1,
+            now_unix_ms: START_MS,
+        })
+    );
+    assert_eq!(provider.expired_message_count(), 1);
+    assert!(timeout(Duration::from_millis(100), remote.recv())
+        .await
+        .is_err());
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a message whose deadline passes while it is queued is never written
+#[tokio::test]
+async fn test_expired_in_queue() -> Result<()> {
+    let clock = Arc::new(AtomicU64::new(START_MS));
+    let (transport, remotes) = MockTransport::new();
+    // The socket holds a single unread frame, so later ones wait in the queue
+    transport.set_write_capacity(Some(0));
+    let provider = provider(transport, &clock)?;
+    provider
+        .receive_link_config_as_target("publisher", HashMap::new())
+        .await?;
+    let mut remote = accept(&remotes).await;
+
+    provider
+        .publish("publisher", message("jobs.1", None))
+        .await?;
+    provider
+        .publish("publisher", message("jobs.2", None))
+        .await?;
+    sleep(Duration::from_millis(100)).await;
+    provider
+        .publish("publisher", message("jobs.3", Some(START_MS + 1_000)))
+        .await?;
+    clock.fetch_add(5_000, Ordering::SeqCst);
+
+    assert_eq!(next_subject(&mut remote).await?, "jobs.1");
+    assert_eq!(next_subject(&mut remote).await?, "jobs.2");
+    provider
+        .publish("publisher", message("jobs.4", None))
+        .await?;
+    assert_eq!(next_subject(&mut remote).await?, "jobs.4");
+    assert_eq!(provider.expired_message_count(), 1);
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that an inbound message past its deadline is dead-lettered instead of forwarded
+#[tokio::test]
+async fn test_expired_inbound() -> Result<()> {
+    let clock = Arc::new(AtomicU64::new(START_MS));
+    let (transport, remotes) = MockTransport::new();
+    let provider = provider(transport, &clock)?;
+    let mut events = provider.subscribe_events();
+    provider
+        .receive_link_config_as_target("publisher", HashMap::new())
+        .await?;
+    let source = accept(&remotes).await;
+    provider
+        .receive_link_config_as_source("handler", HashMap::new())
+        .await?;
+    let mut handler = accept(&remotes).await;
+
+    for (subject, deadline) in [("jobs.late", START_MS - 1), ("jobs.on_time", START_MS + 1)] {
+        source.send(Message::Text(
+            serde_json::json!({
+                "subject": subject,
+                "body": "",
+                "headers": { DEADLINE_HEADER: deadline.to_string() },
+            })
+            .to_string(),
+        ))?;
+    }
+
+    assert_eq!(next_subject(&mut handler).await?, "jobs.on_time");
+    loop {
+        let event = timeout(Duration::from_secs(1), events.recv()).await??;
+        if let ProviderEvent::DeadLetter {
+            subject, reason, ..
+        } = event
+        {
+            assert_eq!(subject, "jobs.late");
+            assert_eq!(reason, DeadLetterReason::DeadlineExceeded);
+            assert_eq!(reason.as_str(), "deadline_exceeded");
+            break;
+        }
+    }
+    assert_eq!(provider.expired_message_count(), 1);
+
+    provider.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,98 @@
+use anyhow::Result;
+use std::collections::HashMap;
+
+use wasmcloud_provider_messaging_websocket::mock::MockTransport;
+use wasmcloud_provider_messaging_websocket::testing::{accept, connect, serve, server_config};
+use wasmcloud_provider_messaging_websocket::{
+    LinkConnectionStatus, LinkRole, WebSocketMessagingProvider, WsConnectionMode,
+};
+
+/// Test that the snapshot lists both links, the server client and their sessions
+/// consistently, with the link secrets redacted
+#[tokio::test]
+async fn test_snapshot_reflects_links_and_clients() -> Result<()> {
+    let config = server_config(&[("AUTH_TOKEN", "server-secret")]);
+    // Links still open client connections of their own; the mock stands in
+    let (transport, remotes) = MockTransport::new();
+    let provider =
+        serve(WebSocketMessagingProvider::from_config(config)?.with_transport(transport)).await?;
+    let addr = provider.get_server_addr().await.unwrap();
+
+    let link = |extra: &[(&str, &str)]| {
+        let mut link = HashMap::from([
+            ("URI".to_string(), "ws://mock.invalid/ws".to_string()),
+            ("AUTH_TOKEN".to_string(), "link-secret".to_string()),
+            ("HEADER_X-API-Key".to_string(), "key-4711".to_string()),
+        ]);
+        for (key, value) in extra {
+            link.insert(key.to_string(), value.to_string());
+        }
+        link
+    };
+    provider
+        .receive_link_config_as_target("publisher", link(&[]))
+        .await?;
+    accept(&remotes).await;
+    provider
+        .receive_link_config_as_source("orders", link(&[("SUBSCRIPTIONS", "orders.>")]))
+        .await?;
+    accept(&remotes).await;
+
+    let (_client, session_id) = connect(&provider).await?;
+    provider
+        .add_ws_client_to_group(&session_id, "room-1")
+        .await?;
+
+    let snapshot = provider.diagnostics().await;
+    assert_eq!(snapshot.mode, WsConnectionMode::Server);
+    assert_eq!(snapshot.server_addr, Some(addr));
+    assert_eq!(snapshot.config.auth_token.as_deref(), Some("[REDACTED]"));
+
+    let mut linked = snapshot.consumers.clone();
+    linked.extend(snapshot.handlers.clone());
+    linked.sort();
+    assert_eq!(linked, vec!["orders".to_string(), "publisher".to_string()]);
+    assert_eq!(snapshot.connections.len(), 2);
+    for connection in &snapshot.connections {
+        assert_eq!(
+            connection.link.connection_status,
+            LinkConnectionStatus::Connected
+        );
+        assert_eq!(connection.queued_frames, 0);
+        assert!(connection.queue_capacity > 0);
+        assert_eq!(connection.config.auth_token.as_deref(), Some("[REDACTED]"));
+        assert_eq!(connection.config.custom_headers["X-API-Key"], "[REDACTED]");
+
+        let listed = match connection.link.role {
+            LinkRole::Consumer => &snapshot.consumers,
+            LinkRole::Handler => &snapshot.handlers,
+        };
+        assert!(listed.contains(&connection.link.component_id));
+        // Every connection's session is among the component sessions
+        let session = connection.link.session_id.clone().unwrap();
+        assert!(snapshot
+            .sessions
+            .contains(&(session, connection.link.component_id.clone())));
+    }
+    assert!(snapshot.failed_links.is_empty());
+    let subscriptions = snapshot
+        .subscriptions
+        .values()
+        .find(|patterns| !patterns.is_empty());
+    assert_eq!(subscriptions, Some(&vec!["orders.>".to_string()]));
+
+    assert_eq!(snapshot.server_clients.len(), 1);
+    let client = &snapshot.server_clients[0];
+    assert_eq!(client.session_id, session_id);
+    assert_eq!(client.groups, vec!["room-1".to_string()]);
+    assert!(client.metadata.contains_key("connection_id"));
+
+    // Nothing secret survives into a dump attached to a ticket
+    let dump = serde_json::to_string(&snapshot)?;
+    assert!(!dump.contains("server-secret"), "{}", dump);
+    assert!(!dump.contains("link-secret"), "{}", dump);
+    assert!(!dump.contains("key-4711"), "{}", dump);
+
+    provider.shutdown().await?;
+    Ok(())
+}
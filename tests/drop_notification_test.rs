@@ -0,0 +1,123 @@
+use anyhow::Result;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::{timeout, Instant};
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::mock::{MockRemote, MockTransport};
+use wasmcloud_provider_messaging_websocket::testing::accept;
+use wasmcloud_provider_messaging_websocket::WebSocketMessagingProvider;
+
+/// Bodies of the `$SYS.drop` notifications arriving on `remote` within `wait`
+async fn drop_notices(remote: &mut MockRemote, wait: Duration) -> Vec<serde_json::Value> {
+    let deadline = Instant::now() + wait;
+    let mut notices = Vec::new();
+    while let Ok(Some(frame)) = timeout(deadline - Instant::now(), remote.recv()).await {
+        let Message::Text(text) = frame else {
+            continue;
+        };
+        let json: serde_json::Value = serde_json::from_str(&text).unwrap();
+        if json["subject"] == "$SYS.drop" {
+            assert_eq!(json["body_encoding"], "base64");
+            let body = STANDARD.decode(json["body"].as_str().unwrap()).unwrap();
+            notices.push(serde_json::from_slice(&body).unwrap());
+        }
+    }
+    notices
+}
+
+/// Link a handler that never drains its queue and a publisher whose messages it drops
+async fn link_stalled_handler(
+    notifications: bool,
+) -> Result<(WebSocketMessagingProvider, MockRemote, MockRemote)> {
+    let mut config = HashMap::new();
+    config.insert("URI".to_string(), "ws://mock.invalid/ws".to_string());
+
+    let (transport, remotes) = MockTransport::new();
+    let provider =
+        WebSocketMessagingProvider::from_config(config)?.with_transport(transport.clone());
+
+    transport.set_write_capacity(Some(0));
+    let mut stalled = HashMap::new();
+    stalled.insert("OUTBOUND_QUEUE_CAPACITY".to_string(), "2".to_string());
+    stalled.insert("SUBSCRIPTIONS".to_string(), "orders.>".to_string());
+    provider
+        .receive_link_config_as_source("stalled", stalled)
+        .await?;
+    let stalled_remote = accept(&remotes).await;
+
+    transport.set_write_capacity(None);
+    let mut publisher = HashMap::new();
+    publisher.insert("SUBSCRIPTIONS".to_string(), "replies.>".to_string());
+    publisher.insert("DROP_NOTIFICATIONS".to_string(), notifications.to_string());
+    provider
+        .receive_link_config_as_source("publisher", publisher)
+        .await?;
+    let publisher_remote = accept(&remotes).await;
+
+    Ok((provider, stalled_remote, publisher_remote))
+}
+
+fn order(n: usize) -> Message {
+    Message::Text(format!(
+        r#"{{"subject":"orders.{n}","body":"x","headers":{{"msg-id":"m{n}"}}}}"#
+    ))
+}
+
+/// Test that overflow drops are reported to the publisher, individually and then summarized
+#[tokio::test]
+async fn test_overflow_drops_notified() -> Result<()> {
+    let (provider, _stalled, mut publisher) = link_stalled_handler(true).await?;
+
+    const MESSAGES: usize = 40;
+    for n in 0..MESSAGES {
+        publisher.send(order(n))?;
+    }
+
+    let notices = drop_notices(&mut publisher, Duration::from_millis(1500)).await;
+    let (summaries, individual): (Vec<_>, Vec<_>) =
+        notices.iter().partition(|n| n["summary"] == true);
+    assert_eq!(individual.len(), 10, "individual notices are rate-limited");
+    assert_eq!(individual[0]["reason"], "handler_overflow");
+    assert_eq!(individual[0]["component_id"], "stalled");
+    assert!(individual[0]["msg_id"].as_str().unwrap().starts_with('m'));
+    assert!(individual[0]["timestamp_ms"].as_u64().unwrap() > 0);
+
+    assert_eq!(summaries.len(), 1);
+    let dropped = provider.handler_dropped_count("stalled").await.unwrap();
+    assert_eq!(
+        summaries[0]["dropped"].as_u64().unwrap() + individual.len() as u64,
+        dropped
+    );
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that drops aren't reported without `DROP_NOTIFICATIONS` or a `msg-id`
+#[tokio::test]
+async fn test_drops_not_notified_by_default() -> Result<()> {
+    let (provider, _stalled, mut publisher) = link_stalled_handler(false).await?;
+    for n in 0..20 {
+        publisher.send(order(n))?;
+    }
+    assert!(drop_notices(&mut publisher, Duration::from_millis(200))
+        .await
+        .is_empty());
+    assert!(provider.handler_dropped_count("stalled").await.unwrap() > 0);
+    provider.shutdown().await?;
+
+    let (provider, _stalled, mut publisher) = link_stalled_handler(true).await?;
+    for n in 0..20 {
+        publisher.send(Message::Text(format!(
+            r#"{{"subject":"orders.{n}","body":"x"}}"#
+        )))?;
+    }
+    assert!(drop_notices(&mut publisher, Duration::from_millis(200))
+        .await
+        .is_empty());
+    provider.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,249 @@
+use anyhow::Result;
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::{sleep, timeout};
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::mock::{MockRemote, MockTransport};
+use wasmcloud_provider_messaging_websocket::testing::{
+    connect, record_subjects, start_server, WsClient,
+};
+use wasmcloud_provider_messaging_websocket::{
+    BrokerMessage, WebSocketMessagingProvider, ENVELOPE_V1, ENVELOPE_V2,
+};
+
+const COMPONENT: &str = "test-handler";
+
+async fn handler_link(grace_ms: &str) -> Result<(WebSocketMessagingProvider, MockRemote)> {
+    let mut config = HashMap::new();
+    config.insert("MODE".to_string(), "client".to_string());
+    config.insert("URI".to_string(), "ws://mock.invalid/ws".to_string());
+    let (transport, remotes) = MockTransport::new();
+    let provider = WebSocketMessagingProvider::from_config(config)?.with_transport(transport);
+
+    let mut link = HashMap::new();
+    link.insert(
+        "ENVELOPE_UPGRADE_GRACE_MS".to_string(),
+        grace_ms.to_string(),
+    );
+    provider
+        .receive_link_config_as_source(COMPONENT, link)
+        .await?;
+    let remote = timeout(Duration::from_secs(1), remotes.accept())
+        .await?
+        .expect("transport should be alive");
+    Ok((provider, remote))
+}
+
+fn envelope(subject: &str, version: Option<u8>) -> Message {
+    let mut json = serde_json::json!({ "subject": subject, "body": "x" });
+    if let Some(version) = version {
+        json["v"] = serde_json::json!(version);
+    }
+    Message::Text(json.to_string())
+}
+
+/// Next frame from the provider, as JSON
+async fn recv_json(remote: &mut MockRemote) -> Result<serde_json::Value> {
+    let frame = timeout(Duration::from_secs(1), remote.recv())
+        .await?
+        .expect("client should still be connected");
+    let Message::Text(text) = frame else {
+        anyhow::bail!("expected a text frame, got {:?}", frame)
+    };
+    Ok(serde_json::from_str(&text)?)
+}
+
+/// Next text frame a server-mode provider sends its client, as JSON
+async fn next_json(ws: &mut WsClient) -> Result<serde_json::Value> {
+    loop {
+        let frame = timeout(Duration::from_secs(1), ws.next())
+            .await?
+            .expect("server should still be connected")?;
+        if let Message::Text(text) = frame {
+            return Ok(serde_json::from_str(&text)?);
+        }
+    }
+}
+
+/// Collect frames until `count` handler deliveries have arrived, returning them with
+/// whether each arrived after the upgrade ack
+async fn deliveries_around_ack(
+    remote: &mut MockRemote,
+    count: usize,
+) -> Result<(Vec<(bool, serde_json::Value)>, serde_json::Value)> {
+    let mut ack = None;
+    let mut deliveries = Vec::new();
+    while deliveries.len() < count {
+        let json = recv_json(remote).await?;
+        if json.get("op").is_some() {
+            ack = Some(json);
+        } else {
+            deliveries.push((ack.is_some(), json));
+        }
+    }
+    Ok((deliveries, ack.expect("upgrade should be acknowledged")))
+}
+
+/// Test that an upgrade requested by the remote server is acknowledged, frames it sent
+/// in v1 before seeing the ack are still accepted, and every frame after the ack is v2
+#[tokio::test]
+async fn test_remote_initiated_upgrade_with_frames_in_flight() -> Result<()> {
+    let (provider, mut remote) = handler_link("5000").await?;
+    assert_eq!(
+        provider.envelope_version(COMPONENT).await,
+        Some(ENVELOPE_V1)
+    );
+
+    remote.send(envelope("before", None))?;
+    remote.send(Message::Text(
+        r#"{"op":"upgrade","versions":[2]}"#.to_string(),
+    ))?;
+    // Sent before the remote has seen the ack
+    remote.send(envelope("in-flight", None))?;
+    remote.send(envelope("after", Some(ENVELOPE_V2)))?;
+
+    let (deliveries, ack) = deliveries_around_ack(&mut remote, 3).await?;
+    assert_eq!(ack["op"], "upgrade_ack");
+    assert_eq!(ack["version"], ENVELOPE_V2);
+    let subjects: Vec<_> = deliveries
+        .iter()
+        .map(|(_, json)| json["subject"].as_str().unwrap_or_default())
+        .collect();
+    assert_eq!(subjects, vec!["before", "in-flight", "after"]);
+    for (after_ack, json) in &deliveries[1..] {
+        assert!(after_ack, "{} was written before the ack", json);
+        assert_eq!(json["v"], ENVELOPE_V2);
+    }
+    // Encoded before the upgrade; it may be written on either side of the ack
+    assert!(deliveries[0].1.get("v").is_none());
+
+    assert_eq!(
+        provider.envelope_version(COMPONENT).await,
+        Some(ENVELOPE_V2)
+    );
+    assert!(provider.parse_failure_counts().is_empty());
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that an upgrade asked for by the provider applies once acknowledged, and v1
+/// frames are only accepted during the grace window
+#[tokio::test]
+async fn test_provider_initiated_upgrade_and_grace_window() -> Result<()> {
+    let (provider, mut remote) = handler_link("200").await?;
+
+    provider.upgrade_envelope(COMPONENT, ENVELOPE_V2).await?;
+    let request = recv_json(&mut remote).await?;
+    assert_eq!(request["op"], "upgrade");
+    assert_eq!(request["versions"], serde_json::json!([2]));
+    assert_eq!(
+        provider.envelope_version(COMPONENT).await,
+        Some(ENVELOPE_V1)
+    );
+
+    remote.send(Message::Text(
+        r#"{"op":"upgrade_ack","version":2}"#.to_string(),
+    ))?;
+    remote.send(envelope("in-flight", None))?;
+    let delivered = recv_json(&mut remote).await?;
+    assert_eq!(delivered["subject"], "in-flight");
+    assert_eq!(delivered["v"], ENVELOPE_V2);
+    assert_eq!(
+        provider.envelope_version(COMPONENT).await,
+        Some(ENVELOPE_V2)
+    );
+    assert!(provider.parse_failure_counts().is_empty());
+
+    // Once the grace window has passed, v1 frames are rejected
+    sleep(Duration::from_millis(300)).await;
+    remote.send(envelope("stale", None))?;
+    remote.send(envelope("current", Some(ENVELOPE_V2)))?;
+    let delivered = recv_json(&mut remote).await?;
+    assert_eq!(delivered["subject"], "current");
+    assert_eq!(provider.parse_failure_counts()["unsupported_version"], 1);
+
+    assert!(provider.upgrade_envelope(COMPONENT, 9).await.is_err());
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that refused upgrades leave the session on its current version
+#[tokio::test]
+async fn test_refused_upgrade_keeps_version() -> Result<()> {
+    let (provider, mut remote) = handler_link("5000").await?;
+
+    remote.send(Message::Text(
+        r#"{"op":"upgrade","versions":[9]}"#.to_string(),
+    ))?;
+    let refused = recv_json(&mut remote).await?;
+    assert_eq!(refused["op"], "upgrade_refused");
+    assert_eq!(refused["version"], ENVELOPE_V1);
+
+    provider.upgrade_envelope(COMPONENT, ENVELOPE_V2).await?;
+    assert_eq!(recv_json(&mut remote).await?["op"], "upgrade");
+    remote.send(Message::Text(
+        r#"{"op":"upgrade_refused","version":1}"#.to_string(),
+    ))?;
+    remote.send(envelope("still-v1", None))?;
+    let delivered = recv_json(&mut remote).await?;
+    assert_eq!(delivered["subject"], "still-v1");
+    assert!(delivered.get("v").is_none());
+    assert_eq!(
+        provider.envelope_version(COMPONENT).await,
+        Some(ENVELOPE_V1)
+    );
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a server-mode client can upgrade its session: the ack is written before the
+/// first v2 envelope, and once the grace window has passed only v2 envelopes from the
+/// client are delivered
+#[tokio::test]
+async fn test_server_session_upgrade() -> Result<()> {
+    let provider = start_server(&[("ENVELOPE_UPGRADE_GRACE_MS", "200")]).await?;
+    let received = record_subjects(&provider).await?;
+    let (mut ws, _) = connect(&provider).await?;
+
+    ws.send(Message::Text(
+        r#"{"op":"upgrade","versions":[2]}"#.to_string(),
+    ))
+    .await?;
+    // Sent before the client has seen the ack
+    ws.send(envelope("in-flight", None)).await?;
+    let ack = next_json(&mut ws).await?;
+    assert_eq!(ack["op"], "upgrade_ack");
+    assert_eq!(ack["version"], ENVELOPE_V2);
+
+    provider
+        .broadcast_to_clients(BrokerMessage {
+            subject: "news".to_string(),
+            body: Bytes::from("x"),
+            reply_to: None,
+            headers: HashMap::new(),
+        })
+        .await?;
+    let broadcast = next_json(&mut ws).await?;
+    assert_eq!(broadcast["subject"], "news");
+    assert_eq!(broadcast["v"], ENVELOPE_V2);
+
+    sleep(Duration::from_millis(300)).await;
+    ws.send(envelope("stale", None)).await?;
+    ws.send(envelope("current", Some(ENVELOPE_V2))).await?;
+    for _ in 0..100 {
+        if received.lock().unwrap().len() == 2 {
+            break;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(*received.lock().unwrap(), vec!["in-flight", "current"]);
+    assert_eq!(provider.parse_failure_counts()["unsupported_version"], 1);
+
+    provider.shutdown().await?;
+    Ok(())
+}
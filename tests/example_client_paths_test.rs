@@ -0,0 +1,157 @@
+use anyhow::Result;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::testing::spawn_recording_server;
+use wasmcloud_provider_messaging_websocket::{BrokerMessage, WebSocketMessagingProvider};
+
+/// Id the example's wadm manifest gives the component
+const COMPONENT_ID: &str = "client-component";
+
+/// Subjects the component's handler link subscribes to
+const SUBSCRIPTIONS: &str = "example.>";
+
+/// Header the component expects the remote server to echo back
+const CHECK_HEADER: &str = "x-example-check";
+
+/// Start the remote server the example expects: a request (an envelope with a `corr_id`)
+/// is answered on its `reply_to` subject with the same body, anything else is echoed
+async fn start_scripted_server() -> Result<SocketAddr> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let Ok(mut ws) = tokio_tungstenite::accept_async(stream).await else {
+                    return;
+                };
+                while let Some(Ok(Message::Text(text))) = ws.next().await {
+                    let envelope: serde_json::Value = serde_json::from_str(&text).unwrap();
+                    let answer = match (envelope.get("corr_id"), envelope.get("reply_to")) {
+                        (Some(corr_id), Some(reply_to)) if !reply_to.is_null() => {
+                            serde_json::json!({
+                                "subject": reply_to,
+                                "body": envelope["body"],
+                                "body_encoding": envelope["body_encoding"],
+                                "corr_id": corr_id,
+                            })
+                        }
+                        _ => envelope,
+                    };
+                    if ws.send(Message::Text(answer.to_string())).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    Ok(addr)
+}
+
+/// Link the component as the example's manifest does: publishing through the scripted
+/// server, and with its handler link subscribed to `example.>` delivered to `handler_addr`
+async fn link_component(
+    server_addr: SocketAddr,
+    handler_addr: SocketAddr,
+) -> Result<WebSocketMessagingProvider> {
+    let provider = WebSocketMessagingProvider::from_config(HashMap::from([(
+        "MODE".to_string(),
+        "client".to_string(),
+    )]))?;
+    let consumer = HashMap::from([("URI".to_string(), format!("ws://{}/ws", server_addr))]);
+    provider
+        .receive_link_config_as_target(COMPONENT_ID, consumer)
+        .await?;
+    let handler = HashMap::from([
+        ("URI".to_string(), format!("ws://{}/ws", handler_addr)),
+        ("SUBSCRIPTIONS".to_string(), SUBSCRIPTIONS.to_string()),
+    ]);
+    provider
+        .receive_link_config_as_source(COMPONENT_ID, handler)
+        .await?;
+    Ok(provider)
+}
+
+/// The next envelope delivered to the component's handler, or `None` if nothing comes
+async fn next_delivery(
+    delivered: &mut mpsc::UnboundedReceiver<Message>,
+) -> Result<Option<serde_json::Value>> {
+    let Ok(frame) = timeout(Duration::from_millis(500), delivered.recv()).await else {
+        return Ok(None);
+    };
+    let frame = frame.expect("recording server should be running");
+    Ok(Some(serde_json::from_str(frame.to_text()?)?))
+}
+
+fn message(subject: &str, body: &str, headers: HashMap<String, String>) -> BrokerMessage {
+    BrokerMessage {
+        subject: subject.to_string(),
+        body: Bytes::from(body.to_string()),
+        reply_to: None,
+        headers,
+    }
+}
+
+/// Test the provider paths the example client component calls, through the provider's
+/// Rust API: a handler link only gets subjects it subscribed to, a published header
+/// comes back on the echo, and a request resolves with the remote server's reply
+///
+/// The component itself isn't run, so this doesn't check the component's own behaviour.
+#[tokio::test]
+async fn test_provider_paths_used_by_example_client() -> Result<()> {
+    let _ = tracing_subscriber::fmt().with_test_writer().try_init();
+    let server_addr = start_scripted_server().await?;
+    let (handler_addr, mut delivered, recorder) = spawn_recording_server().await?;
+    let provider = link_component(server_addr, handler_addr).await?;
+
+    // Subscription: an echo outside `example.>` never reaches the handler
+    provider
+        .publish(
+            COMPONENT_ID,
+            message("other.subject", "ignored", HashMap::new()),
+        )
+        .await?;
+
+    // Header echo: a header set on a publish comes back on the echo the handler gets
+    let check = uuid::Uuid::new_v4().to_string();
+    let headers = HashMap::from([(CHECK_HEADER.to_string(), check.clone())]);
+    provider
+        .publish(COMPONENT_ID, message("example.headers", "hello", headers))
+        .await?;
+    let envelope = next_delivery(&mut delivered)
+        .await?
+        .expect("echo on example.headers should be delivered");
+    assert_eq!(envelope["subject"], "example.headers");
+    assert_eq!(envelope["headers"][CHECK_HEADER], check.as_str());
+    assert_eq!(
+        STANDARD.decode(envelope["body"].as_str().unwrap())?,
+        b"hello"
+    );
+    assert!(next_delivery(&mut delivered).await?.is_none());
+
+    // Request-reply: the call the component makes on `example.start`
+    let reply = provider
+        .request(
+            COMPONENT_ID,
+            "example.echo".to_string(),
+            Bytes::from("ping"),
+            Duration::from_secs(2),
+        )
+        .await?;
+    assert_eq!(reply.body, Bytes::from("ping"));
+    // The reply resolved the request instead of going to the handler
+    assert!(next_delivery(&mut delivered).await?.is_none());
+
+    provider.shutdown().await?;
+    recorder.shutdown().await;
+    Ok(())
+}
@@ -0,0 +1,141 @@
+use anyhow::Result;
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::time::{sleep, timeout};
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::testing::spawn_recording_server;
+use wasmcloud_provider_messaging_websocket::{BrokerMessage, WebSocketMessagingProvider};
+
+fn failover_provider() -> Result<WebSocketMessagingProvider> {
+    WebSocketMessagingProvider::from_config(HashMap::from([
+        ("RECONNECT".to_string(), "true".to_string()),
+        ("RECONNECT_BASE_DELAY_MS".to_string(), "10".to_string()),
+    ]))
+}
+
+fn message(subject: &str) -> BrokerMessage {
+    BrokerMessage {
+        subject: subject.to_string(),
+        body: Bytes::from("hello"),
+        reply_to: None,
+        headers: HashMap::new(),
+    }
+}
+
+/// The subject of the next envelope a recording server received
+async fn next_subject(received: &mut mpsc::UnboundedReceiver<Message>) -> Result<String> {
+    let frame = timeout(Duration::from_secs(2), received.recv())
+        .await?
+        .expect("recording server should be running");
+    let envelope: serde_json::Value = serde_json::from_str(frame.to_text()?)?;
+    Ok(envelope["subject"].as_str().unwrap_or_default().to_string())
+}
+
+async fn connected_uri(provider: &WebSocketMessagingProvider, component_id: &str) -> String {
+    provider
+        .client_session_info(component_id)
+        .await
+        .expect("component should be linked")
+        .metadata["connected_uri"]
+        .clone()
+}
+
+/// Test that killing the first endpoint moves a link's traffic to the second, without
+/// relinking the component
+#[tokio::test]
+async fn test_fails_over_to_next_uri() -> Result<()> {
+    let (first_addr, mut first, first_server) = spawn_recording_server().await?;
+    let (second_addr, mut second, second_server) = spawn_recording_server().await?;
+    let first_uri = format!("ws://{}/ws", first_addr);
+    let second_uri = format!("ws://{}/ws", second_addr);
+
+    let provider = failover_provider()?;
+    provider
+        .receive_link_config_as_target(
+            "publisher",
+            HashMap::from([("URI".to_string(), format!("{},{}", first_uri, second_uri))]),
+        )
+        .await?;
+    assert_eq!(connected_uri(&provider, "publisher").await, first_uri);
+    let (session_id, label) = provider.list_sessions().await.pop().unwrap();
+    assert_eq!(label, format!("component:publisher@{}", first_uri));
+
+    provider.publish("publisher", message("before")).await?;
+    assert_eq!(next_subject(&mut first).await?, "before");
+
+    first_server.shutdown().await;
+    let mut moved = false;
+    for _ in 0..100 {
+        if connected_uri(&provider, "publisher").await == second_uri {
+            moved = true;
+            break;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    assert!(moved, "connection should fail over to the second URI");
+    assert_eq!(
+        provider.list_sessions().await,
+        vec![(session_id, format!("component:publisher@{}", second_uri))]
+    );
+
+    provider.publish("publisher", message("after")).await?;
+    assert_eq!(next_subject(&mut second).await?, "after");
+
+    provider.shutdown().await?;
+    second_server.shutdown().await;
+    Ok(())
+}
+
+/// Test that a URI that can't be reached is skipped when linking
+#[tokio::test]
+async fn test_skips_unreachable_uri() -> Result<()> {
+    // A port nothing listens on any more
+    let closed = TcpListener::bind("127.0.0.1:0").await?.local_addr()?;
+    let (addr, mut received, server) = spawn_recording_server().await?;
+    let uri = format!("ws://{}/ws", addr);
+
+    let provider = failover_provider()?;
+    provider
+        .receive_link_config_as_target(
+            "publisher",
+            HashMap::from([("URI".to_string(), format!("ws://{}/ws, {}", closed, uri))]),
+        )
+        .await?;
+    assert_eq!(connected_uri(&provider, "publisher").await, uri);
+
+    provider.publish("publisher", message("hello")).await?;
+    assert_eq!(next_subject(&mut received).await?, "hello");
+
+    provider.shutdown().await?;
+    server.shutdown().await;
+    Ok(())
+}
+
+/// Test that a link fails when none of its URIs can be reached, naming each of them
+#[tokio::test]
+async fn test_all_uris_unreachable() -> Result<()> {
+    let first = TcpListener::bind("127.0.0.1:0").await?.local_addr()?;
+    let second = TcpListener::bind("127.0.0.1:0").await?.local_addr()?;
+
+    let provider = WebSocketMessagingProvider::from_config(HashMap::new())?;
+    let error = provider
+        .receive_link_config_as_target(
+            "publisher",
+            HashMap::from([(
+                "URI".to_string(),
+                format!("ws://{}/ws,ws://{}/ws", first, second),
+            )]),
+        )
+        .await
+        .unwrap_err();
+    let error = format!("{:#}", error);
+    assert!(error.contains(&first.to_string()), "{}", error);
+    assert!(error.contains(&second.to_string()), "{}", error);
+
+    provider.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,170 @@
+use anyhow::Result;
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::{sleep, timeout};
+
+use wasmcloud_provider_messaging_websocket::mock::{MockRemote, MockTransport};
+use wasmcloud_provider_messaging_websocket::testing::accept;
+use wasmcloud_provider_messaging_websocket::{
+    BrokerMessage, FlowControl, WebSocketMessagingProvider, FLOW_PAUSE_SUBJECT, FLOW_RESUME_SUBJECT,
+};
+
+fn message(n: usize) -> BrokerMessage {
+    BrokerMessage {
+        subject: format!("batch.{}", n),
+        body: Bytes::from("x"),
+        reply_to: None,
+        headers: HashMap::new(),
+    }
+}
+
+/// A provider whose connection holds 10 frames and whose remote doesn't read until told,
+/// with the client message handler reporting what it is given
+async fn stalled_provider(
+    flow_signals: bool,
+) -> Result<(
+    WebSocketMessagingProvider,
+    MockRemote,
+    mpsc::UnboundedReceiver<(String, BrokerMessage)>,
+)> {
+    let (transport, remotes) = MockTransport::new();
+    transport.set_write_capacity(Some(0));
+    let provider = WebSocketMessagingProvider::from_config(HashMap::from([
+        ("URI".to_string(), "ws://mock.invalid/ws".to_string()),
+        ("OUTBOUND_QUEUE_CAPACITY".to_string(), "10".to_string()),
+        ("FLOW_SIGNALS".to_string(), flow_signals.to_string()),
+    ]))?
+    .with_transport(transport);
+    let (tx, rx) = mpsc::unbounded_channel();
+    provider
+        .set_client_message_handler(move |session_id: String, msg: BrokerMessage| {
+            tx.send((session_id, msg))?;
+            Ok(())
+        })
+        .await;
+    provider
+        .receive_link_config_as_target("publisher", HashMap::new())
+        .await?;
+    let remote = accept(&remotes).await;
+
+    // The writer takes the first frame and waits for the remote to read it
+    provider.publish("publisher", message(0)).await?;
+    sleep(Duration::from_millis(100)).await;
+    Ok((provider, remote, rx))
+}
+
+/// Test that a filling queue is reported by `get_flow_control`, paused once at the high
+/// watermark and resumed once writing drains it to the low one
+#[tokio::test]
+async fn test_pause_and_resume_signals() -> Result<()> {
+    let (provider, mut remote, mut signals) = stalled_provider(true).await?;
+    assert_eq!(
+        provider.get_flow_control("publisher").await,
+        Some(FlowControl {
+            queue_capacity: 10,
+            queue_used: 0,
+            rate_limit_remaining: None,
+            suggested_delay_ms: 0,
+        })
+    );
+    assert_eq!(provider.get_flow_control("unknown").await, None);
+
+    for n in 1..8 {
+        provider.publish("publisher", message(n)).await?;
+    }
+    let flow = provider.get_flow_control("publisher").await.unwrap();
+    assert_eq!(flow.queue_used, 7);
+    assert!(signals.try_recv().is_err());
+
+    // The eighth queued frame reaches 80% of the capacity
+    provider.publish("publisher", message(8)).await?;
+    let (session_id, pause) = signals.try_recv()?;
+    assert_eq!(pause.subject, FLOW_PAUSE_SUBJECT);
+    let session = provider.client_session_info("publisher").await.unwrap();
+    assert_eq!(session_id, session.session_id);
+    let body: serde_json::Value = serde_json::from_slice(&pause.body)?;
+    assert_eq!(body["component_id"], "publisher");
+    assert_eq!(body["queue_capacity"], 10);
+    assert_eq!(body["queue_used"], 8);
+
+    for n in 9..11 {
+        provider.publish("publisher", message(n)).await?;
+    }
+    let flow = provider.get_flow_control("publisher").await.unwrap();
+    assert_eq!(flow.queue_capacity, 10);
+    assert_eq!(flow.queue_used, 10);
+    assert!(flow.suggested_delay_ms > 0);
+    assert!(provider.publish("publisher", message(11)).await.is_err());
+    assert!(signals.try_recv().is_err(), "paused only once");
+
+    // Reading lets the writer drain the queue
+    for _ in 0..11 {
+        timeout(Duration::from_secs(1), remote.recv())
+            .await?
+            .expect("client should still be connected");
+    }
+    let (_, resume) = timeout(Duration::from_secs(1), signals.recv())
+        .await?
+        .expect("handler should be installed");
+    assert_eq!(resume.subject, FLOW_RESUME_SUBJECT);
+    let body: serde_json::Value = serde_json::from_slice(&resume.body)?;
+    assert!(body["queue_used"].as_u64().unwrap() <= 2);
+
+    let flow = provider.get_flow_control("publisher").await.unwrap();
+    assert_eq!(flow.queue_used, 0);
+    assert_eq!(flow.suggested_delay_ms, 0);
+    assert!(signals.try_recv().is_err());
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that without `FLOW_SIGNALS` a full queue is only reported when asked
+#[tokio::test]
+async fn test_no_signals_by_default() -> Result<()> {
+    let (provider, _remote, mut signals) = stalled_provider(false).await?;
+    for n in 1..11 {
+        provider.publish("publisher", message(n)).await?;
+    }
+    let flow = provider.get_flow_control("publisher").await.unwrap();
+    assert_eq!(flow.queue_used, 10);
+    assert!(signals.try_recv().is_err());
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that requests count towards the queue like publishes: one that reaches the high
+/// watermark signals the pause, and one refused by a full queue is the link's last error
+#[tokio::test]
+async fn test_requests_fill_the_queue() -> Result<()> {
+    let (provider, _remote, mut signals) = stalled_provider(true).await?;
+    for n in 1..8 {
+        provider.publish("publisher", message(n)).await?;
+    }
+    let provider = &provider;
+    let request = move |subject: &str| {
+        provider.request(
+            "publisher",
+            subject.to_string(),
+            Bytes::from("?"),
+            Duration::from_millis(10),
+        )
+    };
+    assert!(request("rpc.8").await.is_err(), "nobody replies");
+    let (_, pause) = signals.try_recv()?;
+    assert_eq!(pause.subject, FLOW_PAUSE_SUBJECT);
+
+    for n in 9..11 {
+        provider.publish("publisher", message(n)).await?;
+    }
+    let error = request("rpc.11").await.unwrap_err();
+    assert!(error.to_string().contains("Failed to send"), "{}", error);
+    let links = provider.list_links().await;
+    assert!(links[0].last_error.is_some());
+
+    provider.shutdown().await?;
+    Ok(())
+}
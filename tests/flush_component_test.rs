@@ -0,0 +1,104 @@
+use anyhow::Result;
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::sleep;
+
+use wasmcloud_provider_messaging_websocket::mock::{MockRemote, MockTransport};
+use wasmcloud_provider_messaging_websocket::{
+    BrokerMessage, FlushTimeout, WebSocketMessagingProvider,
+};
+
+const MESSAGES: usize = 5;
+
+/// Link a publisher whose mock socket holds a single unread frame
+async fn link_publisher() -> Result<(WebSocketMessagingProvider, MockRemote)> {
+    let mut config = HashMap::new();
+    config.insert("URI".to_string(), "ws://mock.invalid/ws".to_string());
+
+    let (transport, remotes) = MockTransport::new();
+    transport.set_write_capacity(Some(0));
+    let provider = WebSocketMessagingProvider::from_config(config)?.with_transport(transport);
+    provider
+        .receive_link_config_as_target("publisher", HashMap::new())
+        .await?;
+    let remote = remotes.accept().await.expect("connection");
+    Ok((provider, remote))
+}
+
+async fn publish_all(provider: &WebSocketMessagingProvider) -> Result<()> {
+    for n in 0..MESSAGES {
+        let msg = BrokerMessage {
+            subject: format!("orders.{}", n),
+            body: Bytes::from("x"),
+            reply_to: None,
+            headers: HashMap::new(),
+        };
+        provider.publish("publisher", msg).await?;
+    }
+    Ok(())
+}
+
+/// Test that a flush returns only once the queued messages have been written
+#[tokio::test]
+async fn test_flush_waits_for_writes() -> Result<()> {
+    let (provider, mut remote) = link_publisher().await?;
+
+    // The remote reads slowly, so writes complete one at a time
+    let read = Arc::new(AtomicUsize::new(0));
+    let reader_count = Arc::clone(&read);
+    let reader = tokio::spawn(async move {
+        while remote.recv().await.is_some() {
+            reader_count.fetch_add(1, Ordering::SeqCst);
+            sleep(Duration::from_millis(20)).await;
+        }
+    });
+
+    publish_all(&provider).await?;
+    provider
+        .flush_component("publisher", Duration::from_secs(5))
+        .await?;
+    // All were written; at most the last one still sits unread in the socket
+    assert!(read.load(Ordering::SeqCst) >= MESSAGES - 1);
+
+    provider.shutdown().await?;
+    reader.abort();
+    Ok(())
+}
+
+/// Test that a flush behind a stalled socket times out, and succeeds once it drains
+#[tokio::test]
+async fn test_flush_times_out_on_stalled_socket() -> Result<()> {
+    let (provider, mut remote) = link_publisher().await?;
+    publish_all(&provider).await?;
+
+    let timeout = Duration::from_millis(200);
+    let error = provider
+        .flush_component("publisher", timeout)
+        .await
+        .expect_err("flush should time out");
+    assert_eq!(
+        error.downcast_ref::<FlushTimeout>(),
+        Some(&FlushTimeout {
+            component_id: "publisher".to_string(),
+            timeout,
+        })
+    );
+
+    for _ in 0..MESSAGES {
+        remote.recv().await.expect("queued message");
+    }
+    provider
+        .flush_component("publisher", Duration::from_secs(2))
+        .await?;
+
+    assert!(provider
+        .flush_component("unknown", Duration::from_secs(1))
+        .await
+        .is_err());
+
+    provider.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,102 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::mock::{MockRemote, MockTransport};
+use wasmcloud_provider_messaging_websocket::testing::accept;
+use wasmcloud_provider_messaging_websocket::WebSocketMessagingProvider;
+
+const MESSAGES: usize = 50;
+
+/// Read the next `count` forwarded subjects from a handler's remote
+async fn subjects(remote: &mut MockRemote, count: usize) -> Result<Vec<String>> {
+    let mut subjects = Vec::new();
+    while subjects.len() < count {
+        let frame = timeout(Duration::from_secs(2), remote.recv())
+            .await?
+            .expect("forwarded frame");
+        let json: serde_json::Value = serde_json::from_str(frame.to_text()?)?;
+        subjects.push(json["subject"].as_str().unwrap().to_string());
+    }
+    Ok(subjects)
+}
+
+/// Test that a handler that stops reading holds up neither the other handlers nor the
+/// receive loop, and every handler still gets its messages in order
+#[tokio::test]
+async fn test_slow_handler_does_not_hold_up_others() -> Result<()> {
+    let (transport, remotes) = MockTransport::new();
+    // Every socket holds a single unread frame
+    transport.set_write_capacity(Some(0));
+    let config = HashMap::from([
+        ("URI".to_string(), "ws://mock.invalid/ws".to_string()),
+        ("HANDLER_OVERFLOW".to_string(), "wait".to_string()),
+        ("HANDLER_CONCURRENCY".to_string(), "4".to_string()),
+        ("OUTBOUND_QUEUE_CAPACITY".to_string(), "1".to_string()),
+    ]);
+    let provider = WebSocketMessagingProvider::from_config(config)?.with_transport(transport);
+    provider
+        .receive_link_config_as_target("publisher", HashMap::new())
+        .await?;
+    let source = accept(&remotes).await;
+    let mut handlers = Vec::new();
+    for component_id in ["slow", "fast-1", "fast-2"] {
+        provider
+            .receive_link_config_as_source(component_id, HashMap::new())
+            .await?;
+        handlers.push(accept(&remotes).await);
+    }
+    let mut slow = handlers.remove(0);
+
+    let expected: Vec<String> = (0..MESSAGES).map(|n| format!("tick.{}", n)).collect();
+    for subject in &expected {
+        source.send(Message::Text(format!(
+            r#"{{"subject":"{}","body":"1"}}"#,
+            subject
+        )))?;
+    }
+
+    // The fast handlers get everything while the slow one hasn't read a frame
+    for fast in &mut handlers {
+        assert_eq!(subjects(fast, MESSAGES).await?, expected);
+    }
+
+    // The receive loop is still reading: a reply to a request gets through
+    let reply = {
+        let request = provider.request(
+            "publisher",
+            "ping".to_string(),
+            "?".into(),
+            Duration::from_secs(2),
+        );
+        let answer = async {
+            let mut source = source;
+            loop {
+                let frame = timeout(Duration::from_secs(2), source.recv())
+                    .await?
+                    .expect("request frame");
+                let json: serde_json::Value = serde_json::from_str(frame.to_text()?)?;
+                if json["subject"] == "ping" {
+                    let reply = serde_json::json!({
+                        "subject": json["reply_to"],
+                        "body": "pong",
+                        "corr_id": json["corr_id"],
+                    });
+                    source.send(Message::Text(reply.to_string()))?;
+                    return anyhow::Ok(source);
+                }
+            }
+        };
+        let (reply, _source) = tokio::try_join!(request, answer)?;
+        reply
+    };
+    assert_eq!(reply.body.as_ref(), b"pong");
+
+    // The slow handler catches up in order once it reads
+    assert_eq!(subjects(&mut slow, MESSAGES).await?, expected);
+
+    provider.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,103 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::{sleep, timeout};
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::mock::{MockRemote, MockTransport};
+use wasmcloud_provider_messaging_websocket::testing::accept;
+use wasmcloud_provider_messaging_websocket::WebSocketMessagingProvider;
+
+fn provider(
+    transport: MockTransport,
+    extra: &[(&str, &str)],
+) -> Result<WebSocketMessagingProvider> {
+    let mut config = HashMap::new();
+    config.insert("URI".to_string(), "ws://mock.invalid/ws".to_string());
+    for (key, value) in extra {
+        config.insert(key.to_string(), value.to_string());
+    }
+    Ok(WebSocketMessagingProvider::from_config(config)?.with_transport(transport))
+}
+
+fn push(remote: &MockRemote, subject: &str) -> Result<()> {
+    remote.send(Message::Text(format!(
+        r#"{{"subject":"{}","body":"1"}}"#,
+        subject
+    )))
+}
+
+/// Test that a handler linked right before a message arrives receives it
+#[tokio::test]
+async fn test_handler_linked_before_message_receives_it() -> Result<()> {
+    let (transport, remotes) = MockTransport::new();
+    let provider = provider(transport, &[])?;
+    provider
+        .receive_link_config_as_target("publisher", HashMap::new())
+        .await?;
+    let source = accept(&remotes).await;
+
+    for n in 0..10 {
+        let component_id = format!("handler-{}", n);
+        provider
+            .receive_link_config_as_source(&component_id, HashMap::new())
+            .await?;
+        let mut handler = accept(&remotes).await;
+        let subject = format!("tick.{}", n);
+        push(&source, &subject)?;
+
+        let frame = timeout(Duration::from_secs(1), handler.recv())
+            .await?
+            .expect("forwarded frame");
+        let json: serde_json::Value = serde_json::from_str(frame.to_text()?)?;
+        assert_eq!(json["subject"], subject.as_str(), "{}", component_id);
+    }
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that linking a handler isn't held up by a forward waiting on a full queue
+#[tokio::test]
+async fn test_link_not_blocked_by_waiting_forward() -> Result<()> {
+    let (transport, remotes) = MockTransport::new();
+    // Every socket holds a single unread frame
+    transport.set_write_capacity(Some(0));
+    let provider = provider(
+        transport,
+        &[
+            ("HANDLER_OVERFLOW", "wait"),
+            ("OUTBOUND_QUEUE_CAPACITY", "1"),
+        ],
+    )?;
+    provider
+        .receive_link_config_as_target("publisher", HashMap::new())
+        .await?;
+    let source = accept(&remotes).await;
+    provider
+        .receive_link_config_as_source("stalled", HashMap::new())
+        .await?;
+    // Never read, so forwards to it end up waiting for room in its queue
+    let _stalled = accept(&remotes).await;
+
+    for n in 0..5 {
+        push(&source, &format!("backlog.{}", n))?;
+    }
+    sleep(Duration::from_millis(100)).await;
+
+    timeout(
+        Duration::from_secs(2),
+        provider.receive_link_config_as_source("late", HashMap::new()),
+    )
+    .await
+    .expect("link should not wait for the stalled handler")?;
+    timeout(
+        Duration::from_secs(2),
+        provider.delete_link_as_source("late"),
+    )
+    .await
+    .expect("unlink should not wait for the stalled handler")?;
+
+    provider.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use anyhow::Result;
+use futures::{SinkExt, StreamExt};
+use std::collections::HashSet;
+use std::time::Duration;
+use tokio::time::{sleep, timeout};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::testing::{serve, server_config, server_url};
+use wasmcloud_provider_messaging_websocket::{
+    HandoffPhase, ProviderEvent, WebSocketMessagingProvider, WsResumeRegistry,
+};
+
+async fn start_server(registry: &WsResumeRegistry) -> Result<WebSocketMessagingProvider> {
+    let config = server_config(&[
+        ("HANDOFF_BATCH_SIZE", "2"),
+        ("HANDOFF_BATCH_INTERVAL_MS", "50"),
+    ]);
+    serve(WebSocketMessagingProvider::from_config(config)?.with_resume_registry(registry.clone()))
+        .await
+}
+
+/// Behave like a well-mannered client: ack the reconnect frame, wait for the 1001 close,
+/// then reconnect to the advertised URL with the resume token
+async fn follow_handoff(source_url: String) -> Result<()> {
+    let (mut ws, _) = tokio_tungstenite::connect_async(source_url).await?;
+
+    let (url, token) = loop {
+        let frame = timeout(Duration::from_secs(5), ws.next())
+            .await?
+            .expect("reconnect frame")?;
+        if let Message::Text(text) = frame {
+            let json: serde_json::Value = serde_json::from_str(&text)?;
+            if json["op"] == "reconnect" {
+                break (
+                    json["url"].as_str().unwrap().to_string(),
+                    json["resume_token"].as_str().unwrap().to_string(),
+                );
+            }
+        }
+    };
+    ws.send(Message::Text(r#"{"op":"reconnect_ack"}"#.to_string()))
+        .await?;
+
+    let close = timeout(Duration::from_secs(5), ws.next())
+        .await?
+        .expect("close frame")?;
+    match close {
+        Message::Close(Some(frame)) => assert_eq!(frame.code, CloseCode::Away),
+        other => panic!("unexpected frame: {:?}", other),
+    }
+
+    let (mut resumed, _) =
+        tokio_tungstenite::connect_async(format!("{}?resume_token={}", url, token)).await?;
+    // Hold the new connection open until the test is done
+    while resumed.next().await.is_some() {}
+    Ok(())
+}
+
+/// Test that three clients are migrated in waves and resume their sessions on the target
+#[tokio::test]
+async fn test_handoff_migrates_clients_to_target() -> Result<()> {
+    let registry = WsResumeRegistry::default();
+    let source = start_server(&registry).await?;
+    let target = start_server(&registry).await?;
+    let source_url = server_url(&source).await?;
+    let target_url = server_url(&target).await?;
+
+    let clients: Vec<_> = (0..3)
+        .map(|_| tokio::spawn(follow_handoff(source_url.clone())))
+        .collect();
+    for _ in 0..50 {
+        if source.list_ws_clients().await?.len() == 3 {
+            break;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    let original: HashSet<String> = source.list_ws_clients().await?.into_iter().collect();
+    assert_eq!(original.len(), 3);
+
+    let mut events = source.subscribe_events();
+    source.initiate_handoff(&target_url).await?;
+
+    loop {
+        let event = timeout(Duration::from_secs(5), events.recv()).await??;
+        if let ProviderEvent::HandoffCompleted {
+            acknowledged,
+            timed_out,
+        } = event
+        {
+            assert_eq!(acknowledged, 3);
+            assert_eq!(timed_out, 0);
+            break;
+        }
+    }
+    let status = source.handoff_status();
+    assert_eq!(status.phase, HandoffPhase::Completed);
+    assert_eq!(status.total, 3);
+    assert_eq!(status.notified, 3);
+
+    // Every client is back on the target under its original session id
+    for _ in 0..100 {
+        if target.list_ws_clients().await?.len() == 3 {
+            break;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    let resumed: HashSet<String> = target.list_ws_clients().await?.into_iter().collect();
+    assert_eq!(resumed, original);
+
+    for client in clients {
+        client.abort();
+    }
+    source.shutdown().await?;
+    target.shutdown().await?;
+    Ok(())
+}
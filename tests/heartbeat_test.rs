@@ -0,0 +1,191 @@
+use anyhow::Result;
+use futures::{SinkExt, StreamExt};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::{sleep, timeout};
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::mock::{MockRemote, MockTransport};
+use wasmcloud_provider_messaging_websocket::testing::{server_url, start_server, WsClient};
+use wasmcloud_provider_messaging_websocket::WebSocketMessagingProvider;
+
+/// Server expecting a heartbeat every second and closing sessions silent for three
+async fn heartbeat_server() -> Result<WebSocketMessagingProvider> {
+    start_server(&[
+        ("WELCOME_FRAME", "true"),
+        ("HEARTBEAT_INTERVAL_MS", "1000"),
+        ("IDLE_TIMEOUT_MS", "3000"),
+    ])
+    .await
+}
+
+/// Connect and read the welcome frame, returning it with the session id
+async fn connect(
+    provider: &WebSocketMessagingProvider,
+) -> Result<(WsClient, serde_json::Value, String)> {
+    let (mut ws, _) = tokio_tungstenite::connect_async(server_url(provider).await?).await?;
+    let welcome = next_json(&mut ws).await?;
+    let session_id = welcome["session_id"].as_str().unwrap().to_string();
+    Ok((ws, welcome, session_id))
+}
+
+async fn next_json(ws: &mut WsClient) -> Result<serde_json::Value> {
+    loop {
+        match timeout(Duration::from_secs(2), ws.next()).await? {
+            Some(Ok(Message::Text(text))) => return Ok(serde_json::from_str(&text)?),
+            Some(Ok(_)) => continue,
+            other => anyhow::bail!("expected a text frame, got {:?}", other),
+        }
+    }
+}
+
+async fn hello(ws: &mut WsClient, interval_ms: u64) -> Result<serde_json::Value> {
+    let hello = serde_json::json!({ "op": "hello", "heartbeat_interval_ms": interval_ms });
+    ws.send(Message::Text(hello.to_string())).await?;
+    next_json(ws).await
+}
+
+async fn heartbeat_metadata(
+    provider: &WebSocketMessagingProvider,
+    session_id: &str,
+) -> Result<Option<String>> {
+    Ok(provider
+        .ws_client_info(session_id)
+        .await?
+        .and_then(|info| info.metadata.get("heartbeat_interval_ms").cloned()))
+}
+
+/// Test that the welcome advertises the heartbeat and a longer proposal gets the server's
+/// maximum
+#[tokio::test]
+async fn test_long_proposal_gets_server_max() -> Result<()> {
+    let provider = heartbeat_server().await?;
+    let (mut ws, welcome, session_id) = connect(&provider).await?;
+    assert_eq!(welcome["heartbeat"]["interval_ms"], 1000);
+    assert_eq!(welcome["heartbeat"]["idle_timeout_ms"], 3000);
+
+    let ack = hello(&mut ws, 60_000).await?;
+    assert_eq!(ack["op"], "hello_ack");
+    assert_eq!(ack["heartbeat_interval_ms"], 1000);
+    assert_eq!(ack["idle_timeout_ms"], 3000);
+    assert_eq!(
+        heartbeat_metadata(&provider, &session_id).await?.as_deref(),
+        Some("1000")
+    );
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a session is closed after its negotiated idle timeout while one that didn't
+/// negotiate keeps the configured one
+#[tokio::test]
+async fn test_idle_eviction_uses_negotiated_timeout() -> Result<()> {
+    let provider = heartbeat_server().await?;
+    let (mut negotiated, _, negotiated_id) = connect(&provider).await?;
+    let (_silent, _, silent_id) = connect(&provider).await?;
+
+    // A tenth of the interval, so idle after a tenth of the timeout
+    let ack = hello(&mut negotiated, 100).await?;
+    assert_eq!(ack["heartbeat_interval_ms"], 100);
+    assert_eq!(ack["idle_timeout_ms"], 300);
+    assert_eq!(
+        heartbeat_metadata(&provider, &negotiated_id)
+            .await?
+            .as_deref(),
+        Some("100")
+    );
+
+    let close = loop {
+        match timeout(Duration::from_secs(2), negotiated.next()).await? {
+            Some(Ok(Message::Close(Some(close)))) => break close,
+            Some(Ok(_)) => continue,
+            other => anyhow::bail!("expected a close frame, got {:?}", other),
+        }
+    };
+    assert_eq!(u16::from(close.code), 4003);
+    assert_eq!(close.reason, "idle timeout");
+
+    // The other session falls back to the configured interval and timeout
+    for _ in 0..50 {
+        if provider.list_ws_clients().await?.len() == 1 {
+            break;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(provider.list_ws_clients().await?, vec![silent_id.clone()]);
+    assert_eq!(
+        heartbeat_metadata(&provider, &silent_id).await?.as_deref(),
+        Some("1000")
+    );
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Wait for the next ping from a client connection, skipping other frames
+async fn next_ping(remote: &mut MockRemote, within: Duration) -> Option<()> {
+    timeout(within, async {
+        loop {
+            match remote.recv().await? {
+                Message::Ping(_) => return Some(()),
+                _ => continue,
+            }
+        }
+    })
+    .await
+    .ok()
+    .flatten()
+}
+
+/// Test that a client connection proposes its interval and pings at the one the server
+/// announces
+#[tokio::test]
+async fn test_client_adopts_server_interval() -> Result<()> {
+    let (transport, remotes) = MockTransport::new();
+    let config = HashMap::from([("URI".to_string(), "ws://mock.invalid/ws".to_string())]);
+    let provider = WebSocketMessagingProvider::from_config(config)?.with_transport(transport);
+
+    // Without a configured or announced interval the client never pings
+    provider
+        .receive_link_config_as_target("quiet", HashMap::new())
+        .await?;
+    let mut quiet = remotes.accept().await.expect("connection");
+    assert!(next_ping(&mut quiet, Duration::from_millis(300))
+        .await
+        .is_none());
+
+    // A welcome announcing an interval starts the pings
+    quiet.send(Message::Text(
+        serde_json::json!({
+            "session_id": "s1",
+            "heartbeat": { "interval_ms": 50, "idle_timeout_ms": 150 },
+        })
+        .to_string(),
+    ))?;
+    assert!(next_ping(&mut quiet, Duration::from_secs(1))
+        .await
+        .is_some());
+
+    // A configured interval is proposed in a hello, and the ack's interval replaces it
+    let link = HashMap::from([("HEARTBEAT_INTERVAL_MS".to_string(), "60000".to_string())]);
+    provider
+        .receive_link_config_as_target("proposing", link)
+        .await?;
+    let mut proposing = remotes.accept().await.expect("connection");
+    let hello = timeout(Duration::from_secs(1), proposing.recv())
+        .await?
+        .expect("hello frame");
+    let hello: serde_json::Value = serde_json::from_str(hello.to_text()?)?;
+    assert_eq!(hello["op"], "hello");
+    assert_eq!(hello["heartbeat_interval_ms"], 60_000);
+    proposing.send(Message::Text(
+        serde_json::json!({ "op": "hello_ack", "heartbeat_interval_ms": 50 }).to_string(),
+    ))?;
+    assert!(next_ping(&mut proposing, Duration::from_secs(1))
+        .await
+        .is_some());
+
+    provider.shutdown().await?;
+    Ok(())
+}
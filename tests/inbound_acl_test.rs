@@ -0,0 +1,90 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::mock::{MockRemote, MockTransport};
+use wasmcloud_provider_messaging_websocket::WebSocketMessagingProvider;
+
+async fn handler_link(link: &[(&str, &str)]) -> Result<(WebSocketMessagingProvider, MockRemote)> {
+    let mut config = HashMap::new();
+    config.insert("MODE".to_string(), "client".to_string());
+    config.insert("URI".to_string(), "ws://mock.invalid/ws".to_string());
+    let (transport, remotes) = MockTransport::new();
+    let provider = WebSocketMessagingProvider::from_config(config)?.with_transport(transport);
+
+    let link = link
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    provider
+        .receive_link_config_as_source("test-handler", link)
+        .await?;
+    let remote = timeout(Duration::from_secs(1), remotes.accept())
+        .await?
+        .expect("transport should be alive");
+    Ok((provider, remote))
+}
+
+/// Send one envelope per subject, then return the subjects the handler received
+async fn deliver(remote: &mut MockRemote, subjects: &[&str]) -> Result<Vec<String>> {
+    for subject in subjects {
+        let envelope = serde_json::json!({ "subject": subject, "body": "x" });
+        remote.send(Message::Text(envelope.to_string()))?;
+    }
+    // Handler deliveries come back over the mock connection
+    let mut delivered = Vec::new();
+    while let Ok(Some(frame)) = timeout(Duration::from_millis(200), remote.recv()).await {
+        let Message::Text(text) = frame else {
+            anyhow::bail!("expected a text frame, got {:?}", frame)
+        };
+        let json: serde_json::Value = serde_json::from_str(&text)?;
+        delivered.push(json["subject"].as_str().unwrap_or_default().to_string());
+    }
+    Ok(delivered)
+}
+
+/// Test that a denied subject is dropped and counted while others pass
+#[tokio::test]
+async fn test_denied_subject_dropped() -> Result<()> {
+    let (provider, mut remote) = handler_link(&[("INBOUND_DENY", "debug.>")]).await?;
+
+    let delivered = deliver(&mut remote, &["debug.trace", "orders.new"]).await?;
+    assert_eq!(delivered, vec!["orders.new"]);
+    assert_eq!(provider.inbound_denied_count(), 1);
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that only subjects on the allow list reach the handler
+#[tokio::test]
+async fn test_allowed_subject_passes() -> Result<()> {
+    let (provider, mut remote) = handler_link(&[("INBOUND_ALLOW", "orders.*")]).await?;
+
+    let delivered = deliver(&mut remote, &["orders.new", "metrics.cpu", "orders.paid"]).await?;
+    assert_eq!(delivered, vec!["orders.new", "orders.paid"]);
+    assert_eq!(provider.inbound_denied_count(), 1);
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a subject matching both lists is dropped
+#[tokio::test]
+async fn test_deny_overrides_allow() -> Result<()> {
+    let (provider, mut remote) = handler_link(&[
+        ("INBOUND_ALLOW", "orders.>"),
+        ("INBOUND_DENY", "orders.internal.>"),
+        ("LOG_INBOUND_DENIED", "true"),
+    ])
+    .await?;
+
+    let delivered = deliver(&mut remote, &["orders.internal.audit", "orders.new"]).await?;
+    assert_eq!(delivered, vec!["orders.new"]);
+    assert_eq!(provider.inbound_denied_count(), 1);
+
+    provider.shutdown().await?;
+    Ok(())
+}
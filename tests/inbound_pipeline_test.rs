@@ -0,0 +1,88 @@
+use anyhow::Result;
+use futures::SinkExt;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::{sleep, timeout};
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::testing::{serve, server_config, server_url};
+use wasmcloud_provider_messaging_websocket::{BrokerMessage, WebSocketMessagingProvider};
+
+/// Custom stage recording the subject it saw
+fn seen(_session_id: &str, mut msg: BrokerMessage) -> Result<Option<BrokerMessage>> {
+    msg.headers
+        .insert("x-seen-subject".to_string(), msg.subject.clone());
+    Ok(Some(msg))
+}
+
+/// Start a server with `stages`, send `frames` from a raw client and collect deliveries
+async fn run(stages: Option<&str>, frames: &[(&str, &str)]) -> Result<Vec<BrokerMessage>> {
+    let mut config = server_config(&[]);
+    if let Some(stages) = stages {
+        config.insert("INBOUND_STAGES".to_string(), stages.to_string());
+    }
+    let provider =
+        serve(WebSocketMessagingProvider::from_config(config)?.with_inbound_stage("seen", seen))
+            .await?;
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    provider
+        .set_message_sink(
+            move |_session_id: String, msg: BrokerMessage| -> Result<()> {
+                tx.send(msg)?;
+                Ok(())
+            },
+        )
+        .await?;
+
+    let url = server_url(&provider).await?;
+    let (mut ws, _) = tokio_tungstenite::connect_async(url.as_str()).await?;
+    for (subject, msg_id) in frames {
+        ws.send(Message::Text(format!(
+            r#"{{"subject":"{}","body":"x","headers":{{"msg-id":"{}"}}}}"#,
+            subject, msg_id
+        )))
+        .await?;
+    }
+    sleep(Duration::from_millis(200)).await;
+
+    let mut delivered = Vec::new();
+    while let Ok(Some(msg)) = timeout(Duration::from_millis(50), rx.recv()).await {
+        delivered.push(msg);
+    }
+    provider.shutdown().await?;
+    Ok(delivered)
+}
+
+/// Test that stages run in the configured order, custom ones included
+#[tokio::test]
+async fn test_stages_run_in_order() -> Result<()> {
+    let frames = [
+        (" orders..created", "1"),
+        ("orders.created", "1"),
+        ("orders.shipped", "2"),
+    ];
+    let delivered = run(Some("decode,normalize,seen,dedup"), &frames).await?;
+
+    // Normalized before the custom stage saw it and before duplicates were compared
+    let subjects: Vec<&str> = delivered.iter().map(|m| m.subject.as_str()).collect();
+    assert_eq!(subjects, vec!["orders.created", "orders.shipped"]);
+    assert_eq!(delivered[0].headers["x-seen-subject"], "orders.created");
+    Ok(())
+}
+
+/// Test that stages left out of the list are skipped, and custom stages run by default
+#[tokio::test]
+async fn test_unlisted_stages_skipped() -> Result<()> {
+    let frames = [("orders..created", "1"), ("orders..created", "1")];
+
+    let delivered = run(Some("decode"), &frames).await?;
+    assert_eq!(delivered.len(), 2);
+    assert_eq!(delivered[0].subject, "orders..created");
+    assert!(!delivered[0].headers.contains_key("x-seen-subject"));
+
+    let delivered = run(None, &frames).await?;
+    assert_eq!(delivered.len(), 2);
+    assert_eq!(delivered[0].headers["x-seen-subject"], "orders..created");
+    Ok(())
+}
@@ -4,7 +4,9 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use tracing_subscriber;
 
-use wasmcloud_provider_messaging_websocket::{BrokerMessage, WebSocketMessagingProvider};
+use wasmcloud_provider_messaging_websocket::{
+    BrokerMessage, ProviderShutDown, WebSocketMessagingProvider,
+};
 
 /// Integration test demonstrating session management features
 #[tokio::test]
@@ -48,6 +50,7 @@ fn test_message_creation() {
         subject: "test.subject".to_string(),
         body: Bytes::from("test payload"),
         reply_to: Some("reply.subject".to_string()),
+        headers: HashMap::new(),
     };
 
     assert_eq!(message.subject, "test.subject");
@@ -115,3 +118,47 @@ async fn test_shutdown() -> Result<()> {
 
     Ok(())
 }
+
+/// Test that operations after shutdown fail with ProviderShutDown
+#[tokio::test]
+async fn test_publish_after_shutdown() -> Result<()> {
+    let provider = WebSocketMessagingProvider::new();
+    provider.shutdown().await?;
+
+    let msg = BrokerMessage {
+        subject: "test.subject".to_string(),
+        body: Bytes::from("late"),
+        reply_to: None,
+        headers: HashMap::new(),
+    };
+
+    // Not the misleading "Component not linked" error
+    let err = provider
+        .publish("test-component", msg.clone())
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<ProviderShutDown>(),
+        Some(&ProviderShutDown)
+    );
+
+    let err = provider
+        .broadcast_to_clients(msg.clone())
+        .await
+        .unwrap_err();
+    assert!(err.is::<ProviderShutDown>());
+
+    let err = provider
+        .send_to_session("some-session", msg)
+        .await
+        .unwrap_err();
+    assert!(err.is::<ProviderShutDown>());
+
+    let err = provider
+        .receive_link_config_as_target("test-component", HashMap::new())
+        .await
+        .unwrap_err();
+    assert!(err.is::<ProviderShutDown>());
+
+    Ok(())
+}
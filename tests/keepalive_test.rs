@@ -0,0 +1,117 @@
+use anyhow::Result;
+use axum::extract::ws::{Message as AxumMessage, WebSocket};
+use axum::extract::{State, WebSocketUpgrade};
+use axum::response::Response;
+use axum::routing::get;
+use axum::Router;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::mock::MockTransport;
+use wasmcloud_provider_messaging_websocket::{LinkConnectionStatus, WebSocketMessagingProvider};
+
+/// Echoes data frames and reports each ping; axum answers the pings itself
+async fn echo(State(ping_tx): State<mpsc::UnboundedSender<()>>, ws: WebSocketUpgrade) -> Response {
+    ws.on_upgrade(move |mut socket: WebSocket| async move {
+        while let Some(Ok(msg)) = socket.recv().await {
+            match msg {
+                AxumMessage::Ping(_) => {
+                    let _ = ping_tx.send(());
+                }
+                AxumMessage::Text(_) | AxumMessage::Binary(_) => {
+                    if socket.send(msg).await.is_err() {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+    })
+}
+
+/// Test that `PING_INTERVAL_SEC` pings an echo server on every tick and a server that
+/// answers keeps the connection
+#[tokio::test]
+async fn test_pings_sent_at_interval() -> Result<()> {
+    let (ping_tx, mut ping_rx) = mpsc::unbounded_channel();
+    let app = Router::new().route("/ws", get(echo)).with_state(ping_tx);
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let provider = WebSocketMessagingProvider::from_config(HashMap::from([
+        ("URI".to_string(), format!("ws://{}/ws", addr)),
+        ("PING_INTERVAL_SEC".to_string(), "1".to_string()),
+        ("PONG_TIMEOUT_SEC".to_string(), "1".to_string()),
+    ]))?;
+    provider
+        .receive_link_config_as_target("test-consumer", HashMap::new())
+        .await?;
+
+    let mut pings = 0;
+    while pings < 3 {
+        timeout(Duration::from_secs(2), ping_rx.recv())
+            .await?
+            .expect("server should be running");
+        pings += 1;
+    }
+    let links = provider.list_links().await;
+    assert_eq!(links.len(), 1);
+    assert_eq!(links[0].connection_status, LinkConnectionStatus::Connected);
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a server that stops answering pings is dropped after `PONG_TIMEOUT_SEC`
+/// and, with `RECONNECT`, redialled
+#[tokio::test]
+async fn test_missing_pong_drops_connection() -> Result<()> {
+    let (transport, remotes) = MockTransport::new();
+    let provider = WebSocketMessagingProvider::from_config(HashMap::from([
+        ("URI".to_string(), "ws://mock.invalid/ws".to_string()),
+        ("PING_INTERVAL_SEC".to_string(), "1".to_string()),
+        ("PONG_TIMEOUT_SEC".to_string(), "1".to_string()),
+        ("RECONNECT".to_string(), "true".to_string()),
+        ("RECONNECT_BASE_DELAY_MS".to_string(), "10".to_string()),
+    ]))?
+    .with_transport(transport);
+    provider
+        .receive_link_config_as_target("test-consumer", HashMap::new())
+        .await?;
+
+    // The mock remote never answers, so the first ping goes unanswered
+    let mut remote = timeout(Duration::from_secs(1), remotes.accept())
+        .await?
+        .expect("transport should be alive");
+    let ping = timeout(Duration::from_secs(2), remote.recv())
+        .await?
+        .expect("client should still be connected");
+    assert!(matches!(ping, Message::Ping(_)), "{:?}", ping);
+
+    let mut redialled = timeout(Duration::from_secs(3), remotes.accept())
+        .await?
+        .expect("transport should be alive");
+    let links = provider.list_links().await;
+    assert!(links[0]
+        .last_error
+        .as_ref()
+        .is_some_and(|(_, error)| error.contains("PONG_TIMEOUT")));
+
+    // Answering keeps the new connection
+    let ping = timeout(Duration::from_secs(2), redialled.recv())
+        .await?
+        .expect("client should still be connected");
+    assert!(matches!(ping, Message::Ping(_)), "{:?}", ping);
+    redialled.send(Message::Pong(Vec::new()))?;
+    assert!(timeout(Duration::from_millis(1500), remotes.accept())
+        .await
+        .is_err());
+
+    provider.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::mock::{MockRemote, MockRemotes, MockTransport};
+use wasmcloud_provider_messaging_websocket::WebSocketMessagingProvider;
+
+async fn link(
+    provider: &WebSocketMessagingProvider,
+    remotes: &MockRemotes,
+    component_id: &str,
+    label: &str,
+    subscriptions: &str,
+) -> Result<MockRemote> {
+    let mut config = HashMap::new();
+    config.insert("LABEL".to_string(), label.to_string());
+    config.insert("SUBSCRIPTIONS".to_string(), subscriptions.to_string());
+    provider
+        .receive_link_config_as_source(component_id, config)
+        .await?;
+    Ok(timeout(Duration::from_secs(1), remotes.accept())
+        .await?
+        .expect("connection should be made"))
+}
+
+async fn expect_forwarded(remote: &mut MockRemote) {
+    let frame = timeout(Duration::from_secs(1), remote.recv()).await;
+    assert!(matches!(frame, Ok(Some(Message::Text(_)))), "{:?}", frame);
+}
+
+/// Test that connections with different labels are counted independently
+#[tokio::test]
+async fn test_metrics_tracked_per_label() -> Result<()> {
+    let mut config = HashMap::new();
+    config.insert("URI".to_string(), "ws://mock.invalid/ws".to_string());
+    let (transport, remotes) = MockTransport::new();
+    let provider = WebSocketMessagingProvider::from_config(config)?.with_transport(transport);
+
+    let mut a = link(&provider, &remotes, "a", "tenant-a", "a.>").await?;
+    let mut b = link(&provider, &remotes, "b", "tenant-b", "b.>").await?;
+
+    for _ in 0..3 {
+        a.send(Message::Text(r#"{"subject":"a.x","body":"1"}"#.to_string()))?;
+        expect_forwarded(&mut a).await;
+    }
+    b.send(Message::Text(r#"{"subject":"b.x","body":"1"}"#.to_string()))?;
+    expect_forwarded(&mut b).await;
+
+    // Each message is counted once received and once forwarded on the same connection
+    let metrics = provider.label_metrics();
+    assert_eq!(metrics.len(), 2);
+    assert_eq!(metrics["tenant-a"].connections, 1);
+    assert_eq!(metrics["tenant-a"].messages, 6);
+    assert_eq!(metrics["tenant-b"].connections, 1);
+    assert_eq!(metrics["tenant-b"].messages, 2);
+    assert!(metrics["tenant-a"].bytes > metrics["tenant-b"].bytes);
+
+    provider.shutdown().await?;
+    Ok(())
+}
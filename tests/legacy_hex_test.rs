@@ -0,0 +1,80 @@
+use anyhow::Result;
+use bytes::Bytes;
+use futures::SinkExt;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::testing::{server_url, start_server};
+use wasmcloud_provider_messaging_websocket::{
+    BrokerMessage, WebSocketMessagingProvider, LEGACY_HEX_HEADER,
+};
+
+/// A server with a sink, a raw client that sent `bodies`, and what the sink received
+async fn deliver(
+    policy: &str,
+    bodies: &[&str],
+) -> Result<(WebSocketMessagingProvider, Vec<BrokerMessage>)> {
+    let provider = start_server(&[("LEGACY_HEX_BODIES", policy)]).await?;
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    provider
+        .set_message_sink(
+            move |_session_id: String, msg: BrokerMessage| -> Result<()> {
+                tx.send(msg)?;
+                Ok(())
+            },
+        )
+        .await?;
+
+    let url = server_url(&provider).await?;
+    let (mut ws, _) = tokio_tungstenite::connect_async(url.as_str()).await?;
+    for body in bodies {
+        ws.send(Message::Text(format!(
+            r#"{{"subject":"data","body":"{}"}}"#,
+            body
+        )))
+        .await?;
+    }
+
+    let mut received = Vec::new();
+    for _ in bodies {
+        received.push(
+            timeout(Duration::from_secs(2), rx.recv())
+                .await?
+                .expect("delivered message"),
+        );
+    }
+    Ok((provider, received))
+}
+
+/// Test that hex bodies from old peers are decoded, tagged and counted
+#[tokio::test]
+async fn test_hex_bodies_decoded_and_counted() -> Result<()> {
+    // Hex of "hello", hex of "test" (also valid base64), and a plain body
+    let (provider, received) = deliver("accept", &["68656c6c6f", "74657374", "plain"]).await?;
+
+    assert_eq!(received[0].body, Bytes::from("hello"));
+    assert_eq!(received[0].headers[LEGACY_HEX_HEADER], "true");
+    assert_eq!(received[1].body, Bytes::from("test"));
+    assert_eq!(received[2].body, Bytes::from("plain"));
+    assert!(!received[2].headers.contains_key(LEGACY_HEX_HEADER));
+    assert_eq!(provider.legacy_hex_body_count(), 2);
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that `reject` leaves hex-looking bodies alone
+#[tokio::test]
+async fn test_reject_policy_keeps_hex_strings() -> Result<()> {
+    let (provider, received) = deliver("reject", &["68656c6c6f"]).await?;
+
+    assert_eq!(received[0].body, Bytes::from("68656c6c6f"));
+    assert!(!received[0].headers.contains_key(LEGACY_HEX_HEADER));
+    assert_eq!(provider.legacy_hex_body_count(), 0);
+
+    provider.shutdown().await?;
+    Ok(())
+}
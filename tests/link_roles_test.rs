@@ -0,0 +1,119 @@
+use anyhow::Result;
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::sleep;
+
+use wasmcloud_provider_messaging_websocket::testing::{server_url, start_server};
+use wasmcloud_provider_messaging_websocket::{BrokerMessage, WebSocketMessagingProvider};
+
+async fn client_of(server: &WebSocketMessagingProvider) -> Result<WebSocketMessagingProvider> {
+    let mut config = HashMap::new();
+    config.insert("MODE".to_string(), "client".to_string());
+    config.insert("URI".to_string(), server_url(server).await?);
+    config.insert("ENABLE_SESSION_TRACKING".to_string(), "true".to_string());
+    WebSocketMessagingProvider::from_config(config)
+}
+
+fn message() -> BrokerMessage {
+    BrokerMessage {
+        subject: "replies.1".to_string(),
+        body: Bytes::from("ok"),
+        reply_to: None,
+        headers: HashMap::new(),
+    }
+}
+
+/// Test that a component linked in both roles with the same config shares one socket
+#[tokio::test]
+async fn test_same_config_shares_connection() -> Result<()> {
+    let server = start_server(&[]).await?;
+    let client = client_of(&server).await?;
+
+    client
+        .receive_link_config_as_target("both", HashMap::new())
+        .await?;
+    client
+        .receive_link_config_as_source("both", HashMap::new())
+        .await?;
+    sleep(Duration::from_millis(100)).await;
+
+    assert_eq!(server.list_ws_clients().await?.len(), 1);
+    let sessions = client.list_sessions().await;
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].1, "component:both");
+    assert!(client.consistency_report().await.is_consistent());
+
+    client.shutdown().await?;
+    server.shutdown().await?;
+    Ok(())
+}
+
+/// Test that different configs keep separate connections with role-tagged sessions
+#[tokio::test]
+async fn test_different_config_keeps_roles_apart() -> Result<()> {
+    let server = start_server(&[]).await?;
+    let client = client_of(&server).await?;
+
+    client
+        .receive_link_config_as_target("both", HashMap::new())
+        .await?;
+    let mut handler = HashMap::new();
+    handler.insert("SUBSCRIPTIONS".to_string(), "orders.>".to_string());
+    client
+        .receive_link_config_as_source("both", handler)
+        .await?;
+    sleep(Duration::from_millis(100)).await;
+
+    assert_eq!(server.list_ws_clients().await?.len(), 2);
+    let mut labels: Vec<String> = client
+        .list_sessions()
+        .await
+        .into_iter()
+        .map(|(_, label)| label)
+        .collect();
+    labels.sort();
+    assert_eq!(
+        labels,
+        vec!["component:both/consumer", "component:both/handler"]
+    );
+
+    // Each session replies over its own connection
+    for (session_id, _) in client.list_sessions().await {
+        client.send_to_session(&session_id, message()).await?;
+    }
+
+    client.shutdown().await?;
+    server.shutdown().await?;
+    Ok(())
+}
+
+/// Test that unlinking one role of a shared connection leaves the other working
+#[tokio::test]
+async fn test_unlinking_one_role_keeps_the_other() -> Result<()> {
+    let server = start_server(&[]).await?;
+    let client = client_of(&server).await?;
+
+    client
+        .receive_link_config_as_target("both", HashMap::new())
+        .await?;
+    client
+        .receive_link_config_as_source("both", HashMap::new())
+        .await?;
+    let (session_id, _) = client.list_sessions().await.pop().unwrap();
+
+    client.delete_link_as_target("both").await?;
+    sleep(Duration::from_millis(100)).await;
+    assert!(client.publish("both", message()).await.is_err());
+    assert_eq!(server.list_ws_clients().await?.len(), 1);
+    client.send_to_session(&session_id, message()).await?;
+    assert!(client.consistency_report().await.is_consistent());
+
+    client.delete_link_as_source("both").await?;
+    sleep(Duration::from_millis(200)).await;
+    assert!(server.list_ws_clients().await?.is_empty());
+
+    client.shutdown().await?;
+    server.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use anyhow::Result;
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::{sleep, timeout};
+use tokio_tungstenite::tungstenite::{Error as WsError, Message};
+
+use wasmcloud_provider_messaging_websocket::mock::{MockRemotes, MockTransport};
+use wasmcloud_provider_messaging_websocket::testing::accept;
+use wasmcloud_provider_messaging_websocket::{
+    BrokerMessage, LinkConnectionStatus, LinkRole, LinkStatus, WebSocketMessagingProvider,
+};
+
+fn mock_provider() -> Result<(WebSocketMessagingProvider, MockTransport, MockRemotes)> {
+    let mut config = HashMap::new();
+    config.insert("URI".to_string(), "ws://mock.invalid/ws".to_string());
+    let (transport, remotes) = MockTransport::new();
+    let provider =
+        WebSocketMessagingProvider::from_config(config)?.with_transport(transport.clone());
+    Ok((provider, transport, remotes))
+}
+
+async fn link(provider: &WebSocketMessagingProvider, component_id: &str) -> LinkStatus {
+    provider
+        .list_links()
+        .await
+        .into_iter()
+        .find(|link| link.component_id == component_id)
+        .expect("link should be listed")
+}
+
+/// Test that a healthy link reports its traffic, a link whose connection died reports
+/// the error, and a link that never connected is listed as failed
+#[tokio::test]
+async fn test_link_statuses() -> Result<()> {
+    let (provider, transport, remotes) = mock_provider()?;
+
+    let labelled = HashMap::from([("LABEL".to_string(), "tenant-a".to_string())]);
+    provider
+        .receive_link_config_as_target("healthy", labelled)
+        .await?;
+    let mut healthy_remote = accept(&remotes).await;
+    provider
+        .receive_link_config_as_source("broken", HashMap::new())
+        .await?;
+    let broken_remote = accept(&remotes).await;
+
+    provider
+        .publish(
+            "healthy",
+            BrokerMessage {
+                subject: "orders".to_string(),
+                body: Bytes::from("x"),
+                reply_to: None,
+                headers: HashMap::new(),
+            },
+        )
+        .await?;
+    timeout(Duration::from_secs(1), healthy_remote.recv()).await?;
+    healthy_remote.send(Message::Text(r#"{"subject":"in","body":"y"}"#.to_string()))?;
+    broken_remote.fail(WsError::ConnectionClosed)?;
+
+    transport.refuse_connections(true);
+    assert!(provider
+        .receive_link_config_as_target("refused", HashMap::new())
+        .await
+        .is_err());
+
+    // Let the connection tasks see the inbound frame and the failure
+    for _ in 0..50 {
+        let broken = link(&provider, "broken").await;
+        if broken.connection_status == LinkConnectionStatus::Closed
+            && link(&provider, "healthy").await.messages_in > 0
+        {
+            break;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+
+    let links = provider.list_links().await;
+    let ids: Vec<_> = links.iter().map(|l| l.component_id.as_str()).collect();
+    assert_eq!(ids, vec!["broken", "healthy", "refused"]);
+
+    let healthy = &links[1];
+    assert_eq!(healthy.role, LinkRole::Consumer);
+    assert_eq!(healthy.label.as_deref(), Some("tenant-a"));
+    assert_eq!(healthy.connection_status, LinkConnectionStatus::Connected);
+    assert!(healthy.session_id.is_some());
+    assert_eq!(healthy.messages_out, 1);
+    assert_eq!(healthy.messages_in, 1);
+    assert_eq!(healthy.last_error, None);
+
+    let broken = &links[0];
+    assert_eq!(broken.role, LinkRole::Handler);
+    assert_eq!(broken.connection_status, LinkConnectionStatus::Closed);
+    let (_, error) = broken.last_error.as_ref().expect("error recorded");
+    assert!(error.contains("WebSocket error"), "{}", error);
+
+    let refused = &links[2];
+    assert_eq!(refused.role, LinkRole::Consumer);
+    assert_eq!(refused.connection_status, LinkConnectionStatus::Failed);
+    assert_eq!(refused.session_id, None);
+    let (_, error) = refused.last_error.as_ref().expect("error recorded");
+    assert!(error.contains("refused"), "{}", error);
+
+    // A successful retry replaces the failed entry
+    transport.refuse_connections(false);
+    provider
+        .receive_link_config_as_target("refused", HashMap::new())
+        .await?;
+    let _retried_remote = accept(&remotes).await;
+    let refused = link(&provider, "refused").await;
+    assert_eq!(refused.connection_status, LinkConnectionStatus::Connected);
+    assert_eq!(refused.last_error, None);
+    assert_eq!(provider.list_links().await.len(), 3);
+
+    provider.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,121 @@
+use anyhow::Result;
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::sleep;
+
+use wasmcloud_provider_messaging_websocket::testing::{
+    record_subjects, server_url, start_server, Subjects,
+};
+use wasmcloud_provider_messaging_websocket::{
+    BrokerMessage, ForbiddenSubject, LinkUpdateAction, WebSocketMessagingProvider,
+};
+
+/// Start a server-mode provider recording the subjects it receives
+async fn recording_server() -> Result<(WebSocketMessagingProvider, String, Subjects)> {
+    let provider = start_server(&[]).await?;
+    let received = record_subjects(&provider).await?;
+    let url = server_url(&provider).await?;
+    Ok((provider, url, received))
+}
+
+fn message(n: usize) -> BrokerMessage {
+    BrokerMessage {
+        subject: format!("seq.{}", n),
+        body: Bytes::from("payload"),
+        reply_to: None,
+        headers: HashMap::new(),
+    }
+}
+
+/// Relink `publisher` with `link` while another task publishes 100 messages, then check
+/// all of them reached the server
+async fn relink_while_publishing(
+    link: HashMap<String, String>,
+) -> Result<(
+    WebSocketMessagingProvider,
+    WebSocketMessagingProvider,
+    LinkUpdateAction,
+)> {
+    let (server, url, received) = recording_server().await?;
+    let mut config = HashMap::new();
+    config.insert("URI".to_string(), url);
+    let client = WebSocketMessagingProvider::from_config(config)?;
+    let summary = client
+        .receive_link_config_as_target("publisher", HashMap::new())
+        .await?;
+    assert_eq!(summary.update.action, LinkUpdateAction::Connected);
+
+    let publisher = client.clone();
+    let publishing = tokio::spawn(async move {
+        for n in 0..100 {
+            publisher.publish("publisher", message(n)).await?;
+            sleep(Duration::from_millis(2)).await;
+        }
+        anyhow::Ok(())
+    });
+    sleep(Duration::from_millis(20)).await;
+    let summary = client
+        .receive_link_config_as_target("publisher", link)
+        .await?;
+    publishing.await??;
+    sleep(Duration::from_millis(300)).await;
+
+    let mut all = received.lock().unwrap().clone();
+    all.sort();
+    let mut expected: Vec<String> = (0..100).map(|n| format!("seq.{}", n)).collect();
+    expected.sort();
+    assert_eq!(all, expected, "messages were lost or duplicated");
+    Ok((client, server, summary.update.action))
+}
+
+/// Test that a link changing only publish rules keeps its socket and loses nothing
+#[tokio::test]
+async fn test_in_place_update_keeps_connection() -> Result<()> {
+    let link = HashMap::from([("PUBLISH_DENY".to_string(), "secret.>".to_string())]);
+    let (client, server, action) = relink_while_publishing(link).await?;
+    assert_eq!(action, LinkUpdateAction::InPlace);
+
+    // Still the one client, and the new rule applies to it
+    assert_eq!(server.list_ws_clients().await?.len(), 1);
+    let err = client
+        .publish(
+            "publisher",
+            BrokerMessage {
+                subject: "secret.plans".to_string(),
+                ..message(0)
+            },
+        )
+        .await
+        .unwrap_err();
+    assert!(err.downcast_ref::<ForbiddenSubject>().is_some());
+
+    // Delivering the same link again changes nothing
+    let summary = client
+        .receive_link_config_as_target(
+            "publisher",
+            HashMap::from([("PUBLISH_DENY".to_string(), "secret.>".to_string())]),
+        )
+        .await?;
+    assert_eq!(summary.update.action, LinkUpdateAction::Unchanged);
+    assert!(summary.update.changed.is_empty());
+
+    client.shutdown().await?;
+    server.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a header change opens a new connection before closing the old one
+#[tokio::test]
+async fn test_socket_change_reconnects_make_before_break() -> Result<()> {
+    let link = HashMap::from([("HEADER_X-Team".to_string(), "payments".to_string())]);
+    let (client, server, action) = relink_while_publishing(link).await?;
+    assert_eq!(action, LinkUpdateAction::Reconnected);
+
+    // The old connection was closed once drained
+    assert_eq!(server.list_ws_clients().await?.len(), 1);
+
+    client.shutdown().await?;
+    server.shutdown().await?;
+    Ok(())
+}
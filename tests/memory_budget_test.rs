@@ -0,0 +1,65 @@
+use anyhow::Result;
+use bytes::Bytes;
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::testing::{connect, start_server};
+use wasmcloud_provider_messaging_websocket::{BrokerMessage, ProviderEvent};
+
+const BUDGET: usize = 256 * 1024;
+
+fn message(subject: &str, len: usize) -> BrokerMessage {
+    BrokerMessage {
+        subject: subject.to_string(),
+        body: Bytes::from(vec![b'x'; len]),
+        reply_to: None,
+        headers: HashMap::new(),
+    }
+}
+
+/// Test that broadcasts to a client that never reads are shed once the budget is used up
+#[tokio::test]
+async fn test_broadcasts_shed_over_budget() -> Result<()> {
+    let server = start_server(&[("SERVER_MEMORY_BUDGET_BYTES", &BUDGET.to_string())]).await?;
+    let mut events = server.subscribe_events();
+    // Never read, so frames pile up once the socket buffers are full
+    let (_stalled, _) = connect(&server).await?;
+
+    let mut shed = None;
+    for _ in 0..4000 {
+        server
+            .broadcast_to_clients(message("news", 16 * 1024))
+            .await?;
+        assert!(server.server_queued_bytes() <= BUDGET);
+        if let Ok(ProviderEvent::BackpressureShed {
+            shed: clients,
+            budget_bytes,
+            ..
+        }) = events.try_recv()
+        {
+            shed = Some((clients, budget_bytes));
+            break;
+        }
+        tokio::task::yield_now().await;
+    }
+    assert_eq!(shed, Some((1, BUDGET)), "shedding never kicked in");
+
+    // The server still serves new clients and direct sends while shedding broadcasts
+    let (mut fresh, session_id) = connect(&server).await?;
+    server
+        .send_to_ws_client(&session_id, message("direct", 16))
+        .await?;
+    let frame = timeout(Duration::from_secs(2), fresh.next())
+        .await?
+        .expect("direct message")?;
+    let Message::Text(text) = frame else {
+        panic!("expected a text frame, got {:?}", frame);
+    };
+    assert!(text.contains("direct"));
+
+    server.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,135 @@
+use anyhow::Result;
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::mock::{MockRemote, MockTransport};
+use wasmcloud_provider_messaging_websocket::testing::accept;
+use wasmcloud_provider_messaging_websocket::{
+    BrokerMessage, WebSocketMessagingProvider, SIGNATURE_FIELD,
+};
+
+async fn next_json(remote: &mut MockRemote) -> Result<serde_json::Value> {
+    let frame = timeout(Duration::from_secs(2), remote.recv())
+        .await?
+        .expect("frame");
+    Ok(serde_json::from_str(frame.to_text()?)?)
+}
+
+/// Provider with a publisher link and a handler link, returning their remotes
+async fn linked(
+    extra: &[(&str, &str)],
+) -> Result<(WebSocketMessagingProvider, MockRemote, MockRemote)> {
+    let (transport, remotes) = MockTransport::new();
+    let mut config = HashMap::from([("URI".to_string(), "ws://mock.invalid/ws".to_string())]);
+    for (key, value) in extra {
+        config.insert(key.to_string(), value.to_string());
+    }
+    let provider = WebSocketMessagingProvider::from_config(config)?.with_transport(transport);
+    provider
+        .receive_link_config_as_target("publisher", HashMap::new())
+        .await?;
+    let source = accept(&remotes).await;
+    provider
+        .receive_link_config_as_source("handler", HashMap::new())
+        .await?;
+    let handler = accept(&remotes).await;
+    Ok((provider, source, handler))
+}
+
+fn order(body: &'static [u8]) -> BrokerMessage {
+    BrokerMessage {
+        subject: "orders.created".to_string(),
+        body: Bytes::from_static(body),
+        reply_to: None,
+        headers: HashMap::new(),
+    }
+}
+
+/// Test that a published message is signed, verifies when it comes back, and that
+/// tampered and unsigned messages are dropped and counted
+#[tokio::test]
+async fn test_signed_messages_verified() -> Result<()> {
+    let (provider, mut source, mut handler) = linked(&[("MESSAGE_HMAC_KEY", "s3cret")]).await?;
+
+    provider.publish("publisher", order(b"signed")).await?;
+    let signed = next_json(&mut source).await?;
+    assert!(signed[SIGNATURE_FIELD].is_string(), "{}", signed);
+
+    // The signed envelope round-trips to the handler
+    source.send(Message::Text(signed.to_string()))?;
+    let delivered = next_json(&mut handler).await?;
+    assert_eq!(delivered["subject"], "orders.created");
+    assert_eq!(delivered["body"], signed["body"]);
+
+    // A changed body no longer matches its signature
+    let mut tampered = signed.clone();
+    tampered["body"] = serde_json::Value::String("dGFtcGVyZWQ=".to_string());
+    source.send(Message::Text(tampered.to_string()))?;
+
+    // Unsigned messages are rejected, as text or as raw bytes
+    let mut unsigned = signed.clone();
+    unsigned.as_object_mut().unwrap().remove(SIGNATURE_FIELD);
+    source.send(Message::Text(unsigned.to_string()))?;
+    source.send(Message::Binary(vec![0xff, 0xfe]))?;
+
+    // Only the message sent after them is delivered
+    source.send(Message::Text(signed.to_string()))?;
+    let delivered = next_json(&mut handler).await?;
+    assert_eq!(delivered["body"], signed["body"]);
+    assert!(timeout(Duration::from_millis(100), handler.recv())
+        .await
+        .is_err());
+    assert_eq!(provider.parse_failure_counts()["invalid_signature"], 3);
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that `MESSAGE_HMAC_ALLOW_UNSIGNED` lets unsigned messages through but still
+/// drops ones with a wrong signature
+#[tokio::test]
+async fn test_allow_unsigned() -> Result<()> {
+    let (provider, source, mut handler) = linked(&[
+        ("MESSAGE_HMAC_KEY", "s3cret"),
+        ("MESSAGE_HMAC_ALLOW_UNSIGNED", "true"),
+    ])
+    .await?;
+
+    source.send(Message::Text(
+        r#"{"subject":"orders.created","body":"b2s=","body_encoding":"base64","sig":"AAAA"}"#
+            .to_string(),
+    ))?;
+    source.send(Message::Text(
+        r#"{"subject":"orders.created","body":"dW5zaWduZWQ=","body_encoding":"base64"}"#
+            .to_string(),
+    ))?;
+    let delivered = next_json(&mut handler).await?;
+    assert_eq!(delivered["body"], "dW5zaWduZWQ=");
+    assert_eq!(provider.parse_failure_counts()["invalid_signature"], 1);
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that signing is off by default: nothing is signed and unsigned messages pass
+#[tokio::test]
+async fn test_unsigned_by_default() -> Result<()> {
+    let (provider, mut source, mut handler) = linked(&[]).await?;
+
+    provider.publish("publisher", order(b"plain")).await?;
+    let sent = next_json(&mut source).await?;
+    assert!(sent.get(SIGNATURE_FIELD).is_none(), "{}", sent);
+
+    source.send(Message::Text(sent.to_string()))?;
+    let delivered = next_json(&mut handler).await?;
+    assert_eq!(delivered["body"], sent["body"]);
+    assert!(!provider
+        .parse_failure_counts()
+        .contains_key("invalid_signature"));
+
+    provider.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use anyhow::Result;
+use futures::future::BoxFuture;
+use futures::SinkExt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::{sleep, timeout};
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::testing::{server_url, start_server};
+use wasmcloud_provider_messaging_websocket::{BrokerMessage, MessageSink};
+
+fn frame(subject: &str) -> Message {
+    Message::Text(format!(r#"{{"subject":"{}","body":"x"}}"#, subject))
+}
+
+/// Sink that takes a second for `slow.` subjects and reports every other subject
+struct SlowSubjectSink(mpsc::UnboundedSender<String>);
+
+impl MessageSink for SlowSubjectSink {
+    fn deliver(&self, _session_id: String, msg: BrokerMessage) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            if msg.subject.starts_with("slow.") {
+                sleep(Duration::from_secs(1)).await;
+            }
+            self.0.send(msg.subject)?;
+            Ok(())
+        })
+    }
+}
+
+/// Sink that never finishes accepting a message
+struct StuckSink(Arc<AtomicUsize>);
+
+impl MessageSink for StuckSink {
+    fn deliver(&self, _session_id: String, _msg: BrokerMessage) -> BoxFuture<'_, Result<()>> {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        Box::pin(futures::future::pending())
+    }
+}
+
+/// Test that messages arriving before a sink is installed are flushed to it in order
+#[tokio::test]
+async fn test_buffered_messages_flushed_to_installed_sink() -> Result<()> {
+    let provider = start_server(&[]).await?;
+    let url = server_url(&provider).await?;
+
+    let (mut ws, _) = tokio_tungstenite::connect_async(url.as_str()).await?;
+    for subject in ["a", "b", "c"] {
+        ws.send(frame(subject)).await?;
+    }
+    sleep(Duration::from_millis(100)).await;
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    provider
+        .set_message_sink(
+            move |_session_id: String, msg: BrokerMessage| -> Result<()> {
+                tx.send(msg.subject)?;
+                Ok(())
+            },
+        )
+        .await?;
+    ws.send(frame("d")).await?;
+
+    let mut subjects = Vec::new();
+    while subjects.len() < 4 {
+        subjects.push(timeout(Duration::from_secs(1), rx.recv()).await?.unwrap());
+    }
+    assert_eq!(subjects, vec!["a", "b", "c", "d"]);
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a sink that never completes is timed out instead of wedging the socket
+#[tokio::test]
+async fn test_stuck_sink_times_out() -> Result<()> {
+    let provider = start_server(&[("DELIVERY_TIMEOUT_MS", "50")]).await?;
+    let url = server_url(&provider).await?;
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    provider
+        .set_message_sink(StuckSink(Arc::clone(&calls)))
+        .await?;
+
+    let (mut ws, _) = tokio_tungstenite::connect_async(url.as_str()).await?;
+    ws.send(frame("first")).await?;
+    ws.send(frame("second")).await?;
+
+    // The second delivery only starts once the first has timed out
+    for _ in 0..50 {
+        if calls.load(Ordering::SeqCst) == 2 {
+            break;
+        }
+        sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a handler stuck past its subject's timeout doesn't hold up the next message
+#[tokio::test]
+async fn test_handler_timeout_per_subject() -> Result<()> {
+    let provider = start_server(&[("HANDLER_TIMEOUT_MS", "slow.>=50")]).await?;
+    let url = server_url(&provider).await?;
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    provider.set_message_sink(SlowSubjectSink(tx)).await?;
+
+    let (mut ws, _) = tokio_tungstenite::connect_async(url.as_str()).await?;
+    ws.send(frame("slow.report")).await?;
+    ws.send(frame("fast.ping")).await?;
+
+    let next = timeout(Duration::from_millis(500), rx.recv()).await?;
+    assert_eq!(next.as_deref(), Some("fast.ping"));
+    assert_eq!(provider.handler_timeout_count(), 1);
+
+    provider.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,148 @@
+use anyhow::Result;
+use futures::SinkExt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::sleep;
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::testing::{connect, start_server, WsClient};
+use wasmcloud_provider_messaging_websocket::{
+    BrokerMessage, TrafficStats, WebSocketMessagingProvider, OVERFLOW_SUBJECT,
+};
+
+/// Start a server-mode provider with the given metrics keys, counting what its sink receives
+async fn counting_server(
+    extra: &[(&str, &str)],
+) -> Result<(WebSocketMessagingProvider, Arc<AtomicUsize>)> {
+    let provider = start_server(extra).await?;
+    let received = Arc::new(AtomicUsize::new(0));
+    let sink = Arc::clone(&received);
+    provider
+        .set_message_sink(
+            move |_session_id: String, _msg: BrokerMessage| -> Result<()> {
+                sink.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            },
+        )
+        .await?;
+    Ok((provider, received))
+}
+
+/// Send `count` messages on distinct subjects from a new client and wait for all of them,
+/// returning the still open client and its session id
+async fn send_distinct_subjects(
+    provider: &WebSocketMessagingProvider,
+    received: &AtomicUsize,
+    count: usize,
+) -> Result<(WsClient, String)> {
+    let (mut ws, session_id) = connect(provider).await?;
+    for i in 0..count {
+        let frame = serde_json::json!({ "subject": format!("s{}.x", i), "body": "abcd" });
+        ws.send(Message::Text(frame.to_string())).await?;
+    }
+    for _ in 0..500 {
+        if received.load(Ordering::SeqCst) == count {
+            break;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(received.load(Ordering::SeqCst), count);
+    Ok((ws, session_id))
+}
+
+/// Fetch `GET /metrics` over a plain HTTP/1.1 connection
+async fn fetch_metrics(provider: &WebSocketMessagingProvider) -> Result<String> {
+    let addr = provider.get_server_addr().await.unwrap();
+    let mut stream = TcpStream::connect(addr).await?;
+    let request = format!(
+        "GET /metrics HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+        addr
+    );
+    stream.write_all(request.as_bytes()).await?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert!(
+        response.contains("text/plain; version=0.0.4"),
+        "{}",
+        response
+    );
+    let (_, body) = response.split_once("\r\n\r\n").unwrap();
+    Ok(body.to_string())
+}
+
+/// Sample lines of the `messages_total` series, as (labels, value)
+fn messages_total(text: &str) -> Vec<(String, u64)> {
+    text.lines()
+        .filter_map(|line| line.strip_prefix("messages_total{"))
+        .map(|line| {
+            let (labels, value) = line.rsplit_once("} ").unwrap();
+            (labels.to_string(), value.parse().unwrap())
+        })
+        .collect()
+}
+
+/// Test that traffic on 1000 subjects stays under the series cap with accurate totals
+#[tokio::test]
+async fn test_series_capped_with_accurate_totals() -> Result<()> {
+    let (provider, received) = counting_server(&[("METRIC_MAX_SERIES", "50")]).await?;
+    let (_ws, session_id) = send_distinct_subjects(&provider, &received, 1000).await?;
+
+    let text = fetch_metrics(&provider).await?;
+    let series = messages_total(&text);
+    assert!(series.len() <= 50, "{} series", series.len());
+    assert_eq!(series.iter().map(|(_, value)| value).sum::<u64>(), 1000);
+    assert!(series.iter().any(|(labels, _)| {
+        labels
+            == &format!(
+                "mode=\"server\",role=\"handler\",subject=\"{}\"",
+                OVERFLOW_SUBJECT
+            )
+    }));
+    assert!(series
+        .iter()
+        .any(|(labels, _)| labels == "mode=\"server\",role=\"handler\",subject=\"s0\""));
+    assert!(!text.contains("session="));
+
+    // Per-session detail is kept outside the labels
+    assert_eq!(
+        provider.message_stats().sessions[&session_id],
+        TrafficStats {
+            messages: 1000,
+            bytes: 4000
+        }
+    );
+    assert_eq!(provider.metrics_text(), text);
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that subjects are grouped by their configured prefix and sessions become labels
+/// with HIGH_CARDINALITY_METRICS
+#[tokio::test]
+async fn test_subject_depth_and_session_labels() -> Result<()> {
+    let (provider, received) = counting_server(&[
+        ("METRIC_SUBJECT_DEPTH", "2"),
+        ("HIGH_CARDINALITY_METRICS", "true"),
+    ])
+    .await?;
+    let (_ws, session_id) = send_distinct_subjects(&provider, &received, 3).await?;
+
+    let series = messages_total(&fetch_metrics(&provider).await?);
+    assert_eq!(series.len(), 3);
+    assert!(series.contains(&(
+        format!(
+            "mode=\"server\",role=\"handler\",subject=\"s1.x\",session=\"{}\"",
+            session_id
+        ),
+        1
+    )));
+
+    provider.shutdown().await?;
+    Ok(())
+}
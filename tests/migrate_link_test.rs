@@ -0,0 +1,91 @@
+use anyhow::Result;
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::sleep;
+
+use wasmcloud_provider_messaging_websocket::testing::{
+    record_subjects, server_url, start_server, Subjects,
+};
+use wasmcloud_provider_messaging_websocket::{BrokerMessage, WebSocketMessagingProvider};
+
+/// Start a server-mode provider recording the subjects it receives
+async fn recording_server() -> Result<(WebSocketMessagingProvider, String, Subjects)> {
+    let provider = start_server(&[]).await?;
+    let received = record_subjects(&provider).await?;
+    let url = server_url(&provider).await?;
+    Ok((provider, url, received))
+}
+
+fn message(n: usize) -> BrokerMessage {
+    BrokerMessage {
+        subject: format!("seq.{}", n),
+        body: Bytes::from("payload"),
+        reply_to: None,
+        headers: HashMap::new(),
+    }
+}
+
+/// Test that publishes across a migration all arrive, and later ones at the new server
+#[tokio::test]
+async fn test_migrate_link_without_message_loss() -> Result<()> {
+    let (server_a, url_a, received_a) = recording_server().await?;
+    let (server_b, url_b, received_b) = recording_server().await?;
+
+    let mut config = HashMap::new();
+    config.insert("MODE".to_string(), "client".to_string());
+    config.insert("URI".to_string(), url_a);
+    config.insert("ENABLE_SESSION_TRACKING".to_string(), "true".to_string());
+    let client = WebSocketMessagingProvider::from_config(config)?;
+    client
+        .receive_link_config_as_target("publisher", HashMap::new())
+        .await?;
+
+    // Publish continuously while the link migrates
+    let publisher = client.clone();
+    let publishing = tokio::spawn(async move {
+        for n in 0..100 {
+            publisher.publish("publisher", message(n)).await?;
+            sleep(Duration::from_millis(2)).await;
+        }
+        anyhow::Ok(())
+    });
+    sleep(Duration::from_millis(20)).await;
+    client.migrate_link("publisher", &url_b).await?;
+    publishing.await??;
+
+    for n in 100..110 {
+        client.publish("publisher", message(n)).await?;
+    }
+    sleep(Duration::from_millis(300)).await;
+
+    let at_a = received_a.lock().unwrap().clone();
+    let at_b = received_b.lock().unwrap().clone();
+    let mut all: Vec<String> = at_a.iter().chain(at_b.iter()).cloned().collect();
+    all.sort();
+    let mut expected: Vec<String> = (0..110).map(|n| format!("seq.{}", n)).collect();
+    expected.sort();
+    assert_eq!(all, expected, "messages were lost or duplicated");
+    for n in 100..110 {
+        assert!(at_b.contains(&format!("seq.{}", n)), "seq.{} not at B", n);
+    }
+
+    // The migrated link keeps its session tracked and the old server lost its client
+    assert_eq!(client.list_sessions().await.len(), 1);
+    assert!(server_a.list_ws_clients().await?.is_empty());
+
+    client.shutdown().await?;
+    server_a.shutdown().await?;
+    server_b.shutdown().await?;
+    Ok(())
+}
+
+/// Test that migrating an unknown component fails
+#[tokio::test]
+async fn test_migrate_unlinked_component() -> Result<()> {
+    let (server, url, _) = recording_server().await?;
+    let client = WebSocketMessagingProvider::new();
+    assert!(client.migrate_link("nobody", &url).await.is_err());
+    server.shutdown().await?;
+    Ok(())
+}
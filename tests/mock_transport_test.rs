@@ -0,0 +1,395 @@
+use anyhow::Result;
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::{sleep, timeout};
+use tokio_tungstenite::tungstenite::protocol::{frame::coding::CloseCode, CloseFrame};
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::mock::{MockRemote, MockRemotes, MockTransport};
+use wasmcloud_provider_messaging_websocket::testing::accept;
+use wasmcloud_provider_messaging_websocket::{
+    BrokerMessage, ForbiddenReason, ForbiddenSubject, MessageTooLarge, WebSocketMessagingProvider,
+};
+
+fn mock_provider() -> Result<(WebSocketMessagingProvider, MockTransport, MockRemotes)> {
+    let mut config = HashMap::new();
+    config.insert("MODE".to_string(), "client".to_string());
+    config.insert("URI".to_string(), "ws://mock.invalid/ws".to_string());
+    config.insert("ENABLE_SESSION_TRACKING".to_string(), "true".to_string());
+
+    let (transport, remotes) = MockTransport::new();
+    let provider =
+        WebSocketMessagingProvider::from_config(config)?.with_transport(transport.clone());
+    Ok((provider, transport, remotes))
+}
+
+async fn next_frame(remote: &mut MockRemote) -> Message {
+    timeout(Duration::from_secs(1), remote.recv())
+        .await
+        .expect("frame should arrive")
+        .expect("client should still be connected")
+}
+
+/// Test that a consumer's published message reaches the remote server
+#[tokio::test]
+async fn test_publish_reaches_remote() -> Result<()> {
+    let (provider, _transport, remotes) = mock_provider()?;
+    provider
+        .receive_link_config_as_target("test-consumer", HashMap::new())
+        .await?;
+    let mut remote = accept(&remotes).await;
+    assert_eq!(remote.uri(), "ws://mock.invalid/ws");
+
+    provider
+        .publish(
+            "test-consumer",
+            BrokerMessage {
+                subject: "test.publish".to_string(),
+                body: Bytes::from("hello"),
+                reply_to: None,
+                headers: HashMap::new(),
+            },
+        )
+        .await?;
+
+    let Message::Text(text) = next_frame(&mut remote).await else {
+        panic!("expected a text frame");
+    };
+    let json: serde_json::Value = serde_json::from_str(&text)?;
+    assert_eq!(json["subject"], "test.publish");
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that messages from the remote server are forwarded to handler components
+#[tokio::test]
+async fn test_remote_message_forwarded_to_handlers() -> Result<()> {
+    let (provider, _transport, remotes) = mock_provider()?;
+    provider
+        .receive_link_config_as_source("test-handler", HashMap::new())
+        .await?;
+    let mut remote = accept(&remotes).await;
+
+    remote.send(Message::Text(
+        r#"{"subject":"test.inbound","body":"hi"}"#.to_string(),
+    ))?;
+
+    let Message::Text(text) = next_frame(&mut remote).await else {
+        panic!("expected a text frame");
+    };
+    let json: serde_json::Value = serde_json::from_str(&text)?;
+    assert_eq!(json["subject"], "test.inbound");
+
+    // The session id is used as reply target when the remote didn't set one
+    let sessions = provider.list_sessions().await;
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(json["reply_to"], sessions[0].0.as_str());
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that pings from the remote server are answered with pongs
+#[tokio::test]
+async fn test_ping_answered_with_pong() -> Result<()> {
+    let (provider, _transport, remotes) = mock_provider()?;
+    provider
+        .receive_link_config_as_target("test-consumer", HashMap::new())
+        .await?;
+    let mut remote = accept(&remotes).await;
+
+    remote.send(Message::Ping(b"keepalive".to_vec()))?;
+    assert_eq!(
+        next_frame(&mut remote).await,
+        Message::Pong(b"keepalive".to_vec())
+    );
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a close frame from the remote server ends the session
+#[tokio::test]
+async fn test_remote_close_ends_session() -> Result<()> {
+    let (provider, _transport, remotes) = mock_provider()?;
+    provider
+        .receive_link_config_as_target("test-consumer", HashMap::new())
+        .await?;
+    let remote = accept(&remotes).await;
+    assert_eq!(provider.list_sessions().await.len(), 1);
+
+    remote.send(Message::Close(Some(CloseFrame {
+        code: CloseCode::Away,
+        reason: "going away".into(),
+    })))?;
+
+    for _ in 0..50 {
+        if provider.list_sessions().await.is_empty() {
+            break;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    assert!(provider.list_sessions().await.is_empty());
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a failed connection attempt is reported to the linker
+#[tokio::test]
+async fn test_refused_connection_fails_link() -> Result<()> {
+    let (provider, transport, remotes) = mock_provider()?;
+    transport.refuse_connections(true);
+
+    let result = provider
+        .receive_link_config_as_target("test-consumer", HashMap::new())
+        .await;
+    assert!(result.is_err());
+
+    // Connections succeed again once the transport accepts them
+    transport.refuse_connections(false);
+    provider
+        .receive_link_config_as_target("test-consumer", HashMap::new())
+        .await?;
+    accept(&remotes).await;
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that the reported link config follows the merge rules key by key
+#[tokio::test]
+async fn test_link_reports_effective_config() -> Result<()> {
+    let mut defaults = HashMap::new();
+    defaults.insert("URI".to_string(), "ws://default.invalid/ws".to_string());
+    defaults.insert("CONNECT_TIMEOUT_SEC".to_string(), "10".to_string());
+    defaults.insert("HANDLER_BATCH_SIZE".to_string(), "5".to_string());
+    defaults.insert("HEADER_X-Default".to_string(), "a".to_string());
+
+    let (transport, remotes) = MockTransport::new();
+    let provider = WebSocketMessagingProvider::from_config(defaults)?.with_transport(transport);
+
+    let mut link = HashMap::new();
+    link.insert("URI".to_string(), "ws://link.invalid/ws".to_string());
+    link.insert("AUTH_TOKEN".to_string(), "secret123".to_string());
+    link.insert("HANDLER_BATCH_SIZE".to_string(), "8".to_string());
+    link.insert("HEADER_X-Link".to_string(), "b".to_string());
+
+    let summary = provider
+        .receive_link_config_as_target("test-consumer", link)
+        .await?;
+    let remote = accept(&remotes).await;
+    assert_eq!(remote.uri(), "ws://link.invalid/ws");
+
+    // Link values win, everything else comes from the provider defaults
+    let effective = &summary.effective;
+    assert_eq!(effective.uri, "ws://link.invalid/ws");
+    assert_eq!(effective.connect_timeout, Duration::from_secs(10));
+    assert_eq!(effective.handler_batch_size, 8);
+    assert!(effective.enable_session_tracking);
+    assert_eq!(effective.custom_headers.len(), 2);
+
+    // Secrets never leave the provider
+    assert_eq!(effective.auth_token.as_deref(), Some("[REDACTED]"));
+    assert!(effective.custom_headers.values().all(|v| v == "[REDACTED]"));
+
+    assert_eq!(
+        summary.from_link,
+        vec!["AUTH_TOKEN", "HANDLER_BATCH_SIZE", "HEADER_X-Link", "URI"]
+    );
+    assert!(summary
+        .from_default
+        .contains(&"CONNECT_TIMEOUT_SEC".to_string()));
+    assert!(!summary.from_default.contains(&"URI".to_string()));
+
+    // The same effective config is retrievable after linking
+    let stored = provider
+        .get_link_config("test-consumer")
+        .await
+        .expect("link config should be stored");
+    assert_eq!(stored.uri, effective.uri);
+    assert_eq!(stored.handler_batch_size, 8);
+    assert_eq!(stored.connect_timeout, Duration::from_secs(10));
+    assert_eq!(stored.auth_token, effective.auth_token);
+    assert!(provider.get_link_config("unknown").await.is_none());
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a session id announced in the server's first message replaces the local one
+#[tokio::test]
+async fn test_server_assigned_session_from_first_message() -> Result<()> {
+    let mut config = HashMap::new();
+    config.insert("URI".to_string(), "ws://mock.invalid/ws".to_string());
+    config.insert("SERVER_SESSION_FIELD".to_string(), "_session".to_string());
+
+    let (transport, remotes) = MockTransport::new();
+    let provider = WebSocketMessagingProvider::from_config(config)?.with_transport(transport);
+    provider
+        .receive_link_config_as_source("test-handler", HashMap::new())
+        .await?;
+    let mut remote = accept(&remotes).await;
+
+    remote.send(Message::Text(r#"{"_session":"srv-123"}"#.to_string()))?;
+    for _ in 0..50 {
+        if provider.get_session("srv-123").await.is_some() {
+            break;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(
+        provider.get_session("srv-123").await.as_deref(),
+        Some("test-handler")
+    );
+    assert_eq!(provider.list_sessions().await.len(), 1);
+
+    // The welcome isn't forwarded, later messages default their reply_to to the server id
+    remote.send(Message::Text(
+        r#"{"subject":"test.after","body":"x"}"#.to_string(),
+    ))?;
+    let Message::Text(text) = next_frame(&mut remote).await else {
+        panic!("expected a text frame");
+    };
+    let json: serde_json::Value = serde_json::from_str(&text)?;
+    assert_eq!(json["subject"], "test.after");
+    assert_eq!(json["reply_to"], "srv-123");
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a session id from the upgrade response header is used from the start
+#[tokio::test]
+async fn test_server_assigned_session_from_header() -> Result<()> {
+    let mut config = HashMap::new();
+    config.insert("URI".to_string(), "ws://mock.invalid/ws".to_string());
+    config.insert(
+        "SERVER_SESSION_HEADER".to_string(),
+        "X-Session-Id".to_string(),
+    );
+
+    let (transport, remotes) = MockTransport::new();
+    transport.set_response_header("X-Session-Id", "srv-456");
+    let provider = WebSocketMessagingProvider::from_config(config)?.with_transport(transport);
+    provider
+        .receive_link_config_as_target("test-consumer", HashMap::new())
+        .await?;
+    accept(&remotes).await;
+
+    assert_eq!(
+        provider.get_session("srv-456").await.as_deref(),
+        Some("test-consumer")
+    );
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that publishes on forbidden subjects are rejected before reaching the wire
+#[tokio::test]
+async fn test_publish_rejects_forbidden_subjects() -> Result<()> {
+    let (provider, _transport, remotes) = mock_provider()?;
+    let mut link = HashMap::new();
+    link.insert("PUBLISH_ALLOW".to_string(), "tenant-a.>".to_string());
+    provider
+        .receive_link_config_as_target("tenant-a", link)
+        .await?;
+    let mut remote = accept(&remotes).await;
+
+    let message = |subject: &str| BrokerMessage {
+        subject: subject.to_string(),
+        body: Bytes::from("x"),
+        reply_to: None,
+        headers: HashMap::new(),
+    };
+
+    let err = provider
+        .publish("tenant-a", message("$SYS.lifecycle"))
+        .await
+        .unwrap_err();
+    let forbidden = err
+        .downcast_ref::<ForbiddenSubject>()
+        .expect("typed ForbiddenSubject error");
+    assert_eq!(forbidden.reason, ForbiddenReason::Reserved);
+    assert_eq!(forbidden.component_id, "tenant-a");
+
+    let err = provider
+        .publish("tenant-a", message("tenant-b.orders"))
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<ForbiddenSubject>().map(|e| e.reason),
+        Some(ForbiddenReason::NotAllowed)
+    );
+    assert_eq!(provider.forbidden_publish_count(), 2);
+
+    // Only the permitted publish reaches the remote server
+    provider
+        .publish("tenant-a", message("tenant-a.orders"))
+        .await?;
+    let Message::Text(text) = next_frame(&mut remote).await else {
+        panic!("expected a text frame");
+    };
+    let json: serde_json::Value = serde_json::from_str(&text)?;
+    assert_eq!(json["subject"], "tenant-a.orders");
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that oversized messages fail with MessageTooLarge before anything is sent
+#[tokio::test]
+async fn test_oversized_publish_rejected_before_send() -> Result<()> {
+    let (provider, _transport, remotes) = mock_provider()?;
+    let mut link = HashMap::new();
+    link.insert("MAX_MESSAGE_BYTES".to_string(), "100".to_string());
+    provider
+        .receive_link_config_as_target("test-consumer", link)
+        .await?;
+    let mut remote = accept(&remotes).await;
+
+    let message = |body: Vec<u8>| BrokerMessage {
+        subject: "test.large".to_string(),
+        body: Bytes::from(body),
+        reply_to: None,
+        headers: HashMap::new(),
+    };
+
+    // Rejected on the body alone, without encoding
+    let err = provider
+        .publish("test-consumer", message(vec![b'x'; 200]))
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<MessageTooLarge>(),
+        Some(&MessageTooLarge {
+            size: 200,
+            limit: 100
+        })
+    );
+
+    // The body fits but the encoded envelope does not
+    let err = provider
+        .publish("test-consumer", message(vec![b'x'; 90]))
+        .await
+        .unwrap_err();
+    let too_large = err
+        .downcast_ref::<MessageTooLarge>()
+        .expect("typed MessageTooLarge error");
+    assert!(too_large.size > 100);
+
+    assert!(timeout(Duration::from_millis(100), remote.recv())
+        .await
+        .is_err());
+
+    provider
+        .publish("test-consumer", message(vec![b'x'; 10]))
+        .await?;
+    next_frame(&mut remote).await;
+
+    provider.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,129 @@
+use anyhow::Result;
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::mock::{MockRemote, MockTransport};
+use wasmcloud_provider_messaging_websocket::{
+    BrokerMessage, ForbiddenReason, SubjectNotPermitted, WebSocketMessagingProvider,
+};
+
+const COMPONENT: &str = "test-consumer";
+
+async fn consumer_link(link: &[(&str, &str)]) -> Result<(WebSocketMessagingProvider, MockRemote)> {
+    let mut config = HashMap::new();
+    config.insert("MODE".to_string(), "client".to_string());
+    config.insert("URI".to_string(), "ws://mock.invalid/ws".to_string());
+    let (transport, remotes) = MockTransport::new();
+    let provider = WebSocketMessagingProvider::from_config(config)?.with_transport(transport);
+
+    let link = link
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    provider
+        .receive_link_config_as_target(COMPONENT, link)
+        .await?;
+    let remote = timeout(Duration::from_secs(1), remotes.accept())
+        .await?
+        .expect("transport should be alive");
+    Ok((provider, remote))
+}
+
+fn message(subject: &str) -> BrokerMessage {
+    BrokerMessage {
+        subject: subject.to_string(),
+        body: Bytes::from("x"),
+        reply_to: None,
+        headers: HashMap::new(),
+    }
+}
+
+/// The reason a send was refused with `SubjectNotPermitted`
+fn not_permitted(result: Result<()>) -> ForbiddenReason {
+    let err = result.expect_err("send should be refused");
+    err.downcast_ref::<SubjectNotPermitted>()
+        .unwrap_or_else(|| panic!("unexpected error: {:#}", err))
+        .reason
+}
+
+async fn sent_subject(remote: &mut MockRemote) -> Result<String> {
+    let frame = timeout(Duration::from_secs(1), remote.recv())
+        .await?
+        .expect("client should still be connected");
+    let Message::Text(text) = frame else {
+        anyhow::bail!("expected a text frame, got {:?}", frame)
+    };
+    let json: serde_json::Value = serde_json::from_str(&text)?;
+    Ok(json["subject"].as_str().unwrap_or_default().to_string())
+}
+
+/// Test that publishing on a denied subject fails without sending anything
+#[tokio::test]
+async fn test_denied_publish_errors() -> Result<()> {
+    let (provider, mut remote) = consumer_link(&[("OUTBOUND_DENY", "admin.>")]).await?;
+
+    let reason = not_permitted(provider.publish(COMPONENT, message("admin.reset")).await);
+    assert_eq!(reason, ForbiddenReason::Denied);
+    provider.publish(COMPONENT, message("orders.new")).await?;
+    assert_eq!(sent_subject(&mut remote).await?, "orders.new");
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that only subjects on the allow list are sent, by `publish` and `send_to_session`
+#[tokio::test]
+async fn test_allowed_subject_succeeds() -> Result<()> {
+    let (provider, mut remote) = consumer_link(&[("OUTBOUND_ALLOW", "orders.*")]).await?;
+    let session_id = provider.list_links().await[0]
+        .session_id
+        .clone()
+        .expect("link should be connected");
+
+    provider.publish(COMPONENT, message("orders.new")).await?;
+    assert_eq!(sent_subject(&mut remote).await?, "orders.new");
+    let reason = not_permitted(provider.publish(COMPONENT, message("metrics.cpu")).await);
+    assert_eq!(reason, ForbiddenReason::NotAllowed);
+
+    provider
+        .send_to_session(&session_id, message("orders.paid"))
+        .await?;
+    assert_eq!(sent_subject(&mut remote).await?, "orders.paid");
+    let reason = not_permitted(
+        provider
+            .send_to_session(&session_id, message("metrics.cpu"))
+            .await,
+    );
+    assert_eq!(reason, ForbiddenReason::NotAllowed);
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a subject matching both lists is refused, as for inbound rules
+#[tokio::test]
+async fn test_deny_overrides_allow() -> Result<()> {
+    let (provider, mut remote) = consumer_link(&[
+        ("OUTBOUND_ALLOW", "orders.>"),
+        ("OUTBOUND_DENY", "orders.internal.>"),
+    ])
+    .await?;
+
+    let reason = not_permitted(
+        provider
+            .publish(COMPONENT, message("orders.internal.audit"))
+            .await,
+    );
+    assert_eq!(reason, ForbiddenReason::Denied);
+    provider.publish(COMPONENT, message("orders.new")).await?;
+    assert_eq!(sent_subject(&mut remote).await?, "orders.new");
+    assert!(timeout(Duration::from_millis(100), remote.recv())
+        .await
+        .is_err());
+
+    provider.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,74 @@
+use anyhow::Result;
+use futures::{SinkExt, StreamExt};
+use std::time::Duration;
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::testing::{server_url, start_server};
+use wasmcloud_provider_messaging_websocket::ProviderEvent;
+
+/// Test that a garbage flood triggers a single cooldown event
+#[tokio::test]
+async fn test_garbage_flood_starts_cooldown() -> Result<()> {
+    let provider = start_server(&[
+        ("PARSE_FAIL_THRESHOLD", "5"),
+        ("PARSE_FAIL_COOLDOWN_MS", "60000"),
+    ])
+    .await?;
+    let mut events = provider.subscribe_events();
+    let url = server_url(&provider).await?;
+
+    let (mut ws, _) = tokio_tungstenite::connect_async(url.as_str()).await?;
+    for _ in 0..100 {
+        ws.send(Message::Text("not json {".to_string())).await?;
+    }
+
+    let event = timeout(Duration::from_secs(2), events.recv()).await??;
+    match event {
+        ProviderEvent::ParseFailureCooldown { failures, .. } => assert_eq!(failures, 5),
+        other => panic!("unexpected event: {:?}", other),
+    }
+
+    // The rest of the flood is dropped without parsing, so no further events fire
+    assert!(timeout(Duration::from_millis(200), events.recv())
+        .await
+        .is_err());
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a session is closed once the disconnect threshold is reached
+#[tokio::test]
+async fn test_garbage_flood_escalates_to_disconnect() -> Result<()> {
+    let provider = start_server(&[
+        ("PARSE_FAIL_THRESHOLD", "0"),
+        ("PARSE_FAIL_DISCONNECT_THRESHOLD", "3"),
+    ])
+    .await?;
+    let mut events = provider.subscribe_events();
+    let url = server_url(&provider).await?;
+
+    let (mut ws, _) = tokio_tungstenite::connect_async(url.as_str()).await?;
+    for _ in 0..3 {
+        ws.send(Message::Text("garbage".to_string())).await?;
+    }
+
+    let event = timeout(Duration::from_secs(2), events.recv()).await??;
+    assert!(matches!(
+        event,
+        ProviderEvent::ParseFailureDisconnect { failures: 3, .. }
+    ));
+
+    let frame = timeout(Duration::from_secs(2), ws.next())
+        .await?
+        .expect("close frame")?;
+    match frame {
+        Message::Close(Some(close)) => assert_eq!(close.code, CloseCode::Policy),
+        other => panic!("unexpected frame: {:?}", other),
+    }
+
+    provider.shutdown().await?;
+    Ok(())
+}
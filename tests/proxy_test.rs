@@ -0,0 +1,119 @@
+use anyhow::Result;
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::sleep;
+
+use wasmcloud_provider_messaging_websocket::testing::{
+    record_subjects, server_url, start_server, Subjects,
+};
+use wasmcloud_provider_messaging_websocket::{BrokerMessage, WebSocketMessagingProvider};
+
+type Received = Arc<Mutex<Vec<String>>>;
+
+/// Start a server-mode provider recording the subjects it receives
+async fn recording_server() -> Result<(WebSocketMessagingProvider, String, Subjects)> {
+    let provider = start_server(&[]).await?;
+    let received = record_subjects(&provider).await?;
+    let url = server_url(&provider).await?;
+    Ok((provider, url, received))
+}
+
+/// Start a minimal HTTP CONNECT proxy recording the authorities it tunnels to
+async fn start_connect_proxy() -> Result<(String, Received)> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let url = format!("http://{}", listener.local_addr()?);
+    let tunnels = Received::default();
+    let recorded = Arc::clone(&tunnels);
+    tokio::spawn(async move {
+        while let Ok((mut client, _)) = listener.accept().await {
+            let recorded = Arc::clone(&recorded);
+            tokio::spawn(async move {
+                let mut header = Vec::new();
+                while !header.ends_with(b"\r\n\r\n") {
+                    let Ok(byte) = client.read_u8().await else {
+                        return;
+                    };
+                    header.push(byte);
+                }
+                let header = String::from_utf8_lossy(&header).into_owned();
+                let Some(authority) = header
+                    .strip_prefix("CONNECT ")
+                    .and_then(|rest| rest.split_whitespace().next())
+                else {
+                    let _ = client
+                        .write_all(b"HTTP/1.1 405 Method Not Allowed\r\n\r\n")
+                        .await;
+                    return;
+                };
+                recorded.lock().unwrap().push(authority.to_string());
+                let Ok(mut upstream) = TcpStream::connect(authority).await else {
+                    let _ = client.write_all(b"HTTP/1.1 502 Bad Gateway\r\n\r\n").await;
+                    return;
+                };
+                let _ = client
+                    .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+                    .await;
+                let _ = tokio::io::copy_bidirectional(&mut client, &mut upstream).await;
+            });
+        }
+    });
+    Ok((url, tunnels))
+}
+
+/// Test that a client link with PROXY_URL reaches its server through the CONNECT proxy
+#[tokio::test]
+async fn test_client_connects_through_http_proxy() -> Result<()> {
+    let (server, url, received) = recording_server().await?;
+    let (proxy_url, tunnels) = start_connect_proxy().await?;
+    let authority = url
+        .trim_start_matches("ws://")
+        .trim_end_matches("/ws")
+        .to_string();
+
+    let mut config = HashMap::new();
+    config.insert("MODE".to_string(), "client".to_string());
+    config.insert("URI".to_string(), url);
+    config.insert("PROXY_URL".to_string(), proxy_url);
+    let client = WebSocketMessagingProvider::from_config(config)?;
+    client
+        .receive_link_config_as_target("publisher", HashMap::new())
+        .await?;
+    assert_eq!(*tunnels.lock().unwrap(), vec![authority]);
+
+    client
+        .publish(
+            "publisher",
+            BrokerMessage {
+                subject: "via.proxy".to_string(),
+                body: Bytes::from("hello"),
+                reply_to: None,
+                headers: HashMap::new(),
+            },
+        )
+        .await?;
+    for _ in 0..50 {
+        if !received.lock().unwrap().is_empty() {
+            break;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(*received.lock().unwrap(), vec!["via.proxy"]);
+
+    client.shutdown().await?;
+    server.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a PROXY_URL with an unsupported scheme is rejected when the config is parsed
+#[test]
+fn test_unsupported_proxy_scheme_rejected() {
+    let mut config = HashMap::new();
+    config.insert("MODE".to_string(), "client".to_string());
+    config.insert("URI".to_string(), "ws://127.0.0.1:1/ws".to_string());
+    config.insert("PROXY_URL".to_string(), "ftp://proxy.local".to_string());
+    assert!(WebSocketMessagingProvider::from_config(config).is_err());
+}
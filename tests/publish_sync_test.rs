@@ -0,0 +1,89 @@
+use anyhow::Result;
+use bytes::Bytes;
+use futures::FutureExt;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use wasmcloud_provider_messaging_websocket::mock::{MockRemote, MockTransport};
+use wasmcloud_provider_messaging_websocket::{
+    BrokerMessage, FlushTimeout, WebSocketMessagingProvider, PUBLISH_SYNC_TIMEOUT,
+};
+
+/// Link a publisher whose mock socket holds a single unread frame
+async fn link_publisher() -> Result<(WebSocketMessagingProvider, MockRemote)> {
+    let mut config = HashMap::new();
+    config.insert("URI".to_string(), "ws://mock.invalid/ws".to_string());
+
+    let (transport, remotes) = MockTransport::new();
+    transport.set_write_capacity(Some(0));
+    let provider = WebSocketMessagingProvider::from_config(config)?.with_transport(transport);
+    provider
+        .receive_link_config_as_target("publisher", HashMap::new())
+        .await?;
+    let remote = remotes.accept().await.expect("connection");
+    Ok((provider, remote))
+}
+
+fn message(subject: &str) -> BrokerMessage {
+    BrokerMessage {
+        subject: subject.to_string(),
+        body: Bytes::from("x"),
+        reply_to: None,
+        headers: HashMap::new(),
+    }
+}
+
+/// Test that `publish_sync` returns only once its frame is waiting at the remote
+#[tokio::test]
+async fn test_publish_sync_returns_after_write() -> Result<()> {
+    let (provider, mut remote) = link_publisher().await?;
+
+    provider
+        .publish_sync("publisher", message("control.shutdown"))
+        .await?;
+    // Already written, so available without waiting on the provider
+    let frame = remote
+        .recv()
+        .now_or_never()
+        .flatten()
+        .expect("frame written before publish_sync returned");
+    assert!(frame.to_text()?.contains("control.shutdown"));
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that `publish_sync` behind a stalled socket fails within its timeout
+#[tokio::test]
+async fn test_publish_sync_fails_on_stalled_socket() -> Result<()> {
+    let (provider, mut remote) = link_publisher().await?;
+    // Fill the socket's only slot; the remote doesn't read it
+    provider.publish("publisher", message("orders.1")).await?;
+
+    let started = Instant::now();
+    let error = provider
+        .publish_sync("publisher", message("control.shutdown"))
+        .await
+        .expect_err("publish_sync should time out");
+    assert!(started.elapsed() < PUBLISH_SYNC_TIMEOUT + Duration::from_secs(1));
+    assert_eq!(
+        error.downcast_ref::<FlushTimeout>(),
+        Some(&FlushTimeout {
+            component_id: "publisher".to_string(),
+            timeout: PUBLISH_SYNC_TIMEOUT,
+        })
+    );
+
+    // Both frames go out once the remote reads
+    for subject in ["orders.1", "control.shutdown"] {
+        let frame = remote.recv().await.expect("queued frame");
+        assert!(frame.to_text()?.contains(subject));
+    }
+    assert!(provider
+        .publish_sync("unknown", message("control.shutdown"))
+        .await
+        .is_err());
+
+    provider.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::sleep;
+
+use wasmcloud_provider_messaging_websocket::testing::{server_url, start_server};
+use wasmcloud_provider_messaging_websocket::WebSocketMessagingProvider;
+
+/// Client-mode provider with `component` linked to `server`, tracking sessions
+async fn linked_client(
+    server: &WebSocketMessagingProvider,
+    component: &str,
+) -> Result<WebSocketMessagingProvider> {
+    let mut config = HashMap::new();
+    config.insert("MODE".to_string(), "client".to_string());
+    config.insert("URI".to_string(), server_url(server).await?);
+    config.insert("ENABLE_SESSION_TRACKING".to_string(), "true".to_string());
+
+    let client = WebSocketMessagingProvider::from_config(config)?;
+    client
+        .receive_link_config_as_target(component, HashMap::new())
+        .await?;
+    Ok(client)
+}
+
+/// Test that an orphan session is removed once two passes have seen it
+#[tokio::test]
+async fn test_orphan_session_removed() -> Result<()> {
+    let provider = WebSocketMessagingProvider::new();
+    provider
+        .inject_session("orphan", "unlinked-component")
+        .await;
+
+    assert!(provider.consistency_report().await.is_consistent());
+    assert!(provider.get_session("orphan").await.is_some());
+
+    let report = provider.consistency_report().await;
+    assert_eq!(report.orphan_sessions, vec!["orphan"]);
+    assert!(provider.get_session("orphan").await.is_none());
+    assert_eq!(provider.state_inconsistencies_total(), 1);
+    assert!(provider.consistency_report().await.is_consistent());
+    Ok(())
+}
+
+/// Test that a healthy link is consistent and a missing session entry is reported
+#[tokio::test]
+async fn test_connection_without_session_reported() -> Result<()> {
+    let server = start_server(&[]).await?;
+    let client = linked_client(&server, "publisher").await?;
+    assert!(client.consistency_report().await.is_consistent());
+
+    let (session_id, _) = client.list_sessions().await.pop().unwrap();
+    client.forget_session(&session_id).await;
+    let report = client.consistency_report().await;
+    assert_eq!(report.untracked_connections, vec!["publisher"]);
+
+    client.shutdown().await?;
+    server.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a link whose connection task ended is reported as dead
+#[tokio::test]
+async fn test_dead_connection_reported() -> Result<()> {
+    let server = start_server(&[]).await?;
+    let client = linked_client(&server, "publisher").await?;
+
+    client.abort_link_task("publisher").await;
+    sleep(Duration::from_millis(50)).await;
+    let report = client.consistency_report().await;
+    assert_eq!(report.dead_connections, vec!["publisher"]);
+    assert!(report.untracked_connections.is_empty());
+
+    client.shutdown().await?;
+    server.shutdown().await?;
+    Ok(())
+}
+
+/// Test that stale server clients and their group memberships are removed
+#[tokio::test]
+async fn test_stale_client_and_memberships_removed() -> Result<()> {
+    let server = start_server(&[]).await?;
+    server.inject_stale_ws_client("stale").await;
+    server.inject_group_membership("gone", "room-7");
+
+    let report = server.consistency_report().await;
+    assert_eq!(
+        report.orphan_memberships,
+        vec![("gone".to_string(), "room-7".to_string())]
+    );
+    assert!(report.stale_clients.is_empty());
+    assert!(server.list_ws_group_members("room-7").await?.is_empty());
+
+    let report = server.consistency_report().await;
+    assert_eq!(report.stale_clients, vec!["stale"]);
+    assert!(server.list_ws_clients().await?.is_empty());
+    assert_eq!(server.state_inconsistencies_total(), 2);
+
+    server.shutdown().await?;
+    Ok(())
+}
+
+/// Test that the janitor's pass is listed as the `reconcile` job and runs on demand
+#[tokio::test]
+async fn test_reconcile_job_runs_on_demand() -> Result<()> {
+    let mut config = HashMap::new();
+    config.insert("JOB_RECONCILE_INTERVAL_SEC".to_string(), "3600".to_string());
+    let provider = WebSocketMessagingProvider::from_config(config)?;
+    provider.start_janitor();
+    provider
+        .inject_session("orphan", "unlinked-component")
+        .await;
+
+    let jobs = provider.background_jobs();
+    assert_eq!(jobs.len(), 1);
+    assert_eq!(jobs[0].name, "reconcile");
+    assert_eq!(jobs[0].interval, Duration::from_secs(3600));
+    assert_eq!(jobs[0].runs, 0);
+
+    // The orphan is removed by the second pass that sees it
+    assert_eq!(provider.run_job_now("reconcile").await?, 0);
+    assert_eq!(provider.run_job_now("reconcile").await?, 1);
+    let job = &provider.background_jobs()[0];
+    assert_eq!((job.runs, job.last_items), (2, 1));
+    assert!(job.last_run.is_some() && !job.running);
+    assert!(provider.run_job_now("missing").await.is_err());
+
+    provider.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,203 @@
+use anyhow::Result;
+use bytes::Bytes;
+use futures::{stream, StreamExt};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::{sleep, timeout};
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::mock::{MockRemote, MockRemotes, MockTransport};
+use wasmcloud_provider_messaging_websocket::testing::accept;
+use wasmcloud_provider_messaging_websocket::{
+    BodyStreamAborted, BrokerMessage, LinkConnectionStatus, LinkRole, WebSocketMessagingProvider,
+};
+
+fn reconnecting_provider(
+    max_retries: &str,
+) -> Result<(WebSocketMessagingProvider, MockTransport, MockRemotes)> {
+    let mut config = HashMap::new();
+    config.insert("URI".to_string(), "ws://mock.invalid/ws".to_string());
+    config.insert("RECONNECT".to_string(), "true".to_string());
+    config.insert("RECONNECT_MAX_RETRIES".to_string(), max_retries.to_string());
+    config.insert("RECONNECT_BASE_DELAY_MS".to_string(), "10".to_string());
+    let (transport, remotes) = MockTransport::new();
+    let provider =
+        WebSocketMessagingProvider::from_config(config)?.with_transport(transport.clone());
+    Ok((provider, transport, remotes))
+}
+
+async fn next_frame(remote: &mut MockRemote) -> Message {
+    timeout(Duration::from_secs(1), remote.recv())
+        .await
+        .expect("frame should arrive")
+        .expect("client should still be connected")
+}
+
+fn message(subject: &str) -> BrokerMessage {
+    BrokerMessage {
+        subject: subject.to_string(),
+        body: Bytes::from("hello"),
+        reply_to: None,
+        headers: HashMap::new(),
+    }
+}
+
+/// Test that a dropped connection is redialled and the link keeps its session
+#[tokio::test]
+async fn test_dropped_connection_is_redialled() -> Result<()> {
+    let (provider, _transport, remotes) = reconnecting_provider("5")?;
+    provider
+        .receive_link_config_as_target("test-consumer", HashMap::new())
+        .await?;
+    let remote = accept(&remotes).await;
+    let sessions = provider.list_sessions().await;
+    assert_eq!(sessions.len(), 1);
+
+    remote.disconnect();
+    let mut remote = accept(&remotes).await;
+    assert_eq!(provider.list_sessions().await, sessions);
+
+    provider
+        .publish("test-consumer", message("after.reconnect"))
+        .await?;
+    let Message::Text(text) = next_frame(&mut remote).await else {
+        panic!("expected a text frame");
+    };
+    let json: serde_json::Value = serde_json::from_str(&text)?;
+    assert_eq!(json["subject"], "after.reconnect");
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a body stream being published when the connection drops fails with
+/// `connection lost` instead of carrying on over the redialled connection
+#[tokio::test]
+async fn test_redial_aborts_published_body_stream() -> Result<()> {
+    let (provider, _transport, remotes) = reconnecting_provider("5")?;
+    provider
+        .receive_link_config_as_target("uploader", HashMap::new())
+        .await?;
+    let mut remote = accept(&remotes).await;
+
+    // A source that never ends on its own
+    let endless = stream::repeat(()).then(|()| async {
+        sleep(Duration::from_millis(5)).await;
+        Bytes::from(vec![7u8; 1024])
+    });
+    let publisher = provider.clone();
+    let publish = tokio::spawn(async move {
+        publisher
+            .publish_stream("uploader", "files.upload", endless, HashMap::new())
+            .await
+    });
+    next_frame(&mut remote).await;
+
+    remote.disconnect();
+    let error = timeout(Duration::from_secs(5), publish)
+        .await??
+        .expect_err("publish should fail");
+    let aborted = error
+        .downcast_ref::<BodyStreamAborted>()
+        .unwrap_or_else(|| panic!("expected BodyStreamAborted, got {}", error));
+    assert_eq!(aborted.reason, "connection lost");
+    accept(&remotes).await;
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a link whose redials all fail is removed and listed as failed
+#[tokio::test]
+async fn test_link_removed_after_retries_exhausted() -> Result<()> {
+    let (provider, transport, remotes) = reconnecting_provider("2")?;
+    provider
+        .receive_link_config_as_source("test-handler", HashMap::new())
+        .await?;
+    let remote = accept(&remotes).await;
+
+    transport.refuse_connections(true);
+    remote.disconnect();
+
+    let mut status = None;
+    for _ in 0..100 {
+        let links = provider.list_links().await;
+        if let [link] = links.as_slice() {
+            if link.connection_status == LinkConnectionStatus::Failed {
+                status = Some(link.clone());
+                break;
+            }
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    let status = status.expect("link should be listed as failed");
+    assert_eq!(status.component_id, "test-handler");
+    assert_eq!(status.role, LinkRole::Handler);
+    assert!(provider.list_sessions().await.is_empty());
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that frames queued during an outage are dropped on reconnect once older than
+/// `RECONNECT_BUFFER_MAX_AGE`, while the recent ones are still written
+#[tokio::test]
+async fn test_stale_frames_dropped_on_reconnect() -> Result<()> {
+    let (provider, transport, remotes) = reconnecting_provider("0")?;
+    // Redials 100, 300 and 700 ms into the outage
+    let link_config = HashMap::from([
+        ("RECONNECT_BASE_DELAY_MS".to_string(), "100".to_string()),
+        ("RECONNECT_BUFFER_MAX_AGE_MS".to_string(), "500".to_string()),
+    ]);
+    provider
+        .receive_link_config_as_target("test-consumer", link_config)
+        .await?;
+    let remote = accept(&remotes).await;
+
+    transport.refuse_connections(true);
+    remote.disconnect();
+    for subject in ["old.1", "old.2", "old.3"] {
+        provider.publish("test-consumer", message(subject)).await?;
+    }
+    sleep(Duration::from_millis(400)).await;
+    provider.publish("test-consumer", message("recent")).await?;
+    transport.refuse_connections(false);
+
+    let mut remote = accept(&remotes).await;
+    provider
+        .publish("test-consumer", message("after.reconnect"))
+        .await?;
+    let mut subjects = Vec::new();
+    for _ in 0..2 {
+        let Message::Text(text) = next_frame(&mut remote).await else {
+            panic!("expected a text frame");
+        };
+        let json: serde_json::Value = serde_json::from_str(&text)?;
+        subjects.push(json["subject"].as_str().unwrap().to_string());
+    }
+    assert_eq!(subjects, vec!["recent", "after.reconnect"]);
+    assert_eq!(provider.stale_reconnect_frame_count(), 3);
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that without `RECONNECT` a dropped connection is not redialled
+#[tokio::test]
+async fn test_no_reconnect_by_default() -> Result<()> {
+    let mut config = HashMap::new();
+    config.insert("URI".to_string(), "ws://mock.invalid/ws".to_string());
+    let (transport, remotes) = MockTransport::new();
+    let provider = WebSocketMessagingProvider::from_config(config)?.with_transport(transport);
+    provider
+        .receive_link_config_as_target("test-consumer", HashMap::new())
+        .await?;
+    accept(&remotes).await.disconnect();
+
+    assert!(timeout(Duration::from_millis(200), remotes.accept())
+        .await
+        .is_err());
+
+    provider.shutdown().await?;
+    Ok(())
+}
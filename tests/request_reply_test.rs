@@ -0,0 +1,148 @@
+use anyhow::Result;
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::mock::{MockRemote, MockTransport};
+use wasmcloud_provider_messaging_websocket::testing::accept;
+use wasmcloud_provider_messaging_websocket::{BrokerMessage, WebSocketMessagingProvider};
+
+async fn next_json(remote: &mut MockRemote) -> Result<serde_json::Value> {
+    let frame = timeout(Duration::from_secs(1), remote.recv())
+        .await?
+        .expect("frame");
+    Ok(serde_json::from_str(frame.to_text()?)?)
+}
+
+/// A provider with a consumer link `requester` and a handler link `handler`, and the
+/// remote ends of both connections
+async fn linked() -> Result<(WebSocketMessagingProvider, MockRemote, MockRemote)> {
+    let (transport, remotes) = MockTransport::new();
+    let config = HashMap::from([("URI".to_string(), "ws://mock.invalid/ws".to_string())]);
+    let provider = WebSocketMessagingProvider::from_config(config)?.with_transport(transport);
+    provider
+        .receive_link_config_as_target("requester", HashMap::new())
+        .await?;
+    let requester = accept(&remotes).await;
+    provider
+        .receive_link_config_as_source("handler", HashMap::new())
+        .await?;
+    let handler = accept(&remotes).await;
+    Ok((provider, requester, handler))
+}
+
+fn reply(subject: &str, corr_id: Option<&str>) -> Message {
+    let mut envelope = serde_json::json!({ "subject": subject, "body": "pong" });
+    if let Some(corr_id) = corr_id {
+        envelope["corr_id"] = serde_json::json!(corr_id);
+    }
+    Message::Text(envelope.to_string())
+}
+
+/// Send a `svc.ping` request from `requester` in the background
+fn spawn_request(provider: &WebSocketMessagingProvider) -> JoinHandle<Result<BrokerMessage>> {
+    let provider = provider.clone();
+    tokio::spawn(async move {
+        provider
+            .request(
+                "requester",
+                "svc.ping".to_string(),
+                Bytes::from("ping"),
+                Duration::from_secs(2),
+            )
+            .await
+    })
+}
+
+/// Test that the reply on the generated inbox subject resolves the request and is not
+/// forwarded to handlers
+#[tokio::test]
+async fn test_request_resolves_with_reply() -> Result<()> {
+    let (provider, mut requester, mut handler) = linked().await?;
+
+    let request = spawn_request(&provider);
+    let sent = next_json(&mut requester).await?;
+    assert_eq!(sent["subject"], "svc.ping");
+    let reply_to = sent["reply_to"]
+        .as_str()
+        .expect("reply subject")
+        .to_string();
+    assert!(reply_to.starts_with("_INBOX."));
+    let corr_id = sent["corr_id"].as_str().expect("corr_id");
+
+    requester.send(reply(&reply_to, Some(corr_id)))?;
+    let response = request.await??;
+    assert_eq!(response.subject, reply_to);
+    assert_eq!(response.body, Bytes::from("pong"));
+
+    // Only ordinary messages reach the handler
+    requester.send(reply("svc.event", None))?;
+    assert_eq!(next_json(&mut handler).await?["subject"], "svc.event");
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a request without a reply times out and stops claiming its inbox subject
+#[tokio::test]
+async fn test_request_times_out_and_cleans_up() -> Result<()> {
+    let (provider, mut requester, mut handler) = linked().await?;
+
+    let error = provider
+        .request(
+            "requester",
+            "svc.slow".to_string(),
+            Bytes::new(),
+            Duration::from_millis(100),
+        )
+        .await
+        .expect_err("request should time out");
+    assert!(error.to_string().contains("timed out"));
+    let sent = next_json(&mut requester).await?;
+    let reply_to = sent["reply_to"]
+        .as_str()
+        .expect("reply subject")
+        .to_string();
+
+    // A late reply is an ordinary message now
+    requester.send(reply(&reply_to, sent["corr_id"].as_str()))?;
+    assert_eq!(next_json(&mut handler).await?["subject"], reply_to);
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a message on the inbox subject from a third party doesn't resolve the
+/// request: without its `corr_id`, with another one, or with it on another connection
+#[tokio::test]
+async fn test_third_party_message_does_not_resolve_request() -> Result<()> {
+    let (provider, mut requester, mut handler) = linked().await?;
+
+    let request = spawn_request(&provider);
+    let sent = next_json(&mut requester).await?;
+    let reply_to = sent["reply_to"]
+        .as_str()
+        .expect("reply subject")
+        .to_string();
+    let corr_id = sent["corr_id"].as_str().expect("corr_id").to_string();
+
+    requester.send(reply(&reply_to, None))?;
+    requester.send(reply(&reply_to, Some("forged")))?;
+    handler.send(reply(&reply_to, Some(&corr_id)))?;
+    // Each is passed on to the handler as an ordinary message
+    for _ in 0..3 {
+        assert_eq!(next_json(&mut handler).await?["subject"], reply_to.as_str());
+    }
+    assert!(!request.is_finished());
+
+    requester.send(reply(&reply_to, Some(&corr_id)))?;
+    let response = request.await??;
+    assert_eq!(response.subject, reply_to);
+    assert_eq!(response.body, Bytes::from("pong"));
+
+    provider.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,84 @@
+use anyhow::Result;
+use bytes::Bytes;
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::testing::{server_url, start_server};
+use wasmcloud_provider_messaging_websocket::{BrokerMessage, WebSocketMessagingProvider};
+
+async fn start_retaining_server() -> Result<WebSocketMessagingProvider> {
+    start_server(&[
+        ("RETAIN_SUBJECTS", "metrics.>"),
+        ("RETAIN_CLEAR_ON_EMPTY", "true"),
+    ])
+    .await
+}
+
+fn metric(subject: &str, body: &'static str) -> BrokerMessage {
+    BrokerMessage {
+        subject: subject.to_string(),
+        body: Bytes::from(body),
+        reply_to: None,
+        headers: HashMap::new(),
+    }
+}
+
+/// Test that a client connecting after a publish receives the retained value first
+#[tokio::test]
+async fn test_retained_message_delivered_on_connect() -> Result<()> {
+    let provider = start_retaining_server().await?;
+    let url = server_url(&provider).await?;
+
+    provider
+        .broadcast_to_clients(metric("metrics.cpu", "42"))
+        .await?;
+    provider
+        .broadcast_to_clients(metric("events.login", "not retained"))
+        .await?;
+
+    let (mut ws, _) = tokio_tungstenite::connect_async(url.as_str()).await?;
+    let frame = timeout(Duration::from_secs(2), ws.next())
+        .await?
+        .expect("retained frame")?;
+
+    let text = match frame {
+        Message::Text(text) => text,
+        other => panic!("unexpected frame: {:?}", other),
+    };
+    let json: serde_json::Value = serde_json::from_str(&text)?;
+    assert_eq!(json["subject"], "metrics.cpu");
+    assert_eq!(json["headers"]["retained"], "true");
+
+    // Nothing else was retained
+    assert!(timeout(Duration::from_millis(200), ws.next())
+        .await
+        .is_err());
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that publishing an empty body clears the retained value
+#[tokio::test]
+async fn test_retained_message_cleared_by_empty_body() -> Result<()> {
+    let provider = start_retaining_server().await?;
+    let url = server_url(&provider).await?;
+
+    provider
+        .broadcast_to_clients(metric("metrics.cpu", "42"))
+        .await?;
+    provider
+        .broadcast_to_clients(metric("metrics.cpu", ""))
+        .await?;
+
+    let (mut ws, _) = tokio_tungstenite::connect_async(url.as_str()).await?;
+    assert!(timeout(Duration::from_millis(200), ws.next())
+        .await
+        .is_err());
+
+    provider.shutdown().await?;
+    Ok(())
+}
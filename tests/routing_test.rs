@@ -0,0 +1,145 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::mock::{MockRemote, MockTransport};
+use wasmcloud_provider_messaging_websocket::testing::accept;
+use wasmcloud_provider_messaging_websocket::WebSocketMessagingProvider;
+
+/// Subject of the next frame forwarded to a handler, or `None` if nothing arrives
+async fn forwarded_subject(remote: &mut MockRemote) -> Option<String> {
+    let frame = timeout(Duration::from_millis(200), remote.recv())
+        .await
+        .ok()??;
+    let Message::Text(text) = frame else {
+        panic!("expected a text frame, got {:?}", frame);
+    };
+    let json: serde_json::Value = serde_json::from_str(&text).ok()?;
+    json["subject"].as_str().map(str::to_string)
+}
+
+/// Link a high-priority `orders.>` handler and a low-priority `orders.*` handler
+async fn link_handlers(
+    delivery: &str,
+) -> Result<(WebSocketMessagingProvider, MockRemote, MockRemote)> {
+    let mut config = HashMap::new();
+    config.insert("URI".to_string(), "ws://mock.invalid/ws".to_string());
+    config.insert("ROUTE_DELIVERY".to_string(), delivery.to_string());
+
+    let (transport, remotes) = MockTransport::new();
+    let provider = WebSocketMessagingProvider::from_config(config)?.with_transport(transport);
+
+    let mut high = HashMap::new();
+    high.insert("SUBSCRIPTIONS".to_string(), "orders.>".to_string());
+    high.insert("ROUTE_PRIORITY".to_string(), "10".to_string());
+    provider.receive_link_config_as_source("high", high).await?;
+    let high_remote = accept(&remotes).await;
+
+    let mut low = HashMap::new();
+    low.insert("SUBSCRIPTIONS".to_string(), "orders.*".to_string());
+    low.insert("ROUTE_PRIORITY".to_string(), "1".to_string());
+    provider.receive_link_config_as_source("low", low).await?;
+    let low_remote = accept(&remotes).await;
+
+    Ok((provider, high_remote, low_remote))
+}
+
+/// Test that `first` delivers only to the highest-priority matching handler
+#[tokio::test]
+async fn test_first_delivers_to_highest_priority_handler() -> Result<()> {
+    let (provider, mut high, mut low) = link_handlers("first").await?;
+
+    high.send(Message::Text(
+        r#"{"subject":"orders.new","body":"1"}"#.to_string(),
+    ))?;
+    assert_eq!(
+        forwarded_subject(&mut high).await.as_deref(),
+        Some("orders.new")
+    );
+    assert_eq!(forwarded_subject(&mut low).await, None);
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that `all` fans out to every matching handler and skips non-matching ones
+#[tokio::test]
+async fn test_all_reaches_every_matching_handler() -> Result<()> {
+    let (provider, mut high, mut low) = link_handlers("all").await?;
+
+    low.send(Message::Text(
+        r#"{"subject":"orders.new","body":"1"}"#.to_string(),
+    ))?;
+    assert_eq!(
+        forwarded_subject(&mut high).await.as_deref(),
+        Some("orders.new")
+    );
+    assert_eq!(
+        forwarded_subject(&mut low).await.as_deref(),
+        Some("orders.new")
+    );
+
+    // Only the wide subscription matches a deeper subject
+    low.send(Message::Text(
+        r#"{"subject":"orders.new.eu","body":"2"}"#.to_string(),
+    ))?;
+    assert_eq!(
+        forwarded_subject(&mut high).await.as_deref(),
+        Some("orders.new.eu")
+    );
+    assert_eq!(forwarded_subject(&mut low).await, None);
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a handler whose queue is full is skipped without holding up the others
+#[tokio::test]
+async fn test_saturated_handler_does_not_block_others() -> Result<()> {
+    let mut config = HashMap::new();
+    config.insert("URI".to_string(), "ws://mock.invalid/ws".to_string());
+
+    let (transport, remotes) = MockTransport::new();
+    let provider =
+        WebSocketMessagingProvider::from_config(config)?.with_transport(transport.clone());
+
+    // The stalled handler's remote never reads, so its queue fills up
+    transport.set_write_capacity(Some(0));
+    let mut stalled = HashMap::new();
+    stalled.insert("OUTBOUND_QUEUE_CAPACITY".to_string(), "2".to_string());
+    provider
+        .receive_link_config_as_source("stalled", stalled)
+        .await?;
+    let _stalled_remote = accept(&remotes).await;
+
+    transport.set_write_capacity(None);
+    provider
+        .receive_link_config_as_source("b", HashMap::new())
+        .await?;
+    let mut b = accept(&remotes).await;
+    provider
+        .receive_link_config_as_source("c", HashMap::new())
+        .await?;
+    let mut c = accept(&remotes).await;
+
+    const MESSAGES: usize = 20;
+    for i in 0..MESSAGES {
+        b.send(Message::Text(format!(
+            r#"{{"subject":"orders.{i}","body":"x"}}"#
+        )))?;
+    }
+
+    for i in 0..MESSAGES {
+        let expected = format!("orders.{i}");
+        assert_eq!(forwarded_subject(&mut b).await, Some(expected.clone()));
+        assert_eq!(forwarded_subject(&mut c).await, Some(expected));
+    }
+    let dropped = provider.handler_dropped_count("stalled").await.unwrap();
+    assert!(dropped > 0, "stalled handler should have dropped messages");
+    assert_eq!(provider.handler_dropped_count("b").await, Some(0));
+
+    provider.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,122 @@
+use anyhow::Result;
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use futures::SinkExt;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::{sleep, timeout};
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::mock::MockTransport;
+use wasmcloud_provider_messaging_websocket::testing::{server_url, start_server};
+use wasmcloud_provider_messaging_websocket::{
+    BrokerMessage, MessageSink, WebSocketMessagingProvider,
+};
+
+const STALL: Duration = Duration::from_millis(200);
+
+/// Sink that never finishes accepting a message
+struct StuckSink;
+
+impl MessageSink for StuckSink {
+    fn deliver(&self, _session_id: String, _msg: BrokerMessage) -> BoxFuture<'_, Result<()>> {
+        Box::pin(futures::future::pending())
+    }
+}
+
+/// Test that a client connection stuck writing reports the age of its oldest message
+#[tokio::test]
+async fn test_stalled_client_dispatcher_reported() -> Result<()> {
+    let mut config = HashMap::new();
+    config.insert("URI".to_string(), "ws://mock.invalid/ws".to_string());
+    let (transport, remotes) = MockTransport::new();
+    // The socket takes one frame, then stops accepting writes
+    transport.set_write_capacity(Some(0));
+    let provider = WebSocketMessagingProvider::from_config(config)?.with_transport(transport);
+    provider
+        .receive_link_config_as_target("publisher", HashMap::new())
+        .await?;
+    let mut remote = remotes.accept().await.expect("connection");
+
+    let health = provider.runtime_health();
+    assert_eq!(health.tasks.get("client-connection"), Some(&1));
+    assert_eq!(
+        health.oldest_message_age.get("component:publisher"),
+        Some(&None)
+    );
+
+    for n in 0..3 {
+        let msg = BrokerMessage {
+            subject: format!("orders.{}", n),
+            body: Bytes::from("x"),
+            reply_to: None,
+            headers: HashMap::new(),
+        };
+        provider.publish("publisher", msg).await?;
+    }
+    sleep(STALL).await;
+
+    let age = provider.runtime_health().oldest_message_age["component:publisher"];
+    assert!(age >= Some(STALL - Duration::from_millis(50)), "{:?}", age);
+
+    // Reading the frames unblocks the writer
+    for _ in 0..3 {
+        timeout(Duration::from_secs(2), remote.recv())
+            .await?
+            .expect("frame");
+    }
+    sleep(Duration::from_millis(50)).await;
+    assert_eq!(
+        provider.runtime_health().oldest_message_age["component:publisher"],
+        None
+    );
+
+    provider.shutdown().await?;
+    let health = provider.runtime_health();
+    assert!(health.tasks.is_empty(), "{:?}", health);
+    assert!(health.oldest_message_age.is_empty(), "{:?}", health);
+    Ok(())
+}
+
+/// Test that a server session stuck delivering to a slow sink reports its oldest message
+#[tokio::test]
+async fn test_stalled_server_dispatcher_reported() -> Result<()> {
+    let provider = start_server(&[]).await?;
+    provider.set_message_sink(StuckSink).await?;
+    let url = server_url(&provider).await?;
+
+    let mut clients = Vec::new();
+    for _ in 0..2 {
+        let (ws, _) = tokio_tungstenite::connect_async(url.as_str()).await?;
+        clients.push(ws);
+    }
+    sleep(Duration::from_millis(50)).await;
+    let health = provider.runtime_health();
+    assert_eq!(health.tasks.get("server"), Some(&1));
+    assert_eq!(health.tasks.get("server-send"), Some(&2));
+    assert_eq!(health.tasks.get("server-recv"), Some(&2));
+
+    clients[0]
+        .send(Message::Text(r#"{"subject":"a","body":"x"}"#.to_string()))
+        .await?;
+    sleep(STALL).await;
+
+    let health = provider.runtime_health();
+    let ages: Vec<Option<Duration>> = health
+        .oldest_message_age
+        .iter()
+        .filter(|(name, _)| name.starts_with("session:"))
+        .map(|(_, age)| *age)
+        .collect();
+    assert_eq!(ages.len(), 2, "{:?}", health);
+    assert_eq!(ages.iter().filter(|age| age.is_none()).count(), 1);
+    let stalled = ages.into_iter().flatten().next().unwrap();
+    assert!(
+        stalled >= STALL - Duration::from_millis(50),
+        "{:?}",
+        stalled
+    );
+
+    provider.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,143 @@
+use anyhow::Result;
+use bytes::Bytes;
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::time::{sleep, timeout};
+
+use wasmcloud_provider_messaging_websocket::testing::{server_url, start_server, WsClient};
+use wasmcloud_provider_messaging_websocket::{
+    BrokerMessage, SendOutcome, WebSocketMessagingProvider, DEADLINE_HEADER,
+};
+
+const CLIENTS: usize = 300;
+
+async fn next_json(ws: &mut WsClient) -> Result<serde_json::Value> {
+    let frame = timeout(Duration::from_secs(5), ws.next())
+        .await?
+        .expect("frame")?;
+    Ok(serde_json::from_str(frame.to_text()?)?)
+}
+
+/// Connect a client and return it with the session id from its welcome frame
+async fn connect(url: &str) -> Result<(WsClient, String)> {
+    let (mut ws, _) = tokio_tungstenite::connect_async(url).await?;
+    let welcome = next_json(&mut ws).await?;
+    let session_id = welcome["session_id"].as_str().unwrap().to_string();
+    Ok((ws, session_id))
+}
+
+fn notification(session_id: &str, body: &'static [u8]) -> BrokerMessage {
+    BrokerMessage {
+        subject: format!("notify.{}", session_id),
+        body: Bytes::from_static(body),
+        reply_to: None,
+        headers: HashMap::new(),
+    }
+}
+
+/// Test that a batch reaches every connected session with its own message, reports
+/// unknown sessions, full queues and rejected messages per item, and isn't slower than
+/// sending the same messages one call at a time
+#[tokio::test]
+async fn test_batch_to_many_sessions() -> Result<()> {
+    let provider = start_server(&[
+        ("WELCOME_FRAME", "true"),
+        ("MAX_SESSION_BUFFER_BYTES", "4096"),
+    ])
+    .await?;
+    let url = server_url(&provider).await?;
+
+    let mut clients = Vec::new();
+    for _ in 0..CLIENTS {
+        clients.push(connect(&url).await?);
+    }
+    for _ in 0..100 {
+        if provider.list_ws_clients().await?.len() == CLIENTS {
+            break;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(provider.list_ws_clients().await?.len(), CLIENTS);
+
+    // One call per session
+    let started = Instant::now();
+    for (_, session_id) in &clients {
+        provider
+            .send_to_ws_client(session_id, notification(session_id, b"one"))
+            .await?;
+    }
+    let per_call = started.elapsed();
+
+    // The same in one batch, with a few messages that can't be sent mixed in
+    let mut batch: Vec<_> = clients
+        .iter()
+        .map(|(_, session_id)| (session_id.clone(), notification(session_id, b"batch")))
+        .collect();
+    for n in 0..10 {
+        let unknown = format!("unknown-{}", n);
+        let message = notification(&unknown, b"lost");
+        batch.insert(n * 25, (unknown, message));
+    }
+    let full = clients[0].1.clone();
+    let mut oversized = notification(&full, b"");
+    oversized.body = Bytes::from(vec![b'x'; 8192]);
+    batch.push((full, oversized));
+    let mut expired = notification(&clients[1].1, b"late");
+    expired
+        .headers
+        .insert(DEADLINE_HEADER.to_string(), "1".to_string());
+    batch.push((clients[1].1.clone(), expired));
+    let expected_len = batch.len();
+
+    let started = Instant::now();
+    let report = provider.send_batch(batch).await?;
+    let batched = started.elapsed();
+    eprintln!(
+        "{} sessions: per call {:?}, batched {:?}",
+        CLIENTS, per_call, batched
+    );
+    assert!(batched <= per_call * 2, "{:?} vs {:?}", batched, per_call);
+
+    assert_eq!(report.outcomes.len(), expected_len);
+    assert_eq!(report.sent(), CLIENTS);
+    assert_eq!(report.failed(), 12);
+    for (n, (session_id, outcome)) in report.outcomes.iter().enumerate() {
+        if session_id.starts_with("unknown-") {
+            assert_eq!(outcome, &SendOutcome::UnknownSession);
+        } else if n == expected_len - 2 {
+            assert_eq!(outcome, &SendOutcome::QueueFull);
+        } else if n == expected_len - 1 {
+            assert!(
+                matches!(outcome, SendOutcome::Rejected(reason) if reason.contains("expired")),
+                "{:?}",
+                outcome
+            );
+        } else {
+            assert!(outcome.is_sent(), "{}: {:?}", session_id, outcome);
+        }
+    }
+    // Outcomes come back in the order the messages were given
+    assert_eq!(report.outcomes[0].0, "unknown-0");
+    assert_eq!(report.outcomes[1].0, clients[0].1);
+
+    // Every client got its own message from each pass
+    for (ws, session_id) in &mut clients {
+        for _ in 0..2 {
+            let json = next_json(ws).await?;
+            assert_eq!(json["subject"], format!("notify.{}", session_id));
+        }
+    }
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that batches need server mode
+#[tokio::test]
+async fn test_batch_requires_server_mode() -> Result<()> {
+    let provider = WebSocketMessagingProvider::new();
+    let batch = vec![("session".to_string(), notification("session", b"x"))];
+    assert!(provider.send_batch(batch).await.is_err());
+    Ok(())
+}
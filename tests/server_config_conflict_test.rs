@@ -0,0 +1,83 @@
+use anyhow::Result;
+use std::collections::HashMap;
+
+use wasmcloud_provider_messaging_websocket::mock::{MockRemotes, MockTransport};
+use wasmcloud_provider_messaging_websocket::{ServerConfigConflict, WebSocketMessagingProvider};
+
+fn server_provider(extra: &[(&str, &str)]) -> Result<(WebSocketMessagingProvider, MockRemotes)> {
+    let mut config = HashMap::new();
+    config.insert("MODE".to_string(), "server".to_string());
+    config.insert("URI".to_string(), "ws://mock.invalid/ws".to_string());
+    config.insert("RETAIN_TTL_SEC".to_string(), "60".to_string());
+    config.insert("PARSE_FAIL_THRESHOLD".to_string(), "5".to_string());
+    for (key, value) in extra {
+        config.insert(key.to_string(), value.to_string());
+    }
+
+    let (transport, remotes) = MockTransport::new();
+    let provider = WebSocketMessagingProvider::from_config(config)?.with_transport(transport);
+    Ok((provider, remotes))
+}
+
+fn link_config(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+    pairs
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+/// Test that a link overriding server-level keys is rejected with every conflict listed
+#[tokio::test]
+async fn test_conflicting_server_keys_rejected() -> Result<()> {
+    let (provider, _remotes) = server_provider(&[])?;
+    let config = link_config(&[
+        ("RETAIN_TTL_SEC", "5"),
+        ("PARSE_FAIL_THRESHOLD", "0"),
+        ("LABEL", "team-b"),
+    ]);
+
+    let err = provider
+        .receive_link_config_as_source("team-b", config)
+        .await
+        .unwrap_err();
+    let conflict = err
+        .downcast_ref::<ServerConfigConflict>()
+        .expect("typed conflict error");
+    let keys: Vec<&str> = conflict.conflicts.iter().map(|c| c.key.as_str()).collect();
+    assert_eq!(keys, vec!["RETAIN_TTL_SEC", "PARSE_FAIL_THRESHOLD"]);
+    assert_eq!(conflict.conflicts[0].server, "60");
+    assert_eq!(conflict.conflicts[0].link, "5");
+    assert!(provider.get_link_config("team-b").await.is_none());
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that links repeating the server's values or only setting client keys are accepted
+#[tokio::test]
+async fn test_matching_server_keys_accepted() -> Result<()> {
+    let (provider, _remotes) = server_provider(&[])?;
+
+    provider
+        .receive_link_config_as_source("team-a", link_config(&[("RETAIN_TTL_SEC", "60")]))
+        .await?;
+    provider
+        .receive_link_config_as_target("team-b", link_config(&[("LABEL", "team-b")]))
+        .await?;
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that ALLOW_SERVER_OVERRIDE at startup lets conflicting links through
+#[tokio::test]
+async fn test_conflicts_allowed_with_override() -> Result<()> {
+    let (provider, _remotes) = server_provider(&[("ALLOW_SERVER_OVERRIDE", "true")])?;
+
+    provider
+        .receive_link_config_as_source("team-b", link_config(&[("RETAIN_TTL_SEC", "5")]))
+        .await?;
+
+    provider.shutdown().await?;
+    Ok(())
+}
@@ -1,10 +1,12 @@
 use anyhow::Result;
 use bytes::Bytes;
+use futures::StreamExt;
 use std::collections::HashMap;
 use std::time::Duration;
 use tokio::time::sleep;
 use tracing_subscriber;
 
+use wasmcloud_provider_messaging_websocket::testing::{connect, start_server};
 use wasmcloud_provider_messaging_websocket::{BrokerMessage, WebSocketMessagingProvider};
 
 /// Test server mode initialization
@@ -88,17 +90,41 @@ async fn test_broadcast_server_mode() -> Result<()> {
         subject: "test.broadcast".to_string(),
         body: Bytes::from("broadcast message"),
         reply_to: None,
+        headers: HashMap::new(),
     };
 
-    // This should succeed even with no clients
-    let result = provider.broadcast_to_clients(msg).await;
-    assert!(result.is_ok());
+    // This should succeed even with no clients, reaching nobody
+    let recipients = provider.broadcast_to_clients(msg).await?;
+    assert_eq!(recipients, 0);
 
     provider.shutdown().await?;
 
     Ok(())
 }
 
+/// Test that a broadcast reaches a connected client and reports it as a recipient
+#[tokio::test]
+async fn test_broadcast_reports_recipients() -> Result<()> {
+    let provider = start_server(&[]).await?;
+    let (mut ws, _) = connect(&provider).await?;
+
+    let msg = BrokerMessage {
+        subject: "test.broadcast".to_string(),
+        body: Bytes::from("broadcast message"),
+        reply_to: None,
+        headers: HashMap::new(),
+    };
+    assert_eq!(provider.broadcast_to_clients(msg).await?, 1);
+
+    let frame = tokio::time::timeout(Duration::from_secs(1), ws.next())
+        .await?
+        .expect("broadcast frame")?;
+    assert!(frame.to_text()?.contains("test.broadcast"));
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
 /// Test listing WebSocket clients in server mode
 #[tokio::test]
 async fn test_list_ws_clients() -> Result<()> {
@@ -158,6 +184,7 @@ async fn test_reply_to_handling() -> Result<()> {
         subject: "test.request".to_string(),
         body: Bytes::from("request data"),
         reply_to: Some("session-123".to_string()),
+        headers: HashMap::new(),
     };
 
     assert_eq!(msg.reply_to, Some("session-123".to_string()));
@@ -167,6 +194,7 @@ async fn test_reply_to_handling() -> Result<()> {
         subject: "test.publish".to_string(),
         body: Bytes::from("publish data"),
         reply_to: None,
+        headers: HashMap::new(),
     };
 
     assert_eq!(msg2.reply_to, None);
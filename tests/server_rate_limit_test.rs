@@ -0,0 +1,137 @@
+use anyhow::Result;
+use futures::SinkExt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::testing::{connect, start_server, WsClient};
+use wasmcloud_provider_messaging_websocket::{BrokerMessage, WebSocketMessagingProvider};
+
+type Deliveries = Arc<Mutex<Vec<(String, Instant)>>>;
+
+/// Start a server-mode provider limited to `rate` messages per second under `policy`,
+/// recording the session and time of every message its sink receives
+async fn limited_server(
+    rate: u64,
+    policy: &str,
+) -> Result<(WebSocketMessagingProvider, Deliveries)> {
+    let provider = start_server(&[
+        ("SERVER_MAX_MESSAGES_PER_SEC", &rate.to_string()),
+        ("SERVER_RATE_POLICY", policy),
+    ])
+    .await?;
+    let deliveries = Deliveries::default();
+    let sink = Arc::clone(&deliveries);
+    provider
+        .set_message_sink(
+            move |session_id: String, _msg: BrokerMessage| -> Result<()> {
+                sink.lock().unwrap().push((session_id, Instant::now()));
+                Ok(())
+            },
+        )
+        .await?;
+    Ok((provider, deliveries))
+}
+
+/// Connect `n` clients and return them with their session ids
+async fn connect_clients(
+    provider: &WebSocketMessagingProvider,
+    n: usize,
+) -> Result<Vec<(WsClient, String)>> {
+    let mut clients = Vec::new();
+    for _ in 0..n {
+        clients.push(connect(provider).await?);
+    }
+    Ok(clients)
+}
+
+async fn send_burst(ws: &mut WsClient, count: usize) -> Result<()> {
+    for _ in 0..count {
+        let frame = serde_json::json!({ "subject": "load.test", "body": "" });
+        ws.send(Message::Text(frame.to_string())).await?;
+    }
+    Ok(())
+}
+
+fn count_of(deliveries: &[(String, Instant)], session_id: &str) -> usize {
+    deliveries
+        .iter()
+        .filter(|(session, _)| session == session_id)
+        .count()
+}
+
+/// Test that clients together exceeding the server rate are shed down to it, with each
+/// sender getting its share
+#[tokio::test]
+async fn test_shed_caps_aggregate_rate_fairly() -> Result<()> {
+    let (provider, deliveries) = limited_server(20, "shed").await?;
+    let mut clients = connect_clients(&provider, 2).await?;
+
+    // Each client offers 100 messages a second, ten times the server's share for it
+    let start = Instant::now();
+    for _ in 0..20 {
+        for (ws, _) in clients.iter_mut() {
+            send_burst(ws, 10).await?;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+    sleep(Duration::from_millis(200)).await;
+    let elapsed = start.elapsed();
+
+    let deliveries = deliveries.lock().unwrap().clone();
+    // At most one second's worth per started window
+    let windows = elapsed.as_secs() + 1;
+    assert!(
+        deliveries.len() as u64 <= 20 * windows,
+        "{} delivered",
+        deliveries.len()
+    );
+    assert!(deliveries.len() >= 20);
+    for (_, session_id) in &clients {
+        let share = count_of(&deliveries, session_id) as f64 / deliveries.len() as f64;
+        assert!(
+            share >= 0.4,
+            "session got {:.0}% of deliveries",
+            share * 100.0
+        );
+    }
+    assert_eq!(
+        provider.server_rate_limited_count() as usize,
+        400 - deliveries.len()
+    );
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that queued messages are all delivered at the server rate, alternating between
+/// the clients
+#[tokio::test]
+async fn test_queue_paces_aggregate_rate() -> Result<()> {
+    let (provider, deliveries) = limited_server(50, "queue").await?;
+    let mut clients = connect_clients(&provider, 2).await?;
+
+    let start = Instant::now();
+    for (ws, _) in clients.iter_mut() {
+        send_burst(ws, 25).await?;
+    }
+    for _ in 0..300 {
+        if deliveries.lock().unwrap().len() == 50 {
+            break;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    let deliveries = deliveries.lock().unwrap().clone();
+    assert_eq!(deliveries.len(), 50);
+    // 50 messages a second, one every 20 ms
+    assert!(start.elapsed() >= Duration::from_millis(900));
+    let first_half = &deliveries[..25];
+    for (_, session_id) in &clients {
+        assert!(count_of(first_half, session_id) >= 10);
+    }
+    assert!(provider.server_rate_limited_count() > 0);
+
+    provider.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,107 @@
+use anyhow::Result;
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use std::time::Duration;
+use tokio::time::sleep;
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::testing::{connect, start_server};
+
+/// Test that a request to a server client resolves with the reply it sends back
+#[tokio::test]
+async fn test_request_from_client_resolves_with_reply() -> Result<()> {
+    let provider = start_server(&[]).await?;
+    let (mut ws, session_id) = connect(&provider).await?;
+
+    // Answer on the reply subject, echoing the request's corr_id
+    let client = tokio::spawn(async move {
+        while let Some(Ok(Message::Text(text))) = ws.next().await {
+            let request: serde_json::Value = serde_json::from_str(&text).unwrap();
+            let Some(reply_to) = request["reply_to"].as_str() else {
+                continue;
+            };
+            let reply = serde_json::json!({
+                "subject": reply_to,
+                "body": "pong",
+                "corr_id": request["corr_id"],
+            });
+            ws.send(Message::Text(reply.to_string())).await.unwrap();
+        }
+    });
+
+    let reply = provider
+        .request_from_client(
+            &session_id,
+            "health.ping".to_string(),
+            Bytes::from("ping"),
+            Duration::from_secs(2),
+        )
+        .await?;
+    assert!(reply.subject.starts_with("_INBOX."));
+    assert_eq!(reply.body, Bytes::from("pong"));
+
+    provider.shutdown().await?;
+    client.abort();
+    Ok(())
+}
+
+/// Test that frames on the reply subject without the request's corr_id don't resolve it
+#[tokio::test]
+async fn test_reply_requires_matching_corr_id() -> Result<()> {
+    let provider = start_server(&[]).await?;
+    let (mut ws, session_id) = connect(&provider).await?;
+
+    // Publish on the inbox with no corr_id and a wrong one before the real reply
+    let client = tokio::spawn(async move {
+        while let Some(Ok(Message::Text(text))) = ws.next().await {
+            let request: serde_json::Value = serde_json::from_str(&text).unwrap();
+            let Some(reply_to) = request["reply_to"].as_str() else {
+                continue;
+            };
+            let frames = [
+                serde_json::json!({ "subject": reply_to, "body": "absent" }),
+                serde_json::json!({ "subject": reply_to, "body": "mismatched", "corr_id": "other" }),
+                serde_json::json!({ "subject": reply_to, "body": "pong", "corr_id": request["corr_id"] }),
+            ];
+            for frame in frames {
+                ws.send(Message::Text(frame.to_string())).await.unwrap();
+                sleep(Duration::from_millis(20)).await;
+            }
+        }
+    });
+
+    let reply = provider
+        .request_from_client(
+            &session_id,
+            "health.ping".to_string(),
+            Bytes::from("ping"),
+            Duration::from_secs(2),
+        )
+        .await?;
+    assert_eq!(reply.body, Bytes::from("pong"));
+
+    provider.shutdown().await?;
+    client.abort();
+    Ok(())
+}
+
+/// Test that a request times out when the client never replies
+#[tokio::test]
+async fn test_request_from_client_times_out() -> Result<()> {
+    let provider = start_server(&[]).await?;
+    let (_ws, session_id) = connect(&provider).await?;
+
+    let err = provider
+        .request_from_client(
+            &session_id,
+            "health.ping".to_string(),
+            Bytes::from("ping"),
+            Duration::from_millis(100),
+        )
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("timed out"), "{}", err);
+
+    provider.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,105 @@
+use anyhow::Result;
+use futures::StreamExt;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::time::{sleep, timeout};
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::testing::start_server;
+use wasmcloud_provider_messaging_websocket::{ProviderEvent, WebSocketMessagingProvider};
+
+/// Wait for the first event `pick` accepts
+async fn next_event<T>(
+    events: &mut broadcast::Receiver<ProviderEvent>,
+    pick: impl Fn(ProviderEvent) -> Option<T>,
+) -> Result<T> {
+    timeout(Duration::from_secs(2), async {
+        loop {
+            if let Some(picked) = pick(events.recv().await?) {
+                return Ok::<T, anyhow::Error>(picked);
+            }
+        }
+    })
+    .await?
+}
+
+async fn wait_for_clients(provider: &WebSocketMessagingProvider, count: usize) -> Result<()> {
+    for _ in 0..100 {
+        if provider.list_ws_clients().await?.len() == count {
+            return Ok(());
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    anyhow::bail!("expected {} clients", count)
+}
+
+/// Test that a server task dying is reported, clears the address and drops its clients
+#[tokio::test]
+async fn test_dead_server_task_reported() -> Result<()> {
+    let provider = start_server(&[]).await?;
+    let addr = provider.get_server_addr().await.unwrap();
+    let (mut client, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws", addr)).await?;
+    wait_for_clients(&provider, 1).await?;
+    let mut events = provider.subscribe_events();
+
+    provider.abort_server_task();
+    let error = next_event(&mut events, |event| match event {
+        ProviderEvent::ServerStopped { error } => Some(error),
+        _ => None,
+    })
+    .await?;
+    assert_eq!(error, "server task was aborted");
+    assert!(!provider.is_server_running().await);
+    assert_eq!(provider.get_server_addr().await, None);
+    assert!(provider.list_ws_clients().await?.is_empty());
+
+    // The client is told, and nothing new connects
+    let close = loop {
+        match timeout(Duration::from_secs(2), client.next()).await? {
+            Some(Ok(Message::Close(close))) => break close,
+            Some(Ok(_)) => continue,
+            other => anyhow::bail!("expected a close frame, got {:?}", other),
+        }
+    };
+    assert_eq!(close.map(|close| u16::from(close.code)), Some(1001));
+    assert!(
+        tokio_tungstenite::connect_async(format!("ws://{}/ws", addr))
+            .await
+            .is_err()
+    );
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that with SERVER_RESTART the server is bound again on the same address
+#[tokio::test]
+async fn test_server_rebinds_with_restart() -> Result<()> {
+    let provider = start_server(&[
+        ("SERVER_RESTART", "true"),
+        ("SERVER_RESTART_DELAY_MS", "50"),
+    ])
+    .await?;
+    let addr = provider.get_server_addr().await.unwrap();
+    let mut events = provider.subscribe_events();
+
+    provider.abort_server_task();
+    next_event(&mut events, |event| {
+        matches!(event, ProviderEvent::ServerStopped { .. }).then_some(())
+    })
+    .await?;
+    let restarted = next_event(&mut events, |event| match event {
+        ProviderEvent::ServerRestarted { addr } => Some(addr),
+        _ => None,
+    })
+    .await?;
+    assert_eq!(restarted, addr);
+    assert!(provider.is_server_running().await);
+    assert_eq!(provider.get_server_addr().await, Some(addr));
+
+    let (_client, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws", addr)).await?;
+    wait_for_clients(&provider, 1).await?;
+
+    provider.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,142 @@
+use anyhow::Result;
+use bytes::Bytes;
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::testing::{connect, start_server, WsClient};
+use wasmcloud_provider_messaging_websocket::{
+    BrokerMessage, SessionMemory, WebSocketMessagingProvider,
+};
+
+const CAP: usize = 64 * 1024;
+const FRAME: usize = 16 * 1024;
+
+async fn capped_server(policy: &str) -> Result<WebSocketMessagingProvider> {
+    start_server(&[
+        ("MAX_SESSION_BUFFER_BYTES", &CAP.to_string()),
+        ("SESSION_BUFFER_POLICY", policy),
+    ])
+    .await
+}
+
+fn message(subject: &str, len: usize) -> BrokerMessage {
+    BrokerMessage {
+        subject: subject.to_string(),
+        body: Bytes::from(vec![b'x'; len]),
+        reply_to: None,
+        headers: HashMap::new(),
+    }
+}
+
+fn usage_of(provider: &WebSocketMessagingProvider, session_id: &str) -> SessionMemory {
+    provider
+        .top_sessions_by_memory(usize::MAX)
+        .into_iter()
+        .find(|usage| usage.session_id == session_id)
+        .expect("session in the ledger")
+}
+
+/// Read `count` text frames, failing on anything else
+async fn read_texts(ws: &mut WsClient, count: usize) -> Result<()> {
+    for _ in 0..count {
+        let frame = timeout(Duration::from_secs(2), ws.next())
+            .await?
+            .expect("text frame")?;
+        assert!(matches!(frame, Message::Text(_)), "got {:?}", frame);
+    }
+    Ok(())
+}
+
+/// Test that a session that never reads is held to the cap by dropping its oldest frames,
+/// while a session that keeps up next to it loses nothing
+#[tokio::test]
+async fn test_greedy_session_drops_oldest() -> Result<()> {
+    let server = capped_server("drop_oldest").await?;
+    // Never read, so frames pile up once the socket buffers are full
+    let (_greedy, greedy_id) = connect(&server).await?;
+    let (mut normal, normal_id) = connect(&server).await?;
+
+    let mut dropped = false;
+    for _ in 0..4000 {
+        server
+            .send_to_ws_client(&greedy_id, message("bulk", FRAME))
+            .await?;
+        let greedy = usage_of(&server, &greedy_id);
+        assert!(greedy.buffered_bytes <= CAP, "{:?}", greedy);
+        if greedy.dropped_frames > 0 {
+            dropped = true;
+            break;
+        }
+        tokio::task::yield_now().await;
+    }
+    assert!(dropped, "the cap never kicked in");
+
+    // The session at its cap tops the list
+    let top = server.top_sessions_by_memory(1);
+    assert_eq!(top[0].session_id, greedy_id);
+    assert!(top[0].queued_bytes > 0);
+
+    for _ in 0..10 {
+        server
+            .send_to_ws_client(&normal_id, message("news", FRAME))
+            .await?;
+    }
+    read_texts(&mut normal, 10).await?;
+    let normal_usage = usage_of(&server, &normal_id);
+    assert_eq!(normal_usage.dropped_frames, 0);
+
+    // Every queued byte is charged to exactly one session
+    let report = server.consistency_report().await;
+    assert!(report.buffer_accounting.is_empty(), "{:?}", report);
+
+    server.shutdown().await?;
+    Ok(())
+}
+
+/// Test that under the disconnect policy only the greedy session is closed, with 4006
+#[tokio::test]
+async fn test_greedy_session_disconnected() -> Result<()> {
+    let server = capped_server("disconnect").await?;
+    let (mut greedy, greedy_id) = connect(&server).await?;
+    let (mut normal, normal_id) = connect(&server).await?;
+
+    let mut refused = false;
+    for _ in 0..4000 {
+        if server
+            .send_to_ws_client(&greedy_id, message("bulk", FRAME))
+            .await
+            .is_err()
+        {
+            refused = true;
+            break;
+        }
+        tokio::task::yield_now().await;
+    }
+    assert!(refused, "the cap never kicked in");
+
+    // Frames already on the wire come first, then the close frame
+    let code = loop {
+        let frame = timeout(Duration::from_secs(2), greedy.next())
+            .await?
+            .expect("close frame")?;
+        if let Message::Close(Some(close)) = frame {
+            break u16::from(close.code);
+        }
+    };
+    assert_eq!(code, 4006);
+
+    server
+        .send_to_ws_client(&normal_id, message("news", 16))
+        .await?;
+    read_texts(&mut normal, 1).await?;
+    assert!(server
+        .top_sessions_by_memory(usize::MAX)
+        .iter()
+        .all(|usage| usage.session_id != greedy_id || usage.buffered_bytes == 0));
+
+    server.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,147 @@
+use anyhow::Result;
+use futures::SinkExt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::sleep;
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::testing::{server_url, start_server};
+use wasmcloud_provider_messaging_websocket::{
+    BrokerMessage, WebSocketMessagingProvider, SESSION_CONNECTED_SUBJECT,
+    SESSION_DISCONNECTED_SUBJECT,
+};
+
+const CLIENTS: usize = 40;
+const BURST: usize = 25;
+
+type Observed = Arc<Mutex<Vec<(String, String)>>>;
+
+async fn observed_server(session_events: bool) -> Result<(WebSocketMessagingProvider, Observed)> {
+    let provider = start_server(&[("SESSION_EVENTS", &session_events.to_string())]).await?;
+    let observed = Observed::default();
+    let sink = Arc::clone(&observed);
+    provider
+        .set_message_sink(move |session_id: String, msg: BrokerMessage| {
+            sink.lock().unwrap().push((session_id, msg.subject));
+            Ok(())
+        })
+        .await?;
+    Ok((provider, observed))
+}
+
+/// Connect, send a burst and go away, closing cleanly or just dropping the socket
+async fn burst_client(url: String, n: usize) -> Result<()> {
+    let (mut ws, _) = tokio_tungstenite::connect_async(url).await?;
+    for i in 0..BURST {
+        // Clients can't fake the end of their own session
+        let subject = if i == BURST / 2 {
+            SESSION_DISCONNECTED_SUBJECT.to_string()
+        } else {
+            format!("data.{}", i)
+        };
+        ws.feed(Message::Text(format!(
+            r#"{{"subject":"{}","body":"eA=="}}"#,
+            subject
+        )))
+        .await?;
+    }
+    ws.flush().await?;
+    if n % 2 == 0 {
+        ws.close(None).await?;
+    }
+    Ok(())
+}
+
+/// Test that under rapid connects and disconnects every session's messages reach the
+/// sink between its connected and disconnected events
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_data_stays_within_session_window() -> Result<()> {
+    let (provider, observed) = observed_server(true).await?;
+    let url = server_url(&provider).await?;
+
+    for round in 0..3 {
+        let clients: Vec<_> = (0..CLIENTS)
+            .map(|n| tokio::spawn(burst_client(url.clone(), round * CLIENTS + n)))
+            .collect();
+        for client in clients {
+            client.await??;
+        }
+    }
+
+    let sessions = 3 * CLIENTS;
+    for _ in 0..200 {
+        let disconnected = observed
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, subject)| subject == SESSION_DISCONNECTED_SUBJECT)
+            .count();
+        if disconnected == sessions {
+            break;
+        }
+        sleep(Duration::from_millis(25)).await;
+    }
+
+    let mut per_session: HashMap<String, Vec<String>> = HashMap::new();
+    for (session_id, subject) in observed.lock().unwrap().iter() {
+        per_session
+            .entry(session_id.clone())
+            .or_default()
+            .push(subject.clone());
+    }
+    assert_eq!(per_session.len(), sessions);
+    for (session_id, subjects) in &per_session {
+        assert_eq!(
+            subjects.first().unwrap(),
+            SESSION_CONNECTED_SUBJECT,
+            "{}",
+            session_id
+        );
+        assert_eq!(
+            subjects.last().unwrap(),
+            SESSION_DISCONNECTED_SUBJECT,
+            "{}",
+            session_id
+        );
+        let middle = &subjects[1..subjects.len() - 1];
+        assert!(
+            middle.iter().all(|subject| subject.starts_with("data.")),
+            "{}: {:?}",
+            session_id,
+            subjects
+        );
+        // Data arrives in the order it was sent
+        let sent: Vec<_> = (0..BURST)
+            .filter(|i| *i != BURST / 2)
+            .map(|i| format!("data.{}", i))
+            .collect();
+        assert!(sent.starts_with(middle), "{}: {:?}", session_id, middle);
+    }
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that without `SESSION_EVENTS` the sink only sees the clients' own messages
+#[tokio::test]
+async fn test_no_events_by_default() -> Result<()> {
+    let (provider, observed) = observed_server(false).await?;
+    let url = server_url(&provider).await?;
+
+    burst_client(url, 0).await?;
+    for _ in 0..100 {
+        if observed.lock().unwrap().len() == BURST {
+            break;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    sleep(Duration::from_millis(100)).await;
+    let observed = observed.lock().unwrap().clone();
+    assert_eq!(observed.len(), BURST);
+    assert!(observed
+        .iter()
+        .all(|(_, subject)| subject != SESSION_CONNECTED_SUBJECT));
+
+    provider.shutdown().await?;
+    Ok(())
+}
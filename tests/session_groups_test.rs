@@ -0,0 +1,192 @@
+use anyhow::Result;
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::{sleep, timeout};
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::testing::{connect, start_server, WsClient};
+use wasmcloud_provider_messaging_websocket::{BrokerMessage, GroupLimit, GroupLimitExceeded};
+
+/// Send a control frame and return the server's JSON reply
+async fn control(ws: &mut WsClient, frame: &str) -> Result<serde_json::Value> {
+    ws.send(Message::Text(frame.to_string())).await?;
+    let reply = timeout(Duration::from_secs(2), ws.next())
+        .await?
+        .expect("control reply")?;
+    Ok(serde_json::from_str(reply.to_text()?)?)
+}
+
+fn message(subject: &str) -> BrokerMessage {
+    BrokerMessage {
+        subject: subject.to_string(),
+        body: Bytes::from("hello room"),
+        reply_to: None,
+        headers: HashMap::new(),
+    }
+}
+
+/// Test joining and leaving through control frames, and membership in session metadata
+#[tokio::test]
+async fn test_join_and_leave_via_control_frames() -> Result<()> {
+    let provider = start_server(&[]).await?;
+    let (mut ws, session_id) = connect(&provider).await?;
+
+    let reply = control(&mut ws, r#"{"op":"join","group":"room-7"}"#).await?;
+    assert_eq!(reply["op"], "joined");
+    assert_eq!(
+        provider.list_ws_group_members("room-7").await?,
+        vec![session_id.clone()]
+    );
+    let info = provider.ws_client_info(&session_id).await?.unwrap();
+    assert_eq!(info.metadata["groups"], "room-7");
+
+    let reply = control(&mut ws, r#"{"op":"leave","group":"room-7"}"#).await?;
+    assert_eq!(reply["op"], "left");
+    assert!(provider.list_ws_group_members("room-7").await?.is_empty());
+    let info = provider.ws_client_info(&session_id).await?.unwrap();
+    assert!(!info.metadata.contains_key("groups"));
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a group send reaches only the members and reports how many
+#[tokio::test]
+async fn test_send_to_group_counts_members() -> Result<()> {
+    let provider = start_server(&[]).await?;
+    let (mut member_a, session_a) = connect(&provider).await?;
+    let (mut member_b, session_b) = connect(&provider).await?;
+    let (mut outsider, _) = connect(&provider).await?;
+
+    provider
+        .add_ws_client_to_group(&session_a, "room-7")
+        .await?;
+    provider
+        .add_ws_client_to_group(&session_b, "room-7")
+        .await?;
+
+    let sent = provider
+        .send_to_ws_group("room-7", message("chat.room-7"))
+        .await?;
+    assert_eq!(sent, 2);
+    for ws in [&mut member_a, &mut member_b] {
+        let frame = timeout(Duration::from_secs(2), ws.next())
+            .await?
+            .expect("group message")?;
+        assert!(frame.to_text()?.contains("chat.room-7"));
+    }
+    assert!(
+        timeout(Duration::from_millis(200), outsider.next())
+            .await
+            .is_err(),
+        "non-member received the group message"
+    );
+
+    assert_eq!(provider.send_to_ws_group("empty", message("x")).await?, 0);
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a disconnected client is removed from its groups
+#[tokio::test]
+async fn test_disconnect_cleans_up_membership() -> Result<()> {
+    let provider = start_server(&[]).await?;
+    let (mut ws, session_id) = connect(&provider).await?;
+    control(&mut ws, r#"{"op":"join","group":"room-7"}"#).await?;
+    control(&mut ws, r#"{"op":"join","group":"lobby"}"#).await?;
+
+    ws.close(None).await?;
+    for _ in 0..50 {
+        if provider.list_ws_clients().await?.is_empty() {
+            break;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    assert!(provider.list_ws_group_members("room-7").await?.is_empty());
+    assert!(provider.list_ws_group_members("lobby").await?.is_empty());
+    assert!(provider.ws_client_info(&session_id).await?.is_none());
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that MAX_GROUPS_PER_SESSION and MAX_GROUP_SIZE are enforced with a typed error
+#[tokio::test]
+async fn test_group_limits() -> Result<()> {
+    let provider =
+        start_server(&[("MAX_GROUPS_PER_SESSION", "1"), ("MAX_GROUP_SIZE", "1")]).await?;
+    let (mut ws, session_a) = connect(&provider).await?;
+    let (_other, session_b) = connect(&provider).await?;
+
+    provider
+        .add_ws_client_to_group(&session_a, "room-1")
+        .await?;
+
+    let err = provider
+        .add_ws_client_to_group(&session_a, "room-2")
+        .await
+        .unwrap_err();
+    let exceeded = err.downcast_ref::<GroupLimitExceeded>().unwrap();
+    assert_eq!(exceeded.limit, GroupLimit::GroupsPerSession);
+
+    let err = provider
+        .add_ws_client_to_group(&session_b, "room-1")
+        .await
+        .unwrap_err();
+    let exceeded = err.downcast_ref::<GroupLimitExceeded>().unwrap();
+    assert_eq!(exceeded.limit, GroupLimit::GroupSize);
+    assert_eq!(exceeded.max, 1);
+
+    // Clients are told when a join is refused
+    let reply = control(&mut ws, r#"{"op":"join","group":"room-2"}"#).await?;
+    assert_eq!(reply["op"], "join_failed");
+    assert_eq!(
+        provider.list_ws_group_members("room-1").await?,
+        vec![session_a]
+    );
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that MAX_GROUPS blocks creating a group, and an emptied group frees its slot
+#[tokio::test]
+async fn test_group_count_limit() -> Result<()> {
+    let provider = start_server(&[("MAX_GROUPS", "1")]).await?;
+    let (_a, session_a) = connect(&provider).await?;
+    let (mut ws_b, session_b) = connect(&provider).await?;
+
+    provider
+        .add_ws_client_to_group(&session_a, "room-1")
+        .await?;
+    let err = provider
+        .add_ws_client_to_group(&session_b, "room-2")
+        .await
+        .unwrap_err();
+    let exceeded = err.downcast_ref::<GroupLimitExceeded>().unwrap();
+    assert_eq!(exceeded.limit, GroupLimit::GroupCount);
+    assert_eq!(exceeded.max, 1);
+    let reply = control(&mut ws_b, r#"{"op":"join","group":"room-2"}"#).await?;
+    assert_eq!(reply["op"], "join_failed");
+
+    // Joining the existing group is still allowed
+    provider
+        .add_ws_client_to_group(&session_b, "room-1")
+        .await?;
+
+    for session_id in [&session_a, &session_b] {
+        assert!(
+            provider
+                .remove_ws_client_from_group(session_id, "room-1")
+                .await?
+        );
+    }
+    let reply = control(&mut ws_b, r#"{"op":"join","group":"room-2"}"#).await?;
+    assert_eq!(reply["op"], "joined");
+
+    provider.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,118 @@
+use anyhow::Result;
+use std::collections::HashMap;
+
+use wasmcloud_provider_messaging_websocket::testing::{connect, connect_with_query, start_server};
+use wasmcloud_provider_messaging_websocket::{
+    MetadataRejected, MetadataRejection, WebSocketMessagingProvider,
+};
+
+/// Server allowing four metadata entries with keys up to 8 and values up to 16 bytes
+async fn metadata_server() -> Result<WebSocketMessagingProvider> {
+    start_server(&[
+        ("MAX_METADATA_ENTRIES", "4"),
+        ("MAX_METADATA_KEY_LEN", "8"),
+        ("MAX_METADATA_VALUE_LEN", "16"),
+    ])
+    .await
+}
+
+async fn metadata(
+    provider: &WebSocketMessagingProvider,
+    session_id: &str,
+) -> Result<HashMap<String, String>> {
+    Ok(provider
+        .ws_client_info(session_id)
+        .await?
+        .expect("client connected")
+        .metadata)
+}
+
+fn rejection(error: anyhow::Error) -> MetadataRejection {
+    error
+        .downcast_ref::<MetadataRejected>()
+        .expect("MetadataRejected")
+        .reason
+}
+
+/// Test that an upgrade-derived entry over the limits is skipped while the connection
+/// still succeeds
+#[tokio::test]
+async fn test_upgrade_metadata_skipped_over_limit() -> Result<()> {
+    let provider = metadata_server().await?;
+
+    let (_short, short_id) = connect_with_query(&provider, "?tenant=acme").await?;
+    assert_eq!(
+        metadata(&provider, &short_id)
+            .await?
+            .get("tenant")
+            .map(String::as_str),
+        Some("acme")
+    );
+
+    let long_tenant = "t".repeat(17);
+    let (_long, long_id) =
+        connect_with_query(&provider, &format!("?tenant={}", long_tenant)).await?;
+    let long = metadata(&provider, &long_id).await?;
+    assert_eq!(long.get("tenant"), None);
+    assert!(long.contains_key("connection_id"));
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that component writes are held to the limits and kept out of the reserved prefix
+#[tokio::test]
+async fn test_component_metadata_limits() -> Result<()> {
+    let provider = metadata_server().await?;
+    let (_ws, session_id) = connect(&provider).await?;
+    // connection_id is already there
+    assert_eq!(metadata(&provider, &session_id).await?.len(), 1);
+
+    let error = provider
+        .set_session_metadata(&session_id, "_wcp_role", "admin")
+        .await
+        .expect_err("reserved prefix");
+    assert_eq!(rejection(error), MetadataRejection::ReservedKey);
+
+    let error = provider
+        .set_session_metadata(&session_id, "much_too_long", "x")
+        .await
+        .expect_err("key too long");
+    assert_eq!(rejection(error), MetadataRejection::KeyTooLong { max: 8 });
+
+    let error = provider
+        .set_session_metadata(&session_id, "region", "x".repeat(17))
+        .await
+        .expect_err("value too long");
+    assert_eq!(
+        rejection(error),
+        MetadataRejection::ValueTooLong { max: 16 }
+    );
+
+    for key in ["a", "b", "c"] {
+        provider.set_session_metadata(&session_id, key, "1").await?;
+    }
+    let error = provider
+        .set_session_metadata(&session_id, "d", "1")
+        .await
+        .expect_err("too many entries");
+    assert_eq!(
+        rejection(error),
+        MetadataRejection::TooManyEntries { max: 4 }
+    );
+
+    // Replacing an entry at the limit is fine
+    provider.set_session_metadata(&session_id, "a", "2").await?;
+    let stored = metadata(&provider, &session_id).await?;
+    assert_eq!(stored.len(), 4);
+    assert_eq!(stored.get("a").map(String::as_str), Some("2"));
+    assert!(!stored.keys().any(|key| key.starts_with("_wcp_")));
+
+    assert!(provider
+        .set_session_metadata("unknown", "a", "1")
+        .await
+        .is_err());
+
+    provider.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,126 @@
+use anyhow::Result;
+use futures::SinkExt;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::sleep;
+use tokio_tungstenite::tungstenite::protocol::{frame::coding::CloseCode, CloseFrame};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+
+use wasmcloud_provider_messaging_websocket::testing::{server_url, start_server};
+
+/// A span as seen by the capturing layer
+#[derive(Debug, Clone)]
+struct CapturedSpan {
+    id: u64,
+    name: String,
+    parent: Option<u64>,
+    fields: HashMap<String, String>,
+}
+
+#[derive(Default)]
+struct FieldVisitor(HashMap<String, String>);
+
+impl Visit for FieldVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{:?}", value));
+    }
+}
+
+/// Layer recording every span with its parent and recorded fields
+#[derive(Clone, Default)]
+struct CaptureLayer {
+    spans: Arc<Mutex<Vec<CapturedSpan>>>,
+}
+
+impl<S> Layer<S> for CaptureLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut visitor = FieldVisitor::default();
+        attrs.record(&mut visitor);
+        let parent = ctx
+            .span(id)
+            .and_then(|span| span.parent())
+            .map(|parent| parent.id().into_u64());
+        self.spans.lock().unwrap().push(CapturedSpan {
+            id: id.into_u64(),
+            name: attrs.metadata().name().to_string(),
+            parent,
+            fields: visitor.0,
+        });
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = FieldVisitor::default();
+        values.record(&mut visitor);
+        let mut spans = self.spans.lock().unwrap();
+        if let Some(span) = spans.iter_mut().rev().find(|s| s.id == id.into_u64()) {
+            span.fields.extend(visitor.0);
+        }
+    }
+}
+
+/// Test that a server-mode session gets one long-lived span with per-message children
+/// and final stats recorded on disconnect
+#[tokio::test]
+async fn test_session_span_lifecycle() -> Result<()> {
+    let layer = CaptureLayer::default();
+    let spans = Arc::clone(&layer.spans);
+    let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(layer));
+
+    let provider = start_server(&[]).await?;
+    let url = server_url(&provider).await?;
+
+    let (mut ws, _) = tokio_tungstenite::connect_async(url.as_str()).await?;
+    ws.send(Message::Text(
+        r#"{"subject":"test.span","body":"hello"}"#.to_string(),
+    ))
+    .await?;
+    ws.send(Message::Close(Some(CloseFrame {
+        code: CloseCode::Normal,
+        reason: "done".into(),
+    })))
+    .await?;
+
+    sleep(Duration::from_millis(300)).await;
+
+    let spans = spans.lock().unwrap().clone();
+    let session = spans
+        .iter()
+        .find(|s| s.name == "session")
+        .expect("session span should be created");
+    assert_eq!(
+        session.fields.get("mode").map(String::as_str),
+        Some("\"server\"")
+    );
+    assert!(session.fields.contains_key("session_id"));
+    assert!(session.fields.contains_key("remote"));
+
+    let message = spans
+        .iter()
+        .find(|s| s.name == "message")
+        .expect("per-message span should be created");
+    assert_eq!(message.parent, Some(session.id));
+
+    assert_eq!(
+        session.fields.get("messages").map(String::as_str),
+        Some("1")
+    );
+    assert!(session.fields.contains_key("bytes"));
+    assert!(session.fields.contains_key("duration_ms"));
+    assert_eq!(
+        session.fields.get("close_code").map(String::as_str),
+        Some("1000")
+    );
+
+    provider.shutdown().await?;
+    Ok(())
+}
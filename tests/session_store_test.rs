@@ -0,0 +1,131 @@
+use anyhow::Result;
+use futures::future::BoxFuture;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::sleep;
+
+use wasmcloud_provider_messaging_websocket::testing::{server_url, start_server};
+use wasmcloud_provider_messaging_websocket::{
+    MemorySessionStore, SessionStore, WebSocketMessagingProvider,
+};
+
+/// In-memory store that records every insert and remove
+#[derive(Default, Clone)]
+struct RecordingStore {
+    inner: Arc<MemorySessionStore>,
+    calls: Arc<Mutex<Vec<String>>>,
+}
+
+impl RecordingStore {
+    fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+impl SessionStore for RecordingStore {
+    fn insert(&self, session_id: String, component_id: String) -> BoxFuture<'_, Result<()>> {
+        self.calls
+            .lock()
+            .unwrap()
+            .push(format!("insert {} {}", session_id, component_id));
+        self.inner.insert(session_id, component_id)
+    }
+
+    fn get(&self, session_id: String) -> BoxFuture<'_, Result<Option<String>>> {
+        self.inner.get(session_id)
+    }
+
+    fn remove(&self, session_id: String) -> BoxFuture<'_, Result<Option<String>>> {
+        self.calls
+            .lock()
+            .unwrap()
+            .push(format!("remove {}", session_id));
+        self.inner.remove(session_id)
+    }
+
+    fn list(&self) -> BoxFuture<'_, Result<Vec<(String, String)>>> {
+        self.inner.list()
+    }
+}
+
+/// Test that sessions are recorded in the store on link and removed on unlink
+#[tokio::test]
+async fn test_store_follows_link_lifecycle() -> Result<()> {
+    let server = start_server(&[]).await?;
+    let store = RecordingStore::default();
+    // Recorded by another instance sharing the store
+    store
+        .inner
+        .insert("elsewhere".to_string(), "other".to_string())
+        .await?;
+
+    let mut config = HashMap::new();
+    config.insert("MODE".to_string(), "client".to_string());
+    config.insert("URI".to_string(), server_url(&server).await?);
+    let client = WebSocketMessagingProvider::from_config(config)?.with_session_store(store.clone());
+    client
+        .receive_link_config_as_target("publisher", HashMap::new())
+        .await?;
+
+    let calls = store.calls();
+    assert_eq!(calls.len(), 1);
+    let session_id = calls[0]
+        .strip_prefix("insert ")
+        .and_then(|rest| rest.strip_suffix(" publisher"))
+        .expect("insert for the linked component")
+        .to_string();
+
+    let mut sessions = client.list_sessions().await;
+    sessions.sort();
+    let mut expected = vec![
+        (session_id.clone(), "component:publisher".to_string()),
+        ("elsewhere".to_string(), "component:other".to_string()),
+    ];
+    expected.sort();
+    assert_eq!(sessions, expected);
+    assert_eq!(
+        client.get_session(&session_id).await.as_deref(),
+        Some("publisher")
+    );
+
+    client.delete_link_as_target("publisher").await?;
+    sleep(Duration::from_millis(100)).await;
+    assert_eq!(
+        store.calls().last(),
+        Some(&format!("remove {}", session_id))
+    );
+    assert_eq!(
+        client.list_sessions().await,
+        vec![("elsewhere".to_string(), "component:other".to_string())]
+    );
+
+    // Shutdown leaves other instances' sessions alone
+    client.shutdown().await?;
+    assert_eq!(store.calls().len(), 2);
+    server.shutdown().await?;
+    Ok(())
+}
+
+/// Test that nothing is recorded when session tracking is disabled
+#[tokio::test]
+async fn test_store_unused_without_tracking() -> Result<()> {
+    let server = start_server(&[]).await?;
+    let store = RecordingStore::default();
+
+    let mut config = HashMap::new();
+    config.insert("MODE".to_string(), "client".to_string());
+    config.insert("URI".to_string(), server_url(&server).await?);
+    config.insert("ENABLE_SESSION_TRACKING".to_string(), "false".to_string());
+    let client = WebSocketMessagingProvider::from_config(config)?.with_session_store(store.clone());
+    client
+        .receive_link_config_as_target("publisher", HashMap::new())
+        .await?;
+    client.delete_link_as_target("publisher").await?;
+    sleep(Duration::from_millis(100)).await;
+
+    assert!(store.calls().is_empty());
+    client.shutdown().await?;
+    server.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use anyhow::Result;
+use futures::{SinkExt, StreamExt};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::time::{sleep, timeout};
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::WebSocketMessagingProvider;
+
+/// Test that shutting down while the server is still sending stops forwarding promptly:
+/// every message that reached the handler did so whole and in order, and the rest are
+/// counted as dropped rather than forwarded
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_shutdown_stops_inbound_forwarding() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let sent = Arc::new(AtomicUsize::new(0));
+    let server_sent = Arc::clone(&sent);
+    let (echo_tx, mut echo_rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await?;
+        let (mut ws_tx, mut ws_rx) = tokio_tungstenite::accept_async(stream).await?.split();
+        let writer = async move {
+            for n in 0.. {
+                let frame = format!(r#"{{"subject":"feed.{}","body":"x"}}"#, n);
+                if ws_tx.send(Message::Text(frame)).await.is_err() {
+                    break;
+                }
+                server_sent.fetch_add(1, Ordering::SeqCst);
+                sleep(Duration::from_millis(1)).await;
+            }
+        };
+        // The handler's connection is the server's own, so forwarded messages come back
+        let reader = async move {
+            while let Some(Ok(msg)) = ws_rx.next().await {
+                if let Message::Text(text) = msg {
+                    let _ = echo_tx.send(text);
+                }
+            }
+        };
+        tokio::select! {
+            _ = writer => {}
+            _ = reader => {}
+        }
+        anyhow::Ok(())
+    });
+
+    let config = HashMap::from([("URI".to_string(), format!("ws://{}/ws", addr))]);
+    let provider = WebSocketMessagingProvider::from_config(config)?;
+    provider
+        .receive_link_config_as_source("handler", HashMap::new())
+        .await?;
+
+    let mut echoed = Vec::new();
+    while echoed.len() < 20 {
+        let text = timeout(Duration::from_secs(2), echo_rx.recv())
+            .await?
+            .expect("server should be running");
+        echoed.push(text);
+    }
+
+    let report = timeout(Duration::from_secs(2), provider.shutdown()).await??;
+    // The connection ends with the shutdown, so the echoes stop
+    while let Some(text) = timeout(Duration::from_secs(2), echo_rx.recv()).await? {
+        echoed.push(text);
+    }
+
+    let mut forwarded = Vec::new();
+    for text in &echoed {
+        let json: serde_json::Value = serde_json::from_str(text)?;
+        let Some(subject) = json["subject"].as_str() else {
+            continue;
+        };
+        if let Some(n) = subject.strip_prefix("feed.") {
+            assert!(
+                json["body"].is_string(),
+                "partially processed message: {}",
+                text
+            );
+            forwarded.push(n.parse::<usize>()?);
+        }
+    }
+    assert!(forwarded.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(forwarded.len() + report.inbound_dropped <= sent.load(Ordering::SeqCst));
+    assert!(provider.list_links().await.is_empty());
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use anyhow::Result;
+use bytes::Bytes;
+use futures::SinkExt;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::{sleep, timeout};
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::mock::{MockRemote, MockTransport};
+use wasmcloud_provider_messaging_websocket::testing::{accept, connect, start_server};
+use wasmcloud_provider_messaging_websocket::{
+    read_spill, BrokerMessage, SpillReason, WebSocketMessagingProvider,
+};
+
+const MESSAGES: usize = 5;
+
+fn spill_path() -> PathBuf {
+    std::env::temp_dir().join(format!("shutdown-spill-{}.ndjson", uuid::Uuid::new_v4()))
+}
+
+/// Link a publisher whose mock socket stops accepting writes after the first frame
+async fn link_publisher(
+    extra: &[(&str, &str)],
+) -> Result<(WebSocketMessagingProvider, MockRemote)> {
+    let mut config = HashMap::new();
+    config.insert("URI".to_string(), "ws://mock.invalid/ws".to_string());
+    for (key, value) in extra {
+        config.insert(key.to_string(), value.to_string());
+    }
+
+    let (transport, remotes) = MockTransport::new();
+    transport.set_write_capacity(Some(0));
+    let provider = WebSocketMessagingProvider::from_config(config)?.with_transport(transport);
+    provider
+        .receive_link_config_as_target("publisher", HashMap::new())
+        .await?;
+    let remote = accept(&remotes).await;
+    Ok((provider, remote))
+}
+
+/// Test that queued publishes are spilled on shutdown and re-sent once by an import
+#[tokio::test]
+async fn test_outbound_queue_spilled_and_imported() -> Result<()> {
+    let path = spill_path();
+    let path_value = path.display().to_string();
+    let (provider, _remote) = link_publisher(&[("SHUTDOWN_SPILL_PATH", &path_value)]).await?;
+
+    for n in 0..MESSAGES {
+        let msg = BrokerMessage {
+            subject: format!("orders.{}", n),
+            body: Bytes::from("x"),
+            reply_to: None,
+            headers: HashMap::from([("msg-id".to_string(), format!("m{}", n))]),
+        };
+        provider.publish("publisher", msg).await?;
+    }
+    sleep(Duration::from_millis(50)).await;
+
+    let report = provider.shutdown().await?;
+    // One frame reached the socket and at most one more was mid-write
+    assert!(report.unsent >= MESSAGES - 2, "{:?}", report);
+    assert_eq!(report.spilled, report.unsent);
+    assert_eq!(report.spill_path.as_deref(), Some(path.as_path()));
+
+    let entries = read_spill(&path)?;
+    assert_eq!(entries.len(), report.spilled);
+    let subjects: Vec<&str> = entries
+        .iter()
+        .map(|entry| entry.envelope["subject"].as_str().unwrap())
+        .collect();
+    let expected: Vec<String> = (MESSAGES - entries.len()..MESSAGES)
+        .map(|n| format!("orders.{}", n))
+        .collect();
+    assert_eq!(subjects, expected);
+    assert!(entries
+        .iter()
+        .all(|entry| entry.reason == SpillReason::OutboundQueue
+            && entry.component_id.as_deref() == Some("publisher")));
+
+    // The same message spilled twice is only re-sent once
+    let duplicate = serde_json::to_string(&entries[0])?;
+    writeln!(
+        std::fs::OpenOptions::new().append(true).open(&path)?,
+        "{}",
+        duplicate
+    )?;
+
+    let (restarted, mut remote) = link_publisher(&[]).await?;
+    assert_eq!(restarted.import_spill(&path).await?, entries.len());
+    for subject in &expected {
+        let frame = timeout(Duration::from_secs(2), remote.recv())
+            .await?
+            .expect("re-sent frame");
+        let json: serde_json::Value = serde_json::from_str(frame.to_text()?)?;
+        assert_eq!(json["subject"], subject.as_str());
+    }
+
+    restarted.shutdown().await?;
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+/// Test that messages buffered for a missing sink are spilled and delivered by an import
+#[tokio::test]
+async fn test_sink_buffer_spilled_and_imported() -> Result<()> {
+    let path = spill_path();
+    let path_value = path.display().to_string();
+    let server = start_server(&[("SHUTDOWN_SPILL_PATH", &path_value)]).await?;
+    let (mut ws, _) = connect(&server).await?;
+    for subject in ["a", "b"] {
+        ws.send(Message::Text(format!(
+            r#"{{"subject":"{}","body":"hello"}}"#,
+            subject
+        )))
+        .await?;
+    }
+    sleep(Duration::from_millis(100)).await;
+
+    let report = server.shutdown().await?;
+    assert_eq!((report.unsent, report.spilled), (2, 2));
+    let entries = read_spill(&path)?;
+    assert!(entries
+        .iter()
+        .all(|entry| entry.reason == SpillReason::SinkBuffer && entry.component_id.is_none()));
+
+    let restarted = start_server(&[]).await?;
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    restarted
+        .set_message_sink(
+            move |_session_id: String, msg: BrokerMessage| -> Result<()> {
+                tx.send(msg)?;
+                Ok(())
+            },
+        )
+        .await?;
+    assert_eq!(restarted.import_spill(&path).await?, 2);
+    for subject in ["a", "b"] {
+        let msg = timeout(Duration::from_secs(2), rx.recv())
+            .await?
+            .expect("imported message");
+        assert_eq!(msg.subject, subject);
+        assert_eq!(msg.body, Bytes::from("hello"));
+    }
+
+    restarted.shutdown().await?;
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use anyhow::Result;
+use bytes::Bytes;
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+
+use wasmcloud_provider_messaging_websocket::testing::{connect, start_server};
+use wasmcloud_provider_messaging_websocket::{BrokerMessage, ProviderEvent};
+
+const QUEUE_CAPACITY: usize = 32;
+const SUSTAINED_MS: u64 = 200;
+
+/// Test that a client that stops reading is reported once its queue stays high, while a
+/// client keeping up is not
+#[tokio::test]
+async fn test_slow_consumer_reported_after_sustained_backlog() -> Result<()> {
+    let server = start_server(&[
+        ("OUTBOUND_QUEUE_CAPACITY", &QUEUE_CAPACITY.to_string()),
+        ("SLOW_CONSUMER_HIGH_WATER_PCT", "50"),
+        ("SLOW_CONSUMER_AFTER_MS", &SUSTAINED_MS.to_string()),
+    ])
+    .await?;
+    let mut events = server.subscribe_events();
+
+    // Never read, so frames pile up once the socket buffers are full
+    let (_slow, slow_session) = connect(&server).await?;
+    let (mut fast, fast_session) = connect(&server).await?;
+    let reading = tokio::spawn(async move { while fast.next().await.is_some() {} });
+
+    let msg = BrokerMessage {
+        subject: "ticks".to_string(),
+        body: Bytes::from(vec![b'x'; 16 * 1024]),
+        reply_to: None,
+        headers: HashMap::new(),
+    };
+    let started = Instant::now();
+    let mut reported = None;
+    while reported.is_none() && started.elapsed() < Duration::from_secs(10) {
+        server.broadcast_to_clients(msg.clone()).await?;
+        sleep(Duration::from_millis(1)).await;
+        while let Ok(event) = events.try_recv() {
+            if let ProviderEvent::SlowConsumer {
+                session_id,
+                queue_depth,
+            } = event
+            {
+                assert_ne!(session_id, fast_session, "the reading client was reported");
+                reported = Some((session_id, queue_depth, started.elapsed()));
+            }
+        }
+    }
+
+    let (session_id, queue_depth, elapsed) = reported.expect("SlowConsumer never raised");
+    assert_eq!(session_id, slow_session);
+    assert!(queue_depth >= QUEUE_CAPACITY / 2, "{}", queue_depth);
+    assert!(elapsed >= Duration::from_millis(SUSTAINED_MS));
+
+    reading.abort();
+    server.shutdown().await?;
+    Ok(())
+}
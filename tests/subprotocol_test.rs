@@ -0,0 +1,119 @@
+use anyhow::Result;
+use axum::extract::ws::WebSocket;
+use axum::extract::{State, WebSocketUpgrade};
+use axum::response::Response as AxumResponse;
+use axum::routing::get;
+use axum::Router;
+use std::collections::HashMap;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+
+use wasmcloud_provider_messaging_websocket::WebSocketMessagingProvider;
+
+/// Accepts the protocols it supports and reports the one it selected
+async fn select_protocol(
+    State(selected_tx): State<mpsc::UnboundedSender<Option<String>>>,
+    ws: WebSocketUpgrade,
+) -> AxumResponse {
+    ws.protocols(["acme.v2", "graphql-transport-ws"])
+        .on_upgrade(move |socket: WebSocket| async move {
+            let selected = socket
+                .protocol()
+                .and_then(|protocol| protocol.to_str().ok())
+                .map(str::to_string);
+            let _ = selected_tx.send(selected);
+            std::future::pending::<()>().await;
+        })
+}
+
+/// Test that `SUBPROTOCOLS` is offered in the upgrade request and the protocol the
+/// server selects is recorded in the session metadata
+#[tokio::test]
+async fn test_selected_subprotocol_in_session_metadata() -> Result<()> {
+    let (selected_tx, mut selected_rx) = mpsc::unbounded_channel();
+    let app = Router::new()
+        .route("/ws", get(select_protocol))
+        .with_state(selected_tx);
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let provider = WebSocketMessagingProvider::from_config(HashMap::from([(
+        "URI".to_string(),
+        format!("ws://{}/ws", addr),
+    )]))?;
+    provider
+        .receive_link_config_as_target(
+            "graphql",
+            HashMap::from([(
+                "SUBPROTOCOLS".to_string(),
+                "graphql-transport-ws, graphql-ws".to_string(),
+            )]),
+        )
+        .await?;
+    assert_eq!(
+        selected_rx.recv().await.flatten().as_deref(),
+        Some("graphql-transport-ws")
+    );
+    let info = provider
+        .client_session_info("graphql")
+        .await
+        .expect("graphql should be linked");
+    assert_eq!(info.metadata["subprotocol"], "graphql-transport-ws");
+
+    // Without SUBPROTOCOLS the server selects none and none is recorded
+    provider
+        .receive_link_config_as_target("plain", HashMap::new())
+        .await?;
+    assert_eq!(selected_rx.recv().await, Some(None));
+    let info = provider
+        .client_session_info("plain")
+        .await
+        .expect("plain should be linked");
+    assert!(!info.metadata.contains_key("subprotocol"));
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a server selecting a subprotocol that wasn't requested fails the link
+#[tokio::test]
+async fn test_unrequested_subprotocol_rejected() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await?;
+        let ws =
+            tokio_tungstenite::accept_hdr_async(stream, |_: &Request, mut response: Response| {
+                response
+                    .headers_mut()
+                    .insert("Sec-WebSocket-Protocol", HeaderValue::from_static("mqtt"));
+                Ok::<Response, _>(response)
+            })
+            .await?;
+        anyhow::Ok(ws)
+    });
+
+    let provider = WebSocketMessagingProvider::from_config(HashMap::from([
+        ("URI".to_string(), format!("ws://{}/ws", addr)),
+        (
+            "SUBPROTOCOLS".to_string(),
+            "graphql-transport-ws".to_string(),
+        ),
+    ]))?;
+    let error = provider
+        .receive_link_config_as_target("graphql", HashMap::new())
+        .await
+        .expect_err("link should fail");
+    assert!(
+        format!("{:#}", error).contains("subprotocol mqtt which was not requested"),
+        "{:#}",
+        error
+    );
+    assert!(provider.client_session_info("graphql").await.is_none());
+
+    provider.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,213 @@
+use anyhow::Result;
+use futures::SinkExt;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::{header::AUTHORIZATION, StatusCode};
+use tokio_tungstenite::tungstenite::{Error as WsError, Message};
+
+use wasmcloud_provider_messaging_websocket::mock::{MockRemotes, MockTransport};
+use wasmcloud_provider_messaging_websocket::testing::{serve, server_config, server_url, WsClient};
+use wasmcloud_provider_messaging_websocket::{
+    BrokerMessage, DeadLetterReason, ProviderEvent, WebSocketMessagingProvider, DEFAULT_TENANT,
+    TENANT_HANDLER_HEADER,
+};
+
+/// Start a server-mode provider, with `extra` config, whose sink reports
+/// `(subject, handler)` of each message
+async fn tenant_server(
+    extra: &[(&str, &str)],
+) -> Result<(
+    WebSocketMessagingProvider,
+    MockRemotes,
+    mpsc::UnboundedReceiver<(String, Option<String>)>,
+)> {
+    // Handler links still open a client connection of their own; the mock stands in
+    let (transport, remotes) = MockTransport::new();
+    let provider = serve(
+        WebSocketMessagingProvider::from_config(server_config(extra))?.with_transport(transport),
+    )
+    .await?;
+    let (tx, rx) = mpsc::unbounded_channel();
+    provider
+        .set_message_sink(
+            move |_session_id: String, msg: BrokerMessage| -> Result<()> {
+                let handler = msg.headers.get(TENANT_HANDLER_HEADER).cloned();
+                let _ = tx.send((msg.subject, handler));
+                Ok(())
+            },
+        )
+        .await?;
+    Ok((provider, remotes, rx))
+}
+
+async fn link_handler(
+    provider: &WebSocketMessagingProvider,
+    component_id: &str,
+    tenant: &str,
+) -> Result<()> {
+    let config = HashMap::from([
+        ("URI".to_string(), "ws://mock.invalid/ws".to_string()),
+        ("TENANT".to_string(), tenant.to_string()),
+    ]);
+    provider
+        .receive_link_config_as_source(component_id, config)
+        .await?;
+    Ok(())
+}
+
+async fn connect(provider: &WebSocketMessagingProvider, tenant: &str) -> Result<WsClient> {
+    let url = format!("{}?tenant={}", server_url(provider).await?, tenant);
+    let (ws, _) = tokio_tungstenite::connect_async(url).await?;
+    Ok(ws)
+}
+
+/// Connect presenting `token` as a bearer token, naming `tenant` if given
+async fn connect_with_token(
+    provider: &WebSocketMessagingProvider,
+    tenant: Option<&str>,
+    token: Option<&str>,
+) -> Result<WsClient, WsError> {
+    let url = server_url(provider)
+        .await
+        .expect("server should be running");
+    let url = match tenant {
+        Some(tenant) => format!("{}?tenant={}", url, tenant),
+        None => url,
+    };
+    let mut request = url.into_client_request()?;
+    if let Some(token) = token {
+        request
+            .headers_mut()
+            .insert(AUTHORIZATION, format!("Bearer {}", token).parse().unwrap());
+    }
+    let (ws, _) = tokio_tungstenite::connect_async(request).await?;
+    Ok(ws)
+}
+
+fn rejected_with(result: Result<WsClient, WsError>) -> StatusCode {
+    match result {
+        Err(WsError::Http(response)) => response.status(),
+        Err(e) => panic!("upgrade failed with {}", e),
+        Ok(_) => panic!("upgrade should be refused"),
+    }
+}
+
+async fn send(ws: &mut WsClient, subject: &str) -> Result<()> {
+    let envelope = serde_json::json!({ "subject": subject, "body": "x" });
+    ws.send(Message::Text(envelope.to_string())).await?;
+    Ok(())
+}
+
+/// Test that each tenant's messages reach its own handler and an unknown tenant's are
+/// dead-lettered until a default handler is linked
+#[tokio::test]
+async fn test_messages_routed_by_tenant() -> Result<()> {
+    let (provider, _remotes, mut delivered) = tenant_server(&[]).await?;
+    link_handler(&provider, "acme-handler", "acme").await?;
+    link_handler(&provider, "globex-handler", "globex").await?;
+    let mut events = provider.subscribe_events();
+
+    let mut acme = connect(&provider, "acme").await?;
+    let mut globex = connect(&provider, "globex").await?;
+    let mut initech = connect(&provider, "initech").await?;
+
+    send(&mut acme, "orders.acme").await?;
+    send(&mut globex, "orders.globex").await?;
+    send(&mut initech, "orders.initech").await?;
+
+    let mut routed = Vec::new();
+    for _ in 0..2 {
+        routed.push(
+            timeout(Duration::from_secs(2), delivered.recv())
+                .await?
+                .expect("delivered message"),
+        );
+    }
+    routed.sort();
+    assert_eq!(
+        routed,
+        vec![
+            ("orders.acme".to_string(), Some("acme-handler".to_string())),
+            (
+                "orders.globex".to_string(),
+                Some("globex-handler".to_string())
+            ),
+        ]
+    );
+
+    loop {
+        let event = timeout(Duration::from_secs(2), events.recv()).await??;
+        if let ProviderEvent::DeadLetter {
+            tenant,
+            subject,
+            reason,
+            ..
+        } = event
+        {
+            assert_eq!(tenant.as_deref(), Some("initech"));
+            assert_eq!(subject, "orders.initech");
+            assert_eq!(reason, DeadLetterReason::NoTenantHandler);
+            assert_eq!(reason.as_str(), "no_tenant_handler");
+            break;
+        }
+    }
+    assert!(delivered.try_recv().is_err());
+
+    // Tenants without a handler of their own fall back to the default one
+    link_handler(&provider, "fallback", DEFAULT_TENANT).await?;
+    send(&mut initech, "orders.initech").await?;
+    let fallback = timeout(Duration::from_secs(2), delivered.recv())
+        .await?
+        .expect("delivered message");
+    assert_eq!(
+        fallback,
+        ("orders.initech".to_string(), Some("fallback".to_string()))
+    );
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that under `TENANT_TOKENS` a client's tenant comes from its token, and that
+/// naming another tenant or presenting no known token is refused
+#[tokio::test]
+async fn test_tenant_taken_from_token() -> Result<()> {
+    let (provider, _remotes, mut delivered) =
+        tenant_server(&[("TENANT_TOKENS", "acme=acme-token,globex=globex-token")]).await?;
+    link_handler(&provider, "acme-handler", "acme").await?;
+    link_handler(&provider, "globex-handler", "globex").await?;
+
+    // The token alone names the tenant; naming the same one is allowed
+    let mut acme = connect_with_token(&provider, None, Some("acme-token")).await?;
+    let mut globex = connect_with_token(&provider, Some("globex"), Some("globex-token")).await?;
+    for (ws, subject, handler) in [
+        (&mut acme, "orders.acme", "acme-handler"),
+        (&mut globex, "orders.globex", "globex-handler"),
+    ] {
+        send(ws, subject).await?;
+        let routed = timeout(Duration::from_secs(2), delivered.recv())
+            .await?
+            .expect("delivered message");
+        assert_eq!(routed, (subject.to_string(), Some(handler.to_string())));
+    }
+
+    assert_eq!(
+        rejected_with(connect_with_token(&provider, Some("globex"), Some("acme-token")).await),
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        rejected_with(connect_with_token(&provider, Some("acme"), None).await),
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        rejected_with(connect_with_token(&provider, None, Some("guess")).await),
+        StatusCode::UNAUTHORIZED
+    );
+    assert!(delivered.try_recv().is_err());
+
+    provider.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,299 @@
+use anyhow::Result;
+use bytes::Bytes;
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::time::sleep;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::{RootCertStore, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+
+use wasmcloud_provider_messaging_websocket::testing::start_server;
+use wasmcloud_provider_messaging_websocket::{BrokerMessage, WebSocketMessagingProvider};
+
+type Received = Arc<Mutex<Vec<String>>>;
+
+fn fixture(name: &str) -> String {
+    format!("{}/tests/fixtures/tls/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+fn certs(name: &str) -> Vec<CertificateDer<'static>> {
+    let mut reader = BufReader::new(File::open(fixture(name)).unwrap());
+    rustls_pemfile::certs(&mut reader)
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+fn key(name: &str) -> PrivateKeyDer<'static> {
+    let mut reader = BufReader::new(File::open(fixture(name)).unwrap());
+    rustls_pemfile::private_key(&mut reader).unwrap().unwrap()
+}
+
+/// Start a `wss://` server signed by the test CA, recording the text frames it receives;
+/// with `require_client_cert` it only accepts clients with a certificate from that CA
+async fn start_wss_server(require_client_cert: bool) -> Result<(String, Received)> {
+    let builder = if require_client_cert {
+        let mut roots = RootCertStore::empty();
+        for cert in certs("ca.pem") {
+            roots.add(cert)?;
+        }
+        ServerConfig::builder()
+            .with_client_cert_verifier(WebPkiClientVerifier::builder(Arc::new(roots)).build()?)
+    } else {
+        ServerConfig::builder().with_no_client_auth()
+    };
+    let tls = builder.with_single_cert(certs("server.pem"), key("server.key"))?;
+    let acceptor = TlsAcceptor::from(Arc::new(tls));
+
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let url = format!("wss://localhost:{}/ws", listener.local_addr()?.port());
+    let received = Received::default();
+    let recorded = Arc::clone(&received);
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let acceptor = acceptor.clone();
+            let recorded = Arc::clone(&recorded);
+            tokio::spawn(async move {
+                let Ok(stream) = acceptor.accept(stream).await else {
+                    return;
+                };
+                let Ok(mut ws) = tokio_tungstenite::accept_async(stream).await else {
+                    return;
+                };
+                while let Some(Ok(frame)) = ws.next().await {
+                    if let Ok(text) = frame.to_text() {
+                        recorded.lock().unwrap().push(text.to_string());
+                    }
+                }
+            });
+        }
+    });
+    Ok((url, received))
+}
+
+async fn link(url: &str, tls: &[(&str, String)]) -> Result<WebSocketMessagingProvider> {
+    let mut config = HashMap::from([
+        ("MODE".to_string(), "client".to_string()),
+        ("URI".to_string(), url.to_string()),
+    ]);
+    for (key, value) in tls {
+        config.insert(key.to_string(), value.clone());
+    }
+    let provider = WebSocketMessagingProvider::from_config(config)?;
+    provider
+        .receive_link_config_as_target("publisher", HashMap::new())
+        .await?;
+    Ok(provider)
+}
+
+/// Publish a message over the link and wait for the server to receive it
+async fn publish_and_wait(
+    provider: &WebSocketMessagingProvider,
+    received: &Received,
+) -> Result<()> {
+    provider
+        .publish(
+            "publisher",
+            BrokerMessage {
+                subject: "over.tls".to_string(),
+                body: Bytes::from("hello"),
+                reply_to: None,
+                headers: HashMap::new(),
+            },
+        )
+        .await?;
+    for _ in 0..50 {
+        if !received.lock().unwrap().is_empty() {
+            break;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 1);
+    assert!(received[0].contains("over.tls"), "{}", received[0]);
+    Ok(())
+}
+
+/// Test that a client link trusts a server signed by `TLS_CA_FILE` and not without it
+#[tokio::test]
+async fn test_custom_ca() -> Result<()> {
+    let (url, received) = start_wss_server(false).await?;
+
+    assert!(link(&url, &[]).await.is_err());
+
+    let provider = link(&url, &[("TLS_CA_FILE", fixture("ca.pem"))]).await?;
+    publish_and_wait(&provider, &received).await?;
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that `TLS_INSECURE_SKIP_VERIFY` connects to a server nothing vouches for
+#[tokio::test]
+async fn test_insecure_skip_verify() -> Result<()> {
+    let (url, received) = start_wss_server(false).await?;
+
+    let provider = link(&url, &[("TLS_INSECURE_SKIP_VERIFY", "true".to_string())]).await?;
+    publish_and_wait(&provider, &received).await?;
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a server requiring client certificates accepts the link once it presents
+/// `TLS_CLIENT_CERT_FILE`
+#[tokio::test]
+async fn test_client_certificate() -> Result<()> {
+    let (url, received) = start_wss_server(true).await?;
+    let ca = ("TLS_CA_FILE", fixture("ca.pem"));
+
+    // The handshake completes client-side before the server rejects it, so the failure
+    // can show up at link time or as no message arriving
+    if let Ok(provider) = link(&url, &[ca.clone()]).await {
+        let _ = provider
+            .publish(
+                "publisher",
+                BrokerMessage {
+                    subject: "anonymous".to_string(),
+                    body: Bytes::from("hello"),
+                    reply_to: None,
+                    headers: HashMap::new(),
+                },
+            )
+            .await;
+        sleep(Duration::from_millis(100)).await;
+        provider.shutdown().await?;
+    }
+    assert!(received.lock().unwrap().is_empty());
+
+    let provider = link(
+        &url,
+        &[
+            ca,
+            ("TLS_CLIENT_CERT_FILE", fixture("client.pem")),
+            ("TLS_CLIENT_KEY_FILE", fixture("client.key")),
+        ],
+    )
+    .await?;
+    publish_and_wait(&provider, &received).await?;
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a TLS file that doesn't exist fails the link with an error naming it
+#[tokio::test]
+async fn test_missing_file_fails_link() -> Result<()> {
+    let (url, _) = start_wss_server(false).await?;
+    let missing = fixture("missing.pem");
+
+    let error = link(
+        &url,
+        &[
+            ("TLS_CLIENT_CERT_FILE", missing.clone()),
+            ("TLS_CLIENT_KEY_FILE", fixture("client.key")),
+        ],
+    )
+    .await
+    .err()
+    .expect("link should fail");
+    let message = format!("{:#}", error);
+    assert!(
+        message.contains(&format!("TLS_CLIENT_CERT_FILE {}", missing)),
+        "{}",
+        message
+    );
+    Ok(())
+}
+
+/// Test that `TLS_CERT_PATH` and `TLS_KEY_PATH` make the server speak `wss://` to a
+/// client trusting its CA, on the address `get_server_addr` reports
+#[tokio::test]
+async fn test_server_mode_wss() -> Result<()> {
+    let server = start_server(&[
+        ("TLS_CERT_PATH", &fixture("server.pem")),
+        ("TLS_KEY_PATH", &fixture("server.key")),
+    ])
+    .await?;
+    let received = Received::default();
+    let recorded = Arc::clone(&received);
+    server
+        .set_message_sink(
+            move |_session_id: String, msg: BrokerMessage| -> Result<()> {
+                recorded.lock().unwrap().push(msg.subject);
+                Ok(())
+            },
+        )
+        .await?;
+    let addr = server
+        .get_server_addr()
+        .await
+        .expect("server should be bound");
+    let url = format!("wss://localhost:{}/ws", addr.port());
+
+    // Plain `ws://` gets no WebSocket out of it
+    assert!(
+        tokio_tungstenite::connect_async(format!("ws://{}/ws", addr))
+            .await
+            .is_err()
+    );
+
+    let client = link(&url, &[("TLS_CA_FILE", fixture("ca.pem"))]).await?;
+    publish_and_wait(&client, &received).await?;
+
+    client.shutdown().await?;
+    server.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a server with only one of `TLS_CERT_PATH` / `TLS_KEY_PATH`, or a pair that
+/// can't be loaded, fails to start with an error naming the setting
+#[tokio::test]
+async fn test_server_mode_tls_config_errors() -> Result<()> {
+    let error = start_server(&[("TLS_CERT_PATH", &fixture("server.pem"))])
+        .await
+        .err()
+        .expect("a certificate without a key should fail");
+    assert_eq!(
+        error.to_string(),
+        "TLS_CERT_PATH is set without TLS_KEY_PATH"
+    );
+
+    let error = start_server(&[("TLS_KEY_PATH", &fixture("server.key"))])
+        .await
+        .err()
+        .expect("a key without a certificate should fail");
+    assert_eq!(
+        error.to_string(),
+        "TLS_KEY_PATH is set without TLS_CERT_PATH"
+    );
+
+    let missing = fixture("missing.pem");
+    let error = start_server(&[
+        ("TLS_CERT_PATH", missing.clone()),
+        ("TLS_KEY_PATH", &fixture("server.key")),
+    ])
+    .await
+    .err()
+    .expect("a missing certificate should fail");
+    let message = format!("{:#}", error);
+    assert!(
+        message.contains(&format!("TLS_CERT_PATH {}", missing)),
+        "{}",
+        message
+    );
+
+    // A certificate where the key should be
+    let error = start_server(&[
+        ("TLS_CERT_PATH", &fixture("server.pem")),
+        ("TLS_KEY_PATH", &fixture("server.pem")),
+    ])
+    .await
+    .err()
+    .expect("a file without a key should fail");
+    let message = format!("{:#}", error);
+    assert!(message.contains("holds no PEM private key"), "{}", message);
+    Ok(())
+}
@@ -0,0 +1,97 @@
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use wasmcloud_provider_messaging_websocket::mock::MockTransport;
+use wasmcloud_provider_messaging_websocket::testing::accept;
+use wasmcloud_provider_messaging_websocket::WebSocketMessagingProvider;
+
+/// A provider with a static `AUTH_TOKEN` and a token provider handing out `token-1`,
+/// `token-2`, ... (failing while `fail` is set), plus how many tokens it fetched
+fn provider(
+    transport: MockTransport,
+    fail: Arc<AtomicUsize>,
+) -> Result<(WebSocketMessagingProvider, Arc<AtomicUsize>)> {
+    let config = HashMap::from([
+        ("URI".to_string(), "ws://mock.invalid/ws".to_string()),
+        ("AUTH_TOKEN".to_string(), "static".to_string()),
+    ]);
+    let fetched = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&fetched);
+    let provider = WebSocketMessagingProvider::from_config(config)?
+        .with_transport(transport)
+        .with_token_provider(move || {
+            let counter = Arc::clone(&counter);
+            let fail = Arc::clone(&fail);
+            async move {
+                if fail.load(Ordering::SeqCst) > 0 {
+                    bail!("token service unavailable");
+                }
+                Ok(format!(
+                    "token-{}",
+                    counter.fetch_add(1, Ordering::SeqCst) + 1
+                ))
+            }
+        });
+    Ok((provider, fetched))
+}
+
+/// Test that the first connection and every reconnect present a freshly fetched token
+#[tokio::test]
+async fn test_each_connection_fetches_fresh_token() -> Result<()> {
+    let (transport, remotes) = MockTransport::new();
+    let (provider, fetched) = provider(transport, Arc::default())?;
+
+    provider
+        .receive_link_config_as_target("publisher", HashMap::new())
+        .await?;
+    let remote = accept(&remotes).await;
+    assert_eq!(remote.auth_token(), Some("token-1"));
+
+    // Moving the link reconnects
+    provider
+        .migrate_link("publisher", "ws://mock.invalid/other")
+        .await?;
+    let remote = accept(&remotes).await;
+    assert_eq!(remote.uri(), "ws://mock.invalid/other");
+    assert_eq!(remote.auth_token(), Some("token-2"));
+
+    // So does a link update changing a connection-level key
+    let update = HashMap::from([
+        ("URI".to_string(), "ws://mock.invalid/other".to_string()),
+        ("HEADER_X-Trace".to_string(), "1".to_string()),
+    ]);
+    provider
+        .receive_link_config_as_target("publisher", update)
+        .await?;
+    let remote = accept(&remotes).await;
+    assert_eq!(remote.auth_token(), Some("token-3"));
+    assert_eq!(fetched.load(Ordering::SeqCst), 3);
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a failed token fetch fails the connection attempt
+#[tokio::test]
+async fn test_token_fetch_failure_fails_connect() -> Result<()> {
+    let (transport, remotes) = MockTransport::new();
+    let fail = Arc::new(AtomicUsize::new(1));
+    let (provider, _) = provider(transport, Arc::clone(&fail))?;
+
+    let error = provider
+        .receive_link_config_as_target("publisher", HashMap::new())
+        .await
+        .expect_err("link should fail without a token");
+    assert!(format!("{:#}", error).contains("token service unavailable"));
+
+    fail.store(0, Ordering::SeqCst);
+    provider
+        .receive_link_config_as_target("publisher", HashMap::new())
+        .await?;
+    assert_eq!(accept(&remotes).await.auth_token(), Some("token-1"));
+
+    provider.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use anyhow::Result;
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::{HeaderValue, StatusCode};
+use tokio_tungstenite::tungstenite::Error as WsError;
+
+use wasmcloud_provider_messaging_websocket::testing::start_server;
+use wasmcloud_provider_messaging_websocket::WebSocketMessagingProvider;
+
+async fn limited_server() -> Result<(WebSocketMessagingProvider, SocketAddr)> {
+    let provider = start_server(&[
+        ("MAX_UPGRADE_BODY_BYTES", "1024"),
+        ("MAX_UPGRADE_HEADER_BYTES", "4096"),
+    ])
+    .await?;
+    let addr = provider.get_server_addr().await.unwrap();
+    Ok((provider, addr))
+}
+
+/// Test that an upgrade request with an oversized body gets 413 instead of a WebSocket
+#[tokio::test]
+async fn test_oversized_upgrade_body_rejected() -> Result<()> {
+    let (provider, addr) = limited_server().await?;
+
+    let body = vec![b'x'; 4096];
+    let mut stream = TcpStream::connect(addr).await?;
+    let head = format!(
+        "GET /ws HTTP/1.1\r\nHost: {}\r\nConnection: Upgrade\r\nUpgrade: websocket\r\n\
+         Sec-WebSocket-Version: 13\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+         Content-Length: {}\r\n\r\n",
+        addr,
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(&body).await?;
+
+    let mut response = vec![0; 64];
+    let n = stream.read(&mut response).await?;
+    let status_line = String::from_utf8_lossy(&response[..n]);
+    assert!(status_line.starts_with("HTTP/1.1 413"), "{}", status_line);
+    assert!(provider.list_ws_clients().await?.is_empty());
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that oversized upgrade headers get 431 while a normal upgrade still succeeds
+#[tokio::test]
+async fn test_oversized_upgrade_headers_rejected() -> Result<()> {
+    let (provider, addr) = limited_server().await?;
+    let url = format!("ws://{}/ws", addr);
+
+    let mut request = url.as_str().into_client_request()?;
+    request
+        .headers_mut()
+        .insert("x-padding", HeaderValue::from_str(&"x".repeat(8192))?);
+    match tokio_tungstenite::connect_async(request).await {
+        Err(WsError::Http(response)) => {
+            assert_eq!(
+                response.status(),
+                StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE
+            )
+        }
+        other => panic!("expected an HTTP error, got {:?}", other.map(|_| ())),
+    }
+
+    let (_ws, _) = tokio_tungstenite::connect_async(url).await?;
+
+    provider.shutdown().await?;
+    Ok(())
+}
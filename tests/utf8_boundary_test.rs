@@ -0,0 +1,150 @@
+use anyhow::Result;
+use futures::SinkExt;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::{sleep, timeout};
+use tokio_tungstenite::tungstenite::{Error as WsError, Message};
+
+use wasmcloud_provider_messaging_websocket::mock::{MockRemote, MockTransport};
+use wasmcloud_provider_messaging_websocket::testing::{server_url, start_server};
+use wasmcloud_provider_messaging_websocket::{BrokerMessage, WebSocketMessagingProvider};
+
+/// Envelopes that must never be dispatched, with the reason they are counted under
+const INVALID_TEXT: &[(&str, &str)] = &[
+    (r#"{"subject":"\ud800","body":"x"}"#, "invalid_utf8"),
+    (r#"{"subject":"\udc00","body":"x"}"#, "invalid_utf8"),
+    (r#"{"subject":"a.b","body":"\ud83d"}"#, "invalid_utf8"),
+    (r#"{"subject":"a.b","body":"\ud83dx"}"#, "invalid_utf8"),
+    (r#"{"subject":"a.b","body":"\ud800A"}"#, "invalid_utf8"),
+    (r#"{"subject":"a\u0000b","body":"x"}"#, "invalid_subject"),
+    (r#"{"subject":"a\nb","body":"x"}"#, "invalid_subject"),
+    (r#"{"subject":"a.�","body":"x"}"#, "invalid_subject"),
+];
+
+fn expected_counts(rounds: u64) -> HashMap<String, u64> {
+    let mut counts = HashMap::new();
+    for (_, reason) in INVALID_TEXT {
+        *counts.entry(reason.to_string()).or_default() += rounds;
+    }
+    counts
+}
+
+/// Pseudo-random bytes that are never valid UTF-8 (0xFE can't appear in UTF-8)
+fn garbage(seed: u64, len: usize) -> Vec<u8> {
+    let mut state = seed | 1;
+    std::iter::once(0xFE)
+        .chain((1..len).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        }))
+        .collect()
+}
+
+/// Test that invalid text from a server client is counted by reason and never
+/// dispatched, while non-UTF-8 binary frames are still only delivered as raw binary
+#[tokio::test]
+async fn test_server_rejects_invalid_text() -> Result<()> {
+    let provider = start_server(&[("PARSE_FAIL_THRESHOLD", "0")]).await?;
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    provider
+        .set_message_sink(
+            move |_session_id: String, msg: BrokerMessage| -> Result<()> {
+                tx.send(msg)?;
+                Ok(())
+            },
+        )
+        .await?;
+
+    let url = server_url(&provider).await?;
+    let (mut ws, _) = tokio_tungstenite::connect_async(url.as_str()).await?;
+    for (text, _) in INVALID_TEXT {
+        ws.send(Message::Text(text.to_string())).await?;
+        // The same envelopes in binary frames take the same path
+        ws.send(Message::Binary(text.as_bytes().to_vec())).await?;
+    }
+    for seed in 0..100 {
+        ws.send(Message::Binary(garbage(seed, 1 + seed as usize % 64)))
+            .await?;
+    }
+    ws.send(Message::Text(r#"{"subject":"ok","body":"x"}"#.to_string()))
+        .await?;
+
+    let mut subjects = Vec::new();
+    while let Ok(Some(msg)) = timeout(Duration::from_millis(500), rx.recv()).await {
+        subjects.push(msg.subject);
+    }
+    assert_eq!(subjects.len(), 101, "{:?}", subjects);
+    assert!(subjects[..100].iter().all(|s| s == "binary.message"));
+    assert_eq!(subjects[100], "ok");
+    assert_eq!(provider.parse_failure_counts(), expected_counts(2));
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+async fn client_link() -> Result<(WebSocketMessagingProvider, MockRemote)> {
+    let mut config = HashMap::new();
+    config.insert("MODE".to_string(), "client".to_string());
+    config.insert("URI".to_string(), "ws://mock.invalid/ws".to_string());
+    let (transport, remotes) = MockTransport::new();
+    let provider = WebSocketMessagingProvider::from_config(config)?.with_transport(transport);
+    provider
+        .receive_link_config_as_source("test-handler", HashMap::new())
+        .await?;
+    let remote = timeout(Duration::from_secs(1), remotes.accept())
+        .await?
+        .expect("transport should be alive");
+    Ok((provider, remote))
+}
+
+/// Test that invalid text from the remote server is counted by reason and never reaches
+/// the handler, instead of falling back to a plain-text message
+#[tokio::test]
+async fn test_client_rejects_invalid_text() -> Result<()> {
+    let (provider, mut remote) = client_link().await?;
+
+    for (text, _) in INVALID_TEXT {
+        remote.send(Message::Text(text.to_string()))?;
+        remote.send(Message::Binary(text.as_bytes().to_vec()))?;
+    }
+    remote.send(Message::Text(r#"{"subject":"ok","body":"x"}"#.to_string()))?;
+
+    // Handler deliveries come back over the mock connection; only the valid one arrives
+    let frame = timeout(Duration::from_secs(1), remote.recv())
+        .await?
+        .expect("client should still be connected");
+    let Message::Text(text) = frame else {
+        panic!("expected a text frame, got {:?}", frame);
+    };
+    let json: serde_json::Value = serde_json::from_str(&text)?;
+    assert_eq!(json["subject"], "ok");
+    assert!(timeout(Duration::from_millis(100), remote.recv())
+        .await
+        .is_err());
+    assert_eq!(provider.parse_failure_counts(), expected_counts(2));
+
+    provider.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a text frame tungstenite refused as invalid UTF-8 is counted
+#[tokio::test]
+async fn test_client_counts_protocol_utf8_error() -> Result<()> {
+    let (provider, remote) = client_link().await?;
+    remote.fail(WsError::Utf8)?;
+
+    for _ in 0..50 {
+        if provider.parse_failure_counts().get("invalid_utf8") == Some(&1) {
+            break;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(provider.parse_failure_counts()["invalid_utf8"], 1);
+
+    provider.shutdown().await?;
+    Ok(())
+}
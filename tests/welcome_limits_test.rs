@@ -0,0 +1,161 @@
+use anyhow::Result;
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::Message;
+
+use wasmcloud_provider_messaging_websocket::testing::{connect, start_server, WsClient};
+use wasmcloud_provider_messaging_websocket::{
+    BrokerMessage, MessageTooLarge, WebSocketMessagingProvider,
+};
+
+const MAX_MESSAGE_BYTES: usize = 1024;
+
+async fn limited_server(welcome: bool) -> Result<WebSocketMessagingProvider> {
+    start_server(&[
+        ("MAX_MESSAGE_BYTES", &MAX_MESSAGE_BYTES.to_string()),
+        ("WELCOME_FRAME", &welcome.to_string()),
+    ])
+    .await
+}
+
+async fn next_json(ws: &mut WsClient) -> Result<serde_json::Value> {
+    loop {
+        match timeout(Duration::from_secs(2), ws.next()).await? {
+            Some(Ok(Message::Text(text))) => return Ok(serde_json::from_str(&text)?),
+            Some(Ok(_)) => continue,
+            other => anyhow::bail!("expected a text frame, got {:?}", other),
+        }
+    }
+}
+
+fn message(body: Vec<u8>) -> BrokerMessage {
+    BrokerMessage {
+        subject: "test.limits".to_string(),
+        body: Bytes::from(body),
+        reply_to: None,
+        headers: HashMap::new(),
+    }
+}
+
+/// Test that the welcome frame carries the session id and the server's limits
+#[tokio::test]
+async fn test_welcome_frame_advertises_limits() -> Result<()> {
+    let server = limited_server(true).await?;
+    let (mut ws, _) = connect(&server).await?;
+
+    let welcome = next_json(&mut ws).await?;
+    let session_id = server.list_ws_clients().await?.remove(0);
+    assert_eq!(welcome["session_id"], session_id.as_str());
+    assert_eq!(welcome["limits"]["max_message_bytes"], MAX_MESSAGE_BYTES);
+    assert!(
+        welcome["limits"]["max_decompressed_bytes"]
+            .as_u64()
+            .unwrap()
+            > 0
+    );
+    assert!(welcome.get("subject").is_none());
+
+    server.shutdown().await?;
+    Ok(())
+}
+
+/// Test that every configured limit a client is held to appears in the welcome frame
+#[tokio::test]
+async fn test_welcome_frame_advertises_every_limit() -> Result<()> {
+    let limits = [
+        ("MAX_MESSAGE_BYTES", "4096"),
+        ("MAX_DECOMPRESSED_BYTES", "8192"),
+        ("MAX_SUBJECT_LEN", "128"),
+        ("MAX_REPLY_TO_LEN", "64"),
+        ("SERVER_MAX_MESSAGES_PER_SEC", "50"),
+        ("SERVER_RATE_POLICY", "queue"),
+        ("MAX_SESSION_BUFFER_BYTES", "65536"),
+        ("SESSION_BUFFER_POLICY", "disconnect"),
+        ("MAX_GROUPS_PER_SESSION", "3"),
+        ("MAX_GROUP_SIZE", "7"),
+        ("MAX_GROUPS", "11"),
+        ("PARSE_FAIL_THRESHOLD", "4"),
+        ("PARSE_FAIL_DISCONNECT_THRESHOLD", "9"),
+    ];
+    let server = start_server(&[limits.as_slice(), &[("WELCOME_FRAME", "true")]].concat()).await?;
+    let (mut ws, _) = connect(&server).await?;
+
+    let welcome = next_json(&mut ws).await?;
+    let advertised = welcome["limits"].as_object().unwrap();
+    assert_eq!(advertised.len(), limits.len());
+    for (key, value) in limits {
+        let advertised = &advertised[&key.to_lowercase()];
+        let advertised = match advertised.as_str() {
+            Some(policy) => policy.to_string(),
+            None => advertised.to_string(),
+        };
+        assert_eq!(advertised, value, "{}", key);
+    }
+
+    server.shutdown().await?;
+    Ok(())
+}
+
+/// Test that without `WELCOME_FRAME` the first frame is the first message
+#[tokio::test]
+async fn test_no_welcome_frame_by_default() -> Result<()> {
+    let server = limited_server(false).await?;
+    let (mut ws, _) = connect(&server).await?;
+
+    server.broadcast_to_clients(message(b"hi".to_vec())).await?;
+    let first = next_json(&mut ws).await?;
+    assert_eq!(first["subject"], "test.limits");
+
+    server.shutdown().await?;
+    Ok(())
+}
+
+/// Test that the advertised limit holds both ways: oversized outbound messages fail with
+/// `MessageTooLarge` and an oversized inbound frame closes the connection
+#[tokio::test]
+async fn test_limits_enforced_both_ways() -> Result<()> {
+    let server = limited_server(true).await?;
+    let (mut ws, _) = connect(&server).await?;
+    next_json(&mut ws).await?;
+    let session_id = server.list_ws_clients().await?.remove(0);
+
+    let oversized = vec![b'x'; MAX_MESSAGE_BYTES + 1];
+    let err = server
+        .send_to_ws_client(&session_id, message(oversized.clone()))
+        .await
+        .unwrap_err();
+    assert!(err.downcast_ref::<MessageTooLarge>().is_some());
+    let err = server
+        .broadcast_to_clients(message(oversized.clone()))
+        .await
+        .unwrap_err();
+    assert!(err.downcast_ref::<MessageTooLarge>().is_some());
+
+    // The client is still connected and gets messages within the limit
+    server
+        .send_to_ws_client(&session_id, message(b"small".to_vec()))
+        .await?;
+    assert_eq!(next_json(&mut ws).await?["subject"], "test.limits");
+
+    ws.send(Message::Text("x".repeat(MAX_MESSAGE_BYTES + 1)))
+        .await?;
+    let closed = timeout(Duration::from_secs(2), async {
+        loop {
+            match ws.next().await {
+                None | Some(Err(_)) | Some(Ok(Message::Close(_))) => break,
+                Some(Ok(_)) => continue,
+            }
+        }
+    })
+    .await;
+    assert!(
+        closed.is_ok(),
+        "oversized inbound frame did not close the connection"
+    );
+
+    server.shutdown().await?;
+    Ok(())
+}
@@ -4,13 +4,53 @@ wit_bindgen::generate!({
 });
 
 use exports::wasmcloud::messaging::handler::{Guest, BrokerMessage as HandlerMessage};
-use wasmcloud::messaging::consumer::{publish, BrokerMessage as ConsumerMessage};
+use wasmcloud::messaging::consumer::{publish, request, BrokerMessage as ConsumerMessage};
 
 // Response subject prefix
 const RESPONSE_PREFIX: &str = "response.";
 
+// Subjects the handler link subscribes to with `SUBSCRIPTIONS=example.>`
+const SUBSCRIPTION_PREFIX: &str = "example.";
+
+// Subject the remote server sends to make the component run its request-reply check
+const TRIGGER_SUBJECT: &str = "example.start";
+
+// Subject the component sends its request on; the remote server is expected to answer
+// with the same body
+const ECHO_SUBJECT: &str = "example.echo";
+
+// How long the component waits for the reply
+const REQUEST_TIMEOUT_MS: u32 = 2000;
+
 struct Component;
 
+impl Component {
+    /// Send a request and check that the reply echoes its body
+    ///
+    /// Errors are returned from `handle_message`, so a broken request-reply path shows
+    /// up as a failed delivery instead of being ignored.
+    fn check_request_reply(trigger: &HandlerMessage) -> Result<(), String> {
+        let reply = request(ECHO_SUBJECT, &trigger.body, REQUEST_TIMEOUT_MS)
+            .map_err(|e| format!("Request on {} failed: {}", ECHO_SUBJECT, e))?;
+        if reply.body != trigger.body {
+            return Err(format!(
+                "Reply on {} did not echo the request body ({} bytes sent, {} received)",
+                reply.subject,
+                trigger.body.len(),
+                reply.body.len()
+            ));
+        }
+
+        // Report the outcome to the remote server
+        publish(&ConsumerMessage {
+            subject: format!("{}{}", RESPONSE_PREFIX, TRIGGER_SUBJECT),
+            body: b"ok".to_vec(),
+            reply_to: trigger.reply_to.clone(),
+        })
+        .map_err(|e| format!("Failed to report request-reply result: {}", e))
+    }
+}
+
 // Implement the handler interface to receive messages from the remote WebSocket server
 impl Guest for Component {
     fn handle_message(msg: HandlerMessage) -> Result<(), String> {
@@ -19,7 +59,16 @@ impl Guest for Component {
         // 1. Process the message body
         // 2. Perform business logic
         // 3. Optionally send a response back using the consumer interface
-        
+        if !msg.subject.starts_with(SUBSCRIPTION_PREFIX) {
+            return Err(format!(
+                "Received {} although the handler link only subscribes to {}>",
+                msg.subject, SUBSCRIPTION_PREFIX
+            ));
+        }
+        if msg.subject == TRIGGER_SUBJECT {
+            return Self::check_request_reply(&msg);
+        }
+
         // For this example, we'll echo the message back if it has a reply_to field
         if let Some(reply_to) = msg.reply_to {
             // Send a response back to the remote server
@@ -28,11 +77,11 @@ impl Guest for Component {
                 body: msg.body.clone(),
                 reply_to: Some(reply_to),
             };
-            
+
             // Publish response back through the WebSocket provider
             publish(&response).map_err(|e| format!("Failed to send response: {}", e))?;
         }
-        
+
         Ok(())
     }
 }